@@ -0,0 +1,121 @@
+//! Time-boxed benchmarks of the signature operations, for tracking regressions as the zexe
+//! dependencies move. They require the `bench` feature. Save a baseline and compare against it
+//! with:
+//!
+//! `cargo bench --features bench --bench bls_ops -- --save-baseline main`
+//! `cargo bench --features bench --bench bls_ops -- --baseline main`
+//!
+//! Criterion writes the estimates of each baseline as JSON, under
+//! `target/criterion/<group>/<benchmark>/<baseline>/estimates.json`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::time::Duration;
+
+use bls_crypto::{
+    hash_to_curve::try_and_increment::{COMPOSITE_HASH_TO_G1, DIRECT_HASH_TO_G1},
+    HashToCurve, PrivateKey, PublicKey, Signature, SIG_DOMAIN,
+};
+
+const MESSAGE: &[u8] = b"epoch block";
+const EXTRA_DATA: &[u8] = b"extra data";
+
+fn sign(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sign");
+    let rng = &mut rand::thread_rng();
+    let key = PrivateKey::generate(rng);
+    group.bench_function("direct", |b| {
+        b.iter(|| key.sign(MESSAGE, EXTRA_DATA, &*DIRECT_HASH_TO_G1).unwrap())
+    });
+    group.bench_function("composite", |b| {
+        b.iter(|| {
+            key.sign(MESSAGE, EXTRA_DATA, &*COMPOSITE_HASH_TO_G1)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+    let rng = &mut rand::thread_rng();
+    let key = PrivateKey::generate(rng);
+    let public_key = key.to_public();
+    let direct = key.sign(MESSAGE, EXTRA_DATA, &*DIRECT_HASH_TO_G1).unwrap();
+    let composite = key
+        .sign(MESSAGE, EXTRA_DATA, &*COMPOSITE_HASH_TO_G1)
+        .unwrap();
+    group.bench_function("direct", |b| {
+        b.iter(|| {
+            public_key
+                .verify(MESSAGE, EXTRA_DATA, &direct, &*DIRECT_HASH_TO_G1)
+                .unwrap()
+        })
+    });
+    group.bench_function("composite", |b| {
+        b.iter(|| {
+            public_key
+                .verify(MESSAGE, EXTRA_DATA, &composite, &*COMPOSITE_HASH_TO_G1)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn aggregate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aggregate");
+    let rng = &mut rand::thread_rng();
+    for num_validators in &[10, 100] {
+        let keys = (0..*num_validators)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|k| k.to_public()).collect::<Vec<_>>();
+        let signatures = keys
+            .iter()
+            .map(|k| k.sign(MESSAGE, EXTRA_DATA, &*DIRECT_HASH_TO_G1).unwrap())
+            .collect::<Vec<_>>();
+        group.bench_with_input(
+            BenchmarkId::new("signatures", num_validators),
+            &signatures,
+            |b, signatures| b.iter(|| Signature::aggregate(signatures)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("public keys", num_validators),
+            &public_keys,
+            |b, public_keys| b.iter(|| PublicKey::aggregate(public_keys)),
+        );
+    }
+    group.finish();
+}
+
+fn hash_to_curve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hash to G1");
+    group.bench_function("direct", |b| {
+        b.iter(|| {
+            DIRECT_HASH_TO_G1
+                .hash(SIG_DOMAIN, MESSAGE, EXTRA_DATA)
+                .unwrap()
+        })
+    });
+    group.bench_function("composite", |b| {
+        b.iter(|| {
+            COMPOSITE_HASH_TO_G1
+                .hash(SIG_DOMAIN, MESSAGE, EXTRA_DATA)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+/// Bounds each benchmark to a few seconds so that the suite can run on every change
+fn time_boxed() -> Criterion {
+    Criterion::default()
+        .sample_size(20)
+        .warm_up_time(Duration::from_secs(1))
+        .measurement_time(Duration::from_secs(3))
+}
+
+criterion_group! {
+    name = benches;
+    config = time_boxed();
+    targets = sign, verify, aggregate, hash_to_curve
+}
+criterion_main!(benches);
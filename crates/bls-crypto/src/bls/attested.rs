@@ -0,0 +1,240 @@
+use super::{Bitmap, PublicKey, Signature};
+use crate::{BLSError, BlsResult, HashToCurve, ValidationPolicy};
+
+use algebra::{
+    bls12_377::G1Projective, CanonicalDeserialize, CanonicalSerialize, ProjectiveCurve,
+    SerializationError, Zero,
+};
+use log::error;
+use std::io::{Read, Write};
+
+/// An aggregate signature along with the context needed to verify it: the epoch whose
+/// validator set signed, the bitmap of the signers in that set and their number.
+///
+/// It is serialized canonically as `epoch (LE u16) || signer_count (LE u32) || bitmap ||
+/// signature`, with the bitmap in its canonical encoding and the signature compressed.
+/// Deserializing rejects any other encoding, as well as aggregates which fail `validate`, so
+/// that network layers can drop malformed aggregates before looking up the epoch's validator
+/// set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttestedAggregate {
+    epoch: u16,
+    bitmap: Bitmap,
+    signer_count: u32,
+    signature: Signature,
+}
+
+impl AttestedAggregate {
+    /// Wraps the aggregate signature of the validators of `epoch` whose bits are set in
+    /// `bitmap`
+    pub fn new(epoch: u16, bitmap: Bitmap, signature: Signature) -> Self {
+        let signer_count = bitmap.popcount() as u32;
+        Self {
+            epoch,
+            bitmap,
+            signer_count,
+            signature,
+        }
+    }
+
+    /// The epoch whose validator set signed
+    pub fn epoch(&self) -> u16 {
+        self.epoch
+    }
+
+    /// The bitmap of the signers in the epoch's validator set
+    pub fn bitmap(&self) -> &Bitmap {
+        &self.bitmap
+    }
+
+    /// The number of signers, i.e. of bits set in the bitmap
+    pub fn signer_count(&self) -> u32 {
+        self.signer_count
+    }
+
+    /// The aggregate signature of the signers
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Checks that the aggregate is well formed: it has at least one signer, its signer count
+    /// matches its bitmap, and its signature is a point of the prime order subgroup other than
+    /// the identity. This does not verify the signature.
+    pub fn validate(&self) -> BlsResult<()> {
+        let count = self.bitmap.popcount();
+        if count != self.signer_count as usize {
+            return Err(BLSError::InvalidAttestedAggregate(
+                "the signer count does not match the bitmap",
+            ));
+        }
+        if count == 0 {
+            return Err(BLSError::InvalidAttestedAggregate("there are no signers"));
+        }
+        let signature = self.signature.as_ref().into_affine();
+        if signature.is_zero() {
+            return Err(BLSError::InvalidAttestedAggregate(
+                "the signature is the identity",
+            ));
+        }
+        // aggregates were always checked to be in the subgroup, regardless of the default policy
+        ValidationPolicy::STRICT.validate(&signature)
+    }
+
+    /// Aggregates the public keys of the signers, given the validator set of the epoch
+    pub fn aggregate_public_key(&self, public_keys: &[PublicKey]) -> BlsResult<PublicKey> {
+        if public_keys.len() != self.bitmap.len() {
+            return Err(BLSError::InvalidAttestedAggregate(
+                "the bitmap does not match the validator set",
+            ));
+        }
+        Ok(PublicKey::aggregate(
+            public_keys
+                .iter()
+                .zip(&self.bitmap)
+                .filter(|(_, signed)| **signed)
+                .map(|(public_key, _)| public_key),
+        ))
+    }
+
+    /// Verifies the aggregate signature over the message-extra_data pair, given the validator
+    /// set of the epoch
+    pub fn verify<H: HashToCurve<Output = G1Projective>>(
+        &self,
+        public_keys: &[PublicKey],
+        message: &[u8],
+        extra_data: &[u8],
+        hash_to_g1: &H,
+    ) -> BlsResult<()> {
+        self.validate()?;
+        self.aggregate_public_key(public_keys)?.verify(
+            message,
+            extra_data,
+            &self.signature,
+            hash_to_g1,
+        )
+    }
+
+    /// Serializes the aggregate canonically
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.serialized_size());
+        self.serialize(&mut bytes)
+            .expect("serializing to a vector cannot fail");
+        bytes
+    }
+
+    /// Deserializes a canonically serialized aggregate, rejecting trailing bytes
+    pub fn from_bytes(mut bytes: &[u8]) -> BlsResult<Self> {
+        let aggregate = Self::deserialize(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(BLSError::InvalidAttestedAggregate("trailing bytes"));
+        }
+        Ok(aggregate)
+    }
+}
+
+impl CanonicalSerialize for AttestedAggregate {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        writer.write_all(&self.epoch.to_le_bytes())?;
+        writer.write_all(&self.signer_count.to_le_bytes())?;
+        self.bitmap.serialize(&mut writer)?;
+        self.signature.serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        2 + 4 + self.bitmap.serialized_size() + self.signature.serialized_size()
+    }
+}
+
+impl CanonicalDeserialize for AttestedAggregate {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let mut epoch = [0u8; 2];
+        reader.read_exact(&mut epoch)?;
+        let mut signer_count = [0u8; 4];
+        reader.read_exact(&mut signer_count)?;
+        let aggregate = Self {
+            epoch: u16::from_le_bytes(epoch),
+            bitmap: Bitmap::deserialize(&mut reader)?,
+            signer_count: u32::from_le_bytes(signer_count),
+            signature: Signature::deserialize(reader)?,
+        };
+        aggregate.validate().map_err(|e| {
+            error!("invalid attested aggregate: {}", e);
+            SerializationError::InvalidData
+        })?;
+        Ok(aggregate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_to_curve::try_and_increment::DIRECT_HASH_TO_G1, PrivateKey};
+
+    #[test]
+    fn roundtrips_and_verifies() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..10)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|k| k.to_public()).collect::<Vec<_>>();
+        let hasher = &*DIRECT_HASH_TO_G1;
+        let bitmap = (0..10).map(|i| i % 3 != 0).collect::<Bitmap>();
+        let signature = Signature::aggregate(
+            keys.iter()
+                .zip(&bitmap)
+                .filter(|(_, signed)| **signed)
+                .map(|(key, _)| key.sign(b"epoch", &[], hasher).unwrap()),
+        );
+
+        let aggregate = AttestedAggregate::new(7, bitmap, signature);
+        assert_eq!(aggregate.signer_count(), 6);
+        let bytes = aggregate.to_bytes();
+        assert_eq!(bytes.len(), aggregate.serialized_size());
+        let decoded = AttestedAggregate::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, aggregate);
+        decoded.verify(&public_keys, b"epoch", &[], hasher).unwrap();
+
+        assert!(decoded.verify(&public_keys, b"other", &[], hasher).is_err());
+        assert!(matches!(
+            decoded.verify(&public_keys[1..], b"epoch", &[], hasher),
+            Err(BLSError::InvalidAttestedAggregate(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_encodings() {
+        let rng = &mut rand::thread_rng();
+        let signature = PrivateKey::generate(rng)
+            .sign(b"epoch", &[], &*DIRECT_HASH_TO_G1)
+            .unwrap();
+        let bytes = AttestedAggregate::new(1, vec![true, false, true].into(), signature).to_bytes();
+        assert!(AttestedAggregate::from_bytes(&bytes).is_ok());
+
+        // the signer count does not match the bitmap
+        let mut invalid = bytes.clone();
+        invalid[2] = 1;
+        assert!(AttestedAggregate::from_bytes(&invalid).is_err());
+        // a padding bit is set
+        let mut invalid = bytes.clone();
+        invalid[10] |= 0b1000;
+        assert!(AttestedAggregate::from_bytes(&invalid).is_err());
+        // the bitmap is truncated
+        let mut invalid = bytes.clone();
+        invalid[6] = 200;
+        assert!(AttestedAggregate::from_bytes(&invalid).is_err());
+        // trailing bytes
+        let mut invalid = bytes.clone();
+        invalid.push(0);
+        assert!(matches!(
+            AttestedAggregate::from_bytes(&invalid),
+            Err(BLSError::InvalidAttestedAggregate("trailing bytes"))
+        ));
+        assert!(AttestedAggregate::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // no signers
+        let empty =
+            AttestedAggregate::new(1, Bitmap::new(3), Signature::from(G1Projective::zero()));
+        assert!(empty.validate().is_err());
+        assert!(AttestedAggregate::from_bytes(&empty.to_bytes()).is_err());
+    }
+}
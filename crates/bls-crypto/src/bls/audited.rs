@@ -0,0 +1,208 @@
+//! Auditable key generation
+//!
+//! `PrivateKey::generate_audited` draws a random scalar `r`, outputs the commitment `R = r * G2`
+//! and derives the private key `r + c`, where `c` is a challenge derived from `R` with a
+//! `Transcript` personalized to `KEYGEN_DOMAIN`. The public key is thus `R + c * G2`, which
+//! anyone can recompute from the commitment. Since `c` depends on `R`, a validator cannot steer
+//! its public key towards a value of its choosing (e.g. to mount a rogue key attack against
+//! aggregation) short of breaking the hash, and once the commitments of a ceremony were
+//! collected before the keys, each key is bound to the entropy committed earlier.
+//!
+//! `PrivateKey::prove_derivation` then produces a Schnorr proof of knowledge of `r`, which
+//! shows that the key holder knows the committed entropy without revealing it, and
+//! `KeyDerivationProof::verify` checks both the derivation and the proof.
+use super::{PrivateKey, PublicKey, SecretScalarMul};
+use crate::{BLSError, BlsResult, Transcript, ValidationPolicy, KEYGEN_DOMAIN};
+
+use algebra::{
+    bls12_377::{Fr, G2Affine, G2Projective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, ProjectiveCurve, SerializationError,
+    UniformRand, Zero,
+};
+use rand::Rng;
+use std::io::{Read, Write};
+
+/// A commitment to the randomness from which `PrivateKey::generate_audited` derived a key
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct KeyCommitment(G2Affine);
+
+impl AsRef<G2Affine> for KeyCommitment {
+    fn as_ref(&self) -> &G2Affine {
+        &self.0
+    }
+}
+
+/// A proof that a public key was derived from a `KeyCommitment` by its holder
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct KeyDerivationProof {
+    nonce_commitment: G2Affine,
+    response: Fr,
+}
+
+impl PrivateKey {
+    /// Generates a new private key from the provided RNG, and writes the commitment to the
+    /// randomness it was derived from to `commitment_out`. The commitment is meant to be
+    /// published before the key, so that `prove_derivation` can later show that the key was
+    /// derived from it rather than chosen.
+    pub fn generate_audited<R: Rng, W: Write>(
+        rng: &mut R,
+        commitment_out: W,
+    ) -> BlsResult<PrivateKey> {
+        let randomness = Fr::rand(rng);
+        let commitment = KeyCommitment(
+            G2Projective::prime_subgroup_generator()
+                .mul_secret(&randomness)
+                .into_affine(),
+        );
+        let (_, offset) = derivation_transcript(&commitment)?;
+        commitment.serialize(commitment_out)?;
+        Ok(PrivateKey::from(randomness + offset))
+    }
+
+    /// Proves that the key was derived from the commitment output by `generate_audited`
+    pub fn prove_derivation<R: Rng>(
+        &self,
+        commitment: &KeyCommitment,
+        rng: &mut R,
+    ) -> BlsResult<KeyDerivationProof> {
+        let (mut transcript, offset) = derivation_transcript(commitment)?;
+        let generator = G2Projective::prime_subgroup_generator();
+        let randomness = *self.as_ref() - offset;
+        if generator.mul_secret(&randomness).into_affine() != commitment.0 {
+            return Err(BLSError::InvalidKeyDerivation(
+                "the key was not derived from the commitment",
+            ));
+        }
+
+        let nonce = Fr::rand(rng);
+        let nonce_commitment = generator.mul_secret(&nonce).into_affine();
+        let challenge =
+            derivation_challenge(&mut transcript, &self.to_public(), &nonce_commitment)?;
+        Ok(KeyDerivationProof {
+            nonce_commitment,
+            response: nonce + challenge * randomness,
+        })
+    }
+}
+
+impl KeyDerivationProof {
+    /// Checks that the public key was derived from the commitment, and that the proof shows
+    /// knowledge of the committed randomness
+    pub fn verify(&self, public_key: &PublicKey, commitment: &KeyCommitment) -> BlsResult<()> {
+        for point in &[commitment.0, self.nonce_commitment] {
+            ValidationPolicy::CANONICAL.validate(point)?;
+        }
+        let (mut transcript, offset) = derivation_transcript(commitment)?;
+        let generator = G2Projective::prime_subgroup_generator();
+        if *public_key.as_ref() != commitment.0.into_projective() + generator.mul(offset) {
+            return Err(BLSError::InvalidKeyDerivation(
+                "the public key was not derived from the commitment",
+            ));
+        }
+
+        let challenge = derivation_challenge(&mut transcript, public_key, &self.nonce_commitment)?;
+        if generator.mul(self.response)
+            != self.nonce_commitment.into_projective() + commitment.0.mul(challenge)
+        {
+            return Err(BLSError::InvalidKeyDerivation(
+                "the proof of knowledge of the randomness is invalid",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Starts the derivation's transcript with the commitment, and returns it along with the
+/// offset which is added to the committed randomness to obtain the private key
+fn derivation_transcript(commitment: &KeyCommitment) -> BlsResult<(Transcript, Fr)> {
+    if commitment.0.is_zero() {
+        return Err(BLSError::InvalidKeyDerivation(
+            "the commitment is the identity",
+        ));
+    }
+    let mut transcript = Transcript::new(KEYGEN_DOMAIN)?;
+    transcript.absorb_point(&commitment.0)?;
+    let offset = transcript.squeeze_field()?;
+    Ok((transcript, offset))
+}
+
+/// Derives the challenge of the proof of knowledge from the public key and the nonce's
+/// commitment
+fn derivation_challenge(
+    transcript: &mut Transcript,
+    public_key: &PublicKey,
+    nonce_commitment: &G2Affine,
+) -> BlsResult<Fr> {
+    let public_key = public_key.as_ref().into_affine();
+    if public_key.is_zero() || nonce_commitment.is_zero() {
+        return Err(BLSError::InvalidKeyDerivation("unexpected identity point"));
+    }
+    transcript.absorb_point(&public_key)?;
+    transcript.absorb_point(nonce_commitment)?;
+    transcript.squeeze_field()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn proves_and_verifies_derivation() {
+        let rng = &mut thread_rng();
+        let mut commitment_bytes = vec![];
+        let key = PrivateKey::generate_audited(rng, &mut commitment_bytes).unwrap();
+        let commitment = KeyCommitment::deserialize(&commitment_bytes[..]).unwrap();
+        let public_key = key.to_public();
+
+        let proof = key.prove_derivation(&commitment, rng).unwrap();
+        proof.verify(&public_key, &commitment).unwrap();
+        let mut proof_bytes = vec![];
+        proof.serialize(&mut proof_bytes).unwrap();
+        let decoded = KeyDerivationProof::deserialize(&proof_bytes[..]).unwrap();
+        decoded.verify(&public_key, &commitment).unwrap();
+
+        // another key cannot claim the commitment
+        let other = PrivateKey::generate(rng);
+        assert!(matches!(
+            other.prove_derivation(&commitment, rng),
+            Err(BLSError::InvalidKeyDerivation(_))
+        ));
+        assert!(proof.verify(&other.to_public(), &commitment).is_err());
+
+        // nor can the key claim another commitment
+        let mut other_bytes = vec![];
+        PrivateKey::generate_audited(rng, &mut other_bytes).unwrap();
+        let other_commitment = KeyCommitment::deserialize(&other_bytes[..]).unwrap();
+        assert!(key.prove_derivation(&other_commitment, rng).is_err());
+        assert!(proof.verify(&public_key, &other_commitment).is_err());
+    }
+
+    #[test]
+    fn rejects_forged_proofs() {
+        let rng = &mut thread_rng();
+        let mut commitment_bytes = vec![];
+        let key = PrivateKey::generate_audited(rng, &mut commitment_bytes).unwrap();
+        let commitment = KeyCommitment::deserialize(&commitment_bytes[..]).unwrap();
+        let public_key = key.to_public();
+        let proof = key.prove_derivation(&commitment, rng).unwrap();
+
+        let mut forged = proof.clone();
+        forged.response += Fr::from(1u64);
+        assert!(matches!(
+            forged.verify(&public_key, &commitment),
+            Err(BLSError::InvalidKeyDerivation(_))
+        ));
+        let mut forged = proof;
+        forged.nonce_commitment = G2Affine::zero();
+        assert!(forged.verify(&public_key, &commitment).is_err());
+
+        let identity = KeyCommitment(G2Affine::zero());
+        assert!(matches!(
+            key.prove_derivation(&identity, rng),
+            Err(BLSError::InvalidKeyDerivation(
+                "the commitment is the identity"
+            ))
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{BLSError, BlsResult};
+
+use algebra::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use log::error;
+use std::{
+    io::{Read, Write},
+    iter::FromIterator,
+    ops::Deref,
+};
+
+/// A bitmap over the validators of a set, e.g. of the signers of an aggregate signature.
+///
+/// Its canonical encoding is `len (LE u32) || bits`, with the bits packed in little-endian bit
+/// order and the padding bits of the last byte set to zero. This is the only encoding which
+/// deserializing accepts, so that every bitmap exchanged with other clients has exactly one
+/// representation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitmap {
+    bits: Vec<bool>,
+}
+
+impl Bitmap {
+    /// A bitmap of `len` unset bits
+    pub fn new(len: usize) -> Self {
+        Self {
+            bits: vec![false; len],
+        }
+    }
+
+    /// A bitmap of `len` set bits
+    pub fn full(len: usize) -> Self {
+        Self {
+            bits: vec![true; len],
+        }
+    }
+
+    /// Sets the bit at `index`
+    ///
+    /// # Panics
+    /// If `index` is out of bounds
+    pub fn set(&mut self, index: usize, value: bool) {
+        self.bits[index] = value;
+    }
+
+    /// The number of set bits
+    pub fn popcount(&self) -> usize {
+        self.bits.iter().filter(|bit| **bit).count()
+    }
+
+    /// The bitmap with every bit flipped, e.g. the non-signers of a bitmap of signers
+    pub fn complement(&self) -> Self {
+        self.bits.iter().map(|bit| !bit).collect()
+    }
+
+    /// Whether the bitmap has the same length as `other`, and every bit set in the bitmap
+    /// is also set in `other`
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .bits
+                .iter()
+                .zip(&other.bits)
+                .all(|(bit, other)| !bit || *other)
+    }
+
+    /// The bits packed in little-endian bit order, with the padding bits set to zero, i.e.
+    /// the canonical encoding without its length
+    pub fn to_packed_bytes(&self) -> Vec<u8> {
+        let mut packed = vec![0u8; (self.len() + 7) / 8];
+        for (i, bit) in self.bits.iter().enumerate() {
+            packed[i / 8] |= (*bit as u8) << (i % 8);
+        }
+        packed
+    }
+
+    /// Unpacks `len` bits packed as by `to_packed_bytes`, rejecting any other number of bytes
+    /// and set padding bits
+    pub fn from_packed_bytes(len: usize, packed: &[u8]) -> BlsResult<Self> {
+        if packed.len() != (len + 7) / 8 {
+            return Err(BLSError::InvalidBitmap(
+                "the number of bytes does not match the length",
+            ));
+        }
+        // the padding bits must be zero, so that the encoding is unique
+        if len % 8 != 0 && packed[packed.len() - 1] >> (len % 8) != 0 {
+            return Err(BLSError::InvalidBitmap("the padding bits are not zero"));
+        }
+        Ok((0..len)
+            .map(|i| packed[i / 8] & (1 << (i % 8)) != 0)
+            .collect())
+    }
+
+    /// Serializes the bitmap canonically
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.serialized_size());
+        self.serialize(&mut bytes)
+            .expect("serializing to a vector cannot fail");
+        bytes
+    }
+
+    /// Deserializes a canonically serialized bitmap, rejecting trailing bytes
+    pub fn from_bytes(mut bytes: &[u8]) -> BlsResult<Self> {
+        let bitmap = Self::deserialize(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(BLSError::InvalidBitmap("trailing bytes"));
+        }
+        Ok(bitmap)
+    }
+}
+
+impl Deref for Bitmap {
+    type Target = [bool];
+
+    fn deref(&self) -> &[bool] {
+        &self.bits
+    }
+}
+
+impl From<Vec<bool>> for Bitmap {
+    fn from(bits: Vec<bool>) -> Self {
+        Self { bits }
+    }
+}
+
+impl From<Bitmap> for Vec<bool> {
+    fn from(bitmap: Bitmap) -> Self {
+        bitmap.bits
+    }
+}
+
+impl FromIterator<bool> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        Self {
+            bits: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Bitmap {
+    type Item = &'a bool;
+    type IntoIter = std::slice::Iter<'a, bool>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.bits.iter()
+    }
+}
+
+impl CanonicalSerialize for Bitmap {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        writer.write_all(&(self.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.to_packed_bytes())?;
+        Ok(())
+    }
+
+    fn serialized_size(&self) -> usize {
+        4 + (self.len() + 7) / 8
+    }
+}
+
+impl CanonicalDeserialize for Bitmap {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+
+        // reading through `take` only allocates as much as the reader actually holds
+        let num_bytes = (len + 7) / 8;
+        let mut packed = Vec::new();
+        reader.take(num_bytes as u64).read_to_end(&mut packed)?;
+        if packed.len() != num_bytes {
+            return Err(SerializationError::NotEnoughSpace);
+        }
+        Self::from_packed_bytes(len, &packed).map_err(|e| {
+            error!("invalid bitmap: {}", e);
+            SerializationError::InvalidData
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_compares() {
+        let bitmap = Bitmap::from(vec![true, false, true, true]);
+        assert_eq!(bitmap.popcount(), 3);
+        assert_eq!(
+            bitmap.complement(),
+            Bitmap::from(vec![false, true, false, false])
+        );
+        assert_eq!(bitmap.complement().popcount(), 1);
+
+        assert!(bitmap.is_subset_of(&Bitmap::full(4)));
+        assert!(bitmap.is_subset_of(&bitmap));
+        assert!(Bitmap::new(4).is_subset_of(&bitmap));
+        assert!(!bitmap.is_subset_of(&Bitmap::from(vec![true, true, false, true])));
+        // bitmaps over different sets are never subsets of each other
+        assert!(!bitmap.is_subset_of(&Bitmap::full(5)));
+
+        let mut other = Bitmap::new(4);
+        other.set(2, true);
+        assert!(other.is_subset_of(&bitmap));
+        assert!(!other.complement().is_subset_of(&bitmap));
+    }
+
+    #[test]
+    fn encodes_canonically() {
+        let bitmap = (0..11).map(|i| i % 3 == 0).collect::<Bitmap>();
+        assert_eq!(bitmap.to_packed_bytes(), vec![0b0100_1001, 0b0000_0010]);
+        let bytes = bitmap.to_bytes();
+        assert_eq!(bytes, vec![11, 0, 0, 0, 0b0100_1001, 0b0000_0010]);
+        assert_eq!(bytes.len(), bitmap.serialized_size());
+        assert_eq!(Bitmap::from_bytes(&bytes).unwrap(), bitmap);
+        assert_eq!(
+            Bitmap::from_bytes(&[0, 0, 0, 0]).unwrap(),
+            Bitmap::default()
+        );
+
+        // a padding bit is set
+        let mut invalid = bytes.clone();
+        invalid[5] |= 0b1000;
+        assert!(Bitmap::from_bytes(&invalid).is_err());
+        assert!(matches!(
+            Bitmap::from_packed_bytes(11, &invalid[4..]),
+            Err(BLSError::InvalidBitmap(_))
+        ));
+        // the bitmap is truncated
+        assert!(Bitmap::from_bytes(&bytes[..5]).is_err());
+        assert!(Bitmap::from_packed_bytes(11, &bytes[4..5]).is_err());
+        assert!(Bitmap::from_packed_bytes(8, &bytes[4..]).is_err());
+        // trailing bytes
+        let mut invalid = bytes;
+        invalid.push(0);
+        assert!(matches!(
+            Bitmap::from_bytes(&invalid),
+            Err(BLSError::InvalidBitmap("trailing bytes"))
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+//! Scalar multiplication by secret scalars (private keys)
+//!
+//! With the `constant-time` feature, the multiplications performed while signing and deriving
+//! public keys go through a Montgomery ladder whose sequence of group operations and memory
+//! accesses does not depend on the bits of the private key:
+//!
+//! - the scalar is padded with a multiple of the group order to a fixed bit length, so
+//!   that leading zeros are not leaked by the number of iterations
+//! - every iteration performs exactly one addition and one doubling, and the operands are
+//!   selected with `subtle`'s constant time conditional swaps
+//!
+//! The ladder is only as constant time as zexe's field and group arithmetic, which has not
+//! been audited for it. The `dudect`-style tests at the bottom of this file provide evidence
+//! for the full signing path on the machine they are run on.
+//!
+//! Without the feature, zexe's (variable time) double-and-add is used.
+#[cfg(feature = "constant-time")]
+use algebra::{
+    bls12_377::{Fq, Fq2, FrParameters},
+    BigInteger, FpParameters, PrimeField,
+};
+use algebra::{
+    bls12_377::{Fr, G1Projective, G2Projective},
+    ProjectiveCurve,
+};
+#[cfg(feature = "constant-time")]
+use subtle::{Choice, ConditionallySelectable};
+
+/// Groups which can be multiplied by private keys
+pub trait SecretScalarMul: ProjectiveCurve<ScalarField = Fr> {
+    /// Returns `self * scalar`, in constant time if the `constant-time` feature is enabled
+    fn mul_secret(&self, scalar: &Fr) -> Self;
+}
+
+impl SecretScalarMul for G1Projective {
+    fn mul_secret(&self, scalar: &Fr) -> Self {
+        mul(self, scalar)
+    }
+}
+
+impl SecretScalarMul for G2Projective {
+    fn mul_secret(&self, scalar: &Fr) -> Self {
+        mul(self, scalar)
+    }
+}
+
+#[cfg(not(feature = "constant-time"))]
+fn mul<G: ProjectiveCurve<ScalarField = Fr>>(base: &G, scalar: &Fr) -> G {
+    base.mul(*scalar)
+}
+
+#[cfg(feature = "constant-time")]
+fn mul<G: ConditionallySwappable + ProjectiveCurve<ScalarField = Fr>>(base: &G, scalar: &Fr) -> G {
+    // the padded scalar has its top bit at `top`, so the ladder starts from `base` and
+    // processes the remaining `top` bits
+    let top = FrParameters::MODULUS_BITS as usize;
+    let scalar = pad_scalar(scalar);
+
+    let mut r0 = *base;
+    let mut r1 = base.double();
+    for i in (0..top).rev() {
+        // invariant: r1 - r0 = base
+        let bit = Choice::from(scalar.get_bit(i) as u8);
+        G::conditional_swap(&mut r0, &mut r1, bit);
+        r1 += &r0;
+        r0.double_in_place();
+        G::conditional_swap(&mut r0, &mut r1, bit);
+    }
+    r0
+}
+
+/// Returns `scalar + r` or `scalar + 2r`, whichever has bit `MODULUS_BITS` set. Since
+/// `2^(MODULUS_BITS - 1) < r < 2^MODULUS_BITS`, exactly one of them does and it is congruent
+/// to `scalar` modulo `r`.
+#[cfg(feature = "constant-time")]
+fn pad_scalar(scalar: &Fr) -> <Fr as PrimeField>::BigInt {
+    let modulus = FrParameters::MODULUS;
+    let mut once = scalar.into_repr();
+    once.add_nocarry(&modulus);
+    let mut twice = once;
+    twice.add_nocarry(&modulus);
+
+    let use_twice = Choice::from(!once.get_bit(FrParameters::MODULUS_BITS as usize) as u8);
+    swap_limbs(once.as_mut(), twice.as_mut(), use_twice);
+    once
+}
+
+/// Points whose coordinates can be swapped in constant time
+#[cfg(feature = "constant-time")]
+trait ConditionallySwappable {
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice);
+}
+
+#[cfg(feature = "constant-time")]
+impl ConditionallySwappable for G1Projective {
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        swap_fq(&mut a.x, &mut b.x, choice);
+        swap_fq(&mut a.y, &mut b.y, choice);
+        swap_fq(&mut a.z, &mut b.z, choice);
+    }
+}
+
+#[cfg(feature = "constant-time")]
+impl ConditionallySwappable for G2Projective {
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        swap_fq2(&mut a.x, &mut b.x, choice);
+        swap_fq2(&mut a.y, &mut b.y, choice);
+        swap_fq2(&mut a.z, &mut b.z, choice);
+    }
+}
+
+#[cfg(feature = "constant-time")]
+fn swap_fq2(a: &mut Fq2, b: &mut Fq2, choice: Choice) {
+    swap_fq(&mut a.c0, &mut b.c0, choice);
+    swap_fq(&mut a.c1, &mut b.c1, choice);
+}
+
+#[cfg(feature = "constant-time")]
+fn swap_fq(a: &mut Fq, b: &mut Fq, choice: Choice) {
+    swap_limbs(a.0.as_mut(), b.0.as_mut(), choice);
+}
+
+#[cfg(feature = "constant-time")]
+fn swap_limbs(a: &mut [u64], b: &mut [u64], choice: Choice) {
+    for (a, b) in a.iter_mut().zip(b.iter_mut()) {
+        u64::conditional_swap(a, b, choice);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{One, UniformRand, Zero};
+
+    #[test]
+    fn matches_variable_time_mul() {
+        let rng = &mut rand::thread_rng();
+        let g1 = G1Projective::rand(rng);
+        let g2 = G2Projective::rand(rng);
+        let minus_one = -Fr::one();
+
+        for scalar in [Fr::zero(), Fr::one(), minus_one]
+            .iter()
+            .cloned()
+            .chain((0..5).map(|_| Fr::rand(rng)))
+        {
+            assert_eq!(g1.mul_secret(&scalar), g1.mul(scalar));
+            assert_eq!(g2.mul_secret(&scalar), g2.mul(scalar));
+        }
+    }
+
+    #[cfg(feature = "constant-time")]
+    mod timing {
+        use super::*;
+        use crate::{
+            hash_to_curve::try_and_increment::DIRECT_HASH_TO_G1, HashToCurve, PrivateKey,
+            SIG_DOMAIN,
+        };
+        use std::time::Instant;
+
+        /// Number of measurements per class
+        const SAMPLES: usize = 2000;
+
+        /// dudect's threshold above which a timing leak is considered certain
+        const T_THRESHOLD: f64 = 10.0;
+
+        /// Welch's t statistic of the two samples
+        fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+            let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+            let var = |x: &[f64], m: f64| {
+                x.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (x.len() - 1) as f64
+            };
+            let (mean_a, mean_b) = (mean(a), mean(b));
+            let (var_a, var_b) = (var(a, mean_a), var(b, mean_b));
+            (mean_a - mean_b) / (var_a / a.len() as f64 + var_b / b.len() as f64).sqrt()
+        }
+
+        /// Measures `op` on a fixed input class and a random input class, interleaving the
+        /// classes randomly as dudect does, and returns the t statistic of the timings.
+        fn leakage<T, F: Fn(&T)>(fixed: T, random: impl Fn() -> T, op: F) -> f64 {
+            let rng = &mut rand::thread_rng();
+            let (mut fixed_times, mut random_times) = (vec![], vec![]);
+            while fixed_times.len() < SAMPLES || random_times.len() < SAMPLES {
+                let use_fixed = rand::Rng::gen::<bool>(rng);
+                let input = if use_fixed { None } else { Some(random()) };
+                let input = input.as_ref().unwrap_or(&fixed);
+
+                let start = Instant::now();
+                op(input);
+                let elapsed = start.elapsed().as_nanos() as f64;
+
+                if use_fixed {
+                    fixed_times.push(elapsed);
+                } else {
+                    random_times.push(elapsed);
+                }
+            }
+            welch_t(&fixed_times, &random_times)
+        }
+
+        // Timing measurements are noisy on shared machines, so these only run on request:
+        // `cargo test --release --features constant-time -- --ignored timing`
+
+        #[test]
+        #[ignore]
+        fn scalar_mul_timing() {
+            let rng = &mut rand::thread_rng();
+            let base = G1Projective::rand(rng);
+            // a low hamming weight scalar, which leaks the most with double-and-add
+            let t = leakage(
+                Fr::one(),
+                || Fr::rand(&mut rand::thread_rng()),
+                |scalar| {
+                    base.mul_secret(scalar);
+                },
+            );
+            assert!(t.abs() < T_THRESHOLD, "t = {}", t);
+        }
+
+        #[test]
+        #[ignore]
+        fn signing_timing() {
+            let message = b"dudect";
+            let hash = DIRECT_HASH_TO_G1.hash(SIG_DOMAIN, message, &[]).unwrap();
+            let t = leakage(
+                PrivateKey::from(Fr::one()),
+                || PrivateKey::generate(&mut rand::thread_rng()),
+                |key| {
+                    key.sign_exact(&hash);
+                },
+            );
+            assert!(t.abs() < T_THRESHOLD, "t = {}", t);
+        }
+    }
+}
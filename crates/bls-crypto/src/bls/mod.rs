@@ -1,5 +1,13 @@
 //! Implements BLS signatures as specified in https://crypto.stanford.edu/~dabo/pubs/papers/BLSmultisig.html.
 
+use crate::BLSError;
+use algebra::{
+    bls12_377::{Bls12_377, Fq12, G1Affine, G1Prepared, G1Projective, G2Affine, G2Prepared},
+    AffineCurve, PairingEngine, ProjectiveCurve, Zero,
+};
+use once_cell::sync::Lazy;
+use std::{cell::RefCell, iter, ops::Neg};
+
 mod secret;
 pub use secret::PrivateKey;
 
@@ -9,5 +17,89 @@ pub use public::PublicKey;
 mod signature;
 pub use signature::Signature;
 
+mod partial;
+pub use partial::PartialSignatureSet;
+
+mod bitmap;
+pub use bitmap::Bitmap;
+
+mod attested;
+pub use attested::AttestedAggregate;
+
+mod audited;
+pub use audited::{KeyCommitment, KeyDerivationProof};
+
+mod signing_session;
+pub use signing_session::SigningSession;
+
+mod rotation;
+pub use rotation::KeyRotation;
+
+mod threshold;
+pub use threshold::{KeyShare, RefreshDealing, ShareCommitments, ShareUpdate};
+
+mod scheme;
+pub use scheme::{BlsScheme, MinPk, MinSig};
+
 mod cache;
 pub use cache::PublicKeyCache;
+
+mod validation;
+pub use validation::{SubgroupCheck, ValidationPolicy};
+
+mod constant_time;
+pub use constant_time::SecretScalarMul;
+
+/// The negated generator of G2, prepared for pairings. Every verification pairs the signature
+/// with it, so its line coefficients are computed once rather than on every verification.
+pub static NEG_G2_GENERATOR_PREPARED: Lazy<G2Prepared> =
+    Lazy::new(|| G2Affine::prime_subgroup_generator().neg().into());
+
+/// The negated generator of G1, prepared for pairings, which `MinPk` signatures are paired
+/// with
+pub static NEG_G1_GENERATOR_PREPARED: Lazy<G1Prepared> =
+    Lazy::new(|| G1Affine::prime_subgroup_generator().neg().into());
+
+thread_local! {
+    /// The pair of a signature and `NEG_G2_GENERATOR_PREPARED`, whose line coefficients are
+    /// copied once per thread. Only the signature is replaced on each verification.
+    static SIGNATURE_PAIR: RefCell<(G1Prepared, G2Prepared)> = RefCell::new((
+        G1Affine::prime_subgroup_generator().into(),
+        NEG_G2_GENERATOR_PREPARED.clone(),
+    ));
+}
+
+/// Returns `e(signature, -g_2) * Π e(p_i, q_i)` over the prepared `pairs`, pairing the
+/// signature with `NEG_G2_GENERATOR_PREPARED` without copying its line coefficients
+pub(crate) fn product_with_neg_g2_generator(
+    signature: &G1Projective,
+    pairs: &[(G1Prepared, G2Prepared)],
+) -> Fq12 {
+    SIGNATURE_PAIR.with(|signature_pair| {
+        let mut signature_pair = signature_pair.borrow_mut();
+        signature_pair.0 = signature.into_affine().into();
+        Bls12_377::product_of_pairings(iter::once(&*signature_pair).chain(pairs))
+    })
+}
+
+/// Canonically encodes the consensus metadata which a signature is bound to as
+/// `height (LE u64) || round (LE u32)`. It is used as the extra data of the signatures
+/// produced by `PrivateKey::sign_with_context`, so that a signature for one round cannot
+/// be reused in another.
+pub fn encode_signing_context(round: u32, height: u64) -> Vec<u8> {
+    let mut extra_data = Vec::with_capacity(12);
+    extra_data.extend_from_slice(&height.to_le_bytes());
+    extra_data.extend_from_slice(&round.to_le_bytes());
+    extra_data
+}
+
+/// Checks a message hash provided by the caller rather than computed with a `HashToCurve`.
+/// Signing the identity produces the identity, which verifies under every key, and signing a
+/// point outside the prime order subgroup leaks the key modulo the cofactor.
+fn check_message_hash(message_hash: &G1Projective) -> Result<(), BLSError> {
+    let message_hash = message_hash.into_affine();
+    if message_hash.is_zero() {
+        return Err(BLSError::IdentityMessageHash);
+    }
+    ValidationPolicy::STRICT.validate(&message_hash)
+}
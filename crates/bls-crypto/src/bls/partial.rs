@@ -0,0 +1,154 @@
+use super::{AttestedAggregate, Bitmap, PublicKey, Signature};
+use crate::{BLSError, BlsResult, HashToCurve, SIG_DOMAIN};
+
+use algebra::bls12_377::G1Projective;
+
+/// Collects the partial signatures of a validator set over the same message as they
+/// arrive. Each partial signature is verified individually before being accepted, and
+/// once `threshold` validators have contributed, the signed bitmap and the aggregate
+/// signature can be emitted.
+#[derive(Clone, Debug)]
+pub struct PartialSignatureSet {
+    pub(super) public_keys: Vec<PublicKey>,
+    pub(super) message_hash: G1Projective,
+    pub(super) signatures: Vec<Option<Signature>>,
+    num_signers: usize,
+    pub(super) threshold: usize,
+}
+
+impl PartialSignatureSet {
+    /// Creates an empty set for the validators' public keys over an already hashed message.
+    /// The validator index of each partial signature is its public key's position in `public_keys`.
+    pub fn new(public_keys: Vec<PublicKey>, message_hash: G1Projective, threshold: usize) -> Self {
+        let signatures = vec![None; public_keys.len()];
+        Self {
+            public_keys,
+            message_hash,
+            signatures,
+            num_signers: 0,
+            threshold,
+        }
+    }
+
+    /// Creates an empty set for the validators' public keys over the message-extra_data pair,
+    /// which is hashed with the `hash_to_g1` hasher under the `SIG_DOMAIN`.
+    pub fn for_message<H: HashToCurve<Output = G1Projective>>(
+        public_keys: Vec<PublicKey>,
+        message: &[u8],
+        extra_data: &[u8],
+        hash_to_g1: &H,
+        threshold: usize,
+    ) -> BlsResult<Self> {
+        let message_hash = hash_to_g1.hash(SIG_DOMAIN, message, extra_data)?;
+        Ok(Self::new(public_keys, message_hash, threshold))
+    }
+
+    /// Verifies the partial signature of the validator at `index` and adds it to the set.
+    /// Returns whether the threshold has been reached. Repeated signatures from a validator
+    /// which has already contributed are ignored.
+    pub fn add(&mut self, index: usize, signature: Signature) -> BlsResult<bool> {
+        let public_key = self
+            .public_keys
+            .get(index)
+            .ok_or(BLSError::InvalidValidatorIndex(index))?;
+        if self.signatures[index].is_none() {
+            public_key.verify_partial(&self.message_hash, &signature)?;
+            self.signatures[index] = Some(signature);
+            self.num_signers += 1;
+        }
+        Ok(self.is_complete())
+    }
+
+    /// Returns whether the validator at `index` has contributed a valid partial signature
+    pub fn has_signed(&self, index: usize) -> bool {
+        self.signatures
+            .get(index)
+            .map(Option::is_some)
+            .unwrap_or(false)
+    }
+
+    /// The number of validators which have contributed a valid partial signature
+    pub fn num_signers(&self) -> usize {
+        self.num_signers
+    }
+
+    /// Whether the threshold has been reached
+    pub fn is_complete(&self) -> bool {
+        self.num_signers >= self.threshold
+    }
+
+    /// The bitmap of the validators which have contributed a valid partial signature
+    pub fn bitmap(&self) -> Bitmap {
+        self.signatures.iter().map(Option::is_some).collect()
+    }
+
+    /// Returns the bitmap and the aggregate signature, if the threshold has been reached
+    pub fn finalize(&self) -> Option<(Bitmap, Signature)> {
+        if !self.is_complete() {
+            return None;
+        }
+        let aggregate = Signature::aggregate(self.signatures.iter().flatten());
+        Some((self.bitmap(), aggregate))
+    }
+
+    /// Same as `finalize`, but wraps the bitmap and the aggregate signature along with the
+    /// epoch of the validator set, for transport over the network
+    pub fn attest(&self, epoch: u16) -> Option<AttestedAggregate> {
+        self.finalize()
+            .map(|(bitmap, aggregate)| AttestedAggregate::new(epoch, bitmap, aggregate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_to_curve::try_and_increment::DIRECT_HASH_TO_G1, PrivateKey};
+
+    #[test]
+    fn collects_partial_signatures() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..4)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|k| k.to_public()).collect::<Vec<_>>();
+        let hasher = &*DIRECT_HASH_TO_G1;
+        let sign = |i: usize, message: &[u8]| keys[i].sign(message, &[], hasher).unwrap();
+
+        let mut set =
+            PartialSignatureSet::for_message(public_keys.clone(), b"hello", &[], hasher, 3)
+                .unwrap();
+        assert!(!set.add(0, sign(0, b"hello")).unwrap());
+        // signatures over another message are rejected
+        assert!(set.add(1, sign(1, b"world")).is_err());
+        // signatures by another validator are rejected
+        assert!(set.add(1, sign(2, b"hello")).is_err());
+        assert!(matches!(
+            set.add(4, sign(0, b"hello")),
+            Err(BLSError::InvalidValidatorIndex(4))
+        ));
+        assert!(!set.add(2, sign(2, b"hello")).unwrap());
+        // duplicates are not counted
+        assert!(!set.add(2, sign(2, b"hello")).unwrap());
+        assert_eq!(set.num_signers(), 2);
+        assert!(set.finalize().is_none());
+
+        assert!(set.add(3, sign(3, b"hello")).unwrap());
+        assert!(set.has_signed(3) && !set.has_signed(1) && !set.has_signed(10));
+        let (bitmap, aggregate) = set.finalize().unwrap();
+        assert_eq!(bitmap, Bitmap::from(vec![true, false, true, true]));
+        let attested = set.attest(5).unwrap();
+        assert_eq!(attested.signer_count(), 3);
+        attested
+            .verify(&public_keys, b"hello", &[], hasher)
+            .unwrap();
+
+        let signers = public_keys
+            .iter()
+            .zip(&bitmap)
+            .filter(|(_, signed)| **signed)
+            .map(|(pk, _)| pk);
+        PublicKey::aggregate(signers)
+            .verify(b"hello", &[], &aggregate, hasher)
+            .unwrap();
+    }
+}
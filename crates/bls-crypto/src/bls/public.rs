@@ -1,15 +1,21 @@
-use crate::{BLSError, BlsResult, HashToCurve, PrivateKey, Signature, POP_DOMAIN, SIG_DOMAIN};
+use super::{
+    check_message_hash, encode_signing_context, product_with_neg_g2_generator,
+    validation::into_serialization_error, SecretScalarMul,
+};
+use crate::{
+    BLSError, BlsResult, HashToCurve, PrivateKey, Signature, ValidationPolicy, POP_DOMAIN,
+    SIG_DOMAIN,
+};
 
 use algebra::{
-    bls12_377::{Bls12_377, Fq12, G1Projective, G2Affine, G2Projective},
-    AffineCurve, CanonicalDeserialize, CanonicalSerialize, One, PairingEngine, ProjectiveCurve,
-    SerializationError,
+    bls12_377::{Fq12, G1Projective, G2Affine, G2Projective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, One, ProjectiveCurve,
+    SerializationError, Zero,
 };
 
 use std::{
     borrow::Borrow,
     io::{Read, Write},
-    ops::Neg,
 };
 
 /// A BLS public key on G2
@@ -24,7 +30,7 @@ impl From<G2Projective> for PublicKey {
 
 impl From<&PrivateKey> for PublicKey {
     fn from(pk: &PrivateKey) -> PublicKey {
-        PublicKey::from(G2Projective::prime_subgroup_generator().mul(*pk.as_ref()))
+        PublicKey::from(G2Projective::prime_subgroup_generator().mul_secret(pk.as_ref()))
     }
 }
 
@@ -44,6 +50,13 @@ impl PublicKey {
             .into()
     }
 
+    /// Removes a public key from the aggregate public key, e.g. the key of a validator whose
+    /// signature was removed from the aggregate signature with `Signature::remove`. The
+    /// public key MUST have been part of the aggregate, otherwise the result is meaningless.
+    pub fn remove(&self, public_key: &PublicKey) -> PublicKey {
+        PublicKey(self.0 - public_key.0)
+    }
+
     /// Verifies the provided signature against the message-extra_data pair using the
     /// `hash_to_g1` hasher.
     ///
@@ -58,6 +71,24 @@ impl PublicKey {
         self.verify_sig(SIG_DOMAIN, message, extra_data, signature, hash_to_g1)
     }
 
+    /// Verifies a signature produced by `PrivateKey::sign_with_context` for the
+    /// provided consensus round and height
+    pub fn verify_with_context<H: HashToCurve<Output = G1Projective>>(
+        &self,
+        message: &[u8],
+        round: u32,
+        height: u64,
+        signature: &Signature,
+        hash_to_g1: &H,
+    ) -> BlsResult<()> {
+        self.verify(
+            message,
+            &encode_signing_context(round, height),
+            signature,
+            hash_to_g1,
+        )
+    }
+
     /// Verifies the provided proof of possession signature against the message using the
     /// `hash_to_g1` hasher.
     ///
@@ -79,25 +110,95 @@ impl PublicKey {
         signature: &Signature,
         hash_to_g1: &H,
     ) -> BlsResult<()> {
-        let pairing = Bls12_377::product_of_pairings(&vec![
-            (
-                signature.as_ref().into_affine().into(),
-                G2Affine::prime_subgroup_generator().neg().into(),
-            ),
-            (
-                hash_to_g1
-                    .hash(domain, message, extra_data)?
-                    .into_affine()
-                    .into(),
+        let message_hash = hash_to_g1.hash(domain, message, extra_data)?;
+        self.verify_exact(&message_hash, signature)
+    }
+
+    /// Verifies an individual validator's signature over an already hashed message, e.g.
+    /// when checking partial signatures as they are gossiped before aggregating them.
+    ///
+    /// Fails fast without computing any pairings if the signature is the identity or
+    /// is not in the prime order subgroup.
+    pub fn verify_partial(
+        &self,
+        message_hash: &G1Projective,
+        signature: &Signature,
+    ) -> BlsResult<()> {
+        if signature.as_ref().is_zero() {
+            return Err(BLSError::VerificationFailed);
+        }
+        self.verify_partial_with_policy(message_hash, signature, ValidationPolicy::STRICT)
+    }
+
+    /// Verifies an individual validator's signature over an already hashed message like
+    /// `verify_partial`, validating the signature as the policy requires, e.g. skipping the
+    /// subgroup check of signatures which were already validated when they were received.
+    pub fn verify_partial_with_policy(
+        &self,
+        message_hash: &G1Projective,
+        signature: &Signature,
+        policy: ValidationPolicy,
+    ) -> BlsResult<()> {
+        policy.validate(&signature.as_ref().into_affine())?;
+        self.verify_exact(message_hash, signature)
+    }
+
+    /// Verifies a signature produced by `PrivateKey::sign_exact` over a message
+    /// which has already been hashed to G1. The key and the signature are validated with the
+    /// default `ValidationPolicy`, which does not check them, see `verify_exact_with_policy`.
+    pub fn verify_exact(
+        &self,
+        message_hash: &G1Projective,
+        signature: &Signature,
+    ) -> BlsResult<()> {
+        self.verify_exact_with_policy(message_hash, signature, ValidationPolicy::default())
+    }
+
+    /// Verifies a signature like `verify_exact`, validating the key and the signature as the
+    /// policy requires, e.g. skipping the subgroup check of a key which was validated when
+    /// it was registered
+    pub fn verify_exact_with_policy(
+        &self,
+        message_hash: &G1Projective,
+        signature: &Signature,
+        policy: ValidationPolicy,
+    ) -> BlsResult<()> {
+        policy.validate(&self.0.into_affine())?;
+        policy.validate(&signature.as_ref().into_affine())?;
+        let pairing = product_with_neg_g2_generator(
+            signature.as_ref(),
+            &[(
+                message_hash.into_affine().into(),
                 self.0.into_affine().into(),
-            ),
-        ]);
+            )],
+        );
         if pairing == Fq12::one() {
             Ok(())
         } else {
             Err(BLSError::VerificationFailed)
         }
     }
+
+    /// Verifies a signature produced by `PrivateKey::sign_prehashed`. The message hash is
+    /// checked as when signing, and the signature as in `verify_partial`.
+    pub fn verify_prehashed(
+        &self,
+        message_hash: &G1Projective,
+        signature: &Signature,
+    ) -> BlsResult<()> {
+        check_message_hash(message_hash)?;
+        self.verify_partial(message_hash, signature)
+    }
+
+    /// Deserializes a compressed public key, validating it as the policy requires instead of
+    /// always running the subgroup check like `CanonicalDeserialize`
+    pub fn deserialize_with_policy<R: Read>(
+        reader: R,
+        policy: ValidationPolicy,
+    ) -> BlsResult<PublicKey> {
+        let point: G2Affine = policy.read_point(reader)?;
+        Ok(PublicKey::from(point.into_projective()))
+    }
 }
 
 impl CanonicalSerialize for PublicKey {
@@ -116,14 +217,14 @@ impl CanonicalSerialize for PublicKey {
 
 impl CanonicalDeserialize for PublicKey {
     fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
-        Ok(PublicKey::from(
-            G2Affine::deserialize(reader)?.into_projective(),
-        ))
+        Self::deserialize_with_policy(reader, ValidationPolicy::CANONICAL)
+            .map_err(into_serialization_error)
     }
 
     fn deserialize_uncompressed<R: Read>(reader: R) -> Result<Self, SerializationError> {
-        Ok(PublicKey::from(
-            G2Affine::deserialize_uncompressed(reader)?.into_projective(),
-        ))
+        let point: G2Affine = ValidationPolicy::CANONICAL
+            .read_point_uncompressed(reader)
+            .map_err(into_serialization_error)?;
+        Ok(PublicKey::from(point.into_projective()))
     }
 }
@@ -0,0 +1,144 @@
+use super::{PrivateKey, PublicKey, Signature};
+use crate::{BlsResult, HashToCurve, ROTATION_DOMAIN};
+
+use algebra::{
+    bls12_377::{Fq, FqParameters, G1Projective},
+    BigInteger, FpParameters, PrimeField, ProjectiveCurve,
+};
+
+/// A handoff from a validator's old key to its new key, signed with the old key.
+///
+/// The handoff lets a validator rotate its key within an epoch while the epoch's signers
+/// remain accountable: whoever verifies the rotation knows that the new key was chosen by
+/// the holder of the old one. It does not prove possession of the new key, which must be
+/// checked separately with `PublicKey::verify_pop` before the new key is trusted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRotation {
+    /// The key which is rotated out
+    pub old_key: PublicKey,
+    /// The key which replaces it
+    pub new_key: PublicKey,
+    /// The old key's signature over the handoff message
+    pub signature: Signature,
+}
+
+impl KeyRotation {
+    /// Signs the handoff from the key's public key to `new_key`
+    pub fn sign<H: HashToCurve<Output = G1Projective>>(
+        old_key: &PrivateKey,
+        new_key: PublicKey,
+        hash_to_g1: &H,
+    ) -> BlsResult<Self> {
+        let old_key_public = old_key.to_public();
+        let message_hash = Self::hash_handoff(&old_key_public, &new_key, hash_to_g1)?;
+        Ok(Self {
+            signature: old_key.sign_exact(&message_hash),
+            old_key: old_key_public,
+            new_key,
+        })
+    }
+
+    /// Encodes the handoff message as `old_key || new_key`, with each key encoded as in the
+    /// epoch blocks (the big-endian bits of the x coordinate's `c0` and `c1`, followed by
+    /// whether y is over half the modulus), and the bits packed into little-endian bytes.
+    /// This is the message which `epoch_snark`'s key rotation circuit hashes, so handoffs
+    /// which are proven must be hashed with `COMPOSITE_HASH_TO_G1_CIP22`.
+    pub fn encode_handoff(old_key: &PublicKey, new_key: &PublicKey) -> BlsResult<Vec<u8>> {
+        let mut bits = [encode_key(old_key), encode_key(new_key)].concat();
+        bits.reverse();
+        Ok(bits
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, bit)| byte | ((*bit as u8) << i))
+            })
+            .collect())
+    }
+
+    /// Hashes the handoff message to G1 with the `hash_to_g1` hasher under the
+    /// `ROTATION_DOMAIN`
+    pub fn hash_handoff<H: HashToCurve<Output = G1Projective>>(
+        old_key: &PublicKey,
+        new_key: &PublicKey,
+        hash_to_g1: &H,
+    ) -> BlsResult<G1Projective> {
+        let message = Self::encode_handoff(old_key, new_key)?;
+        hash_to_g1.hash(ROTATION_DOMAIN, &message, &[])
+    }
+
+    /// Returns the hash of the rotation's handoff message
+    pub fn message_hash<H: HashToCurve<Output = G1Projective>>(
+        &self,
+        hash_to_g1: &H,
+    ) -> BlsResult<G1Projective> {
+        Self::hash_handoff(&self.old_key, &self.new_key, hash_to_g1)
+    }
+
+    /// Verifies that the old key signed the handoff to the new key
+    pub fn verify<H: HashToCurve<Output = G1Projective>>(&self, hash_to_g1: &H) -> BlsResult<()> {
+        let message_hash = self.message_hash(hash_to_g1)?;
+        self.old_key.verify_exact(&message_hash, &self.signature)
+    }
+
+    /// Applies the rotations to a validator set, replacing each rotation's old key by its new
+    /// key. The rotations must have been verified beforehand.
+    pub fn apply(rotations: &[KeyRotation], validators: &mut [PublicKey]) {
+        for rotation in rotations {
+            for validator in validators.iter_mut() {
+                if *validator == rotation.old_key {
+                    *validator = rotation.new_key.clone();
+                }
+            }
+        }
+    }
+}
+
+// the big-endian bits of the compressed key, as in `epoch_snark::encoding::encode_public_key`
+fn encode_key(key: &PublicKey) -> Vec<bool> {
+    let key = key.as_ref().into_affine();
+    let modulus_bits = FqParameters::MODULUS_BITS as usize;
+    let mut bits = vec![];
+    for coordinate in &[key.x.c0, key.x.c1] {
+        let coordinate_bits = coordinate.into_repr().to_bits();
+        bits.extend_from_slice(&coordinate_bits[coordinate_bits.len() - modulus_bits..]);
+    }
+    let (y_c0, y_c1) = (key.y.c0.into_repr(), key.y.c1.into_repr());
+    let half = Fq::modulus_minus_one_div_two();
+    bits.push(y_c1 > half || (y_c1.is_zero() && y_c0 > half));
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_to_curve::try_and_increment::DIRECT_HASH_TO_G1;
+
+    #[test]
+    fn verifies_handoffs() {
+        let rng = &mut rand::thread_rng();
+        let hasher = &*DIRECT_HASH_TO_G1;
+        let old_key = PrivateKey::generate(rng);
+        let new_key = PrivateKey::generate(rng).to_public();
+
+        let rotation = KeyRotation::sign(&old_key, new_key.clone(), hasher).unwrap();
+        rotation.verify(hasher).unwrap();
+
+        // the handoff cannot be redirected to another key
+        let mut redirected = rotation.clone();
+        redirected.new_key = PrivateKey::generate(rng).to_public();
+        assert!(redirected.verify(hasher).is_err());
+
+        // nor signed by another key on behalf of the old one
+        let mut forged = rotation.clone();
+        forged.signature =
+            PrivateKey::generate(rng).sign_exact(&rotation.message_hash(hasher).unwrap());
+        assert!(forged.verify(hasher).is_err());
+
+        let other = PrivateKey::generate(rng).to_public();
+        let mut validators = vec![other.clone(), old_key.to_public()];
+        KeyRotation::apply(&[rotation], &mut validators);
+        assert_eq!(validators, vec![other, new_key]);
+    }
+}
@@ -0,0 +1,204 @@
+use super::{
+    product_with_neg_g2_generator, PrivateKey, SecretScalarMul, NEG_G1_GENERATOR_PREPARED,
+};
+use crate::{BLSError, BlsResult, HashToCurve, SIG_DOMAIN};
+
+use algebra::{
+    bls12_377::{Bls12_377, Fq12, G1Projective, G2Projective},
+    One, PairingEngine, ProjectiveCurve, Zero,
+};
+
+/// Parameterizes BLS signatures over the group which holds the public keys and the group
+/// which holds the signatures (and message hashes).
+///
+/// - `MinSig`: signatures on G1 and public keys on G2. This is the scheme implemented by
+///   `PublicKey` and `Signature`, and the one used by Celo.
+/// - `MinPk`: public keys on G1 and signatures on G2, for integrations which need small
+///   public keys at the expense of larger signatures.
+pub trait BlsScheme {
+    /// The group of the public keys
+    type PublicKey: SecretScalarMul;
+    /// The group of the signatures and of the hashed messages
+    type Signature: SecretScalarMul;
+
+    /// Returns `e(signature, -g) * e(message_hash, public_key) == 1`, with the pairing
+    /// arguments ordered according to the groups of the scheme
+    fn pairing_check(
+        public_key: &Self::PublicKey,
+        message_hash: &Self::Signature,
+        signature: &Self::Signature,
+    ) -> bool;
+
+    /// Returns the public key of the private key in the scheme's public key group
+    fn public_key(private_key: &PrivateKey) -> Self::PublicKey {
+        Self::PublicKey::prime_subgroup_generator().mul_secret(private_key.as_ref())
+    }
+
+    /// Hashes the message/extra_data tuple with the provided `hash_to_curve` function
+    /// and then signs it in the SIG_DOMAIN
+    fn sign<H: HashToCurve<Output = Self::Signature>>(
+        private_key: &PrivateKey,
+        message: &[u8],
+        extra_data: &[u8],
+        hash_to_curve: &H,
+    ) -> BlsResult<Self::Signature> {
+        let hash = hash_to_curve.hash(SIG_DOMAIN, message, extra_data)?;
+        Ok(hash.mul_secret(private_key.as_ref()))
+    }
+
+    /// Verifies the signature against the message/extra_data tuple and the public key
+    fn verify<H: HashToCurve<Output = Self::Signature>>(
+        public_key: &Self::PublicKey,
+        message: &[u8],
+        extra_data: &[u8],
+        signature: &Self::Signature,
+        hash_to_curve: &H,
+    ) -> BlsResult<()> {
+        let hash = hash_to_curve.hash(SIG_DOMAIN, message, extra_data)?;
+        if Self::pairing_check(public_key, &hash, signature) {
+            Ok(())
+        } else {
+            Err(BLSError::VerificationFailed)
+        }
+    }
+
+    /// Sums the provided public keys
+    fn aggregate_public_keys<'a>(
+        public_keys: impl IntoIterator<Item = &'a Self::PublicKey>,
+    ) -> Self::PublicKey
+    where
+        Self::PublicKey: 'a,
+    {
+        public_keys
+            .into_iter()
+            .fold(Self::PublicKey::zero(), |acc, pk| acc + *pk)
+    }
+
+    /// Sums the provided signatures
+    fn aggregate_signatures<'a>(
+        signatures: impl IntoIterator<Item = &'a Self::Signature>,
+    ) -> Self::Signature
+    where
+        Self::Signature: 'a,
+    {
+        signatures
+            .into_iter()
+            .fold(Self::Signature::zero(), |acc, sig| acc + *sig)
+    }
+}
+
+/// Signatures on G1, public keys on G2
+#[derive(Clone, Copy, Debug)]
+pub struct MinSig;
+
+/// Public keys on G1, signatures on G2
+#[derive(Clone, Copy, Debug)]
+pub struct MinPk;
+
+impl BlsScheme for MinSig {
+    type PublicKey = G2Projective;
+    type Signature = G1Projective;
+
+    fn pairing_check(
+        public_key: &G2Projective,
+        message_hash: &G1Projective,
+        signature: &G1Projective,
+    ) -> bool {
+        product_with_neg_g2_generator(
+            signature,
+            &[(
+                message_hash.into_affine().into(),
+                public_key.into_affine().into(),
+            )],
+        ) == Fq12::one()
+    }
+}
+
+impl BlsScheme for MinPk {
+    type PublicKey = G1Projective;
+    type Signature = G2Projective;
+
+    fn pairing_check(
+        public_key: &G1Projective,
+        message_hash: &G2Projective,
+        signature: &G2Projective,
+    ) -> bool {
+        Bls12_377::product_of_pairings(&[
+            (
+                NEG_G1_GENERATOR_PREPARED.clone(),
+                signature.into_affine().into(),
+            ),
+            (
+                public_key.into_affine().into(),
+                message_hash.into_affine().into(),
+            ),
+        ]) == Fq12::one()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_to_curve::try_and_increment::{
+        COMPOSITE_HASH_TO_G1, DIRECT_HASH_TO_G1, DIRECT_HASH_TO_G2,
+    };
+    use algebra::UniformRand;
+
+    fn sign_and_verify<S: BlsScheme, H: HashToCurve<Output = S::Signature>>(hasher: &H) {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..3)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(S::public_key).collect::<Vec<_>>();
+        let signatures = keys
+            .iter()
+            .map(|key| S::sign(key, b"hello", b"extra", hasher).unwrap())
+            .collect::<Vec<_>>();
+
+        for (public_key, signature) in public_keys.iter().zip(&signatures) {
+            S::verify(public_key, b"hello", b"extra", signature, hasher).unwrap();
+            S::verify(public_key, b"goodbye", b"extra", signature, hasher).unwrap_err();
+        }
+
+        let apk = S::aggregate_public_keys(&public_keys);
+        let asig = S::aggregate_signatures(&signatures);
+        S::verify(&apk, b"hello", b"extra", &asig, hasher).unwrap();
+        S::verify(&apk, b"hello", b"extra", &signatures[0], hasher).unwrap_err();
+    }
+
+    #[test]
+    fn min_sig() {
+        sign_and_verify::<MinSig, _>(&*DIRECT_HASH_TO_G1);
+    }
+
+    #[test]
+    fn min_pk() {
+        sign_and_verify::<MinPk, _>(&*DIRECT_HASH_TO_G2);
+    }
+
+    #[test]
+    fn pairs_signatures_with_the_prepared_generator() {
+        let rng = &mut rand::thread_rng();
+        let (p, q) = (G1Projective::rand(rng), G2Projective::rand(rng));
+        // the pair of the previous signature is reused, with the signature replaced
+        for _ in 0..2 {
+            let signature = G1Projective::rand(rng);
+            let expected = Bls12_377::pairing(signature, -G2Projective::prime_subgroup_generator())
+                * &Bls12_377::pairing(p, q);
+            let pairs = [(p.into_affine().into(), q.into_affine().into())];
+            assert_eq!(product_with_neg_g2_generator(&signature, &pairs), expected);
+        }
+    }
+
+    #[test]
+    fn min_sig_matches_existing_types() {
+        let rng = &mut rand::thread_rng();
+        let key = PrivateKey::generate(rng);
+        let signature = key.sign(b"hello", b"", &*COMPOSITE_HASH_TO_G1).unwrap();
+        assert_eq!(MinSig::public_key(&key), *key.to_public().as_ref());
+        assert_eq!(
+            MinSig::sign(&key, b"hello", b"", &*COMPOSITE_HASH_TO_G1).unwrap(),
+            *signature.as_ref()
+        );
+    }
+}
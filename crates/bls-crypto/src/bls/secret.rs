@@ -1,8 +1,9 @@
+use super::{check_message_hash, encode_signing_context, SecretScalarMul};
 use crate::{BLSError, HashToCurve, PublicKey, Signature, POP_DOMAIN, SIG_DOMAIN};
 
 use algebra::{
     bls12_377::{Fr, G1Projective},
-    CanonicalDeserialize, CanonicalSerialize, Group, SerializationError, UniformRand,
+    CanonicalDeserialize, CanonicalSerialize, SerializationError, UniformRand,
 };
 use rand::Rng;
 use std::io::{Read, Write};
@@ -40,6 +41,18 @@ impl PrivateKey {
         self.sign_message(SIG_DOMAIN, message, extra_data, hash_to_g1)
     }
 
+    /// Signs the message bound to the consensus round and height, which are
+    /// encoded with `encode_signing_context` and used as the extra data
+    pub fn sign_with_context<H: HashToCurve<Output = G1Projective>>(
+        &self,
+        message: &[u8],
+        round: u32,
+        height: u64,
+        hash_to_g1: &H,
+    ) -> Result<Signature, BLSError> {
+        self.sign(message, &encode_signing_context(round, height), hash_to_g1)
+    }
+
     /// Hashes the message with the provided `hash_to_g1` function
     /// and then signs it in the POP_DOMAIN
     pub fn sign_pop<H: HashToCurve<Output = G1Projective>>(
@@ -59,11 +72,22 @@ impl PrivateKey {
         hash_to_g1: &H,
     ) -> Result<Signature, BLSError> {
         let hash = hash_to_g1.hash(domain, message, extra_data)?;
-        Ok(self.sign_raw(&hash))
+        Ok(self.sign_exact(&hash))
+    }
+
+    /// Signs a message which has already been hashed to G1. No domain separation or
+    /// hashing is applied, so the signature only depends on the key and the provided point.
+    pub fn sign_exact(&self, message_hash: &G1Projective) -> Signature {
+        message_hash.mul_secret(self.as_ref()).into()
     }
 
-    fn sign_raw(&self, message: &G1Projective) -> Signature {
-        message.mul(self.as_ref()).into()
+    /// Signs a message which the caller has already hashed to G1, e.g. inside another
+    /// circuit or an HSM, so that the signed point is exactly the provided one. Unlike
+    /// `sign_exact`, the point is rejected if it is the identity or is not in the prime order
+    /// subgroup.
+    pub fn sign_prehashed(&self, message_hash: &G1Projective) -> Result<Signature, BLSError> {
+        check_message_hash(message_hash)?;
+        Ok(self.sign_exact(message_hash))
     }
 
     /// Converts the private key to a public key
@@ -82,7 +106,11 @@ mod tests {
             DirectHasher, Hasher,
         },
     };
-    use algebra::{bls12_377::Parameters, curves::models::bls12::Bls12Parameters};
+    use algebra::{
+        bls12_377::{G1Affine, Parameters},
+        curves::models::bls12::Bls12Parameters,
+        AffineCurve, ProjectiveCurve, Zero,
+    };
     use rand::{thread_rng, Rng};
 
     #[test]
@@ -114,6 +142,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sig_with_context() {
+        let rng = &mut thread_rng();
+        let direct_hasher = DirectHasher;
+        let try_and_increment =
+            TryAndIncrement::<_, <Parameters as Bls12Parameters>::G1Parameters>::new(
+                &direct_hasher,
+            );
+        let sk = PrivateKey::generate(rng);
+        let pk = sk.to_public();
+
+        let sig = sk
+            .sign_with_context(b"hello", 1, 100, &try_and_increment)
+            .unwrap();
+        pk.verify_with_context(b"hello", 1, 100, &sig, &try_and_increment)
+            .unwrap();
+        pk.verify(
+            b"hello",
+            &encode_signing_context(1, 100),
+            &sig,
+            &try_and_increment,
+        )
+        .unwrap();
+        // the signature cannot be reused for other rounds or heights
+        pk.verify_with_context(b"hello", 2, 100, &sig, &try_and_increment)
+            .unwrap_err();
+        pk.verify_with_context(b"hello", 1, 101, &sig, &try_and_increment)
+            .unwrap_err();
+        pk.verify(b"hello", &[], &sig, &try_and_increment)
+            .unwrap_err();
+
+        assert_eq!(
+            encode_signing_context(0x0102_0304, 0x05),
+            vec![5, 0, 0, 0, 0, 0, 0, 0, 4, 3, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_prehashed() {
+        let rng = &mut thread_rng();
+        let try_and_increment =
+            TryAndIncrement::<_, <Parameters as Bls12Parameters>::G1Parameters>::new(&DirectHasher);
+        let sk = PrivateKey::generate(rng);
+        let pk = sk.to_public();
+
+        let hash = try_and_increment.hash(SIG_DOMAIN, b"hello", &[]).unwrap();
+        let sig = sk.sign_prehashed(&hash).unwrap();
+        // same as hashing and signing
+        assert_eq!(sig, sk.sign(b"hello", &[], &try_and_increment).unwrap());
+        pk.verify_prehashed(&hash, &sig).unwrap();
+        pk.verify_prehashed(&hash.double(), &sig).unwrap_err();
+        PrivateKey::generate(rng)
+            .to_public()
+            .verify_prehashed(&hash, &sig)
+            .unwrap_err();
+
+        assert!(matches!(
+            sk.sign_prehashed(&G1Projective::zero()),
+            Err(BLSError::IdentityMessageHash)
+        ));
+        assert!(matches!(
+            pk.verify_prehashed(
+                &G1Projective::zero(),
+                &Signature::from(G1Projective::zero())
+            ),
+            Err(BLSError::IdentityMessageHash)
+        ));
+
+        // a point on the curve outside of the prime order subgroup
+        let outside = (1u64..)
+            .filter_map(|x| G1Affine::get_point_from_x(x.into(), false))
+            .find(|point| !point.is_in_correct_subgroup_assuming_on_curve())
+            .unwrap()
+            .into_projective();
+        assert!(matches!(
+            sk.sign_prehashed(&outside),
+            Err(BLSError::NotInSubgroup)
+        ));
+        assert!(matches!(
+            pk.verify_prehashed(&outside, &sk.sign_exact(&outside)),
+            Err(BLSError::NotInSubgroup)
+        ));
+    }
+
     #[test]
     fn test_pop() {
         let rng = &mut thread_rng();
@@ -1,16 +1,15 @@
-use super::PublicKey;
-use crate::{BLSError, HashToCurve};
+use super::{product_with_neg_g2_generator, validation::into_serialization_error, PublicKey};
+use crate::{BLSError, BlsResult, HashToCurve, ValidationPolicy};
 
 use algebra::{
-    bls12_377::{Bls12_377, Fq12, G1Affine, G1Projective, G2Affine},
-    AffineCurve, CanonicalDeserialize, CanonicalSerialize, One, PairingEngine, ProjectiveCurve,
+    bls12_377::{Fq12, G1Affine, G1Projective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, One, ProjectiveCurve,
     SerializationError,
 };
 
 use std::{
     borrow::Borrow,
     io::{Read, Write},
-    ops::Neg,
 };
 
 /// A BLS signature on G1.
@@ -45,15 +44,15 @@ impl CanonicalSerialize for Signature {
 
 impl CanonicalDeserialize for Signature {
     fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
-        Ok(Signature::from(
-            G1Affine::deserialize(reader)?.into_projective(),
-        ))
+        Self::deserialize_with_policy(reader, ValidationPolicy::CANONICAL)
+            .map_err(into_serialization_error)
     }
 
     fn deserialize_uncompressed<R: Read>(reader: R) -> Result<Self, SerializationError> {
-        Ok(Signature::from(
-            G1Affine::deserialize_uncompressed(reader)?.into_projective(),
-        ))
+        let point: G1Affine = ValidationPolicy::CANONICAL
+            .read_point_uncompressed(reader)
+            .map_err(into_serialization_error)?;
+        Ok(Signature::from(point.into_projective()))
     }
 }
 
@@ -67,6 +66,24 @@ impl Signature {
             .into()
     }
 
+    /// Removes a signature from the aggregate signature, e.g. an invalid or equivocating
+    /// contribution which was discovered after aggregating, without re-aggregating the
+    /// remaining signatures. The signature MUST have been part of the aggregate, otherwise
+    /// the result is meaningless.
+    pub fn remove(&self, signature: &Signature) -> Signature {
+        Signature(self.0 - signature.0)
+    }
+
+    /// Deserializes a compressed signature, validating it as the policy requires instead of
+    /// always running the subgroup check like `CanonicalDeserialize`
+    pub fn deserialize_with_policy<R: Read>(
+        reader: R,
+        policy: ValidationPolicy,
+    ) -> BlsResult<Signature> {
+        let point: G1Affine = policy.read_point(reader)?;
+        Ok(Signature::from(point.into_projective()))
+    }
+
     /// Verifies the signature against a vector of pubkey & message tuples, for the provided
     /// messages domain.
     ///
@@ -77,12 +94,34 @@ impl Signature {
     ///
     /// The verification equation can be found in pg.11 from
     /// https://eprint.iacr.org/2018/483.pdf: "Batch verification"
+    ///
+    /// The signature and the public keys are validated with the default `ValidationPolicy`,
+    /// which does not check them.
     pub fn batch_verify<H: HashToCurve<Output = G1Projective>, P: Borrow<PublicKey>>(
         &self,
         pubkeys: &[P],
         domain: &[u8],
         messages: &[(&[u8], &[u8])],
         hash_to_g1: &H,
+    ) -> Result<(), BLSError> {
+        self.batch_verify_with_policy(
+            pubkeys,
+            domain,
+            messages,
+            hash_to_g1,
+            ValidationPolicy::default(),
+        )
+    }
+
+    /// Verifies the signature like `batch_verify`, validating the signature and the public
+    /// keys as the policy requires
+    pub fn batch_verify_with_policy<H: HashToCurve<Output = G1Projective>, P: Borrow<PublicKey>>(
+        &self,
+        pubkeys: &[P],
+        domain: &[u8],
+        messages: &[(&[u8], &[u8])],
+        hash_to_g1: &H,
+        policy: ValidationPolicy,
     ) -> Result<(), BLSError> {
         if pubkeys.len() != messages.len() {
             return Err(BLSError::UnevenNumKeysMessages);
@@ -92,7 +131,7 @@ impl Signature {
             .map(|(message, extra_data)| hash_to_g1.hash(domain, message, extra_data))
             .collect::<Result<Vec<G1Projective>, _>>()?;
 
-        self.batch_verify_hashes(pubkeys, &message_hashes)
+        self.batch_verify_hashes_with_policy(pubkeys, &message_hashes, policy)
     }
 
     /// Verifies the signature against a vector of pubkey & message hash tuples
@@ -101,20 +140,36 @@ impl Signature {
     ///
     /// The verification equation can be found in pg.11 from
     /// https://eprint.iacr.org/2018/483.pdf: "Batch verification"
+    ///
+    /// The signature and the public keys are validated with the default `ValidationPolicy`,
+    /// which does not check them.
     pub fn batch_verify_hashes<P: Borrow<PublicKey>>(
         &self,
         pubkeys: &[P],
         message_hashes: &[G1Projective],
+    ) -> Result<(), BLSError> {
+        self.batch_verify_hashes_with_policy(pubkeys, message_hashes, ValidationPolicy::default())
+    }
+
+    /// Verifies the signature against a vector of pubkey & message hash tuples like
+    /// `batch_verify_hashes`, after validating the signature and the public keys as the policy
+    /// requires
+    pub fn batch_verify_hashes_with_policy<P: Borrow<PublicKey>>(
+        &self,
+        pubkeys: &[P],
+        message_hashes: &[G1Projective],
+        policy: ValidationPolicy,
     ) -> Result<(), BLSError> {
         if pubkeys.len() != message_hashes.len() {
             return Err(BLSError::UnevenNumKeysMessages);
         };
+        policy.validate(&self.0.into_affine())?;
+        for pubkey in pubkeys {
+            policy.validate(&pubkey.borrow().as_ref().into_affine())?;
+        }
+
         // `.into()` is needed to prepared the points
-        let mut els = Vec::with_capacity(message_hashes.len() + 1);
-        els.push((
-            self.as_ref().into_affine().into(),
-            G2Affine::prime_subgroup_generator().neg().into(),
-        ));
+        let mut els = Vec::with_capacity(message_hashes.len());
         message_hashes
             .iter()
             .zip(pubkeys)
@@ -125,7 +180,7 @@ impl Signature {
                 ));
             });
 
-        let pairing = Bls12_377::product_of_pairings(&els);
+        let pairing = product_with_neg_g2_generator(self.as_ref(), &els);
         if pairing == Fq12::one() {
             Ok(())
         } else {
@@ -207,6 +262,34 @@ mod tests {
             .unwrap_err();
     }
 
+    #[test]
+    fn removes_from_aggregate() {
+        let message = b"hello";
+        let rng = &mut thread_rng();
+        let hasher = &*DIRECT_HASH_TO_G1;
+
+        let secret_keys = (0..3)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = secret_keys
+            .iter()
+            .map(|sk| sk.to_public())
+            .collect::<Vec<_>>();
+        let sigs = secret_keys
+            .iter()
+            .map(|sk| sk.sign(&message[..], &[], hasher).unwrap())
+            .collect::<Vec<_>>();
+
+        let apk = PublicKey::aggregate(&public_keys).remove(&public_keys[1]);
+        let asig = Signature::aggregate(&sigs).remove(&sigs[1]);
+        assert_eq!(
+            apk,
+            PublicKey::aggregate(vec![&public_keys[0], &public_keys[2]])
+        );
+        assert_eq!(asig, Signature::aggregate(vec![&sigs[0], &sigs[2]]));
+        apk.verify(&message[..], &[], &asig, hasher).unwrap();
+    }
+
     #[test]
     fn test_batch_verify() {
         let try_and_increment_direct =
@@ -337,6 +420,28 @@ mod tests {
         let res = asig.batch_verify_hashes(&aggregate_pubkeys, &messages);
 
         assert!(res.is_ok());
+
+        asig.batch_verify_hashes_with_policy(
+            &aggregate_pubkeys,
+            &messages,
+            ValidationPolicy::CACHED,
+        )
+        .unwrap();
+        let mut with_identity = aggregate_pubkeys;
+        with_identity[0] = PublicKey::from(G2Projective::zero());
+        assert!(matches!(
+            asig.batch_verify_hashes_with_policy(
+                &with_identity,
+                &messages,
+                ValidationPolicy::PREVALIDATED
+            ),
+            Err(BLSError::UnexpectedInfinity)
+        ));
+        // the default path leaves the checks to the caller
+        assert!(matches!(
+            asig.batch_verify_hashes(&with_identity, &messages),
+            Err(BLSError::VerificationFailed)
+        ));
     }
 
     #[test]
@@ -362,6 +467,17 @@ mod tests {
             sig.serialize(&mut sig_bytes).unwrap();
             let de = Signature::deserialize(&mut &sig_bytes[..]).unwrap();
             assert_eq!(sig, de);
+            let de =
+                Signature::deserialize_with_policy(&sig_bytes[..], ValidationPolicy::PREVALIDATED)
+                    .unwrap();
+            assert_eq!(sig, de);
+
+            let pk = sk.to_public();
+            let mut pk_bytes = vec![];
+            pk.serialize(&mut pk_bytes).unwrap();
+            let de = PublicKey::deserialize_with_policy(&pk_bytes[..], ValidationPolicy::CACHED)
+                .unwrap();
+            assert_eq!(pk, de);
         }
     }
 }
@@ -0,0 +1,165 @@
+use super::{Bitmap, PartialSignatureSet, PublicKey, Signature};
+use crate::{BLSError, BlsResult, HashToCurve};
+
+use algebra::{
+    bls12_377::{G1Affine, G1Projective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, ProjectiveCurve,
+};
+use std::io::{Read, Write};
+
+/// A co-signing session, in which a validator set signs a message until a threshold of
+/// signers is reached.
+///
+/// Incoming signatures are rejected if they are invalid or if their validator has already
+/// contributed one. Once the threshold is reached, the aggregate signature and the signed
+/// bitmap can be obtained with `finalize`. The session can be written to disk after each
+/// accepted signature with `write`, and restored after a crash with `read`.
+#[derive(Clone, Debug)]
+pub struct SigningSession {
+    signatures: PartialSignatureSet,
+}
+
+impl SigningSession {
+    /// Starts a session for the validators' public keys over the message-extra_data pair,
+    /// which is hashed with the `hash_to_g1` hasher under the `SIG_DOMAIN`. The validator
+    /// index of each signature is its public key's position in `public_keys`.
+    pub fn new<H: HashToCurve<Output = G1Projective>>(
+        public_keys: Vec<PublicKey>,
+        message: &[u8],
+        extra_data: &[u8],
+        hash_to_g1: &H,
+        threshold: usize,
+    ) -> BlsResult<Self> {
+        Ok(Self {
+            signatures: PartialSignatureSet::for_message(
+                public_keys,
+                message,
+                extra_data,
+                hash_to_g1,
+                threshold,
+            )?,
+        })
+    }
+
+    /// Verifies the signature of the validator at `index` and adds it to the session.
+    /// Returns whether the threshold has been reached.
+    ///
+    /// Fails with `BLSError::DuplicateSignature` if the validator has already contributed a
+    /// signature, and with the verification error if the signature is invalid.
+    pub fn add(&mut self, index: usize, signature: Signature) -> BlsResult<bool> {
+        if self.signatures.has_signed(index) {
+            return Err(BLSError::DuplicateSignature(index));
+        }
+        self.signatures.add(index, signature)
+    }
+
+    /// The number of validators which have contributed a valid signature
+    pub fn num_signers(&self) -> usize {
+        self.signatures.num_signers()
+    }
+
+    /// Whether the threshold has been reached
+    pub fn is_complete(&self) -> bool {
+        self.signatures.is_complete()
+    }
+
+    /// Returns the aggregate signature and the bitmap of the validators which contributed
+    /// to it, if the threshold has been reached
+    pub fn finalize(&self) -> Option<(Signature, Bitmap)> {
+        self.signatures
+            .finalize()
+            .map(|(bitmap, aggregate)| (aggregate, bitmap))
+    }
+
+    /// Serializes the public keys, the message hash, the threshold, and the index and
+    /// signature of each validator which has contributed one
+    pub fn write<W: Write>(&self, mut writer: W) -> BlsResult<()> {
+        let set = &self.signatures;
+        set.public_keys.serialize(&mut writer)?;
+        set.message_hash.into_affine().serialize(&mut writer)?;
+        (set.threshold as u64).serialize(&mut writer)?;
+
+        let contributed = set
+            .signatures
+            .iter()
+            .enumerate()
+            .filter_map(|(index, signature)| signature.as_ref().map(|s| (index, s)))
+            .collect::<Vec<_>>();
+        (contributed.len() as u64).serialize(&mut writer)?;
+        for (index, signature) in contributed {
+            (index as u64).serialize(&mut writer)?;
+            signature.serialize(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Restores a session written with `write`. The signatures are verified again, so that
+    /// a corrupted session cannot produce an invalid aggregate signature.
+    pub fn read<R: Read>(mut reader: R) -> BlsResult<Self> {
+        let public_keys = Vec::<PublicKey>::deserialize(&mut reader)?;
+        let message_hash = G1Affine::deserialize(&mut reader)?.into_projective();
+        let threshold = u64::deserialize(&mut reader)? as usize;
+        let mut session = Self {
+            signatures: PartialSignatureSet::new(public_keys, message_hash, threshold),
+        };
+
+        let num_contributed = u64::deserialize(&mut reader)?;
+        for _ in 0..num_contributed {
+            let index = u64::deserialize(&mut reader)? as usize;
+            let signature = Signature::deserialize(&mut reader)?;
+            session.add(index, signature)?;
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_to_curve::try_and_increment::DIRECT_HASH_TO_G1, PrivateKey};
+
+    #[test]
+    fn aggregates_at_threshold_and_recovers() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..4)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|k| k.to_public()).collect::<Vec<_>>();
+        let hasher = &*DIRECT_HASH_TO_G1;
+        let sign = |i: usize| keys[i].sign(b"hello", &[], hasher).unwrap();
+
+        let mut session =
+            SigningSession::new(public_keys.clone(), b"hello", &[], hasher, 3).unwrap();
+        assert!(!session.add(0, sign(0)).unwrap());
+        assert!(matches!(
+            session.add(0, sign(0)),
+            Err(BLSError::DuplicateSignature(0))
+        ));
+        assert!(session.add(1, sign(2)).is_err());
+        assert!(!session.add(2, sign(2)).unwrap());
+        assert!(session.finalize().is_none());
+
+        // the session survives a restart
+        let mut bytes = vec![];
+        session.write(&mut bytes).unwrap();
+        let mut session = SigningSession::read(&bytes[..]).unwrap();
+        assert_eq!(session.num_signers(), 2);
+
+        assert!(session.add(3, sign(3)).unwrap());
+        let (aggregate, bitmap) = session.finalize().unwrap();
+        assert_eq!(bitmap, Bitmap::from(vec![true, false, true, true]));
+        let signers = public_keys
+            .iter()
+            .zip(&bitmap)
+            .filter(|(_, signed)| **signed)
+            .map(|(pk, _)| pk);
+        PublicKey::aggregate(signers)
+            .verify(b"hello", &[], &aggregate, hasher)
+            .unwrap();
+
+        // a corrupted signature is rejected on recovery
+        let len = bytes.len();
+        bytes[len - 1] ^= 1;
+        assert!(SigningSession::read(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! Threshold keys
+//!
+//! `PrivateKey::split` shares a key among `n` participants with Shamir's scheme, so that any
+//! `threshold` of them can sign under the key's public key while fewer learn nothing about
+//! the key. The participants have indices 1 to `n`, and each `KeyShare` is the evaluation at
+//! the participant's index of a random polynomial of degree `threshold - 1` whose constant
+//! term is the key. The dealer publishes `ShareCommitments`, the polynomial's coefficients
+//! times the G2 generator (Feldman's scheme), against which each participant checks its
+//! share, and from which anyone derives the public key of each share to verify its
+//! signatures. `Signature::combine_shares` interpolates `threshold` signatures of shares into
+//! the signature of the key.
+//!
+//! # Proactive refresh
+//!
+//! Shares of long-lived keys are periodically re-randomized without changing the key, so
+//! that an attacker has to compromise `threshold` participants between two refreshes rather
+//! than over the lifetime of the key. Each participant deals a random polynomial of the same
+//! degree which vanishes at zero with `KeyShare::deal_refresh`: it broadcasts the
+//! `RefreshDealing`, i.e. the commitments to the polynomial's non-constant coefficients, and
+//! privately sends each participant a `ShareUpdate` with the evaluation at its index. The
+//! commitments are the proofs of validity of the updates: each recipient checks its updates
+//! against them before adding them to its share with `KeyShare::refresh`, and since they do
+//! not include a constant term, every dealt polynomial vanishes at zero. The refreshed shares
+//! thus interpolate to the same key, but cannot be combined with shares from before the
+//! refresh. Everyone updates the commitments with `ShareCommitments::refresh`.
+use super::{PrivateKey, PublicKey, SecretScalarMul, Signature};
+use crate::{BLSError, BlsResult, ValidationPolicy};
+
+use algebra::{
+    bls12_377::{Fr, G1Projective, G2Affine, G2Projective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, Field, One, ProjectiveCurve,
+    SerializationError, UniformRand, Zero,
+};
+use rand::Rng;
+use std::io::{Read, Write};
+
+/// A participant's share of a threshold key
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct KeyShare {
+    index: u64,
+    secret: Fr,
+}
+
+/// The commitments to the coefficients of the polynomial which a threshold key is shared
+/// with, starting with the constant term, i.e. the key's public key
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ShareCommitments(Vec<G2Affine>);
+
+/// A participant's contribution to a proactive refresh, i.e. the commitments to the
+/// non-constant coefficients of a polynomial which vanishes at zero
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct RefreshDealing {
+    dealer: u64,
+    commitments: Vec<G2Affine>,
+}
+
+/// The evaluation of a `RefreshDealing`'s polynomial at a participant's index, which the
+/// dealer sends to that participant only
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ShareUpdate {
+    dealer: u64,
+    recipient: u64,
+    value: Fr,
+}
+
+impl PrivateKey {
+    /// Shares the key among `participants` participants, with indices 1 to `participants`,
+    /// any `threshold` of which can sign under the key's public key. The shares are sent
+    /// privately to their participants, and the commitments are published.
+    pub fn split<R: Rng>(
+        &self,
+        threshold: usize,
+        participants: usize,
+        rng: &mut R,
+    ) -> BlsResult<(Vec<KeyShare>, ShareCommitments)> {
+        check_parameters(threshold, participants)?;
+        let mut coefficients = vec![*self.as_ref()];
+        coefficients.extend((1..threshold).map(|_| Fr::rand(rng)));
+        let shares = (1..=participants as u64)
+            .map(|index| KeyShare {
+                index,
+                secret: evaluate(&coefficients, index),
+            })
+            .collect();
+        Ok((shares, ShareCommitments(commit(&coefficients))))
+    }
+}
+
+impl KeyShare {
+    /// The index of the share's participant
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// The share as a private key, to sign with. Its signatures verify under `to_public`,
+    /// and are combined with `Signature::combine_shares`.
+    pub fn to_private(&self) -> PrivateKey {
+        PrivateKey::from(self.secret)
+    }
+
+    /// The public key of the share, which `ShareCommitments::public_share` also derives
+    pub fn to_public(&self) -> PublicKey {
+        self.to_private().to_public()
+    }
+
+    /// Checks that the share is the evaluation of the committed polynomial at its index
+    pub fn verify(&self, commitments: &ShareCommitments) -> BlsResult<()> {
+        if self.to_public() != commitments.public_share(self.index)? {
+            return Err(BLSError::InvalidKeyShare(
+                "the share does not match the commitments",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Deals this participant's contribution to a refresh of the shares of `participants`
+    /// participants, including its own, for a key shared with `threshold`. The dealing is
+    /// broadcast, and each update is sent privately to its recipient.
+    pub fn deal_refresh<R: Rng>(
+        &self,
+        threshold: usize,
+        participants: usize,
+        rng: &mut R,
+    ) -> BlsResult<(RefreshDealing, Vec<ShareUpdate>)> {
+        check_parameters(threshold, participants)?;
+        let mut coefficients = vec![Fr::zero()];
+        coefficients.extend((1..threshold).map(|_| Fr::rand(rng)));
+        let updates = (1..=participants as u64)
+            .map(|recipient| ShareUpdate {
+                dealer: self.index,
+                recipient,
+                value: evaluate(&coefficients, recipient),
+            })
+            .collect();
+        let dealing = RefreshDealing {
+            dealer: self.index,
+            commitments: commit(&coefficients[1..]),
+        };
+        Ok((dealing, updates))
+    }
+
+    /// Checks each update against the dealing of its dealer, and returns the share refreshed
+    /// with them. There must be one dealing for each update, in the same order. The dealings
+    /// must also be checked against the key's commitments, see `ShareCommitments::refresh`.
+    pub fn refresh(
+        &self,
+        dealings: &[RefreshDealing],
+        updates: &[ShareUpdate],
+    ) -> BlsResult<KeyShare> {
+        if dealings.len() != updates.len() {
+            return Err(BLSError::InvalidKeyShare(
+                "there must be one dealing for each update",
+            ));
+        }
+        let mut secret = self.secret;
+        for (dealing, update) in dealings.iter().zip(updates) {
+            if update.recipient != self.index {
+                return Err(BLSError::InvalidKeyShare(
+                    "the update was dealt to another participant",
+                ));
+            }
+            dealing.verify_update(update)?;
+            secret += &update.value;
+        }
+        Ok(KeyShare {
+            index: self.index,
+            secret,
+        })
+    }
+}
+
+impl ShareCommitments {
+    /// The number of shares needed to sign
+    pub fn threshold(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The public key of the shared key
+    pub fn public_key(&self) -> BlsResult<PublicKey> {
+        let constant = self
+            .0
+            .first()
+            .ok_or(BLSError::InvalidKeyShare("there are no commitments"))?;
+        Ok(PublicKey::from(constant.into_projective()))
+    }
+
+    /// The public key of the share at `index`, against which its signatures verify
+    pub fn public_share(&self, index: u64) -> BlsResult<PublicKey> {
+        if index == 0 {
+            return Err(BLSError::InvalidKeyShare("shares start at index 1"));
+        }
+        Ok(PublicKey::from(evaluate_commitments(&self.0, index)))
+    }
+
+    /// Checks the dealings of a refresh, and returns the commitments to the refreshed
+    /// shares. The public key is unchanged.
+    pub fn refresh(&self, dealings: &[RefreshDealing]) -> BlsResult<ShareCommitments> {
+        let mut commitments = self
+            .0
+            .iter()
+            .map(|commitment| commitment.into_projective())
+            .collect::<Vec<_>>();
+        for dealing in dealings {
+            dealing.check()?;
+            if dealing.commitments.len() + 1 != commitments.len() {
+                return Err(BLSError::InvalidKeyShare(
+                    "the dealing's polynomial does not have the degree of the key's",
+                ));
+            }
+            for (commitment, dealt) in commitments[1..].iter_mut().zip(&dealing.commitments) {
+                *commitment += &dealt.into_projective();
+            }
+        }
+        Ok(ShareCommitments(
+            commitments
+                .iter()
+                .map(|commitment| commitment.into_affine())
+                .collect(),
+        ))
+    }
+}
+
+impl RefreshDealing {
+    /// The index of the participant which dealt the refresh
+    pub fn dealer(&self) -> u64 {
+        self.dealer
+    }
+
+    /// Checks that the update is the evaluation of the dealt polynomial at its recipient's
+    /// index
+    pub fn verify_update(&self, update: &ShareUpdate) -> BlsResult<()> {
+        self.check()?;
+        if update.dealer != self.dealer {
+            return Err(BLSError::InvalidKeyShare(
+                "the update was dealt by another participant",
+            ));
+        }
+        if update.recipient == 0 {
+            return Err(BLSError::InvalidKeyShare("shares start at index 1"));
+        }
+        // the constant term of the dealt polynomial is zero
+        let expected = evaluate_commitments(&self.commitments, update.recipient)
+            .mul(Fr::from(update.recipient));
+        if G2Projective::prime_subgroup_generator().mul(update.value) != expected {
+            return Err(BLSError::InvalidKeyShare(
+                "the update does not match the dealing",
+            ));
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> BlsResult<()> {
+        for commitment in &self.commitments {
+            ValidationPolicy::CANONICAL.validate(commitment)?;
+        }
+        Ok(())
+    }
+}
+
+impl ShareUpdate {
+    /// The index of the participant which dealt the update
+    pub fn dealer(&self) -> u64 {
+        self.dealer
+    }
+
+    /// The index of the participant which the update was dealt to
+    pub fn recipient(&self) -> u64 {
+        self.recipient
+    }
+}
+
+impl Signature {
+    /// Combines the signatures of `threshold` distinct shares over the same message, each
+    /// given with the index of its share, into the signature of the shared key. The
+    /// signatures are not verified, which callers do against `ShareCommitments::public_share`.
+    pub fn combine_shares(shares: &[(u64, Signature)]) -> BlsResult<Signature> {
+        let indices = shares.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+        let mut combined = G1Projective::zero();
+        for (position, (index, signature)) in shares.iter().enumerate() {
+            if *index == 0 {
+                return Err(BLSError::InvalidKeyShare("shares start at index 1"));
+            }
+            if indices[..position].contains(index) {
+                return Err(BLSError::InvalidKeyShare("duplicate share"));
+            }
+            combined += &signature.as_ref().mul(lagrange_at_zero(&indices, *index));
+        }
+        Ok(Signature::from(combined))
+    }
+}
+
+fn check_parameters(threshold: usize, participants: usize) -> BlsResult<()> {
+    if threshold == 0 || threshold > participants {
+        return Err(BLSError::InvalidKeyShare(
+            "the threshold must be between 1 and the number of participants",
+        ));
+    }
+    Ok(())
+}
+
+/// Evaluates the polynomial with the coefficients, starting with the constant term, at `x`
+fn evaluate(coefficients: &[Fr], x: u64) -> Fr {
+    let x = Fr::from(x);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Fr::zero(), |acc, coefficient| acc * &x + coefficient)
+}
+
+/// Evaluates the committed polynomial at `x` in the exponent
+fn evaluate_commitments(commitments: &[G2Affine], x: u64) -> G2Projective {
+    let x = Fr::from(x);
+    commitments
+        .iter()
+        .rev()
+        .fold(G2Projective::zero(), |acc, commitment| {
+            acc.mul(x) + &commitment.into_projective()
+        })
+}
+
+/// Commits to each coefficient
+fn commit(coefficients: &[Fr]) -> Vec<G2Affine> {
+    let generator = G2Projective::prime_subgroup_generator();
+    coefficients
+        .iter()
+        .map(|coefficient| generator.mul_secret(coefficient).into_affine())
+        .collect()
+}
+
+/// The Lagrange coefficient of the share at `index` for interpolating the shares at
+/// `indices` at zero
+fn lagrange_at_zero(indices: &[u64], index: u64) -> Fr {
+    let x = Fr::from(index);
+    let (numerator, denominator) = indices
+        .iter()
+        .filter(|other| **other != index)
+        .map(|other| Fr::from(*other))
+        .fold((Fr::one(), Fr::one()), |(numerator, denominator), other| {
+            (numerator * &other, denominator * &(other - &x))
+        });
+    numerator
+        * &denominator
+            .inverse()
+            .expect("the indices are distinct, so the denominator is not zero")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    fn sign_with(shares: &[&KeyShare], message_hash: &G1Projective) -> Signature {
+        let signatures = shares
+            .iter()
+            .map(|share| (share.index(), share.to_private().sign_exact(message_hash)))
+            .collect::<Vec<_>>();
+        Signature::combine_shares(&signatures).unwrap()
+    }
+
+    #[test]
+    fn threshold_of_shares_signs() {
+        let rng = &mut thread_rng();
+        let key = PrivateKey::generate(rng);
+        let (shares, commitments) = key.split(3, 5, rng).unwrap();
+        assert_eq!(commitments.threshold(), 3);
+        assert_eq!(commitments.public_key().unwrap(), key.to_public());
+        for share in &shares {
+            share.verify(&commitments).unwrap();
+            assert_eq!(
+                commitments.public_share(share.index()).unwrap(),
+                share.to_public()
+            );
+        }
+
+        let message_hash = G1Projective::rand(rng);
+        let expected = key.sign_exact(&message_hash);
+        assert_eq!(
+            sign_with(&[&shares[0], &shares[2], &shares[4]], &message_hash),
+            expected
+        );
+        assert_eq!(
+            sign_with(&[&shares[3], &shares[1], &shares[2]], &message_hash),
+            expected
+        );
+        assert_ne!(
+            sign_with(&[&shares[0], &shares[1]], &message_hash),
+            expected
+        );
+
+        assert!(matches!(
+            key.split(6, 5, rng),
+            Err(BLSError::InvalidKeyShare(_))
+        ));
+        let signature = shares[0].to_private().sign_exact(&message_hash);
+        assert!(Signature::combine_shares(&[(1, signature.clone()), (1, signature)]).is_err());
+    }
+
+    #[test]
+    fn refreshes_shares_without_changing_the_key() {
+        let rng = &mut thread_rng();
+        let key = PrivateKey::generate(rng);
+        let (shares, commitments) = key.split(3, 4, rng).unwrap();
+
+        let (dealings, updates): (Vec<_>, Vec<_>) = shares
+            .iter()
+            .map(|share| share.deal_refresh(3, 4, rng).unwrap())
+            .unzip();
+        let refreshed_commitments = commitments.refresh(&dealings).unwrap();
+        assert_eq!(refreshed_commitments.public_key().unwrap(), key.to_public());
+        assert_ne!(refreshed_commitments, commitments);
+        let refreshed = shares
+            .iter()
+            .enumerate()
+            .map(|(position, share)| {
+                let received = updates
+                    .iter()
+                    .map(|dealt| dealt[position].clone())
+                    .collect::<Vec<_>>();
+                share.refresh(&dealings, &received).unwrap()
+            })
+            .collect::<Vec<_>>();
+        for (share, old) in refreshed.iter().zip(&shares) {
+            share.verify(&refreshed_commitments).unwrap();
+            assert!(share.verify(&commitments).is_err());
+            assert_ne!(share, old);
+        }
+
+        let message_hash = G1Projective::rand(rng);
+        let expected = key.sign_exact(&message_hash);
+        assert_eq!(
+            sign_with(
+                &[&refreshed[0], &refreshed[1], &refreshed[3]],
+                &message_hash
+            ),
+            expected
+        );
+        // shares from before the refresh do not combine with the refreshed ones
+        assert_ne!(
+            sign_with(&[&shares[0], &refreshed[1], &refreshed[3]], &message_hash),
+            expected
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_refreshes() {
+        let rng = &mut thread_rng();
+        let key = PrivateKey::generate(rng);
+        let (shares, commitments) = key.split(2, 3, rng).unwrap();
+        let (dealing, updates) = shares[0].deal_refresh(2, 3, rng).unwrap();
+        dealing.verify_update(&updates[1]).unwrap();
+
+        // a tampered update fails its validity check
+        let mut tampered = updates[1].clone();
+        tampered.value += &Fr::one();
+        assert!(matches!(
+            shares[1].refresh(&[dealing.clone()], &[tampered]),
+            Err(BLSError::InvalidKeyShare(
+                "the update does not match the dealing"
+            ))
+        ));
+        // so does an update for another participant
+        assert!(shares[1]
+            .refresh(&[dealing.clone()], &[updates[2].clone()])
+            .is_err());
+
+        // a dealing of a higher degree would raise the threshold
+        let (higher, _) = shares[0].deal_refresh(3, 3, rng).unwrap();
+        assert!(matches!(
+            commitments.refresh(&[higher]),
+            Err(BLSError::InvalidKeyShare(_))
+        ));
+        commitments.refresh(&[dealing]).unwrap();
+    }
+}
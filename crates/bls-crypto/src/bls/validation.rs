@@ -0,0 +1,279 @@
+//! Policies for validating the curve points of keys and signatures
+//!
+//! Points received from untrusted sources must be checked to be in the prime order subgroup,
+//! and usually to not be the identity, before they are used. The subgroup check costs a scalar
+//! multiplication, which performance-sensitive paths may want to skip for points which were
+//! already validated, e.g. the keys of a validator set validated when it was registered. A
+//! `ValidationPolicy` makes that choice explicit at each call site.
+//!
+//! Keys and signatures are deserialized with `ValidationPolicy::CANONICAL`. They are verified
+//! with the default `ValidationPolicy::UNCHECKED`, as they were before policies existed, so the
+//! stricter checks are opt-in through the `_with_policy` variants.
+use crate::{BLSError, BlsResult};
+
+use algebra::{
+    curves::models::{short_weierstrass_jacobian::GroupAffine, SWModelParameters},
+    AffineCurve, CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize, SWFlags,
+    SerializationError, Zero,
+};
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use std::{io::Read, sync::Mutex};
+
+/// The number of points which `SubgroupCheck::Cached` remembers to be in the subgroup
+const SUBGROUP_CACHE_SIZE: usize = 4096;
+
+/// The compressed encodings of the points which passed the subgroup check. The encodings of
+/// G1 and G2 points have different lengths, so they can share the cache.
+static SUBGROUP_CACHE: Lazy<Mutex<LruCache<Vec<u8>, ()>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(SUBGROUP_CACHE_SIZE)));
+
+/// Whether points are checked to be in the prime order subgroup
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubgroupCheck {
+    /// Every point is checked
+    Always,
+    /// Points are checked once, and the points which passed are remembered in a process-wide
+    /// cache of bounded size
+    Cached,
+    /// Points are trusted to be in the subgroup. Only use this for points which were
+    /// validated before, since signing or verifying with points outside the subgroup is unsafe.
+    Never,
+}
+
+/// How to validate the curve points of keys and signatures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// Whether points are checked to be in the prime order subgroup
+    pub subgroup_check: SubgroupCheck,
+    /// Whether the identity is accepted
+    pub allow_infinity: bool,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self::UNCHECKED
+    }
+}
+
+impl ValidationPolicy {
+    /// Checks every point, and rejects the identity
+    pub const STRICT: Self = Self {
+        subgroup_check: SubgroupCheck::Always,
+        allow_infinity: false,
+    };
+
+    /// Checks each point once, and rejects the identity
+    pub const CACHED: Self = Self {
+        subgroup_check: SubgroupCheck::Cached,
+        allow_infinity: false,
+    };
+
+    /// Skips the subgroup check of points which were validated before, but still rejects
+    /// the identity since that check is cheap
+    pub const PREVALIDATED: Self = Self {
+        subgroup_check: SubgroupCheck::Never,
+        allow_infinity: false,
+    };
+
+    /// Performs no checks. This is the default, since verification did not validate keys and
+    /// signatures before policies existed; their deserialization already checks the subgroup.
+    pub const UNCHECKED: Self = Self {
+        subgroup_check: SubgroupCheck::Never,
+        allow_infinity: true,
+    };
+
+    /// Checks every point and accepts the identity, as `CanonicalDeserialize` does. This is
+    /// how keys and signatures are deserialized by default, since an aggregate may be the
+    /// identity; verifying with the identity is still rejected by `STRICT`.
+    pub const CANONICAL: Self = Self {
+        subgroup_check: SubgroupCheck::Always,
+        allow_infinity: true,
+    };
+
+    /// Validates a point which is known to be on the curve
+    pub fn validate<P: SWModelParameters>(&self, point: &GroupAffine<P>) -> BlsResult<()> {
+        if point.is_zero() {
+            return if self.allow_infinity {
+                Ok(())
+            } else {
+                Err(BLSError::UnexpectedInfinity)
+            };
+        }
+        let in_subgroup = match self.subgroup_check {
+            SubgroupCheck::Always => point.is_in_correct_subgroup_assuming_on_curve(),
+            SubgroupCheck::Cached => {
+                let mut encoding = Vec::with_capacity(point.serialized_size());
+                point.serialize(&mut encoding)?;
+                if SUBGROUP_CACHE
+                    .lock()
+                    .expect("mutex poisoned")
+                    .get(&encoding)
+                    .is_some()
+                {
+                    true
+                } else {
+                    // the lock is not held during the check, so that concurrent callers are not
+                    // serialized behind it
+                    let in_subgroup = point.is_in_correct_subgroup_assuming_on_curve();
+                    if in_subgroup {
+                        SUBGROUP_CACHE
+                            .lock()
+                            .expect("mutex poisoned")
+                            .put(encoding, ());
+                    }
+                    in_subgroup
+                }
+            }
+            SubgroupCheck::Never => true,
+        };
+        if in_subgroup {
+            Ok(())
+        } else {
+            Err(BLSError::NotInSubgroup)
+        }
+    }
+
+    /// Reads a compressed point as serialized by `CanonicalSerialize`, and validates it. Unlike
+    /// `CanonicalDeserialize`, which always runs the subgroup check, the check is only run as
+    /// the policy requires. Decompressing always ensures that the point is on the curve.
+    pub fn read_point<P: SWModelParameters, R: Read>(
+        &self,
+        reader: R,
+    ) -> BlsResult<GroupAffine<P>> {
+        let (x, flags): (P::BaseField, SWFlags) =
+            CanonicalDeserializeWithFlags::deserialize_with_flags(reader)?;
+        let point = if flags.is_infinity() {
+            GroupAffine::zero()
+        } else {
+            let greatest = flags.is_positive().ok_or(SerializationError::InvalidData)?;
+            GroupAffine::get_point_from_x(x, greatest).ok_or(SerializationError::InvalidData)?
+        };
+        self.validate(&point)?;
+        Ok(point)
+    }
+
+    /// Reads an uncompressed point as serialized by `CanonicalSerialize`, checks that it is
+    /// on the curve and validates it as the policy requires
+    pub fn read_point_uncompressed<P: SWModelParameters, R: Read>(
+        &self,
+        reader: R,
+    ) -> BlsResult<GroupAffine<P>> {
+        let point = GroupAffine::<P>::deserialize_unchecked(reader)?;
+        if !point.is_zero() && !point.is_on_curve() {
+            return Err(SerializationError::InvalidData.into());
+        }
+        self.validate(&point)?;
+        Ok(point)
+    }
+}
+
+/// Converts an error of reading a point with a policy for `CanonicalDeserialize`, which
+/// reports invalid points as invalid data
+pub(crate) fn into_serialization_error(error: BLSError) -> SerializationError {
+    match error {
+        BLSError::SerializationError(error) => error,
+        _ => SerializationError::InvalidData,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{
+        bls12_377::{G1Affine, G1Projective, G2Affine, G2Projective},
+        ProjectiveCurve, UniformRand,
+    };
+
+    /// A point of G1 outside the prime order subgroup
+    fn point_outside_subgroup() -> G1Affine {
+        let rng = &mut rand::thread_rng();
+        loop {
+            let x = UniformRand::rand(rng);
+            if let Some(point) = G1Affine::get_point_from_x(x, false) {
+                if !point.is_in_correct_subgroup_assuming_on_curve() {
+                    return point;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn applies_policies() {
+        let rng = &mut rand::thread_rng();
+        let point = G2Projective::rand(rng).into_affine();
+        let outside = point_outside_subgroup();
+        let identity = G1Affine::zero();
+
+        for policy in &[ValidationPolicy::STRICT, ValidationPolicy::CACHED] {
+            policy.validate(&point).unwrap();
+            // a second pass hits the cache
+            policy.validate(&point).unwrap();
+            assert!(matches!(
+                policy.validate(&outside),
+                Err(BLSError::NotInSubgroup)
+            ));
+            assert!(matches!(
+                policy.validate(&identity),
+                Err(BLSError::UnexpectedInfinity)
+            ));
+        }
+
+        ValidationPolicy::PREVALIDATED.validate(&outside).unwrap();
+        assert!(ValidationPolicy::PREVALIDATED.validate(&identity).is_err());
+        let lenient = ValidationPolicy {
+            allow_infinity: true,
+            ..ValidationPolicy::STRICT
+        };
+        lenient.validate(&identity).unwrap();
+        assert!(lenient.validate(&outside).is_err());
+
+        // the default keeps the checks opt-in
+        assert_eq!(ValidationPolicy::default(), ValidationPolicy::UNCHECKED);
+        ValidationPolicy::UNCHECKED.validate(&outside).unwrap();
+        ValidationPolicy::UNCHECKED.validate(&identity).unwrap();
+    }
+
+    #[test]
+    fn reads_points() {
+        let rng = &mut rand::thread_rng();
+        let point = G1Projective::rand(rng).into_affine();
+        let mut bytes = vec![];
+        point.serialize(&mut bytes).unwrap();
+        for policy in &[
+            ValidationPolicy::STRICT,
+            ValidationPolicy::CACHED,
+            ValidationPolicy::PREVALIDATED,
+        ] {
+            let read: G1Affine = policy.read_point(&bytes[..]).unwrap();
+            assert_eq!(read, point);
+        }
+
+        let outside = point_outside_subgroup();
+        let mut bytes = vec![];
+        outside.serialize(&mut bytes).unwrap();
+        let read: BlsResult<G1Affine> = ValidationPolicy::STRICT.read_point(&bytes[..]);
+        assert!(matches!(read, Err(BLSError::NotInSubgroup)));
+        let read: G1Affine = ValidationPolicy::PREVALIDATED
+            .read_point(&bytes[..])
+            .unwrap();
+        assert_eq!(read, outside);
+
+        let mut bytes = vec![];
+        outside.serialize_uncompressed(&mut bytes).unwrap();
+        let read: BlsResult<G1Affine> =
+            ValidationPolicy::CANONICAL.read_point_uncompressed(&bytes[..]);
+        assert!(matches!(read, Err(BLSError::NotInSubgroup)));
+        let read: G1Affine = ValidationPolicy::PREVALIDATED
+            .read_point_uncompressed(&bytes[..])
+            .unwrap();
+        assert_eq!(read, outside);
+
+        let mut bytes = vec![];
+        G2Affine::zero().serialize(&mut bytes).unwrap();
+        let read: BlsResult<G2Affine> = ValidationPolicy::STRICT.read_point(&bytes[..]);
+        assert!(matches!(read, Err(BLSError::UnexpectedInfinity)));
+        let read: G2Affine = ValidationPolicy::CANONICAL.read_point(&bytes[..]).unwrap();
+        assert!(read.is_zero());
+    }
+}
@@ -0,0 +1,74 @@
+//! Pinned signatures which must remain byte-for-byte stable across crate versions.
+//!
+//! BLS signing is deterministic, so a signature only depends on the private key and the
+//! message's hash to G1. The hashes are pinned by the hash to curve test vectors, and these
+//! vectors pin signing over them via `PrivateKey::sign_exact`. The message hashes below are
+//! the first 3 CIP22 hash to curve test vectors.
+use crate::{PrivateKey, Signature};
+
+use algebra::{
+    bls12_377::{Fr, G1Affine},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize,
+};
+
+/// (message hash, private key, expected signature), with points serialized in compressed form
+const VECTORS: &[(&str, u64, &str)] = &[
+    (
+        "c24b44bf3aef0949a25f614a89fd20e457b89e4c5d63923b7a63748443275ad47210e7fb8eff38d5582e7d301ee1d400",
+        1,
+        "c24b44bf3aef0949a25f614a89fd20e457b89e4c5d63923b7a63748443275ad47210e7fb8eff38d5582e7d301ee1d400",
+    ),
+    (
+        "c24b44bf3aef0949a25f614a89fd20e457b89e4c5d63923b7a63748443275ad47210e7fb8eff38d5582e7d301ee1d400",
+        42,
+        "6e3af92623d60decd1083eb6a5726c6268abf26d91bdd508d7f2eb27f0ccf176fd7c2ee9dc95590313f4e45eed1bfd80",
+    ),
+    (
+        "c24b44bf3aef0949a25f614a89fd20e457b89e4c5d63923b7a63748443275ad47210e7fb8eff38d5582e7d301ee1d400",
+        0xdead_beef_cafe_babe,
+        "80366e718b0d67965bbd0118da1914bdc665983a3c4a243ee0a97fad3ed24cced3923dbabfe63f5e53b00ce768f62401",
+    ),
+    (
+        "30caf0778a1d5a30f53c42cc58bbf0b0b9ec0c969e01d805b47f0d556025dbd395af2a506cc6fda3c41e361290c76d01",
+        42,
+        "4436a4a89b82f64c464f45e0f003369781b780bb1b98879b9adc3ac156503e090ceac4d8f0e52f0b874b4dfc29132480",
+    ),
+    (
+        "30caf0778a1d5a30f53c42cc58bbf0b0b9ec0c969e01d805b47f0d556025dbd395af2a506cc6fda3c41e361290c76d01",
+        0xdead_beef_cafe_babe,
+        "bebfb3295bb574002a9170e23cf4575c935124fb7a76d0510e4345a1e44e4e23d3af67605b6086ef9ae9273a9e28ab01",
+    ),
+    (
+        "d9a4b28b8159977581a16ccfa69d1e93b220ccfeafa90a391cbc93c5beedd89953a8df8dcd99be80620fba1b5a191281",
+        42,
+        "dc93429a9be1c4583048fd9167c22b615766b48b528be31558837cd262b576881847910c134abd0cb7464b55ef832180",
+    ),
+    (
+        "d9a4b28b8159977581a16ccfa69d1e93b220ccfeafa90a391cbc93c5beedd89953a8df8dcd99be80620fba1b5a191281",
+        0xdead_beef_cafe_babe,
+        "60d903116099fb9cff810cdaee8e77d06b92ace14725a97c705d43756571c171fe620ac38510e56fc20b82cdf8b60f01",
+    ),
+];
+
+#[test]
+fn sign_exact_vectors() {
+    for (message_hash, private_key, expected) in VECTORS {
+        let message_hash = G1Affine::deserialize(&hex::decode(message_hash).unwrap()[..])
+            .unwrap()
+            .into_projective();
+        let private_key = PrivateKey::from(Fr::from(*private_key));
+
+        let signature = private_key.sign_exact(&message_hash);
+        let mut bytes = vec![];
+        signature.serialize(&mut bytes).unwrap();
+        assert_eq!(hex::encode(&bytes), *expected);
+
+        // the signature must verify against the public key
+        let expected = Signature::deserialize(&hex::decode(expected).unwrap()[..]).unwrap();
+        assert_eq!(signature, expected);
+        private_key
+            .to_public()
+            .verify_exact(&message_hash, &signature)
+            .unwrap();
+    }
+}
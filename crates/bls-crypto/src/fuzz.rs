@@ -0,0 +1,90 @@
+//! Structured fuzzing support
+//!
+//! Provides `Arbitrary` implementations which always produce valid group elements, so that
+//! fuzzers spend their time past the deserialization checks, along with entrypoints for the
+//! deserializers which take untrusted input.
+use crate::{PrivateKey, PublicKey, Signature};
+
+use algebra::{
+    bls12_377::{Fr, G1Projective, G2Projective},
+    CanonicalDeserialize, CanonicalSerialize, ProjectiveCurve,
+};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+impl Arbitrary for PrivateKey {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Result<Self> {
+        Ok(PrivateKey::from(Fr::from(u64::arbitrary(u)?)))
+    }
+}
+
+impl Arbitrary for PublicKey {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Result<Self> {
+        let scalar = Fr::from(u64::arbitrary(u)?);
+        Ok(PublicKey::from(
+            G2Projective::prime_subgroup_generator().mul(scalar),
+        ))
+    }
+}
+
+impl Arbitrary for Signature {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Result<Self> {
+        let scalar = Fr::from(u64::arbitrary(u)?);
+        Ok(Signature::from(
+            G1Projective::prime_subgroup_generator().mul(scalar),
+        ))
+    }
+}
+
+/// Deserializes `data` as `T` in both compressed and uncompressed form. Any value which
+/// deserializes successfully must serialize back to the same bytes.
+fn roundtrip<T: CanonicalSerialize + CanonicalDeserialize>(data: &[u8]) {
+    if let Ok(value) = T::deserialize(data) {
+        let mut serialized = vec![];
+        value.serialize(&mut serialized).unwrap();
+        assert_eq!(&data[..serialized.len()], &serialized[..]);
+    }
+    if let Ok(value) = T::deserialize_uncompressed(data) {
+        let mut serialized = vec![];
+        value.serialize_uncompressed(&mut serialized).unwrap();
+        assert_eq!(&data[..serialized.len()], &serialized[..]);
+    }
+}
+
+/// Fuzz entrypoint for private key deserialization
+pub fn deserialize_private_key(data: &[u8]) {
+    let _ = PrivateKey::deserialize(data);
+}
+
+/// Fuzz entrypoint for public key deserialization
+pub fn deserialize_public_key(data: &[u8]) {
+    roundtrip::<PublicKey>(data);
+}
+
+/// Fuzz entrypoint for signature deserialization
+pub fn deserialize_signature(data: &[u8]) {
+    roundtrip::<Signature>(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbitrary_points_roundtrip() {
+        let data = (0..=255u8).collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        let public_key = PublicKey::arbitrary(&mut u).unwrap();
+        let signature = Signature::arbitrary(&mut u).unwrap();
+
+        let mut bytes = vec![];
+        public_key.serialize(&mut bytes).unwrap();
+        deserialize_public_key(&bytes);
+        let mut bytes = vec![];
+        signature.serialize(&mut bytes).unwrap();
+        deserialize_signature(&bytes);
+
+        deserialize_public_key(&data);
+        deserialize_signature(&data);
+        deserialize_private_key(&data);
+    }
+}
@@ -5,6 +5,8 @@
 /// as an elliptic curve point, it returns. If not, it increments the counter and tries again.
 ///
 /// **This algorithm is not constant time**.
+/// For a constant-time alternative, see the [`sw`](sw/index.html) module, which implements the
+/// Shallue-van de Woestijne map to G1.
 ///
 /// # Examples
 ///
@@ -41,6 +43,7 @@
 /// let (hash, counter) = hasher.hash_with_attempt(OUT_DOMAIN, &b"some_data"[..], &b"extra"[..]).expect("should not fail");
 /// assert_eq!(counter, 3);
 /// ```
+pub mod sw;
 pub mod try_and_increment;
 pub mod try_and_increment_cip22;
 
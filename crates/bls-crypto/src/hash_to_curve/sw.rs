@@ -0,0 +1,304 @@
+//! Constant-time hashing to BLS12-377's G1 with the Shallue-van de Woestijne map, as
+//! specified for curves of the form `y^2 = x^3 + b` by Fouque and Tibouchi in
+//! [Indifferentiable Hashing to Barreto-Naehrig Curves](https://www.di.ens.fr/~fouque/pub/latincrypt12.pdf).
+//!
+//! The message is compressed with the hasher's CRH, and the XOF of the result is interpreted as
+//! two field elements `t1, t2`. The hash is `(f(t1) + f(t2)) * cofactor`, where `f` is the SW map.
+//!
+//! Unlike try-and-increment, the sequence of hashing and field operations does not depend on the
+//! message: all the candidate points are always computed and the result is selected
+//! arithmetically, while square roots and inversions use fixed exponentiations. Note that the
+//! underlying field arithmetic is Zexe's, which has not been audited for constant-time execution.
+use super::{hash_length, HashToCurve};
+use crate::hashers::{
+    composite::{CompositeHasher, COMPOSITE_HASHER, CRH},
+    Hasher,
+};
+use crate::BLSError;
+
+use algebra::{
+    bls12_377::{Fq, FqParameters, G1Affine, G1Projective},
+    AffineCurve, BigInteger, Field, FpParameters, One, PrimeField, ProjectiveCurve, Zero,
+};
+use once_cell::sync::Lazy;
+
+/// The number of XOF bytes which are interpreted as each of the two field elements. Only the
+/// first `CAPACITY` bits are used, so that the element is always smaller than the modulus.
+pub const FIELD_ELEMENT_BYTES: usize = 48;
+
+/// Composite (Bowe-Hopwood CRH, Blake2x XOF) SW map hasher for BLS 12-377.
+pub static COMPOSITE_SW_HASH_TO_G1: Lazy<SWHashToCurve<CompositeHasher<CRH>>> =
+    Lazy::new(|| SWHashToCurve::new(&*COMPOSITE_HASHER));
+
+/// Constants of the SW map and of the constant-time square root
+struct Constants {
+    /// The square root of -3 which is smaller than (p - 1) / 2
+    sqrt_minus_three: Fq,
+    /// (sqrt(-3) - 1) / 2
+    cube_root_of_unity: Fq,
+    /// The smallest quadratic non-residue
+    non_residue: Fq,
+    /// (p - 1) / 2
+    modulus_minus_one_div_two: <Fq as PrimeField>::BigInt,
+    /// p - 2
+    modulus_minus_two: <Fq as PrimeField>::BigInt,
+    /// The largest `s` such that 2^s divides p - 1
+    two_adicity: u32,
+    /// (t - 1) / 2, where p - 1 = t * 2^s
+    t_minus_one_div_two: <Fq as PrimeField>::BigInt,
+    /// non_residue^t
+    root_of_unity: Fq,
+}
+
+static CONSTANTS: Lazy<Constants> = Lazy::new(|| {
+    let modulus_minus_one_div_two = Fq::modulus_minus_one_div_two();
+
+    let mut modulus_minus_two = modulus_minus_one_div_two;
+    modulus_minus_two.mul2();
+    modulus_minus_two.sub_noborrow(&1u64.into());
+
+    let mut t = modulus_minus_one_div_two;
+    let mut two_adicity = 1;
+    while t.is_even() {
+        t.div2();
+        two_adicity += 1;
+    }
+    let mut t_minus_one_div_two = t;
+    t_minus_one_div_two.div2();
+
+    let non_residue = (2u64..)
+        .map(Fq::from)
+        .find(|x| x.pow(modulus_minus_one_div_two) != -Fq::one())
+        .expect("half of the field elements are non-residues");
+    let root_of_unity = non_residue.pow(t);
+
+    let mut constants = Constants {
+        sqrt_minus_three: Fq::zero(),
+        cube_root_of_unity: Fq::zero(),
+        non_residue,
+        modulus_minus_one_div_two,
+        modulus_minus_two,
+        two_adicity,
+        t_minus_one_div_two,
+        root_of_unity,
+    };
+    let sqrt_minus_three = sqrt(&constants, &-Fq::from(3u64)).expect("-3 is a square in Fq");
+    constants.sqrt_minus_three =
+        select(sgn0(&sqrt_minus_three), -sqrt_minus_three, sqrt_minus_three);
+    constants.cube_root_of_unity =
+        (constants.sqrt_minus_three - Fq::one()) * inverse(&constants, &Fq::from(2u64));
+    constants
+});
+
+/// Returns the square root of -3 used by the SW map
+pub fn sqrt_minus_three() -> Fq {
+    CONSTANTS.sqrt_minus_three
+}
+
+/// Returns the cube root of unity `(sqrt(-3) - 1) / 2` used by the SW map
+pub fn cube_root_of_unity() -> Fq {
+    CONSTANTS.cube_root_of_unity
+}
+
+/// Returns the quadratic non-residue used to prove that an element is not a square
+pub fn non_residue() -> Fq {
+    CONSTANTS.non_residue
+}
+
+/// Returns true if the element is a square in Fq (including zero)
+pub fn is_square(x: &Fq) -> bool {
+    x.pow(CONSTANTS.modulus_minus_one_div_two) != -Fq::one()
+}
+
+/// Returns the "sign" of the element, i.e. whether it is larger than (p - 1) / 2. This is the
+/// same convention as the one used for the y coordinate in point compression.
+pub fn sgn0(x: &Fq) -> bool {
+    x.into_repr() > Fq::modulus_minus_one_div_two()
+}
+
+/// Maps the field element to a point on the curve (which is not necessarily in the prime
+/// order subgroup). The sign of the point's y coordinate is the sign of `t`.
+///
+/// Fails if `t` is zero or `t^2 = -2`, which happens with negligible probability for
+/// hashed inputs.
+pub fn map_to_curve(t: &Fq) -> Result<G1Projective, BLSError> {
+    let constants = &*CONSTANTS;
+    // 1 + b + t^2, where b = 1
+    let denominator = t.square() + Fq::from(2u64);
+    if t.is_zero() || denominator.is_zero() {
+        return Err(BLSError::HashToCurveError);
+    }
+
+    let w = constants.sqrt_minus_three * t * inverse(constants, &denominator);
+    let x1 = constants.cube_root_of_unity - *t * w;
+    let x2 = -Fq::one() - x1;
+    let x3 = Fq::one() + inverse(constants, &w.square());
+
+    // at least one of the candidates is the x coordinate of a point on the curve
+    let x = select(
+        is_square(&curve_equation(&x1)),
+        x1,
+        select(is_square(&curve_equation(&x2)), x2, x3),
+    );
+    let y = sqrt(constants, &curve_equation(&x)).ok_or(BLSError::HashToCurveError)?;
+    let y = select(sgn0(&y) == sgn0(t), y, -y);
+
+    Ok(G1Affine::new(x, y, false).into_projective())
+}
+
+/// Interprets the first `CAPACITY` bits of the little endian bytes as a field element
+pub fn to_field_element(bytes: &[u8]) -> Fq {
+    let mut bits = bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .take(FqParameters::CAPACITY as usize)
+        .collect::<Vec<_>>();
+    // `BigInt::from_bits` takes big endian bits
+    bits.reverse();
+    Fq::from_repr(<Fq as PrimeField>::BigInt::from_bits(&bits))
+        .expect("elements with CAPACITY bits are smaller than the modulus")
+}
+
+/// A hasher to BLS12-377's G1 which uses the SW map instead of try-and-increment
+#[derive(Clone)]
+pub struct SWHashToCurve<'a, H> {
+    hasher: &'a H,
+}
+
+impl<'a, H: Hasher<Error = BLSError>> SWHashToCurve<'a, H> {
+    /// Instantiates a new SW map hasher with the provided hashing method
+    pub fn new(hasher: &'a H) -> Self {
+        SWHashToCurve { hasher }
+    }
+
+    /// Returns the two field elements which are mapped to the curve for the provided input
+    pub fn hash_to_field(
+        &self,
+        domain: &[u8],
+        message: &[u8],
+        extra_data: &[u8],
+    ) -> Result<(Fq, Fq), BLSError> {
+        let inner_hash = self
+            .hasher
+            .crh(domain, message, hash_length(FIELD_ELEMENT_BYTES))?;
+        let xof = self.hasher.xof(
+            domain,
+            &[extra_data, &inner_hash].concat(),
+            2 * FIELD_ELEMENT_BYTES,
+        )?;
+        Ok((
+            to_field_element(&xof[..FIELD_ELEMENT_BYTES]),
+            to_field_element(&xof[FIELD_ELEMENT_BYTES..]),
+        ))
+    }
+}
+
+impl<'a, H: Hasher<Error = BLSError>> HashToCurve for SWHashToCurve<'a, H> {
+    type Output = G1Projective;
+
+    fn hash(
+        &self,
+        domain: &[u8],
+        message: &[u8],
+        extra_data: &[u8],
+    ) -> Result<Self::Output, BLSError> {
+        let (t1, t2) = self.hash_to_field(domain, message, extra_data)?;
+        let point = map_to_curve(&t1)? + map_to_curve(&t2)?;
+        let scaled = point.into_affine().scale_by_cofactor();
+        if scaled.is_zero() {
+            return Err(BLSError::HashToCurveError);
+        }
+        Ok(scaled)
+    }
+}
+
+fn curve_equation(x: &Fq) -> Fq {
+    x.square() * x + Fq::one()
+}
+
+/// Returns `a` if `condition` is set, else `b`, without branching on the condition
+fn select(condition: bool, a: Fq, b: Fq) -> Fq {
+    b + Fq::from(condition as u64) * (a - b)
+}
+
+/// Inversion via Fermat's little theorem, which returns 0 for 0
+fn inverse(constants: &Constants, x: &Fq) -> Fq {
+    x.pow(constants.modulus_minus_two)
+}
+
+/// Constant-time Tonelli-Shanks, as described in Appendix I.4 of the
+/// [hash-to-curve draft](https://tools.ietf.org/html/draft-irtf-cfrg-hash-to-curve-10#appendix-I.4)
+fn sqrt(constants: &Constants, x: &Fq) -> Option<Fq> {
+    let mut z = x.pow(constants.t_minus_one_div_two);
+    let mut t = z.square() * x;
+    z *= x;
+    let mut b = t;
+    let mut c = constants.root_of_unity;
+    for i in (2..=constants.two_adicity).rev() {
+        for _ in 0..i - 2 {
+            b.square_in_place();
+        }
+        let is_one = b.is_one();
+        z = select(is_one, z, z * c);
+        c.square_in_place();
+        t = select(is_one, t, t * c);
+        b = t;
+    }
+
+    if z.square() == *x {
+        Some(z)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashers::DirectHasher;
+    use algebra::UniformRand;
+    use rand::RngCore;
+
+    #[test]
+    fn constant_time_sqrt() {
+        let rng = &mut rand::thread_rng();
+        for _ in 0..20 {
+            let x = Fq::rand(rng);
+            let square = x.square();
+            let root = sqrt(&CONSTANTS, &square).unwrap();
+            assert!(root == x || root == -x);
+            assert!(is_square(&square));
+            assert!(sqrt(&CONSTANTS, &(square * non_residue())).is_none());
+        }
+        assert_eq!(sqrt(&CONSTANTS, &Fq::zero()), Some(Fq::zero()));
+        assert_eq!(sqrt_minus_three().square(), -Fq::from(3u64));
+        assert!(!sgn0(&sqrt_minus_three()));
+    }
+
+    #[test]
+    fn maps_to_curve() {
+        let rng = &mut rand::thread_rng();
+        for _ in 0..20 {
+            let t = Fq::rand(rng);
+            let point = map_to_curve(&t).unwrap().into_affine();
+            assert!(point.is_on_curve());
+            assert_eq!(sgn0(&point.y), sgn0(&t));
+        }
+        assert!(map_to_curve(&Fq::zero()).is_err());
+    }
+
+    #[test]
+    fn hashes_to_subgroup() {
+        let hasher = SWHashToCurve::new(&DirectHasher);
+        let rng = &mut rand::thread_rng();
+        let mut message = vec![0; 64];
+        rng.fill_bytes(&mut message);
+
+        let hash = hasher.hash(b"domain", &message, b"extra").unwrap();
+        assert_eq!(hash, hasher.hash(b"domain", &message, b"extra").unwrap());
+        assert!(hash
+            .into_affine()
+            .is_in_correct_subgroup_assuming_on_curve());
+        assert_ne!(hash, hasher.hash(b"domain", &message, b"other").unwrap());
+    }
+}
@@ -33,6 +33,18 @@ pub static DIRECT_HASH_TO_G1: Lazy<
     TryAndIncrement<DirectHasher, <Parameters as Bls12Parameters>::G1Parameters>,
 > = Lazy::new(|| TryAndIncrement::new(&DirectHasher));
 
+/// Composite (Bowe-Hopwood CRH, Blake2x XOF) Try-and-Increment hasher to G2 for BLS 12-377.
+/// Used for signatures in the `MinPk` scheme.
+pub static COMPOSITE_HASH_TO_G2: Lazy<
+    TryAndIncrement<CompositeHasher<CRH>, <Parameters as Bls12Parameters>::G2Parameters>,
+> = Lazy::new(|| TryAndIncrement::new(&*COMPOSITE_HASHER));
+
+/// Direct (Blake2s CRH, Blake2x XOF) Try-and-Increment hasher to G2 for BLS 12-377.
+/// Used for signatures in the `MinPk` scheme.
+pub static DIRECT_HASH_TO_G2: Lazy<
+    TryAndIncrement<DirectHasher, <Parameters as Bls12Parameters>::G2Parameters>,
+> = Lazy::new(|| TryAndIncrement::new(&DirectHasher));
+
 /// A try-and-increment method for hashing to G1 and G2. See page 521 in
 /// https://link.springer.com/content/pdf/10.1007/3-540-45682-1_30.pdf.
 #[derive(Clone)]
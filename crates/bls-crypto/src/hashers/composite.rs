@@ -4,14 +4,15 @@
 use crate::{hashers::DirectHasher, BLSError, Hasher};
 
 use algebra::{
-    ed_on_bw6_761::{EdwardsParameters, EdwardsProjective},
-    CanonicalSerialize, ProjectiveCurve,
+    ed_on_bw6_761::{EdwardsAffine, EdwardsParameters, EdwardsProjective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, ProjectiveCurve, SerializationError,
 };
 use blake2s_simd::Params;
-use crypto_primitives::crh::{bowe_hopwood, pedersen, FixedLengthCRH};
+use crypto_primitives::crh::{bowe_hopwood, pedersen, pedersen::Window, FixedLengthCRH};
 use once_cell::sync::Lazy;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
+use std::io::{Read, Write};
 
 // Fix to get around leaking a private type in a public interface
 mod window {
@@ -31,6 +32,89 @@ mod window {
 /// `NUM_WINDOWS = 560`
 pub type CRH = bowe_hopwood::CRH<EdwardsParameters, window::Window>;
 
+/// The personalization used when deriving the default CRH parameters
+pub const CRH_PERSONALIZATION: &[u8; 8] = b"UL_prngs";
+/// The seed used when deriving the default CRH parameters
+pub const CRH_SEED: &[u8] = b"ULTRALIGHT PRNG SEED";
+
+/// The generators of the Bowe Hopwood Pedersen `CRH`. Deployments which need CRH parameters
+/// independent from the default ones can derive them via `CRHParameters::generate` and pass
+/// them to `CompositeHasher::from_parameters` and to the hash to group gadget.
+#[derive(Clone, Debug)]
+pub struct CRHParameters(pub <CRH as FixedLengthCRH>::Parameters);
+
+impl CRHParameters {
+    /// Deterministically derives the CRH's generators from a ChaCha PRNG seeded with
+    /// the Blake2s hash of `seed` under the provided personalization
+    pub fn generate(personalization: &[u8; 8], seed: &[u8]) -> Result<Self, BLSError> {
+        Ok(Self(CompositeHasher::<CRH>::setup_crh_from_seed(
+            personalization,
+            seed,
+        )?))
+    }
+
+    fn to_affine(&self) -> Vec<Vec<EdwardsAffine>> {
+        self.0
+            .generators
+            .iter()
+            .map(|window| window.iter().map(|g| g.into_affine()).collect())
+            .collect()
+    }
+
+    /// The generators must match the CRH's window, otherwise evaluating the CRH would fail
+    fn from_affine(generators: Vec<Vec<EdwardsAffine>>) -> Result<Self, SerializationError> {
+        if generators.len() != window::Window::NUM_WINDOWS
+            || generators
+                .iter()
+                .any(|window| window.len() != window::Window::WINDOW_SIZE)
+        {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(Self(bowe_hopwood::Parameters {
+            generators: generators
+                .into_iter()
+                .map(|window| window.into_iter().map(|g| g.into_projective()).collect())
+                .collect(),
+        }))
+    }
+}
+
+impl Default for CRHParameters {
+    fn default() -> Self {
+        Self(COMPOSITE_HASHER.parameters().clone())
+    }
+}
+
+impl PartialEq for CRHParameters {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.generators == other.0.generators
+    }
+}
+
+impl CanonicalSerialize for CRHParameters {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.to_affine().serialize(writer)
+    }
+
+    fn serialize_uncompressed<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.to_affine().serialize_uncompressed(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.to_affine().serialized_size()
+    }
+}
+
+impl CanonicalDeserialize for CRHParameters {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        Self::from_affine(Vec::<Vec<EdwardsAffine>>::deserialize(reader)?)
+    }
+
+    fn deserialize_uncompressed<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        Self::from_affine(Vec::<Vec<EdwardsAffine>>::deserialize_uncompressed(reader)?)
+    }
+}
+
 /// Lazily evaluated composite hasher instantiated over the
 /// Bowe-Hopwood-Pedersen CRH.
 pub static COMPOSITE_HASHER: Lazy<CompositeHasher<CRH>> =
@@ -51,12 +135,22 @@ impl<H: FixedLengthCRH> CompositeHasher<H> {
         })
     }
 
-    fn prng() -> impl Rng {
+    /// Returns a new hasher which uses the provided CRH parameters
+    pub fn from_parameters(parameters: H::Parameters) -> CompositeHasher<H> {
+        CompositeHasher { parameters }
+    }
+
+    /// Returns the CRH parameters used by the hasher
+    pub fn parameters(&self) -> &H::Parameters {
+        &self.parameters
+    }
+
+    fn prng(personalization: &[u8; 8], message: &[u8]) -> impl Rng {
         let hash_result = Params::new()
             .hash_length(32)
-            .personal(b"UL_prngs") // personalization
+            .personal(personalization)
             .to_state()
-            .update(b"ULTRALIGHT PRNG SEED") // message
+            .update(message)
             .finalize()
             .as_ref()
             .to_vec();
@@ -67,7 +161,16 @@ impl<H: FixedLengthCRH> CompositeHasher<H> {
 
     /// Instantiates the CRH's parameters
     pub fn setup_crh() -> Result<H::Parameters, BLSError> {
-        let mut rng = Self::prng();
+        Self::setup_crh_from_seed(CRH_PERSONALIZATION, CRH_SEED)
+    }
+
+    /// Instantiates the CRH's parameters from a PRNG seeded with the Blake2s hash
+    /// of the seed under the provided personalization
+    pub fn setup_crh_from_seed(
+        personalization: &[u8; 8],
+        seed: &[u8],
+    ) -> Result<H::Parameters, BLSError> {
+        let mut rng = Self::prng(personalization, seed);
         Ok(H::setup::<_>(&mut rng)?)
     }
 }
@@ -102,6 +205,35 @@ mod test {
     use rand::{Rng, SeedableRng};
     use rand_xorshift::XorShiftRng;
 
+    #[test]
+    fn crh_parameters_injection() {
+        let default = CRHParameters::generate(CRH_PERSONALIZATION, CRH_SEED).unwrap();
+        assert!(default == CRHParameters::default());
+
+        let mut serialized = vec![];
+        default.serialize(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), default.serialized_size());
+        let deserialized = CRHParameters::deserialize(&mut &serialized[..]).unwrap();
+        assert!(deserialized == default);
+        // truncated parameters are rejected
+        assert!(CRHParameters::deserialize(&mut &serialized[..serialized.len() / 2]).is_err());
+
+        let other = CRHParameters::generate(b"otherprs", CRH_SEED).unwrap();
+        assert!(other != default);
+
+        let msg = vec![1u8; 32];
+        let hasher = CompositeHasher::<CRH>::from_parameters(other.0);
+        assert_ne!(
+            hasher.crh(&[], &msg, 96).unwrap(),
+            COMPOSITE_HASHER.crh(&[], &msg, 96).unwrap()
+        );
+        let hasher = CompositeHasher::<CRH>::from_parameters(deserialized.0);
+        assert_eq!(
+            hasher.crh(&[], &msg, 96).unwrap(),
+            COMPOSITE_HASHER.crh(&[], &msg, 96).unwrap()
+        );
+    }
+
     #[test]
     fn test_crh_empty() {
         let msg: Vec<u8> = vec![];
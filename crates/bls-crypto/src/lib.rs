@@ -52,7 +52,12 @@
 //! algebra's `PairingEngine` trait. We will also support public keys on G1 and signatures on G2.
 
 pub mod bls;
-pub use bls::{PrivateKey, PublicKey, PublicKeyCache, Signature};
+pub use bls::{
+    encode_signing_context, AttestedAggregate, Bitmap, KeyCommitment, KeyDerivationProof,
+    KeyRotation, KeyShare, PartialSignatureSet, PrivateKey, PublicKey, PublicKeyCache,
+    RefreshDealing, ShareCommitments, ShareUpdate, Signature, SigningSession, SubgroupCheck,
+    ValidationPolicy, NEG_G1_GENERATOR_PREPARED, NEG_G2_GENERATOR_PREPARED,
+};
 
 /// Traits and implementations for hashing arbitrary data to an elliptic curve's group element
 pub mod hash_to_curve;
@@ -62,12 +67,31 @@ pub use hash_to_curve::HashToCurve;
 pub mod hashers;
 pub use hashers::Hasher;
 
+/// Encodings of points and scalars as expected by the BLS12-377 precompiles of Celo's EVM
+pub mod precompile;
+
+/// Fiat-Shamir transcripts for deriving challenges from protocol messages
+pub mod transcript;
+pub use transcript::Transcript;
+
+/// The Poseidon hash function over prime fields, for hashing field elements in circuits
+pub mod poseidon;
+pub use poseidon::PoseidonParameters;
+
 #[cfg(any(test, feature = "test-helpers"))]
 pub mod test_helpers;
 
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+
+#[cfg(test)]
+mod deterministic_test_vectors;
+
 use log::error;
 use thiserror::Error;
 
+pub use celo_bls_errors::{ErrorCode, ToErrorCode};
+
 /// Convenience result alias
 pub type BlsResult<T> = std::result::Result<T, BLSError>;
 
@@ -80,6 +104,31 @@ pub const POP_DOMAIN: &[u8] = b"ULforpop";
 /// Domain separator for public inputs to the snark
 pub const OUT_DOMAIN: &[u8] = b"ULforout";
 
+/// Domain separator for the commitment to the signed bitmaps of a proven epoch range
+pub const BITMAP_DOMAIN: &[u8] = b"ULforbmp";
+
+/// Domain separator for the commitment to the auxiliary data of a proven epoch range
+pub const AUX_DATA_DOMAIN: &[u8] = b"ULforaux";
+
+/// Domain separator for deriving the randomness beacon output of an epoch
+pub const RANDOMNESS_DOMAIN: &[u8] = b"ULforrnd";
+
+/// Domain separator for the commitment to the randomness beacon outputs of a proven epoch range
+pub const RANDOMNESS_COMMITMENT_DOMAIN: &[u8] = b"ULforrcm";
+
+/// Domain separator for the commitment to the per-validator signed epoch counts of a proven
+/// epoch range
+pub const REWARD_DOMAIN: &[u8] = b"ULforrwd";
+
+/// Domain separator for deriving audited keys from their committed randomness
+pub const KEYGEN_DOMAIN: &[u8] = b"ULforkgn";
+
+/// Domain separator for the handoff messages of validator key rotations
+pub const ROTATION_DOMAIN: &[u8] = b"ULforrot";
+
+/// Domain separator for deriving the round constants of Poseidon parameters
+pub const POSEIDON_DOMAIN: &[u8] = b"ULforpsd";
+
 #[derive(Debug, Error)]
 /// Error type
 pub enum BLSError {
@@ -107,7 +156,75 @@ pub enum BLSError {
     #[error("there must be the same number of keys and messages")]
     UnevenNumKeysMessages,
 
+    /// The validator index is not part of the validator set
+    #[error("invalid validator index: {0}")]
+    InvalidValidatorIndex(usize),
+
+    /// The validator has already contributed a signature
+    #[error("duplicate signature from validator {0}")]
+    DuplicateSignature(usize),
+
     /// Serialization error in Zexe
     #[error(transparent)]
     SerializationError(#[from] algebra::SerializationError),
+
+    /// The point is not in the prime order subgroup
+    #[error("point is not in the prime order subgroup")]
+    NotInSubgroup,
+
+    /// A pre-hashed message is the identity
+    #[error("the message hash is the identity")]
+    IdentityMessageHash,
+
+    /// An attested aggregate signature is malformed
+    #[error("invalid attested aggregate: {0}")]
+    InvalidAttestedAggregate(&'static str),
+
+    /// An audited key was not derived from its commitment
+    #[error("invalid key derivation: {0}")]
+    InvalidKeyDerivation(&'static str),
+
+    /// A point is the identity, which the validation policy rejects
+    #[error("the point is the identity")]
+    UnexpectedInfinity,
+
+    /// A bitmap is not canonically encoded
+    #[error("invalid bitmap: {0}")]
+    InvalidBitmap(&'static str),
+
+    /// A threshold key share, or a refresh of it, does not match its commitments
+    #[error("invalid key share: {0}")]
+    InvalidKeyShare(&'static str),
+
+    /// Bytes are not a valid encoding of the precompiles
+    #[error("invalid precompile encoding: {0}")]
+    InvalidPrecompileEncoding(&'static str),
+
+    /// An argument is malformed, e.g. a null pointer or a negative length passed over FFI
+    #[error("invalid argument: {0}")]
+    InvalidArgument(&'static str),
+}
+
+impl ToErrorCode for BLSError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            BLSError::VerificationFailed => ErrorCode::InvalidSignature,
+            BLSError::IoError(_) => ErrorCode::Io,
+            BLSError::HashingError(_) | BLSError::HashToCurveError => ErrorCode::HashingFailed,
+            BLSError::DomainTooLarge(_)
+            | BLSError::UnevenNumKeysMessages
+            | BLSError::InvalidValidatorIndex(_)
+            | BLSError::DuplicateSignature(_)
+            | BLSError::IdentityMessageHash
+            | BLSError::InvalidAttestedAggregate(_)
+            | BLSError::InvalidKeyDerivation(_)
+            | BLSError::InvalidKeyShare(_)
+            | BLSError::UnexpectedInfinity
+            | BLSError::InvalidArgument(_) => ErrorCode::InvalidArgument,
+            BLSError::SerializationError(_)
+            | BLSError::InvalidBitmap(_)
+            | BLSError::InvalidPrecompileEncoding(_) => ErrorCode::Serialization,
+            BLSError::NotInSubgroup => ErrorCode::NotInSubgroup,
+        }
+    }
 }
@@ -0,0 +1,185 @@
+use crate::{
+    hashers::{DirectHasher, Hasher},
+    BlsResult, POSEIDON_DOMAIN,
+};
+
+use algebra::{Field, FpParameters, PrimeField};
+
+/// The number of field elements in the Poseidon state
+pub const WIDTH: usize = 3;
+
+/// The number of field elements absorbed by each permutation. The remaining element of the
+/// state is the capacity.
+pub const RATE: usize = WIDTH - 1;
+
+/// Parameters of the Poseidon hash function over `F`, with a state of `WIDTH` elements and
+/// the `x^alpha` S-box.
+///
+/// The round constants are derived from a seed with Blake2Xs under the `POSEIDON_DOMAIN`, and
+/// the MDS matrix is the Cauchy matrix `1 / (i + j + WIDTH)`, so the parameters are fully
+/// determined by the number of rounds, `alpha` and the seed. `alpha` must be coprime with
+/// `p - 1` for the S-box to be a permutation (e.g. 5 for the BLS12-377 base field).
+///
+/// `bls_gadgets::PoseidonGadget` computes the same hash in a constraint system.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoseidonParameters<F: PrimeField> {
+    full_rounds: usize,
+    partial_rounds: usize,
+    alpha: u64,
+    round_constants: Vec<F>,
+    mds: Vec<Vec<F>>,
+}
+
+impl<F: PrimeField> PoseidonParameters<F> {
+    /// Generates the parameters for `full_rounds` rounds with the S-box applied to the whole
+    /// state (half of them before the partial rounds, and half after), and `partial_rounds`
+    /// rounds with the S-box applied only to the first element of the state
+    pub fn new(
+        full_rounds: usize,
+        partial_rounds: usize,
+        alpha: u64,
+        seed: &[u8],
+    ) -> BlsResult<Self> {
+        let num_bytes = (F::Params::MODULUS_BITS as usize + 7) / 8;
+        let num_constants = WIDTH * (full_rounds + partial_rounds);
+        let mut round_constants = Vec::with_capacity(num_constants);
+        for index in 0..num_constants as u32 {
+            // try-and-increment until the hash is a field element
+            let mut attempt = 0u32;
+            let constant = loop {
+                let mut message = seed.to_vec();
+                message.extend_from_slice(&index.to_le_bytes());
+                message.extend_from_slice(&attempt.to_le_bytes());
+                let hash = DirectHasher.xof(POSEIDON_DOMAIN, &message, num_bytes)?;
+                if let Some(constant) = F::from_random_bytes(&hash) {
+                    break constant;
+                }
+                attempt += 1;
+            };
+            round_constants.push(constant);
+        }
+
+        let mds = (0..WIDTH)
+            .map(|i| {
+                (0..WIDTH)
+                    .map(|j| {
+                        F::from((i + j + WIDTH) as u64)
+                            .inverse()
+                            .expect("the entries are non-zero")
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            full_rounds,
+            partial_rounds,
+            alpha,
+            round_constants,
+            mds,
+        })
+    }
+
+    /// The number of rounds applying the S-box to the whole state
+    pub fn full_rounds(&self) -> usize {
+        self.full_rounds
+    }
+
+    /// The number of rounds applying the S-box to the first element of the state
+    pub fn partial_rounds(&self) -> usize {
+        self.partial_rounds
+    }
+
+    /// The exponent of the S-box
+    pub fn alpha(&self) -> u64 {
+        self.alpha
+    }
+
+    /// The constants added to the state on each round, `WIDTH` per round
+    pub fn round_constants(&self) -> &[F] {
+        &self.round_constants
+    }
+
+    /// The `WIDTH`x`WIDTH` matrix which mixes the state at the end of each round
+    pub fn mds(&self) -> &[Vec<F>] {
+        &self.mds
+    }
+
+    /// Whether the round applies the S-box to the whole state
+    pub fn is_full_round(&self, round: usize) -> bool {
+        round < self.full_rounds / 2 || round >= self.full_rounds / 2 + self.partial_rounds
+    }
+
+    /// Hashes the field elements to a single field element.
+    ///
+    /// The capacity element is initialized with the number of inputs, and the inputs are
+    /// added to the rest of the state `RATE` at a time, each followed by a permutation. The
+    /// output is the first element of the rate.
+    pub fn hash(&self, inputs: &[F]) -> F {
+        let mut state = [F::zero(); WIDTH];
+        state[0] = F::from(inputs.len() as u64);
+        if inputs.is_empty() {
+            self.permute(&mut state);
+        }
+        for chunk in inputs.chunks(RATE) {
+            for (element, input) in state[1..].iter_mut().zip(chunk) {
+                *element += input;
+            }
+            self.permute(&mut state);
+        }
+        state[1]
+    }
+
+    fn permute(&self, state: &mut [F; WIDTH]) {
+        for round in 0..self.full_rounds + self.partial_rounds {
+            for (i, element) in state.iter_mut().enumerate() {
+                *element += &self.round_constants[round * WIDTH + i];
+            }
+
+            if self.is_full_round(round) {
+                for element in state.iter_mut() {
+                    *element = element.pow([self.alpha]);
+                }
+            } else {
+                state[0] = state[0].pow([self.alpha]);
+            }
+
+            let mut mixed = [F::zero(); WIDTH];
+            for (mixed, row) in mixed.iter_mut().zip(&self.mds) {
+                for (entry, element) in row.iter().zip(state.iter()) {
+                    *mixed += &(*entry * element);
+                }
+            }
+            *state = mixed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{bls12_377::Fq, UniformRand, Zero};
+
+    #[test]
+    fn hashes_deterministically() {
+        let rng = &mut rand::thread_rng();
+        let params = PoseidonParameters::<Fq>::new(8, 60, 5, b"test").unwrap();
+        assert_eq!(params.round_constants().len(), WIDTH * 68);
+        assert_eq!(
+            PoseidonParameters::<Fq>::new(8, 60, 5, b"test").unwrap(),
+            params
+        );
+        assert_ne!(
+            PoseidonParameters::<Fq>::new(8, 60, 5, b"other").unwrap(),
+            params
+        );
+
+        let inputs = (0..5).map(|_| Fq::rand(rng)).collect::<Vec<_>>();
+        let hash = params.hash(&inputs);
+        assert_eq!(params.hash(&inputs), hash);
+        assert_ne!(params.hash(&inputs[..4]), hash);
+        // the number of inputs is part of the hash, so zero padding changes it
+        assert_ne!(params.hash(&[Fq::zero()]), params.hash(&[Fq::zero(); 2]));
+        assert_ne!(params.hash(&[]), params.hash(&[Fq::zero()]));
+    }
+}
@@ -0,0 +1,287 @@
+//! Encodings of the BLS12-377 precompiles
+//!
+//! Celo's EVM exposes BLS12-377 operations as precompiles (CIP-25, which follows EIP-2539),
+//! whose inputs and outputs use an uncompressed big-endian encoding which differs from this
+//! crate's compressed little-endian serialization:
+//!
+//! - a base field element is the 48 bytes of its big-endian encoding, left-padded with 16
+//!   zero bytes to `FP_BYTES`
+//! - an element of the quadratic extension is the encoding of `c0` followed by that of `c1`
+//! - a point is the encoding of `x` followed by that of `y`, and the identity is all zeros,
+//!   so G1 points (signatures and message hashes) are `G1_BYTES` long and G2 points (public
+//!   keys) are `G2_BYTES` long
+//! - a scalar is its `SCALAR_BYTES` big-endian encoding
+//!
+//! Contracts which verify signatures with the precompiles must be given the points in this
+//! encoding. Decoding checks the padding, that the coordinates are canonical and that the
+//! point is on the curve and in the prime order subgroup.
+use crate::{BLSError, BlsResult, PublicKey, Signature, ValidationPolicy};
+
+use algebra::{
+    biginteger::{BigInteger256, BigInteger384},
+    bls12_377::{Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine, G2Projective},
+    curves::models::{short_weierstrass_jacobian::GroupAffine, SWModelParameters},
+    AffineCurve, PrimeField, ProjectiveCurve, Zero,
+};
+
+/// The length of an encoded base field element
+pub const FP_BYTES: usize = 64;
+
+/// The length of an encoded G1 point
+pub const G1_BYTES: usize = 2 * FP_BYTES;
+
+/// The length of an encoded G2 point
+pub const G2_BYTES: usize = 4 * FP_BYTES;
+
+/// The length of an encoded scalar
+pub const SCALAR_BYTES: usize = 32;
+
+/// The number of zero bytes which pad each base field element
+const FP_PADDING: usize = FP_BYTES - 48;
+
+/// Encodes a G1 point, e.g. a signature or a message hash
+pub fn encode_g1(point: &G1Projective) -> Vec<u8> {
+    let point = point.into_affine();
+    let mut bytes = Vec::with_capacity(G1_BYTES);
+    if point.is_zero() {
+        bytes.resize(G1_BYTES, 0);
+    } else {
+        encode_fq(&point.x, &mut bytes);
+        encode_fq(&point.y, &mut bytes);
+    }
+    bytes
+}
+
+/// Decodes a G1 point encoded with `encode_g1`
+pub fn decode_g1(bytes: &[u8]) -> BlsResult<G1Projective> {
+    check_len(bytes, G1_BYTES)?;
+    if is_identity(bytes) {
+        return Ok(G1Projective::zero());
+    }
+    let (x, y) = bytes.split_at(FP_BYTES);
+    let point = G1Affine::new(decode_fq(x)?, decode_fq(y)?, false);
+    check_point(&point)?;
+    Ok(point.into_projective())
+}
+
+/// Encodes a G2 point, e.g. a public key
+pub fn encode_g2(point: &G2Projective) -> Vec<u8> {
+    let point = point.into_affine();
+    let mut bytes = Vec::with_capacity(G2_BYTES);
+    if point.is_zero() {
+        bytes.resize(G2_BYTES, 0);
+    } else {
+        encode_fq2(&point.x, &mut bytes);
+        encode_fq2(&point.y, &mut bytes);
+    }
+    bytes
+}
+
+/// Decodes a G2 point encoded with `encode_g2`
+pub fn decode_g2(bytes: &[u8]) -> BlsResult<G2Projective> {
+    check_len(bytes, G2_BYTES)?;
+    if is_identity(bytes) {
+        return Ok(G2Projective::zero());
+    }
+    let (x, y) = bytes.split_at(2 * FP_BYTES);
+    let point = G2Affine::new(decode_fq2(x)?, decode_fq2(y)?, false);
+    check_point(&point)?;
+    Ok(point.into_projective())
+}
+
+/// Encodes a scalar, e.g. the multiplier of a precompile's scalar multiplication
+pub fn encode_scalar(scalar: &Fr) -> Vec<u8> {
+    limbs_to_be_bytes(&scalar.into_repr().0)
+}
+
+/// Decodes a scalar encoded with `encode_scalar`. The precompiles accept any 256-bit
+/// multiplier, but scalars at or above the group order are rejected, so that every scalar
+/// has a single encoding.
+pub fn decode_scalar(bytes: &[u8]) -> BlsResult<Fr> {
+    check_len(bytes, SCALAR_BYTES)?;
+    Fr::from_repr(BigInteger256(be_bytes_to_limbs(bytes))).ok_or(
+        BLSError::InvalidPrecompileEncoding("the scalar is not canonical"),
+    )
+}
+
+impl PublicKey {
+    /// Encodes the public key as the precompiles expect, see `encode_g2`
+    pub fn to_precompile_bytes(&self) -> Vec<u8> {
+        encode_g2(self.as_ref())
+    }
+
+    /// Decodes a public key encoded with `to_precompile_bytes`
+    pub fn from_precompile_bytes(bytes: &[u8]) -> BlsResult<PublicKey> {
+        decode_g2(bytes).map(PublicKey::from)
+    }
+}
+
+impl Signature {
+    /// Encodes the signature as the precompiles expect, see `encode_g1`
+    pub fn to_precompile_bytes(&self) -> Vec<u8> {
+        encode_g1(self.as_ref())
+    }
+
+    /// Decodes a signature encoded with `to_precompile_bytes`
+    pub fn from_precompile_bytes(bytes: &[u8]) -> BlsResult<Signature> {
+        decode_g1(bytes).map(Signature::from)
+    }
+}
+
+fn encode_fq(element: &Fq, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&[0; FP_PADDING]);
+    bytes.extend_from_slice(&limbs_to_be_bytes(&element.into_repr().0));
+}
+
+fn decode_fq(bytes: &[u8]) -> BlsResult<Fq> {
+    let (padding, element) = bytes.split_at(FP_PADDING);
+    if padding.iter().any(|byte| *byte != 0) {
+        return Err(BLSError::InvalidPrecompileEncoding(
+            "the padding of a field element is not zero",
+        ));
+    }
+    Fq::from_repr(BigInteger384(be_bytes_to_limbs(element))).ok_or(
+        BLSError::InvalidPrecompileEncoding("the field element is not canonical"),
+    )
+}
+
+fn encode_fq2(element: &Fq2, bytes: &mut Vec<u8>) {
+    encode_fq(&element.c0, bytes);
+    encode_fq(&element.c1, bytes);
+}
+
+fn decode_fq2(bytes: &[u8]) -> BlsResult<Fq2> {
+    let (c0, c1) = bytes.split_at(FP_BYTES);
+    Ok(Fq2::new(decode_fq(c0)?, decode_fq(c1)?))
+}
+
+fn check_len(bytes: &[u8], expected: usize) -> BlsResult<()> {
+    if bytes.len() != expected {
+        return Err(BLSError::InvalidPrecompileEncoding(
+            "the encoding does not have the expected length",
+        ));
+    }
+    Ok(())
+}
+
+fn is_identity(bytes: &[u8]) -> bool {
+    bytes.iter().all(|byte| *byte == 0)
+}
+
+fn check_point<P: SWModelParameters>(point: &GroupAffine<P>) -> BlsResult<()> {
+    if !point.is_on_curve() {
+        return Err(BLSError::InvalidPrecompileEncoding(
+            "the point is not on the curve",
+        ));
+    }
+    ValidationPolicy::CANONICAL.validate(point)
+}
+
+/// Encodes little-endian limbs as big-endian bytes
+fn limbs_to_be_bytes(limbs: &[u64]) -> Vec<u8> {
+    limbs
+        .iter()
+        .rev()
+        .flat_map(|limb| limb.to_be_bytes().to_vec())
+        .collect()
+}
+
+/// Decodes big-endian bytes, a whole number of limbs long, to little-endian limbs
+fn be_bytes_to_limbs<L: Default + AsMut<[u64]>>(bytes: &[u8]) -> L {
+    let mut limbs = L::default();
+    for (limb, chunk) in limbs.as_mut().iter_mut().rev().zip(bytes.chunks(8)) {
+        let mut limb_bytes = [0; 8];
+        limb_bytes.copy_from_slice(chunk);
+        *limb = u64::from_be_bytes(limb_bytes);
+    }
+    limbs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PrivateKey;
+    use algebra::{Field, One, UniformRand};
+
+    #[test]
+    fn roundtrips_keys_signatures_and_scalars() {
+        let rng = &mut rand::thread_rng();
+        for _ in 0..10 {
+            let key = PrivateKey::generate(rng);
+            let public_key = key.to_public();
+            let bytes = public_key.to_precompile_bytes();
+            assert_eq!(bytes.len(), G2_BYTES);
+            assert_eq!(
+                PublicKey::from_precompile_bytes(&bytes).unwrap(),
+                public_key
+            );
+
+            let signature = key.sign_exact(&G1Projective::rand(rng));
+            let bytes = signature.to_precompile_bytes();
+            assert_eq!(bytes.len(), G1_BYTES);
+            assert_eq!(Signature::from_precompile_bytes(&bytes).unwrap(), signature);
+
+            let bytes = encode_scalar(key.as_ref());
+            assert_eq!(bytes.len(), SCALAR_BYTES);
+            assert_eq!(decode_scalar(&bytes).unwrap(), *key.as_ref());
+        }
+
+        // the identity is all zeros
+        assert_eq!(encode_g1(&G1Projective::zero()), vec![0; G1_BYTES]);
+        assert!(decode_g1(&[0; G1_BYTES]).unwrap().is_zero());
+        assert_eq!(encode_g2(&G2Projective::zero()), vec![0; G2_BYTES]);
+        assert!(decode_g2(&[0; G2_BYTES]).unwrap().is_zero());
+    }
+
+    #[test]
+    fn encodes_the_generator_big_endian() {
+        let bytes = encode_g1(&G1Projective::prime_subgroup_generator());
+        let expected = [
+            "00000000000000000000000000000000",
+            "008848defe740a67c8fc6225bf87ff5485951e2caa9d41bb188282c8bd37cb5cd5481512ffcd394eeab9b16eb21be9ef",
+            "00000000000000000000000000000000",
+            "01914a69c5102eff1f674f5d30afeec4bd7fb348ca3e52d96d182ad44fb82305c2fe3d3634a9591afd82de55559c8ea6",
+        ]
+        .concat();
+        assert_eq!(hex::encode(&bytes), expected);
+        assert_eq!(encode_scalar(&Fr::one())[SCALAR_BYTES - 1], 1);
+    }
+
+    #[test]
+    fn rejects_malformed_encodings() {
+        let generator = encode_g1(&G1Projective::prime_subgroup_generator());
+        assert!(matches!(
+            decode_g1(&generator[1..]),
+            Err(BLSError::InvalidPrecompileEncoding(_))
+        ));
+
+        let mut padded = generator.clone();
+        padded[0] = 1;
+        assert!(matches!(
+            decode_g1(&padded),
+            Err(BLSError::InvalidPrecompileEncoding(
+                "the padding of a field element is not zero"
+            ))
+        ));
+
+        let mut off_curve = generator;
+        off_curve[G1_BYTES - 1] ^= 1;
+        assert!(matches!(
+            decode_g1(&off_curve),
+            Err(BLSError::InvalidPrecompileEncoding(
+                "the point is not on the curve"
+            ))
+        ));
+
+        // the modulus is not a canonical field element
+        let mut modulus = vec![0; FP_PADDING];
+        modulus.extend_from_slice(&limbs_to_be_bytes(Fq::characteristic()));
+        assert!(matches!(
+            decode_g1(&[modulus.clone(), modulus].concat()),
+            Err(BLSError::InvalidPrecompileEncoding(
+                "the field element is not canonical"
+            ))
+        ));
+        assert!(decode_scalar(&[0xff; SCALAR_BYTES]).is_err());
+    }
+}
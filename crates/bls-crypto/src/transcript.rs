@@ -0,0 +1,140 @@
+use crate::{
+    hashers::{DirectHasher, Hasher},
+    BLSError, BlsResult,
+};
+
+use algebra::{
+    curves::models::{short_weierstrass_jacobian::GroupAffine, SWModelParameters},
+    BigInteger, CanonicalSerialize, FpParameters, PrimeField,
+};
+
+/// The number of bytes of the transcript's state after each squeeze
+const STATE_BYTES: usize = 32;
+
+/// A Fiat-Shamir transcript, which derives challenges from all the data absorbed into it.
+///
+/// Bytes and curve points are appended to the transcript's state, the bytes framed by their
+/// label and both lengths so that different splits of the same data absorb different states.
+/// Squeezing hashes the state with Blake2Xs personalized to the transcript's domain, replaces
+/// the state with the first `STATE_BYTES` bytes of the output and turns the rest of the output
+/// into the challenge, so that every challenge depends on all the previous ones.
+///
+/// `bls_gadgets::TranscriptVar` derives the same challenges in a constraint system.
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    domain: Vec<u8>,
+    state: Vec<u8>,
+}
+
+impl Transcript {
+    /// Starts an empty transcript whose challenges are separated by `domain`, which cannot be
+    /// larger than 8 bytes
+    pub fn new(domain: &[u8]) -> BlsResult<Self> {
+        if domain.len() > 8 {
+            return Err(BLSError::DomainTooLarge(domain.len()));
+        }
+        Ok(Self {
+            domain: domain.to_vec(),
+            state: vec![],
+        })
+    }
+
+    /// Appends the bytes to the transcript, as the little-endian `u32` length of the label, the
+    /// label, the little-endian `u32` length of the bytes and the bytes
+    pub fn absorb_bytes(&mut self, label: &[u8], bytes: &[u8]) {
+        self.state
+            .extend_from_slice(&(label.len() as u32).to_le_bytes());
+        self.state.extend_from_slice(label);
+        self.state
+            .extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.state.extend_from_slice(bytes);
+    }
+
+    /// Appends the point's affine coordinates to the transcript, each serialized as its
+    /// little-endian bytes. The point must not be the point at infinity.
+    pub fn absorb_point<P: SWModelParameters>(&mut self, point: &GroupAffine<P>) -> BlsResult<()> {
+        point.x.serialize(&mut self.state)?;
+        point.y.serialize(&mut self.state)?;
+        Ok(())
+    }
+
+    /// Derives a challenge from the transcript. The challenge is uniformly distributed over
+    /// the field elements which fit in `F::Params::CAPACITY` bits.
+    pub fn squeeze_field<F: PrimeField>(&mut self) -> BlsResult<F> {
+        let num_bits = F::Params::CAPACITY as usize;
+        let output =
+            DirectHasher.xof(&self.domain, &self.state, STATE_BYTES + (num_bits + 7) / 8)?;
+        self.state = output[..STATE_BYTES].to_vec();
+
+        // the challenge's bits are the output's bits in little-endian order
+        let mut bits = output[STATE_BYTES..]
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+            .take(num_bits)
+            .collect::<Vec<_>>();
+        bits.reverse();
+        Ok(F::from_repr(F::BigInt::from_bits(&bits))
+            .expect("the challenge has fewer bits than the field's capacity"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SIG_DOMAIN;
+    use algebra::{
+        bls12_377::{Fr, G1Projective},
+        ProjectiveCurve, UniformRand,
+    };
+
+    #[test]
+    fn derives_challenges_from_the_transcript() {
+        let rng = &mut rand::thread_rng();
+        let point = G1Projective::rand(rng).into_affine();
+        let transcript = |domain: &[u8], message: &[u8]| {
+            let mut transcript = Transcript::new(domain).unwrap();
+            transcript.absorb_bytes(b"message", message);
+            transcript.absorb_point(&point).unwrap();
+            transcript
+        };
+
+        let mut first = transcript(b"ULfortst", b"hello");
+        let challenge = first.squeeze_field::<Fr>().unwrap();
+        // challenges are deterministic
+        assert_eq!(
+            transcript(b"ULfortst", b"hello")
+                .squeeze_field::<Fr>()
+                .unwrap(),
+            challenge
+        );
+        // and depend on the previous challenges, the domain and the absorbed data
+        assert_ne!(first.squeeze_field::<Fr>().unwrap(), challenge);
+        assert_ne!(
+            transcript(SIG_DOMAIN, b"hello")
+                .squeeze_field::<Fr>()
+                .unwrap(),
+            challenge
+        );
+        assert_ne!(
+            transcript(b"ULfortst", b"hellp")
+                .squeeze_field::<Fr>()
+                .unwrap(),
+            challenge
+        );
+
+        // absorbs are framed, so splitting the same bytes differently changes the challenge
+        let split = |label: &[u8], first: &[u8], second: &[u8]| {
+            let mut transcript = Transcript::new(b"ULfortst").unwrap();
+            transcript.absorb_bytes(label, first);
+            transcript.absorb_bytes(label, second);
+            transcript.squeeze_field::<Fr>().unwrap()
+        };
+        assert_ne!(split(b"m", b"ab", b"c"), split(b"m", b"a", b"bc"));
+        assert_ne!(split(b"m", b"ab", b"c"), split(b"ma", b"b", b"c"));
+
+        assert!(matches!(
+            Transcript::new(b"too long domain"),
+            Err(BLSError::DomainTooLarge(15))
+        ));
+    }
+}
@@ -1,14 +1,38 @@
-use algebra::PrimeField;
-use r1cs_core::{lc, LinearCombination, SynthesisError, Variable};
+use algebra::{BigInteger, PrimeField};
+use r1cs_core::{lc, ConstraintSystemRef, LinearCombination, SynthesisError, Variable};
 use r1cs_std::{fields::fp::FpVar, prelude::*};
+use std::ops::Deref;
 
+/// The number of bits of the slack between the occurrences and their maximum in
+/// `enforce_maximum_occurrences_sparse`, i.e. the maximum must fit in a `u32`
+const SLACK_BITS: usize = 32;
+
+/// Gadgets which bound the number of set (or unset) bits in a bitmap
 pub trait Bitmap<F: PrimeField> {
     /// Enforces that there are no more than `max_occurrences` of `value` (0 or 1)
-    /// present in the provided bitmap
+    /// present in the provided bitmap, and returns the constrained number of occurrences
+    /// so that composing circuits can reuse it instead of counting them again
     fn enforce_maximum_occurrences_in_bitmap(
         &self,
         max_occurrences: &FpVar<F>,
         value: bool,
+    ) -> Result<FpVar<F>, SynthesisError>;
+
+    /// Same as `enforce_maximum_occurrences_in_bitmap`, for bitmaps where `value` is known
+    /// to occur at most `capacity` times, e.g. the non-signers when `capacity` is the
+    /// largest maximum number of non-signers the circuit must support.
+    ///
+    /// Each of the `capacity` slots is witnessed as used if it holds an occurrence, and the
+    /// number of used slots must be the number of occurrences. Instead of comparing the
+    /// count against the maximum over the whole field, the slack between them is proven to
+    /// fit in 32 bits, so this costs `capacity + 34` constraints.
+    /// The constraint system is unsatisfied if there are more than `capacity` occurrences
+    /// or if `max_occurrences` is not smaller than `2^32`.
+    fn enforce_maximum_occurrences_sparse(
+        &self,
+        max_occurrences: &FpVar<F>,
+        capacity: usize,
+        value: bool,
     ) -> Result<(), SynthesisError>;
 }
 
@@ -18,7 +42,7 @@ impl<F: PrimeField> Bitmap<F> for [Boolean<F>] {
         &self,
         max_occurrences: &FpVar<F>,
         value: bool,
-    ) -> Result<(), SynthesisError> {
+    ) -> Result<FpVar<F>, SynthesisError> {
         let mut value_fp = F::one();
         if !value {
             // using the opposite value if we are counting 0s
@@ -56,7 +80,7 @@ impl<F: PrimeField> Bitmap<F> for [Boolean<F>] {
         // Enforce `occurences <= max_occurences`
         occurrences.enforce_cmp(&max_occurrences, std::cmp::Ordering::Less, true)?;
 
-        let occurrences_var = match occurrences {
+        let occurrences_var = match &occurrences {
             FpVar::Var(v) => v.variable,
             _ => unreachable!(),
         };
@@ -67,10 +91,189 @@ impl<F: PrimeField> Bitmap<F> for [Boolean<F>] {
             lc!() + occurrences_var,
         )?;
 
+        Ok(occurrences)
+    }
+
+    #[tracing::instrument(target = "r1cs")]
+    fn enforce_maximum_occurrences_sparse(
+        &self,
+        max_occurrences: &FpVar<F>,
+        capacity: usize,
+        value: bool,
+    ) -> Result<(), SynthesisError> {
+        let cs = self.cs().or(max_occurrences.cs());
+        let is_setup = cs.is_in_setup_mode();
+
+        let mut occurrences = 0;
+        let mut occurrences_lc = LinearCombination::zero();
+        for bit in self {
+            occurrences_lc = if value {
+                occurrences_lc + bit.lc()
+            } else {
+                occurrences_lc + (F::one(), Variable::One) - bit.lc()
+            };
+            if !is_setup {
+                occurrences += (bit.value()? == value) as usize;
+            }
+        }
+
+        // the first `occurrences` slots are used
+        let slots = (0..capacity)
+            .map(|i| Boolean::new_witness(cs.clone(), || Ok(i < occurrences)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let slots_lc = slots
+            .iter()
+            .fold(LinearCombination::zero(), |lc, slot| lc + slot.lc());
+        // Enforce that the used slots are the occurrences, so there are at most `capacity`
+        cs.enforce_constraint(
+            occurrences_lc,
+            lc!() + (F::one(), Variable::One),
+            slots_lc.clone(),
+        )?;
+
+        // Enforce `occurrences + slack = max_occurrences` with `0 <= slack < 2^32`. Since
+        // both the occurrences and the slack are small, this cannot wrap around the modulus
+        let slack_bits = if is_setup {
+            vec![None; SLACK_BITS]
+        } else {
+            let slack = max_occurrences.value()? - &F::from(occurrences as u64);
+            let mut bits = slack.into_repr().to_bits();
+            // `to_bits` is big endian
+            bits.reverse();
+            bits.into_iter().take(SLACK_BITS).map(Some).collect()
+        };
+        let mut slack_lc = LinearCombination::zero();
+        let mut coeff = F::one();
+        for bit in slack_bits {
+            let bit =
+                Boolean::new_witness(cs.clone(), || bit.ok_or(SynthesisError::AssignmentMissing))?;
+            slack_lc = slack_lc + bit.lc() * coeff;
+            coeff.double_in_place();
+        }
+        let max_occurrences_lc = match max_occurrences {
+            FpVar::Var(v) => lc!() + v.variable,
+            FpVar::Constant(c) => lc!() + (*c, Variable::One),
+        };
+        cs.enforce_constraint(
+            slots_lc + slack_lc,
+            lc!() + (F::one(), Variable::One),
+            max_occurrences_lc,
+        )?;
+
         Ok(())
     }
 }
 
+/// A bitmap in the constraint system, the counterpart of `bls_crypto::Bitmap`. It
+/// dereferences to its bits, so the `Bitmap` gadgets apply to it directly.
+#[derive(Clone, Debug)]
+pub struct BitmapVar<F: PrimeField> {
+    bits: Vec<Boolean<F>>,
+}
+
+impl<F: PrimeField> BitmapVar<F> {
+    /// Allocates a bitmap of `len` bits, which costs one constraint per bit. The length is
+    /// part of the circuit, so it must be provided even when the bitmap is not assigned, and
+    /// assigning a bitmap of another length fails.
+    pub fn new_witness(
+        cs: ConstraintSystemRef<F>,
+        len: usize,
+        f: impl FnOnce() -> Result<bls_crypto::Bitmap, SynthesisError>,
+    ) -> Result<Self, SynthesisError> {
+        // the bitmap is only assigned when generating the proof
+        let bitmap = f().ok();
+        if bitmap.as_ref().map_or(false, |bitmap| bitmap.len() != len) {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let bits = (0..len)
+            .map(|i| {
+                Boolean::new_witness(cs.clone(), || {
+                    bitmap
+                        .as_ref()
+                        .map(|bitmap| bitmap[i])
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { bits })
+    }
+
+    /// Wraps bits which are already constrained to be booleans
+    pub fn from_bits(bits: Vec<Boolean<F>>) -> Self {
+        Self { bits }
+    }
+
+    /// The bitmap's value, if it was assigned
+    pub fn value(&self) -> Result<bls_crypto::Bitmap, SynthesisError> {
+        self.bits.iter().map(|bit| bit.value()).collect()
+    }
+
+    /// The number of set bits, as a linear combination of the bits which costs no constraints
+    pub fn popcount(&self) -> FpVar<F> {
+        self.bits
+            .iter()
+            .fold(FpVar::zero(), |count, bit| count + FpVar::from(bit.clone()))
+    }
+
+    /// The bitmap with every bit flipped, which costs no constraints
+    pub fn complement(&self) -> Self {
+        Self {
+            bits: self.bits.iter().map(Boolean::not).collect(),
+        }
+    }
+
+    /// Enforces that every bit set in the bitmap is also set in `other`, with one constraint
+    /// per bit
+    ///
+    /// # Panics
+    /// If the bitmaps have different lengths, which cannot be satisfied by any assignment
+    pub fn enforce_subset_of(&self, other: &Self) -> Result<(), SynthesisError> {
+        assert_eq!(self.len(), other.len());
+        let cs = self.cs().or(other.cs());
+        if cs.is_none() {
+            return if self.value()?.is_subset_of(&other.value()?) {
+                Ok(())
+            } else {
+                Err(SynthesisError::Unsatisfiable)
+            };
+        }
+        // `bit * (1 - other) = 0`
+        for (bit, other) in self.bits.iter().zip(&other.bits) {
+            cs.enforce_constraint(bit.lc(), lc!() + Variable::One - other.lc(), lc!())?;
+        }
+        Ok(())
+    }
+
+    /// The canonical encoding of the bitmap, i.e. its length (LE u32) followed by its bits
+    /// packed in little-endian bit order with zero padding, as serialized by
+    /// `bls_crypto::Bitmap`. The length is a constant of the circuit.
+    pub fn to_bytes(&self) -> Vec<UInt8<F>> {
+        let mut bytes = (self.len() as u32)
+            .to_le_bytes()
+            .iter()
+            .map(|byte| UInt8::constant(*byte))
+            .collect::<Vec<_>>();
+        bytes.extend(self.to_packed_bits_le().chunks(8).map(UInt8::from_bits_le));
+        bytes
+    }
+
+    /// The bits padded with zeros to a whole number of bytes, i.e. the packed bits of the
+    /// canonical encoding
+    pub fn to_packed_bits_le(&self) -> Vec<Boolean<F>> {
+        let mut bits = self.bits.clone();
+        bits.resize(8 * ((self.len() + 7) / 8), Boolean::constant(false));
+        bits
+    }
+}
+
+impl<F: PrimeField> Deref for BitmapVar<F> {
+    type Target = [Boolean<F>];
+
+    fn deref(&self) -> &[Boolean<F>] {
+        &self.bits
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,7 +313,8 @@ mod tests {
                     .collect::<Vec<_>>();
                 let max_occurrences =
                     FpVar::<Fr>::new_witness(cs, || Ok(Fr::from(self.max_occurrences))).unwrap();
-                bitmap.enforce_maximum_occurrences_in_bitmap(&max_occurrences, self.value)
+                bitmap.enforce_maximum_occurrences_in_bitmap(&max_occurrences, self.value)?;
+                Ok(())
             }
         }
 
@@ -158,6 +362,120 @@ mod tests {
         cs
     }
 
+    #[tracing::instrument(target = "r1cs")]
+    fn cs_enforce_sparse(
+        bitmap: &[bool],
+        max_number: u64,
+        capacity: usize,
+        is_one: bool,
+    ) -> ConstraintSystemRef<Fq> {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let bitmap = bitmap
+            .iter()
+            .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)).unwrap())
+            .collect::<Vec<_>>();
+        let max_occurrences =
+            FpVar::<Fq>::new_witness(cs.clone(), || Ok(Fq::from(max_number))).unwrap();
+        bitmap[..]
+            .enforce_maximum_occurrences_sparse(&max_occurrences, capacity, is_one)
+            .unwrap();
+        cs
+    }
+
+    #[test]
+    fn returns_occurrences() {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let bitmap = [false, true, true, false, true]
+            .iter()
+            .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)).unwrap())
+            .collect::<Vec<_>>();
+        let max_occurrences = FpVar::<Fq>::new_witness(cs.clone(), || Ok(Fq::from(4u64))).unwrap();
+        let zeros = bitmap
+            .enforce_maximum_occurrences_in_bitmap(&max_occurrences, false)
+            .unwrap();
+        let ones = bitmap
+            .enforce_maximum_occurrences_in_bitmap(&max_occurrences, true)
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(zeros.value().unwrap(), Fq::from(2u64));
+        assert_eq!(ones.value().unwrap(), Fq::from(3u64));
+    }
+
+    #[test]
+    fn bitmap_var_matches_native_bitmap() {
+        let bitmap = (0..11).map(|i| i % 3 == 0).collect::<bls_crypto::Bitmap>();
+        let full = bls_crypto::Bitmap::full(11);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let bitmap_var = BitmapVar::new_witness(cs.clone(), 11, || Ok(bitmap.clone())).unwrap();
+        let full_var = BitmapVar::new_witness(cs.clone(), 11, || Ok(full.clone())).unwrap();
+
+        assert_eq!(bitmap_var.value().unwrap(), bitmap);
+        assert_eq!(bitmap_var.popcount().value().unwrap(), Fq::from(4u64));
+        assert_eq!(
+            bitmap_var.complement().value().unwrap(),
+            bitmap.complement()
+        );
+        let bytes = bitmap_var
+            .to_bytes()
+            .iter()
+            .map(|byte| byte.value().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bytes, bitmap.to_bytes());
+
+        bitmap_var.enforce_subset_of(&full_var).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        full_var.enforce_subset_of(&bitmap_var).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // the length of the assignment must match the circuit's
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        assert!(BitmapVar::new_witness(cs, 10, || Ok(bitmap)).is_err());
+    }
+
+    mod sparse {
+        use super::*;
+
+        #[test]
+        fn within_maximum() {
+            run_profile_constraints(|| {
+                let bitmap = [true, false, true, true, false, true];
+                for (max_number, capacity) in &[(2, 2), (2, 3), (5, 2), (1 << 31, 2)] {
+                    let cs = cs_enforce_sparse(&bitmap, *max_number, *capacity, false);
+                    print_unsatisfied_constraints(cs.clone());
+                    assert!(cs.is_satisfied().unwrap());
+                }
+                let cs = cs_enforce_sparse(&bitmap, 4, 4, true);
+                assert!(cs.is_satisfied().unwrap());
+            });
+        }
+
+        #[test]
+        fn over_maximum_or_capacity() {
+            run_profile_constraints(|| {
+                let bitmap = [true, false, true, true, false, true];
+                // more occurrences than allowed
+                assert!(!cs_enforce_sparse(&bitmap, 1, 3, false)
+                    .is_satisfied()
+                    .unwrap());
+                // more occurrences than slots
+                assert!(!cs_enforce_sparse(&bitmap, 3, 1, false)
+                    .is_satisfied()
+                    .unwrap());
+                assert!(!cs_enforce_sparse(&bitmap, 3, 4, true)
+                    .is_satisfied()
+                    .unwrap());
+            });
+        }
+
+        #[test]
+        fn cheaper_than_dense_comparison() {
+            let bitmap = vec![true; 100];
+            let dense = cs_enforce_value(&bitmap, 3, false).num_constraints();
+            let sparse = cs_enforce_sparse(&bitmap, 3, 3, false).num_constraints();
+            assert!(sparse < dense);
+        }
+    }
+
     mod zeros {
         use super::*;
 
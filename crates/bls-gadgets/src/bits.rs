@@ -0,0 +1,273 @@
+//! # Bit Utilities
+//!
+//! Conversions between bytes, bits and field elements, natively and in the constraint system.
+//!
+//! Every function names the endianness of its input and output: `bits_le` are bits in
+//! ascending order of significance and `bits_be` in descending order, while `bytes_le` are
+//! bytes in ascending order of significance, each of which is a number (i.e. the bits of a
+//! byte are never reordered). For example, the number 2 is `[0, 1, 0, ...]` as `bits_le`,
+//! `[..., 0, 1, 0]` as `bits_be` and `[2, 0, ...]` as `bytes_le`.
+//!
+//! The gadgets allocate witnesses with values which may be missing, so that the same code
+//! generates the constraints in setup mode.
+use algebra::{BigInteger, PrimeField};
+use r1cs_core::{ConstraintSystemRef, SynthesisError};
+use r1cs_std::{fields::fp::FpVar, prelude::*, Assignment};
+
+/// Converts the provided big endian bits to LE bytes. If the number of bits is not a
+/// multiple of 8, the most significant byte is the one which is not full.
+pub fn bits_be_to_bytes_le(bits: &[bool]) -> Vec<u8> {
+    let reversed_bits = {
+        let mut tmp = bits.to_owned();
+        tmp.reverse();
+        tmp
+    };
+
+    let mut bytes = vec![];
+    for chunk in reversed_bits.chunks(8) {
+        let mut byte = 0;
+        let mut twoi: u64 = 1;
+        for c in chunk {
+            byte += (twoi * (*c as u64)) as u8;
+            twoi *= 2;
+        }
+        bytes.push(byte);
+    }
+
+    bytes
+}
+
+/// Converts the provided little endian bits to LE bytes. If the number of bits is not a
+/// multiple of 8, the most significant byte is the one which is not full.
+pub fn bits_le_to_bytes_le(bits: &[bool]) -> Vec<u8> {
+    bits_be_to_bytes_le(&bits.iter().cloned().rev().collect::<Vec<_>>())
+}
+
+/// If bytes is a little endian representation of a number, this returns the `bits_to_take`
+/// least significant bits of the number in descending order
+pub fn bytes_le_to_bits_be(bytes: &[u8], bits_to_take: usize) -> Vec<bool> {
+    let mut bits = vec![];
+    for b in bytes {
+        let mut byte = *b;
+        for _ in 0..8 {
+            bits.push((byte & 1) == 1);
+            byte >>= 1;
+        }
+    }
+
+    bits.into_iter()
+        .take(bits_to_take)
+        .collect::<Vec<bool>>()
+        .into_iter()
+        .rev()
+        .collect()
+}
+
+/// Converts the provided little endian bytes to the `bits_to_take` least significant LE bits
+pub fn bytes_le_to_bits_le(bytes: &[u8], bits_to_take: usize) -> Vec<bool> {
+    bytes_le_to_bits_be(bytes, bits_to_take)
+        .into_iter()
+        .rev()
+        .collect()
+}
+
+/// Allocates the bits as witnesses, constraining each of them to be boolean
+#[tracing::instrument(target = "r1cs")]
+pub fn constrain_bool<F: PrimeField>(
+    input: &[Option<bool>],
+    cs: ConstraintSystemRef<F>,
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    input
+        .iter()
+        .map(|b| Boolean::new_witness(cs.clone(), || b.get()))
+        .collect::<Result<Vec<_>, _>>()
+}
+
+/// Allocates the bits as public inputs, constraining each of them to be boolean
+#[tracing::instrument(target = "r1cs")]
+pub fn constrain_bool_input<F: PrimeField>(
+    input: &[Option<bool>],
+    cs: ConstraintSystemRef<F>,
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    input
+        .iter()
+        .map(|b| Boolean::new_input(cs.clone(), || b.get()))
+        .collect::<Result<Vec<_>, _>>()
+}
+
+/// Allocates a witness for the number whose little endian bytes are provided. The bytes
+/// are reduced modulo the field's characteristic, so they must fit in the field for the
+/// conversion to be injective.
+///
+/// This does not constrain the witness, which is typically done by decomposing it with
+/// `fp_to_bits_le`.
+pub fn bytes_le_to_fp<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    bytes: Option<&[u8]>,
+) -> Result<FpVar<F>, SynthesisError> {
+    FpVar::new_witness(cs, || {
+        let bits = bytes_le_to_bits_be(bytes.get()?, 64 * F::BigInt::NUM_LIMBS);
+        Ok(F::from(F::BigInt::from_bits(&bits)))
+    })
+}
+
+/// Returns the `length` least significant bits of the field element, in little endian
+/// order. The more significant bits are dropped without being constrained to be zero, so
+/// the element must be known to fit in `length` bits (e.g. if it was allocated from as many
+/// bits) for the result to be its full representation.
+#[tracing::instrument(target = "r1cs")]
+pub fn fp_to_bits_le<F: PrimeField>(
+    input: &FpVar<F>,
+    length: usize,
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    let input = input.to_bits_le()?;
+    let result = input[0..length].to_vec();
+    Ok(result)
+}
+
+/// Returns whether the number whose big endian bits are `a` is smaller than the one whose
+/// big endian bits are `b`, i.e. whether `a` precedes `b` lexicographically. Costs 2
+/// constraints per bit.
+///
+/// # Panics
+///
+/// - If `a.len() != b.len()`
+#[tracing::instrument(target = "r1cs")]
+pub fn is_less_than_be<F: PrimeField>(
+    a: &[Boolean<F>],
+    b: &[Boolean<F>],
+) -> Result<Boolean<F>, SynthesisError> {
+    assert_eq!(a.len(), b.len());
+    // from the least significant bit: `a < b` if they differ at this bit and `b` has it set,
+    // or if they are equal at this bit and `a < b` over the less significant bits
+    let mut is_less = Boolean::constant(false);
+    for (a, b) in a.iter().zip(b).rev() {
+        let differ = a.xor(b)?;
+        is_less = Boolean::conditionally_select(&differ, b, &is_less)?;
+    }
+    Ok(is_less)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::print_unsatisfied_constraints;
+    use algebra::bls12_377::Fq;
+    use r1cs_core::ConstraintSystem;
+    use rand::{thread_rng, Rng};
+
+    fn values(bits: &[Boolean<Fq>]) -> Vec<bool> {
+        bits.iter().map(|bit| bit.value().unwrap()).collect()
+    }
+
+    fn random_bits(rng: &mut impl Rng) -> Vec<bool> {
+        let len = rng.gen_range(0, 100);
+        (0..len).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn conversions_are_explicit_about_endianness() {
+        let two_le = [false, true, false, false, false, false, false, false];
+        let two_be = [false, false, false, false, false, false, true, false];
+        assert_eq!(bits_le_to_bytes_le(&two_le), vec![2]);
+        assert_eq!(bits_be_to_bytes_le(&two_be), vec![2]);
+        assert_eq!(bytes_le_to_bits_le(&[2], 8), two_le.to_vec());
+        assert_eq!(bytes_le_to_bits_be(&[2], 8), two_be.to_vec());
+        // 0x0201
+        let mut bits = vec![true];
+        bits.extend_from_slice(&[false; 7]);
+        bits.extend_from_slice(&two_le[..2]);
+        assert_eq!(bytes_le_to_bits_le(&[1, 2], 10), bits);
+        assert_eq!(
+            bits_le_to_bytes_le(&bytes_le_to_bits_le(&[1, 2], 16)),
+            [1, 2]
+        );
+        // partial bytes are the most significant
+        assert_eq!(
+            bits_be_to_bytes_le(&[true, false, false, false, false, false, false, false, false]),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn conversions_roundtrip() {
+        let rng = &mut thread_rng();
+        for _ in 0..100 {
+            let mut bits = random_bits(rng);
+            let num_bits = bits.len();
+            let bytes = bits_le_to_bytes_le(&bits);
+            assert_eq!(bytes.len(), (num_bits + 7) / 8);
+            assert_eq!(bytes_le_to_bits_le(&bytes, num_bits), bits);
+
+            bits.reverse();
+            let bytes_from_be = bits_be_to_bytes_le(&bits);
+            assert_eq!(bytes_from_be, bytes);
+            assert_eq!(bytes_le_to_bits_be(&bytes_from_be, num_bits), bits);
+
+            let bytes = (0..rng.gen_range(0, 20))
+                .map(|_| rng.gen())
+                .collect::<Vec<u8>>();
+            let bits = bytes_le_to_bits_le(&bytes, 8 * bytes.len());
+            assert_eq!(bits_le_to_bytes_le(&bits), bytes);
+            // taking more bits than there are is the same as taking all of them
+            assert_eq!(bytes_le_to_bits_le(&bytes, 8 * bytes.len() + 5), bits);
+        }
+    }
+
+    #[test]
+    fn gadgets_match_native_conversions() {
+        let rng = &mut thread_rng();
+        for _ in 0..10 {
+            let bytes = (0..rng.gen_range(1, 32))
+                .map(|_| rng.gen())
+                .collect::<Vec<u8>>();
+            let num_bits = 8 * bytes.len();
+            let cs = ConstraintSystem::<Fq>::new_ref();
+
+            let fp = bytes_le_to_fp(cs.clone(), Some(&bytes[..])).unwrap();
+            let bits = fp_to_bits_le(&fp, num_bits).unwrap();
+            assert_eq!(values(&bits), bytes_le_to_bits_le(&bytes, num_bits));
+
+            let options = values(&bits).into_iter().map(Some).collect::<Vec<_>>();
+            let witnesses = constrain_bool(&options, cs.clone()).unwrap();
+            let inputs = constrain_bool_input(&options, cs.clone()).unwrap();
+            assert_eq!(values(&witnesses), values(&inputs));
+            assert_eq!(values(&inputs), values(&bits));
+            assert_eq!(cs.num_instance_variables(), num_bits + 1);
+
+            print_unsatisfied_constraints(cs.clone());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn compares_big_endian_bits() {
+        let rng = &mut thread_rng();
+        for _ in 0..20 {
+            let (a, b): (u16, u16) = (rng.gen(), rng.gen());
+            // also compare equal numbers, and numbers with a common prefix
+            for (a, b) in [(a, b), (a, a), (a, a ^ 1), (a & 0xff00, a | 0xff)].iter() {
+                let cs = ConstraintSystem::<Fq>::new_ref();
+                let be_bits = |x: u16| {
+                    let options = bytes_le_to_bits_be(&x.to_le_bytes(), 16)
+                        .into_iter()
+                        .map(Some)
+                        .collect::<Vec<_>>();
+                    constrain_bool(&options, cs.clone()).unwrap()
+                };
+                let is_less = is_less_than_be(&be_bits(*a), &be_bits(*b)).unwrap();
+                assert_eq!(is_less.value().unwrap(), a < b);
+                assert!(cs.is_satisfied().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn allocates_in_setup_mode() {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        cs.set_mode(r1cs_core::SynthesisMode::Setup);
+        let bits = constrain_bool(&[None; 5], cs.clone()).unwrap();
+        fp_to_bits_le(&bytes_le_to_fp(cs.clone(), None).unwrap(), 5).unwrap();
+        assert_eq!(bits.len(), 5);
+    }
+}
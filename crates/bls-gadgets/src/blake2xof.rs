@@ -0,0 +1,132 @@
+use algebra::PrimeField;
+use crypto_primitives::prf::{
+    blake2s::constraints::evaluate_blake2s_with_parameters, Blake2sWithParameterBlock,
+};
+use r1cs_core::SynthesisError;
+use r1cs_std::boolean::Boolean;
+use tracing::trace;
+
+/// Gadget which enforces the calculation of Blake2Xs, the extendable output function built
+/// on top of Blake2s, as specified in: https://blake2.net/blake2x.pdf
+///
+/// Matches the output of `DirectHasher::xof`, i.e. each output block is calculated directly
+/// over the message with the block's node offset, instead of over a root hash.
+pub struct Blake2XofGadget;
+
+impl Blake2XofGadget {
+    /// The maximum number of output bytes, since `0xffff` denotes an unknown output length
+    pub const MAX_OUTPUT_BYTES: u16 = u16::MAX - 1;
+
+    /// Parameters for Blake2Xs:
+    /// • “Key length” is set to 0 (even if the root hash was keyed)
+    /// • “Fanout” is set to 0 (unlimited)
+    /// • “Maximal depth” is set to 0
+    /// • “Leaf maximal byte length” is set to 32 for BLAKE2Xs
+    /// • “XOF digest length” is set to the length of the final output digest
+    /// • “Node depth” is set to 0 (leaves)
+    /// • “Inner hash byte length” is set to 32 for BLAKE2Xs
+    ///
+    /// `digest_length` is 32 for all blocks but the last one, which only outputs
+    /// the remaining bytes.
+    pub fn parameters(
+        output_bytes: u16,
+        digest_length: u8,
+        node_offset: u32,
+        personalization: [u8; 8],
+    ) -> Blake2sWithParameterBlock {
+        Blake2sWithParameterBlock {
+            digest_length,
+            key_length: 0,
+            fan_out: 0,
+            depth: 0,
+            leaf_length: 32,
+            node_offset,
+            xof_digest_length: output_bytes,
+            node_depth: 0,
+            inner_length: 32,
+            salt: [0; 8],
+            personalization,
+        }
+    }
+
+    /// Returns the first `output_bytes` bytes of the Blake2Xs hash of the message
+    /// with the provided personalization, as LE bits.
+    ///
+    /// # Panics
+    ///
+    /// If `output_bytes` is 0 or larger than `MAX_OUTPUT_BYTES`
+    #[tracing::instrument(target = "r1cs")]
+    pub fn evaluate<F: PrimeField>(
+        message: &[Boolean<F>],
+        output_bytes: u16,
+        personalization: [u8; 8],
+    ) -> Result<Vec<Boolean<F>>, SynthesisError> {
+        assert!(
+            output_bytes > 0 && output_bytes <= Self::MAX_OUTPUT_BYTES,
+            "invalid output length"
+        );
+        let output_bytes_len = output_bytes as usize;
+        let num_blocks = (output_bytes_len + 31) / 32;
+
+        let mut output = Vec::with_capacity(output_bytes_len * 8);
+        for i in 0..num_blocks {
+            trace!(blake_iteration = i);
+            let digest_length = std::cmp::min(32, output_bytes_len - 32 * i);
+            let parameters =
+                Self::parameters(output_bytes, digest_length as u8, i as u32, personalization);
+            let block = evaluate_blake2s_with_parameters(message, &parameters.parameters())?;
+            output.extend(
+                block
+                    .into_iter()
+                    .flat_map(|n| n.to_bits_le())
+                    .take(digest_length * 8),
+            );
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{bytes_le_to_bits_le, test_helpers::print_unsatisfied_constraints};
+    use algebra::bls12_377::Fq;
+    use bls_crypto::{
+        hashers::{DirectHasher, Hasher},
+        SIG_DOMAIN,
+    };
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::{alloc::AllocVar, R1CSVar};
+    use rand::{thread_rng, RngCore};
+
+    #[test]
+    fn matches_native_xof() {
+        let rng = &mut thread_rng();
+        let mut personalization = [0; 8];
+        personalization.copy_from_slice(SIG_DOMAIN);
+
+        for output_bytes in &[1u16, 20, 32, 48, 64, 100] {
+            let mut message = vec![0u8; 40];
+            rng.fill_bytes(&mut message);
+            let expected = DirectHasher
+                .xof(&personalization, &message, *output_bytes as usize)
+                .unwrap();
+
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            let message_bits = bytes_le_to_bits_le(&message, message.len() * 8)
+                .into_iter()
+                .map(|b| Boolean::new_witness(cs.clone(), || Ok(b)).unwrap())
+                .collect::<Vec<_>>();
+            let hash =
+                Blake2XofGadget::evaluate(&message_bits, *output_bytes, personalization).unwrap();
+
+            assert_eq!(
+                hash.iter().map(|b| b.value().unwrap()).collect::<Vec<_>>(),
+                bytes_le_to_bits_le(&expected, expected.len() * 8)
+            );
+            print_unsatisfied_constraints(cs.clone());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+}
@@ -1,14 +1,24 @@
-use crate::Bitmap;
-use algebra::{PairingEngine, PrimeField, ProjectiveCurve};
-use r1cs_core::SynthesisError;
+use crate::{Bitmap, NeutralPreparedGadget};
+use algebra::{AffineCurve, PairingEngine, PrimeField};
+use once_cell::sync::Lazy;
+use r1cs_core::{Namespace, SynthesisError};
 use r1cs_std::{
-    alloc::AllocationMode, boolean::Boolean, eq::EqGadget, fields::fp::FpVar, fields::FieldVar,
+    alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::fp::FpVar, fields::FieldVar,
     groups::CurveVar, pairing::PairingVar, R1CSVar,
 };
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::marker::PhantomData;
-use std::ops::AddAssign;
+use std::ops::{AddAssign, Neg};
+use std::sync::Mutex;
 use tracing::{debug, span, trace, Level};
 
+/// The negated generator of G2 of each pairing engine which was verified against, prepared
+/// natively. Preparing it is independent of the constraint system, so every constraint
+/// system allocates the same prepared value as a constant.
+static NEG_G2_GENERATORS: Lazy<Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
 /// BLS Signature Verification Gadget.
 ///
 /// Implements BLS Verification as written in [BDN18](https://eprint.iacr.org/2018/483.pdf)
@@ -49,7 +59,7 @@ where
         let _enter = span.enter();
         // Get the message hash and the aggregated public key based on the bitmap
         // and allowed number of non-signers
-        let (message_hash, aggregated_pk) =
+        let (message_hash, aggregated_pk, _) =
             Self::enforce_bitmap(pub_keys, signed_bitmap, message_hash, maximum_non_signers)?;
 
         let prepared_aggregated_pk = P::prepare_g2(&aggregated_pk)?;
@@ -121,6 +131,37 @@ where
         Ok(())
     }
 
+    /// Batch verification against prepared messages, in which the pairs whose bit is not set
+    /// in `present` (e.g. the padding of a fixed size batch) are replaced with neutral pairs,
+    /// so that the aggregate signature does not need to cover them. Unlike selecting dummy
+    /// points before preparing them, this only costs a few constraints per pair.
+    ///
+    /// # Panics
+    /// If `present` and `prepared_message_hashes` have different lengths
+    #[tracing::instrument(target = "r1cs")]
+    pub fn batch_verify_prepared_present(
+        prepared_aggregated_pub_keys: &[P::G2PreparedVar],
+        prepared_message_hashes: &[P::G1PreparedVar],
+        present: &[Boolean<F>],
+        aggregated_signature: &P::G1Var,
+    ) -> Result<(), SynthesisError>
+    where
+        P::G1PreparedVar: NeutralPreparedGadget<F>,
+    {
+        assert_eq!(present.len(), prepared_message_hashes.len());
+        let prepared_message_hashes = prepared_message_hashes
+            .iter()
+            .zip(present)
+            .map(|(message_hash, present)| message_hash.select_or_neutral(present))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::batch_verify_prepared(
+            prepared_aggregated_pub_keys,
+            &prepared_message_hashes,
+            aggregated_signature,
+        )
+    }
+
     /// Returns a gadget which checks that an aggregate pubkey is correctly calculated
     /// by the sum of the pub keys which had a 1 in the bitmap
     ///
@@ -161,7 +202,9 @@ where
     }
 
     /// Enforces that the provided bitmap contains no more than `maximum_non_signers`
-    /// 0s. Also returns a gadget of the prepared message hash and a gadget for the aggregate public key
+    /// 0s. Also returns a gadget of the prepared message hash, a gadget for the aggregate public key
+    /// and the number of non-signers, which composing circuits can reuse instead of counting the
+    /// bitmap's 0s again
     ///
     /// # Panics
     /// If signed_bitmap length != pub_keys length (due to internal call to `enforced_aggregated_pubkeys`)
@@ -171,13 +214,37 @@ where
         signed_bitmap: &[Boolean<F>],
         message_hash: &P::G1Var,
         maximum_non_signers: &FpVar<F>,
-    ) -> Result<(P::G1Var, P::G2Var), SynthesisError> {
+    ) -> Result<(P::G1Var, P::G2Var, FpVar<F>), SynthesisError> {
         trace!("enforcing bitmap");
-        signed_bitmap.enforce_maximum_occurrences_in_bitmap(maximum_non_signers, false)?;
+        let num_non_signers =
+            signed_bitmap.enforce_maximum_occurrences_in_bitmap(maximum_non_signers, false)?;
 
         let aggregated_pk = Self::enforce_aggregated_pubkeys(pub_keys, signed_bitmap)?;
 
-        Ok((message_hash.clone(), aggregated_pk))
+        Ok((message_hash.clone(), aggregated_pk, num_non_signers))
+    }
+
+    /// Returns the negated generator of G2, prepared for the pairing, as a constant of `cs`.
+    ///
+    /// The generator is prepared natively once per pairing engine and cached, so unlike
+    /// preparing a constant generator with `P::prepare_g2`, repeated verifications do not
+    /// recompute its line coefficients.
+    pub fn prepared_neg_g2_generator(
+        cs: impl Into<Namespace<F>>,
+    ) -> Result<P::G2PreparedVar, SynthesisError> {
+        let prepared = NEG_G2_GENERATORS
+            .lock()
+            .expect("mutex poisoned")
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| {
+                Box::new(E::G2Prepared::from(
+                    E::G2Affine::prime_subgroup_generator().neg(),
+                ))
+            })
+            .downcast_ref::<E::G2Prepared>()
+            .expect("the generators are keyed by their pairing engine")
+            .clone();
+        P::G2PreparedVar::new_constant(cs, prepared)
     }
 
     /// Verifying BLS signatures requires preparing a G1 Signature and
@@ -188,16 +255,7 @@ where
     ) -> Result<(P::G1PreparedVar, P::G2PreparedVar), SynthesisError> {
         // Ensure the signature is prepared
         let prepared_signature = P::prepare_g1(signature)?;
-
-        // Allocate the generator on G2
-        let g2_generator = P::G2Var::new_variable_omit_prime_order_check(
-            signature.cs(),
-            || Ok(E::G2Projective::prime_subgroup_generator()),
-            AllocationMode::Constant,
-        )?;
-        // and negate it for the purpose of verification
-        let g2_neg_generator = g2_generator.negate()?;
-        let prepared_g2_neg_generator = P::prepare_g2(&g2_neg_generator)?;
+        let prepared_g2_neg_generator = Self::prepared_neg_g2_generator(signature.cs())?;
 
         Ok((prepared_signature, prepared_g2_neg_generator))
     }
@@ -233,7 +291,7 @@ mod verify_one_message {
     };
     use r1cs_core::{ConstraintSystem, ConstraintSystemRef};
     use r1cs_std::{
-        alloc::AllocVar,
+        alloc::{AllocVar, AllocationMode},
         bls12_377::{G1Var, G2Var, PairingVar as Bls12_377PairingGadget},
         boolean::Boolean,
     };
@@ -510,4 +568,15 @@ mod verify_one_message {
         print_unsatisfied_constraints(cs.clone());
         assert!(cs.is_satisfied().unwrap());
     }
+
+    #[test]
+    fn prepared_neg_g2_generator_is_constant() {
+        type Gadget = BlsVerifyGadget<Bls12_377, BW6_761Fr, Bls12_377PairingGadget>;
+        let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+        // the second call is served from the cache
+        Gadget::prepared_neg_g2_generator(cs.clone()).unwrap();
+        Gadget::prepared_neg_g2_generator(cs.clone()).unwrap();
+        assert_eq!(cs.num_constraints(), 0);
+        assert_eq!(cs.num_witness_variables(), 0);
+    }
 }
@@ -0,0 +1,351 @@
+use crate::{BlsVerifyGadget, PoseidonGadget};
+use algebra::{FpParameters, PairingEngine, PrimeField};
+use bls_crypto::PoseidonParameters;
+use r1cs_core::{ConstraintSystem, SynthesisError};
+use r1cs_std::{fields::fp::FpVar, pairing::PairingVar, prelude::*};
+use std::marker::PhantomData;
+use std::ops::AddAssign;
+use tracing::{span, Level};
+
+/// Two-level BLS aggregation for very large validator sets.
+///
+/// Validators are grouped into committees of `committee_size` consecutive keys, and the
+/// signed bitmap indicates which _committees_ signed. The aggregate public key of each
+/// committee is a witness, which is bound to the validator set by a Poseidon commitment to
+/// the committee keys (see `commitment`), so that the committees are never re-aggregated from
+/// the validator keys in the circuit. Aggregating the signers' public key then requires one
+/// conditional addition per committee (`N / k`) instead of one per validator (`N`), and
+/// checking the commitment also costs a constant per committee.
+///
+/// The commitment is computed outside of the circuit, by whoever manages the validator set,
+/// and is typically a public input of the circuit.
+pub struct CommitteeAggregationGadget<E, F, P> {
+    pairing_engine_type: PhantomData<E>,
+    constraint_field_type: PhantomData<F>,
+    pairing_gadget_type: PhantomData<P>,
+}
+
+impl<E, F, P> CommitteeAggregationGadget<E, F, P>
+where
+    E: PairingEngine,
+    F: PrimeField,
+    P: PairingVar<E, F>,
+    P::G2Var: for<'a> AddAssign<&'a P::G2Var>,
+{
+    /// Returns the aggregate public key of each committee. The last committee
+    /// contains the remaining keys if `committee_size` does not divide the number of keys.
+    ///
+    /// # Panics
+    /// If `committee_size` is 0
+    pub fn committee_pubkeys(
+        pub_keys: &[E::G2Projective],
+        committee_size: usize,
+    ) -> Vec<E::G2Projective> {
+        pub_keys
+            .chunks(committee_size)
+            .map(|committee| committee.iter().copied().sum())
+            .collect()
+    }
+
+    /// Returns the commitment to the committee public keys, which `verify` checks them
+    /// against. The keys are encoded with the same gadget as in `verify`, over constants, so
+    /// that the encodings match.
+    pub fn commitment(
+        parameters: &PoseidonParameters<F>,
+        committee_pub_keys: &[E::G2Projective],
+    ) -> Result<F, SynthesisError> {
+        let cs = ConstraintSystem::<F>::new_ref();
+        let committee_pub_keys = committee_pub_keys
+            .iter()
+            .map(|pk| P::G2Var::new_constant(cs.clone(), pk))
+            .collect::<Result<Vec<_>, _>>()?;
+        let elements = Self::encode(&committee_pub_keys)?
+            .iter()
+            .map(|element| element.value())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(parameters.hash(&elements))
+    }
+
+    /// Enforces that `commitment` is the commitment to the committee public keys
+    #[tracing::instrument(target = "r1cs", skip(parameters))]
+    pub fn enforce_commitment(
+        parameters: &PoseidonParameters<F>,
+        committee_pub_keys: &[P::G2Var],
+        commitment: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        let elements = Self::encode(committee_pub_keys)?;
+        PoseidonGadget::hash(parameters, &elements)?.enforce_equal(commitment)
+    }
+
+    /// Enforces verification of a BLS signature produced by the committees which had a 1
+    /// in the committee bitmap, allowing at most `maximum_non_signing_committees` committees
+    /// to not sign. The committee public keys must match the commitment, see `commitment`.
+    ///
+    /// # Panics
+    /// If committee_bitmap length != committee_pub_keys length
+    #[tracing::instrument(target = "r1cs", skip(parameters))]
+    pub fn verify(
+        parameters: &PoseidonParameters<F>,
+        committee_pub_keys: &[P::G2Var],
+        commitment: &FpVar<F>,
+        committee_bitmap: &[Boolean<F>],
+        message_hash: &P::G1Var,
+        signature: &P::G1Var,
+        maximum_non_signing_committees: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        let span = span!(Level::TRACE, "CommitteeAggregationGadget_verify");
+        let _enter = span.enter();
+        Self::enforce_commitment(parameters, committee_pub_keys, commitment)?;
+        // the committees act as the signers of a regular BLS multi-signature
+        BlsVerifyGadget::<E, F, P>::verify(
+            committee_pub_keys,
+            committee_bitmap,
+            message_hash,
+            signature,
+            maximum_non_signing_committees,
+        )
+    }
+
+    /// Packs the bits of the committee public keys into as few field elements as possible
+    fn encode(committee_pub_keys: &[P::G2Var]) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        let mut bits = vec![];
+        for pk in committee_pub_keys {
+            bits.extend(pk.to_bits_le()?);
+        }
+        // the packing is linear, so it adds no constraints
+        Ok(bits
+            .chunks(F::Params::CAPACITY as usize)
+            .map(|chunk| {
+                let mut coeff = F::one();
+                chunk.iter().fold(FpVar::zero(), |packed, bit| {
+                    let packed = packed + FpVar::from(bit.clone()) * coeff;
+                    coeff.double_in_place();
+                    packed
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::{print_unsatisfied_constraints, run_profile_constraints};
+
+    use algebra::{
+        bls12_377::{Bls12_377, Fr as Bls12_377Fr, G1Projective, G2Projective},
+        bw6_761::Fr as BW6_761Fr,
+        ProjectiveCurve, UniformRand,
+    };
+    use once_cell::sync::Lazy;
+    use r1cs_core::ConstraintSystemRef;
+    use r1cs_std::{alloc::AllocationMode, bls12_377::PairingVar as Bls12_377PairingGadget};
+
+    type Gadget = CommitteeAggregationGadget<Bls12_377, BW6_761Fr, Bls12_377PairingGadget>;
+    type G2Var = <Bls12_377PairingGadget as PairingVar<Bls12_377, BW6_761Fr>>::G2Var;
+    type G1Var = <Bls12_377PairingGadget as PairingVar<Bls12_377, BW6_761Fr>>::G1Var;
+
+    static PARAMETERS: Lazy<PoseidonParameters<BW6_761Fr>> =
+        Lazy::new(|| PoseidonParameters::new(8, 60, 5, b"test").unwrap());
+
+    fn alloc_g2(cs: &ConstraintSystemRef<BW6_761Fr>, points: &[G2Projective]) -> Vec<G2Var> {
+        points
+            .iter()
+            .map(|pk| {
+                G2Var::new_variable_omit_prime_order_check(
+                    cs.clone(),
+                    || Ok(*pk),
+                    AllocationMode::Witness,
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn alloc_commitment(
+        cs: &ConstraintSystemRef<BW6_761Fr>,
+        committee_pub_keys: &[G2Projective],
+    ) -> FpVar<BW6_761Fr> {
+        let commitment = Gadget::commitment(&PARAMETERS, committee_pub_keys).unwrap();
+        FpVar::new_input(cs.clone(), || Ok(commitment)).unwrap()
+    }
+
+    #[tracing::instrument(target = "r1cs")]
+    fn cs_verify(
+        message_hash: G1Projective,
+        committee_pub_keys: &[G2Projective],
+        committed_pub_keys: &[G2Projective],
+        signature: G1Projective,
+        committee_bitmap: &[bool],
+        num_non_signing_committees: u64,
+    ) -> ConstraintSystemRef<BW6_761Fr> {
+        let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+        let alloc_g1 = |point: G1Projective| {
+            G1Var::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || Ok(point),
+                AllocationMode::Witness,
+            )
+            .unwrap()
+        };
+        let message_hash = alloc_g1(message_hash);
+        let signature = alloc_g1(signature);
+        let commitment = alloc_commitment(&cs, committed_pub_keys);
+        let committee_pub_keys = alloc_g2(&cs, committee_pub_keys);
+        let committee_bitmap = committee_bitmap
+            .iter()
+            .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)).unwrap())
+            .collect::<Vec<_>>();
+        let maximum_non_signing_committees = FpVar::new_witness(cs.clone(), || {
+            Ok(BW6_761Fr::from(num_non_signing_committees))
+        })
+        .unwrap();
+
+        Gadget::verify(
+            &PARAMETERS,
+            &committee_pub_keys,
+            &commitment,
+            &committee_bitmap,
+            &message_hash,
+            &signature,
+            &maximum_non_signing_committees,
+        )
+        .unwrap();
+        cs
+    }
+
+    #[test]
+    fn computes_committee_pubkeys() {
+        let rng = &mut rand::thread_rng();
+        let pub_keys = (0..5).map(|_| G2Projective::rand(rng)).collect::<Vec<_>>();
+        let committees = Gadget::committee_pubkeys(&pub_keys, 2);
+        assert_eq!(
+            committees,
+            vec![
+                pub_keys[0] + &pub_keys[1],
+                pub_keys[2] + &pub_keys[3],
+                pub_keys[4],
+            ]
+        );
+    }
+
+    #[test]
+    fn enforces_commitment() {
+        let rng = &mut rand::thread_rng();
+        let committee_pub_keys = (0..3).map(|_| G2Projective::rand(rng)).collect::<Vec<_>>();
+        let is_satisfied = |committed: &[G2Projective]| {
+            let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+            let commitment = alloc_commitment(&cs, committed);
+            let committee_pub_keys = alloc_g2(&cs, &committee_pub_keys);
+            Gadget::enforce_commitment(&PARAMETERS, &committee_pub_keys, &commitment).unwrap();
+            print_unsatisfied_constraints(cs.clone());
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(&committee_pub_keys));
+
+        let mut other = committee_pub_keys.clone();
+        other[1] = G2Projective::rand(rng);
+        assert!(!is_satisfied(&other));
+        other.swap(0, 2);
+        assert!(!is_satisfied(&other[..2]));
+    }
+
+    #[test]
+    fn verifies_committee_signatures() {
+        run_profile_constraints(verifies_committee_signatures_inner);
+    }
+    #[tracing::instrument(target = "r1cs")]
+    fn verifies_committee_signatures_inner() {
+        let rng = &mut rand::thread_rng();
+        let secret_keys = (0..6).map(|_| Bls12_377Fr::rand(rng)).collect::<Vec<_>>();
+        let pub_keys = secret_keys
+            .iter()
+            .map(|sk| G2Projective::prime_subgroup_generator().mul(*sk))
+            .collect::<Vec<_>>();
+        let committees = Gadget::committee_pubkeys(&pub_keys, 2);
+        let message_hash = G1Projective::rand(rng);
+
+        // committees of 2 validators, the first and the last committee sign
+        let signature = [0, 1, 4, 5]
+            .iter()
+            .map(|i| message_hash.mul(secret_keys[*i]))
+            .sum::<G1Projective>();
+        let bitmap = [true, false, true];
+        let cs = cs_verify(
+            message_hash,
+            &committees,
+            &committees,
+            signature,
+            &bitmap,
+            1,
+        );
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+
+        // one non-signing committee is not allowed with 0 non signers
+        let cs = cs_verify(
+            message_hash,
+            &committees,
+            &committees,
+            signature,
+            &bitmap,
+            0,
+        );
+        assert!(!cs.is_satisfied().unwrap());
+
+        // a committee which only partially signed cannot be counted
+        let partial = signature - message_hash.mul(secret_keys[5]);
+        let cs = cs_verify(message_hash, &committees, &committees, partial, &bitmap, 1);
+        assert!(!cs.is_satisfied().unwrap());
+
+        // nor can its key be replaced by the key of its signers
+        let mut signers = committees.clone();
+        signers[2] = pub_keys[4];
+        let cs = cs_verify(message_hash, &signers, &committees, partial, &bitmap, 1);
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    /// The constraints which aggregating the signers' public key costs, with the committee
+    /// public keys checked against their commitment
+    fn aggregation_constraints(num_validators: usize, committee_size: usize) -> usize {
+        let rng = &mut rand::thread_rng();
+        let pub_keys = (0..num_validators)
+            .map(|_| G2Projective::rand(rng))
+            .collect::<Vec<_>>();
+        let committees = Gadget::committee_pubkeys(&pub_keys, committee_size);
+        let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+        let commitment = alloc_commitment(&cs, &committees);
+        let committee_pub_keys = alloc_g2(&cs, &committees);
+        let committee_bitmap = committees
+            .iter()
+            .map(|_| Boolean::new_witness(cs.clone(), || Ok(true)).unwrap())
+            .collect::<Vec<_>>();
+
+        let before = cs.num_constraints();
+        Gadget::enforce_commitment(&PARAMETERS, &committee_pub_keys, &commitment).unwrap();
+        BlsVerifyGadget::<Bls12_377, BW6_761Fr, Bls12_377PairingGadget>::enforce_aggregated_pubkeys(
+            &committee_pub_keys,
+            &committee_bitmap,
+        )
+        .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        cs.num_constraints() - before
+    }
+
+    #[test]
+    fn aggregation_is_linear_in_the_number_of_committees() {
+        // the cost of a single committee, which is the unit of the N / k + k bound
+        let unit = aggregation_constraints(4, 4);
+        for &(num_validators, committee_size) in &[(16, 4), (32, 8), (30, 4)] {
+            let num_committees = (num_validators + committee_size - 1) / committee_size;
+            let constraints = aggregation_constraints(num_validators, committee_size);
+            assert!(constraints <= unit * (num_committees + committee_size));
+        }
+        // the committee keys are not aggregated from the validator keys, so the cost does not
+        // depend on the size of the committees
+        assert_eq!(
+            aggregation_constraints(16, 4),
+            aggregation_constraints(64, 16)
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Diagnostics for locating failures in constraint systems built with this crate's gadgets.
+//!
+//! Unlike `ConstraintSystemRef::which_is_unsatisfied`, which only returns the name of the
+//! first failing constraint, `unsatisfied_constraints` returns every unsatisfied constraint
+//! along with the values of its linear combinations, so that failures can be inspected
+//! programmatically.
+//!
+//! The gadget path of a constraint is only recorded when running under a `ConstraintLayer`
+//! tracing subscriber, and zexe only exposes it for the first unsatisfied constraint.
+//!
+//! A failed point equality is reported by `unsatisfied_constraints` as the values of a
+//! single coordinate's constraint, which rarely tells which points differed.
+//! `assert_points_equal` enforces the same equality, and logs the affine coordinates of both
+//! points when they differ.
+use algebra::{Field, ProjectiveCurve};
+use r1cs_core::{ConstraintSystemRef, SynthesisError};
+use r1cs_std::{eq::EqGadget, groups::CurveVar, R1CSVar};
+use std::fmt;
+use tracing::error;
+
+/// An R1CS constraint `a * b = c` which does not hold for the current assignment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsatisfiedConstraint<F: Field> {
+    /// The index of the constraint in the constraint system
+    pub index: usize,
+    /// The gadget span path under which the constraint was generated, if it was recorded
+    pub path: Option<String>,
+    /// The value of the constraint's `A` linear combination
+    pub a: F,
+    /// The value of the constraint's `B` linear combination
+    pub b: F,
+    /// The value of the constraint's `C` linear combination
+    pub c: F,
+}
+
+impl<F: Field> UnsatisfiedConstraint<F> {
+    /// The left hand side of the constraint, `a * b`
+    pub fn lhs(&self) -> F {
+        self.a * &self.b
+    }
+
+    /// The right hand side of the constraint, `c`
+    pub fn rhs(&self) -> F {
+        self.c
+    }
+}
+
+impl<F: Field> fmt::Display for UnsatisfiedConstraint<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "constraint #{} ({}): {} * {} != {}",
+            self.index,
+            self.path.as_deref().unwrap_or("unknown path"),
+            self.a,
+            self.b,
+            self.c
+        )
+    }
+}
+
+/// Returns all the unsatisfied constraints of the constraint system, in the order in
+/// which they were generated.
+///
+/// This inlines the linear combinations of the constraint system. Fails with
+/// `SynthesisError::AssignmentMissing` if the constraint system has no assignments
+/// (e.g. when it was built in setup mode).
+pub fn unsatisfied_constraints<F: Field>(
+    cs: &ConstraintSystemRef<F>,
+) -> Result<Vec<UnsatisfiedConstraint<F>>, SynthesisError> {
+    if cs.is_satisfied()? {
+        return Ok(vec![]);
+    }
+    // only the first unsatisfied constraint's path is exposed by the constraint system
+    let mut first_path = cs.which_is_unsatisfied()?;
+
+    cs.inline_all_lcs();
+    let matrices = cs.to_matrices().ok_or(SynthesisError::AssignmentMissing)?;
+    let assignment = {
+        let inner = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        inner
+            .instance_assignment
+            .iter()
+            .chain(inner.witness_assignment.iter())
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    let evaluate = |row: &[(F, usize)]| {
+        row.iter().fold(F::zero(), |acc, (coeff, i)| {
+            acc + &(*coeff * &assignment[*i])
+        })
+    };
+
+    let mut unsatisfied = vec![];
+    for (index, ((a, b), c)) in matrices
+        .a
+        .iter()
+        .zip(&matrices.b)
+        .zip(&matrices.c)
+        .enumerate()
+    {
+        let (a, b, c) = (evaluate(a), evaluate(b), evaluate(c));
+        if a * &b != c {
+            unsatisfied.push(UnsatisfiedConstraint {
+                index,
+                path: first_path.take(),
+                a,
+                b,
+                c,
+            });
+        }
+    }
+
+    Ok(unsatisfied)
+}
+
+/// Enforces that two points (e.g. two `G1Var`s or two `G2Var`s) are equal, as
+/// `EqGadget::enforce_equal` does. Unless the constraint system is in setup mode, an error
+/// with `label` and the affine coordinates of both points is logged if they differ.
+#[tracing::instrument(target = "r1cs")]
+pub fn assert_points_equal<C, F, GG>(label: &str, a: &GG, b: &GG) -> Result<(), SynthesisError>
+where
+    C: ProjectiveCurve,
+    F: Field,
+    GG: CurveVar<C, F>,
+{
+    if let Some((a_value, b_value)) = point_mismatch(a, b)? {
+        error!(
+            target: "r1cs",
+            "{}: the points differ, {} != {}", label, a_value, b_value
+        );
+    }
+    a.enforce_equal(b)
+}
+
+/// Returns the affine values of the points if they differ, or `None` if they are equal or
+/// the constraint system is in setup mode
+fn point_mismatch<C, F, GG>(
+    a: &GG,
+    b: &GG,
+) -> Result<Option<(C::Affine, C::Affine)>, SynthesisError>
+where
+    C: ProjectiveCurve,
+    F: Field,
+    GG: CurveVar<C, F>,
+{
+    if a.cs().or(b.cs()).is_in_setup_mode() {
+        return Ok(None);
+    }
+    let (a, b) = (a.value()?, b.value()?);
+    if a == b {
+        return Ok(None);
+    }
+    Ok(Some((a.into_affine(), b.into_affine())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::run_profile_constraints;
+    use algebra::{
+        bls12_377::{Fq, G1Projective, Parameters as Bls12_377_Parameters},
+        One, UniformRand,
+    };
+    use r1cs_core::{ConstraintSystem, SynthesisMode};
+    use r1cs_std::{alloc::AllocVar, boolean::Boolean, fields::fp::FpVar, groups::bls12::G1Var};
+
+    #[test]
+    fn satisfied_cs_has_no_unsatisfied_constraints() {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let a = Boolean::new_witness(cs.clone(), || Ok(true)).unwrap();
+        a.enforce_equal(&Boolean::Constant(true)).unwrap();
+        assert!(unsatisfied_constraints(&cs).unwrap().is_empty());
+    }
+
+    #[tracing::instrument(target = "r1cs")]
+    fn cs_enforce_equal(a: Fq, b: Fq) -> ConstraintSystemRef<Fq> {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let a = FpVar::new_witness(cs.clone(), || Ok(a)).unwrap();
+        let b = FpVar::new_witness(cs.clone(), || Ok(b)).unwrap();
+        a.enforce_equal(&b).unwrap();
+        cs
+    }
+
+    #[test]
+    fn reports_values_and_path() {
+        run_profile_constraints(|| {
+            let cs = cs_enforce_equal(Fq::one(), Fq::one() + &Fq::one());
+
+            let unsatisfied = unsatisfied_constraints(&cs).unwrap();
+            assert_eq!(unsatisfied.len(), 1);
+            let constraint = &unsatisfied[0];
+            assert_ne!(constraint.lhs(), constraint.rhs());
+            assert!(constraint
+                .path
+                .as_ref()
+                .unwrap()
+                .contains("cs_enforce_equal"));
+        });
+    }
+
+    #[test]
+    fn reports_mismatched_points() {
+        let rng = &mut rand::thread_rng();
+        let (a, b) = (G1Projective::rand(rng), G1Projective::rand(rng));
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let a_var = G1Var::<Bls12_377_Parameters>::new_witness(cs.clone(), || Ok(a)).unwrap();
+        let b_var = G1Var::<Bls12_377_Parameters>::new_witness(cs.clone(), || Ok(b)).unwrap();
+
+        assert_eq!(point_mismatch(&a_var, &a_var).unwrap(), None);
+        assert_eq!(
+            point_mismatch(&a_var, &b_var).unwrap(),
+            Some((a.into_affine(), b.into_affine()))
+        );
+
+        assert_points_equal("same", &a_var, &a_var).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_points_equal("different", &a_var, &b_var).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn skips_values_in_setup_mode() {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        cs.set_mode(SynthesisMode::Setup);
+        let a = G1Var::<Bls12_377_Parameters>::new_witness(cs.clone(), || {
+            Ok(G1Projective::prime_subgroup_generator())
+        })
+        .unwrap();
+        let b = G1Var::<Bls12_377_Parameters>::new_witness(cs.clone(), || {
+            Ok(G1Projective::prime_subgroup_generator())
+        })
+        .unwrap();
+        assert_eq!(point_mismatch(&a, &b).unwrap(), None);
+        assert_points_equal("setup", &a, &b).unwrap();
+        assert!(cs.num_constraints() > 0);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::YToBitGadget;
+use algebra::{
+    bls12_377::{
+        Fq as Bls12_377_Fq, Fq2 as Bls12_377_Fq2, FqParameters, G2Affine,
+        Parameters as Bls12_377_Parameters,
+    },
+    AffineCurve, BigInteger, FpParameters, PrimeField,
+};
+use r1cs_core::SynthesisError;
+use r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    bits::ToBitsGadget,
+    boolean::Boolean,
+    eq::EqGadget,
+    groups::bls12::G2Var,
+    Assignment, R1CSVar,
+};
+use tracing::{span, trace, Level};
+
+/// The number of bits of each coordinate of x in the compressed encoding
+const COORDINATE_BITS: usize = FqParameters::MODULUS_BITS as usize;
+
+/// Gadget which converts BLS12-377 G2 points to and from the compressed bit encoding used
+/// by the epoch SNARK when hashing validator public keys.
+///
+/// A point is encoded as the big endian bits of `x.c0`, followed by the big endian bits of
+/// `x.c1`, followed by the sign of `y`, i.e. whether it is lexicographically larger than
+/// `-y` (see `YToBitGadget`). The point at infinity cannot be encoded, which is not an issue
+/// for BLS public keys.
+pub struct G2CompressGadget;
+
+impl G2CompressGadget {
+    /// The number of bits of a compressed point
+    pub const COMPRESSED_BITS: usize = 2 * COORDINATE_BITS + 1;
+
+    /// Returns the compressed bits of the point
+    #[tracing::instrument(target = "r1cs")]
+    pub fn compress(
+        point: &G2Var<Bls12_377_Parameters>,
+    ) -> Result<Vec<Boolean<Bls12_377_Fq>>, SynthesisError> {
+        let mut x_0 = point.x.c0.to_bits_le()?;
+        let mut x_1 = point.x.c1.to_bits_le()?;
+        x_0.reverse();
+        x_1.reverse();
+        let y_bit = point.y_to_bit()?;
+        let mut output = Vec::with_capacity(Self::COMPRESSED_BITS);
+        output.extend_from_slice(&x_0);
+        output.extend_from_slice(&x_1);
+        output.push(y_bit);
+        Ok(output)
+    }
+
+    /// Returns the point encoded by the compressed bits. The point is enforced to be on the
+    /// curve and its compression to be the provided bits, but it is NOT checked to be in the
+    /// prime order subgroup.
+    ///
+    /// # Panics
+    ///
+    /// - If `bits.len() != G2CompressGadget::COMPRESSED_BITS`
+    #[tracing::instrument(target = "r1cs")]
+    pub fn decompress(
+        bits: &[Boolean<Bls12_377_Fq>],
+    ) -> Result<G2Var<Bls12_377_Parameters>, SynthesisError> {
+        let span = span!(Level::TRACE, "G2CompressGadget_decompress");
+        let _enter = span.enter();
+        assert_eq!(bits.len(), Self::COMPRESSED_BITS);
+
+        trace!("getting G2 point from bits");
+        let point = G2Var::new_variable_omit_prime_order_check(
+            bits.cs(),
+            || {
+                if bits.cs().is_in_setup_mode() {
+                    return Err(SynthesisError::AssignmentMissing);
+                }
+                let bits = bits
+                    .iter()
+                    .map(|bit| bit.value())
+                    .collect::<Result<Vec<bool>, _>>()?;
+                let c0 = fq_from_bits_be(&bits[..COORDINATE_BITS])?;
+                let c1 = fq_from_bits_be(&bits[COORDINATE_BITS..2 * COORDINATE_BITS])?;
+                let point = G2Affine::get_point_from_x(
+                    Bls12_377_Fq2::new(c0, c1),
+                    bits[2 * COORDINATE_BITS],
+                )
+                .get()?;
+                Ok(point.into_projective())
+            },
+            AllocationMode::Witness,
+        )?;
+
+        // allocating the point enforces that it is on the curve, so it remains to check
+        // that it is the point which was encoded
+        trace!("compressing the decompressed point");
+        for (a, b) in Self::compress(&point)?.iter().zip(bits) {
+            a.enforce_equal(b)?;
+        }
+
+        Ok(point)
+    }
+}
+
+fn fq_from_bits_be(bits: &[bool]) -> Result<Bls12_377_Fq, SynthesisError> {
+    Bls12_377_Fq::from_repr(<Bls12_377_Fq as PrimeField>::BigInt::from_bits(bits)).get()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_helpers::{print_unsatisfied_constraints, run_profile_constraints};
+    use algebra::{bls12_377::G2Projective, ProjectiveCurve, UniformRand};
+    use r1cs_core::ConstraintSystem;
+
+    #[test]
+    fn compress_roundtrip() {
+        run_profile_constraints(compress_roundtrip_inner);
+    }
+
+    #[tracing::instrument(target = "r1cs")]
+    fn compress_roundtrip_inner() {
+        let rng = &mut rand::thread_rng();
+        for _ in 0..5 {
+            let point = G2Projective::rand(rng);
+            let cs = ConstraintSystem::<Bls12_377_Fq>::new_ref();
+            let point_var =
+                G2Var::<Bls12_377_Parameters>::new_witness(cs.clone(), || Ok(point)).unwrap();
+
+            let bits = G2CompressGadget::compress(&point_var).unwrap();
+            assert_eq!(bits.len(), G2CompressGadget::COMPRESSED_BITS);
+            let decompressed = G2CompressGadget::decompress(&bits).unwrap();
+
+            print_unsatisfied_constraints(cs.clone());
+            assert!(cs.is_satisfied().unwrap());
+            assert_eq!(decompressed.value().unwrap(), point);
+        }
+    }
+
+    #[test]
+    fn sign_selects_the_point() {
+        let rng = &mut rand::thread_rng();
+        let point = G2Projective::rand(rng).into_affine();
+        let cs = ConstraintSystem::<Bls12_377_Fq>::new_ref();
+        let point_var =
+            G2Var::<Bls12_377_Parameters>::new_witness(cs.clone(), || Ok(point.into_projective()))
+                .unwrap();
+        let mut bits = G2CompressGadget::compress(&point_var).unwrap();
+
+        // flipping the sign bit encodes the other point with the same x
+        let last = bits.len() - 1;
+        bits[last] = bits[last].not();
+        let decompressed = G2CompressGadget::decompress(&bits).unwrap();
+
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(decompressed.value().unwrap(), (-point).into_projective());
+    }
+}
@@ -0,0 +1,121 @@
+//! Assertions for testing circuits built on top of the gadgets of this crate.
+//!
+//! These are the same checks used by the crate's internal tests, exposed so that
+//! downstream circuits can be tested with the same ergonomics.
+//!
+//! # Example
+//!
+//! ```rust
+//! use algebra::bls12_377::Fq;
+//! use bls_gadgets::gadget_test_harness::{assert_constraints_at_most, assert_satisfied};
+//! use r1cs_core::ConstraintSystem;
+//! use r1cs_std::{alloc::AllocVar, boolean::Boolean, eq::EqGadget};
+//!
+//! let cs = ConstraintSystem::<Fq>::new_ref();
+//! let a = Boolean::new_witness(cs.clone(), || Ok(true)).unwrap();
+//! let b = Boolean::new_witness(cs.clone(), || Ok(true)).unwrap();
+//! a.enforce_equal(&b).unwrap();
+//!
+//! assert_satisfied(&cs);
+//! assert_constraints_at_most(&cs, 3);
+//! ```
+use algebra::Field;
+use r1cs_core::ConstraintSystemRef;
+
+/// Panics if the constraint system has more than `max_constraints` constraints
+pub fn assert_constraints_at_most<F: Field>(cs: &ConstraintSystemRef<F>, max_constraints: usize) {
+    let num_constraints = cs.num_constraints();
+    assert!(
+        num_constraints <= max_constraints,
+        "expected at most {} constraints, got {}",
+        max_constraints,
+        num_constraints
+    );
+}
+
+/// Panics with the path of the first unsatisfied constraint if the constraint
+/// system is not satisfied
+pub fn assert_satisfied<F: Field>(cs: &ConstraintSystemRef<F>) {
+    if let Some(unsatisfied) = first_unsatisfied(cs) {
+        panic!("constraint system is not satisfied: {}", unsatisfied);
+    }
+}
+
+/// Panics if the constraint system is satisfied, or if the first unsatisfied constraint's
+/// path does not contain `reason`.
+///
+/// Constraint paths are only recorded when running under a `ConstraintLayer` tracing
+/// subscriber (e.g. via `run_profile_constraints`), otherwise they will be empty.
+pub fn assert_unsatisfied_with_reason<F: Field>(cs: &ConstraintSystemRef<F>, reason: &str) {
+    match first_unsatisfied(cs) {
+        Some(unsatisfied) => assert!(
+            unsatisfied.contains(reason),
+            "expected unsatisfied constraint matching \"{}\", got \"{}\"",
+            reason,
+            unsatisfied
+        ),
+        None => panic!(
+            "expected unsatisfied constraint matching \"{}\", but the constraint system is satisfied",
+            reason
+        ),
+    }
+}
+
+/// Returns the path of the first unsatisfied constraint, if any
+fn first_unsatisfied<F: Field>(cs: &ConstraintSystemRef<F>) -> Option<String> {
+    if cs.is_satisfied().expect("constraint system has no assignments") {
+        return None;
+    }
+    Some(
+        cs.which_is_unsatisfied()
+            .expect("constraint system has no assignments")
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::run_profile_constraints;
+
+    use algebra::bls12_377::Fq;
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::{alloc::AllocVar, boolean::Boolean, eq::EqGadget};
+
+    #[tracing::instrument(target = "r1cs")]
+    fn cs_enforce_equal(a: bool, b: bool) -> ConstraintSystemRef<Fq> {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let a = Boolean::new_witness(cs.clone(), || Ok(a)).unwrap();
+        let b = Boolean::new_witness(cs.clone(), || Ok(b)).unwrap();
+        a.enforce_equal(&b).unwrap();
+        cs
+    }
+
+    #[test]
+    fn satisfied() {
+        let cs = cs_enforce_equal(true, true);
+        assert_satisfied(&cs);
+        assert_constraints_at_most(&cs, cs.num_constraints());
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_constraints() {
+        let cs = cs_enforce_equal(true, true);
+        assert_constraints_at_most(&cs, cs.num_constraints() - 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_satisfied() {
+        assert_satisfied(&cs_enforce_equal(true, false));
+    }
+
+    #[test]
+    fn unsatisfied_with_reason() {
+        run_profile_constraints(|| {
+            let cs = cs_enforce_equal(true, false);
+            assert_unsatisfied_with_reason(&cs, "cs_enforce_equal");
+        });
+    }
+}
@@ -1,5 +1,5 @@
 use crate::utils::{bits_le_to_bytes_le, bytes_le_to_bits_le};
-use crate::YToBitGadget;
+use crate::{Blake2XofGadget, YToBitGadget};
 use algebra::{
     bls12_377::{Fq as Bls12_377_Fq, Parameters as Bls12_377_Parameters},
     curves::{
@@ -13,14 +13,13 @@ use algebra::{
 };
 use bls_crypto::{
     hashers::{
-        composite::{CompositeHasher, CRH},
+        composite::{COMPOSITE_HASHER, CRH},
         DirectHasher, Hasher,
     },
     SIG_DOMAIN,
 };
-use crypto_primitives::{
-    crh::{bowe_hopwood::constraints::CRHGadget as BHHash, FixedLengthCRHGadget},
-    prf::{blake2s::constraints::evaluate_blake2s_with_parameters, Blake2sWithParameterBlock},
+use crypto_primitives::crh::{
+    bowe_hopwood::constraints::CRHGadget as BHHash, FixedLengthCRH, FixedLengthCRHGadget,
 };
 use r1cs_core::SynthesisError;
 use r1cs_std::{
@@ -46,34 +45,6 @@ const SIGN_BIT_POSITION: usize = 383;
 // The bits from the hash which will be interpreted as the x coordinate of a group element
 const X_BITS: usize = 377;
 
-/// Parameters for Blake2x as specified in: https://blake2.net/blake2x.pdf
-/// • “Key length” is set to 0 (even if the root hash was keyed)
-/// • “Fanout” is set to 0 (unlimited)
-/// • “Maximal depth” is set to 0
-/// • “Leaf maximal byte length” is set to 32 for BLAKE2Xs, and 64 for BLAKE2Xb
-/// • “XOF digest length” is set to the length of the final output digest
-/// • “Node depth” is set to 0 (leaves)
-/// • “Inner hash byte length” is set to 32 for BLAKE2Xs and 64 for BLAKE2Xb
-fn blake2xs_params(
-    hash_length: u16,
-    offset: u32,
-    personalization: [u8; 8],
-) -> Blake2sWithParameterBlock {
-    Blake2sWithParameterBlock {
-        digest_length: 32,
-        key_length: 0,
-        fan_out: 0,
-        depth: 0,
-        leaf_length: 32,
-        node_offset: offset,
-        xof_digest_length: hash_length / 8, // need to convert to bytes
-        node_depth: 0,
-        inner_length: 32,
-        salt: [0; 8],
-        personalization,
-    }
-}
-
 /// Gadget which enforces correct calculation of hashing to group of arbitrary data, implementing
 /// the "try and increment" method. For more information on the method, refer to the [non-gadget
 /// implementation][hash_to_group].
@@ -114,12 +85,92 @@ impl HashToGroupGadget<Bls12_377_Parameters, Bls12_377_Fq> {
             Vec<Boolean<Bls12_377_Fq>>,
         ),
         SynthesisError,
+    > {
+        Self::enforce_hash_to_group_with_crh_parameters(
+            counter,
+            message,
+            extra_data,
+            generate_constraints_for_hash,
+            COMPOSITE_HASHER.parameters(),
+        )
+    }
+
+    /// Same as `enforce_hash_to_group`, but compresses the input with a Pedersen CRH
+    /// instantiated with the provided parameters instead of the default ones.
+    #[allow(clippy::type_complexity)]
+    #[tracing::instrument(target = "r1cs", skip(crh_parameters))]
+    pub fn enforce_hash_to_group_with_crh_parameters(
+        counter: UInt8<Bls12_377_Fq>,
+        message: &[UInt8<Bls12_377_Fq>],
+        extra_data: &[UInt8<Bls12_377_Fq>],
+        generate_constraints_for_hash: bool,
+        crh_parameters: &<CRH as FixedLengthCRH>::Parameters,
+    ) -> Result<
+        (
+            G1Var<Bls12_377_Parameters>,
+            Vec<Boolean<Bls12_377_Fq>>,
+            Vec<Boolean<Bls12_377_Fq>>,
+        ),
+        SynthesisError,
+    > {
+        Self::hash_to_group_in_domain(
+            SIG_DOMAIN,
+            counter,
+            message,
+            extra_data,
+            generate_constraints_for_hash,
+            crh_parameters,
+        )
+    }
+
+    /// Same as `enforce_hash_to_group`, but personalizes the XOF with `domain` instead of
+    /// `SIG_DOMAIN`, as `TryAndIncrementCIP22::hash_with_attempt_cip22` does for the domain.
+    /// The XOF helper proof only covers `SIG_DOMAIN`, so the hash is always constrained.
+    ///
+    /// # Panics
+    ///
+    /// If the domain is larger than 8 bytes
+    #[allow(clippy::type_complexity)]
+    #[tracing::instrument(target = "r1cs")]
+    pub fn enforce_hash_to_group_in_domain(
+        domain: &[u8],
+        counter: UInt8<Bls12_377_Fq>,
+        message: &[UInt8<Bls12_377_Fq>],
+        extra_data: &[UInt8<Bls12_377_Fq>],
+    ) -> Result<G1Var<Bls12_377_Parameters>, SynthesisError> {
+        let (hash, _, _) = Self::hash_to_group_in_domain(
+            domain,
+            counter,
+            message,
+            extra_data,
+            true,
+            COMPOSITE_HASHER.parameters(),
+        )?;
+        Ok(hash)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn hash_to_group_in_domain(
+        domain: &[u8],
+        counter: UInt8<Bls12_377_Fq>,
+        message: &[UInt8<Bls12_377_Fq>],
+        extra_data: &[UInt8<Bls12_377_Fq>],
+        generate_constraints_for_hash: bool,
+        crh_parameters: &<CRH as FixedLengthCRH>::Parameters,
+    ) -> Result<
+        (
+            G1Var<Bls12_377_Parameters>,
+            Vec<Boolean<Bls12_377_Fq>>,
+            Vec<Boolean<Bls12_377_Fq>>,
+        ),
+        SynthesisError,
     > {
         let span = span!(Level::TRACE, "enforce_hash_to_group",);
         let _enter = span.enter();
+        assert!(domain.len() <= 8, "domain length is too large");
 
         // compress the input
-        let crh_bits = Self::pedersen_hash(&message)?;
+        let crh_bits = Self::pedersen_hash(&message, crh_parameters)?;
 
         // combine the counter with the inner hash
         let mut input = counter.to_bits_le()?;
@@ -133,7 +184,7 @@ impl HashToGroupGadget<Bls12_377_Parameters, Bls12_377_Fq> {
 
         // Hash to bits
         let mut personalization = [0; 8];
-        personalization.copy_from_slice(SIG_DOMAIN);
+        personalization[..domain.len()].copy_from_slice(domain);
         // We want 378 random bits for hashing to curve, so we get 512 from the hash and will
         // discard any unneeded ones. We do not generate constraints.
         let xof_bits = hash_to_bits(&input, 512, personalization, generate_constraints_for_hash)?;
@@ -144,16 +195,17 @@ impl HashToGroupGadget<Bls12_377_Parameters, Bls12_377_Fq> {
         Ok((hash, crh_bits, xof_bits))
     }
 
-    /// Compress the input by passing it through a Pedersen hash
-    fn pedersen_hash(
+    /// Compress the input by passing it through a Pedersen hash. Returns the LE bits of the
+    /// hash's x coordinate padded to whole bytes, i.e. the bits of `CompositeHasher::crh`
+    pub fn pedersen_hash(
         input: &[UInt8<Bls12_377_Fq>],
+        crh_parameters: &<CRH as FixedLengthCRH>::Parameters,
     ) -> Result<Vec<Boolean<Bls12_377_Fq>>, SynthesisError> {
-        // We setup by getting the Parameters over the provided CRH
+        // The CRH's parameters are constants in the circuit
         let crh_params =
             <BHHash<EdwardsParameters, _> as FixedLengthCRHGadget<CRH, _>>::ParametersVar::new_constant(
                 input.cs(),
-                CompositeHasher::<CRH>::setup_crh()
-                    .map_err(|_| SynthesisError::AssignmentMissing)?,
+                crh_parameters,
             )?;
 
         let pedersen_hash =
@@ -201,42 +253,24 @@ pub fn hash_to_bits<F: PrimeField>(
     let _enter = span.enter();
     let xof_bits = if generate_constraints_for_hash {
         trace!("generating hash with constraints");
-        let message = message.to_vec();
         // Blake2s outputs 256 bit hashes so the desired output hash length
         // must be a multiple of that.
         assert_eq!(hash_length % 256, 0, "invalid hash length size");
-        let iterations = hash_length / 256;
-        let mut xof_bits = Vec::new();
-        // Run Blake on the message N times, each time offset by `i`
-        // to get a `hash_length` hash. The hash is in LE.
-        for i in 0..iterations {
-            trace!(blake_iteration = i);
-            // calculate the hash (Vec<Boolean>)
-            let blake2s_parameters = blake2xs_params(hash_length, i.into(), personalization);
-
-            let xof_result =
-                evaluate_blake2s_with_parameters(&message, &blake2s_parameters.parameters())?;
-            // convert hash result to LE bits
-            let xof_bits_i = xof_result
-                .into_iter()
-                .map(|n| n.to_bits_le())
-                .flatten()
-                .collect::<Vec<Boolean<F>>>();
-            xof_bits.extend_from_slice(&xof_bits_i);
-        }
-        xof_bits
+        Blake2XofGadget::evaluate(message, hash_length / 8, personalization)?
     } else {
         trace!("generating hash without constraints");
         let bits = if message.cs().is_in_setup_mode() {
-            vec![false; 512]
+            vec![false; hash_length as usize]
         } else {
             let message = message
                 .iter()
                 .map(|m| m.value())
                 .collect::<Result<Vec<_>, _>>()?;
             let message = bits_le_to_bytes_le(&message);
-            let hash_result = DirectHasher.xof(&personalization, &message, 64).unwrap();
-            bytes_le_to_bits_le(&hash_result, 512)
+            let hash_result = DirectHasher
+                .xof(&personalization, &message, hash_length as usize / 8)
+                .unwrap();
+            bytes_le_to_bits_le(&hash_result, hash_length as usize)
         };
 
         bits.iter()
@@ -325,7 +359,7 @@ impl<P: Bls12Parameters> HashToGroupGadget<P, Bls12_377_Fq> {
 
     /// Checks that the result is equal to the given point
     /// multiplied by the cofactor in g1
-    fn scale_by_cofactor_g1(
+    pub(crate) fn scale_by_cofactor_g1(
         p: &G1Var<Bls12_377_Parameters>,
     ) -> Result<G1Var<Bls12_377_Parameters>, SynthesisError>
     where
@@ -352,7 +386,13 @@ mod test {
     use crate::utils::test_helpers::{print_unsatisfied_constraints, run_profile_constraints};
 
     use algebra::bls12_377;
-    use bls_crypto::hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22;
+    use bls_crypto::{
+        hash_to_curve::try_and_increment_cip22::{
+            TryAndIncrementCIP22, COMPOSITE_HASH_TO_G1_CIP22,
+        },
+        hashers::composite::{CRHParameters, CompositeHasher},
+        HashToCurve, ROTATION_DOMAIN,
+    };
     use r1cs_core::ConstraintSystem;
     use r1cs_std::bits::uint8::UInt8;
     use rand::{thread_rng, RngCore};
@@ -376,6 +416,72 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_hash_to_group_with_crh_parameters() {
+        let parameters = CRHParameters::generate(b"otherprs", b"other seed").unwrap();
+        let hasher = CompositeHasher::<CRH>::from_parameters(parameters.0.clone());
+        let try_and_increment = TryAndIncrementCIP22::<_, bls12_377::g1::Parameters>::new(&hasher);
+        let (expected_hash, attempt) = try_and_increment
+            .hash_with_attempt_cip22(SIG_DOMAIN, b"hello", b"world")
+            .unwrap();
+
+        let cs = ConstraintSystem::<bls12_377::Fq>::new_ref();
+        let counter = UInt8::new_witness(cs.clone(), || Ok(attempt as u8)).unwrap();
+        let input = b"hello"
+            .iter()
+            .map(|num| UInt8::new_witness(cs.clone(), || Ok(num)).unwrap())
+            .collect::<Vec<_>>();
+        let extra_input = b"world"
+            .iter()
+            .map(|num| UInt8::new_witness(cs.clone(), || Ok(num)).unwrap())
+            .collect::<Vec<_>>();
+        let hash = HashToGroupGadget::<bls12_377::Parameters, bls12_377::Fq>::enforce_hash_to_group_with_crh_parameters(
+            counter,
+            &input,
+            &extra_input,
+            true,
+            &parameters.0,
+        )
+        .unwrap()
+        .0;
+
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(expected_hash, hash.value().unwrap());
+    }
+
+    #[test]
+    fn test_hash_to_group_in_domain() {
+        let (expected_hash, attempt) = COMPOSITE_HASH_TO_G1_CIP22
+            .hash_with_attempt_cip22(ROTATION_DOMAIN, b"hello", &[])
+            .unwrap();
+
+        let cs = ConstraintSystem::<bls12_377::Fq>::new_ref();
+        let counter = UInt8::new_witness(cs.clone(), || Ok(attempt as u8)).unwrap();
+        let input = b"hello"
+            .iter()
+            .map(|num| UInt8::new_witness(cs.clone(), || Ok(num)).unwrap())
+            .collect::<Vec<_>>();
+        let hash = HashToGroupGadget::<bls12_377::Parameters, bls12_377::Fq>::enforce_hash_to_group_in_domain(
+            ROTATION_DOMAIN,
+            counter,
+            &input,
+            &[],
+        )
+        .unwrap();
+
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(expected_hash, hash.value().unwrap());
+        // the domain separates the hashes
+        assert_ne!(
+            COMPOSITE_HASH_TO_G1_CIP22
+                .hash(SIG_DOMAIN, b"hello", &[])
+                .unwrap(),
+            expected_hash
+        );
+    }
+
     #[tracing::instrument(target = "r1cs")]
     fn hash_to_group(input: &[u8], extra_input: &[u8]) {
         let try_and_increment = &*COMPOSITE_HASH_TO_G1_CIP22;
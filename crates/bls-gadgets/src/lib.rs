@@ -2,18 +2,87 @@
 //!
 //! This module provides gadgets for constructing R1CS involving BLS Signatures
 //! over the BLS12-377 curve.
+//!
+//! The gadgets are grouped behind features, all enabled by default, so that circuits which
+//! only need some of them can cut their compile times:
+//!
+//! - `bls`: the BLS signature verification gadgets, of which `CommitteeAggregationGadget` also
+//!   requires `hash-gadgets`
+//! - `hash-gadgets`: the hash-to-group, Blake2Xof, transcript and Poseidon gadgets, which
+//!   enable `y-to-bit`
+//! - `y-to-bit`: the y-to-bit and G2 compression gadgets
+//!
+//! The bitmap, bits and diagnostics modules are always available.
 
+#[cfg(feature = "bls")]
 mod bls;
+#[cfg(feature = "bls")]
 pub use bls::BlsVerifyGadget;
 
+#[cfg(feature = "bls")]
+mod prepared;
+#[cfg(feature = "bls")]
+pub use prepared::{NeutralPreparedGadget, PreparedSelectGadget};
+
+#[cfg(feature = "bls")]
+mod min_pk;
+#[cfg(feature = "bls")]
+pub use min_pk::MinPkBlsVerifyGadget;
+
+#[cfg(all(feature = "bls", feature = "hash-gadgets"))]
+mod committee;
+#[cfg(all(feature = "bls", feature = "hash-gadgets"))]
+pub use committee::CommitteeAggregationGadget;
+
+#[cfg(feature = "bls")]
+mod rotation;
+#[cfg(feature = "bls")]
+pub use rotation::KeyRotationGadget;
+
 mod bitmap;
-pub(crate) use bitmap::Bitmap;
+pub use bitmap::{Bitmap, BitmapVar};
 
+#[cfg(feature = "y-to-bit")]
 mod y_to_bit;
+#[cfg(feature = "y-to-bit")]
 pub use y_to_bit::{FpUtils, YToBitGadget};
 
+#[cfg(feature = "y-to-bit")]
+mod g2_compress;
+#[cfg(feature = "y-to-bit")]
+pub use g2_compress::G2CompressGadget;
+
+#[cfg(feature = "hash-gadgets")]
+mod blake2xof;
+#[cfg(feature = "hash-gadgets")]
+pub use blake2xof::Blake2XofGadget;
+
+#[cfg(feature = "hash-gadgets")]
 mod hash_to_group;
+#[cfg(feature = "hash-gadgets")]
 pub use hash_to_group::{hash_to_bits, HashToGroupGadget};
 
+#[cfg(feature = "hash-gadgets")]
+mod sw_hash_to_group;
+#[cfg(feature = "hash-gadgets")]
+pub use sw_hash_to_group::SWHashToGroupGadget;
+
+#[cfg(feature = "hash-gadgets")]
+mod transcript;
+#[cfg(feature = "hash-gadgets")]
+pub use transcript::TranscriptVar;
+
+#[cfg(feature = "hash-gadgets")]
+mod poseidon;
+#[cfg(feature = "hash-gadgets")]
+pub use poseidon::PoseidonGadget;
+
+/// Conversions between bytes, bits and field elements, natively and in the constraint system
+pub mod bits;
+
 /// Utility functions which do not involve generating constraints
 pub mod utils;
+
+pub mod diagnostics;
+
+pub mod gadget_test_harness;
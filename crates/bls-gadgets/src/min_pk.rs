@@ -0,0 +1,194 @@
+use crate::Bitmap;
+use algebra::{PairingEngine, PrimeField, ProjectiveCurve};
+use r1cs_core::SynthesisError;
+use r1cs_std::{
+    alloc::AllocationMode, boolean::Boolean, eq::EqGadget, fields::fp::FpVar, fields::FieldVar,
+    groups::CurveVar, pairing::PairingVar, R1CSVar,
+};
+use std::marker::PhantomData;
+use std::ops::AddAssign;
+use tracing::{span, trace, Level};
+
+/// BLS Signature Verification Gadget for the "swapped" (`MinPk`) scheme, where the
+/// public keys are on G1 and the signatures and message hashes are on G2.
+///
+/// See `BlsVerifyGadget` for the scheme with signatures on G1.
+pub struct MinPkBlsVerifyGadget<E, F, P> {
+    /// The curve being used
+    pairing_engine_type: PhantomData<E>,
+    /// The field we're operating on
+    constraint_field_type: PhantomData<F>,
+    /// The pairing gadget we use, which MUST match our pairing engine
+    pairing_gadget_type: PhantomData<P>,
+}
+
+impl<E, F, P> MinPkBlsVerifyGadget<E, F, P>
+where
+    E: PairingEngine,
+    F: PrimeField,
+    P: PairingVar<E, F>,
+    P::G1Var: for<'a> AddAssign<&'a P::G1Var>,
+{
+    /// Enforces verification of a BLS Signature against a list of public keys and a bitmap
+    /// indicating which of these pubkeys signed, allowing at most `maximum_non_signers`
+    /// of them to not sign.
+    #[tracing::instrument(target = "r1cs")]
+    pub fn verify(
+        pub_keys: &[P::G1Var],
+        signed_bitmap: &[Boolean<F>],
+        message_hash: &P::G2Var,
+        signature: &P::G2Var,
+        maximum_non_signers: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        let span = span!(Level::TRACE, "MinPkBlsVerifyGadget_verify");
+        let _enter = span.enter();
+
+        trace!("enforcing bitmap");
+        signed_bitmap.enforce_maximum_occurrences_in_bitmap(maximum_non_signers, false)?;
+        let aggregated_pk = Self::enforce_aggregated_pubkeys(pub_keys, signed_bitmap)?;
+
+        let prepared_aggregated_pk = P::prepare_g1(&aggregated_pk)?;
+        let prepared_message_hash = P::prepare_g2(message_hash)?;
+        let prepared_signature = P::prepare_g2(signature)?;
+
+        // Allocate the generator on G1 and negate it for the purpose of verification
+        let g1_generator = P::G1Var::new_variable_omit_prime_order_check(
+            signature.cs(),
+            || Ok(E::G1Projective::prime_subgroup_generator()),
+            AllocationMode::Constant,
+        )?;
+        let prepared_g1_neg_generator = P::prepare_g1(&g1_generator.negate()?)?;
+
+        // e(g_1^-1, σ) * e(apk, H(m)) == 1_{G_T}
+        trace!("enforcing BLS equation");
+        let bls_equation = P::product_of_pairings(
+            &[prepared_g1_neg_generator, prepared_aggregated_pk],
+            &[prepared_signature, prepared_message_hash],
+        )?;
+        bls_equation.enforce_equal(&P::GTVar::one())?;
+
+        Ok(())
+    }
+
+    /// Returns a gadget which checks that an aggregate pubkey is correctly calculated
+    /// by the sum of the pub keys which had a 1 in the bitmap
+    ///
+    /// # Panics
+    /// If signed_bitmap length != pub_keys length
+    #[tracing::instrument(target = "r1cs")]
+    pub fn enforce_aggregated_pubkeys(
+        pub_keys: &[P::G1Var],
+        signed_bitmap: &[Boolean<F>],
+    ) -> Result<P::G1Var, SynthesisError> {
+        // Bitmap and Pubkeys must be of the same length
+        assert_eq!(signed_bitmap.len(), pub_keys.len());
+
+        let mut aggregated_pk = P::G1Var::zero();
+        for (pk, bit) in pub_keys.iter().zip(signed_bitmap) {
+            // If bit = 1, add pk
+            let adder = bit.select(pk, &P::G1Var::zero())?;
+            aggregated_pk += &adder;
+        }
+
+        Ok(aggregated_pk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::{print_unsatisfied_constraints, run_profile_constraints};
+
+    use algebra::{
+        bls12_377::{Bls12_377, Fr as Bls12_377Fr, G1Projective, G2Projective},
+        bw6_761::Fr as BW6_761Fr,
+        UniformRand,
+    };
+    use r1cs_core::{ConstraintSystem, ConstraintSystemRef};
+    use r1cs_std::{alloc::AllocVar, bls12_377::PairingVar as Bls12_377PairingGadget};
+
+    type Gadget = MinPkBlsVerifyGadget<Bls12_377, BW6_761Fr, Bls12_377PairingGadget>;
+
+    #[tracing::instrument(target = "r1cs")]
+    fn cs_verify(
+        message_hash: G2Projective,
+        pub_keys: &[G1Projective],
+        signature: G2Projective,
+        bitmap: &[bool],
+        num_non_signers: u64,
+    ) -> ConstraintSystemRef<BW6_761Fr> {
+        let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+        let alloc_g2 = |point: G2Projective| {
+            <Bls12_377PairingGadget as PairingVar<_, _>>::G2Var::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || Ok(point),
+                AllocationMode::Witness,
+            )
+            .unwrap()
+        };
+        let message_hash = alloc_g2(message_hash);
+        let signature = alloc_g2(signature);
+        let pub_keys = pub_keys
+            .iter()
+            .map(|pk| {
+                <Bls12_377PairingGadget as PairingVar<_, _>>::G1Var::new_variable_omit_prime_order_check(
+                    cs.clone(),
+                    || Ok(*pk),
+                    AllocationMode::Witness,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let bitmap = bitmap
+            .iter()
+            .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)).unwrap())
+            .collect::<Vec<_>>();
+        let maximum_non_signers =
+            FpVar::new_witness(cs.clone(), || Ok(BW6_761Fr::from(num_non_signers))).unwrap();
+
+        Gadget::verify(
+            &pub_keys,
+            &bitmap,
+            &message_hash,
+            &signature,
+            &maximum_non_signers,
+        )
+        .unwrap();
+        cs
+    }
+
+    #[test]
+    fn verifies_min_pk_signatures() {
+        run_profile_constraints(verifies_min_pk_signatures_inner);
+    }
+    #[tracing::instrument(target = "r1cs")]
+    fn verifies_min_pk_signatures_inner() {
+        let rng = &mut rand::thread_rng();
+        let secret_keys = (0..2).map(|_| Bls12_377Fr::rand(rng)).collect::<Vec<_>>();
+        let pub_keys = secret_keys
+            .iter()
+            .map(|sk| G1Projective::prime_subgroup_generator().mul(*sk))
+            .collect::<Vec<_>>();
+        let message_hash = G2Projective::rand(rng);
+
+        // only the first key signs, which is allowed with 1 non signer
+        let signature = message_hash.mul(secret_keys[0]);
+        let cs = cs_verify(message_hash, &pub_keys, signature, &[true, false], 1);
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+
+        // not allowed with 0 non signers
+        let cs = cs_verify(message_hash, &pub_keys, signature, &[true, false], 0);
+        assert!(!cs.is_satisfied().unwrap());
+
+        // a random signature fails
+        let cs = cs_verify(
+            message_hash,
+            &pub_keys,
+            G2Projective::rand(rng),
+            &[true, false],
+            1,
+        );
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,95 @@
+use algebra::PrimeField;
+use bls_crypto::poseidon::{PoseidonParameters, RATE, WIDTH};
+use r1cs_core::SynthesisError;
+use r1cs_std::{fields::fp::FpVar, prelude::*};
+use tracing::{span, Level};
+
+/// Gadget which enforces the Poseidon hash of field elements, as computed by
+/// `bls_crypto::PoseidonParameters::hash`
+pub struct PoseidonGadget;
+
+impl PoseidonGadget {
+    /// Enforces the hash of the inputs with the parameters, which are constants of the circuit
+    pub fn hash<F: PrimeField>(
+        parameters: &PoseidonParameters<F>,
+        inputs: &[FpVar<F>],
+    ) -> Result<FpVar<F>, SynthesisError> {
+        let span = span!(Level::TRACE, "PoseidonGadget_hash");
+        let _enter = span.enter();
+
+        let mut state = vec![FpVar::zero(); WIDTH];
+        state[0] = FpVar::constant(F::from(inputs.len() as u64));
+        if inputs.is_empty() {
+            Self::permute(parameters, &mut state)?;
+        }
+        for chunk in inputs.chunks(RATE) {
+            for (element, input) in state[1..].iter_mut().zip(chunk) {
+                *element = &*element + input;
+            }
+            Self::permute(parameters, &mut state)?;
+        }
+        Ok(state[1].clone())
+    }
+
+    fn permute<F: PrimeField>(
+        parameters: &PoseidonParameters<F>,
+        state: &mut Vec<FpVar<F>>,
+    ) -> Result<(), SynthesisError> {
+        let alpha = [parameters.alpha()];
+        let num_rounds = parameters.full_rounds() + parameters.partial_rounds();
+        for round in 0..num_rounds {
+            for (i, element) in state.iter_mut().enumerate() {
+                *element = &*element + parameters.round_constants()[round * WIDTH + i];
+            }
+
+            if parameters.is_full_round(round) {
+                for element in state.iter_mut() {
+                    *element = element.pow_by_constant(&alpha)?;
+                }
+            } else {
+                state[0] = state[0].pow_by_constant(&alpha)?;
+            }
+
+            // the matrix is constant, so mixing is linear and adds no constraints
+            *state = parameters
+                .mds()
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(state.iter())
+                        .fold(FpVar::zero(), |mixed, (entry, element)| {
+                            mixed + element * *entry
+                        })
+                })
+                .collect();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::print_unsatisfied_constraints;
+    use algebra::{bls12_377::Fq, UniformRand};
+    use r1cs_core::ConstraintSystem;
+
+    #[test]
+    fn matches_native_hash() {
+        let rng = &mut rand::thread_rng();
+        let parameters = PoseidonParameters::<Fq>::new(8, 60, 5, b"test").unwrap();
+        for num_inputs in 0..6 {
+            let inputs = (0..num_inputs).map(|_| Fq::rand(rng)).collect::<Vec<_>>();
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            let input_vars = inputs
+                .iter()
+                .map(|input| FpVar::new_witness(cs.clone(), || Ok(*input)).unwrap())
+                .collect::<Vec<_>>();
+            let hash = PoseidonGadget::hash(&parameters, &input_vars).unwrap();
+
+            print_unsatisfied_constraints(cs.clone());
+            assert!(cs.is_satisfied().unwrap());
+            assert_eq!(hash.value().unwrap(), parameters.hash(&inputs));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use algebra::{curves::bls12::Bls12Parameters, PrimeField};
+use r1cs_core::SynthesisError;
+use r1cs_std::{
+    boolean::Boolean,
+    fields::{fp::FpVar, FieldVar},
+    groups::bls12::{G1PreparedVar, G2PreparedVar},
+    select::CondSelectGadget,
+};
+
+/// Conditional selection over prepared pairing inputs, which zexe's prepared variables do not
+/// implement. Selecting after preparing costs a few constraints per coordinate, while
+/// preparing a selected point repeats the whole preparation.
+pub trait PreparedSelectGadget<F: PrimeField>: Sized {
+    /// Returns `true_value` if `cond` is set, `false_value` otherwise
+    fn conditionally_select(
+        cond: &Boolean<F>,
+        true_value: &Self,
+        false_value: &Self,
+    ) -> Result<Self, SynthesisError>;
+}
+
+/// Prepared G1 inputs which can be replaced with a neutral element, i.e. one whose pairing
+/// with any prepared G2 input is the identity of G_T
+pub trait NeutralPreparedGadget<F: PrimeField>: PreparedSelectGadget<F> {
+    /// Returns the prepared input if `keep` is set, the neutral element otherwise
+    fn select_or_neutral(&self, keep: &Boolean<F>) -> Result<Self, SynthesisError>;
+}
+
+impl<P: Bls12Parameters> PreparedSelectGadget<P::Fp> for G1PreparedVar<P> {
+    fn conditionally_select(
+        cond: &Boolean<P::Fp>,
+        true_value: &Self,
+        false_value: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let mut selected = true_value.clone();
+        selected.0.x = FpVar::conditionally_select(cond, &true_value.0.x, &false_value.0.x)?;
+        selected.0.y = FpVar::conditionally_select(cond, &true_value.0.y, &false_value.0.y)?;
+        selected.0.infinity =
+            Boolean::conditionally_select(cond, &true_value.0.infinity, &false_value.0.infinity)?;
+        Ok(selected)
+    }
+}
+
+impl<P: Bls12Parameters> NeutralPreparedGadget<P::Fp> for G1PreparedVar<P> {
+    /// The neutral element has both coordinates set to zero. The Miller loop then only
+    /// multiplies by line evaluations whose squares lie in Fp2, which the final exponentiation
+    /// maps to one since `p^2 - 1` and 2 both divide `(p^12 - 1) / r`.
+    fn select_or_neutral(&self, keep: &Boolean<P::Fp>) -> Result<Self, SynthesisError> {
+        let zero = FpVar::zero();
+        let mut selected = self.clone();
+        selected.0.x = FpVar::conditionally_select(keep, &self.0.x, &zero)?;
+        selected.0.y = FpVar::conditionally_select(keep, &self.0.y, &zero)?;
+        selected.0.infinity = keep.not().or(&self.0.infinity)?;
+        Ok(selected)
+    }
+}
+
+impl<P: Bls12Parameters> PreparedSelectGadget<P::Fp> for G2PreparedVar<P> {
+    /// # Panics
+    /// If the inputs have different numbers of line coefficients, which cannot happen for
+    /// inputs prepared for the same curve
+    fn conditionally_select(
+        cond: &Boolean<P::Fp>,
+        true_value: &Self,
+        false_value: &Self,
+    ) -> Result<Self, SynthesisError> {
+        assert_eq!(true_value.ell_coeffs.len(), false_value.ell_coeffs.len());
+        let ell_coeffs = true_value
+            .ell_coeffs
+            .iter()
+            .zip(&false_value.ell_coeffs)
+            .map(|((true_0, true_1), (false_0, false_1))| {
+                Ok((
+                    CondSelectGadget::conditionally_select(cond, true_0, false_0)?,
+                    CondSelectGadget::conditionally_select(cond, true_1, false_1)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+        Ok(G2PreparedVar { ell_coeffs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlsVerifyGadget;
+    use algebra::{
+        bls12_377::{Bls12_377, Fr, G1Projective, G2Projective},
+        bw6_761::Fr as BW6_761Fr,
+        ProjectiveCurve, UniformRand,
+    };
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::{
+        alloc::{AllocVar, AllocationMode},
+        bls12_377::{G1Var, G2Var, PairingVar as Bls12_377PairingGadget},
+        pairing::PairingVar,
+        R1CSVar,
+    };
+
+    type BlsGadget = BlsVerifyGadget<Bls12_377, BW6_761Fr, Bls12_377PairingGadget>;
+
+    #[test]
+    fn skips_absent_pairs() {
+        let rng = &mut rand::thread_rng();
+        let secret_key = Fr::rand(rng);
+        let public_key = G2Projective::prime_subgroup_generator().mul(secret_key);
+        let message_hash = G1Projective::rand(rng);
+        let signature = message_hash.mul(secret_key);
+        // a pair which the signature does not cover
+        let absent = (G2Projective::rand(rng), G1Projective::rand(rng));
+
+        for (present, satisfied) in &[(false, true), (true, false)] {
+            let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+            let public_keys = [public_key, absent.0]
+                .iter()
+                .map(|pk| {
+                    let pk = G2Var::new_variable_omit_prime_order_check(
+                        cs.clone(),
+                        || Ok(*pk),
+                        AllocationMode::Witness,
+                    )
+                    .unwrap();
+                    Bls12_377PairingGadget::prepare_g2(&pk).unwrap()
+                })
+                .collect::<Vec<_>>();
+            let message_hashes = [message_hash, absent.1]
+                .iter()
+                .map(|hash| {
+                    let hash = G1Var::new_variable_omit_prime_order_check(
+                        cs.clone(),
+                        || Ok(*hash),
+                        AllocationMode::Witness,
+                    )
+                    .unwrap();
+                    Bls12_377PairingGadget::prepare_g1(&hash).unwrap()
+                })
+                .collect::<Vec<_>>();
+            let present = [
+                Boolean::constant(true),
+                Boolean::new_witness(cs.clone(), || Ok(*present)).unwrap(),
+            ];
+            let signature = G1Var::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || Ok(signature),
+                AllocationMode::Witness,
+            )
+            .unwrap();
+
+            BlsGadget::batch_verify_prepared_present(
+                &public_keys,
+                &message_hashes,
+                &present,
+                &signature,
+            )
+            .unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), *satisfied);
+        }
+    }
+
+    #[test]
+    fn selects_prepared_inputs() {
+        let rng = &mut rand::thread_rng();
+        let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+        let g1 = (0..2)
+            .map(|_| {
+                let point = G1Var::new_witness(cs.clone(), || Ok(G1Projective::rand(rng))).unwrap();
+                Bls12_377PairingGadget::prepare_g1(&point).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let g2 = (0..2)
+            .map(|_| {
+                let point = G2Var::new_witness(cs.clone(), || Ok(G2Projective::rand(rng))).unwrap();
+                Bls12_377PairingGadget::prepare_g2(&point).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for cond in &[true, false] {
+            let bit = Boolean::new_witness(cs.clone(), || Ok(*cond)).unwrap();
+            let expected = if *cond { 0 } else { 1 };
+
+            let selected =
+                PreparedSelectGadget::conditionally_select(&bit, &g1[0], &g1[1]).unwrap();
+            assert_eq!(
+                selected.0.x.value().unwrap(),
+                g1[expected].0.x.value().unwrap()
+            );
+            assert_eq!(
+                selected.0.y.value().unwrap(),
+                g1[expected].0.y.value().unwrap()
+            );
+
+            let selected =
+                PreparedSelectGadget::conditionally_select(&bit, &g2[0], &g2[1]).unwrap();
+            for (coeffs, expected) in selected.ell_coeffs.iter().zip(&g2[expected].ell_coeffs) {
+                assert_eq!(coeffs.0.value().unwrap(), expected.0.value().unwrap());
+                assert_eq!(coeffs.1.value().unwrap(), expected.1.value().unwrap());
+            }
+        }
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::BlsVerifyGadget;
+use algebra::{PairingEngine, PrimeField, ProjectiveCurve};
+use r1cs_core::SynthesisError;
+use r1cs_std::{
+    alloc::AllocationMode, boolean::Boolean, groups::CurveVar, pairing::PairingVar, R1CSVar,
+};
+use std::marker::PhantomData;
+use std::ops::AddAssign;
+use tracing::{span, Level};
+
+/// Verifies key rotations (see `bls_crypto::KeyRotation`) within an epoch transition.
+///
+/// Each validator slot can be rotated to a new key, if the slot's old key signed the handoff
+/// to the new key. The handoff signatures of all the rotated slots are aggregated by the
+/// prover and checked with a single product of pairings:
+///
+/// `e(σ + Σ_{i not rotated} H(m_i), g_2^-1) * Π_i e(H(m_i), rotated_i ? pk_i : g_2) == 1`
+///
+/// where slots which are not rotated are paired with the generator (and cancelled out by
+/// their hash being added to the signature) instead of the identity, so that the point at
+/// infinity is never prepared for a pairing. Every slot therefore costs a pairing, regardless
+/// of whether it is rotated.
+pub struct KeyRotationGadget<E, F, P> {
+    pairing_engine_type: PhantomData<E>,
+    constraint_field_type: PhantomData<F>,
+    pairing_gadget_type: PhantomData<P>,
+}
+
+impl<E, F, P> KeyRotationGadget<E, F, P>
+where
+    E: PairingEngine,
+    F: PrimeField,
+    P: PairingVar<E, F>,
+    P::G2Var: for<'a> AddAssign<&'a P::G2Var>,
+    P::G1Var: for<'a> AddAssign<&'a P::G1Var>,
+{
+    /// Enforces that the old key of every slot which has a 1 in `rotated` signed the handoff
+    /// to its new key, and returns the validator set after the rotations.
+    ///
+    /// `handoff_hashes` are the hashes of each slot's handoff message (see
+    /// `bls_crypto::KeyRotation::hash_handoff`), which the caller MUST constrain to be
+    /// computed from the slot's old and new keys, otherwise a rotation can be forged with
+    /// the identity as both the hash and the signature. `epoch_snark::KeyRotations` hashes
+    /// them from the keys' bits with `HashToGroupGadget::enforce_hash_to_group_in_domain`.
+    ///
+    /// # Panics
+    /// If `pub_keys`, `new_pub_keys`, `rotated` and `handoff_hashes` have different lengths
+    #[tracing::instrument(target = "r1cs")]
+    pub fn enforce_rotations(
+        pub_keys: &[P::G2Var],
+        new_pub_keys: &[P::G2Var],
+        rotated: &[Boolean<F>],
+        handoff_hashes: &[P::G1Var],
+        aggregated_signature: &P::G1Var,
+    ) -> Result<Vec<P::G2Var>, SynthesisError> {
+        let span = span!(Level::TRACE, "KeyRotationGadget_enforce_rotations");
+        let _enter = span.enter();
+        assert_eq!(pub_keys.len(), new_pub_keys.len());
+        assert_eq!(pub_keys.len(), rotated.len());
+        assert_eq!(pub_keys.len(), handoff_hashes.len());
+
+        let g2_generator = P::G2Var::new_variable_omit_prime_order_check(
+            aggregated_signature.cs(),
+            || Ok(E::G2Projective::prime_subgroup_generator()),
+            AllocationMode::Constant,
+        )?;
+
+        let mut signature = aggregated_signature.clone();
+        let mut signing_keys = Vec::with_capacity(pub_keys.len());
+        let mut rotated_keys = Vec::with_capacity(pub_keys.len());
+        for (((pk, new_pk), is_rotated), hash) in pub_keys
+            .iter()
+            .zip(new_pub_keys)
+            .zip(rotated)
+            .zip(handoff_hashes)
+        {
+            signature += &is_rotated.select(&P::G1Var::zero(), hash)?;
+            signing_keys.push(is_rotated.select(pk, &g2_generator)?);
+            rotated_keys.push(is_rotated.select(new_pk, pk)?);
+        }
+
+        BlsVerifyGadget::<E, F, P>::batch_verify(&signing_keys, handoff_hashes, &signature)?;
+
+        Ok(rotated_keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::{print_unsatisfied_constraints, run_profile_constraints};
+
+    use algebra::{
+        bls12_377::{Bls12_377, Fr as Bls12_377Fr, G1Projective, G2Projective},
+        bw6_761::Fr as BW6_761Fr,
+        UniformRand, Zero,
+    };
+    use r1cs_core::{ConstraintSystem, ConstraintSystemRef};
+    use r1cs_std::{alloc::AllocVar, bls12_377::PairingVar as Bls12_377PairingGadget};
+
+    type Gadget = KeyRotationGadget<Bls12_377, BW6_761Fr, Bls12_377PairingGadget>;
+    type G2Var = <Bls12_377PairingGadget as PairingVar<Bls12_377, BW6_761Fr>>::G2Var;
+    type G1Var = <Bls12_377PairingGadget as PairingVar<Bls12_377, BW6_761Fr>>::G1Var;
+
+    // allocates the arguments and returns the constraint system and the rotated keys
+    fn cs_rotate(
+        pub_keys: &[G2Projective],
+        new_pub_keys: &[G2Projective],
+        rotated: &[bool],
+        handoff_hashes: &[G1Projective],
+        signature: G1Projective,
+    ) -> (ConstraintSystemRef<BW6_761Fr>, Vec<G2Projective>) {
+        let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+        let alloc_g2 = |points: &[G2Projective]| {
+            points
+                .iter()
+                .map(|point| G2Var::new_witness(cs.clone(), || Ok(*point)).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let alloc_g1 = |point: G1Projective| {
+            G1Var::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || Ok(point),
+                AllocationMode::Witness,
+            )
+            .unwrap()
+        };
+
+        let pub_keys = alloc_g2(pub_keys);
+        let new_pub_keys = alloc_g2(new_pub_keys);
+        let rotated = rotated
+            .iter()
+            .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)).unwrap())
+            .collect::<Vec<_>>();
+        let handoff_hashes = handoff_hashes
+            .iter()
+            .map(|hash| alloc_g1(*hash))
+            .collect::<Vec<_>>();
+        let signature = alloc_g1(signature);
+
+        let rotated_keys = Gadget::enforce_rotations(
+            &pub_keys,
+            &new_pub_keys,
+            &rotated,
+            &handoff_hashes,
+            &signature,
+        )
+        .unwrap();
+        let rotated_keys = rotated_keys
+            .iter()
+            .map(|pk| pk.value().unwrap())
+            .collect::<Vec<_>>();
+        (cs, rotated_keys)
+    }
+
+    #[test]
+    fn rotates_signed_handoffs() {
+        run_profile_constraints(rotates_signed_handoffs_inner);
+    }
+
+    #[tracing::instrument(target = "r1cs")]
+    fn rotates_signed_handoffs_inner() {
+        let rng = &mut rand::thread_rng();
+        let secret_keys = (0..3).map(|_| Bls12_377Fr::rand(rng)).collect::<Vec<_>>();
+        let pub_keys = secret_keys
+            .iter()
+            .map(|sk| G2Projective::prime_subgroup_generator().mul(*sk))
+            .collect::<Vec<_>>();
+        let new_pub_keys = (0..3).map(|_| G2Projective::rand(rng)).collect::<Vec<_>>();
+        let handoff_hashes = (0..3).map(|_| G1Projective::rand(rng)).collect::<Vec<_>>();
+
+        // the first and the last validator rotate their keys
+        let rotated = [true, false, true];
+        let signature =
+            handoff_hashes[0].mul(secret_keys[0]) + handoff_hashes[2].mul(secret_keys[2]);
+        let (cs, rotated_keys) = cs_rotate(
+            &pub_keys,
+            &new_pub_keys,
+            &rotated,
+            &handoff_hashes,
+            signature,
+        );
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(
+            rotated_keys,
+            vec![new_pub_keys[0], pub_keys[1], new_pub_keys[2]]
+        );
+
+        // no rotations with an empty signature keeps the validator set
+        let (cs, rotated_keys) = cs_rotate(
+            &pub_keys,
+            &new_pub_keys,
+            &[false; 3],
+            &handoff_hashes,
+            G1Projective::zero(),
+        );
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(rotated_keys, pub_keys);
+
+        // the middle validator did not sign a handoff
+        let (cs, _) = cs_rotate(
+            &pub_keys,
+            &new_pub_keys,
+            &[true, true, true],
+            &handoff_hashes,
+            signature,
+        );
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,243 @@
+#![allow(clippy::op_ref)] // clippy throws a false positive around field ops
+use crate::{hash_to_bits, FpUtils, HashToGroupGadget};
+use algebra::{
+    bls12_377::{Fq as Bls12_377_Fq, FqParameters, Parameters as Bls12_377_Parameters},
+    FpParameters, One, SquareRootField,
+};
+use bls_crypto::{
+    hash_to_curve::sw::{
+        cube_root_of_unity, is_square, map_to_curve, non_residue, sqrt_minus_three,
+        FIELD_ELEMENT_BYTES,
+    },
+    SIG_DOMAIN,
+};
+use r1cs_core::SynthesisError;
+use r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    bits::ToBitsGadget,
+    boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    groups::{bls12::G1Var, CurveVar},
+    select::CondSelectGadget,
+    uint8::UInt8,
+    Assignment, R1CSVar,
+};
+use tracing::{debug, span, trace, Level};
+
+/// Gadget which enforces correct calculation of hashing to BLS12-377's G1 with the
+/// Shallue-van de Woestijne map. For more information on the method, refer to the
+/// [non-gadget implementation][sw].
+///
+/// Unlike `HashToGroupGadget`, this does not require a witnessed counter, and its
+/// cost does not depend on the message.
+///
+/// [sw]: ../bls_crypto/hash_to_curve/sw/index.html
+pub struct SWHashToGroupGadget;
+
+impl SWHashToGroupGadget {
+    /// Returns the G1 constrained hash of the message.
+    ///
+    /// If `generate_constraints_for_hash` is set to `false`, then constraints will not
+    /// be generated for the CRH -> XOF conversion, in which case you MUST verify that
+    /// it was calculated properly. For that reason, the CRH bits and the XOF bits are
+    /// also returned.
+    #[allow(clippy::type_complexity)]
+    #[tracing::instrument(target = "r1cs")]
+    pub fn enforce_hash_to_group(
+        message: &[UInt8<Bls12_377_Fq>],
+        extra_data: &[UInt8<Bls12_377_Fq>],
+        generate_constraints_for_hash: bool,
+    ) -> Result<
+        (
+            G1Var<Bls12_377_Parameters>,
+            Vec<Boolean<Bls12_377_Fq>>,
+            Vec<Boolean<Bls12_377_Fq>>,
+        ),
+        SynthesisError,
+    > {
+        let span = span!(Level::TRACE, "enforce_sw_hash_to_group");
+        let _enter = span.enter();
+
+        // compress the input
+        let crh_bits = HashToGroupGadget::<Bls12_377_Parameters, Bls12_377_Fq>::pedersen_hash(
+            message,
+            bls_crypto::hashers::composite::COMPOSITE_HASHER.parameters(),
+        )?;
+
+        let mut input = vec![];
+        for v in extra_data {
+            input.extend_from_slice(&v.to_bits_le()?);
+        }
+        input.extend_from_slice(&crh_bits);
+
+        let mut personalization = [0; 8];
+        personalization.copy_from_slice(SIG_DOMAIN);
+        let xof_bits = hash_to_bits(
+            &input,
+            (2 * FIELD_ELEMENT_BYTES * 8) as u16,
+            personalization,
+            generate_constraints_for_hash,
+        )?;
+
+        trace!("mapping the field elements to the curve");
+        let mut point = G1Var::zero();
+        for element_bits in xof_bits.chunks(FIELD_ELEMENT_BYTES * 8) {
+            let t = Self::to_field_element(element_bits)?;
+            point += Self::map_to_curve(&t)?;
+        }
+
+        trace!("scaling by G1 cofactor");
+        let hash =
+            HashToGroupGadget::<Bls12_377_Parameters, Bls12_377_Fq>::scale_by_cofactor_g1(&point)?;
+
+        debug!("message has been hashed to G1");
+        Ok((hash, crh_bits, xof_bits))
+    }
+
+    /// Packs the first `CAPACITY` little endian bits to a field element
+    fn to_field_element(
+        bits: &[Boolean<Bls12_377_Fq>],
+    ) -> Result<FpVar<Bls12_377_Fq>, SynthesisError> {
+        let capacity = FqParameters::CAPACITY as usize;
+        let element = FpVar::new_witness(bits.cs(), || {
+            let bytes = crate::utils::bits_le_to_bytes_le(
+                &bits
+                    .iter()
+                    .map(|bit| bit.value())
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+            Ok(bls_crypto::hash_to_curve::sw::to_field_element(&bytes))
+        })?;
+
+        // the element's canonical bits must be the hash's bits, with the rest set to 0
+        let element_bits = element.to_bits_le()?;
+        for (i, bit) in element_bits.iter().enumerate() {
+            if i < capacity {
+                bit.enforce_equal(&bits[i])?;
+            } else {
+                bit.enforce_equal(&Boolean::Constant(false))?;
+            }
+        }
+        Ok(element)
+    }
+
+    /// Enforces the SW map of `t` to a point on the curve (not necessarily in the prime
+    /// order subgroup)
+    fn map_to_curve(
+        t: &FpVar<Bls12_377_Fq>,
+    ) -> Result<G1Var<Bls12_377_Parameters>, SynthesisError> {
+        let one = FpVar::one();
+        let sqrt_minus_three = FpVar::constant(sqrt_minus_three());
+        let cube_root_of_unity = FpVar::constant(cube_root_of_unity());
+
+        // w = sqrt(-3) * t / (1 + b + t^2), where b = 1. Computing the inverses enforces
+        // that the denominators are not zero, as in the native implementation.
+        let denominator = t.square()? + FpVar::constant(Bls12_377_Fq::from(2u64));
+        let w = &sqrt_minus_three * t * denominator.inverse()?;
+
+        let x1 = cube_root_of_unity - t * &w;
+        let x2 = FpVar::constant(-Bls12_377_Fq::one()) - &x1;
+        let x3 = &one + w.square()?.inverse()?;
+
+        let x1_is_square = Self::is_square(&Self::curve_equation(&x1)?)?;
+        let x2_is_square = Self::is_square(&Self::curve_equation(&x2)?)?;
+        let x = FpVar::conditionally_select(
+            &x1_is_square,
+            &x1,
+            &FpVar::conditionally_select(&x2_is_square, &x2, &x3)?,
+        )?;
+
+        // allocating the point checks that it is on the curve
+        let point = G1Var::new_variable_omit_prime_order_check(
+            t.cs(),
+            || {
+                if t.cs().is_in_setup_mode() {
+                    return Err(SynthesisError::AssignmentMissing);
+                }
+                map_to_curve(&t.value()?).map_err(|_| SynthesisError::AssignmentMissing)
+            },
+            AllocationMode::Witness,
+        )?;
+        point.x.enforce_equal(&x)?;
+        point.y.normalize()?.enforce_equal(&t.normalize()?)?;
+
+        Ok(point)
+    }
+
+    fn curve_equation(x: &FpVar<Bls12_377_Fq>) -> Result<FpVar<Bls12_377_Fq>, SynthesisError> {
+        Ok(x.square()? * x + FpVar::one())
+    }
+
+    /// Returns whether the element is a square. This is proven by providing a square root
+    /// of either the element or of the element multiplied by a non-residue.
+    fn is_square(element: &FpVar<Bls12_377_Fq>) -> Result<Boolean<Bls12_377_Fq>, SynthesisError> {
+        let non_residue = non_residue();
+        let is_square_bit =
+            Boolean::new_witness(element.cs(), || Ok(is_square(&element.value()?)))?;
+        let root = FpVar::new_witness(element.cs(), || {
+            let element = element.value()?;
+            let value = if is_square(&element) {
+                element
+            } else {
+                element * &non_residue
+            };
+            value.sqrt().get()
+        })?;
+
+        let expected = FpVar::conditionally_select(
+            &is_square_bit,
+            element,
+            &(element * FpVar::constant(non_residue)),
+        )?;
+        root.square()?.enforce_equal(&expected)?;
+
+        Ok(is_square_bit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_helpers::{print_unsatisfied_constraints, run_profile_constraints};
+    use bls_crypto::{hash_to_curve::sw::COMPOSITE_SW_HASH_TO_G1, HashToCurve};
+    use r1cs_core::ConstraintSystem;
+    use rand::{thread_rng, RngCore};
+
+    #[test]
+    fn test_sw_hash_to_group() {
+        run_profile_constraints(test_sw_hash_to_group_inner);
+    }
+
+    #[tracing::instrument(target = "r1cs")]
+    fn test_sw_hash_to_group_inner() {
+        let mut rng = thread_rng();
+        for length in &[10, 50] {
+            let mut input = vec![0; *length];
+            rng.fill_bytes(&mut input);
+            let mut extra_input = vec![0; *length];
+            rng.fill_bytes(&mut extra_input);
+
+            let expected_hash = COMPOSITE_SW_HASH_TO_G1
+                .hash(SIG_DOMAIN, &input, &extra_input)
+                .unwrap();
+
+            let cs = ConstraintSystem::<Bls12_377_Fq>::new_ref();
+            let input = input
+                .iter()
+                .map(|num| UInt8::new_witness(cs.clone(), || Ok(num)).unwrap())
+                .collect::<Vec<_>>();
+            let extra_input = extra_input
+                .iter()
+                .map(|num| UInt8::new_witness(cs.clone(), || Ok(num)).unwrap())
+                .collect::<Vec<_>>();
+            let hash = SWHashToGroupGadget::enforce_hash_to_group(&input, &extra_input, true)
+                .unwrap()
+                .0;
+
+            print_unsatisfied_constraints(cs.clone());
+            assert!(cs.is_satisfied().unwrap());
+            assert_eq!(expected_hash, hash.value().unwrap());
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::Blake2XofGadget;
+use algebra::{bls12_377::Fq as Bls12_377_Fq, Field, FpParameters, PrimeField};
+use r1cs_core::SynthesisError;
+use r1cs_std::{
+    bls12_377::{G1Var, G2Var},
+    fields::FieldVar,
+    prelude::*,
+};
+use tracing::{span, Level};
+
+/// The number of bytes of the transcript's state after each squeeze
+const STATE_BYTES: usize = 32;
+
+/// Gadget which derives the same Fiat-Shamir challenges as `bls_crypto::Transcript`, see its
+/// documentation for the construction
+#[derive(Clone, Debug)]
+pub struct TranscriptVar<F: PrimeField> {
+    personalization: [u8; 8],
+    state: Vec<UInt8<F>>,
+}
+
+impl<F: PrimeField> TranscriptVar<F> {
+    /// Starts an empty transcript whose challenges are separated by `domain`
+    ///
+    /// # Panics
+    ///
+    /// If the domain is larger than 8 bytes
+    pub fn new(domain: &[u8]) -> Self {
+        assert!(domain.len() <= 8, "domain length is too large");
+        let mut personalization = [0; 8];
+        personalization[..domain.len()].copy_from_slice(domain);
+        Self {
+            personalization,
+            state: vec![],
+        }
+    }
+
+    /// Appends the bytes to the transcript, framed by the label and both lengths as in
+    /// `bls_crypto::Transcript::absorb_bytes`
+    pub fn absorb_bytes(&mut self, label: &[u8], bytes: &[UInt8<F>]) {
+        let framing = [
+            &(label.len() as u32).to_le_bytes()[..],
+            label,
+            &(bytes.len() as u32).to_le_bytes()[..],
+        ]
+        .concat();
+        self.state.extend(framing.into_iter().map(UInt8::constant));
+        self.state.extend_from_slice(bytes);
+    }
+
+    /// Derives a challenge from the transcript, returned as the little-endian bits of the
+    /// field element returned by `bls_crypto::Transcript::squeeze_field::<T>`, i.e. as
+    /// `T::Params::CAPACITY` bits
+    #[tracing::instrument(target = "r1cs")]
+    pub fn squeeze_bits<T: PrimeField>(&mut self) -> Result<Vec<Boolean<F>>, SynthesisError> {
+        let span = span!(Level::TRACE, "TranscriptVar_squeeze_bits");
+        let _enter = span.enter();
+
+        let num_bits = T::Params::CAPACITY as usize;
+        let mut message = Vec::with_capacity(8 * self.state.len());
+        for byte in &self.state {
+            message.extend(byte.to_bits_le()?);
+        }
+        let output_bytes = STATE_BYTES + (num_bits + 7) / 8;
+        let output =
+            Blake2XofGadget::evaluate(&message, output_bytes as u16, self.personalization)?;
+
+        self.state = output[..8 * STATE_BYTES]
+            .chunks(8)
+            .map(UInt8::from_bits_le)
+            .collect();
+        Ok(output[8 * STATE_BYTES..8 * STATE_BYTES + num_bits].to_vec())
+    }
+
+    // appends the affine coordinates of a point in projective coordinates
+    fn absorb_projective<T: Field, FV: FieldVar<T, F>>(
+        &mut self,
+        x: &FV,
+        y: &FV,
+        z: &FV,
+    ) -> Result<(), SynthesisError> {
+        self.state.extend(x.mul_by_inverse(z)?.to_bytes()?);
+        self.state.extend(y.mul_by_inverse(z)?.to_bytes()?);
+        Ok(())
+    }
+}
+
+impl TranscriptVar<Bls12_377_Fq> {
+    /// Appends the G1 point to the transcript as in `bls_crypto::Transcript::absorb_point`.
+    /// The point must not be the point at infinity, otherwise the constraints are unsatisfied.
+    pub fn absorb_g1(&mut self, point: &G1Var) -> Result<(), SynthesisError> {
+        self.absorb_projective(&point.x, &point.y, &point.z)
+    }
+
+    /// Appends the G2 point to the transcript as in `bls_crypto::Transcript::absorb_point`.
+    /// The point must not be the point at infinity, otherwise the constraints are unsatisfied.
+    pub fn absorb_g2(&mut self, point: &G2Var) -> Result<(), SynthesisError> {
+        self.absorb_projective(&point.x, &point.y, &point.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_helpers::print_unsatisfied_constraints;
+    use algebra::{
+        bls12_377::{Fr, G1Projective, G2Projective},
+        BigInteger, ProjectiveCurve, UniformRand,
+    };
+    use bls_crypto::Transcript;
+    use r1cs_core::ConstraintSystem;
+
+    fn bits_to_fr(bits: &[Boolean<Bls12_377_Fq>]) -> Fr {
+        let mut bits = bits
+            .iter()
+            .map(|bit| bit.value().unwrap())
+            .collect::<Vec<_>>();
+        bits.reverse();
+        Fr::from_repr(<Fr as PrimeField>::BigInt::from_bits(&bits)).unwrap()
+    }
+
+    #[test]
+    fn matches_native_transcript() {
+        let rng = &mut rand::thread_rng();
+        let message = b"hello";
+        let g1 = G1Projective::rand(rng);
+        let g2 = G2Projective::rand(rng);
+
+        let mut transcript = Transcript::new(b"ULfortst").unwrap();
+        transcript.absorb_bytes(b"message", message);
+        transcript.absorb_point(&g1.into_affine()).unwrap();
+        let first = transcript.squeeze_field::<Fr>().unwrap();
+        transcript.absorb_point(&g2.into_affine()).unwrap();
+        let second = transcript.squeeze_field::<Fr>().unwrap();
+
+        let cs = ConstraintSystem::<Bls12_377_Fq>::new_ref();
+        let mut transcript_var = TranscriptVar::new(b"ULfortst");
+        let message_var = message
+            .iter()
+            .map(|byte| UInt8::new_witness(cs.clone(), || Ok(byte)).unwrap())
+            .collect::<Vec<_>>();
+        transcript_var.absorb_bytes(b"message", &message_var);
+        // the points are not in projective coordinates with z = 1
+        let g1_var = G1Var::new_witness(cs.clone(), || Ok(g1)).unwrap();
+        let g1_var = g1_var.double().unwrap() - &g1_var;
+        transcript_var.absorb_g1(&g1_var).unwrap();
+        let first_var = transcript_var.squeeze_bits::<Fr>().unwrap();
+        let g2_var = G2Var::new_witness(cs.clone(), || Ok(g2)).unwrap();
+        transcript_var.absorb_g2(&g2_var).unwrap();
+        let second_var = transcript_var.squeeze_bits::<Fr>().unwrap();
+
+        print_unsatisfied_constraints(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(bits_to_fr(&first_var), first);
+        assert_eq!(bits_to_fr(&second_var), second);
+    }
+}
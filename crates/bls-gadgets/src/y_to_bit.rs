@@ -1,13 +1,15 @@
 #![allow(clippy::op_ref)] // clippy throws a false positive around field ops
 use algebra::{
-    bls12_377::Parameters as Bls12_377_Parameters, curves::bls12::Bls12Parameters, PrimeField, Zero,
+    curves::models::SWModelParameters,
+    fields::{Fp2, Fp2Parameters},
+    PrimeField, Zero,
 };
 use r1cs_core::{lc, LinearCombination, SynthesisError, Variable};
 use r1cs_std::{
     alloc::AllocVar,
     boolean::Boolean,
-    fields::fp::FpVar,
-    groups::curves::short_weierstrass::bls12::{G1Var, G2Var},
+    fields::{fp::FpVar, fp2::Fp2Var},
+    groups::curves::short_weierstrass::AffineVar,
     Assignment, R1CSVar,
 };
 
@@ -16,10 +18,13 @@ use r1cs_std::{
 /// we can normalize any elements greater than $half$ (i.e. in the range
 /// [half+1, p-1]), by subtracting half (resulting in a number in the [1, half]
 /// range). Then we check that the cast element is <= half, which enforces that
-/// originally they were > half. For points in G2, we also check the
-/// lexicographical ordering.
-pub trait YToBitGadget<P: Bls12Parameters> {
-    fn y_to_bit(&self) -> Result<Boolean<P::Fp>, SynthesisError>;
+/// originally they were > half. For points over quadratic extensions (e.g. G2), we
+/// also check the lexicographical ordering.
+///
+/// It is implemented for the variables of any short Weierstrass curve whose base
+/// field is either the constraint field `F` or a quadratic extension of it.
+pub trait YToBitGadget<F: PrimeField> {
+    fn y_to_bit(&self) -> Result<Boolean<F>, SynthesisError>;
 }
 
 pub trait FpUtils<F: PrimeField> {
@@ -29,30 +34,31 @@ pub trait FpUtils<F: PrimeField> {
     fn normalize(&self) -> Result<Boolean<F>, SynthesisError>;
 }
 
-impl YToBitGadget<Bls12_377_Parameters> for G1Var<Bls12_377_Parameters> {
-    fn y_to_bit(
-        &self,
-    ) -> Result<Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>, SynthesisError> {
+impl<P, F> YToBitGadget<F> for AffineVar<P, FpVar<F>>
+where
+    P: SWModelParameters<BaseField = F>,
+    F: PrimeField,
+{
+    fn y_to_bit(&self) -> Result<Boolean<F>, SynthesisError> {
         let y_bit = self.y.normalize()?;
         Ok(y_bit)
     }
 }
 
-impl YToBitGadget<Bls12_377_Parameters> for G2Var<Bls12_377_Parameters> {
-    fn y_to_bit(
-        &self,
-    ) -> Result<Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>, SynthesisError> {
+impl<P, Q> YToBitGadget<Q::Fp> for AffineVar<P, Fp2Var<Q>>
+where
+    P: SWModelParameters<BaseField = Fp2<Q>>,
+    Q: Fp2Parameters,
+    Q::Fp: PrimeField,
+{
+    fn y_to_bit(&self) -> Result<Boolean<Q::Fp>, SynthesisError> {
         // Apply the point compression logic for getting the y bit's value.
         let y_bit = Boolean::new_witness(self.cs(), || {
-            let half = <Bls12_377_Parameters as Bls12Parameters>::Fp::from_repr(
-                <Bls12_377_Parameters as Bls12Parameters>::Fp::modulus_minus_one_div_two(),
-            )
-            .get()?;
+            let half = Q::Fp::from_repr(Q::Fp::modulus_minus_one_div_two()).get()?;
             let c1 = self.y.c1.value()?;
             let c0 = self.y.c0.value()?;
 
-            let bit = c1 > half
-                || (c1 == <Bls12_377_Parameters as Bls12Parameters>::Fp::zero() && c0 > half);
+            let bit = c1 > half || (c1 == Q::Fp::zero() && c0 > half);
             Ok(bit)
         })?;
 
@@ -296,6 +302,47 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_y_to_bit_bw6_761() {
+        run_profile_constraints(test_y_to_bit_bw6_761_inner);
+    }
+    #[tracing::instrument(target = "r1cs")]
+    fn test_y_to_bit_bw6_761_inner() {
+        use algebra::bw6_761::{
+            g1::Parameters as G1Parameters, g2::Parameters as G2Parameters, Fq,
+            G1Projective as BW6G1Projective, G2Projective as BW6G2Projective,
+        };
+
+        let half = Fq::from_repr(Fq::modulus_minus_one_div_two()).unwrap();
+        let rng = &mut rand::thread_rng();
+
+        for _ in 0..5 {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+
+            let g1 = AffineVar::<G1Parameters, FpVar<Fq>>::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || Ok(BW6G1Projective::rand(rng)),
+                AllocationMode::Witness,
+            )
+            .unwrap();
+            let g1_bit = g1.y_to_bit().unwrap();
+            assert_eq!(g1.y.value().unwrap() > half, g1_bit.value().unwrap());
+
+            // BW6-761's G2 is also defined over the base field
+            let g2 = AffineVar::<G2Parameters, FpVar<Fq>>::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || Ok(BW6G2Projective::rand(rng)),
+                AllocationMode::Witness,
+            )
+            .unwrap();
+            let g2_bit = g2.y_to_bit().unwrap();
+            assert_eq!(g2.y.value().unwrap() > half, g2_bit.value().unwrap());
+
+            print_unsatisfied_constraints(cs.clone());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
     // Check points at the edge - c1 == half.
     #[test]
     fn test_y_to_bit_g2_c1_is_half() {
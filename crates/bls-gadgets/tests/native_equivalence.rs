@@ -0,0 +1,202 @@
+//! Property tests checking that `BlsVerifyGadget::verify` is satisfiable exactly when the
+//! native verification of the same keys, bitmap and signature succeeds.
+//!
+//! The cases cover the edges of the native semantics: validators with the identity as
+//! public key, bitmaps without any signer, and any maximum number of non-signers up to the
+//! number of validators.
+#![cfg(feature = "bls")]
+
+use algebra::{
+    bls12_377::{Bls12_377, Fr, G1Projective, G2Projective},
+    bw6_761::Fr as BW6_761Fr,
+    ProjectiveCurve, UniformRand, Zero,
+};
+use bls_crypto::{PublicKey, Signature};
+use bls_gadgets::BlsVerifyGadget;
+use proptest::prelude::*;
+use r1cs_core::ConstraintSystem;
+use r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    bls12_377::{G1Var, G2Var, PairingVar},
+    boolean::Boolean,
+    fields::fp::FpVar,
+    groups::CurveVar,
+};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+type Gadget = BlsVerifyGadget<Bls12_377, BW6_761Fr, PairingVar>;
+
+#[derive(Clone, Copy, Debug)]
+enum SignatureKind {
+    /// The aggregate signature of the signers
+    Valid,
+    /// The aggregate signature of the signers over another message
+    OtherMessage,
+    /// The aggregate signature of the signers and of a validator not in the bitmap
+    ExtraSigner,
+    /// The identity, i.e. the aggregate signature of no signer
+    Identity,
+}
+
+#[derive(Clone, Debug)]
+struct Case {
+    seed: u64,
+    /// Whether each validator's public key is the identity
+    identity_keys: Vec<bool>,
+    bitmap: Vec<bool>,
+    maximum_non_signers: u64,
+    signature: SignatureKind,
+}
+
+fn cases() -> impl Strategy<Value = Case> {
+    (1usize..=6).prop_flat_map(|num_validators| {
+        (
+            any::<u64>(),
+            prop::collection::vec(prop::bool::weighted(0.2), num_validators),
+            prop_oneof![
+                prop::collection::vec(any::<bool>(), num_validators),
+                Just(vec![false; num_validators]),
+                Just(vec![true; num_validators]),
+            ],
+            0..=num_validators as u64,
+            prop_oneof![
+                Just(SignatureKind::Valid),
+                Just(SignatureKind::OtherMessage),
+                Just(SignatureKind::ExtraSigner),
+                Just(SignatureKind::Identity),
+            ],
+        )
+            .prop_map(
+                |(seed, identity_keys, bitmap, maximum_non_signers, signature)| Case {
+                    seed,
+                    identity_keys,
+                    bitmap,
+                    maximum_non_signers,
+                    signature,
+                },
+            )
+    })
+}
+
+/// The keys, message hash and signature of the case
+fn instantiate(case: &Case) -> (Vec<G2Projective>, G1Projective, G1Projective) {
+    let rng = &mut XorShiftRng::seed_from_u64(case.seed);
+    let secret_keys = case
+        .identity_keys
+        .iter()
+        .map(|identity| if *identity { Fr::zero() } else { Fr::rand(rng) })
+        .collect::<Vec<_>>();
+    let public_keys = secret_keys
+        .iter()
+        .map(|secret_key| G2Projective::prime_subgroup_generator().mul(*secret_key))
+        .collect();
+    let message_hash = G1Projective::rand(rng);
+
+    let sign = |message_hash: G1Projective, signers: &[bool]| {
+        secret_keys
+            .iter()
+            .zip(signers)
+            .filter(|(_, signed)| **signed)
+            .fold(G1Projective::zero(), |signature, (secret_key, _)| {
+                signature + message_hash.mul(*secret_key)
+            })
+    };
+    let signature = match case.signature {
+        SignatureKind::Valid => sign(message_hash, &case.bitmap),
+        SignatureKind::OtherMessage => sign(G1Projective::rand(rng), &case.bitmap),
+        SignatureKind::ExtraSigner => sign(message_hash, &vec![true; case.bitmap.len()]),
+        SignatureKind::Identity => G1Projective::zero(),
+    };
+    (public_keys, message_hash, signature)
+}
+
+/// Verifies the case natively. The circuit cannot prove a signature against the identity
+/// as aggregate public key (its pairing gadget does not special case the identity), so the
+/// native semantics it implements reject an empty aggregate.
+fn native_verify(case: &Case) -> bool {
+    let (public_keys, message_hash, signature) = instantiate(case);
+    let non_signers = case.bitmap.iter().filter(|signed| !**signed).count() as u64;
+    let aggregate_public_key = PublicKey::aggregate(
+        public_keys
+            .iter()
+            .zip(&case.bitmap)
+            .filter(|(_, signed)| **signed)
+            .map(|(public_key, _)| PublicKey::from(*public_key)),
+    );
+    non_signers <= case.maximum_non_signers
+        && !aggregate_public_key.as_ref().is_zero()
+        && aggregate_public_key
+            .verify_exact(&message_hash, &Signature::from(signature))
+            .is_ok()
+}
+
+/// Whether the constraints of `BlsVerifyGadget::verify` are satisfied for the case
+fn gadget_verify(case: &Case) -> bool {
+    let (public_keys, message_hash, signature) = instantiate(case);
+    let cs = ConstraintSystem::<BW6_761Fr>::new_ref();
+    let witness = || AllocationMode::Witness;
+
+    let public_keys = public_keys
+        .into_iter()
+        .map(|public_key| {
+            G2Var::new_variable_omit_prime_order_check(cs.clone(), || Ok(public_key), witness())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let bitmap = case
+        .bitmap
+        .iter()
+        .map(|signed| Boolean::new_witness(cs.clone(), || Ok(*signed)))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let message_hash =
+        G1Var::new_variable_omit_prime_order_check(cs.clone(), || Ok(message_hash), witness())
+            .unwrap();
+    let signature =
+        G1Var::new_variable_omit_prime_order_check(cs.clone(), || Ok(signature), witness())
+            .unwrap();
+    let maximum_non_signers =
+        FpVar::new_witness(cs.clone(), || Ok(BW6_761Fr::from(case.maximum_non_signers))).unwrap();
+
+    // failing to synthesize means that no proof can be generated
+    Gadget::verify(
+        &public_keys,
+        &bitmap,
+        &message_hash,
+        &signature,
+        &maximum_non_signers,
+    )
+    .is_ok()
+        && cs.is_satisfied().unwrap()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn gadget_matches_native_verification(case in cases()) {
+        prop_assert_eq!(gadget_verify(&case), native_verify(&case));
+    }
+}
+
+#[test]
+fn covers_both_outcomes() {
+    let case = Case {
+        seed: 0,
+        identity_keys: vec![false, true, false],
+        bitmap: vec![true, true, false],
+        maximum_non_signers: 1,
+        signature: SignatureKind::Valid,
+    };
+    assert!(native_verify(&case));
+    assert!(gadget_verify(&case));
+
+    // the maximum number of non-signers is exceeded
+    let case = Case {
+        maximum_non_signers: 0,
+        ..case
+    };
+    assert!(!native_verify(&case));
+    assert!(!gadget_verify(&case));
+}
@@ -0,0 +1,559 @@
+//! Curve Dispatch
+//!
+//! Functions which take a `curve_id` and only exchange serialized byte buffers, so that a
+//! single library can serve callers on different curves. Each supported curve registers a
+//! `CurveBackend` in `BACKENDS`, and `supported_curves` reports which ones are available.
+//!
+//! Both curves use the same scheme, with public keys in G2 and signatures in G1, and the same
+//! try-and-increment hashers to G1. BLS12-381 signatures are therefore not compatible with
+//! the IETF hash to curve used by Ethereum's BLS signatures, and BLS12-381 private keys are
+//! not multiplied in constant time. The functions which take deserialized objects (e.g.
+//! `sign_message`) always operate on BLS12-377.
+use crate::{convert_result_to_bool, utils::Buffer, PrivateKey, PublicKey, Signature};
+use algebra::{CanonicalDeserialize, CanonicalSerialize};
+use bls_crypto::{
+    hash_to_curve::try_and_increment::DIRECT_HASH_TO_G1,
+    hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, BLSError, ErrorCode,
+    ToErrorCode, SIG_DOMAIN,
+};
+use std::{os::raw::c_int, slice};
+use thiserror::Error;
+
+/// The identifier of BLS12-377, the SNARK-friendly curve used by Celo
+pub const CURVE_BLS12_377: c_int = 0;
+/// The identifier of BLS12-381, the curve used by Ethereum
+pub const CURVE_BLS12_381: c_int = 1;
+
+/// Errors raised by the curve dispatched functions
+#[derive(Debug, Error)]
+pub enum CurveError {
+    /// No backend is registered for the curve
+    #[error("unsupported curve: {0}")]
+    UnsupportedCurve(c_int),
+    /// A buffer array was passed with a negative length, or a null pointer and a positive one
+    #[error("invalid buffer array length: {0}")]
+    InvalidLength(c_int),
+    /// The backend failed
+    #[error(transparent)]
+    Bls(#[from] BLSError),
+}
+
+impl ToErrorCode for CurveError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            CurveError::UnsupportedCurve(_) | CurveError::InvalidLength(_) => {
+                ErrorCode::InvalidArgument
+            }
+            CurveError::Bls(e) => e.error_code(),
+        }
+    }
+}
+
+type BackendResult<T> = Result<T, BLSError>;
+
+/// The operations a curve must implement over serialized keys and signatures. `composite`
+/// selects the curve's composite (CIP22 for BLS12-377) hasher instead of its direct one.
+pub struct CurveBackend {
+    /// Signs `message` and `extra_data` with the private key, returning the signature
+    pub sign: fn(&[u8], &[u8], &[u8], bool) -> BackendResult<Vec<u8>>,
+    /// Returns whether the signature over `message` and `extra_data` is valid for the key
+    pub verify: fn(&[u8], &[u8], &[u8], &[u8], bool) -> BackendResult<bool>,
+    /// Aggregates the public keys
+    pub aggregate_public_keys: fn(&[&[u8]]) -> BackendResult<Vec<u8>>,
+    /// Aggregates the signatures
+    pub aggregate_signatures: fn(&[&[u8]]) -> BackendResult<Vec<u8>>,
+}
+
+/// The backend of each supported curve
+static BACKENDS: &[(c_int, CurveBackend)] = &[
+    (
+        CURVE_BLS12_377,
+        CurveBackend {
+            sign: bls12_377::sign,
+            verify: bls12_377::verify,
+            aggregate_public_keys: bls12_377::aggregate_public_keys,
+            aggregate_signatures: bls12_377::aggregate_signatures,
+        },
+    ),
+    (
+        CURVE_BLS12_381,
+        CurveBackend {
+            sign: bls12_381::sign,
+            verify: bls12_381::verify,
+            aggregate_public_keys: bls12_381::aggregate_public_keys,
+            aggregate_signatures: bls12_381::aggregate_signatures,
+        },
+    ),
+];
+
+/// Returns the backend of the curve
+pub fn backend(curve_id: c_int) -> Result<&'static CurveBackend, CurveError> {
+    BACKENDS
+        .iter()
+        .find(|(id, _)| *id == curve_id)
+        .map(|(_, backend)| backend)
+        .ok_or(CurveError::UnsupportedCurve(curve_id))
+}
+
+fn serialize<T: CanonicalSerialize>(obj: &T) -> BackendResult<Vec<u8>> {
+    let mut bytes = vec![];
+    obj.serialize(&mut bytes)?;
+    Ok(bytes)
+}
+
+mod bls12_377 {
+    use super::*;
+
+    pub fn sign(
+        private_key: &[u8],
+        message: &[u8],
+        extra_data: &[u8],
+        composite: bool,
+    ) -> BackendResult<Vec<u8>> {
+        let private_key = PrivateKey::deserialize(private_key)?;
+        let signature = if composite {
+            private_key.sign(message, extra_data, &*COMPOSITE_HASH_TO_G1_CIP22)?
+        } else {
+            private_key.sign(message, extra_data, &*DIRECT_HASH_TO_G1)?
+        };
+        serialize(&signature)
+    }
+
+    pub fn verify(
+        public_key: &[u8],
+        message: &[u8],
+        extra_data: &[u8],
+        signature: &[u8],
+        composite: bool,
+    ) -> BackendResult<bool> {
+        let public_key = PublicKey::deserialize(public_key)?;
+        let signature = Signature::deserialize(signature)?;
+        let verified = if composite {
+            public_key.verify(
+                message,
+                extra_data,
+                &signature,
+                &*COMPOSITE_HASH_TO_G1_CIP22,
+            )
+        } else {
+            public_key.verify(message, extra_data, &signature, &*DIRECT_HASH_TO_G1)
+        };
+        Ok(verified.is_ok())
+    }
+
+    pub fn aggregate_public_keys(public_keys: &[&[u8]]) -> BackendResult<Vec<u8>> {
+        let public_keys = public_keys
+            .iter()
+            .map(|bytes| PublicKey::deserialize(*bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        serialize(&PublicKey::aggregate(&public_keys))
+    }
+
+    pub fn aggregate_signatures(signatures: &[&[u8]]) -> BackendResult<Vec<u8>> {
+        let signatures = signatures
+            .iter()
+            .map(|bytes| Signature::deserialize(*bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        serialize(&Signature::aggregate(&signatures))
+    }
+}
+
+mod bls12_381 {
+    use super::*;
+    use algebra::{
+        bls12_381::{Bls12_381, Fr, G1Affine, G1Projective, G2Affine, G2Projective, Parameters},
+        curves::models::bls12::Bls12Parameters,
+        AffineCurve, PairingEngine, ProjectiveCurve, Zero,
+    };
+    use bls_crypto::{
+        hash_to_curve::{try_and_increment::TryAndIncrement, HashToCurve},
+        hashers::{composite::COMPOSITE_HASHER, DirectHasher},
+    };
+
+    type G1Parameters = <Parameters as Bls12Parameters>::G1Parameters;
+
+    fn hash_to_g1(
+        message: &[u8],
+        extra_data: &[u8],
+        composite: bool,
+    ) -> BackendResult<G1Projective> {
+        if composite {
+            TryAndIncrement::<_, G1Parameters>::new(&*COMPOSITE_HASHER)
+                .hash(SIG_DOMAIN, message, extra_data)
+        } else {
+            TryAndIncrement::<_, G1Parameters>::new(&DirectHasher)
+                .hash(SIG_DOMAIN, message, extra_data)
+        }
+    }
+
+    pub fn sign(
+        private_key: &[u8],
+        message: &[u8],
+        extra_data: &[u8],
+        composite: bool,
+    ) -> BackendResult<Vec<u8>> {
+        let private_key = Fr::deserialize(private_key)?;
+        let signature = hash_to_g1(message, extra_data, composite)?.mul(private_key);
+        serialize(&signature.into_affine())
+    }
+
+    pub fn verify(
+        public_key: &[u8],
+        message: &[u8],
+        extra_data: &[u8],
+        signature: &[u8],
+        composite: bool,
+    ) -> BackendResult<bool> {
+        let public_key = G2Affine::deserialize(public_key)?;
+        let signature = G1Affine::deserialize(signature)?;
+        // the identity would verify any message for the identity public key
+        if public_key.is_zero() || signature.is_zero() {
+            return Ok(false);
+        }
+        let message_hash = hash_to_g1(message, extra_data, composite)?;
+        Ok(
+            Bls12_381::pairing(signature, G2Affine::prime_subgroup_generator())
+                == Bls12_381::pairing(message_hash, public_key),
+        )
+    }
+
+    pub fn aggregate_public_keys(public_keys: &[&[u8]]) -> BackendResult<Vec<u8>> {
+        let mut aggregate = G2Projective::zero();
+        for bytes in public_keys {
+            aggregate += &G2Affine::deserialize(*bytes)?.into_projective();
+        }
+        serialize(&aggregate.into_affine())
+    }
+
+    pub fn aggregate_signatures(signatures: &[&[u8]]) -> BackendResult<Vec<u8>> {
+        let mut aggregate = G1Projective::zero();
+        for bytes in signatures {
+            aggregate += &G1Affine::deserialize(*bytes)?.into_projective();
+        }
+        serialize(&aggregate.into_affine())
+    }
+}
+
+/// Returns a bitmask of the supported curves, where bit `curve_id` is set if the curve
+/// is supported
+#[no_mangle]
+pub extern "C" fn supported_curves() -> u32 {
+    BACKENDS
+        .iter()
+        .fold(0, |mask, (curve_id, _)| mask | (1 << *curve_id))
+}
+
+// hands the bytes over to the caller, who must free them with `free_vec`
+unsafe fn write_bytes(mut bytes: Vec<u8>, out: *mut *mut u8, out_len: *mut c_int) {
+    bytes.shrink_to_fit();
+    *out = bytes.as_mut_ptr();
+    *out_len = bytes.len() as c_int;
+    std::mem::forget(bytes);
+}
+
+/// Signs the message and extra data with the serialized private key of the curve, and
+/// returns the serialized signature.
+///
+/// The returned buffer must be freed with `free_vec`.
+#[no_mangle]
+pub extern "C" fn sign_message_for_curve(
+    curve_id: c_int,
+    in_private_key: Buffer,
+    in_message: Buffer,
+    in_extra_data: Buffer,
+    should_use_composite: bool,
+    out_signature: *mut *mut u8,
+    out_signature_len: *mut c_int,
+) -> bool {
+    convert_result_to_bool::<_, CurveError, _>(|| {
+        let signature = unsafe {
+            (backend(curve_id)?.sign)(
+                in_private_key.as_slice(),
+                in_message.as_slice(),
+                in_extra_data.as_slice(),
+                should_use_composite,
+            )?
+        };
+        unsafe { write_bytes(signature, out_signature, out_signature_len) };
+        Ok(())
+    })
+}
+
+/// Verifies the serialized signature over the message and extra data against the serialized
+/// public key of the curve.
+///
+/// Returns `false` if the curve is not supported or any of the inputs could not be
+/// deserialized. Otherwise, `out_verified` is set to whether the signature is valid.
+#[no_mangle]
+pub extern "C" fn verify_signature_for_curve(
+    curve_id: c_int,
+    in_public_key: Buffer,
+    in_message: Buffer,
+    in_extra_data: Buffer,
+    in_signature: Buffer,
+    should_use_composite: bool,
+    out_verified: *mut bool,
+) -> bool {
+    convert_result_to_bool::<_, CurveError, _>(|| {
+        let verified = unsafe {
+            (backend(curve_id)?.verify)(
+                in_public_key.as_slice(),
+                in_message.as_slice(),
+                in_extra_data.as_slice(),
+                in_signature.as_slice(),
+                should_use_composite,
+            )?
+        };
+        unsafe { *out_verified = verified };
+        Ok(())
+    })
+}
+
+/// Aggregates the serialized public keys of the curve, and returns the serialized aggregate
+/// public key.
+///
+/// The returned buffer must be freed with `free_vec`.
+#[no_mangle]
+pub extern "C" fn aggregate_public_keys_for_curve(
+    curve_id: c_int,
+    in_public_keys: *const Buffer,
+    in_public_keys_len: c_int,
+    out_public_key: *mut *mut u8,
+    out_public_key_len: *mut c_int,
+) -> bool {
+    convert_result_to_bool::<_, CurveError, _>(|| {
+        let backend = backend(curve_id)?;
+        let public_keys = unsafe { buffers(in_public_keys, in_public_keys_len)? };
+        let public_key = (backend.aggregate_public_keys)(&public_keys)?;
+        unsafe { write_bytes(public_key, out_public_key, out_public_key_len) };
+        Ok(())
+    })
+}
+
+/// Aggregates the serialized signatures of the curve, and returns the serialized aggregate
+/// signature.
+///
+/// The returned buffer must be freed with `free_vec`.
+#[no_mangle]
+pub extern "C" fn aggregate_signatures_for_curve(
+    curve_id: c_int,
+    in_signatures: *const Buffer,
+    in_signatures_len: c_int,
+    out_signature: *mut *mut u8,
+    out_signature_len: *mut c_int,
+) -> bool {
+    convert_result_to_bool::<_, CurveError, _>(|| {
+        let backend = backend(curve_id)?;
+        let signatures = unsafe { buffers(in_signatures, in_signatures_len)? };
+        let signature = (backend.aggregate_signatures)(&signatures)?;
+        unsafe { write_bytes(signature, out_signature, out_signature_len) };
+        Ok(())
+    })
+}
+
+unsafe fn buffers<'a>(ptr: *const Buffer, len: c_int) -> Result<Vec<&'a [u8]>, CurveError> {
+    if len < 0 || (len > 0 && ptr.is_null()) {
+        return Err(CurveError::InvalidLength(len));
+    }
+    if len == 0 {
+        return Ok(vec![]);
+    }
+    Ok(slice::from_raw_parts(ptr, len as usize)
+        .iter()
+        .map(|buffer| buffer.as_slice())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::free_vec;
+
+    fn empty() -> Buffer {
+        Buffer {
+            ptr: std::ptr::null(),
+            len: 0,
+        }
+    }
+
+    fn serialize<T: CanonicalSerialize>(obj: &T) -> Vec<u8> {
+        let mut bytes = vec![];
+        obj.serialize(&mut bytes).unwrap();
+        bytes
+    }
+
+    // copies the returned bytes and frees them
+    fn take_bytes(out: *mut u8, out_len: c_int) -> Vec<u8> {
+        let bytes = unsafe { slice::from_raw_parts(out, out_len as usize) }.to_vec();
+        assert!(unsafe { free_vec(out, out_len) });
+        bytes
+    }
+
+    fn aggregate(
+        aggregate_fn: extern "C" fn(c_int, *const Buffer, c_int, *mut *mut u8, *mut c_int) -> bool,
+        curve_id: c_int,
+        items: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let buffers = items
+            .iter()
+            .map(|item| Buffer::from(&item[..]))
+            .collect::<Vec<_>>();
+        let mut out = std::ptr::null_mut();
+        let mut out_len = 0;
+        assert!(aggregate_fn(
+            curve_id,
+            buffers.as_ptr(),
+            buffers.len() as c_int,
+            &mut out,
+            &mut out_len,
+        ));
+        take_bytes(out, out_len)
+    }
+
+    // signs the message with each of the serialized private keys, and returns the aggregate
+    // public key and signature
+    fn sign_and_aggregate(
+        curve_id: c_int,
+        private_keys: &[Vec<u8>],
+        public_keys: &[Vec<u8>],
+        message: &[u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let signatures = private_keys
+            .iter()
+            .map(|private_key| {
+                let mut out = std::ptr::null_mut();
+                let mut out_len = 0;
+                assert!(sign_message_for_curve(
+                    curve_id,
+                    Buffer::from(&private_key[..]),
+                    Buffer::from(message),
+                    empty(),
+                    true,
+                    &mut out,
+                    &mut out_len,
+                ));
+                take_bytes(out, out_len)
+            })
+            .collect::<Vec<_>>();
+        (
+            aggregate(aggregate_public_keys_for_curve, curve_id, public_keys),
+            aggregate(aggregate_signatures_for_curve, curve_id, &signatures),
+        )
+    }
+
+    fn verify(
+        curve_id: c_int,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> (bool, bool) {
+        let mut verified = false;
+        let ok = verify_signature_for_curve(
+            curve_id,
+            Buffer::from(public_key),
+            Buffer::from(message),
+            empty(),
+            Buffer::from(signature),
+            true,
+            &mut verified,
+        );
+        (ok, verified)
+    }
+
+    #[test]
+    fn dispatches_to_supported_curves() {
+        assert_eq!(
+            supported_curves(),
+            (1 << CURVE_BLS12_377) | (1 << CURVE_BLS12_381)
+        );
+
+        let rng = &mut rand::thread_rng();
+        let message = &b"hello"[..];
+        let private_keys = (0..2)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = private_keys
+            .iter()
+            .map(|private_key| serialize(&private_key.to_public()))
+            .collect::<Vec<_>>();
+        let private_keys = private_keys.iter().map(serialize).collect::<Vec<_>>();
+        let (public_key, signature) =
+            sign_and_aggregate(CURVE_BLS12_377, &private_keys, &public_keys, message);
+
+        assert_eq!(
+            verify(CURVE_BLS12_377, &public_key, message, &signature),
+            (true, true)
+        );
+        assert_eq!(
+            verify(CURVE_BLS12_377, &public_key, b"other", &signature),
+            (true, false)
+        );
+        // the points are not on BLS12-381
+        assert_eq!(
+            verify(CURVE_BLS12_381, &public_key, message, &signature),
+            (false, false)
+        );
+        assert_eq!(verify(2, &public_key, message, &signature), (false, false));
+        assert_eq!(
+            crate::last_error_code(),
+            ErrorCode::InvalidArgument.as_i32()
+        );
+    }
+
+    #[test]
+    fn signs_on_bls12_381() {
+        use algebra::{
+            bls12_381::{Fr, G2Affine},
+            AffineCurve, ProjectiveCurve, UniformRand,
+        };
+
+        let rng = &mut rand::thread_rng();
+        let message = &b"hello"[..];
+        let secrets = (0..2).map(|_| Fr::rand(rng)).collect::<Vec<_>>();
+        let public_keys = secrets
+            .iter()
+            .map(|secret| {
+                serialize(
+                    &G2Affine::prime_subgroup_generator()
+                        .mul(*secret)
+                        .into_affine(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let private_keys = secrets.iter().map(serialize).collect::<Vec<_>>();
+        let (public_key, signature) =
+            sign_and_aggregate(CURVE_BLS12_381, &private_keys, &public_keys, message);
+
+        assert_eq!(
+            verify(CURVE_BLS12_381, &public_key, message, &signature),
+            (true, true)
+        );
+        assert_eq!(
+            verify(CURVE_BLS12_381, &public_key, b"other", &signature),
+            (true, false)
+        );
+        assert_eq!(
+            verify(CURVE_BLS12_377, &public_key, message, &signature),
+            (false, false)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_buffer_arrays() {
+        let mut out = std::ptr::null_mut();
+        let mut out_len = 0;
+        for (ptr, len) in &[(std::ptr::null(), -1), (std::ptr::null(), 1)] {
+            assert!(!aggregate_signatures_for_curve(
+                CURVE_BLS12_377,
+                *ptr,
+                *len,
+                &mut out,
+                &mut out_len,
+            ));
+            assert_eq!(
+                crate::last_error_code(),
+                ErrorCode::InvalidArgument.as_i32()
+            );
+        }
+    }
+}
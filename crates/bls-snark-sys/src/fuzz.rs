@@ -0,0 +1,56 @@
+//! Fuzz entrypoints for the FFI input structs
+//!
+//! The FFI structs hold raw pointers, so instead of generating them directly, each
+//! entrypoint builds them over the fuzzer's input so that they only point to valid memory.
+use crate::snark::epoch_block::{EpochBlockFFI, PUBKEY_BYTES};
+use arbitrary::{Arbitrary, Unstructured};
+use epoch_snark::EpochBlock;
+use std::convert::TryFrom;
+
+/// Fuzz entrypoint for the conversion of an `EpochBlockFFI` to an `EpochBlock`
+pub fn epoch_block_ffi(data: &[u8]) {
+    let mut u = Unstructured::new(data);
+    let (index, round, maximum_non_signers, maximum_validators, has_entropy) =
+        match <(u16, u8, u32, u8, bool)>::arbitrary(&mut u) {
+            Ok(fields) => fields,
+            Err(_) => return,
+        };
+    let entropy = match u.bytes(2 * EpochBlock::ENTROPY_BYTES) {
+        Ok(entropy) => entropy,
+        Err(_) => return,
+    };
+    let pubkeys = u.take_rest();
+
+    let (epoch_entropy, parent_entropy) = if has_entropy {
+        (
+            entropy.as_ptr(),
+            entropy[EpochBlock::ENTROPY_BYTES..].as_ptr(),
+        )
+    } else {
+        (std::ptr::null(), std::ptr::null())
+    };
+    let epoch = EpochBlockFFI {
+        index,
+        round,
+        epoch_entropy,
+        parent_entropy,
+        pubkeys: pubkeys.as_ptr(),
+        pubkeys_num: pubkeys.len() / PUBKEY_BYTES,
+        maximum_non_signers,
+        maximum_validators: maximum_validators as usize,
+    };
+
+    let _ = EpochBlock::try_from(&epoch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_arbitrary_input() {
+        epoch_block_ffi(&[]);
+        let data = (0..1024u32).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        epoch_block_ffi(&data);
+    }
+}
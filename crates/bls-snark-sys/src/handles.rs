@@ -0,0 +1,424 @@
+//! Owned Handles
+//!
+//! Objects handed over to the caller as a `SignatureHandle`, `ProofHandle` or `ParamsHandle`
+//! are owned by the caller, who must release each of them exactly once with the `_free`
+//! function of its type. The caller only gets an opaque `HandleId`, which indexes a slot of
+//! a locked slab and is tagged with the generation of the slot. Freeing a handle bumps the
+//! generation of its slot, so that freeing a handle twice, or passing an ID which is not a
+//! live handle of the expected type, fails and returns `false` even if the slot was reused
+//! since. This allows garbage collected callers (e.g. Go finalizers) to free handles which
+//! may already have been freed explicitly.
+//!
+//! The values are reference counted, so that a handle freed while another thread is using
+//! it is only dropped once that thread is done with it.
+//!
+//! Byte buffers returned alongside handles are owned by the caller as well and must be freed
+//! with `free_vec`.
+use crate::{
+    convert_result_to_bool,
+    snark::epoch_block::{read_slice, EpochBlockFFI, EpochBlockFFIV2},
+    Signature,
+};
+use algebra::CanonicalSerialize;
+use bls_crypto::{ErrorCode, ToErrorCode};
+use epoch_snark::{read_parameters, BWCurve, EncodingError, EpochBlock, VerificationError};
+use groth16::{Parameters, Proof};
+use once_cell::sync::Lazy;
+use std::{
+    any::Any,
+    convert::TryFrom,
+    os::raw::c_int,
+    slice,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use thiserror::Error;
+
+/// An opaque handle: the index of its slot plus one in the low 32 bits, so that 0 is never
+/// a live handle, and the generation of the slot in the high 32 bits
+pub type HandleId = u64;
+
+/// The live handles
+static LIVE_HANDLES: Lazy<Mutex<Slab>> = Lazy::new(|| Mutex::new(Slab::default()));
+
+#[derive(Debug, Error)]
+/// Error raised by the functions taking handles
+pub enum HandleError {
+    #[error("not a live {0}")]
+    InvalidHandle(&'static str),
+    #[error("Encoding Error: {0}")]
+    EncodingError(#[from] EncodingError),
+    #[error("Verification Error: {0}")]
+    VerificationError(#[from] VerificationError),
+}
+
+impl ToErrorCode for HandleError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            HandleError::InvalidHandle(_) => ErrorCode::InvalidArgument,
+            HandleError::EncodingError(e) => e.error_code(),
+            HandleError::VerificationError(e) => e.error_code(),
+        }
+    }
+}
+
+/// A type whose instances are handed over to the caller as tracked handles
+trait Handle: Any + Send + Sync {
+    const NAME: &'static str;
+}
+
+/// A BLS signature owned by the caller, to be freed with `signature_handle_free`
+pub struct SignatureHandle(pub Signature);
+
+/// A proof of the epochs circuit owned by the caller, to be freed with `proof_handle_free`
+pub struct ProofHandle(pub Proof<BWCurve>);
+
+/// The parameters of the epochs circuit owned by the caller, to be freed with
+/// `params_handle_free`
+pub struct ParamsHandle(pub Parameters<BWCurve>);
+
+impl Handle for SignatureHandle {
+    const NAME: &'static str = "SignatureHandle";
+}
+
+impl Handle for ProofHandle {
+    const NAME: &'static str = "ProofHandle";
+}
+
+impl Handle for ParamsHandle {
+    const NAME: &'static str = "ParamsHandle";
+}
+
+#[derive(Default)]
+struct Slot {
+    generation: u32,
+    value: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+/// The slots of the handles, whose free slots are reused
+#[derive(Default)]
+struct Slab {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    live: usize,
+}
+
+impl Slab {
+    fn insert(&mut self, value: Arc<dyn Any + Send + Sync>) -> HandleId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot::default());
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.value = Some(value);
+        self.live += 1;
+        (u64::from(slot.generation) << 32) | (index as u64 + 1)
+    }
+
+    /// The slot of a live handle
+    fn slot(&mut self, id: HandleId) -> Option<&mut Slot> {
+        let index = ((id & u64::from(u32::MAX)) as usize).checked_sub(1)?;
+        let generation = (id >> 32) as u32;
+        self.slots
+            .get_mut(index)
+            .filter(|slot| slot.generation == generation && slot.value.is_some())
+    }
+
+    fn get<T: Handle>(&mut self, id: HandleId) -> Option<Arc<T>> {
+        let value = self.slot(id)?.value.clone()?;
+        value.downcast::<T>().ok()
+    }
+
+    fn remove<T: Handle>(&mut self, id: HandleId) -> Option<Arc<dyn Any + Send + Sync>> {
+        let index = ((id & u64::from(u32::MAX)) as usize).checked_sub(1)?;
+        let slot = self.slot(id)?;
+        if !slot.value.as_ref()?.is::<T>() {
+            return None;
+        }
+        // stale IDs of the slot are rejected once it is reused
+        slot.generation = slot.generation.wrapping_add(1);
+        let value = slot.value.take();
+        self.free.push(index);
+        self.live -= 1;
+        value
+    }
+}
+
+fn live_handles() -> MutexGuard<'static, Slab> {
+    LIVE_HANDLES.lock().expect("mutex poisoned")
+}
+
+/// Moves the value to the slab and returns its handle
+fn into_handle<T: Handle>(value: T) -> HandleId {
+    live_handles().insert(Arc::new(value))
+}
+
+/// Returns a reference to the value of a live handle, which remains valid even if the
+/// handle is freed concurrently
+fn borrow_handle<T: Handle>(handle: HandleId) -> Result<Arc<T>, HandleError> {
+    live_handles()
+        .get(handle)
+        .ok_or(HandleError::InvalidHandle(T::NAME))
+}
+
+/// Unregisters the value of a live handle, which is dropped once it is no longer borrowed.
+/// Returns false if the handle is not live, e.g. because it has already been freed.
+fn free_handle<T: Handle>(handle: HandleId) -> bool {
+    // the value is dropped after the lock is released
+    let value = live_handles().remove::<T>(handle);
+    if value.is_none() {
+        log::error!(
+            "Attempted to free {:#x}, which is not a live {}",
+            handle,
+            T::NAME
+        );
+        return false;
+    }
+    true
+}
+
+/// Returns the number of live handles of all types, e.g. for leak checks in tests
+#[no_mangle]
+pub extern "C" fn live_handles_count() -> usize {
+    live_handles().live
+}
+
+/// Deserializes a compressed signature into a new handle
+///
+/// # Safety
+///
+/// `in_bytes` must point to `in_bytes_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn signature_handle_from_bytes(
+    in_bytes: *const u8,
+    in_bytes_len: c_int,
+    out_handle: *mut HandleId,
+) -> bool {
+    convert_result_to_bool::<_, HandleError, _>(|| {
+        let signature = read_slice(in_bytes, in_bytes_len as usize)?;
+        *out_handle = into_handle(SignatureHandle(signature));
+        Ok(())
+    })
+}
+
+/// Serializes the handle's signature in compressed form. The returned buffer must be freed
+/// with `free_vec`.
+///
+/// # Safety
+///
+/// `out_bytes` and `out_len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn signature_handle_to_bytes(
+    handle: HandleId,
+    out_bytes: *mut *mut u8,
+    out_len: *mut c_int,
+) -> bool {
+    convert_result_to_bool::<_, HandleError, _>(|| {
+        let handle = borrow_handle::<SignatureHandle>(handle)?;
+        let mut bytes = vec![];
+        handle
+            .0
+            .serialize(&mut bytes)
+            .map_err(EncodingError::from)?;
+        bytes.shrink_to_fit();
+        *out_bytes = bytes.as_mut_ptr();
+        *out_len = bytes.len() as c_int;
+        std::mem::forget(bytes);
+        Ok(())
+    })
+}
+
+/// Frees the signature handle. Returns false if it is not a live signature handle.
+#[no_mangle]
+pub extern "C" fn signature_handle_free(handle: HandleId) -> bool {
+    free_handle::<SignatureHandle>(handle)
+}
+
+/// Deserializes a compressed proof of the epochs circuit into a new handle
+///
+/// # Safety
+///
+/// `in_bytes` must point to `in_bytes_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn proof_handle_from_bytes(
+    in_bytes: *const u8,
+    in_bytes_len: c_int,
+    out_handle: *mut HandleId,
+) -> bool {
+    convert_result_to_bool::<_, HandleError, _>(|| {
+        let proof = read_slice(in_bytes, in_bytes_len as usize)?;
+        *out_handle = into_handle(ProofHandle(proof));
+        Ok(())
+    })
+}
+
+/// Frees the proof handle. Returns false if it is not a live proof handle.
+#[no_mangle]
+pub extern "C" fn proof_handle_free(handle: HandleId) -> bool {
+    free_handle::<ProofHandle>(handle)
+}
+
+/// Deserializes the compressed parameters of the epochs circuit into a new handle, checking
+/// that their points are in the prime order subgroup
+///
+/// # Safety
+///
+/// `in_bytes` must point to `in_bytes_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn params_handle_from_bytes(
+    in_bytes: *const u8,
+    in_bytes_len: c_int,
+    out_handle: *mut HandleId,
+) -> bool {
+    convert_result_to_bool::<_, HandleError, _>(|| {
+        let bytes = slice::from_raw_parts(in_bytes, in_bytes_len as usize);
+        let params = read_parameters(bytes).map_err(EncodingError::from)?;
+        *out_handle = into_handle(ParamsHandle(params));
+        Ok(())
+    })
+}
+
+/// Frees the parameters handle. Returns false if it is not a live parameters handle.
+#[no_mangle]
+pub extern "C" fn params_handle_free(handle: HandleId) -> bool {
+    free_handle::<ParamsHandle>(handle)
+}
+
+/// Same as `verify`, but with the verifying key of the parameters handle and the proof of
+/// the proof handle
+///
+/// # Safety
+///
+/// The vectors of pubkeys inside the `EpochBlockFFI`s must point to valid memory.
+#[no_mangle]
+pub unsafe extern "C" fn verify_handles(
+    params: HandleId,
+    proof: HandleId,
+    first_epoch: EpochBlockFFI,
+    last_epoch: EpochBlockFFI,
+) -> bool {
+    convert_result_to_bool::<_, HandleError, _>(|| {
+        let params = borrow_handle::<ParamsHandle>(params)?;
+        let proof = borrow_handle::<ProofHandle>(proof)?;
+        let first_epoch = EpochBlock::try_from(&first_epoch)?;
+        let last_epoch = EpochBlock::try_from(&last_epoch)?;
+        Ok(epoch_snark::verify(
+            &params.0.vk,
+            &first_epoch,
+            &last_epoch,
+            &proof.0,
+        )?)
+    })
+}
+
+/// Same as `verify_v2`, but with the verifying key of the parameters handle and the proof of
+/// the proof handle
+///
+/// # Safety
+///
+/// The vectors of pubkeys and the non-null pointers inside the `EpochBlockFFIV2`s must point
+/// to valid memory.
+#[no_mangle]
+pub unsafe extern "C" fn verify_handles_v2(
+    params: HandleId,
+    proof: HandleId,
+    first_epoch: EpochBlockFFIV2,
+    last_epoch: EpochBlockFFIV2,
+) -> bool {
+    convert_result_to_bool::<_, HandleError, _>(|| {
+        let params = borrow_handle::<ParamsHandle>(params)?;
+        let proof = borrow_handle::<ProofHandle>(proof)?;
+        let first_epoch = EpochBlock::try_from(&first_epoch)?;
+        let last_epoch = EpochBlock::try_from(&last_epoch)?;
+        Ok(epoch_snark::verify(
+            &params.0.vk,
+            &first_epoch,
+            &last_epoch,
+            &proof.0,
+        )?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::free_vec;
+    use algebra::{bls12_377::G1Projective, UniformRand};
+
+    #[test]
+    fn handles_are_freed_once() {
+        let rng = &mut rand::thread_rng();
+        let signature = Signature::from(G1Projective::rand(rng));
+        let mut bytes = vec![];
+        signature.serialize(&mut bytes).unwrap();
+
+        unsafe {
+            let mut handle = 0;
+            assert!(signature_handle_from_bytes(
+                &bytes[0],
+                bytes.len() as c_int,
+                &mut handle
+            ));
+            assert_eq!(
+                borrow_handle::<SignatureHandle>(handle).unwrap().0,
+                signature
+            );
+
+            let mut out = std::ptr::null_mut();
+            let mut out_len = 0;
+            assert!(signature_handle_to_bytes(handle, &mut out, &mut out_len));
+            assert_eq!(slice::from_raw_parts(out, out_len as usize), &bytes[..]);
+            assert!(free_vec(out, out_len));
+
+            // a signature handle is not a proof handle
+            assert!(!proof_handle_free(handle));
+            let borrowed = borrow_handle::<SignatureHandle>(handle).unwrap();
+            assert!(signature_handle_free(handle));
+            assert!(!signature_handle_free(handle));
+            assert!(!signature_handle_to_bytes(handle, &mut out, &mut out_len));
+            assert_eq!(crate::last_error_code(), 7);
+            // a value freed while it is borrowed remains valid until it is released
+            assert_eq!(borrowed.0, signature);
+
+            // the slot is reused with a new generation, so the freed ID remains invalid
+            let mut reused = 0;
+            assert!(signature_handle_from_bytes(
+                &bytes[0],
+                bytes.len() as c_int,
+                &mut reused
+            ));
+            assert_ne!(reused, handle);
+            assert!(!signature_handle_free(handle));
+            assert!(signature_handle_free(reused));
+        }
+        assert!(!signature_handle_free(0));
+    }
+
+    #[test]
+    fn deserializes_proofs() {
+        let mut bytes = vec![];
+        Proof::<BWCurve>::default().serialize(&mut bytes).unwrap();
+
+        unsafe {
+            let mut handle = 0;
+            assert!(proof_handle_from_bytes(
+                &bytes[0],
+                bytes.len() as c_int,
+                &mut handle
+            ));
+            assert_eq!(
+                borrow_handle::<ProofHandle>(handle).unwrap().0,
+                Proof::default()
+            );
+            assert!(proof_handle_free(handle));
+            assert!(!proof_handle_free(handle));
+
+            // nothing is allocated on failure
+            let mut params = 0;
+            assert!(!params_handle_from_bytes(&bytes[0], 1, &mut params));
+            assert_eq!(params, 0);
+        }
+    }
+}
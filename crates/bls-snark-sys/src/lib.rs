@@ -8,21 +8,47 @@ type PrivateKey = bls::PrivateKey;
 type PublicKeyCache = bls::PublicKeyCache;
 
 use bls_crypto::hash_to_curve::try_and_increment::{COMPOSITE_HASH_TO_G1, DIRECT_HASH_TO_G1};
+use bls_crypto::{ErrorCode, ToErrorCode};
 use core::fmt::Display;
 use once_cell::sync::Lazy;
+use std::{cell::Cell, os::raw::c_int};
 
 pub(crate) mod cache;
+pub mod curves;
+pub mod handles;
+pub mod logging;
 pub mod serialization;
 pub mod signatures;
 pub mod snark;
 pub mod utils;
 
-pub fn convert_result_to_bool<T, E: Display, F: Fn() -> Result<T, E>>(f: F) -> bool {
-    if let Err(e) = f() {
-        log::error!("SNARK library error: {}", e);
-        return false;
-    }
-    true
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+
+thread_local! {
+    static LAST_ERROR_CODE: Cell<ErrorCode> = Cell::new(ErrorCode::Success);
+}
+
+/// Runs `f`, logs its error if it failed, and records its error code so that it can be
+/// retrieved with `last_error_code`
+pub fn convert_result_to_bool<T, E: Display + ToErrorCode, F: Fn() -> Result<T, E>>(f: F) -> bool {
+    let code = match f() {
+        Ok(_) => ErrorCode::Success,
+        Err(e) => {
+            log::error!("SNARK library error: {}", e);
+            e.error_code()
+        }
+    };
+    LAST_ERROR_CODE.with(|last| last.set(code));
+    code == ErrorCode::Success
+}
+
+#[no_mangle]
+/// Returns the error code of the last fallible call made from this thread, or 0 if it
+/// succeeded. The codes are listed in the `celo-bls-errors` crate, e.g. 2 for an invalid
+/// signature, 3 for a point outside the prime order subgroup and 10 for an invalid proof.
+pub extern "C" fn last_error_code() -> c_int {
+    LAST_ERROR_CODE.with(|last| last.get().as_i32())
 }
 
 #[no_mangle]
@@ -31,3 +57,23 @@ pub extern "C" fn init() {
     Lazy::force(&COMPOSITE_HASH_TO_G1);
     Lazy::force(&DIRECT_HASH_TO_G1);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls_crypto::BLSError;
+
+    #[test]
+    fn records_last_error_code() {
+        assert!(!convert_result_to_bool(|| Err::<(), _>(
+            BLSError::VerificationFailed
+        )));
+        assert_eq!(last_error_code(), 2);
+        assert!(!convert_result_to_bool(|| Err::<(), _>(
+            BLSError::NotInSubgroup
+        )));
+        assert_eq!(last_error_code(), 3);
+        assert!(convert_result_to_bool(|| Ok::<_, BLSError>(())));
+        assert_eq!(last_error_code(), 0);
+    }
+}
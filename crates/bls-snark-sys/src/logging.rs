@@ -0,0 +1,196 @@
+//! Log Output
+//!
+//! The library reports its progress and errors with `tracing` events (and, for a few call
+//! sites, `log` records), which are discarded until a subscriber is installed. Callers which
+//! embed the library, e.g. the Go client, install one with `init_logging`, which forwards
+//! every event at or above the requested level to a callback, so that the lines end up in
+//! the caller's structured logger rather than on stdout.
+//!
+//! The levels are numbered as in the `log` crate: 1 for errors, 2 for warnings, 3 for info,
+//! 4 for debug and 5 for trace events.
+use crate::convert_result_to_bool;
+use bls_crypto::{ErrorCode, ToErrorCode};
+use std::{
+    ffi::CString,
+    fmt::{self, Write},
+    os::raw::{c_char, c_int},
+};
+use thiserror::Error;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_log::{LogTracer, NormalizeEvent};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    layer::{Context, Layer, SubscriberExt},
+    Registry,
+};
+
+/// Receives each log line with its level, its target (usually the module which emitted it)
+/// and its message, followed by the event's fields as `name=value` pairs. The strings are
+/// only valid for the duration of the call. The callback may be called concurrently from
+/// any thread.
+pub type LogCallback = extern "C" fn(level: c_int, target: *const c_char, message: *const c_char);
+
+#[derive(Debug, Error)]
+/// Error raised when installing the logger
+pub enum LoggingError {
+    #[error("invalid log level {0}, expected 1 (error) to 5 (trace)")]
+    InvalidLevel(c_int),
+    #[error("a logger is already installed")]
+    AlreadyInitialized,
+}
+
+impl ToErrorCode for LoggingError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            LoggingError::InvalidLevel(_) => ErrorCode::InvalidArgument,
+            LoggingError::AlreadyInitialized => ErrorCode::Unknown,
+        }
+    }
+}
+
+/// The levels in the order of their numbers, starting at 1
+const LEVELS: [(Level, log::LevelFilter); 5] = [
+    (Level::ERROR, log::LevelFilter::Error),
+    (Level::WARN, log::LevelFilter::Warn),
+    (Level::INFO, log::LevelFilter::Info),
+    (Level::DEBUG, log::LevelFilter::Debug),
+    (Level::TRACE, log::LevelFilter::Trace),
+];
+
+#[no_mangle]
+/// Installs a process-wide logger which forwards the events at `level` or above (i.e. more
+/// severe) to `callback`. Fails if the level is invalid or if a `tracing` subscriber was
+/// already installed, which includes a previous call to this function. If a `log` logger
+/// was already installed, it is kept and only the `tracing` events are forwarded.
+pub extern "C" fn init_logging(level: c_int, callback: LogCallback) -> bool {
+    convert_result_to_bool::<_, LoggingError, _>(|| {
+        let (tracing_level, log_level) = level_at(level)?;
+        let subscriber = Registry::default()
+            .with(LevelFilter::from_level(tracing_level))
+            .with(CallbackLayer { callback });
+        tracing::subscriber::set_global_default(subscriber)
+            .map_err(|_| LoggingError::AlreadyInitialized)?;
+        // forwards the `log` records as events, unless another `log` logger is installed, in
+        // which case the subscriber is installed all the same and the records go there
+        if LogTracer::init_with_filter(log_level).is_err() {
+            tracing::warn!("a `log` logger is already installed, its records are not forwarded");
+        }
+        Ok(())
+    })
+}
+
+fn level_at(level: c_int) -> Result<(Level, log::LevelFilter), LoggingError> {
+    if level < 1 || level as usize > LEVELS.len() {
+        return Err(LoggingError::InvalidLevel(level));
+    }
+    Ok(LEVELS[level as usize - 1])
+}
+
+fn level_number(level: &Level) -> c_int {
+    LEVELS
+        .iter()
+        .position(|(l, _)| l == level)
+        .expect("all levels are listed") as c_int
+        + 1
+}
+
+/// Tracing layer which formats each event and passes it to the callback
+struct CallbackLayer {
+    callback: LogCallback,
+}
+
+impl<S: Subscriber> Layer<S> for CallbackLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // the metadata of converted `log` records is carried by their fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        let mut line = LineVisitor::default();
+        event.record(&mut line);
+        let message = line.message + &line.fields;
+
+        // the strings cannot contain NUL bytes
+        let target =
+            CString::new(metadata.target().replace('\0', "")).expect("NUL bytes were removed");
+        let message = CString::new(message.replace('\0', "")).expect("NUL bytes were removed");
+        (self.callback)(
+            level_number(metadata.level()),
+            target.as_ptr(),
+            message.as_ptr(),
+        );
+    }
+}
+
+/// Collects the message of an event, and its other fields as `name=value` pairs
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            // the metadata of converted `log` records, see `on_event`
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+    use std::{ffi::CStr, sync::Mutex};
+
+    static LINES: Lazy<Mutex<Vec<(c_int, String, String)>>> = Lazy::new(|| Mutex::new(vec![]));
+
+    extern "C" fn record(level: c_int, target: *const c_char, message: *const c_char) {
+        let (target, message) = unsafe { (CStr::from_ptr(target), CStr::from_ptr(message)) };
+        LINES.lock().unwrap().push((
+            level,
+            target.to_string_lossy().into_owned(),
+            message.to_string_lossy().into_owned(),
+        ));
+    }
+
+    #[test]
+    fn forwards_events_to_the_callback() {
+        assert!(!init_logging(0, record));
+        assert!(!init_logging(6, record));
+        assert!(init_logging(3, record));
+        assert!(!init_logging(3, record));
+
+        tracing::info!(target: "logging_test", epoch = 7, "proving {}", "epochs");
+        tracing::debug!(target: "logging_test", "too verbose");
+        log::warn!(target: "logging_test", "from log");
+
+        let lines = LINES
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, target, _)| target == "logging_test")
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (
+                    3,
+                    "logging_test".to_owned(),
+                    "proving epochs epoch=7".to_owned()
+                ),
+                (2, "logging_test".to_owned(), "from log".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,12 +1,18 @@
 use crate::{
     cache::PUBLIC_KEY_CACHE,
     convert_result_to_bool,
-    utils::{Message, MessageFFI},
+    snark::epoch_block::PUBKEY_BYTES,
+    utils::{Buffer, Message, MessageFFI},
     PrivateKey, PublicKey, Signature, COMPOSITE_HASH_TO_G1, DIRECT_HASH_TO_G1,
 };
-use algebra::{ProjectiveCurve, ToBytes};
+use algebra::{
+    bls12_377::{Fr, G1Affine, G1Projective, G2Affine, G2Projective},
+    AffineCurve, CanonicalDeserialize, CanonicalSerialize, ProjectiveCurve, ToBytes, UniformRand,
+    Zero,
+};
 use bls_crypto::hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22;
 use bls_crypto::{BLSError, HashToCurve, POP_DOMAIN, SIG_DOMAIN};
+use rayon::prelude::*;
 use std::{os::raw::c_int, slice};
 
 /// # Safety
@@ -86,6 +92,27 @@ pub extern "C" fn sign_pop(
     })
 }
 
+/// Signs a message which the caller has already hashed to G1, provided as a compressed
+/// point. Fails if the point is the identity or is not in the prime order subgroup.
+#[no_mangle]
+pub extern "C" fn sign_prehashed(
+    in_private_key: *const PrivateKey,
+    in_message_hash: *const u8,
+    in_message_hash_len: c_int,
+    out_signature: *mut *mut Signature,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        let private_key = unsafe { &*in_private_key };
+        let message_hash = unsafe { read_message_hash(in_message_hash, in_message_hash_len)? };
+        let signature = private_key.sign_prehashed(&message_hash)?;
+        unsafe {
+            *out_signature = Box::into_raw(Box::new(signature));
+        }
+
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn hash_direct(
     in_message: *const u8,
@@ -254,6 +281,172 @@ pub extern "C" fn batch_verify_signature(
     })
 }
 
+#[no_mangle]
+/// Receives a list of messages composed of:
+/// 1. the data
+/// 1. the public key which signed on the data
+/// 1. the signature produced by the public key
+///
+/// Each signature is verified individually against its (data, public key) pair. The
+/// verifications are executed concurrently on Rayon's global thread pool. The result
+/// of each verification is written to the corresponding index of `out_verified`, which
+/// must point to a buffer of `messages_len` booleans allocated by the caller.
+pub extern "C" fn batch_verify_signatures(
+    messages_ptr: *const MessageFFI,
+    messages_len: usize,
+    should_use_composite: bool,
+    should_use_cip22: bool,
+    out_verified: *mut bool,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        let messages: &[MessageFFI] = unsafe { slice::from_raw_parts(messages_ptr, messages_len) };
+        let messages = messages.iter().map(Message::from).collect::<Vec<_>>();
+        let out_verified = unsafe { slice::from_raw_parts_mut(out_verified, messages_len) };
+
+        match (should_use_composite, should_use_cip22) {
+            (true, true) => verify_each(&messages, out_verified, &*COMPOSITE_HASH_TO_G1_CIP22),
+            (false, true) => return Err(BLSError::HashToCurveError),
+            (true, false) => verify_each(&messages, out_verified, &*COMPOSITE_HASH_TO_G1),
+            (false, false) => verify_each(&messages, out_verified, &*DIRECT_HASH_TO_G1),
+        };
+
+        Ok(())
+    })
+}
+
+/// Verifies each message in parallel, writing the result to the same index of `out_verified`
+fn verify_each<H: HashToCurve<Output = G1Projective> + Sync>(
+    messages: &[Message],
+    out_verified: &mut [bool],
+    hash_to_g1: &H,
+) {
+    messages
+        .par_iter()
+        .zip(out_verified.par_iter_mut())
+        .for_each(|(message, verified)| {
+            *verified = message
+                .public_key
+                .verify(message.data, message.extra, message.sig, hash_to_g1)
+                .is_ok();
+        });
+}
+
+#[no_mangle]
+/// Receives a list of signature shares, i.e. messages composed of:
+/// 1. the data
+/// 1. the public key which signed on the data
+/// 1. the signature share produced by the public key
+///
+/// The shares are first checked together with a single randomized batch verification, and
+/// only if that fails is each share verified individually to identify the invalid ones. The
+/// indices of the invalid shares are written in increasing order to `out_invalid`, which must
+/// point to a buffer of `messages_len` indices allocated by the caller, and their number to
+/// `out_invalid_len`, which is 0 if all the shares are valid.
+///
+/// The batch verification weighs each share with a random scalar, so that invalid shares
+/// cannot cancel each other out in the aggregate.
+pub extern "C" fn verify_signature_shares(
+    messages_ptr: *const MessageFFI,
+    messages_len: usize,
+    should_use_composite: bool,
+    should_use_cip22: bool,
+    out_invalid: *mut usize,
+    out_invalid_len: *mut usize,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        let messages: &[MessageFFI] = unsafe { slice::from_raw_parts(messages_ptr, messages_len) };
+        let messages = messages.iter().map(Message::from).collect::<Vec<_>>();
+
+        let invalid = match (should_use_composite, should_use_cip22) {
+            (true, true) => invalid_shares(&messages, &*COMPOSITE_HASH_TO_G1_CIP22)?,
+            (false, true) => return Err(BLSError::HashToCurveError),
+            (true, false) => invalid_shares(&messages, &*COMPOSITE_HASH_TO_G1)?,
+            (false, false) => invalid_shares(&messages, &*DIRECT_HASH_TO_G1)?,
+        };
+
+        let out_invalid = unsafe { slice::from_raw_parts_mut(out_invalid, messages_len) };
+        out_invalid[..invalid.len()].copy_from_slice(&invalid);
+        unsafe { *out_invalid_len = invalid.len() };
+        Ok(())
+    })
+}
+
+/// Returns the indices of the invalid shares, falling back to verifying each share only if
+/// their randomized batch verification fails
+fn invalid_shares<H: HashToCurve<Output = G1Projective> + Sync>(
+    messages: &[Message],
+    hash_to_g1: &H,
+) -> Result<Vec<usize>, BLSError> {
+    let message_hashes = messages
+        .par_iter()
+        .map(|m| hash_to_g1.hash(SIG_DOMAIN, m.data, m.extra))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let rng = &mut rand::thread_rng();
+    let scalars = (0..messages.len())
+        .map(|_| Fr::rand(rng))
+        .collect::<Vec<_>>();
+    let weighted_hashes = message_hashes
+        .iter()
+        .zip(&scalars)
+        .map(|(hash, scalar)| hash.mul(*scalar))
+        .collect::<Vec<_>>();
+    let weighted_signature = Signature::from(
+        messages
+            .iter()
+            .zip(&scalars)
+            .map(|(m, scalar)| m.sig.as_ref().mul(*scalar))
+            .sum::<G1Projective>(),
+    );
+    let pubkeys = messages.iter().map(|m| m.public_key).collect::<Vec<_>>();
+    if weighted_signature
+        .batch_verify_hashes(&pubkeys, &weighted_hashes)
+        .is_ok()
+    {
+        return Ok(vec![]);
+    }
+
+    Ok(messages
+        .par_iter()
+        .zip(&message_hashes)
+        .enumerate()
+        .filter(|(_, (m, hash))| m.public_key.verify_exact(hash, m.sig).is_err())
+        .map(|(i, _)| i)
+        .collect())
+}
+
+/// Verifies a signature produced by `sign_prehashed` over the compressed message hash.
+/// Fails if the message hash is invalid, and sets `out_verified` to whether the signature
+/// is valid otherwise.
+#[no_mangle]
+pub extern "C" fn verify_prehashed(
+    in_public_key: *const PublicKey,
+    in_message_hash: *const u8,
+    in_message_hash_len: c_int,
+    in_signature: *const Signature,
+    out_verified: *mut bool,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        let public_key = unsafe { &*in_public_key };
+        let message_hash = unsafe { read_message_hash(in_message_hash, in_message_hash_len)? };
+        let signature = unsafe { &*in_signature };
+        let verified = match public_key.verify_prehashed(&message_hash, signature) {
+            Ok(()) => true,
+            Err(e @ BLSError::IdentityMessageHash) => return Err(e),
+            Err(_) => false,
+        };
+        unsafe { *out_verified = verified };
+
+        Ok(())
+    })
+}
+
+/// Deserializes a compressed G1 point, which is checked to be on the curve
+unsafe fn read_message_hash(ptr: *const u8, len: c_int) -> Result<G1Projective, BLSError> {
+    let bytes = slice::from_raw_parts(ptr, len as usize);
+    Ok(G1Affine::deserialize(bytes)?.into_projective())
+}
+
 #[no_mangle]
 pub extern "C" fn verify_pop(
     in_public_key: *const PublicKey,
@@ -275,6 +468,61 @@ pub extern "C" fn verify_pop(
     })
 }
 
+/// Signs a proof of possession over the message with the serialized private key, and
+/// returns the serialized signature. Unlike `sign_pop`, only byte buffers cross the FFI
+/// boundary, so callers do not need to manage the lifetime of deserialized keys.
+///
+/// The returned buffer must be freed with `free_vec`.
+#[no_mangle]
+pub extern "C" fn generate_pop(
+    in_private_key: Buffer,
+    in_message: Buffer,
+    out_pop: *mut *mut u8,
+    out_pop_len: *mut c_int,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        let private_key = PrivateKey::deserialize(unsafe { in_private_key.as_slice() })?;
+        let message = unsafe { in_message.as_slice() };
+        let pop = private_key.sign_pop(message, &*DIRECT_HASH_TO_G1)?;
+
+        let mut pop_bytes = vec![];
+        pop.serialize(&mut pop_bytes)?;
+        pop_bytes.shrink_to_fit();
+        unsafe {
+            *out_pop = pop_bytes.as_mut_ptr();
+            *out_pop_len = pop_bytes.len() as c_int;
+        }
+        std::mem::forget(pop_bytes);
+
+        Ok(())
+    })
+}
+
+/// Verifies a serialized proof of possession over the message against the serialized public
+/// key. This is the buffer based equivalent of `verify_pop`, which takes deserialized objects.
+///
+/// Returns `false` if any of the inputs could not be deserialized. Otherwise, `out_verified`
+/// is set to whether the proof of possession is valid.
+#[no_mangle]
+pub extern "C" fn verify_serialized_pop(
+    in_public_key: Buffer,
+    in_message: Buffer,
+    in_pop: Buffer,
+    out_verified: *mut bool,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        let public_key = PublicKey::deserialize(unsafe { in_public_key.as_slice() })?;
+        let pop = Signature::deserialize(unsafe { in_pop.as_slice() })?;
+        let message = unsafe { in_message.as_slice() };
+        let verified = public_key
+            .verify_pop(message, &pop, &*DIRECT_HASH_TO_G1)
+            .is_ok();
+        unsafe { *out_verified = verified };
+
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn aggregate_public_keys(
     in_public_keys: *const *const PublicKey,
@@ -354,3 +602,461 @@ pub extern "C" fn aggregate_signatures(
         Ok(())
     })
 }
+
+/// Aggregates the public keys without copying them into Rust-owned memory. Unlike
+/// `aggregate_public_keys`, this does not go through the public key cache.
+///
+/// # Safety
+///
+/// in_public_keys must point to `in_public_keys_len` valid public key pointers. The keys
+/// are only borrowed for the duration of the call and no reference to them is retained
+/// after it returns. out_public_key must initialized to memory that can contain a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn aggregate_public_keys_borrowed(
+    in_public_keys: *const *const PublicKey,
+    in_public_keys_len: c_int,
+    out_public_key: *mut *mut PublicKey,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        if in_public_keys.is_null() || out_public_key.is_null() {
+            return Err(BLSError::InvalidArgument("null pointer"));
+        }
+        if in_public_keys_len < 0 {
+            return Err(BLSError::InvalidArgument("negative number of public keys"));
+        }
+        let public_keys = slice::from_raw_parts(in_public_keys, in_public_keys_len as usize);
+        let aggregated_public_key = PublicKey::aggregate(public_keys.iter().map(|pk| &**pk));
+        *out_public_key = Box::into_raw(Box::new(aggregated_public_key));
+        Ok(())
+    })
+}
+
+/// Aggregates the compressed public keys serialized back to back in `in_public_keys`,
+/// deserializing them directly from the caller's memory.
+///
+/// This avoids allocating a `PublicKey` per validator, which matters for large
+/// validator sets.
+///
+/// # Safety
+///
+/// in_public_keys must point to `len` readable bytes, which must be a multiple of the 96 byte
+/// compressed public key size. The buffer is only borrowed for the duration of the call and no
+/// reference to it is retained after it returns. out_public_key must initialized to memory
+/// that can contain a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn aggregate_serialized_public_keys(
+    in_public_keys: Buffer,
+    out_public_key: *mut *mut PublicKey,
+) -> bool {
+    convert_result_to_bool::<_, BLSError, _>(|| {
+        if out_public_key.is_null() {
+            return Err(BLSError::InvalidArgument("null pointer"));
+        }
+        if in_public_keys.len % PUBKEY_BYTES != 0 {
+            return Err(BLSError::InvalidArgument(
+                "the length is not a multiple of the public key size",
+            ));
+        }
+        let mut data = in_public_keys.as_slice();
+        let mut aggregate = G2Projective::zero();
+        while !data.is_empty() {
+            aggregate += &G2Affine::deserialize(&mut data)?.into_projective();
+        }
+        *out_public_key = Box::into_raw(Box::new(PublicKey::from(aggregate)));
+        Ok(())
+    })
+}
+
+/// Context for aggregating signatures as they arrive, instead of
+/// buffering them until quorum is reached
+pub struct SignatureAggregator {
+    aggregate: G1Projective,
+    count: usize,
+}
+
+/// # Safety
+///
+/// out_aggregator must initialized to memory that can contain a pointer. The
+/// returned context must be consumed by `aggregate_finalize` or `destroy_aggregator`.
+#[no_mangle]
+pub unsafe extern "C" fn aggregate_new(out_aggregator: *mut *mut SignatureAggregator) -> bool {
+    if out_aggregator.is_null() {
+        return false;
+    }
+    *out_aggregator = Box::into_raw(Box::new(SignatureAggregator {
+        aggregate: G1Projective::zero(),
+        count: 0,
+    }));
+    true
+}
+
+/// # Safety
+///
+/// aggregator must have been created by `aggregate_new` and not yet consumed.
+#[no_mangle]
+pub unsafe extern "C" fn aggregate_add(
+    aggregator: *mut SignatureAggregator,
+    in_signature: *const Signature,
+) -> bool {
+    if aggregator.is_null() || in_signature.is_null() {
+        return false;
+    }
+    let aggregator = &mut *aggregator;
+    aggregator.aggregate += *(*in_signature).as_ref();
+    aggregator.count += 1;
+    true
+}
+
+/// Returns the number of signatures added to the aggregator so far
+///
+/// # Safety
+///
+/// aggregator must have been created by `aggregate_new` and not yet consumed.
+#[no_mangle]
+pub unsafe extern "C" fn aggregate_count(
+    aggregator: *const SignatureAggregator,
+    out_count: *mut usize,
+) -> bool {
+    if aggregator.is_null() || out_count.is_null() {
+        return false;
+    }
+    *out_count = (*aggregator).count;
+    true
+}
+
+/// Consumes the aggregator and returns the aggregate of all the signatures added to it
+///
+/// # Safety
+///
+/// aggregator must have been created by `aggregate_new` and not yet consumed.
+/// out_signature must initialized to memory that can contain a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn aggregate_finalize(
+    aggregator: *mut SignatureAggregator,
+    out_signature: *mut *mut Signature,
+) -> bool {
+    if aggregator.is_null() || out_signature.is_null() {
+        return false;
+    }
+    let aggregator = Box::from_raw(aggregator);
+    *out_signature = Box::into_raw(Box::new(Signature::from(aggregator.aggregate)));
+    true
+}
+
+/// # Safety
+///
+/// aggregator must have been created by `aggregate_new` and not yet consumed.
+#[no_mangle]
+pub unsafe extern "C" fn destroy_aggregator(aggregator: *mut SignatureAggregator) -> bool {
+    if aggregator.is_null() {
+        return false;
+    }
+    Box::from_raw(aggregator);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::{destroy_public_key, destroy_signature, free_vec};
+    use bls_crypto::ErrorCode;
+
+    #[test]
+    fn generates_and_verifies_serialized_pops() {
+        let rng = &mut rand::thread_rng();
+        let private_key = PrivateKey::generate(rng);
+        let mut private_key_bytes = vec![];
+        private_key.serialize(&mut private_key_bytes).unwrap();
+        let mut public_key_bytes = vec![];
+        private_key
+            .to_public()
+            .serialize(&mut public_key_bytes)
+            .unwrap();
+        let message = b"validator address";
+
+        unsafe {
+            let mut pop_ptr = std::ptr::null_mut();
+            let mut pop_len = 0;
+            assert!(generate_pop(
+                Buffer::from(&private_key_bytes[..]),
+                Buffer::from(&message[..]),
+                &mut pop_ptr,
+                &mut pop_len,
+            ));
+            let pop = slice::from_raw_parts(pop_ptr, pop_len as usize).to_vec();
+            assert!(free_vec(pop_ptr, pop_len));
+
+            // matches the pointer based API
+            let expected = private_key.sign_pop(message, &*DIRECT_HASH_TO_G1).unwrap();
+            assert_eq!(Signature::deserialize(&pop[..]).unwrap(), expected);
+
+            let verify = |message: &[u8], pop: &[u8]| {
+                let mut verified = false;
+                let ok = verify_serialized_pop(
+                    Buffer::from(&public_key_bytes[..]),
+                    Buffer::from(message),
+                    Buffer::from(pop),
+                    &mut verified,
+                );
+                (ok, verified)
+            };
+            assert_eq!(verify(message, &pop), (true, true));
+            assert_eq!(verify(b"another address", &pop), (true, false));
+            // a truncated pop cannot be deserialized
+            assert_eq!(verify(message, &pop[..pop.len() - 1]), (false, false));
+        }
+    }
+
+    #[test]
+    fn signs_prehashed_messages() {
+        let rng = &mut rand::thread_rng();
+        let private_key = PrivateKey::generate(rng);
+        let public_key = private_key.to_public();
+        let hash = DIRECT_HASH_TO_G1.hash(SIG_DOMAIN, b"hello", &[]).unwrap();
+        let mut hash_bytes = vec![];
+        hash.into_affine().serialize(&mut hash_bytes).unwrap();
+
+        unsafe {
+            let mut signature = std::ptr::null_mut();
+            assert!(sign_prehashed(
+                &private_key,
+                &hash_bytes[0],
+                hash_bytes.len() as c_int,
+                &mut signature
+            ));
+            assert_eq!(
+                *signature,
+                private_key
+                    .sign(b"hello", &[], &*DIRECT_HASH_TO_G1)
+                    .unwrap()
+            );
+
+            let verify = |public_key: &PublicKey, hash_bytes: &[u8]| {
+                let mut verified = false;
+                let ok = verify_prehashed(
+                    public_key,
+                    &hash_bytes[0],
+                    hash_bytes.len() as c_int,
+                    signature,
+                    &mut verified,
+                );
+                (ok, verified)
+            };
+            assert_eq!(verify(&public_key, &hash_bytes), (true, true));
+            let other_key = PrivateKey::generate(rng).to_public();
+            assert_eq!(verify(&other_key, &hash_bytes), (true, false));
+            // the identity is rejected
+            let mut identity = vec![];
+            G1Affine::zero().serialize(&mut identity).unwrap();
+            assert_eq!(verify(&public_key, &identity), (false, false));
+            let mut out = std::ptr::null_mut();
+            assert!(!sign_prehashed(
+                &private_key,
+                &identity[0],
+                identity.len() as c_int,
+                &mut out
+            ));
+            assert!(out.is_null());
+
+            assert!(destroy_signature(signature));
+        }
+    }
+
+    #[test]
+    fn aggregates_incrementally() {
+        let rng = &mut rand::thread_rng();
+        let signatures = (0..5)
+            .map(|_| {
+                PrivateKey::generate(rng)
+                    .sign(b"hello", &[], &*DIRECT_HASH_TO_G1)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            let mut aggregator = std::ptr::null_mut();
+            assert!(aggregate_new(&mut aggregator));
+            for signature in &signatures {
+                assert!(aggregate_add(aggregator, signature));
+            }
+            let mut count = 0;
+            assert!(aggregate_count(aggregator, &mut count));
+            assert_eq!(count, signatures.len());
+
+            let mut aggregate = std::ptr::null_mut();
+            assert!(aggregate_finalize(aggregator, &mut aggregate));
+            assert_eq!(*aggregate, Signature::aggregate(&signatures));
+            assert!(destroy_signature(aggregate));
+
+            assert!(!aggregate_add(std::ptr::null_mut(), &signatures[0]));
+        }
+    }
+
+    #[test]
+    fn aggregates_borrowed_public_keys() {
+        let rng = &mut rand::thread_rng();
+        let public_keys = (0..5)
+            .map(|_| PrivateKey::generate(rng).to_public())
+            .collect::<Vec<_>>();
+        let expected = PublicKey::aggregate(&public_keys);
+
+        let ptrs = public_keys
+            .iter()
+            .map(|pk| pk as *const PublicKey)
+            .collect::<Vec<_>>();
+        let mut serialized = vec![];
+        for pk in &public_keys {
+            pk.as_ref()
+                .into_affine()
+                .serialize(&mut serialized)
+                .unwrap();
+        }
+
+        unsafe {
+            let mut aggregate = std::ptr::null_mut();
+            assert!(aggregate_public_keys_borrowed(
+                ptrs.as_ptr(),
+                ptrs.len() as c_int,
+                &mut aggregate
+            ));
+            assert_eq!(*aggregate, expected);
+            assert!(destroy_public_key(aggregate));
+
+            let mut aggregate = std::ptr::null_mut();
+            assert!(aggregate_serialized_public_keys(
+                Buffer::from(&serialized[..]),
+                &mut aggregate
+            ));
+            assert_eq!(*aggregate, expected);
+            assert!(destroy_public_key(aggregate));
+
+            // truncated keys are rejected
+            let mut aggregate = std::ptr::null_mut();
+            assert!(!aggregate_serialized_public_keys(
+                Buffer::from(&serialized[..serialized.len() - 1]),
+                &mut aggregate
+            ));
+            assert_eq!(
+                crate::last_error_code(),
+                ErrorCode::InvalidArgument.as_i32()
+            );
+
+            // and so are negative lengths
+            assert!(!aggregate_public_keys_borrowed(
+                ptrs.as_ptr(),
+                -1,
+                &mut aggregate
+            ));
+            assert_eq!(
+                crate::last_error_code(),
+                ErrorCode::InvalidArgument.as_i32()
+            );
+            assert!(aggregate.is_null());
+        }
+    }
+
+    #[test]
+    fn batch_verify_signatures_individually() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..4)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|k| k.to_public()).collect::<Vec<_>>();
+        let data = (0..4u8).map(|i| vec![i; 32]).collect::<Vec<_>>();
+        let extra = vec![0u8; 8];
+        let mut signatures = keys
+            .iter()
+            .zip(&data)
+            .map(|(k, d)| k.sign(d, &extra, &*COMPOSITE_HASH_TO_G1).unwrap())
+            .collect::<Vec<_>>();
+        // the third signature is over a different message
+        signatures[2] = keys[2]
+            .sign(&data[0], &extra, &*COMPOSITE_HASH_TO_G1)
+            .unwrap();
+
+        let messages = (0..4)
+            .map(|i| {
+                MessageFFI::from(&Message {
+                    data: &data[i],
+                    extra: &extra,
+                    public_key: &public_keys[i],
+                    sig: &signatures[i],
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut verified = vec![false; messages.len()];
+        assert!(batch_verify_signatures(
+            messages.as_ptr(),
+            messages.len(),
+            true,
+            false,
+            verified.as_mut_ptr(),
+        ));
+        assert_eq!(verified, vec![true, true, false, true]);
+
+        // direct hashing without cip22 is not supported
+        assert!(!batch_verify_signatures(
+            messages.as_ptr(),
+            messages.len(),
+            false,
+            true,
+            verified.as_mut_ptr(),
+        ));
+    }
+
+    #[test]
+    fn identifies_invalid_signature_shares() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..4)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|k| k.to_public()).collect::<Vec<_>>();
+        let data = vec![7u8; 32];
+        let extra = vec![0u8; 8];
+        let mut signatures = keys
+            .iter()
+            .map(|k| k.sign(&data, &extra, &*COMPOSITE_HASH_TO_G1).unwrap())
+            .collect::<Vec<_>>();
+
+        let verify = |signatures: &[Signature]| {
+            let messages = (0..4)
+                .map(|i| {
+                    MessageFFI::from(&Message {
+                        data: &data,
+                        extra: &extra,
+                        public_key: &public_keys[i],
+                        sig: &signatures[i],
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut invalid = vec![0; messages.len()];
+            let mut invalid_len = 0;
+            assert!(verify_signature_shares(
+                messages.as_ptr(),
+                messages.len(),
+                true,
+                false,
+                invalid.as_mut_ptr(),
+                &mut invalid_len,
+            ));
+            invalid.truncate(invalid_len);
+            invalid
+        };
+        assert_eq!(verify(&signatures), Vec::<usize>::new());
+
+        // the errors of the shares cancel out in the aggregate, which must not hide them
+        let error = G1Projective::prime_subgroup_generator();
+        signatures[1] = Signature::from(*signatures[1].as_ref() + error);
+        signatures[3] = Signature::from(*signatures[3].as_ref() - error);
+        assert!(Signature::aggregate(&signatures)
+            .batch_verify(
+                &public_keys,
+                SIG_DOMAIN,
+                &[(&data[..], &extra[..]); 4],
+                &*COMPOSITE_HASH_TO_G1,
+            )
+            .is_ok());
+        assert_eq!(verify(&signatures), vec![1, 3]);
+    }
+}
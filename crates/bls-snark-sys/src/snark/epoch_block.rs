@@ -11,7 +11,14 @@ use std::{
 };
 
 /// Each pubkey is a BLS G2Projective element
-const PUBKEY_BYTES: usize = 96;
+pub(crate) const PUBKEY_BYTES: usize = 96;
+
+/// Returns the number of bytes of each entropy value, which the entropy pointers of an epoch
+/// block must point to (16, or 32 if built with the `entropy-256` feature)
+#[no_mangle]
+pub extern "C" fn epoch_entropy_bytes() -> c_uint {
+    EpochBlock::ENTROPY_BYTES as c_uint
+}
 
 #[no_mangle]
 pub extern "C" fn encode_epoch_block_to_bytes_cip22(
@@ -105,7 +112,8 @@ pub extern "C" fn encode_epoch_block_to_bytes(
 }
 
 /// Data structure received from consumers of the FFI interface describing
-/// an epoch block.
+/// an epoch block. Its layout is part of the ABI of `verify` and `verify_handles`, so fields
+/// which were added since are in `EpochBlockFFIV2` instead.
 #[repr(C)]
 pub struct EpochBlockFFI {
     /// The epoch's index
@@ -141,10 +149,43 @@ impl TryFrom<&EpochBlockFFI> for EpochBlock {
             maximum_non_signers: src.maximum_non_signers,
             maximum_validators: src.maximum_validators,
             new_public_keys: pubkeys,
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
         })
     }
 }
 
+/// An epoch block along with the data which the circuit's optional features commit to, for
+/// `verify_v2` and `verify_handles_v2`. The original `EpochBlockFFI` is its first field, so
+/// that callers of the original entry points are unaffected.
+#[repr(C)]
+pub struct EpochBlockFFIV2 {
+    /// The fields of the original epoch block
+    pub epoch: EpochBlockFFI,
+    /// Pointer to the 32 byte blinding of the epoch's validator set commitment, used with the
+    /// `blinded-validators` feature. It MUST be kept secret. Null if the epoch has none.
+    pub validator_blinding: *const u8,
+    /// Pointer to the epoch's 32 byte auxiliary data, used with the `epoch-aux-data` feature.
+    /// Null if the epoch has none.
+    pub aux_data: *const u8,
+    /// Pointer to the 32 byte identifier of the epoch's chain, used with the `chain-binding`
+    /// feature. Null if the epoch has none.
+    pub chain_id: *const u8,
+}
+
+impl TryFrom<&EpochBlockFFIV2> for EpochBlock {
+    type Error = EncodingError;
+
+    fn try_from(src: &EpochBlockFFIV2) -> Result<EpochBlock, Self::Error> {
+        let mut epoch_block = EpochBlock::try_from(&src.epoch)?;
+        epoch_block.validator_blinding = unsafe { read_bytes32(src.validator_blinding) };
+        epoch_block.aux_data = unsafe { read_bytes32(src.aux_data) };
+        epoch_block.chain_id = unsafe { read_bytes32(src.chain_id) };
+        Ok(epoch_block)
+    }
+}
+
 /// Reads `len` bytes starting from the pointer's location
 ///
 /// # Safety
@@ -209,6 +250,22 @@ unsafe fn read_epoch_entropy(ptr: *const u8) -> Option<Vec<u8>> {
     }
 }
 
+/// Reads a 32 byte value from the given pointer location, or returns `None` if it is null.
+///
+/// # Safety
+///
+/// This WILL read invalid data if the given pointer locates less than 32 bytes of data. Use
+/// with caution.
+unsafe fn read_bytes32(ptr: *const u8) -> Option<[u8; 32]> {
+    if ptr.is_null() {
+        None
+    } else {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(slice::from_raw_parts(ptr, 32));
+        Some(bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,6 +295,9 @@ mod tests {
             maximum_non_signers: 19,
             maximum_validators: pubkeys.len(),
             new_public_keys: pubkeys,
+            aux_data: Some([4; 32]),
+            chain_id: Some([5; 32]),
+            validator_blinding: Some([3; 32]),
         };
         let src = block;
         let serialized_pubkeys = serialize_pubkeys(&src.new_public_keys).unwrap();
@@ -251,8 +311,26 @@ mod tests {
             pubkeys_num: src.new_public_keys.len(),
             pubkeys: &serialized_pubkeys[0] as *const u8,
         };
+        let ffi_block = EpochBlockFFIV2 {
+            epoch: ffi_block,
+            validator_blinding: src.validator_blinding.as_ref().unwrap().as_ptr(),
+            aux_data: src.aux_data.as_ref().unwrap().as_ptr(),
+            chain_id: src.chain_id.as_ref().unwrap().as_ptr(),
+        };
         let block_from_ffi = EpochBlock::try_from(&ffi_block).unwrap();
         assert_eq!(block_from_ffi, src);
+
+        // the original block has none of the extra data
+        let block_from_ffi = EpochBlock::try_from(&ffi_block.epoch).unwrap();
+        assert_eq!(
+            block_from_ffi,
+            EpochBlock {
+                aux_data: None,
+                chain_id: None,
+                validator_blinding: None,
+                ..src
+            }
+        );
     }
 
     #[test]
@@ -269,6 +347,9 @@ mod tests {
             maximum_non_signers: 19,
             maximum_validators: pubkeys.len(),
             new_public_keys: pubkeys,
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
         };
         let src = block;
         let serialized_pubkeys = serialize_pubkeys(&src.new_public_keys).unwrap();
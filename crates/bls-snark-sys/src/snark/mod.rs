@@ -1,5 +1,5 @@
 pub mod epoch_block;
-use epoch_block::{read_slice, EpochBlockFFI};
+use epoch_block::{read_slice, EpochBlockFFI, EpochBlockFFIV2};
 
 #[cfg(test)]
 mod test_helpers;
@@ -44,6 +44,32 @@ pub unsafe extern "C" fn verify(
     })
 }
 
+#[no_mangle]
+/// Same as `verify`, but with the epoch blocks extended with the data which the circuit's
+/// optional features commit to
+///
+/// # Safety
+/// 1. VK and Proof must be valid pointers
+/// 1. The vector of pubkeys and the non-null pointers inside EpochBlockFFIV2 must point to
+///    valid memory
+pub unsafe extern "C" fn verify_v2(
+    vk: *const u8,
+    vk_len: u32,
+    proof: *const u8,
+    proof_len: u32,
+    first_epoch: EpochBlockFFIV2,
+    last_epoch: EpochBlockFFIV2,
+) -> bool {
+    convert_result_to_bool(|| {
+        let first_epoch = EpochBlock::try_from(&first_epoch)?;
+        let last_epoch = EpochBlock::try_from(&last_epoch)?;
+        let vk = read_slice(vk, vk_len as usize)?;
+        let proof = read_slice(proof, proof_len as usize)?;
+
+        epoch_snark::verify(&vk, &first_epoch, &last_epoch, &proof)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
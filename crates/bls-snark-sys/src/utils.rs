@@ -65,6 +65,22 @@ pub struct Buffer {
     pub len: usize,
 }
 
+impl Buffer {
+    /// Borrows the buffer's memory for the lifetime `'a` without copying it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` readable bytes which remain valid and unmodified for
+    /// the lifetime `'a`. A null `ptr` is only allowed if `len` is 0.
+    pub unsafe fn as_slice<'a>(&self) -> &'a [u8] {
+        if self.len == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+}
+
 impl From<&[u8]> for Buffer {
     fn from(src: &[u8]) -> Self {
         Self {
@@ -97,6 +113,15 @@ mod tests {
         assert_eq!(buf.as_ref() as &[u8], de);
     }
 
+    #[test]
+    fn empty_buffer_as_slice() {
+        let buffer = Buffer {
+            ptr: std::ptr::null(),
+            len: 0,
+        };
+        assert!(unsafe { buffer.as_slice() }.is_empty());
+    }
+
     #[test]
     fn msg_convert_ok() {
         let rng = &mut rand::thread_rng();
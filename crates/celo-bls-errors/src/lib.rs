@@ -0,0 +1,109 @@
+//! # Celo BLS Errors
+//!
+//! Numeric error codes which are shared by the BLS and SNARK crates, so that FFI clients
+//! can distinguish failures (e.g. an invalid signature from an invalid proof) without
+//! parsing error messages.
+//!
+//! Each crate's error type implements [`ToErrorCode`]. The codes are part of the FFI
+//! interface: existing values MUST NOT be changed, new ones are only appended.
+//!
+//! [`ToErrorCode`]: trait.ToErrorCode.html
+use r1cs_core::SynthesisError;
+use std::{fmt, io};
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The category of an error, as returned over FFI
+pub enum ErrorCode {
+    /// The operation succeeded
+    Success = 0,
+    /// An error which does not fall in any other category
+    Unknown = 1,
+    /// A BLS signature failed to verify
+    InvalidSignature = 2,
+    /// A point is not in the prime order subgroup
+    NotInSubgroup = 3,
+    /// Hashing, e.g. to the curve, failed
+    HashingFailed = 4,
+    /// An element could not be serialized or deserialized
+    Serialization = 5,
+    /// An I/O error
+    Io = 6,
+    /// The arguments are malformed, e.g. they have mismatched lengths
+    InvalidArgument = 7,
+    /// A SNARK proof failed to verify
+    InvalidProof = 10,
+    /// Generating the constraints or the proof failed
+    Synthesis = 11,
+    /// The witness does not satisfy the constraints
+    Unsatisfiable = 12,
+    /// The epoch data is malformed
+    InvalidEpochData = 13,
+    /// Encrypting or decrypting the parameters failed
+    Encryption = 14,
+    /// The operation did not complete before its deadline
+    Timeout = 15,
+}
+
+impl ErrorCode {
+    /// Returns the numeric value of the code
+    pub fn as_i32(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<ErrorCode> for i32 {
+    fn from(code: ErrorCode) -> i32 {
+        code.as_i32()
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.as_i32())
+    }
+}
+
+/// Errors which can be reported over FFI with a numeric code
+pub trait ToErrorCode {
+    /// Returns the code of the error's category
+    fn error_code(&self) -> ErrorCode;
+}
+
+impl ToErrorCode for io::Error {
+    fn error_code(&self) -> ErrorCode {
+        ErrorCode::Io
+    }
+}
+
+// `bls-gadgets` has no error type of its own, it only returns zexe's `SynthesisError`
+impl ToErrorCode for SynthesisError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            SynthesisError::Unsatisfiable => ErrorCode::Unsatisfiable,
+            _ => ErrorCode::Synthesis,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_stable() {
+        assert_eq!(ErrorCode::Success.as_i32(), 0);
+        assert_eq!(ErrorCode::InvalidSignature.as_i32(), 2);
+        assert_eq!(ErrorCode::NotInSubgroup.as_i32(), 3);
+        assert_eq!(ErrorCode::InvalidProof.as_i32(), 10);
+        assert_eq!(i32::from(ErrorCode::Encryption), 14);
+        assert_eq!(
+            SynthesisError::Unsatisfiable.error_code(),
+            ErrorCode::Unsatisfiable
+        );
+        assert_eq!(
+            SynthesisError::AssignmentMissing.error_code(),
+            ErrorCode::Synthesis
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Benchmarks of the BW6_761 arithmetic which dominates proving time. Compare a run with
+//! the default backend against a run with the assembly backend:
+//!
+//! `cargo bench --bench bw6_field -- --save-baseline default`
+//! `cargo bench --bench bw6_field --features bw6-asm -- --baseline default`
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use algebra::{
+    bw6_761::{Fq, Fr, G1Projective},
+    msm::VariableBaseMSM,
+    PrimeField, ProjectiveCurve, UniformRand,
+};
+use ff_fft::{EvaluationDomain, Radix2EvaluationDomain};
+
+fn field_mul(c: &mut Criterion) {
+    let rng = &mut rand::thread_rng();
+    let a = Fq::rand(rng);
+    let b = Fq::rand(rng);
+    c.bench_function("bw6_761 Fq mul", |bench| bench.iter(|| a * &b));
+}
+
+fn msm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bw6_761 G1 msm");
+    group.sample_size(10);
+    let rng = &mut rand::thread_rng();
+    for log_size in &[10, 14] {
+        let size = 1 << log_size;
+        let bases = (0..size)
+            .map(|_| G1Projective::rand(rng))
+            .collect::<Vec<_>>();
+        let bases = G1Projective::batch_normalization_into_affine(&bases);
+        let scalars = (0..size)
+            .map(|_| Fr::rand(rng).into_repr())
+            .collect::<Vec<_>>();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |bench, _| {
+            bench.iter(|| VariableBaseMSM::multi_scalar_mul(&bases, &scalars))
+        });
+    }
+    group.finish();
+}
+
+fn fft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bw6_761 Fr fft");
+    group.sample_size(10);
+    let rng = &mut rand::thread_rng();
+    for log_size in &[14, 18] {
+        let size = 1 << log_size;
+        let domain = Radix2EvaluationDomain::<Fr>::new(size).unwrap();
+        let coeffs = (0..size).map(|_| Fr::rand(rng)).collect::<Vec<_>>();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |bench, _| {
+            bench.iter(|| {
+                let mut evals = coeffs.clone();
+                domain.fft_in_place(&mut evals);
+                evals
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, field_mul, msm, fft);
+criterion_main!(benches);
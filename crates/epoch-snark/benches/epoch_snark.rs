@@ -0,0 +1,118 @@
+//! Time-boxed benchmarks of the constraint synthesis of each epoch and of proof verification,
+//! for tracking regressions as the zexe dependencies move, and of the reuse of the epochs' bit
+//! vectors across epochs. They require the `bench` feature.
+//! Save a baseline and compare against it with:
+//!
+//! `cargo bench --features bench --bench epoch_snark -- --save-baseline main`
+//! `cargo bench --features bench --bench epoch_snark -- --baseline main`
+//!
+//! Criterion writes the estimates of each baseline as JSON, under
+//! `target/criterion/<group>/<benchmark>/<baseline>/estimates.json`. The trusted setup and the
+//! proof which the verification benchmark needs are generated once, before measuring.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::time::Duration;
+
+use algebra::bls12_377::Bls12_377;
+use epoch_snark::{prove, trusted_setup, verify, BitBuffers, EpochData, ValidatorSetUpdate};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisMode};
+
+#[path = "../tests/fixtures.rs"]
+mod fixtures;
+use fixtures::generate_test_data;
+
+const NUM_VALIDATORS: usize = 4;
+const FAULTS: usize = (NUM_VALIDATORS - 1) / 3;
+
+fn synthesis(c: &mut Criterion) {
+    let mut group = c.benchmark_group("synthesis");
+    // the difference between the runs is the cost of a single epoch
+    for num_epochs in &[1, 2] {
+        group.bench_with_input(
+            BenchmarkId::new("epochs", num_epochs),
+            num_epochs,
+            |b, num_epochs| {
+                b.iter(|| {
+                    let cs = ConstraintSystem::new_ref();
+                    cs.set_mode(SynthesisMode::Setup);
+                    ValidatorSetUpdate::<Bls12_377>::empty(
+                        NUM_VALIDATORS,
+                        *num_epochs,
+                        FAULTS,
+                        None,
+                    )
+                    .generate_constraints(cs.clone())
+                    .unwrap();
+                    cs.num_constraints()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// The encoding of consecutive epochs of a large validator set to bits, with their bit vectors
+/// recycled from one epoch to the next as `ValidatorSetUpdate` does, or allocated for each epoch
+fn bit_buffers(c: &mut Criterion) {
+    const LARGE_VALIDATOR_SET: usize = 100;
+    const EPOCHS: usize = 4;
+    let epoch = EpochData::<Bls12_377>::empty(LARGE_VALIDATOR_SET, LARGE_VALIDATOR_SET / 3);
+    let mut group = c.benchmark_group("bit buffers");
+    for &pooled in &[false, true] {
+        let name = if pooled { "pooled" } else { "fresh" };
+        group.bench_function(BenchmarkId::new("epochs", name), |b| {
+            b.iter(|| {
+                let cs = ConstraintSystem::new_ref();
+                cs.set_mode(SynthesisMode::Setup);
+                let mut buffers = BitBuffers::default();
+                for _ in 0..EPOCHS {
+                    if !pooled {
+                        buffers = BitBuffers::default();
+                    }
+                    let (epoch_bits, _, first_epoch_bits, last_epoch_bits, ..) = epoch
+                        .to_bits_with_buffers(cs.clone(), &mut buffers)
+                        .unwrap();
+                    buffers.recycle(epoch_bits);
+                    buffers.recycle(first_epoch_bits);
+                    buffers.recycle(last_epoch_bits);
+                }
+                cs.num_constraints()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn verification(c: &mut Criterion) {
+    let rng = &mut rand::thread_rng();
+    let num_epochs = 2;
+    let params = trusted_setup(NUM_VALIDATORS, num_epochs, FAULTS, rng, false).unwrap();
+    let (first_epoch, transitions, last_epoch) =
+        generate_test_data(NUM_VALIDATORS, FAULTS, num_epochs);
+    let proof = prove(
+        &params,
+        NUM_VALIDATORS as u32,
+        &first_epoch,
+        &transitions,
+        num_epochs,
+    )
+    .unwrap();
+
+    c.bench_function("verify proof", |b| {
+        b.iter(|| verify(&params.epochs.vk, &first_epoch, &last_epoch, &proof).unwrap())
+    });
+}
+
+/// Bounds each benchmark to a few seconds so that the suite can run on every change
+fn time_boxed() -> Criterion {
+    Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_secs(1))
+        .measurement_time(Duration::from_secs(5))
+}
+
+criterion_group! {
+    name = benches;
+    config = time_boxed();
+    targets = synthesis, bit_buffers, verification
+}
+criterion_main!(benches);
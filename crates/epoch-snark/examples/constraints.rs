@@ -1,7 +1,7 @@
 use algebra::bls12_377::Bls12_377;
-use epoch_snark::ValidatorSetUpdate;
+use epoch_snark::{export::export_r1cs, registry::CircuitShape, ValidatorSetUpdate};
 use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisMode};
-use std::env;
+use std::{env, fs::File, io::BufWriter};
 
 fn main() {
     let mut args = env::args();
@@ -29,5 +29,12 @@ fn main() {
         num_validators,
         faults,
         cs.num_constraints()
-    )
+    );
+
+    // optionally write the circuit in circom's `.r1cs` format
+    if let Some(path) = args.next() {
+        let file = BufWriter::new(File::create(&path).expect("could not create the file"));
+        export_r1cs(CircuitShape::new(num_validators, num_epochs), file).unwrap();
+        println!("Wrote the constraints to {}", path);
+    }
 }
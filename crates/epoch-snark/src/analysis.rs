@@ -0,0 +1,149 @@
+use crate::{encoding::EncodingError, epoch_block::EpochTransition};
+use algebra::serialize::SerializationError;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+/// The position of a validator inside the signed bitmap of an epoch transition
+pub type ValidatorIndex = usize;
+
+/// Summary of the validators which failed to sign on too many epoch transitions.
+///
+/// Note that a bitmap position refers to the validator set of the _previous_ epoch, so
+/// the same index may correspond to a different validator across transitions if the
+/// validator set was rotated. Consumers must map the indices back to the actual validators.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonSignerReport {
+    /// The number of transitions which were analyzed
+    pub num_epochs: u32,
+    /// The validators over the threshold along with the number of epochs they did not sign
+    pub missed: Vec<(ValidatorIndex, u32)>,
+}
+
+impl NonSignerReport {
+    /// Counts how many transitions each bitmap position did not sign, and keeps
+    /// the ones which missed strictly more than `max_missed_fraction` of them.
+    pub fn new(transitions: &[EpochTransition], max_missed_fraction: f64) -> Self {
+        let num_validators = transitions
+            .iter()
+            .map(|transition| transition.bitmap.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut missed_counts = vec![0u32; num_validators];
+        for transition in transitions {
+            for (i, signed) in transition.bitmap.iter().enumerate() {
+                if !signed {
+                    missed_counts[i] += 1;
+                }
+            }
+        }
+
+        let num_epochs = transitions.len() as u32;
+        let missed = missed_counts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, count)| *count as f64 > max_missed_fraction * num_epochs as f64)
+            .collect();
+
+        Self { num_epochs, missed }
+    }
+
+    /// Encodes the report as `num_epochs || num_entries || (index || missed)*`, with
+    /// every field encoded as a LE u32
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut bytes = Vec::with_capacity(8 + 8 * self.missed.len());
+        bytes.write_u32::<LittleEndian>(self.num_epochs)?;
+        bytes.write_u32::<LittleEndian>(self.missed.len() as u32)?;
+        for (index, count) in &self.missed {
+            bytes.write_u32::<LittleEndian>(*index as u32)?;
+            bytes.write_u32::<LittleEndian>(*count)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a report which was encoded with `to_bytes`, rejecting any trailing bytes
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, EncodingError> {
+        let num_epochs = bytes.read_u32::<LittleEndian>()?;
+        let num_entries = bytes.read_u32::<LittleEndian>()?;
+        // the length is untrusted, so at most as many entries as the remaining 8-byte
+        // chunks are allocated upfront
+        let mut missed = Vec::with_capacity((num_entries as usize).min(bytes.len() / 8));
+        for _ in 0..num_entries {
+            let index = bytes.read_u32::<LittleEndian>()? as ValidatorIndex;
+            let count = bytes.read_u32::<LittleEndian>()?;
+            missed.push((index, count));
+        }
+        if !bytes.is_empty() {
+            return Err(SerializationError::InvalidData.into());
+        }
+        Ok(Self { num_epochs, missed })
+    }
+}
+
+/// Returns the bitmap positions which did not sign strictly more than `max_missed_fraction`
+/// of the provided transitions, in ascending order
+pub fn non_signers(
+    transitions: &[EpochTransition],
+    max_missed_fraction: f64,
+) -> Vec<ValidatorIndex> {
+    NonSignerReport::new(transitions, max_missed_fraction)
+        .missed
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch_block::EpochBlock;
+    use algebra::{bls12_377::G1Projective, ProjectiveCurve};
+    use bls_crypto::Signature;
+
+    fn transition(bitmap: &[bool]) -> EpochTransition {
+        EpochTransition {
+            block: EpochBlock::new(1, 0, None, None, 1, 0, vec![]),
+            aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+            bitmap: bitmap.to_vec().into(),
+        }
+    }
+
+    #[test]
+    fn reports_validators_over_threshold() {
+        let transitions = vec![
+            transition(&[true, false, false, true]),
+            transition(&[true, false, true, true]),
+            transition(&[true, false, false, true]),
+            transition(&[false, true, true, true]),
+        ];
+
+        assert_eq!(non_signers(&transitions, 0.4), vec![1, 2]);
+        assert_eq!(non_signers(&transitions, 0.6), vec![1]);
+        assert_eq!(non_signers(&transitions, 0.0), vec![0, 1, 2]);
+        assert!(non_signers(&transitions, 1.0).is_empty());
+    }
+
+    #[test]
+    fn report_roundtrip() {
+        let transitions = vec![
+            transition(&[true, false, false]),
+            transition(&[false, false, true]),
+        ];
+        let report = NonSignerReport::new(&transitions, 0.0);
+        assert_eq!(report.missed, vec![(0, 1), (1, 2), (2, 1)]);
+
+        let bytes = report.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 8 + 3 * 8);
+        assert_eq!(NonSignerReport::from_bytes(&bytes).unwrap(), report);
+
+        // truncated data fails to decode
+        NonSignerReport::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        // and so do trailing bytes
+        let mut extended = bytes.clone();
+        extended.push(0);
+        NonSignerReport::from_bytes(&extended).unwrap_err();
+        // a huge length prefix fails without allocating for it
+        let mut oversized = bytes;
+        oversized[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        NonSignerReport::from_bytes(&oversized).unwrap_err();
+    }
+}
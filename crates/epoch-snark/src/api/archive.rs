@@ -0,0 +1,580 @@
+//! Proof Archives
+//!
+//! A `ProofArchive` stores the proofs generated over the life of a chain, so that the proof
+//! of any range of epochs can be served later (e.g. to a light client syncing from an old
+//! epoch). Only what verifying the proofs requires is kept: the proofs, the first and last
+//! epoch of each proof and the verifying keys. The archive is compact:
+//!
+//! - each epoch is stored once, even though consecutive proofs share their boundary epoch
+//! - each verifying key is stored once, and referenced by the proofs generated with it
+//! - the archive is written zstd-compressed
+//!
+//! Proofs may overlap, e.g. when the proofs of `0..k` and `k..n` are followed by a proof of
+//! `0..n`. `ProofArchive::prune` drops the proofs whose range is covered by a single wider
+//! proof, which saves space but means that ranges starting or ending inside the wider proof
+//! can no longer be extracted. `ProofArchive::extract` returns the shortest chain of proofs
+//! over a range, as a `ProofChainBundle` which is checked with `verify_proof_chain`.
+//!
+//! The epochs are written in their RLP encoding, see `codec::rlp`. Their validator set
+//! blindings are secret, so they are never archived: with the `blinded-validators` feature,
+//! the extracted proofs must be checked with `verify_with_validator_commitment`.
+use super::{bundle::ProofBundle, verifier::verify_proof_chain, BWCurve, ProofChainError};
+use crate::{
+    codec::{rlp, CodecError, MAX_VALIDATORS},
+    epoch_block::EpochBlock,
+};
+
+use algebra::serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use groth16::{Proof, VerifyingKey};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Read, Write},
+};
+use thiserror::Error;
+use tracing::info;
+
+/// The version of the format written by `ProofArchive::write`
+const ARCHIVE_FORMAT_VERSION: u8 = 1;
+
+/// The maximum length of an archived epoch, which bounds the memory `ProofArchive::read`
+/// allocates for each epoch. It fits the RLP encoding of an epoch with `MAX_VALIDATORS`
+/// public keys of 96 bytes, each with its 2 byte RLP header, along with its other fields.
+const MAX_EPOCH_BYTES: usize = MAX_VALIDATORS * 98 + 1024;
+
+#[derive(Debug, Error)]
+/// Error raised while updating, reading or writing a `ProofArchive`
+pub enum ArchiveError {
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Zexe Error: {0}")]
+    ZexeSerialization(#[from] SerializationError),
+    #[error("Codec Error: {0}")]
+    Codec(#[from] CodecError),
+    #[error("archive format version {0} is not supported")]
+    UnsupportedFormat(u8),
+    #[error("epoch {0} differs from the archived epoch with the same index")]
+    ConflictingEpoch(u16),
+    #[error("no chain of archived proofs goes from epoch {first} to epoch {last}")]
+    MissingRange { first: u16, last: u16 },
+    #[error("the archived proofs from epoch {first} to epoch {last} use several verifying keys")]
+    MixedVerifyingKeys { first: u16, last: u16 },
+}
+
+impl ToErrorCode for ArchiveError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ArchiveError::IoError(_) => ErrorCode::Io,
+            ArchiveError::ZexeSerialization(_) | ArchiveError::UnsupportedFormat(_) => {
+                ErrorCode::Serialization
+            }
+            ArchiveError::Codec(e) => e.error_code(),
+            _ => ErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// A chain of proofs over consecutive ranges of epochs, along with the data needed to
+/// verify it, as expected by `verify_proof_chain`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofChainBundle {
+    /// The verifying key of every proof of the chain
+    pub vk: VerifyingKey<BWCurve>,
+    /// The proofs, in the order of their ranges
+    pub proofs: Vec<Proof<BWCurve>>,
+    /// The first and last epoch of each proof
+    pub summaries: Vec<(EpochBlock, EpochBlock)>,
+}
+
+impl ProofChainBundle {
+    /// Verifies the proofs and that they are linked, see `verify_proof_chain`
+    pub fn verify(&self) -> Result<(), ProofChainError> {
+        verify_proof_chain(&self.vk, &self.proofs, &self.summaries)
+    }
+}
+
+/// A proof of the archive, which references its epochs by index and its verifying key by
+/// its position in the archive's keys
+#[derive(Clone, Debug, PartialEq)]
+struct ArchivedProof {
+    proof: Proof<BWCurve>,
+    vk: usize,
+    hashes_in_bls12_377: bool,
+    circuit_version: u32,
+    first: u16,
+    last: u16,
+}
+
+/// Proofs over ranges of epochs, see the module documentation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProofArchive {
+    vks: Vec<VerifyingKey<BWCurve>>,
+    epochs: BTreeMap<u16, EpochBlock>,
+    /// Sorted by first and then last epoch index
+    proofs: Vec<ArchivedProof>,
+}
+
+impl ProofArchive {
+    /// Instantiates an empty archive
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of archived proofs
+    pub fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    /// Whether the archive has no proofs
+    pub fn is_empty(&self) -> bool {
+        self.proofs.is_empty()
+    }
+
+    /// The first and last epoch index of each archived proof, in increasing order
+    pub fn ranges(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.proofs.iter().map(|proof| (proof.first, proof.last))
+    }
+
+    /// Adds the bundle's proof, first and last epoch and verifying key to the archive. Fails
+    /// if an archived epoch has the index of one of the bundle's epochs but other contents.
+    /// The proof is not verified.
+    pub fn append(&mut self, bundle: &ProofBundle) -> Result<(), ArchiveError> {
+        let first = without_blinding(&bundle.first_epoch);
+        let last = without_blinding(bundle.last_epoch());
+        for epoch in &[&first, &last] {
+            match self.epochs.get(&epoch.index) {
+                Some(archived) if archived != *epoch => {
+                    return Err(ArchiveError::ConflictingEpoch(epoch.index))
+                }
+                _ => {}
+            }
+        }
+        let (first_index, last_index) = (first.index, last.index);
+        self.epochs.insert(first_index, first);
+        self.epochs.insert(last_index, last);
+
+        let vk = match self.vks.iter().position(|vk| *vk == bundle.vk) {
+            Some(vk) => vk,
+            None => {
+                self.vks.push(bundle.vk.clone());
+                self.vks.len() - 1
+            }
+        };
+
+        let proof = ArchivedProof {
+            proof: bundle.proof.clone(),
+            vk,
+            hashes_in_bls12_377: bundle.hashes_in_bls12_377,
+            circuit_version: bundle.circuit_version,
+            first: first_index,
+            last: last_index,
+        };
+        // after the proofs over the same range, so that the oldest proof is kept when pruning
+        let position = self
+            .proofs
+            .iter()
+            .position(|archived| (archived.first, archived.last) > (proof.first, proof.last))
+            .unwrap_or_else(|| self.proofs.len());
+        self.proofs.insert(position, proof);
+        Ok(())
+    }
+
+    /// Removes the proofs whose range is within the range of another proof, and the epochs
+    /// and verifying keys which are no longer referenced. Of several proofs over the same
+    /// range, the first one appended is kept. Returns the number of removed proofs.
+    pub fn prune(&mut self) -> usize {
+        let ranges = self.ranges().collect::<Vec<_>>();
+        let supersedes = |i: usize, j: usize| {
+            let (first, last) = ranges[i];
+            let (other_first, other_last) = ranges[j];
+            first <= other_first && other_last <= last && (ranges[i] != ranges[j] || i < j)
+        };
+        let num_proofs = self.proofs.len();
+        let proofs = std::mem::take(&mut self.proofs)
+            .into_iter()
+            .enumerate()
+            .filter(|(j, _)| !(0..num_proofs).any(|i| i != *j && supersedes(i, *j)))
+            .map(|(_, proof)| proof)
+            .collect::<Vec<_>>();
+
+        let epochs = std::mem::take(&mut self.epochs);
+        self.epochs = epochs
+            .into_iter()
+            .filter(|(index, _)| {
+                proofs
+                    .iter()
+                    .any(|proof| proof.first == *index || proof.last == *index)
+            })
+            .collect();
+
+        let vks = std::mem::take(&mut self.vks);
+        let mut positions = vec![None; vks.len()];
+        for (old, vk) in vks.into_iter().enumerate() {
+            if proofs.iter().any(|proof| proof.vk == old) {
+                positions[old] = Some(self.vks.len());
+                self.vks.push(vk);
+            }
+        }
+        self.proofs = proofs
+            .into_iter()
+            .map(|proof| ArchivedProof {
+                vk: positions[proof.vk].expect("the key of a kept proof is kept"),
+                ..proof
+            })
+            .collect();
+
+        let num_pruned = num_proofs - self.proofs.len();
+        info!("Pruned {} superseded proofs", num_pruned);
+        num_pruned
+    }
+
+    /// Returns the chain with the fewest proofs from the epoch with index `first` to the
+    /// epoch with index `last`. Fails if no chain of archived proofs covers exactly this
+    /// range, or if its proofs were generated with different verifying keys.
+    pub fn extract(&self, first: u16, last: u16) -> Result<ProofChainBundle, ArchiveError> {
+        // breadth-first search over the epochs, remembering the proof which reached each one
+        let mut reached_by = BTreeMap::new();
+        let mut frontier = VecDeque::new();
+        frontier.push_back(first);
+        while let Some(epoch) = frontier.pop_front() {
+            if epoch == last {
+                break;
+            }
+            for (i, proof) in self.proofs.iter().enumerate() {
+                if proof.first == epoch
+                    && epoch < proof.last
+                    && proof.last <= last
+                    && !reached_by.contains_key(&proof.last)
+                {
+                    reached_by.insert(proof.last, i);
+                    frontier.push_back(proof.last);
+                }
+            }
+        }
+
+        let mut links = vec![];
+        let mut epoch = last;
+        while epoch != first || links.is_empty() {
+            let link = *reached_by
+                .get(&epoch)
+                .ok_or(ArchiveError::MissingRange { first, last })?;
+            links.push(link);
+            epoch = self.proofs[link].first;
+        }
+        links.reverse();
+
+        let vk = self.proofs[links[0]].vk;
+        if links.iter().any(|link| self.proofs[*link].vk != vk) {
+            return Err(ArchiveError::MixedVerifyingKeys { first, last });
+        }
+        let block = |index: u16| self.epochs[&index].clone();
+        Ok(ProofChainBundle {
+            vk: self.vks[vk].clone(),
+            proofs: links
+                .iter()
+                .map(|link| self.proofs[*link].proof.clone())
+                .collect(),
+            summaries: links
+                .iter()
+                .map(|link| {
+                    let proof = &self.proofs[*link];
+                    (block(proof.first), block(proof.last))
+                })
+                .collect(),
+        })
+    }
+
+    /// Writes the format version, followed by the zstd-compressed verifying keys, epochs and
+    /// proofs, each list preceded by its length. A proof is the position of its verifying
+    /// key, its first and last epoch index, its hashing mode and circuit version and the
+    /// proof itself.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), ArchiveError> {
+        writer.write_u8(ARCHIVE_FORMAT_VERSION)?;
+        let mut writer =
+            zstd::stream::write::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+
+        writer.write_u32::<LittleEndian>(self.vks.len() as u32)?;
+        for vk in &self.vks {
+            vk.serialize(&mut writer)?;
+        }
+        writer.write_u32::<LittleEndian>(self.epochs.len() as u32)?;
+        for epoch in self.epochs.values() {
+            let bytes = rlp::encode_block(epoch);
+            writer.write_u32::<LittleEndian>(bytes.len() as u32)?;
+            writer.write_all(&bytes)?;
+        }
+        writer.write_u32::<LittleEndian>(self.proofs.len() as u32)?;
+        for proof in &self.proofs {
+            writer.write_u32::<LittleEndian>(proof.vk as u32)?;
+            writer.write_u16::<LittleEndian>(proof.first)?;
+            writer.write_u16::<LittleEndian>(proof.last)?;
+            writer.write_u8(proof.hashes_in_bls12_377 as u8)?;
+            writer.write_u32::<LittleEndian>(proof.circuit_version)?;
+            proof.proof.serialize(&mut writer)?;
+        }
+
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Reads an archive written with `write`, checking that every proof references
+    /// archived epochs and keys, that the proofs are sorted and that no epoch is archived
+    /// twice
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, ArchiveError> {
+        let version = reader.read_u8()?;
+        if version != ARCHIVE_FORMAT_VERSION {
+            return Err(ArchiveError::UnsupportedFormat(version));
+        }
+        let mut reader = zstd::stream::read::Decoder::new(reader)?;
+
+        let mut archive = Self::new();
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            archive.vks.push(VerifyingKey::deserialize(&mut reader)?);
+        }
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            let len = reader.read_u32::<LittleEndian>()? as usize;
+            if len > MAX_EPOCH_BYTES {
+                return Err(SerializationError::InvalidData.into());
+            }
+            let mut bytes = vec![0; len];
+            reader.read_exact(&mut bytes)?;
+            let epoch = rlp::decode_block(&bytes)?;
+            if archive.epochs.insert(epoch.index, epoch).is_some() {
+                return Err(SerializationError::InvalidData.into());
+            }
+        }
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            let proof = ArchivedProof {
+                vk: reader.read_u32::<LittleEndian>()? as usize,
+                first: reader.read_u16::<LittleEndian>()?,
+                last: reader.read_u16::<LittleEndian>()?,
+                hashes_in_bls12_377: reader.read_u8()? != 0,
+                circuit_version: reader.read_u32::<LittleEndian>()?,
+                proof: Proof::deserialize(&mut reader)?,
+            };
+            let unsorted = archive.proofs.last().map_or(false, |previous| {
+                (previous.first, previous.last) > (proof.first, proof.last)
+            });
+            if proof.vk >= archive.vks.len()
+                || !archive.epochs.contains_key(&proof.first)
+                || !archive.epochs.contains_key(&proof.last)
+                || unsorted
+            {
+                return Err(SerializationError::InvalidData.into());
+            }
+            archive.proofs.push(proof);
+        }
+        Ok(archive)
+    }
+}
+
+/// The epoch without its validator set blinding, which must not be archived
+fn without_blinding(epoch: &EpochBlock) -> EpochBlock {
+    EpochBlock {
+        validator_blinding: None,
+        ..epoch.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::vk_registry::{CIRCUIT_FEATURES, CIRCUIT_VERSION},
+        epoch_block::EpochTransition,
+    };
+    use algebra::{
+        bls12_377::{G1Projective, G2Projective},
+        ProjectiveCurve,
+    };
+    use bls_crypto::{PublicKey, Signature};
+
+    fn epoch(index: u16) -> EpochBlock {
+        let pubkeys = vec![PublicKey::from(G2Projective::prime_subgroup_generator()); 2];
+        EpochBlock::new(index, 0, None, None, 0, 2, pubkeys)
+    }
+
+    fn bundle(first: u16, last: u16, num_inputs: usize) -> ProofBundle {
+        let mut vk = VerifyingKey::default();
+        vk.gamma_abc_g1 = vec![Default::default(); num_inputs];
+        ProofBundle {
+            proof: Proof::default(),
+            vk,
+            hashes_in_bls12_377: false,
+            circuit_version: CIRCUIT_VERSION,
+            circuit_features: CIRCUIT_FEATURES,
+            first_epoch: epoch(first),
+            transitions: (first + 1..=last)
+                .map(|index| EpochTransition {
+                    block: epoch(index),
+                    aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                    bitmap: vec![true, true].into(),
+                })
+                .collect(),
+        }
+    }
+
+    fn archive(ranges: &[(u16, u16)]) -> ProofArchive {
+        let mut archive = ProofArchive::new();
+        for (first, last) in ranges {
+            archive.append(&bundle(*first, *last, 1)).unwrap();
+        }
+        archive
+    }
+
+    #[test]
+    fn extracts_shortest_chains() {
+        let mut archive = archive(&[(4, 6), (0, 2), (2, 4)]);
+        assert_eq!(
+            archive.ranges().collect::<Vec<_>>(),
+            vec![(0, 2), (2, 4), (4, 6)]
+        );
+        // the keys and the shared epochs are stored once
+        assert_eq!(archive.vks.len(), 1);
+        assert_eq!(archive.epochs.len(), 4);
+
+        let chain = archive.extract(0, 6).unwrap();
+        assert_eq!(chain.proofs.len(), 3);
+        assert_eq!(chain.summaries[1], (epoch(2), epoch(4)));
+        assert_eq!(archive.extract(2, 4).unwrap().proofs.len(), 1);
+        for (first, last) in &[(1, 4), (0, 5), (2, 2), (4, 2)] {
+            assert!(matches!(
+                archive.extract(*first, *last),
+                Err(ArchiveError::MissingRange { .. })
+            ));
+        }
+
+        // a wider proof shortens the chain
+        archive.append(&bundle(0, 4, 1)).unwrap();
+        let chain = archive.extract(0, 6).unwrap();
+        assert_eq!(
+            chain.summaries,
+            vec![(epoch(0), epoch(4)), (epoch(4), epoch(6))]
+        );
+
+        // proofs of another circuit cannot be chained with the others
+        archive.append(&bundle(6, 8, 2)).unwrap();
+        assert_eq!(archive.vks.len(), 2);
+        assert!(archive.extract(6, 8).is_ok());
+        assert!(matches!(
+            archive.extract(0, 8),
+            Err(ArchiveError::MixedVerifyingKeys { first: 0, last: 8 })
+        ));
+    }
+
+    #[test]
+    fn prunes_superseded_proofs() {
+        let mut archive = archive(&[(0, 2), (2, 4), (4, 6), (6, 8), (0, 4), (0, 4)]);
+        archive.append(&bundle(4, 8, 2)).unwrap();
+        assert_eq!(archive.prune(), 5);
+        assert_eq!(archive.ranges().collect::<Vec<_>>(), vec![(0, 4), (4, 8)]);
+        assert_eq!(archive.epochs.keys().collect::<Vec<_>>(), vec![&0, &4, &8]);
+        // the keys of the pruned proofs are dropped
+        assert_eq!(archive.vks.len(), 2);
+        assert_eq!(archive.proofs[1].vk, 1);
+        assert!(archive.extract(0, 2).is_err());
+        assert_eq!(archive.prune(), 0);
+    }
+
+    #[test]
+    fn rejects_conflicting_epochs() {
+        let mut archive = archive(&[(0, 2)]);
+        let mut other = bundle(2, 4, 1);
+        other.first_epoch.round = 1;
+        assert!(matches!(
+            archive.append(&other),
+            Err(ArchiveError::ConflictingEpoch(2))
+        ));
+        assert_eq!(archive.len(), 1);
+    }
+
+    #[test]
+    fn archive_roundtrip() {
+        let mut archive = archive(&[(0, 2), (2, 4), (0, 4)]);
+        archive.append(&bundle(4, 6, 2)).unwrap();
+
+        let mut bytes = vec![];
+        archive.write(&mut bytes).unwrap();
+        assert_eq!(ProofArchive::read(&bytes[..]).unwrap(), archive);
+
+        bytes[0] += 1;
+        assert!(matches!(
+            ProofArchive::read(&bytes[..]),
+            Err(ArchiveError::UnsupportedFormat(2))
+        ));
+    }
+
+    #[test]
+    fn does_not_archive_blindings() {
+        let mut archive = archive(&[(0, 2)]);
+        let mut blinded = bundle(2, 4, 1);
+        blinded.first_epoch.validator_blinding = Some([1; 32]);
+        archive.append(&blinded).unwrap();
+        assert!(archive
+            .epochs
+            .values()
+            .all(|epoch| epoch.validator_blinding.is_none()));
+    }
+
+    /// Writes the lists of an archive as `ProofArchive::write` does, without its invariants
+    fn write_raw(epochs: &[Vec<u8>], ranges: &[(u16, u16)]) -> Vec<u8> {
+        let mut bytes = vec![ARCHIVE_FORMAT_VERSION];
+        let mut writer = zstd::stream::write::Encoder::new(&mut bytes, 0).unwrap();
+        writer.write_u32::<LittleEndian>(1).unwrap();
+        bundle(0, 1, 1).vk.serialize(&mut writer).unwrap();
+        writer
+            .write_u32::<LittleEndian>(epochs.len() as u32)
+            .unwrap();
+        for epoch in epochs {
+            writer
+                .write_u32::<LittleEndian>(epoch.len() as u32)
+                .unwrap();
+            writer.write_all(epoch).unwrap();
+        }
+        writer
+            .write_u32::<LittleEndian>(ranges.len() as u32)
+            .unwrap();
+        for (first, last) in ranges {
+            writer.write_u32::<LittleEndian>(0).unwrap();
+            writer.write_u16::<LittleEndian>(*first).unwrap();
+            writer.write_u16::<LittleEndian>(*last).unwrap();
+            writer.write_u8(0).unwrap();
+            writer.write_u32::<LittleEndian>(CIRCUIT_VERSION).unwrap();
+            Proof::<BWCurve>::default().serialize(&mut writer).unwrap();
+        }
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn rejects_malformed_archives() {
+        let encoded = |index| rlp::encode_block(&epoch(index));
+        let invalid = |bytes: Vec<u8>| {
+            matches!(
+                ProofArchive::read(&bytes[..]),
+                Err(ArchiveError::ZexeSerialization(
+                    SerializationError::InvalidData
+                ))
+            )
+        };
+
+        let bytes = write_raw(&[encoded(0), encoded(2), encoded(4)], &[(0, 2), (2, 4)]);
+        assert_eq!(ProofArchive::read(&bytes[..]).unwrap().len(), 2);
+        // the proofs are not sorted
+        assert!(invalid(write_raw(
+            &[encoded(0), encoded(2), encoded(4)],
+            &[(2, 4), (0, 2)]
+        )));
+        // an epoch is archived twice
+        assert!(invalid(write_raw(
+            &[encoded(0), encoded(2), encoded(2)],
+            &[(0, 2)]
+        )));
+        // an epoch is too long to be read
+        assert!(invalid(write_raw(
+            &[encoded(0), vec![0; MAX_EPOCH_BYTES + 1]],
+            &[]
+        )));
+    }
+}
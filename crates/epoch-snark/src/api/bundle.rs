@@ -0,0 +1,271 @@
+//! Proof Bundles
+//!
+//! A `ProofBundle` groups a proof with the verifying key and the epochs it was generated
+//! for, i.e. everything a relayer submits. `ProofBundle::inspect` summarizes it in a
+//! human readable report, so that operators can sanity check the artifacts (range,
+//! validator counts, hashing mode, keys) before paying for their submission.
+use super::{
+    manifest::digest,
+    setup::Parameters,
+    verifier,
+    vk_registry::{CIRCUIT_FEATURES, CIRCUIT_VERSION},
+    BLSCurve, BWCurve, VerificationError,
+};
+use crate::{
+    encoding::EncodingError,
+    epoch_block::{EpochBlock, EpochSummary, EpochTransition},
+};
+
+use algebra::serialize::CanonicalSerialize;
+use groth16::{Proof, VerifyingKey};
+use std::fmt;
+
+/// A proof over a range of epochs, along with the data needed to verify it
+#[derive(Clone, Debug)]
+pub struct ProofBundle {
+    /// The epochs proof
+    pub proof: Proof<BWCurve>,
+    /// The verifying key of the epochs circuit
+    pub vk: VerifyingKey<BWCurve>,
+    /// Whether the CRH->XOF hashes were proven in BLS12-377
+    pub hashes_in_bls12_377: bool,
+    /// The version of the epochs circuit the proof was generated for, see `CIRCUIT_VERSION`
+    pub circuit_version: u32,
+    /// The circuit changing features the epochs circuit was built with, see
+    /// `CIRCUIT_FEATURES`
+    pub circuit_features: u32,
+    /// The epoch the proof starts from
+    pub first_epoch: EpochBlock,
+    /// The proven transitions, the last of which is the epoch the proof ends at
+    pub transitions: Vec<EpochTransition>,
+}
+
+impl ProofBundle {
+    /// Bundles a proof generated with `parameters` for the provided epochs, with the current
+    /// circuit version and features
+    pub fn new(
+        parameters: &Parameters<BWCurve, BLSCurve>,
+        first_epoch: EpochBlock,
+        transitions: Vec<EpochTransition>,
+        proof: Proof<BWCurve>,
+    ) -> Self {
+        Self {
+            proof,
+            vk: parameters.epochs.vk.clone(),
+            hashes_in_bls12_377: parameters.hash_to_bits.is_some(),
+            circuit_version: CIRCUIT_VERSION,
+            circuit_features: CIRCUIT_FEATURES,
+            first_epoch,
+            transitions,
+        }
+    }
+
+    /// The epoch the proof ends at, i.e. the first epoch if there are no transitions
+    pub fn last_epoch(&self) -> &EpochBlock {
+        self.transitions
+            .last()
+            .map(|transition| &transition.block)
+            .unwrap_or(&self.first_epoch)
+    }
+
+    /// Verifies the proof against the bundle's first and last epochs
+    pub fn verify(&self) -> Result<(), VerificationError> {
+        verifier::verify(&self.vk, &self.first_epoch, self.last_epoch(), &self.proof)
+    }
+
+    /// Returns a report of the bundle's contents. It does not verify the proof.
+    pub fn inspect(&self) -> Result<ProofInspection, EncodingError> {
+        let mut epochs = vec![EpochInspection::new(&self.first_epoch, None)?];
+        for transition in &self.transitions {
+            let num_signers = transition.bitmap.popcount();
+            epochs.push(EpochInspection::new(&transition.block, Some(num_signers))?);
+        }
+
+        let mut proof_bytes = vec![];
+        self.proof.serialize(&mut proof_bytes)?;
+
+        let first = &epochs[0].summary;
+        let last = &epochs[epochs.len() - 1].summary;
+        Ok(ProofInspection {
+            first_index: self.first_epoch.index,
+            last_index: self.last_epoch().index,
+            range_hash: EpochSummary::range_hash(first, last),
+            epochs,
+            hashes_in_bls12_377: self.hashes_in_bls12_377,
+            vk_fingerprint: digest(&self.vk)?,
+            num_public_inputs: self.vk.gamma_abc_g1.len().saturating_sub(1),
+            proof_bytes,
+        })
+    }
+}
+
+/// Report on the contents of a `ProofBundle`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofInspection {
+    /// The index of the first epoch
+    pub first_index: u16,
+    /// The index of the last epoch
+    pub last_index: u16,
+    /// The hash of the first and last epochs which the proof commits to, see
+    /// `EpochSummary::range_hash`
+    pub range_hash: Vec<u8>,
+    /// The first epoch followed by the epoch of each transition
+    pub epochs: Vec<EpochInspection>,
+    /// Whether the CRH->XOF hashes were proven in BLS12-377
+    pub hashes_in_bls12_377: bool,
+    /// Blake2s hash of the serialized verifying key
+    pub vk_fingerprint: [u8; 32],
+    /// The number of public inputs expected by the verifying key
+    pub num_public_inputs: usize,
+    /// The compressed proof
+    pub proof_bytes: Vec<u8>,
+}
+
+/// Report on a single epoch of a `ProofBundle`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpochInspection {
+    /// The epoch's index
+    pub index: u16,
+    /// The number of validators elected by the epoch
+    pub num_validators: usize,
+    /// The maximum number of validators, to which the circuit pads the public keys
+    pub maximum_validators: usize,
+    /// The maximum allowed number of signers that may be absent
+    pub maximum_non_signers: u32,
+    /// The number of validators of the previous epoch which signed the epoch, or `None`
+    /// for the first epoch
+    pub num_signers: Option<usize>,
+    /// The epoch's summary, whose digests are the ones the circuit commits to when a proof
+    /// starts from or ends at the epoch
+    pub summary: EpochSummary,
+}
+
+impl EpochInspection {
+    fn new(block: &EpochBlock, num_signers: Option<usize>) -> Result<Self, EncodingError> {
+        Ok(Self {
+            index: block.index,
+            num_validators: block.new_public_keys.len(),
+            maximum_validators: block.maximum_validators,
+            maximum_non_signers: block.maximum_non_signers,
+            num_signers,
+            summary: block.summarize()?,
+        })
+    }
+}
+
+impl fmt::Display for ProofInspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "epochs: {} to {} ({} transitions)",
+            self.first_index,
+            self.last_index,
+            self.epochs.len() - 1
+        )?;
+        writeln!(f, "range hash: {}", hex::encode(&self.range_hash))?;
+        writeln!(f, "hashes in BLS12-377: {}", self.hashes_in_bls12_377)?;
+        writeln!(f, "vk fingerprint: {}", hex::encode(self.vk_fingerprint))?;
+        writeln!(f, "public inputs: {}", self.num_public_inputs)?;
+        writeln!(
+            f,
+            "proof ({} bytes): {}",
+            self.proof_bytes.len(),
+            hex::encode(&self.proof_bytes)
+        )?;
+        for (i, epoch) in self.epochs.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", epoch)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EpochInspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "epoch {}: {}/{} validators, maximum non-signers {}, ",
+            self.index, self.num_validators, self.maximum_validators, self.maximum_non_signers
+        )?;
+        if let Some(num_signers) = self.num_signers {
+            write!(f, "{} signers, ", num_signers)?;
+        }
+        write!(
+            f,
+            "first epoch digest {}, last epoch digest {}",
+            hex::encode(self.summary.pubkey_hash),
+            hex::encode(self.summary.apk_commitment)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{
+        bls12_377::{G1Projective, G2Projective},
+        ProjectiveCurve,
+    };
+    use bls_crypto::{PublicKey, Signature};
+
+    fn epoch(index: u16, num_validators: usize) -> EpochBlock {
+        let pubkeys = (0..num_validators)
+            .map(|_| PublicKey::from(G2Projective::prime_subgroup_generator()))
+            .collect::<Vec<_>>();
+        EpochBlock::new(index, 0, None, None, 1, 4, pubkeys)
+    }
+
+    #[test]
+    fn inspects_bundle() {
+        let first_epoch = epoch(10, 4);
+        let transitions = vec![11, 12]
+            .into_iter()
+            .map(|index| EpochTransition {
+                block: epoch(index, 3),
+                aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                bitmap: vec![true, false, true, true].into(),
+            })
+            .collect::<Vec<_>>();
+        let bundle = ProofBundle {
+            proof: Proof::default(),
+            vk: VerifyingKey::default(),
+            hashes_in_bls12_377: false,
+            circuit_version: CIRCUIT_VERSION,
+            circuit_features: CIRCUIT_FEATURES,
+            first_epoch,
+            transitions: transitions.clone(),
+        };
+
+        let inspection = bundle.inspect().unwrap();
+        assert_eq!((inspection.first_index, inspection.last_index), (10, 12));
+        assert_eq!(inspection.epochs.len(), 3);
+        assert_eq!(inspection.epochs[0].num_signers, None);
+        assert_eq!(inspection.epochs[1].num_signers, Some(3));
+        assert_eq!(inspection.epochs[2].num_validators, 3);
+        assert_eq!(
+            inspection.epochs[2].summary,
+            transitions[1].block.summarize().unwrap()
+        );
+        assert_eq!(
+            inspection.range_hash,
+            verifier::public_input_bytes(&bundle.first_epoch, bundle.last_epoch()).unwrap()
+        );
+        assert_eq!(inspection.vk_fingerprint, digest(&bundle.vk).unwrap());
+
+        let report = inspection.to_string();
+        assert!(report.starts_with("epochs: 10 to 12 (2 transitions)\n"));
+        assert_eq!(report.lines().count(), 6 + 3);
+        assert!(report.contains("epoch 11: 3/4 validators, maximum non-signers 1, 3 signers"));
+
+        // a different key changes the fingerprint
+        let mut other = bundle;
+        other.vk.gamma_abc_g1.push(Default::default());
+        assert_ne!(
+            other.inspect().unwrap().vk_fingerprint,
+            inspection.vk_fingerprint
+        );
+        assert_eq!(other.inspect().unwrap().num_public_inputs, 0);
+    }
+}
@@ -0,0 +1,271 @@
+//! Pre-flight Compatibility Checks
+//!
+//! Proving with parameters which were generated for another number of validators, number of
+//! epochs or set of circuit-changing features only fails once the witness does not fit the
+//! proving key, or panics in the middle of proving. Two cheap checks catch this early:
+//!
+//! - `Parameters::check_compatibility` compares the number of public inputs of the keys with
+//!   the number the enabled features and the number of epochs require, before any synthesis
+//! - the prover compares the number of variables of the circuit it synthesizes with the
+//!   proving key right after the synthesis, before any multi-scalar multiplication
+use super::{setup::Parameters, BLSCurve, BWCurve, BWFrParams};
+use crate::{epoch_block::EpochBlock, gadgets::HashToBits};
+
+use algebra::{Field, FpParameters};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use std::{cell::Cell, fmt, rc::Rc};
+use thiserror::Error;
+use tracing::info;
+
+/// Where the CRH->XOF hashes of the epochs are proven
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashMode {
+    /// In the epochs circuit, over BW6_761
+    Bw6_761,
+    /// In a separate circuit over BLS12-377, whose proof the epochs circuit verifies
+    Bls12_377,
+}
+
+impl fmt::Display for HashMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashMode::Bw6_761 => write!(f, "in BW6_761"),
+            HashMode::Bls12_377 => write!(f, "in BLS12-377"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+/// Error raised when parameters cannot prove inputs of the requested shape
+pub enum CompatibilityError {
+    #[error(
+        "the CRH->XOF parameters have {actual} public inputs, but {num_epochs} epochs need {expected}"
+    )]
+    HashToBitsMismatch {
+        num_epochs: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error(
+        "the epochs parameters have {actual} public inputs, but the enabled features need \
+         {expected}"
+    )]
+    PublicInputsMismatch { expected: usize, actual: usize },
+    #[error(
+        "the epochs parameters have {actual_inputs} public inputs and {actual_witnesses} \
+         witnesses, but the circuit for the inputs has {expected_inputs} and \
+         {expected_witnesses}"
+    )]
+    EpochsMismatch {
+        expected_inputs: usize,
+        expected_witnesses: usize,
+        actual_inputs: usize,
+        actual_witnesses: usize,
+    },
+}
+
+impl ToErrorCode for CompatibilityError {
+    fn error_code(&self) -> ErrorCode {
+        ErrorCode::InvalidArgument
+    }
+}
+
+impl Parameters<BWCurve, BLSCurve> {
+    /// Where the parameters prove the CRH->XOF hashes
+    pub fn hash_mode(&self) -> HashMode {
+        if self.hash_to_bits.is_some() {
+            HashMode::Bls12_377
+        } else {
+            HashMode::Bw6_761
+        }
+    }
+
+    /// Checks that the parameters have the public inputs of the epochs circuit built with
+    /// the enabled features, without extra inputs, and that their CRH->XOF parameters (if
+    /// any) can prove the hashes of `num_epochs` epochs (including the dummy epochs which pad
+    /// a proof). This synthesizes nothing, so it does not detect parameters generated for
+    /// another number of validators or epochs, which the prover detects once it synthesized
+    /// the circuit.
+    pub fn check_compatibility(&self, num_epochs: usize) -> Result<(), CompatibilityError> {
+        info!("Checking parameters for {} epochs", num_epochs);
+        if let Some(ref params) = self.hash_to_bits {
+            let expected = HashToBits::num_public_inputs::<BWFrParams>(num_epochs);
+            // the first input is the constant one
+            let actual = params.vk.gamma_abc_g1.len() - 1;
+            if actual != expected {
+                return Err(CompatibilityError::HashToBitsMismatch {
+                    num_epochs,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        let expected = num_public_inputs();
+        let actual = self.epochs.vk.gamma_abc_g1.len() - 1;
+        if actual != expected {
+            return Err(CompatibilityError::PublicInputsMismatch { expected, actual });
+        }
+        Ok(())
+    }
+}
+
+/// The number of public inputs of the epochs circuit built with the enabled features,
+/// without extra inputs, see `EpochBits::verify_edges`
+pub(crate) fn num_public_inputs() -> usize {
+    if cfg!(feature = "compressed-public-inputs") {
+        return 1;
+    }
+    let capacity = BWFrParams::CAPACITY as usize;
+    let packed = |num_bits: usize| (num_bits + capacity - 1) / capacity;
+
+    // the hashes of the first and last epochs
+    let mut num_inputs = packed(2 * 256);
+    if cfg!(feature = "chain-binding") {
+        num_inputs += packed(EpochBlock::CHAIN_ID_BYTES * 8);
+    }
+    if cfg!(feature = "circuit-version") {
+        num_inputs += 1;
+    }
+    // the bitmap, aux data and randomness commitments are Blake2s hashes
+    let commitments = [
+        cfg!(feature = "bitmap-commitment"),
+        cfg!(feature = "epoch-aux-data"),
+        cfg!(feature = "randomness-beacon"),
+    ];
+    num_inputs + commitments.iter().filter(|enabled| **enabled).count() * packed(256)
+}
+
+/// Wraps a circuit and checks, once it is synthesized, that it has as many instance and
+/// witness variables as the proving key, so that the prover stops before its multi-scalar
+/// multiplications when the key was generated for another circuit
+pub(crate) struct CheckedCircuit<C> {
+    circuit: C,
+    /// The number of instance and witness variables of the proving key
+    key_shape: (usize, usize),
+    mismatch: Rc<Cell<Option<CompatibilityError>>>,
+}
+
+/// The mismatch found while synthesizing a `CheckedCircuit`, if any
+pub(crate) struct ShapeCheck {
+    mismatch: Rc<Cell<Option<CompatibilityError>>>,
+}
+
+impl<C> CheckedCircuit<C> {
+    /// Wraps the circuit, which must have `num_instance_variables` (including the constant
+    /// one) and `num_witness_variables`
+    pub(crate) fn new(
+        circuit: C,
+        num_instance_variables: usize,
+        num_witness_variables: usize,
+    ) -> (Self, ShapeCheck) {
+        let mismatch = Rc::new(Cell::new(None));
+        (
+            Self {
+                circuit,
+                key_shape: (num_instance_variables, num_witness_variables),
+                mismatch: mismatch.clone(),
+            },
+            ShapeCheck { mismatch },
+        )
+    }
+}
+
+impl<C: ConstraintSynthesizer<F>, F: Field> ConstraintSynthesizer<F> for CheckedCircuit<C> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        self.circuit.generate_constraints(cs.clone())?;
+        let (actual_inputs, actual_witnesses) = self.key_shape;
+        let (expected_inputs, expected_witnesses) =
+            (cs.num_instance_variables(), cs.num_witness_variables());
+        if (expected_inputs, expected_witnesses) != (actual_inputs, actual_witnesses) {
+            self.mismatch.set(Some(CompatibilityError::EpochsMismatch {
+                expected_inputs,
+                expected_witnesses,
+                actual_inputs,
+                actual_witnesses,
+            }));
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        Ok(())
+    }
+}
+
+impl ShapeCheck {
+    /// The mismatch found during the synthesis, if any
+    pub(crate) fn mismatch(self) -> Option<CompatibilityError> {
+        self.mismatch.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{trusted_setup, BWField};
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::{alloc::AllocVar, fields::fp::FpVar};
+
+    #[test]
+    fn counts_public_inputs() {
+        let rng = &mut rand::thread_rng();
+        let mut params = trusted_setup(3, 2, 1, rng, false).unwrap();
+        assert_eq!(params.hash_mode(), HashMode::Bw6_761);
+        assert_eq!(params.epochs.vk.gamma_abc_g1.len(), num_public_inputs() + 1);
+        params.check_compatibility(2).unwrap();
+
+        params.epochs.vk.gamma_abc_g1.push(Default::default());
+        assert!(matches!(
+            params.check_compatibility(2),
+            Err(CompatibilityError::PublicInputsMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn checks_hash_to_bits_parameters() {
+        let rng = &mut rand::thread_rng();
+        let params = trusted_setup(3, 2, 1, rng, true).unwrap();
+        assert_eq!(params.hash_mode(), HashMode::Bls12_377);
+        params.check_compatibility(2).unwrap();
+
+        assert!(matches!(
+            params.check_compatibility(5),
+            Err(CompatibilityError::HashToBitsMismatch { num_epochs: 5, .. })
+        ));
+    }
+
+    struct Witnesses(usize);
+
+    impl ConstraintSynthesizer<BWField> for Witnesses {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<BWField>,
+        ) -> Result<(), SynthesisError> {
+            for _ in 0..self.0 {
+                FpVar::new_witness(cs.clone(), || Ok(BWField::from(1u64)))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn checks_synthesized_shape() {
+        let (circuit, check) = CheckedCircuit::new(Witnesses(3), 1, 3);
+        circuit
+            .generate_constraints(ConstraintSystem::new_ref())
+            .unwrap();
+        assert!(check.mismatch().is_none());
+
+        let (circuit, check) = CheckedCircuit::new(Witnesses(4), 1, 3);
+        assert!(circuit
+            .generate_constraints(ConstraintSystem::new_ref())
+            .is_err());
+        assert!(matches!(
+            check.mismatch(),
+            Some(CompatibilityError::EpochsMismatch {
+                expected_witnesses: 4,
+                actual_witnesses: 3,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+//! Configuration of the CPU resources used while generating parameters and proofs
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::sync::Arc;
+
+/// Controls the parallelism of `prove_with_config` and `trusted_setup_with_config`.
+///
+/// By default, the global Rayon pool is used. Embedders which run other workloads alongside
+/// the prover can either restrict the number of threads or provide their own pool, which
+/// is then used for all the multi-scalar multiplications and FFTs.
+#[derive(Clone, Debug, Default)]
+pub struct ProverConfig {
+    thread_pool: Option<Arc<ThreadPool>>,
+}
+
+impl ProverConfig {
+    /// Runs the prover on a dedicated pool with the provided number of threads
+    pub fn with_threads(threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+        Ok(Self::with_thread_pool(Arc::new(pool)))
+    }
+
+    /// Runs the prover on an existing pool
+    pub fn with_thread_pool(thread_pool: Arc<ThreadPool>) -> Self {
+        Self {
+            thread_pool: Some(thread_pool),
+        }
+    }
+
+    /// The number of threads used by the prover
+    pub fn threads(&self) -> usize {
+        match self.thread_pool {
+            Some(ref pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+
+    /// Executes `op` in the configured pool, or in the current one if none was provided
+    pub(crate) fn install<T, F>(&self, op: F) -> T
+    where
+        T: Send,
+        F: FnOnce() -> T + Send,
+    {
+        match self.thread_pool {
+            Some(ref pool) => pool.install(op),
+            None => op(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_in_configured_pool() {
+        let config = ProverConfig::with_threads(2).unwrap();
+        assert_eq!(config.threads(), 2);
+        assert_eq!(config.install(rayon::current_num_threads), 2);
+
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(3).build().unwrap());
+        let config = ProverConfig::with_thread_pool(pool.clone());
+        assert_eq!(config.install(rayon::current_num_threads), 3);
+        assert_eq!(config.clone().threads(), pool.current_num_threads());
+    }
+}
@@ -0,0 +1,382 @@
+/// Encryption of the public parameters at rest
+///
+/// The parameters are serialized and encrypted in chunks with XChaCha20-Poly1305, following
+/// the STREAM construction: each chunk's nonce is made of a random prefix, the chunk's counter
+/// and a flag marking the last chunk. This authenticates every chunk individually, so corruption
+/// is detected as soon as the affected chunk is read, and also prevents chunks from being
+/// reordered, dropped or the stream from being truncated.
+use super::{key_io::read_parameters, setup::Parameters};
+
+use algebra::{
+    serialize::{CanonicalSerialize, SerializationError},
+    PairingEngine,
+};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+use thiserror::Error;
+use tracing::info;
+
+/// Identifies an encrypted parameters file
+const MAGIC: &[u8; 8] = b"PLUMOENC";
+/// The version of the encrypted file format
+const VERSION: u8 = 1;
+/// The size of the random part of each chunk's nonce
+const NONCE_PREFIX_BYTES: usize = 19;
+/// The size of each plaintext chunk
+const CHUNK_SIZE: usize = 1 << 20;
+/// The size of the Poly1305 authentication tag appended to each chunk
+const TAG_BYTES: usize = 16;
+
+#[derive(Debug, Error)]
+/// Error raised while encrypting or decrypting the public parameters
+pub enum EncryptionError {
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Zexe Error: {0}")]
+    ZexeSerialization(#[from] SerializationError),
+    #[error("not an encrypted parameters file or unsupported version")]
+    InvalidHeader,
+    #[error("chunk {0} failed authentication (wrong key or corrupted data)")]
+    AuthenticationFailed(u32),
+    #[error("the encrypted stream was truncated")]
+    Truncated,
+}
+
+impl ToErrorCode for EncryptionError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            EncryptionError::IoError(_) => ErrorCode::Io,
+            EncryptionError::ZexeSerialization(_) => ErrorCode::Serialization,
+            EncryptionError::InvalidHeader
+            | EncryptionError::AuthenticationFailed(_)
+            | EncryptionError::Truncated => ErrorCode::Encryption,
+        }
+    }
+}
+
+impl<CP: PairingEngine, BLS: PairingEngine> Parameters<CP, BLS> {
+    /// Serializes the parameters and writes them encrypted under `key` to the file at `path`
+    pub fn write_encrypted<P: AsRef<Path>>(
+        &self,
+        path: P,
+        key: &[u8; 32],
+    ) -> Result<(), EncryptionError> {
+        info!(
+            "Writing encrypted parameters to {}",
+            path.as_ref().display()
+        );
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = EncryptedWriter::new(file, key)?;
+
+        self.epochs.serialize(&mut writer)?;
+        match self.hash_to_bits {
+            Some(ref hash_to_bits) => {
+                writer.write_u8(1)?;
+                hash_to_bits.serialize(&mut writer)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+
+        writer.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Reads and decrypts parameters which were written with `write_encrypted`. Fails if
+    /// any part of the file was modified or the key is wrong.
+    pub fn read_encrypted<P: AsRef<Path>>(
+        path: P,
+        key: &[u8; 32],
+    ) -> Result<Self, EncryptionError> {
+        info!(
+            "Reading encrypted parameters from {}",
+            path.as_ref().display()
+        );
+        let file = BufReader::new(File::open(path)?);
+        let mut reader = DecryptedReader::new(file, key)?;
+
+        let epochs = read_parameters(&mut reader)?;
+        let hash_to_bits = match reader.read_u8()? {
+            0 => None,
+            _ => Some(read_parameters(&mut reader)?),
+        };
+        reader.finish()?;
+
+        Ok(Parameters {
+            epochs,
+            hash_to_bits,
+        })
+    }
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_BYTES], counter: u32, last: bool) -> XNonce {
+    let mut nonce = [0u8; 24];
+    nonce[..NONCE_PREFIX_BYTES].copy_from_slice(prefix);
+    (&mut nonce[NONCE_PREFIX_BYTES..NONCE_PREFIX_BYTES + 4])
+        .write_u32::<BigEndian>(counter)
+        .expect("slice has enough space");
+    nonce[23] = last as u8;
+    *XNonce::from_slice(&nonce)
+}
+
+/// Buffers plaintext and writes it out as `last flag || ciphertext length (LE u32) || ciphertext`
+/// chunks. `finish` must be called to write the final chunk.
+struct EncryptedWriter<W> {
+    inner: W,
+    cipher: XChaCha20Poly1305,
+    header: Vec<u8>,
+    prefix: [u8; NONCE_PREFIX_BYTES],
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptedWriter<W> {
+    fn new(mut inner: W, key: &[u8; 32]) -> io::Result<Self> {
+        let mut prefix = [0u8; NONCE_PREFIX_BYTES];
+        rand::thread_rng().fill_bytes(&mut prefix);
+
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        header.extend_from_slice(&prefix);
+        inner.write_all(&header)?;
+
+        Ok(Self {
+            inner,
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+            header,
+            prefix,
+            counter: 0,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    fn write_chunk(&mut self, last: bool) -> io::Result<()> {
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &self.buffer,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "encryption failed"))?;
+
+        self.inner.write_u8(last as u8)?;
+        self.inner
+            .write_u32::<LittleEndian>(ciphertext.len() as u32)?;
+        self.inner.write_all(&ciphertext)?;
+
+        self.buffer.clear();
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "too many chunks"))?;
+        Ok(())
+    }
+
+    /// Writes the remaining buffered data as the last chunk and returns the inner writer
+    fn finish(mut self) -> io::Result<W> {
+        self.write_chunk(true)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == CHUNK_SIZE {
+            self.write_chunk(false)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // only full chunks are written out before `finish`, so that every
+        // chunk except the last one has the same size
+        self.inner.flush()
+    }
+}
+
+/// Reads and authenticates the chunks written by an `EncryptedWriter`
+struct DecryptedReader<R> {
+    inner: R,
+    cipher: XChaCha20Poly1305,
+    header: Vec<u8>,
+    prefix: [u8; NONCE_PREFIX_BYTES],
+    counter: u32,
+    buffer: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: Read> DecryptedReader<R> {
+    fn new(mut inner: R, key: &[u8; 32]) -> Result<Self, EncryptionError> {
+        let mut header = vec![0u8; MAGIC.len() + 1 + NONCE_PREFIX_BYTES];
+        inner
+            .read_exact(&mut header)
+            .map_err(|_| EncryptionError::InvalidHeader)?;
+        if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION {
+            return Err(EncryptionError::InvalidHeader);
+        }
+        let mut prefix = [0u8; NONCE_PREFIX_BYTES];
+        prefix.copy_from_slice(&header[MAGIC.len() + 1..]);
+
+        Ok(Self {
+            inner,
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+            header,
+            prefix,
+            counter: 0,
+            buffer: vec![],
+            position: 0,
+            done: false,
+        })
+    }
+
+    fn read_chunk(&mut self) -> Result<(), EncryptionError> {
+        let last = match self.inner.read_u8() {
+            Ok(flag) => flag == 1,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(EncryptionError::Truncated)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let len = self.inner.read_u32::<LittleEndian>()? as usize;
+        if len > CHUNK_SIZE + TAG_BYTES {
+            return Err(EncryptionError::AuthenticationFailed(self.counter));
+        }
+        let mut ciphertext = vec![0u8; len];
+        self.inner
+            .read_exact(&mut ciphertext)
+            .map_err(|_| EncryptionError::Truncated)?;
+
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        self.buffer = self
+            .cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: &ciphertext,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|_| EncryptionError::AuthenticationFailed(self.counter))?;
+        self.position = 0;
+        self.counter += 1;
+        self.done = last;
+        Ok(())
+    }
+
+    /// Ensures that the whole stream was consumed up to and including the last chunk
+    fn finish(mut self) -> Result<(), EncryptionError> {
+        while !self.done {
+            self.read_chunk()?;
+        }
+        let mut trailing = [0u8; 1];
+        if self.position != self.buffer.len() || self.inner.read(&mut trailing)? != 0 {
+            return Err(EncryptionError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trailing data after the parameters",
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.read_chunk()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let len = buf.len().min(self.buffer.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::trusted_setup;
+
+    fn encrypt(data: &[u8], key: &[u8; 32]) -> Vec<u8> {
+        let mut writer = EncryptedWriter::new(vec![], key).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, EncryptionError> {
+        let mut reader = DecryptedReader::new(data, key)?;
+        let mut out = vec![];
+        reader.read_to_end(&mut out)?;
+        reader.finish()?;
+        Ok(out)
+    }
+
+    #[test]
+    fn stream_roundtrip_and_tampering() {
+        let key = [7u8; 32];
+        let data = (0..2 * CHUNK_SIZE + 10)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        let encrypted = encrypt(&data, &key);
+        assert_eq!(decrypt(&encrypted, &key).unwrap(), data);
+
+        // wrong key
+        assert!(decrypt(&encrypted, &[8u8; 32]).is_err());
+
+        // flipped bit in the second chunk
+        let mut corrupted = encrypted.clone();
+        let idx = encrypted.len() - 20;
+        corrupted[idx] ^= 1;
+        assert!(decrypt(&corrupted, &key).is_err());
+
+        // the last chunk is dropped
+        let truncated = &encrypted[..encrypted.len() - (5 + 10 + TAG_BYTES)];
+        assert!(decrypt(truncated, &key).is_err());
+
+        // not an encrypted file
+        assert!(matches!(
+            decrypt(&data, &key),
+            Err(EncryptionError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn parameters_roundtrip() {
+        let rng = &mut rand::thread_rng();
+        let params = trusted_setup(3, 2, 1, rng, false).unwrap();
+        let path = std::env::temp_dir().join(format!("params-{}.enc", rng.next_u64()));
+        let key = [1u8; 32];
+
+        params.write_encrypted(&path, &key).unwrap();
+        let decrypted = Parameters::read_encrypted(&path, &key).unwrap();
+        assert_eq!(decrypted.epochs.vk, params.epochs.vk);
+        assert!(decrypted.hash_to_bits.is_none());
+        assert!(
+            Parameters::<crate::api::BWCurve, crate::api::BLSCurve>::read_encrypted(
+                &path, &[2u8; 32]
+            )
+            .is_err()
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+//! Erasure of the witness assignments once a proof has been produced
+use algebra::Field;
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{compiler_fence, Ordering},
+};
+
+type SharedConstraintSystem<F> = Rc<RefCell<Option<ConstraintSystemRef<F>>>>;
+
+/// Wraps a circuit and keeps a handle to the constraint system it gets synthesized in,
+/// so that its assignments can be erased after the prover has consumed the circuit.
+pub(crate) struct ErasingCircuit<C, F: Field> {
+    circuit: C,
+    cs: SharedConstraintSystem<F>,
+}
+
+/// Zeroizes the assignments of the constraint system that the wrapped circuit was
+/// synthesized in when dropped
+pub(crate) struct WitnessEraser<F: Field> {
+    cs: SharedConstraintSystem<F>,
+}
+
+impl<C: ConstraintSynthesizer<F>, F: Field> ErasingCircuit<C, F> {
+    /// Wraps the circuit. The returned eraser must be kept alive until the proof is produced.
+    pub(crate) fn new(circuit: C) -> (Self, WitnessEraser<F>) {
+        let cs = Rc::new(RefCell::new(None));
+        (
+            Self {
+                circuit,
+                cs: cs.clone(),
+            },
+            WitnessEraser { cs },
+        )
+    }
+}
+
+impl<C: ConstraintSynthesizer<F>, F: Field> ConstraintSynthesizer<F> for ErasingCircuit<C, F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        *self.cs.borrow_mut() = Some(cs.clone());
+        self.circuit.generate_constraints(cs)
+    }
+}
+
+impl<F: Field> WitnessEraser<F> {
+    /// Erases the assignments
+    pub(crate) fn erase(self) {}
+}
+
+impl<F: Field> Drop for WitnessEraser<F> {
+    fn drop(&mut self) {
+        if let Some(cs) = self.cs.borrow_mut().take() {
+            erase_assignments(&cs);
+        }
+    }
+}
+
+/// Overwrites the instance and witness assignments of the constraint system with zeros
+/// and then clears them. Copies of the assignments made by the prover internally are
+/// out of our control and are not erased.
+pub(crate) fn erase_assignments<F: Field>(cs: &ConstraintSystemRef<F>) {
+    if let Some(mut cs) = cs.borrow_mut() {
+        zeroize(&mut cs.instance_assignment);
+        zeroize(&mut cs.witness_assignment);
+    }
+}
+
+pub(crate) fn zeroize<F: Field>(values: &mut Vec<F>) {
+    for value in values.iter_mut() {
+        // volatile writes so that the compiler does not optimize them away
+        unsafe { std::ptr::write_volatile(value, F::zero()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+    values.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::BWField;
+    use algebra::{One, Zero};
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::{alloc::AllocVar, fields::fp::FpVar};
+
+    struct Secret(Vec<BWField>);
+
+    impl ConstraintSynthesizer<BWField> for Secret {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<BWField>,
+        ) -> Result<(), SynthesisError> {
+            for value in self.0 {
+                FpVar::new_witness(cs.clone(), || Ok(value))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn erases_witness() {
+        let secret = vec![BWField::one(); 10];
+        let cs = ConstraintSystem::<BWField>::new_ref();
+        let (circuit, eraser) = ErasingCircuit::new(Secret(secret.clone()));
+        circuit.generate_constraints(cs.clone()).unwrap();
+
+        let (ptr, len) = {
+            let cs = cs.borrow().unwrap();
+            assert_eq!(cs.witness_assignment, secret);
+            (cs.witness_assignment.as_ptr(), cs.witness_assignment.len())
+        };
+
+        eraser.erase();
+
+        let cs = cs.borrow().unwrap();
+        assert!(cs.witness_assignment.is_empty());
+        assert!(cs.instance_assignment.is_empty());
+        // the buffer is still allocated, so we can check that it was zeroized
+        assert_eq!(cs.witness_assignment.as_ptr(), ptr);
+        let erased = unsafe { std::slice::from_raw_parts(ptr, len) };
+        assert!(erased.iter().all(|value| value.is_zero()));
+    }
+}
@@ -0,0 +1,320 @@
+//! Constraint System Export
+//!
+//! Writes the epochs circuit in the binary formats of circom, so that external tooling
+//! (snarkjs, R1CS analyzers, academic tooling) can inspect it and cross-check it:
+//!
+//! - `.r1cs` (version 1): a header section with the field and the number of wires, a section
+//!   with the constraints `A * B - C = 0` and a section mapping each wire to a label
+//! - `.wtns` (version 2): a header section with the field and the number of values, and a
+//!   section with the value of each wire
+//!
+//! Every number is little-endian, and field elements are written in their standard (not
+//! Montgomery) form, zero-padded to `n8` bytes. The wires are the variables of the constraint
+//! system in zexe's order: the constant 1, the public inputs and then the witness variables.
+//! Since the circuit does not distinguish private inputs from intermediate variables, all
+//! witness variables are counted as intermediate, i.e. `nPrvIn` is 0, and each wire is
+//! labelled with its own index.
+//!
+//! The exported circuit constrains the CRH->XOF hashes in BW6_761, as the circuit cached by
+//! the `registry` does.
+use super::{
+    prover::{check_transitions, to_circuit, ProverError},
+    registry::{self, CircuitShape},
+    BWField,
+};
+use crate::epoch_block::{EpochBlock, EpochTransition};
+
+use algebra::{FpParameters, PrimeField, ToBytes};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use r1cs_core::{ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+};
+use thiserror::Error;
+use tracing::info;
+
+const R1CS_MAGIC: &[u8; 4] = b"r1cs";
+const R1CS_VERSION: u32 = 1;
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const WTNS_VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+/// Error raised while exporting a circuit or a witness
+pub enum ExportError {
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("Prover Error: {0}")]
+    Prover(#[from] ProverError),
+    #[error("the circuit has {0} {1}, more than the format can hold")]
+    TooLarge(usize, &'static str),
+}
+
+impl ToErrorCode for ExportError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ExportError::IoError(e) => e.error_code(),
+            ExportError::ZexeSynthesisError(e) => e.error_code(),
+            ExportError::Prover(e) => e.error_code(),
+            ExportError::TooLarge(..) => ErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// Writes the epochs circuit of the shape in the `.r1cs` format, synthesizing it unless it is
+/// cached in the `registry`
+pub fn export_r1cs<W: Write>(shape: CircuitShape, writer: W) -> Result<(), ExportError> {
+    info!("Exporting the constraints of the circuit for {:?}", shape);
+    write_r1cs(&registry::circuit_for(shape)?, writer)
+}
+
+/// Writes the witness of the epochs circuit for the provided epochs in the `.wtns` format. The
+/// circuit is that of `export_r1cs` for `CircuitShape::new(num_validators, max_transitions)`.
+/// The witness is exported even if it does not satisfy the circuit, see `prove_check_only`
+/// to find the unsatisfied constraints.
+pub fn export_wtns<W: Write>(
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    writer: W,
+) -> Result<(), ExportError> {
+    info!(
+        "Exporting the witness of {} epochs (first epoch: {}, {} validators per epoch)",
+        transitions.len(),
+        initial_epoch.index,
+        num_validators,
+    );
+    check_transitions(transitions, max_transitions)?;
+    let circuit = to_circuit(
+        num_validators,
+        initial_epoch,
+        transitions,
+        max_transitions,
+        None,
+    )
+    .map_err(ProverError::from)?;
+
+    let cs = ConstraintSystem::<BWField>::new_ref();
+    circuit.generate_constraints(cs.clone())?;
+    let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+    let assignment = [&cs.instance_assignment[..], &cs.witness_assignment[..]].concat();
+    write_wtns(&assignment, writer)
+}
+
+/// Writes the constraint matrices in the `.r1cs` format
+pub fn write_r1cs<F: PrimeField, W: Write>(
+    matrices: &ConstraintMatrices<F>,
+    mut writer: W,
+) -> Result<(), ExportError> {
+    let n8 = field_size::<F>();
+    let num_wires = matrices.num_instance_variables + matrices.num_witness_variables;
+    let num_wires_u32 = to_u32(num_wires, "wires")?;
+    let num_public_inputs = to_u32(matrices.num_instance_variables - 1, "public inputs")?;
+    let num_constraints = to_u32(matrices.num_constraints, "constraints")?;
+
+    writer.write_all(R1CS_MAGIC)?;
+    writer.write_all(&R1CS_VERSION.to_le_bytes())?;
+    writer.write_all(&3u32.to_le_bytes())?;
+
+    // header
+    write_section_header(&mut writer, 1, (n8 + 32) as u64)?;
+    write_field::<F, _>(&mut writer)?;
+    writer.write_all(&num_wires_u32.to_le_bytes())?;
+    // public outputs, public inputs and private inputs
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&num_public_inputs.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    // labels
+    writer.write_all(&(num_wires as u64).to_le_bytes())?;
+    writer.write_all(&num_constraints.to_le_bytes())?;
+
+    // constraints
+    let size = matrices
+        .a
+        .iter()
+        .chain(&matrices.b)
+        .chain(&matrices.c)
+        .map(|row| 4 + row.len() * (4 + n8))
+        .sum::<usize>();
+    write_section_header(&mut writer, 2, size as u64)?;
+    for ((a, b), c) in matrices.a.iter().zip(&matrices.b).zip(&matrices.c) {
+        for row in &[a, b, c] {
+            writer.write_all(&(row.len() as u32).to_le_bytes())?;
+            for (coeff, index) in row.iter() {
+                // the indices are below the number of wires, which fits in a u32
+                writer.write_all(&(*index as u32).to_le_bytes())?;
+                coeff.into_repr().write(&mut writer)?;
+            }
+        }
+    }
+
+    // wire to label map
+    write_section_header(&mut writer, 3, 8 * num_wires as u64)?;
+    for wire in 0..num_wires as u64 {
+        writer.write_all(&wire.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes the values of the wires, i.e. the instance assignment (starting with the constant 1)
+/// followed by the witness assignment, in the `.wtns` format
+pub fn write_wtns<F: PrimeField, W: Write>(
+    assignment: &[F],
+    mut writer: W,
+) -> Result<(), ExportError> {
+    let n8 = field_size::<F>();
+    let num_values = to_u32(assignment.len(), "wires")?;
+
+    writer.write_all(WTNS_MAGIC)?;
+    writer.write_all(&WTNS_VERSION.to_le_bytes())?;
+    writer.write_all(&2u32.to_le_bytes())?;
+
+    // header
+    write_section_header(&mut writer, 1, (n8 + 8) as u64)?;
+    write_field::<F, _>(&mut writer)?;
+    writer.write_all(&num_values.to_le_bytes())?;
+
+    // values
+    write_section_header(&mut writer, 2, (assignment.len() * n8) as u64)?;
+    for value in assignment {
+        value.into_repr().write(&mut writer)?;
+    }
+    Ok(())
+}
+
+/// The number of bytes of a field element, i.e. of its 64-bit limbs
+fn field_size<F: PrimeField>() -> usize {
+    (F::Params::MODULUS_BITS as usize + 63) / 64 * 8
+}
+
+/// Writes the size of the field elements followed by the field's prime
+fn write_field<F: PrimeField, W: Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(&(field_size::<F>() as u32).to_le_bytes())?;
+    F::Params::MODULUS.write(writer)
+}
+
+fn write_section_header<W: Write>(writer: &mut W, section_type: u32, size: u64) -> io::Result<()> {
+    writer.write_all(&section_type.to_le_bytes())?;
+    writer.write_all(&size.to_le_bytes())
+}
+
+fn to_u32(n: usize, what: &'static str) -> Result<u32, ExportError> {
+    u32::try_from(n).map_err(|_| ExportError::TooLarge(n, what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::Cubes;
+    use algebra::{FromBytes, One, UniformRand};
+    use std::io::Read;
+
+    fn read_u32(reader: &mut &[u8]) -> u32 {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes).unwrap();
+        u32::from_le_bytes(bytes)
+    }
+
+    fn read_u64(reader: &mut &[u8]) -> u64 {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes).unwrap();
+        u64::from_le_bytes(bytes)
+    }
+
+    fn read_field(reader: &mut &[u8]) -> BWField {
+        BWField::from_repr(FromBytes::read(reader).unwrap()).unwrap()
+    }
+
+    fn read_prime(reader: &mut &[u8]) {
+        let prime: <BWField as PrimeField>::BigInt = FromBytes::read(reader).unwrap();
+        assert_eq!(prime, <BWField as PrimeField>::Params::MODULUS);
+    }
+
+    /// Reads the sections of a file in either format, checking its magic, version and the
+    /// size of each section
+    fn read_sections<'a>(mut file: &'a [u8], magic: &[u8], version: u32) -> Vec<&'a [u8]> {
+        assert_eq!(&file[..4], magic);
+        file = &file[4..];
+        assert_eq!(read_u32(&mut file), version);
+        let num_sections = read_u32(&mut file);
+        let sections = (1..=num_sections)
+            .map(|section_type| {
+                assert_eq!(read_u32(&mut file), section_type);
+                let size = read_u64(&mut file) as usize;
+                let (section, rest) = file.split_at(size);
+                file = rest;
+                section
+            })
+            .collect();
+        assert!(file.is_empty());
+        sections
+    }
+
+    #[test]
+    fn exported_witness_satisfies_exported_constraints() {
+        let rng = &mut rand::thread_rng();
+        let roots = (0..4).map(|_| BWField::rand(rng)).collect::<Vec<_>>();
+        let cs = ConstraintSystem::<BWField>::new_ref();
+        Cubes(roots).generate_constraints(cs.clone()).unwrap();
+        cs.inline_all_lcs();
+        let matrices = cs.to_matrices().unwrap();
+        let assignment = {
+            let cs = cs.borrow().unwrap();
+            [&cs.instance_assignment[..], &cs.witness_assignment[..]].concat()
+        };
+
+        let mut r1cs = vec![];
+        write_r1cs(&matrices, &mut r1cs).unwrap();
+        let mut wtns = vec![];
+        write_wtns(&assignment, &mut wtns).unwrap();
+
+        let sections = read_sections(&r1cs, R1CS_MAGIC, R1CS_VERSION);
+        let mut header = sections[0];
+        assert_eq!(read_u32(&mut header), 48);
+        read_prime(&mut header);
+        let num_wires = read_u32(&mut header) as usize;
+        assert_eq!(num_wires, assignment.len());
+        // public outputs, public inputs and private inputs
+        assert_eq!(read_u32(&mut header), 0);
+        assert_eq!(read_u32(&mut header), 4);
+        assert_eq!(read_u32(&mut header), 0);
+        assert_eq!(read_u64(&mut header), num_wires as u64);
+        let num_constraints = read_u32(&mut header);
+        assert_eq!(num_constraints as usize, matrices.num_constraints);
+
+        let sections_wtns = read_sections(&wtns, WTNS_MAGIC, WTNS_VERSION);
+        let mut header = sections_wtns[0];
+        assert_eq!(read_u32(&mut header), 48);
+        read_prime(&mut header);
+        assert_eq!(read_u32(&mut header) as usize, num_wires);
+        let mut values = sections_wtns[1];
+        let values = (0..num_wires)
+            .map(|_| read_field(&mut values))
+            .collect::<Vec<_>>();
+        assert_eq!(values, assignment);
+        assert_eq!(values[0], BWField::one());
+
+        let mut constraints = sections[1];
+        for _ in 0..num_constraints {
+            let mut evaluate = || {
+                (0..read_u32(&mut constraints))
+                    .map(|_| {
+                        let wire = read_u32(&mut constraints) as usize;
+                        read_field(&mut constraints) * &values[wire]
+                    })
+                    .sum::<BWField>()
+            };
+            let (a, b, c) = (evaluate(), evaluate(), evaluate());
+            assert_eq!(a * &b, c);
+        }
+        assert!(constraints.is_empty());
+
+        let mut labels = sections[2];
+        for wire in 0..num_wires as u64 {
+            assert_eq!(read_u64(&mut labels), wire);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Circuit Fingerprints
+//!
+//! Any change to the gadgets which alters the epochs circuit invalidates the parameters
+//! (and verifying keys) generated for it. A `CircuitFingerprint` summarizes the circuit of a
+//! shape by a hash of its constraint matrices, along with the number of constraints added
+//! under each gadget span, so that such changes can be caught by comparing the fingerprint
+//! against a snapshot checked into the repository:
+//!
+//! ```text
+//! num_validators = 3
+//! num_epochs = 2
+//! maximum_non_signers = 0
+//! num_constraints = ...
+//! num_instance_variables = ...
+//! num_witness_variables = ...
+//! circuit_hash = 5c0f...
+//! constraints[generate_constraints] = ...
+//! constraints[generate_constraints::ValidatorSetUpdate] = ...
+//! ...
+//! ```
+//!
+//! The gadget counts are inclusive of nested gadgets, and the circuit depends on the enabled
+//! features (e.g. `bft-threshold`), so snapshots are only comparable for the same features.
+use super::{manifest::hash_matrices, registry::CircuitShape, BLSCurve, BWField};
+use crate::gadgets::ValidatorSetUpdate;
+
+use r1cs_core::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError, SynthesisMode,
+};
+use std::{cell::RefCell, collections::BTreeMap, fmt, fs, io, path::Path};
+use thiserror::Error;
+use tracing::{info, span::Id, Subscriber};
+use tracing_subscriber::{
+    layer::{Context, Layer, SubscriberExt},
+    registry::LookupSpan,
+    Registry,
+};
+
+/// Set this environment variable to overwrite mismatching snapshots instead of failing
+pub const UPDATE_SNAPSHOTS_VAR: &str = "UPDATE_CIRCUIT_SNAPSHOTS";
+
+#[derive(Debug, Error)]
+/// Error raised while fingerprinting a circuit or checking it against a snapshot
+pub enum FingerprintError {
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("the circuit does not match its snapshot at {path}:\n{diff}")]
+    SnapshotMismatch { path: String, diff: String },
+    #[error("the circuit snapshot at {0} is missing")]
+    MissingSnapshot(String),
+}
+
+/// Summary of the epochs circuit of a shape, see the module documentation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitFingerprint {
+    /// The shape of the circuit
+    pub shape: CircuitShape,
+    /// The number of constraints
+    pub num_constraints: usize,
+    /// The number of public inputs, including the constant 1
+    pub num_instance_variables: usize,
+    /// The number of witness variables
+    pub num_witness_variables: usize,
+    /// Blake2s hash of the constraint matrices, as in `ParametersManifest::circuit_hash`
+    pub circuit_hash: [u8; 32],
+    /// The number of constraints added under each path of nested gadget spans
+    pub gadget_constraints: BTreeMap<String, usize>,
+}
+
+/// Synthesizes the epochs circuit of the shape (without the CRH->XOF helper) and
+/// fingerprints it
+pub fn circuit_fingerprint(shape: CircuitShape) -> Result<CircuitFingerprint, FingerprintError> {
+    info!("Fingerprinting circuit for {:?}", shape);
+    let cs = ConstraintSystem::<BWField>::new_ref();
+    cs.set_mode(SynthesisMode::Setup);
+    let circuit = ValidatorSetUpdate::<BLSCurve>::empty(
+        shape.num_validators,
+        shape.num_epochs,
+        shape.maximum_non_signers,
+        None,
+    );
+
+    COUNTING.with(|counting| *counting.borrow_mut() = Some(Counting::new(cs.clone())));
+    let subscriber = Registry::default().with(GadgetCounter);
+    let synthesized =
+        tracing::subscriber::with_default(subscriber, || circuit.generate_constraints(cs.clone()));
+    let counting = COUNTING.with(|counting| counting.borrow_mut().take());
+    synthesized?;
+
+    cs.inline_all_lcs();
+    let matrices = cs.to_matrices().ok_or(SynthesisError::AssignmentMissing)?;
+    Ok(CircuitFingerprint {
+        shape,
+        num_constraints: matrices.num_constraints,
+        num_instance_variables: matrices.num_instance_variables,
+        num_witness_variables: matrices.num_witness_variables,
+        circuit_hash: hash_matrices(&matrices)?,
+        gadget_constraints: counting.map(|c| c.counts).unwrap_or_default(),
+    })
+}
+
+impl CircuitFingerprint {
+    /// Compares the fingerprint against the snapshot at `path`. If the snapshot is missing or
+    /// does not match, it is written when the `UPDATE_CIRCUIT_SNAPSHOTS` environment variable
+    /// is set. Otherwise, a missing snapshot fails rather than being recorded silently (which
+    /// would let a changed circuit pass on a fresh checkout), and a mismatch fails with the
+    /// lines which differ.
+    pub fn check_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), FingerprintError> {
+        let path = path.as_ref();
+        let current = self.to_string();
+        let update = std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some();
+        let snapshot = match fs::read_to_string(path) {
+            Ok(snapshot) => snapshot,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if update {
+                    info!("Writing new circuit snapshot to {}", path.display());
+                    return write_snapshot(path, &current);
+                }
+                return Err(FingerprintError::MissingSnapshot(
+                    path.display().to_string(),
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if snapshot == current {
+            return Ok(());
+        }
+        if update {
+            info!("Updating circuit snapshot at {}", path.display());
+            return write_snapshot(path, &current);
+        }
+
+        let mut diff = String::new();
+        let expected = snapshot.lines().collect::<Vec<_>>();
+        let got = current.lines().collect::<Vec<_>>();
+        for line in &expected {
+            if !got.contains(line) {
+                diff.push_str(&format!("- {}\n", line));
+            }
+        }
+        for line in &got {
+            if !expected.contains(line) {
+                diff.push_str(&format!("+ {}\n", line));
+            }
+        }
+        Err(FingerprintError::SnapshotMismatch {
+            path: path.display().to_string(),
+            diff,
+        })
+    }
+}
+
+fn write_snapshot(path: &Path, contents: &str) -> Result<(), FingerprintError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+impl fmt::Display for CircuitFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "num_validators = {}", self.shape.num_validators)?;
+        writeln!(f, "num_epochs = {}", self.shape.num_epochs)?;
+        writeln!(
+            f,
+            "maximum_non_signers = {}",
+            self.shape.maximum_non_signers
+        )?;
+        writeln!(f, "num_constraints = {}", self.num_constraints)?;
+        writeln!(
+            f,
+            "num_instance_variables = {}",
+            self.num_instance_variables
+        )?;
+        writeln!(f, "num_witness_variables = {}", self.num_witness_variables)?;
+        writeln!(f, "circuit_hash = {}", hex::encode(self.circuit_hash))?;
+        for (path, count) in &self.gadget_constraints {
+            writeln!(f, "constraints[{}] = {}", path, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of the fingerprint being computed on this thread. The constraint system is not
+/// `Send`, so it cannot be held by the tracing layer itself.
+struct Counting {
+    cs: ConstraintSystemRef<BWField>,
+    /// The path of each entered span and the number of constraints when it was entered
+    stack: Vec<(String, usize)>,
+    counts: BTreeMap<String, usize>,
+}
+
+impl Counting {
+    fn new(cs: ConstraintSystemRef<BWField>) -> Self {
+        Self {
+            cs,
+            stack: vec![],
+            counts: BTreeMap::new(),
+        }
+    }
+}
+
+thread_local! {
+    static COUNTING: RefCell<Option<Counting>> = RefCell::new(None);
+}
+
+/// Tracing layer which attributes the constraints added while a span is entered to the
+/// path of the entered spans
+struct GadgetCounter;
+
+impl<S> Layer<S> for GadgetCounter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let name = match ctx.span(id) {
+            Some(span) => span.name(),
+            None => return,
+        };
+        COUNTING.with(|counting| {
+            if let Some(counting) = counting.borrow_mut().as_mut() {
+                let path = match counting.stack.last() {
+                    Some((parent, _)) => format!("{}::{}", parent, name),
+                    None => name.to_owned(),
+                };
+                let num_constraints = counting.cs.num_constraints();
+                counting.stack.push((path, num_constraints));
+            }
+        });
+    }
+
+    fn on_exit(&self, _id: &Id, _ctx: Context<'_, S>) {
+        COUNTING.with(|counting| {
+            if let Some(counting) = counting.borrow_mut().as_mut() {
+                if let Some((path, entered_at)) = counting.stack.pop() {
+                    let added = counting.cs.num_constraints() - entered_at;
+                    if added > 0 {
+                        *counting.counts.entry(path).or_insert(0) += added;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints_circuits() {
+        let shape = CircuitShape::new(3, 2);
+        let fingerprint = circuit_fingerprint(shape).unwrap();
+        assert_eq!(circuit_fingerprint(shape).unwrap(), fingerprint);
+        assert!(!fingerprint.gadget_constraints.is_empty());
+        assert!(fingerprint
+            .gadget_constraints
+            .values()
+            .all(|count| *count <= fingerprint.num_constraints));
+
+        let other = circuit_fingerprint(CircuitShape::new(4, 2)).unwrap();
+        assert_ne!(other.circuit_hash, fingerprint.circuit_hash);
+
+        let path = std::env::temp_dir().join(format!(
+            "circuit_fingerprint_{}.txt",
+            hex::encode(fingerprint.circuit_hash)
+        ));
+        let _ = fs::remove_file(&path);
+        assert!(matches!(
+            fingerprint.check_snapshot(&path),
+            Err(FingerprintError::MissingSnapshot(_))
+        ));
+        fs::write(&path, fingerprint.to_string()).unwrap();
+        fingerprint.check_snapshot(&path).unwrap();
+        assert!(matches!(
+            other.check_snapshot(&path),
+            Err(FingerprintError::SnapshotMismatch { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+//! Insecure proving backend for end-to-end tests
+//!
+//! When the `insecure_test_backend` feature is enabled, `prove` skips synthesis entirely and
+//! returns a proof which is deterministically derived from the public inputs, and `verify`
+//! accepts such proofs. The proofs are well formed curve points, so they go through the same
+//! serialization paths as real ones, but they prove nothing. This feature MUST NOT be enabled
+//! in production builds.
+use super::{verifier::public_inputs, BWCurve, BWField};
+use crate::{epoch_block::EpochBlock, scalars::OuterScalar};
+use algebra::{
+    bw6_761::{G1Projective, G2Projective},
+    Field, One, ProjectiveCurve,
+};
+use groth16::Proof;
+use r1cs_core::SynthesisError;
+use tracing::warn;
+
+/// Returns the dummy proof for the transition between the provided epochs. `extra_inputs`
+/// are appended to the public inputs derived from the epochs.
+pub(crate) fn prove(
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+    extra_inputs: &[OuterScalar],
+) -> Result<Proof<BWCurve>, SynthesisError> {
+    warn!("generating an insecure dummy proof");
+    // an epoch which cannot be encoded can never satisfy the circuit
+    let mut inputs =
+        public_inputs(first_epoch, last_epoch).map_err(|_| SynthesisError::Unsatisfiable)?;
+    inputs.extend_from_slice(extra_inputs);
+    Ok(dummy_proof(&inputs))
+}
+
+/// Returns true if the proof is the dummy proof for the provided public inputs
+pub(crate) fn is_dummy_proof(proof: &Proof<BWCurve>, public_inputs: &[OuterScalar]) -> bool {
+    *proof == dummy_proof(public_inputs)
+}
+
+fn dummy_proof(public_inputs: &[OuterScalar]) -> Proof<BWCurve> {
+    let scalar = public_inputs
+        .iter()
+        .fold(BWField::one(), |acc, input| acc.double() + &input.0);
+    let a = G1Projective::prime_subgroup_generator()
+        .mul(scalar)
+        .into_affine();
+    Proof {
+        a,
+        b: G2Projective::prime_subgroup_generator().into_affine(),
+        c: a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::Zero;
+
+    #[test]
+    fn dummy_proof_is_bound_to_inputs() {
+        let (one, zero) = (OuterScalar(BWField::one()), OuterScalar(BWField::zero()));
+        let inputs = vec![one, zero];
+        let proof = dummy_proof(&inputs);
+        assert!(is_dummy_proof(&proof, &inputs));
+        assert!(!is_dummy_proof(&proof, &[zero, one]));
+        assert!(!is_dummy_proof(&Proof::default(), &inputs));
+    }
+}
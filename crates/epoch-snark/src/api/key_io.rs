@@ -0,0 +1,317 @@
+//! Parallel Key Deserialization
+//!
+//! The Groth16 parameters of the epochs circuit contain millions of BW6-761 points, and
+//! `CanonicalDeserialize` decompresses and checks them one after the other, which dominates a
+//! node's startup time. `read_parameters` and `read_verifying_key` read the same encoding,
+//! but only read the raw bytes of each vector of points serially and deserialize the points
+//! in parallel. `Parameters::write` and `Parameters::read` store and read the parameters of
+//! both circuits in a single file this way.
+//!
+//! Keys which have already been validated (e.g. right after a setup, or after a checked read)
+//! can be stored locally with `write_parameters_unchecked` and read back with
+//! `read_parameters_unchecked`. The points are stored uncompressed, and are read with
+//! `deserialize_unchecked`, which skips both the decompression and the curve and subgroup
+//! checks. Keys read this way are only as trustworthy as the storage they were read from, so
+//! this encoding must never be used for keys received from other parties.
+use super::setup::Parameters;
+
+use algebra::{
+    serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError},
+    AffineCurve, PairingEngine,
+};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use groth16::{Parameters as Groth16Parameters, VerifyingKey};
+use rayon::prelude::*;
+use std::io::{self, Read, Write};
+use tracing::info;
+
+type Result<T> = std::result::Result<T, SerializationError>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Validation {
+    /// Compressed points, decompressed and checked to be in the prime order subgroup
+    Checked,
+    /// Uncompressed points, trusted to be valid
+    Unchecked,
+}
+
+/// Reads Groth16 parameters serialized with `CanonicalSerialize`, deserializing their points
+/// in parallel
+pub fn read_parameters<E: PairingEngine, R: Read>(reader: R) -> Result<Groth16Parameters<E>> {
+    read_groth16_parameters(reader, Validation::Checked)
+}
+
+/// Reads a verifying key serialized with `CanonicalSerialize`, deserializing its points in
+/// parallel
+pub fn read_verifying_key<E: PairingEngine, R: Read>(reader: R) -> Result<VerifyingKey<E>> {
+    read_vk(reader, Validation::Checked)
+}
+
+/// Serializes Groth16 parameters with uncompressed points, to be read with
+/// `read_parameters_unchecked`
+pub fn write_parameters_unchecked<E: PairingEngine, W: Write>(
+    params: &Groth16Parameters<E>,
+    mut writer: W,
+) -> Result<()> {
+    write_vk_unchecked(&params.vk, &mut writer)?;
+    params.beta_g1.serialize_uncompressed(&mut writer)?;
+    params.delta_g1.serialize_uncompressed(&mut writer)?;
+    params.a_query.serialize_uncompressed(&mut writer)?;
+    params.b_g1_query.serialize_uncompressed(&mut writer)?;
+    params.b_g2_query.serialize_uncompressed(&mut writer)?;
+    params.h_query.serialize_uncompressed(&mut writer)?;
+    params.l_query.serialize_uncompressed(&mut writer)?;
+    Ok(())
+}
+
+/// Reads Groth16 parameters written with `write_parameters_unchecked`, without validating
+/// their points. See the module documentation.
+pub fn read_parameters_unchecked<E: PairingEngine, R: Read>(
+    reader: R,
+) -> Result<Groth16Parameters<E>> {
+    read_groth16_parameters(reader, Validation::Unchecked)
+}
+
+/// Serializes a verifying key with uncompressed points, to be read with
+/// `read_verifying_key_unchecked`
+pub fn write_verifying_key_unchecked<E: PairingEngine, W: Write>(
+    vk: &VerifyingKey<E>,
+    writer: W,
+) -> Result<()> {
+    write_vk_unchecked(vk, writer)
+}
+
+/// Reads a verifying key written with `write_verifying_key_unchecked`, without validating its
+/// points. See the module documentation.
+pub fn read_verifying_key_unchecked<E: PairingEngine, R: Read>(
+    reader: R,
+) -> Result<VerifyingKey<E>> {
+    read_vk(reader, Validation::Unchecked)
+}
+
+impl<CP: PairingEngine, BLS: PairingEngine> Parameters<CP, BLS> {
+    /// Serializes the epochs parameters, followed by a byte indicating whether the CRH->XOF
+    /// parameters are present and the parameters themselves, with `CanonicalSerialize`. The
+    /// parameters can be read back with `read`.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        self.epochs.serialize(&mut writer)?;
+        match self.hash_to_bits {
+            Some(ref hash_to_bits) => {
+                writer.write_u8(1)?;
+                hash_to_bits.serialize(&mut writer)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+        Ok(())
+    }
+
+    /// Reads parameters written with `write`, deserializing their points in parallel
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        info!("Reading parameters");
+        let epochs = read_parameters(&mut reader)?;
+        let hash_to_bits = match reader.read_u8()? {
+            0 => None,
+            _ => Some(read_parameters(&mut reader)?),
+        };
+        Ok(Parameters {
+            epochs,
+            hash_to_bits,
+        })
+    }
+
+    /// Serializes the epochs parameters, followed by a byte indicating whether the CRH->XOF
+    /// parameters are present and the parameters themselves, with uncompressed points. The
+    /// parameters can be read back with `read_unchecked`.
+    pub fn write_unchecked<W: Write>(&self, mut writer: W) -> Result<()> {
+        write_parameters_unchecked(&self.epochs, &mut writer)?;
+        match self.hash_to_bits {
+            Some(ref hash_to_bits) => {
+                writer.write_u8(1)?;
+                write_parameters_unchecked(hash_to_bits, &mut writer)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+        Ok(())
+    }
+
+    /// Reads parameters written with `write_unchecked`, without validating their points.
+    /// Only use it for parameters which were validated before being stored locally.
+    pub fn read_unchecked<R: Read>(mut reader: R) -> Result<Self> {
+        info!("Reading unchecked parameters");
+        let epochs = read_parameters_unchecked(&mut reader)?;
+        let hash_to_bits = match reader.read_u8()? {
+            0 => None,
+            _ => Some(read_parameters_unchecked(&mut reader)?),
+        };
+        Ok(Parameters {
+            epochs,
+            hash_to_bits,
+        })
+    }
+}
+
+fn write_vk_unchecked<E: PairingEngine, W: Write>(
+    vk: &VerifyingKey<E>,
+    mut writer: W,
+) -> Result<()> {
+    vk.alpha_g1.serialize_uncompressed(&mut writer)?;
+    vk.beta_g2.serialize_uncompressed(&mut writer)?;
+    vk.gamma_g2.serialize_uncompressed(&mut writer)?;
+    vk.delta_g2.serialize_uncompressed(&mut writer)?;
+    vk.gamma_abc_g1.serialize_uncompressed(&mut writer)?;
+    Ok(())
+}
+
+// The fields are read in the order in which they are declared, as `CanonicalSerialize`
+// serializes them
+fn read_vk<E: PairingEngine, R: Read>(
+    mut reader: R,
+    validation: Validation,
+) -> Result<VerifyingKey<E>> {
+    Ok(VerifyingKey {
+        alpha_g1: read_point(&mut reader, validation)?,
+        beta_g2: read_point(&mut reader, validation)?,
+        gamma_g2: read_point(&mut reader, validation)?,
+        delta_g2: read_point(&mut reader, validation)?,
+        gamma_abc_g1: read_points(&mut reader, validation)?,
+    })
+}
+
+fn read_groth16_parameters<E: PairingEngine, R: Read>(
+    mut reader: R,
+    validation: Validation,
+) -> Result<Groth16Parameters<E>> {
+    Ok(Groth16Parameters {
+        vk: read_vk(&mut reader, validation)?,
+        beta_g1: read_point(&mut reader, validation)?,
+        delta_g1: read_point(&mut reader, validation)?,
+        a_query: read_points(&mut reader, validation)?,
+        b_g1_query: read_points(&mut reader, validation)?,
+        b_g2_query: read_points(&mut reader, validation)?,
+        h_query: read_points(&mut reader, validation)?,
+        l_query: read_points(&mut reader, validation)?,
+    })
+}
+
+fn read_point<G: AffineCurve, R: Read>(reader: R, validation: Validation) -> Result<G> {
+    match validation {
+        Validation::Checked => G::deserialize(reader),
+        Validation::Unchecked => G::deserialize_unchecked(reader),
+    }
+}
+
+/// Reads a length-prefixed vector of points. Each point's encoding has a fixed size, so the
+/// bytes of all the points are read first and split between the threads.
+fn read_points<G: AffineCurve, R: Read>(mut reader: R, validation: Validation) -> Result<Vec<G>> {
+    let len = u64::deserialize(&mut reader)?;
+    let point_size = match validation {
+        Validation::Checked => G::default().serialized_size(),
+        Validation::Unchecked => G::default().uncompressed_size(),
+    };
+    let num_bytes = len
+        .checked_mul(point_size as u64)
+        .ok_or(SerializationError::InvalidData)?;
+
+    // the length is not trusted, so the buffer is only grown as the bytes are read
+    let mut bytes = vec![];
+    reader.take(num_bytes).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != num_bytes {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    bytes
+        .par_chunks(point_size)
+        .map(|chunk| read_point(chunk, validation))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{BLSCurve, BWCurve};
+    use algebra::{bls12_377, ProjectiveCurve, UniformRand};
+
+    fn points<G: ProjectiveCurve>(n: usize) -> Vec<G::Affine> {
+        let rng = &mut rand::thread_rng();
+        (0..n).map(|_| G::rand(rng).into_affine()).collect()
+    }
+
+    fn parameters() -> Groth16Parameters<BLSCurve> {
+        let g1 = |n| points::<bls12_377::G1Projective>(n);
+        let g2 = |n| points::<bls12_377::G2Projective>(n);
+        Groth16Parameters {
+            vk: VerifyingKey {
+                alpha_g1: g1(1)[0],
+                beta_g2: g2(1)[0],
+                gamma_g2: g2(1)[0],
+                delta_g2: g2(1)[0],
+                gamma_abc_g1: g1(3),
+            },
+            beta_g1: g1(1)[0],
+            delta_g1: g1(1)[0],
+            a_query: g1(5),
+            b_g1_query: g1(5),
+            b_g2_query: g2(5),
+            h_query: g1(4),
+            l_query: g1(2),
+        }
+    }
+
+    #[test]
+    fn reads_canonical_encoding() {
+        let params = parameters();
+        let mut bytes = vec![];
+        params.serialize(&mut bytes).unwrap();
+        assert_eq!(read_parameters::<BLSCurve, _>(&bytes[..]).unwrap(), params);
+
+        let mut vk_bytes = vec![];
+        params.vk.serialize(&mut vk_bytes).unwrap();
+        assert_eq!(
+            read_verifying_key::<BLSCurve, _>(&vk_bytes[..]).unwrap(),
+            params.vk
+        );
+
+        // truncated and corrupted encodings are rejected
+        assert!(read_parameters::<BLSCurve, _>(&bytes[..bytes.len() - 1]).is_err());
+        let len = vk_bytes.len();
+        vk_bytes[len - 1] ^= 1;
+        assert!(read_verifying_key::<BLSCurve, _>(&vk_bytes[..]).is_err());
+    }
+
+    #[test]
+    fn unchecked_roundtrip() {
+        let params = parameters();
+        let mut bytes = vec![];
+        write_parameters_unchecked(&params, &mut bytes).unwrap();
+        assert_eq!(
+            read_parameters_unchecked::<BLSCurve, _>(&bytes[..]).unwrap(),
+            params
+        );
+
+        let mut vk_bytes = vec![];
+        write_verifying_key_unchecked(&params.vk, &mut vk_bytes).unwrap();
+        assert_eq!(
+            read_verifying_key_unchecked::<BLSCurve, _>(&vk_bytes[..]).unwrap(),
+            params.vk
+        );
+
+        let combined = Parameters::<BLSCurve, BLSCurve> {
+            epochs: params.clone(),
+            hash_to_bits: Some(params),
+        };
+        let mut bytes = vec![];
+        combined.write_unchecked(&mut bytes).unwrap();
+        let read = Parameters::<BLSCurve, BLSCurve>::read_unchecked(&bytes[..]).unwrap();
+        assert_eq!(read.epochs, combined.epochs);
+        assert_eq!(read.hash_to_bits, combined.hash_to_bits);
+
+        // BW6-761 points have a different size
+        let vk = VerifyingKey::<BWCurve>::default();
+        let mut vk_bytes = vec![];
+        write_verifying_key_unchecked(&vk, &mut vk_bytes).unwrap();
+        assert_eq!(
+            read_verifying_key_unchecked::<BWCurve, _>(&vk_bytes[..]).unwrap(),
+            vk
+        );
+    }
+}
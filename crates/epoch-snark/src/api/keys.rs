@@ -0,0 +1,203 @@
+//! Combined Verifying Keys
+//!
+//! With the 2-SNARK technique, the CRH->XOF hashes are proven in BLS12-377 and that proof is
+//! verified inside the epochs circuit, against a verifying key which is a constant of the
+//! circuit. The epochs verifying key is therefore only valid together with the CRH->XOF
+//! verifying key of the same setup. A `CombinedVerifyingKey` can only be created from a
+//! setup's parameters (or deserialized from one), so that the two keys are kept together.
+use super::{
+    bundle::ProofBundle,
+    key_io::{read_verifying_key, read_verifying_key_unchecked, write_verifying_key_unchecked},
+    setup::Parameters,
+    verifier, BLSCurve, BWCurve, VerificationError,
+};
+use crate::encoding::EncodingError;
+
+use algebra::serialize::CanonicalSerialize;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use groth16::VerifyingKey;
+use std::io::{Read, Write};
+
+/// The verifying keys of the epochs circuit and of the CRH->XOF circuit, if any, of a setup
+#[derive(Clone, Debug, PartialEq)]
+pub struct CombinedVerifyingKey {
+    epoch_vk: VerifyingKey<BWCurve>,
+    hash_vk: Option<VerifyingKey<BLSCurve>>,
+}
+
+impl CombinedVerifyingKey {
+    /// The verifying keys of the parameters
+    pub fn from_parameters(parameters: &Parameters<BWCurve, BLSCurve>) -> Self {
+        Self {
+            epoch_vk: parameters.epochs.vk.clone(),
+            hash_vk: parameters
+                .hash_to_bits
+                .as_ref()
+                .map(|params| params.vk.clone()),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new(
+        epoch_vk: VerifyingKey<BWCurve>,
+        hash_vk: Option<VerifyingKey<BLSCurve>>,
+    ) -> Self {
+        Self { epoch_vk, hash_vk }
+    }
+
+    /// The verifying key of the epochs circuit
+    pub fn epoch_vk(&self) -> &VerifyingKey<BWCurve> {
+        &self.epoch_vk
+    }
+
+    /// The verifying key of the CRH->XOF circuit, if the hashes are proven in BLS12-377
+    pub fn hash_vk(&self) -> Option<&VerifyingKey<BLSCurve>> {
+        self.hash_vk.as_ref()
+    }
+
+    /// Verifies the bundle's proof. The bundle must have been generated with the same setup,
+    /// i.e. with the same epochs verifying key and hashing mode. The CRH->XOF proof, if any,
+    /// is verified as part of the epochs proof.
+    pub fn verify(&self, bundle: &ProofBundle) -> Result<(), VerificationError> {
+        if bundle.hashes_in_bls12_377 != self.hash_vk.is_some() {
+            return Err(VerificationError::HashingModeMismatch);
+        }
+        if bundle.vk != self.epoch_vk {
+            return Err(VerificationError::VerifyingKeyMismatch);
+        }
+        verifier::verify(
+            &self.epoch_vk,
+            &bundle.first_epoch,
+            bundle.last_epoch(),
+            &bundle.proof,
+        )
+    }
+
+    /// Serializes the epochs verifying key, followed by a byte indicating whether the
+    /// CRH->XOF verifying key is present and the key itself
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), EncodingError> {
+        self.epoch_vk.serialize(&mut writer)?;
+        match self.hash_vk {
+            Some(ref hash_vk) => {
+                writer.write_u8(1)?;
+                hash_vk.serialize(&mut writer)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+        Ok(())
+    }
+
+    /// Deserializes keys written with `write`
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, EncodingError> {
+        let epoch_vk = read_verifying_key(&mut reader)?;
+        let hash_vk = match reader.read_u8()? {
+            0 => None,
+            _ => Some(read_verifying_key(&mut reader)?),
+        };
+        Ok(Self { epoch_vk, hash_vk })
+    }
+
+    /// Serializes the keys as in `write`, but with uncompressed points, so that keys which
+    /// were already validated can be stored locally and read back with `read_unchecked`
+    pub fn write_unchecked<W: Write>(&self, mut writer: W) -> Result<(), EncodingError> {
+        write_verifying_key_unchecked(&self.epoch_vk, &mut writer)?;
+        match self.hash_vk {
+            Some(ref hash_vk) => {
+                writer.write_u8(1)?;
+                write_verifying_key_unchecked(hash_vk, &mut writer)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+        Ok(())
+    }
+
+    /// Deserializes keys written with `write_unchecked`, without checking that their points
+    /// are valid. Must only be used for keys read from trusted local storage.
+    pub fn read_unchecked<R: Read>(mut reader: R) -> Result<Self, EncodingError> {
+        let epoch_vk = read_verifying_key_unchecked(&mut reader)?;
+        let hash_vk = match reader.read_u8()? {
+            0 => None,
+            _ => Some(read_verifying_key_unchecked(&mut reader)?),
+        };
+        Ok(Self { epoch_vk, hash_vk })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{CIRCUIT_FEATURES, CIRCUIT_VERSION},
+        epoch_block::EpochBlock,
+    };
+    use algebra::{bls12_377::G2Projective, ProjectiveCurve};
+    use bls_crypto::PublicKey;
+    use groth16::Proof;
+
+    fn bundle(hashes_in_bls12_377: bool) -> ProofBundle {
+        ProofBundle {
+            proof: Proof::default(),
+            vk: VerifyingKey::default(),
+            hashes_in_bls12_377,
+            circuit_version: CIRCUIT_VERSION,
+            circuit_features: CIRCUIT_FEATURES,
+            first_epoch: EpochBlock::new(
+                0,
+                0,
+                None,
+                None,
+                0,
+                1,
+                vec![PublicKey::from(G2Projective::prime_subgroup_generator())],
+            ),
+            transitions: vec![],
+        }
+    }
+
+    #[test]
+    fn rejects_bundles_from_other_setups() {
+        let keys = CombinedVerifyingKey {
+            epoch_vk: VerifyingKey::default(),
+            hash_vk: Some(VerifyingKey::default()),
+        };
+
+        assert!(matches!(
+            keys.verify(&bundle(false)),
+            Err(VerificationError::HashingModeMismatch)
+        ));
+
+        let mut other_vk = bundle(true);
+        other_vk.vk.gamma_abc_g1.push(Default::default());
+        assert!(matches!(
+            keys.verify(&other_vk),
+            Err(VerificationError::VerifyingKeyMismatch)
+        ));
+
+        // matching keys proceed to verifying the (invalid) proof
+        assert!(!matches!(
+            keys.verify(&bundle(true)),
+            Err(VerificationError::HashingModeMismatch)
+                | Err(VerificationError::VerifyingKeyMismatch)
+        ));
+    }
+
+    #[test]
+    fn keys_roundtrip() {
+        for hash_vk in vec![None, Some(VerifyingKey::default())] {
+            let keys = CombinedVerifyingKey {
+                epoch_vk: VerifyingKey::default(),
+                hash_vk,
+            };
+            let mut bytes = vec![];
+            keys.write(&mut bytes).unwrap();
+            assert_eq!(CombinedVerifyingKey::read(&bytes[..]).unwrap(), keys);
+
+            let mut bytes = vec![];
+            keys.write_unchecked(&mut bytes).unwrap();
+            assert_eq!(
+                CombinedVerifyingKey::read_unchecked(&bytes[..]).unwrap(),
+                keys
+            );
+        }
+    }
+}
@@ -0,0 +1,419 @@
+//! Parameter Integrity Manifest
+//!
+//! Records what a set of public parameters was generated for: the curves, the shape of the
+//! circuit, whether the CRH->XOF hashes are proven in BLS12-377, a hash of the epochs
+//! circuit's constraint matrices and digests of the serialized parameters. Checking the
+//! parameters against their manifest when loading them catches proving with keys for a
+//! different validator count, epoch count or circuit version, which would otherwise only
+//! surface as proofs which fail to verify.
+//!
+//! The manifest is a text file of `key = value` lines, e.g.
+//!
+//! ```text
+//! version = 1
+//! curve = BW6_761/BLS12_377
+//! num_validators = 100
+//! num_epochs = 120
+//! maximum_non_signers = 33
+//! hashes_in_bls12_377 = true
+//! circuit_hash = 5c0f...
+//! epochs_digest = 81d2...
+//! hash_to_bits_digest = 0a3e...
+//! ```
+use super::{registry, registry::CircuitShape, setup::Parameters, BLSCurve, BWCurve, BWField};
+
+use algebra::{
+    serialize::{CanonicalSerialize, SerializationError},
+    ToBytes,
+};
+use blake2s_simd::{Params, State};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use byteorder::{LittleEndian, WriteBytesExt};
+use r1cs_core::{ConstraintMatrices, SynthesisError};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+use thiserror::Error;
+use tracing::info;
+
+/// The version of the manifest format
+const MANIFEST_VERSION: u8 = 1;
+/// The curves of the epochs and of the CRH->XOF circuits
+const CURVE: &str = "BW6_761/BLS12_377";
+/// Personalization of the digests
+const MANIFEST_DOMAIN: &[u8] = b"ULformft";
+
+#[derive(Debug, Error)]
+/// Error raised while creating, reading or checking a parameters manifest
+pub enum ManifestError {
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Zexe Error: {0}")]
+    ZexeSerialization(#[from] SerializationError),
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("malformed manifest: {0}")]
+    Malformed(String),
+    #[error("unsupported manifest version {0}")]
+    UnsupportedVersion(u8),
+    #[error("the manifest is for curve {0}, expected {}", CURVE)]
+    CurveMismatch(String),
+    #[error("the manifest has hashes_in_bls12_377 = {0}, which the parameters do not match")]
+    HashingModeMismatch(bool),
+    #[error("the {0} parameters do not match their digest in the manifest")]
+    DigestMismatch(&'static str),
+    #[error("the circuit does not match the one the parameters were generated for")]
+    CircuitMismatch,
+}
+
+impl ToErrorCode for ManifestError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ManifestError::IoError(_) => ErrorCode::Io,
+            ManifestError::ZexeSerialization(_) => ErrorCode::Serialization,
+            ManifestError::ZexeSynthesisError(e) => e.error_code(),
+            _ => ErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// Description of a set of public parameters, see the module documentation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParametersManifest {
+    /// The shape of the epochs circuit
+    pub shape: CircuitShape,
+    /// Whether the CRH->XOF hashes are proven in a separate BLS12-377 circuit
+    pub hashes_in_bls12_377: bool,
+    /// Blake2s hash of the epochs circuit's constraint matrices
+    pub circuit_hash: [u8; 32],
+    /// Blake2s hash of the serialized epochs circuit parameters
+    pub epochs_digest: [u8; 32],
+    /// Blake2s hash of the serialized CRH->XOF circuit parameters, if any
+    pub hash_to_bits_digest: Option<[u8; 32]>,
+}
+
+impl Parameters<BWCurve, BLSCurve> {
+    /// Creates the manifest of parameters which were generated for the provided shape.
+    /// This synthesizes the circuit, so it takes about as long as a setup without the
+    /// multi-scalar multiplications.
+    pub fn manifest(&self, shape: CircuitShape) -> Result<ParametersManifest, ManifestError> {
+        let hash_to_bits_digest = match self.hash_to_bits {
+            Some(ref params) => Some(digest(params)?),
+            None => None,
+        };
+        Ok(ParametersManifest {
+            shape,
+            hashes_in_bls12_377: self.hash_to_bits.is_some(),
+            circuit_hash: self.circuit_hash(shape)?,
+            epochs_digest: digest(&self.epochs)?,
+            hash_to_bits_digest,
+        })
+    }
+
+    /// Checks that the parameters are the ones described by the manifest, and that the
+    /// current circuit for the manifest's shape is the one they were generated for
+    pub fn verify_manifest(&self, manifest: &ParametersManifest) -> Result<(), ManifestError> {
+        info!(
+            "Verifying parameters against manifest for {:?}",
+            manifest.shape
+        );
+        self.verify_digests(manifest)?;
+        if self.circuit_hash(manifest.shape)? != manifest.circuit_hash {
+            return Err(ManifestError::CircuitMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks that the parameters are the ones described by the manifest, without
+    /// synthesizing the circuit. This is enough for a pinned manifest which was already
+    /// checked with `verify_manifest` against the circuit of this crate's version.
+    pub fn verify_digests(&self, manifest: &ParametersManifest) -> Result<(), ManifestError> {
+        if manifest.hashes_in_bls12_377 != self.hash_to_bits.is_some() {
+            return Err(ManifestError::HashingModeMismatch(
+                manifest.hashes_in_bls12_377,
+            ));
+        }
+        if digest(&self.epochs)? != manifest.epochs_digest {
+            return Err(ManifestError::DigestMismatch("epochs"));
+        }
+        match (&self.hash_to_bits, manifest.hash_to_bits_digest) {
+            (Some(params), Some(expected)) => {
+                if digest(params)? != expected {
+                    return Err(ManifestError::DigestMismatch("hash to bits"));
+                }
+            }
+            (None, None) => {}
+            // the hashing mode matched, so the manifest itself is inconsistent
+            _ => return Err(missing_digest(manifest.hashes_in_bls12_377)),
+        }
+        Ok(())
+    }
+
+    fn circuit_hash(&self, shape: CircuitShape) -> Result<[u8; 32], ManifestError> {
+        let vk = self.hash_to_bits.as_ref().map(|params| params.vk.clone());
+        let matrices = registry::synthesize(shape, vk)?;
+        Ok(hash_matrices(&matrices)?)
+    }
+}
+
+impl ParametersManifest {
+    /// Writes the manifest in its text format
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), ManifestError> {
+        write!(writer, "{}", self)?;
+        Ok(())
+    }
+
+    /// Reads a manifest which was written with `write`
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, ManifestError> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        contents.parse()
+    }
+}
+
+impl fmt::Display for ParametersManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "version = {}", MANIFEST_VERSION)?;
+        writeln!(f, "curve = {}", CURVE)?;
+        writeln!(f, "num_validators = {}", self.shape.num_validators)?;
+        writeln!(f, "num_epochs = {}", self.shape.num_epochs)?;
+        writeln!(
+            f,
+            "maximum_non_signers = {}",
+            self.shape.maximum_non_signers
+        )?;
+        writeln!(f, "hashes_in_bls12_377 = {}", self.hashes_in_bls12_377)?;
+        writeln!(f, "circuit_hash = {}", hex::encode(self.circuit_hash))?;
+        writeln!(f, "epochs_digest = {}", hex::encode(self.epochs_digest))?;
+        match self.hash_to_bits_digest {
+            Some(digest) => writeln!(f, "hash_to_bits_digest = {}", hex::encode(digest)),
+            None => writeln!(f, "hash_to_bits_digest = none"),
+        }
+    }
+}
+
+impl FromStr for ParametersManifest {
+    type Err = ManifestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let mut field = |key| next_field(&mut lines, key);
+
+        let version = parse(field("version")?)?;
+        if version != MANIFEST_VERSION {
+            return Err(ManifestError::UnsupportedVersion(version));
+        }
+        let curve = field("curve")?;
+        if curve != CURVE {
+            return Err(ManifestError::CurveMismatch(curve.to_owned()));
+        }
+        let shape = CircuitShape {
+            num_validators: parse(field("num_validators")?)?,
+            num_epochs: parse(field("num_epochs")?)?,
+            maximum_non_signers: parse(field("maximum_non_signers")?)?,
+        };
+        let hashes_in_bls12_377 = parse(field("hashes_in_bls12_377")?)?;
+        let circuit_hash = parse_digest(field("circuit_hash")?)?;
+        let epochs_digest = parse_digest(field("epochs_digest")?)?;
+        let hash_to_bits_digest = match field("hash_to_bits_digest")? {
+            "none" => None,
+            digest => Some(parse_digest(digest)?),
+        };
+        if hashes_in_bls12_377 != hash_to_bits_digest.is_some() {
+            return Err(missing_digest(hashes_in_bls12_377));
+        }
+
+        Ok(ParametersManifest {
+            shape,
+            hashes_in_bls12_377,
+            circuit_hash,
+            epochs_digest,
+            hash_to_bits_digest,
+        })
+    }
+}
+
+/// Returns the value of the next line, which must be for `key`
+fn next_field<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    key: &str,
+) -> Result<&'a str, ManifestError> {
+    let line = lines
+        .next()
+        .ok_or_else(|| ManifestError::Malformed(format!("missing `{}`", key)))?;
+    match line.splitn(2, '=').map(str::trim).collect::<Vec<_>>()[..] {
+        [k, value] if k == key => Ok(value),
+        _ => Err(ManifestError::Malformed(format!(
+            "expected `{}`, got `{}`",
+            key, line
+        ))),
+    }
+}
+
+fn missing_digest(hashes_in_bls12_377: bool) -> ManifestError {
+    ManifestError::Malformed(format!(
+        "hashes_in_bls12_377 = {} does not match the hash_to_bits_digest",
+        hashes_in_bls12_377
+    ))
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, ManifestError> {
+    value
+        .parse()
+        .map_err(|_| ManifestError::Malformed(format!("invalid value `{}`", value)))
+}
+
+fn parse_digest(value: &str) -> Result<[u8; 32], ManifestError> {
+    let bytes = hex::decode(value)
+        .map_err(|_| ManifestError::Malformed(format!("invalid digest `{}`", value)))?;
+    if bytes.len() != 32 {
+        return Err(ManifestError::Malformed(format!(
+            "digest must be 32 bytes, got {}",
+            bytes.len()
+        )));
+    }
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&bytes);
+    Ok(digest)
+}
+
+fn hasher() -> State {
+    Params::new()
+        .hash_length(32)
+        .personal(MANIFEST_DOMAIN)
+        .to_state()
+}
+
+fn finalize(state: &State) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(state.finalize().as_ref());
+    hash
+}
+
+/// Hashes the serialized element without buffering it in memory
+pub(super) fn digest<T: CanonicalSerialize>(element: &T) -> Result<[u8; 32], SerializationError> {
+    let mut state = hasher();
+    element.serialize(&mut state)?;
+    Ok(finalize(&state))
+}
+
+/// Hashes the dimensions of the matrices and every `(coefficient, variable)` entry of
+/// their rows
+pub(super) fn hash_matrices(matrices: &ConstraintMatrices<BWField>) -> io::Result<[u8; 32]> {
+    let mut state = hasher();
+    for dimension in &[
+        matrices.num_instance_variables,
+        matrices.num_witness_variables,
+        matrices.num_constraints,
+    ] {
+        state.write_u64::<LittleEndian>(*dimension as u64)?;
+    }
+    for matrix in &[&matrices.a, &matrices.b, &matrices.c] {
+        for row in matrix.iter() {
+            state.write_u64::<LittleEndian>(row.len() as u64)?;
+            for (coeff, index) in row {
+                coeff.write(&mut state)?;
+                state.write_u64::<LittleEndian>(*index as u64)?;
+            }
+        }
+    }
+    Ok(finalize(&state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::trusted_setup;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn verifies_matching_parameters() {
+        let rng = &mut XorShiftRng::seed_from_u64(0);
+        let shape = CircuitShape::new(3, 2);
+        let params = trusted_setup(3, 2, shape.maximum_non_signers, rng, false).unwrap();
+
+        let manifest = params.manifest(shape).unwrap();
+        assert!(!manifest.hashes_in_bls12_377);
+        params.verify_manifest(&manifest).unwrap();
+
+        let mut bytes = vec![];
+        manifest.write(&mut bytes).unwrap();
+        assert_eq!(ParametersManifest::read(&bytes[..]).unwrap(), manifest);
+
+        // parameters for another validator count
+        let other = trusted_setup(4, 2, shape.maximum_non_signers, rng, false).unwrap();
+        assert!(matches!(
+            other.verify_manifest(&manifest),
+            Err(ManifestError::DigestMismatch("epochs"))
+        ));
+
+        // a manifest claiming another shape for the same parameters
+        let mut wrong_shape = manifest.clone();
+        wrong_shape.shape = CircuitShape::new(4, 2);
+        assert!(matches!(
+            params.verify_manifest(&wrong_shape),
+            Err(ManifestError::CircuitMismatch)
+        ));
+
+        let mut wrong_mode = manifest.clone();
+        wrong_mode.hashes_in_bls12_377 = true;
+        assert!(matches!(
+            params.verify_manifest(&wrong_mode),
+            Err(ManifestError::HashingModeMismatch(true))
+        ));
+
+        // a digest for the BLS12-377 parameters is required whenever they are hashed
+        let params = trusted_setup(3, 2, shape.maximum_non_signers, rng, true).unwrap();
+        let mut manifest = params.manifest(shape).unwrap();
+        params.verify_digests(&manifest).unwrap();
+        manifest.hash_to_bits_digest = None;
+        assert!(matches!(
+            params.verify_digests(&manifest),
+            Err(ManifestError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_manifests() {
+        let manifest = ParametersManifest {
+            shape: CircuitShape::new(3, 2),
+            hashes_in_bls12_377: true,
+            circuit_hash: [1; 32],
+            epochs_digest: [2; 32],
+            hash_to_bits_digest: Some([3; 32]),
+        };
+        let text = manifest.to_string();
+        assert_eq!(text.parse::<ParametersManifest>().unwrap(), manifest);
+
+        assert!(matches!(
+            text.replace("version = 1", "version = 2")
+                .parse::<ParametersManifest>(),
+            Err(ManifestError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            text.replace(CURVE, "BN254").parse::<ParametersManifest>(),
+            Err(ManifestError::CurveMismatch(_))
+        ));
+        assert!(matches!(
+            text.replace("num_epochs", "epochs")
+                .parse::<ParametersManifest>(),
+            Err(ManifestError::Malformed(_))
+        ));
+        assert!(matches!(
+            text.replace(&hex::encode([3; 32]), "none")
+                .parse::<ParametersManifest>(),
+            Err(ManifestError::Malformed(_))
+        ));
+        assert!(matches!(
+            text.replace(&hex::encode([2; 32]), "0202")
+                .parse::<ParametersManifest>(),
+            Err(ManifestError::Malformed(_))
+        ));
+    }
+}
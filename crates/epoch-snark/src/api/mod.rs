@@ -1,11 +1,100 @@
+#[cfg(feature = "proof-archive")]
+mod archive;
+#[cfg(feature = "proof-archive")]
+pub use archive::{ArchiveError, ProofArchive, ProofChainBundle};
+
+mod bundle;
+pub use bundle::{EpochInspection, ProofBundle, ProofInspection};
+
+mod compatibility;
+pub use compatibility::{CompatibilityError, HashMode};
+
+mod config;
+pub use config::ProverConfig;
+
+mod erasure;
+
+pub mod export;
+
+mod fingerprint;
+pub use fingerprint::{circuit_fingerprint, CircuitFingerprint, FingerprintError};
+
+mod insecure;
+
+mod manifest;
+pub use manifest::{ManifestError, ParametersManifest};
+
+mod params_source;
+
+mod qap;
+pub use params_source::{LazyParameters, ParamsSource, ParamsSourceError};
+
 mod prover;
-pub use prover::prove;
+pub use prover::{
+    epochs_circuit, prove, prove_check_only, prove_from_provider, prove_with_config,
+    prove_with_witness_spill, ProverError,
+};
+pub(crate) use prover::{feature_public_inputs, to_epoch_data};
+
+#[cfg(feature = "remote-prover")]
+mod remote;
+#[cfg(feature = "remote-prover")]
+pub use remote::{prove_remote, serve_remote_proof, RemoteProgress, RemoteProverError};
+
+mod spill;
+pub use spill::WitnessSpillError;
+
+mod encryption;
+pub use encryption::EncryptionError;
+
+mod keys;
+pub use keys::CombinedVerifyingKey;
+
+mod key_io;
+pub use key_io::{
+    read_parameters, read_parameters_unchecked, read_verifying_key, read_verifying_key_unchecked,
+    write_parameters_unchecked, write_verifying_key_unchecked,
+};
+
+pub mod registry;
+
+mod rewards;
+pub use rewards::{
+    prove_reward_attestation, reward_attestation_public_inputs, reward_attestation_setup,
+    verify_reward_attestation,
+};
 
 mod setup;
-pub use setup::{trusted_setup, Parameters};
+pub use setup::{
+    trusted_setup, trusted_setup_for_shapes, trusted_setup_from_snapshot,
+    trusted_setup_with_config, Parameters,
+};
+
+mod rotation;
+pub use rotation::{
+    key_rotation_public_inputs, key_rotation_setup, prove_key_rotations, verify_key_rotations,
+};
+
+mod slashing;
+pub use slashing::{
+    double_signing_public_inputs, double_signing_setup, prove_double_signing, verify_double_signing,
+};
+
+mod snapshot;
+pub use snapshot::{CircuitSnapshot, SnapshotCircuit};
+
+mod vk_registry;
+pub use vk_registry::{VkId, VkRegistry, VkRegistryError, CIRCUIT_FEATURES, CIRCUIT_VERSION};
 
 mod verifier;
-pub use verifier::{verify, VerificationError};
+#[cfg(feature = "blinded-validators")]
+pub use verifier::verify_with_validator_commitment;
+pub use verifier::{
+    public_input_bytes, public_inputs, verify, verify_batch, verify_proof_chain,
+    verify_with_aux_data_commitment, verify_with_bitmap_commitment, verify_with_deadline,
+    verify_with_extra_inputs, verify_with_randomness_beacon, LinkMismatch, ProofChainError,
+    VerificationError, VerificationStage,
+};
 
 // Instantiate certain types to avoid confusion
 use algebra::{bls12_377, bw6_761};
@@ -15,3 +104,27 @@ pub type BLSCurveG2 = bls12_377::G2Projective;
 pub type BWField = bw6_761::Fr;
 pub type BWCurve = bw6_761::BW6_761;
 pub type BWFrParams = bw6_761::FrParameters;
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use super::BWField;
+    use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, fields::FieldVar};
+
+    /// Proves knowledge of the cubes' roots
+    pub struct Cubes(pub Vec<BWField>);
+
+    impl ConstraintSynthesizer<BWField> for Cubes {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<BWField>,
+        ) -> Result<(), SynthesisError> {
+            for root in self.0 {
+                let cube = FpVar::new_input(cs.clone(), || Ok(root * &root * &root))?;
+                let root = FpVar::new_witness(cs.clone(), || Ok(root))?;
+                root.square()?.mul(&root).enforce_equal(&cube)?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Parameter Sources
+//!
+//! The parameters of the epochs circuit weigh gigabytes, so applications rarely ship them
+//! along with their binary. A `ParamsSource` describes where to load them from:
+//!
+//! - a local file
+//! - bytes embedded in the binary, e.g. with `include_bytes!` for small circuits
+//! - with the `params-download` feature, a URL (e.g. of a CDN) along with the digest of the
+//!   file, which is checked before the file is used, and which names the file in the local
+//!   cache so that it is only downloaded once
+//!
+//! The files contain parameters written with `Parameters::write`. Whatever the source, the
+//! parameters are checked against a pinned `ParametersManifest` with
+//! `Parameters::verify_digests` once loaded. `LazyParameters` loads them on first use, so
+//! that applications only pay for the download and the deserialization when they need them:
+//!
+//! ```ignore
+//! static PARAMS: Lazy<LazyParameters> = Lazy::new(|| {
+//!     LazyParameters::new(
+//!         ParamsSource::Url {
+//!             url: "https://example.com/epochs-100.params".to_owned(),
+//!             digest: PINNED_DIGEST,
+//!         },
+//!         PINNED_MANIFEST.parse().unwrap(),
+//!     )
+//!     .with_cache_dir("/var/cache/epoch-snark")
+//! });
+//!
+//! let proof = prove(PARAMS.get()?, num_validators, &first_epoch, &transitions, max_transitions)?;
+//! ```
+use super::{setup::Parameters, BLSCurve, BWCurve, ManifestError, ParametersManifest};
+
+use algebra::serialize::SerializationError;
+use blake2s_simd::Params;
+use bls_crypto::{ErrorCode, ToErrorCode};
+use once_cell::sync::OnceCell;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use tracing::info;
+#[cfg(feature = "params-download")]
+use {
+    std::io::{Seek, SeekFrom},
+    tempfile::NamedTempFile,
+    tracing::warn,
+};
+
+#[derive(Debug, Error)]
+/// Error raised while loading parameters from a `ParamsSource`
+pub enum ParamsSourceError {
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Zexe Error: {0}")]
+    ZexeSerialization(#[from] SerializationError),
+    #[error("Manifest Error: {0}")]
+    Manifest(#[from] ManifestError),
+    #[cfg(feature = "params-download")]
+    #[error("HTTP Error: {0}")]
+    Http(#[from] Box<ureq::Error>),
+    #[error("the parameters downloaded from {0} do not match their digest")]
+    DigestMismatch(String),
+}
+
+impl ToErrorCode for ParamsSourceError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ParamsSourceError::IoError(_) => ErrorCode::Io,
+            #[cfg(feature = "params-download")]
+            ParamsSourceError::Http(_) => ErrorCode::Io,
+            ParamsSourceError::ZexeSerialization(_) => ErrorCode::Serialization,
+            ParamsSourceError::Manifest(e) => e.error_code(),
+            ParamsSourceError::DigestMismatch(_) => ErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// Where to load parameters from, see the module documentation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParamsSource {
+    /// A file written with `Parameters::write`
+    File(PathBuf),
+    /// The contents of such a file
+    Embedded(&'static [u8]),
+    /// A file to download, whose `file_digest` must be `digest`
+    #[cfg(feature = "params-download")]
+    Url { url: String, digest: [u8; 32] },
+}
+
+impl ParamsSource {
+    /// Loads the parameters and checks them against the manifest. Downloaded files are
+    /// cached in `cache_dir`, if provided, and are only downloaded again if the cached file
+    /// is missing or corrupted.
+    #[cfg_attr(not(feature = "params-download"), allow(unused_variables))]
+    pub fn load(
+        &self,
+        manifest: &ParametersManifest,
+        cache_dir: Option<&Path>,
+    ) -> Result<Parameters<BWCurve, BLSCurve>, ParamsSourceError> {
+        let params = match self {
+            ParamsSource::File(path) => {
+                info!("Loading parameters from {}", path.display());
+                Parameters::read(BufReader::new(File::open(path)?))?
+            }
+            ParamsSource::Embedded(bytes) => Parameters::read(*bytes)?,
+            #[cfg(feature = "params-download")]
+            ParamsSource::Url { url, digest } => {
+                Parameters::read(BufReader::new(fetch(url, digest, cache_dir)?))?
+            }
+        };
+        params.verify_digests(manifest)?;
+        Ok(params)
+    }
+
+    /// The Blake2s-256 hash of a parameters file, which pins the files to download
+    pub fn file_digest<R: Read>(mut reader: R) -> io::Result<[u8; 32]> {
+        let mut state = Params::new().hash_length(32).to_state();
+        io::copy(&mut reader, &mut state)?;
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(state.finalize().as_ref());
+        Ok(digest)
+    }
+}
+
+/// Returns the cached file with the digest, or downloads it to the cache. Without a cache
+/// directory, the file is downloaded to an anonymous temporary file.
+#[cfg(feature = "params-download")]
+fn fetch(
+    url: &str,
+    digest: &[u8; 32],
+    cache_dir: Option<&Path>,
+) -> Result<File, ParamsSourceError> {
+    let dir = match cache_dir {
+        Some(dir) => dir,
+        None => {
+            let mut file = tempfile::tempfile()?;
+            download(url, digest, &mut file)?;
+            return Ok(file);
+        }
+    };
+
+    let path = dir.join(format!("{}.params", hex::encode(digest)));
+    if let Ok(mut file) = File::open(&path) {
+        if ParamsSource::file_digest(BufReader::new(&mut file))? == *digest {
+            info!("Using cached parameters {}", path.display());
+            file.seek(SeekFrom::Start(0))?;
+            return Ok(file);
+        }
+        warn!("Replacing corrupted cached parameters {}", path.display());
+    }
+
+    // the file is only moved to its path in the cache once complete and checked
+    let mut temp = NamedTempFile::new_in(dir)?;
+    download(url, digest, temp.as_file_mut())?;
+    Ok(temp.persist(&path).map_err(|e| e.error)?)
+}
+
+/// Downloads the file to `file`, checks its digest and rewinds it
+#[cfg(feature = "params-download")]
+fn download(url: &str, digest: &[u8; 32], file: &mut File) -> Result<(), ParamsSourceError> {
+    info!("Downloading parameters from {}", url);
+    let response = ureq::get(url).call().map_err(Box::new)?;
+    io::copy(&mut response.into_reader(), file)?;
+    file.seek(SeekFrom::Start(0))?;
+    if ParamsSource::file_digest(BufReader::new(&mut *file))? != *digest {
+        return Err(ParamsSourceError::DigestMismatch(url.to_owned()));
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok(())
+}
+
+/// Parameters which are loaded from their source on first use
+pub struct LazyParameters {
+    source: ParamsSource,
+    manifest: ParametersManifest,
+    cache_dir: Option<PathBuf>,
+    params: OnceCell<Parameters<BWCurve, BLSCurve>>,
+}
+
+impl LazyParameters {
+    /// Parameters to load from the source and to check against the manifest
+    pub fn new(source: ParamsSource, manifest: ParametersManifest) -> Self {
+        Self {
+            source,
+            manifest,
+            cache_dir: None,
+            params: OnceCell::new(),
+        }
+    }
+
+    /// Caches the downloaded parameters in the directory
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Returns the parameters, loading them on the first call. Concurrent calls wait for
+    /// the parameters to be loaded once, and failed loads are retried on the next call.
+    pub fn get(&self) -> Result<&Parameters<BWCurve, BLSCurve>, ParamsSourceError> {
+        self.params
+            .get_or_try_init(|| self.source.load(&self.manifest, self.cache_dir.as_deref()))
+    }
+
+    /// Whether the parameters were loaded
+    pub fn is_loaded(&self) -> bool {
+        self.params.get().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{registry::CircuitShape, trusted_setup};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    fn setup() -> (Vec<u8>, ParametersManifest) {
+        let rng = &mut XorShiftRng::seed_from_u64(0);
+        let shape = CircuitShape::new(3, 2);
+        let params = trusted_setup(3, 2, shape.maximum_non_signers, rng, false).unwrap();
+        let mut bytes = vec![];
+        params.write(&mut bytes).unwrap();
+        (bytes, params.manifest(shape).unwrap())
+    }
+
+    #[test]
+    fn loads_and_checks_parameters() {
+        let (bytes, manifest) = setup();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("epochs.params");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let lazy = LazyParameters::new(ParamsSource::File(path.clone()), manifest.clone());
+        assert!(!lazy.is_loaded());
+        let params = lazy.get().unwrap();
+        assert!(lazy.is_loaded());
+        assert!(std::ptr::eq(params, lazy.get().unwrap()));
+
+        // the same parameters, embedded in the binary
+        let embedded: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+        ParamsSource::Embedded(embedded)
+            .load(&manifest, None)
+            .unwrap();
+
+        // the manifest of other parameters
+        let mut other = manifest;
+        other.epochs_digest = [0; 32];
+        assert!(matches!(
+            ParamsSource::File(path).load(&other, None),
+            Err(ParamsSourceError::Manifest(ManifestError::DigestMismatch(
+                "epochs"
+            )))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "params-download")]
+    fn uses_cached_downloads() {
+        let (bytes, manifest) = setup();
+        let digest = ParamsSource::file_digest(&bytes[..]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(format!("{}.params", hex::encode(digest))),
+            &bytes,
+        )
+        .unwrap();
+
+        // nothing listens on the discard port, so the parameters must come from the cache
+        let source = ParamsSource::Url {
+            url: "http://127.0.0.1:9/epochs.params".to_owned(),
+            digest,
+        };
+        source.load(&manifest, Some(dir.path())).unwrap();
+        assert!(source.load(&manifest, None).is_err());
+
+        // a corrupted cache entry is downloaded again
+        let corrupted = ParamsSource::Url {
+            url: "http://127.0.0.1:9/epochs.params".to_owned(),
+            digest: [1; 32],
+        };
+        std::fs::write(
+            dir.path().join(format!("{}.params", hex::encode([1; 32]))),
+            &bytes,
+        )
+        .unwrap();
+        assert!(matches!(
+            corrupted.load(&manifest, Some(dir.path())),
+            Err(ParamsSourceError::Http(_))
+        ));
+    }
+}
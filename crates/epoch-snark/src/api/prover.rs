@@ -1,118 +1,565 @@
-use super::{setup::Parameters, BLSCurve, BLSCurveG1, BLSCurveG2, BWCurve};
+use super::{
+    compatibility::{CheckedCircuit, CompatibilityError},
+    config::ProverConfig,
+    erasure::ErasingCircuit,
+    qap::create_proof_no_zk_cached,
+    setup::Parameters,
+    spill::{create_proof_no_zk_spilled, WitnessSpillError},
+    BLSCurve, BLSCurveG1, BLSCurveG2, BWCurve, BWField,
+};
 use crate::{
+    aux_data_commitment::AuxDataCommitment,
+    bitmap_commitment::BitmapCommitment,
     epoch_block::{EpochBlock, EpochTransition},
-    gadgets::{EpochData, HashToBits, HashToBitsHelper, SingleUpdate, ValidatorSetUpdate},
+    gadgets::{
+        EpochData, EpochDataError, HashToBits, HashToBitsHelper, SingleUpdate, ValidatorSetUpdate,
+    },
+    provider::{EpochTransitionProvider, ProviderError},
+    randomness_beacon::RandomnessBeacon,
+    reward_vector::RewardVectorError,
+    scalars::OuterScalar,
 };
-use algebra::ProjectiveCurve;
+use algebra::{ProjectiveCurve, Zero};
 use bls_crypto::{
+    hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22,
     hashers::{Hasher, COMPOSITE_HASHER},
-    Signature,
+    Bitmap, ErrorCode, PublicKey, Signature, ToErrorCode, SIG_DOMAIN,
+};
+use bls_gadgets::{
+    diagnostics::{unsatisfied_constraints, UnsatisfiedConstraint},
+    utils::bytes_le_to_bits_be,
 };
-use bls_gadgets::utils::bytes_le_to_bits_be;
 
-use groth16::{create_proof_no_zk, Parameters as Groth16Parameters, Proof as Groth16Proof};
-use r1cs_core::SynthesisError;
+use groth16::{Parameters as Groth16Parameters, Proof as Groth16Proof};
+use r1cs_core::{
+    ConstraintLayer, ConstraintSynthesizer, ConstraintSystem, SynthesisError, TracingMode,
+};
 
+use rayon::prelude::*;
+use std::path::Path;
+use thiserror::Error;
 use tracing::{info, span, Level};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[derive(Debug, Error)]
+/// Error raised when proving fails
+pub enum ProverError {
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("the parameters cannot prove the inputs: {0}")]
+    Incompatible(#[from] CompatibilityError),
+    #[error("invalid epoch data: {0}")]
+    InvalidEpochData(#[from] EpochDataError),
+    #[error("there are no transitions to prove")]
+    NoTransitions,
+    #[error("{transitions} transitions exceed the maximum of {max_transitions}")]
+    TooManyTransitions {
+        transitions: usize,
+        max_transitions: usize,
+    },
+    #[error("invalid rewards: {0}")]
+    InvalidRewards(#[from] RewardVectorError),
+}
+
+impl ToErrorCode for ProverError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ProverError::ZexeSynthesisError(e) => e.error_code(),
+            ProverError::Incompatible(e) => e.error_code(),
+            ProverError::InvalidEpochData(e) => e.error_code(),
+            ProverError::InvalidRewards(e) => e.error_code(),
+            ProverError::NoTransitions | ProverError::TooManyTransitions { .. } => {
+                ErrorCode::InvalidArgument
+            }
+        }
+    }
+}
 
 /// Given the SNARK's Public Parameters, the initial epoch, and a list of state transitions,
 /// generates a SNARK which proves that the final epoch is correctly calculated from the first
 /// epoch. The proof can then be verified only with constant amount of data (the first and last
 /// epochs)
+///
+/// Before synthesizing anything, the parameters are checked to have the public inputs of the
+/// enabled features and CRH->XOF parameters for `max_transitions` epochs. Parameters generated
+/// for another number of validators or epochs are reported right after the synthesis, before
+/// the multi-scalar multiplications, see `CompatibilityError`.
+///
+/// The FFT domain of the QAP reduction is computed on the first proof of each size and reused
+/// by the following proofs of the process.
 pub fn prove(
     parameters: &Parameters<BWCurve, BLSCurve>,
     num_validators: u32,
     initial_epoch: &EpochBlock,
     transitions: &[EpochTransition],
     max_transitions: usize,
-) -> Result<Groth16Proof<BWCurve>, SynthesisError> {
+) -> Result<Groth16Proof<BWCurve>, ProverError> {
+    prove_with_config(
+        parameters,
+        num_validators,
+        initial_epoch,
+        transitions,
+        max_transitions,
+        &ProverConfig::default(),
+    )
+}
+
+/// Same as `prove`, but runs the prover with the parallelism specified in the `ProverConfig`
+pub fn prove_with_config(
+    parameters: &Parameters<BWCurve, BLSCurve>,
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    config: &ProverConfig,
+) -> Result<Groth16Proof<BWCurve>, ProverError> {
+    if cfg!(feature = "insecure_test_backend") {
+        let last_epoch = &transitions.last().ok_or(ProverError::NoTransitions)?.block;
+        let extra_inputs = feature_public_inputs(num_validators, transitions, max_transitions);
+        return Ok(super::insecure::prove(
+            initial_epoch,
+            last_epoch,
+            &extra_inputs,
+        )?);
+    }
+
+    config.install(|| {
+        prove_inner(
+            parameters,
+            initial_epoch,
+            max_transitions,
+            |with_hash_messages| {
+                checked_input(
+                    num_validators,
+                    transitions,
+                    max_transitions,
+                    with_hash_messages,
+                )
+            },
+            create_erased_proof,
+        )
+    })
+}
+
+/// Proves the circuit in memory, erasing its assignment afterwards
+fn create_erased_proof<E: From<SynthesisError>>(
+    circuit: CheckedCircuit<ValidatorSetUpdate<BLSCurve>>,
+    params: &Groth16Parameters<BWCurve>,
+) -> Result<Groth16Proof<BWCurve>, E> {
+    let (circuit, eraser) = ErasingCircuit::new(circuit);
+    let proof = create_proof_no_zk_cached(circuit, params)?;
+    eraser.erase();
+    Ok(proof)
+}
+
+/// Same as `prove`, but takes the transitions from the provider with `next_transition`, until
+/// it has no more transitions or `max_transitions` of them were taken. Each transition is
+/// turned into the circuit's input for its epoch as soon as it is taken, and then dropped, so
+/// the transitions are never all held in memory along with the circuit's input.
+pub fn prove_from_provider<P: EpochTransitionProvider>(
+    parameters: &Parameters<BWCurve, BLSCurve>,
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    provider: &mut P,
+    max_transitions: usize,
+) -> Result<Groth16Proof<BWCurve>, ProviderError<P::Error>> {
+    if cfg!(feature = "insecure_test_backend") {
+        // the insecure backend proves the transitions natively
+        let transitions = provider
+            .take_transitions(max_transitions)
+            .map_err(ProviderError::Source)?;
+        if transitions.is_empty() {
+            return Err(ProviderError::NoTransitions);
+        }
+        return Ok(prove(
+            parameters,
+            num_validators,
+            initial_epoch,
+            &transitions,
+            max_transitions,
+        )?);
+    }
+
+    prove_inner(
+        parameters,
+        initial_epoch,
+        max_transitions,
+        |with_hash_messages| {
+            let mut input = EpochsInput::new(num_validators, with_hash_messages);
+            while input.len() < max_transitions {
+                match provider.next_transition().map_err(ProviderError::Source)? {
+                    Some(transition) => input.push(&transition).map_err(ProverError::from)?,
+                    None => break,
+                }
+            }
+            if input.len() == 0 {
+                return Err(ProviderError::NoTransitions);
+            }
+            info!("took {} transitions from the provider", input.len());
+            Ok(input)
+        },
+        create_erased_proof,
+    )
+}
+
+/// Same as `prove_with_config`, but spills the witness of the epochs circuit to a temporary
+/// file in `spill_dir` during the multi-scalar multiplications. Use it when the witness of
+/// the epochs circuit (e.g. for 150 validators and 180 epochs) does not fit in memory along
+/// with the parameters. The proof is the same, but takes longer to produce. The temporary
+/// file is overwritten with zeros and deleted afterwards.
+pub fn prove_with_witness_spill(
+    parameters: &Parameters<BWCurve, BLSCurve>,
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    spill_dir: &Path,
+    config: &ProverConfig,
+) -> Result<Groth16Proof<BWCurve>, WitnessSpillError> {
+    if cfg!(feature = "insecure_test_backend") {
+        return Ok(prove_with_config(
+            parameters,
+            num_validators,
+            initial_epoch,
+            transitions,
+            max_transitions,
+            config,
+        )?);
+    }
+
+    config.install(|| {
+        prove_inner(
+            parameters,
+            initial_epoch,
+            max_transitions,
+            |with_hash_messages| {
+                Ok(checked_input(
+                    num_validators,
+                    transitions,
+                    max_transitions,
+                    with_hash_messages,
+                )?)
+            },
+            |circuit, params| create_proof_no_zk_spilled(circuit, params, spill_dir),
+        )
+    })
+}
+
+/// Synthesizes the circuit for the provided epochs with their full witness and checks that
+/// it is satisfied, without performing any of the multi-scalar multiplications of `prove`.
+/// Use it to find out why `prove` would produce an invalid proof for some epoch data in
+/// minutes rather than hours.
+///
+/// Returns the first unsatisfied constraint along with the path of the gadgets which
+/// generated it, or `None` if the epochs can be proven. The CRH->XOF hashes are always
+/// constrained in BW6_761, so no parameters are needed. The synthesis runs under its own
+/// tracing subscriber (which records the gadget paths), so its logs are not forwarded to
+/// the caller's subscriber. Malformed epoch data is reported as an error rather than as an
+/// unsatisfied constraint.
+pub fn prove_check_only(
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+) -> Result<Option<UnsatisfiedConstraint<BWField>>, ProverError> {
     info!(
-        "Generating proof for {} epochs (first epoch: {}, {} validators per epoch)",
+        "Checking the circuit for {} epochs (first epoch: {}, {} validators per epoch)",
         transitions.len(),
         initial_epoch.index,
         num_validators,
     );
+    check_transitions(transitions, max_transitions)?;
+    let circuit = to_circuit(
+        num_validators,
+        initial_epoch,
+        transitions,
+        max_transitions,
+        None,
+    )?;
 
-    let span = span!(Level::TRACE, "prove");
-    let _enter = span.enter();
+    let mut layer = ConstraintLayer::default();
+    layer.mode = TracingMode::OnlyConstraints;
+    let subscriber = tracing_subscriber::Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let cs = ConstraintSystem::<BWField>::new_ref();
+        circuit.generate_constraints(cs.clone())?;
+        Ok(unsatisfied_constraints(&cs)?.into_iter().next())
+    })
+}
 
-    let mut epochs = transitions
-        .iter()
-        .map(|transition| to_update(transition))
-        .collect::<Vec<_>>();
+/// Proves the epochs circuit with `create_proof`, after checking the parameters, taking the
+/// input of the transitions' epochs from `epochs` and proving the CRH->XOF helper (which is
+/// small enough to always be proven in memory) if needed. `epochs` is told whether the
+/// parameters need the messages of the CRH->XOF helper.
+fn prove_inner<E: From<SynthesisError> + From<ProverError>>(
+    parameters: &Parameters<BWCurve, BLSCurve>,
+    initial_epoch: &EpochBlock,
+    max_transitions: usize,
+    epochs: impl FnOnce(bool) -> Result<EpochsInput, E>,
+    create_proof: impl FnOnce(
+        CheckedCircuit<ValidatorSetUpdate<BLSCurve>>,
+        &Groth16Parameters<BWCurve>,
+    ) -> Result<Groth16Proof<BWCurve>, E>,
+) -> Result<Groth16Proof<BWCurve>, E> {
+    parameters
+        .check_compatibility(max_transitions)
+        .map_err(ProverError::from)?;
+    let mut input = epochs(parameters.hash_to_bits.is_some())?;
+    info!(
+        "Generating proof for {} epochs (first epoch: {}, {} validators per epoch)",
+        input.len(),
+        initial_epoch.index,
+        input.num_validators,
+    );
 
-    let num_epochs = epochs.len();
-    if num_epochs < max_transitions {
-        epochs = [
-            &epochs[..num_epochs - 1],
-            &(0..max_transitions - num_epochs)
-                .map(|_| to_dummy_update(num_validators))
-                .collect::<Vec<_>>(),
-            &[epochs[num_epochs - 1].clone()],
-        ]
-        .concat();
-    }
+    let span = span!(Level::TRACE, "prove");
+    let _enter = span.enter();
 
     // Generate a helping proof if a Proving Key for the HashToBits
     // circuit was provided
-    let hash_helper = if let Some(ref params) = parameters.hash_to_bits {
-        Some(generate_hash_helper(&params, transitions)?)
-    } else {
-        None
+    let hash_helper = match (&parameters.hash_to_bits, input.hash_messages.take()) {
+        (Some(params), Some(message_bits)) => Some(generate_hash_helper(params, message_bits)?),
+        _ => None,
     };
 
-    // Generate the BLS proof
-    let asig = Signature::aggregate(transitions.iter().map(|epoch| &epoch.aggregate_signature));
-    let mut asig_dummy = (0..max_transitions - num_epochs)
-        .map(|_| Signature::from(BLSCurveG1::prime_subgroup_generator()))
-        .collect::<Vec<_>>();
-    asig_dummy.push(asig);
-    let asig = Signature::aggregate(&asig_dummy);
-
-    let circuit = ValidatorSetUpdate::<BLSCurve> {
-        initial_epoch: to_epoch_data(initial_epoch),
-        epochs,
-        aggregated_signature: Some(*asig.as_ref()),
-        num_validators,
-        hash_helper,
-    };
+    let circuit = input
+        .into_circuit(initial_epoch, max_transitions, hash_helper)
+        .map_err(ProverError::from)?;
 
     info!("proving");
-    let bls_proof = create_proof_no_zk(circuit, &parameters.epochs)?;
+    let (circuit, shape_check) = CheckedCircuit::new(
+        circuit,
+        parameters.epochs.vk.gamma_abc_g1.len(),
+        parameters.epochs.l_query.len(),
+    );
+    let bls_proof = match create_proof(circuit, &parameters.epochs) {
+        Ok(proof) => proof,
+        Err(e) => match shape_check.mismatch() {
+            Some(mismatch) => return Err(ProverError::from(mismatch).into()),
+            None => return Err(e),
+        },
+    };
     info!("proved");
 
     Ok(bls_proof)
 }
 
-/// Helper which creates the hashproof inside BLS12-377
-fn generate_hash_helper(
-    params: &Groth16Parameters<BLSCurve>,
+/// Instantiates the epochs circuit for the provided epochs as `prove` does, with the CRH->XOF
+/// hashes constrained in BW6_761, e.g. to register extra public inputs with
+/// `ValidatorSetUpdate::with_extra_inputs` before proving it with parameters generated for
+/// the same inputs
+pub fn epochs_circuit(
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
     transitions: &[EpochTransition],
-) -> Result<HashToBitsHelper<BLSCurve>, SynthesisError> {
-    let composite_hasher = &COMPOSITE_HASHER;
+    max_transitions: usize,
+) -> Result<ValidatorSetUpdate<BLSCurve>, ProverError> {
+    check_transitions(transitions, max_transitions)?;
+    Ok(to_circuit(
+        num_validators,
+        initial_epoch,
+        transitions,
+        max_transitions,
+        None,
+    )?)
+}
 
-    // Generate the CRH per epoch
-    let message_bits = transitions
-        .iter()
-        .map(|transition| {
-            let block = &transition.block;
-            let (epoch_bytes, _) = block.encode_inner_to_bytes_cip22().unwrap();
-
-            let crh_bytes = composite_hasher.crh(&[], &epoch_bytes, 0).unwrap();
-            // The verifier should run both the crh and the xof here to generate a
-            // valid statement for the verify
-            bytes_le_to_bits_be(&crh_bytes, 384)
-                .iter()
-                .map(|b| Some(*b))
-                .collect()
+/// Checks that `to_circuit` can pad the transitions, i.e. that there is a last transition and
+/// room for the padding
+pub(super) fn check_transitions(
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+) -> Result<(), ProverError> {
+    if transitions.is_empty() {
+        return Err(ProverError::NoTransitions);
+    }
+    if transitions.len() > max_transitions {
+        return Err(ProverError::TooManyTransitions {
+            transitions: transitions.len(),
+            max_transitions,
+        });
+    }
+    Ok(())
+}
+
+/// Canonicalizes the epochs (see `SingleUpdate::canonicalize`), pads the transitions with
+/// dummy epochs up to `max_transitions` and instantiates the Validator Set Update circuit
+/// over them
+pub(super) fn to_circuit(
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    hash_helper: Option<HashToBitsHelper<BLSCurve>>,
+) -> Result<ValidatorSetUpdate<BLSCurve>, EpochDataError> {
+    EpochsInput::from_transitions(num_validators, transitions, false)?.into_circuit(
+        initial_epoch,
+        max_transitions,
+        hash_helper,
+    )
+}
+
+/// Checks the transitions with `check_transitions` and returns the input of their epochs
+fn checked_input(
+    num_validators: u32,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    with_hash_messages: bool,
+) -> Result<EpochsInput, ProverError> {
+    check_transitions(transitions, max_transitions)?;
+    Ok(EpochsInput::from_transitions(
+        num_validators,
+        transitions,
+        with_hash_messages,
+    )?)
+}
+
+/// The input of the Validator Set Update circuit for the transitions' epochs, which is built
+/// one transition at a time so that the transitions need not all be held in memory
+struct EpochsInput {
+    num_validators: u32,
+    /// The canonicalized epochs, without the padding
+    epochs: Vec<SingleUpdate<BLSCurve>>,
+    /// The aggregate of the transitions' signatures
+    aggregated_signature: Signature,
+    /// The CRH outputs of the epochs, if the CRH->XOF helper is proven
+    hash_messages: Option<Vec<Vec<Option<bool>>>>,
+}
+
+impl EpochsInput {
+    /// An input without any epochs, which collects the messages of the CRH->XOF helper if
+    /// `with_hash_messages` is set
+    fn new(num_validators: u32, with_hash_messages: bool) -> Self {
+        Self {
+            num_validators,
+            epochs: vec![],
+            aggregated_signature: Signature::from(BLSCurveG1::zero()),
+            hash_messages: if with_hash_messages {
+                Some(vec![])
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The input of the transitions' epochs. The epochs' witnesses are independent, so their
+    /// hashes to G1 are computed in parallel rather than one after the other while generating
+    /// the constraints.
+    fn from_transitions(
+        num_validators: u32,
+        transitions: &[EpochTransition],
+        with_hash_messages: bool,
+    ) -> Result<Self, EpochDataError> {
+        let epochs = transitions
+            .par_iter()
+            .map(|transition| to_canonical_update(num_validators, transition))
+            .collect::<Result<Vec<_>, EpochDataError>>()?;
+        let hash_messages = if with_hash_messages {
+            Some(transitions.par_iter().map(hash_message_bits).collect())
+        } else {
+            None
+        };
+        Ok(Self {
+            num_validators,
+            epochs,
+            aggregated_signature: Signature::aggregate(
+                transitions.iter().map(|epoch| &epoch.aggregate_signature),
+            ),
+            hash_messages,
+        })
+    }
+
+    /// Adds the transition's epoch after the previous ones
+    fn push(&mut self, transition: &EpochTransition) -> Result<(), EpochDataError> {
+        self.epochs
+            .push(to_canonical_update(self.num_validators, transition)?);
+        self.aggregated_signature = Signature::aggregate(&[
+            self.aggregated_signature.clone(),
+            transition.aggregate_signature.clone(),
+        ]);
+        if let Some(ref mut hash_messages) = self.hash_messages {
+            hash_messages.push(hash_message_bits(transition));
+        }
+        Ok(())
+    }
+
+    /// The number of epochs
+    fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    /// Pads the epochs with dummy epochs up to `max_transitions` and instantiates the circuit
+    /// over them. There must be at least one and at most `max_transitions` epochs, see
+    /// `check_transitions`.
+    fn into_circuit(
+        self,
+        initial_epoch: &EpochBlock,
+        max_transitions: usize,
+        hash_helper: Option<HashToBitsHelper<BLSCurve>>,
+    ) -> Result<ValidatorSetUpdate<BLSCurve>, EpochDataError> {
+        let num_validators = self.num_validators;
+        let mut epochs = self.epochs;
+        let num_epochs = epochs.len();
+        if num_epochs < max_transitions {
+            epochs = [
+                &epochs[..num_epochs - 1],
+                &vec![to_dummy_update(num_validators); max_transitions - num_epochs],
+                &[epochs[num_epochs - 1].clone()],
+            ]
+            .concat();
+        }
+
+        // Aggregate the signatures, including the dummy epochs' generator signatures
+        let mut asig_dummy = (0..max_transitions - num_epochs)
+            .map(|_| Signature::from(BLSCurveG1::prime_subgroup_generator()))
+            .collect::<Vec<_>>();
+        asig_dummy.push(self.aggregated_signature);
+        let asig = Signature::aggregate(&asig_dummy);
+
+        Ok(ValidatorSetUpdate::<BLSCurve> {
+            initial_epoch: to_epoch_data(initial_epoch).canonicalize(num_validators as usize)?,
+            epochs,
+            aggregated_signature: Some(*asig.as_ref()),
+            num_validators,
+            hash_helper,
+            extra_inputs: vec![],
         })
-        .collect::<Vec<_>>();
+    }
+}
+
+/// The canonicalized input of the transition's epoch, along with its hash counter
+fn to_canonical_update(
+    num_validators: u32,
+    transition: &EpochTransition,
+) -> Result<SingleUpdate<BLSCurve>, EpochDataError> {
+    let mut update = to_update(transition).canonicalize(num_validators as usize)?;
+    update.epoch_data.hash_counter = hash_counter(&transition.block);
+    Ok(update)
+}
+
+/// The CRH output of the transition's epoch, as a message of the CRH->XOF helper
+fn hash_message_bits(transition: &EpochTransition) -> Vec<Option<bool>> {
+    let (epoch_bytes, _) = transition.block.encode_inner_to_bytes_cip22().unwrap();
+    // The verifier should run both the crh and the xof here to generate a
+    // valid statement for the verify
+    let crh_bytes = COMPOSITE_HASHER.crh(&[], &epoch_bytes, 0).unwrap();
+    bytes_le_to_bits_be(&crh_bytes, 384)
+        .iter()
+        .map(|b| Some(*b))
+        .collect()
+}
 
+/// Helper which creates the hashproof inside BLS12-377
+fn generate_hash_helper(
+    params: &Groth16Parameters<BLSCurve>,
+    message_bits: Vec<Vec<Option<bool>>>,
+) -> Result<HashToBitsHelper<BLSCurve>, SynthesisError> {
     // Generate proof of correct calculation of the CRH->Blake hashes
     // to make Hash to G1 cheaper
-    let circuit = HashToBits { message_bits };
+    let (circuit, eraser) = ErasingCircuit::new(HashToBits { message_bits });
     info!("CRH->XOF");
-    let hash_proof = create_proof_no_zk(circuit, params)?;
+    let hash_proof = create_proof_no_zk_cached(circuit, params)?;
+    eraser.erase();
 
     Ok(HashToBitsHelper {
         proof: hash_proof,
@@ -120,7 +567,29 @@ fn generate_hash_helper(
     })
 }
 
-fn to_epoch_data(block: &EpochBlock) -> EpochData<BLSCurve> {
+/// The public inputs of the commitments of the enabled features for the transitions, in the
+/// order of `verify_with_randomness_beacon`
+pub(crate) fn feature_public_inputs(
+    num_validators: u32,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+) -> Vec<OuterScalar> {
+    let mut inputs = vec![];
+    if cfg!(feature = "bitmap-commitment") {
+        inputs.extend(
+            BitmapCommitment::new(transitions, max_transitions, num_validators).public_inputs(),
+        );
+    }
+    if cfg!(feature = "epoch-aux-data") {
+        inputs.extend(AuxDataCommitment::new(transitions, max_transitions).public_inputs());
+    }
+    if cfg!(feature = "randomness-beacon") {
+        inputs.extend(RandomnessBeacon::new(transitions, max_transitions).public_inputs());
+    }
+    inputs
+}
+
+pub(crate) fn to_epoch_data(block: &EpochBlock) -> EpochData<BLSCurve> {
     EpochData {
         index: Some(block.index),
         round: Some(block.round),
@@ -132,32 +601,78 @@ fn to_epoch_data(block: &EpochBlock) -> EpochData<BLSCurve> {
             .iter()
             .map(|pubkey| Some(*pubkey.as_ref()))
             .collect(),
+        aux_data: block.aux_data,
+        chain_id: block.chain_id,
+        validator_blinding: block.validator_blinding,
+        hash_counter: None,
     }
 }
 
 fn to_update(transition: &EpochTransition) -> SingleUpdate<BLSCurve> {
     SingleUpdate {
         epoch_data: to_epoch_data(&transition.block),
-        signed_bitmap: transition
-            .bitmap
-            .iter()
-            .map(|b| Some(*b))
-            .collect::<Vec<_>>(),
+        signed_bitmap: Some(transition.bitmap.clone()),
     }
 }
 
 fn to_dummy_update(num_validators: u32) -> SingleUpdate<BLSCurve> {
+    let block = EpochBlock::new(
+        0,
+        0,
+        Some(vec![0u8; EpochBlock::ENTROPY_BYTES]),
+        Some(vec![0u8; EpochBlock::ENTROPY_BYTES]),
+        0,
+        num_validators as usize,
+        vec![PublicKey::from(BLSCurveG2::prime_subgroup_generator()); num_validators as usize],
+    );
     SingleUpdate {
         epoch_data: EpochData {
-            maximum_non_signers: 0,
-            epoch_entropy: Some(vec![0u8; EpochBlock::ENTROPY_BYTES]),
-            parent_entropy: Some(vec![0u8; EpochBlock::ENTROPY_BYTES]),
-            index: Some(0),
-            round: Some(0),
-            public_keys: (0..num_validators)
-                .map(|_| Some(BLSCurveG2::prime_subgroup_generator()))
-                .collect::<Vec<_>>(),
+            hash_counter: hash_counter(&block),
+            ..to_epoch_data(&block)
         },
-        signed_bitmap: (0..num_validators).map(|_| Some(true)).collect::<Vec<_>>(),
+        signed_bitmap: Some(Bitmap::full(num_validators as usize)),
+    }
+}
+
+/// The try-and-increment counter of the epoch's hash to G1, or `None` if the epoch cannot be
+/// encoded, in which case generating the constraints fails as it searches for the counter
+fn hash_counter(block: &EpochBlock) -> Option<u8> {
+    let (message, extra_data) = block.encode_inner_to_bytes_cip22().ok()?;
+    let (_, counter) = COMPOSITE_HASH_TO_G1_CIP22
+        .hash_with_attempt_cip22(SIG_DOMAIN, &message, &extra_data)
+        .ok()?;
+    Some(counter as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{simulate, SimulationConfig};
+
+    #[test]
+    fn builds_the_same_input_one_transition_at_a_time() {
+        let simulation = simulate(&SimulationConfig::new(4, 3), 11).unwrap();
+        let transitions = &simulation.transitions;
+        let mut provider = transitions.clone().into_iter();
+        let mut streamed = EpochsInput::new(4, true);
+        while let Some(transition) = provider.next_transition().unwrap() {
+            streamed.push(&transition).unwrap();
+        }
+
+        let input = EpochsInput::from_transitions(4, transitions, true).unwrap();
+        assert_eq!(streamed.len(), 3);
+        assert_eq!(streamed.aggregated_signature, input.aggregated_signature);
+        assert_eq!(streamed.hash_messages, input.hash_messages);
+        for (streamed, epoch) in streamed.epochs.iter().zip(&input.epochs) {
+            assert_eq!(streamed.signed_bitmap, epoch.signed_bitmap);
+            assert_eq!(
+                streamed.epoch_data.hash_counter,
+                epoch.epoch_data.hash_counter
+            );
+            assert_eq!(
+                streamed.epoch_data.public_keys,
+                epoch.epoch_data.public_keys
+            );
+        }
     }
 }
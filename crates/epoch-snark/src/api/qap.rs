@@ -0,0 +1,232 @@
+//! QAP reduction with cached FFT domains
+//!
+//! The QAP reduction of a proof evaluates the constraints over an FFT domain sized for the
+//! number of constraints and public inputs. Every proof of the same circuit uses the same
+//! domain, so the domains are cached for the lifetime of the process, keyed by their field and
+//! size, instead of being recomputed on each proof. `create_proof_no_zk_cached` produces the
+//! same proofs as groth16's `create_proof_no_zk`, with the domain taken from the cache.
+use super::erasure::zeroize;
+use algebra::{
+    msm::VariableBaseMSM, AffineCurve, Field, PairingEngine, PrimeField, ProjectiveCurve,
+};
+use ff_fft::{EvaluationDomain, GeneralEvaluationDomain};
+use groth16::{Parameters, Proof};
+use once_cell::sync::Lazy;
+use r1cs_core::{ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use rayon::prelude::*;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::debug;
+
+/// A cached domain, along with the number of times it was looked up
+struct CachedDomain {
+    domain: Arc<dyn Any + Send + Sync>,
+    hits: usize,
+}
+
+/// The cached domains, keyed by the type of their field and the size they were requested for
+static DOMAINS: Lazy<Mutex<HashMap<(TypeId, usize), CachedDomain>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the domain for `size` evaluations over `F`, computing it only if it is not cached.
+/// Returns `None` if the field has no domain of that size.
+pub(crate) fn evaluation_domain<F: PrimeField>(
+    size: usize,
+) -> Option<Arc<GeneralEvaluationDomain<F>>> {
+    let mut domains = DOMAINS.lock().expect("mutex poisoned");
+    let key = (TypeId::of::<F>(), size);
+    if let Some(cached) = domains.get_mut(&key) {
+        cached.hits += 1;
+        return cached.domain.clone().downcast().ok();
+    }
+
+    debug!("computing the FFT domain for {} evaluations", size);
+    let domain = Arc::new(GeneralEvaluationDomain::<F>::new(size)?);
+    domains.insert(
+        key,
+        CachedDomain {
+            domain: domain.clone(),
+            hits: 0,
+        },
+    );
+    Some(domain)
+}
+
+/// The number of times the domain for `size` evaluations over `F` was taken from the cache
+#[cfg(test)]
+fn cache_hits<F: PrimeField>(size: usize) -> usize {
+    DOMAINS
+        .lock()
+        .expect("mutex poisoned")
+        .get(&(TypeId::of::<F>(), size))
+        .map_or(0, |cached| cached.hits)
+}
+
+/// Same as groth16's `create_proof_no_zk`, but with the FFT domain taken from the cache. As in
+/// `witness_map`, the assignment is zeroed once the constraints are evaluated, but its big
+/// integer copies for the multi-scalar multiplications are not.
+pub(crate) fn create_proof_no_zk_cached<E, C>(
+    circuit: C,
+    params: &Parameters<E>,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: PairingEngine,
+    C: ConstraintSynthesizer<E::Fr>,
+{
+    let cs = ConstraintSystem::new_ref();
+    circuit.generate_constraints(cs.clone())?;
+    cs.inline_all_lcs();
+    let matrices = cs.to_matrices().ok_or(SynthesisError::AssignmentMissing)?;
+    let (instance, witness) = {
+        let mut cs = cs.borrow_mut().ok_or(SynthesisError::MissingCS)?;
+        (
+            std::mem::take(&mut cs.instance_assignment),
+            std::mem::take(&mut cs.witness_assignment),
+        )
+    };
+    drop(cs);
+
+    // the constant 1 is not part of the scalars, its bases are added directly
+    let num_inputs = instance.len() - 1;
+    let assignment = instance[1..]
+        .iter()
+        .chain(&witness)
+        .map(|scalar| scalar.into_repr())
+        .collect::<Vec<_>>();
+    let mut h = witness_map(matrices, instance, witness)?;
+    let h_assignment = h
+        .iter()
+        .map(|scalar| scalar.into_repr())
+        .collect::<Vec<_>>();
+    zeroize(&mut h);
+
+    let g_a = params.vk.alpha_g1.into_projective()
+        + &params.a_query[0].into_projective()
+        + &VariableBaseMSM::multi_scalar_mul(&params.a_query[1..], &assignment);
+    let g_b = params.vk.beta_g2.into_projective()
+        + &params.b_g2_query[0].into_projective()
+        + &VariableBaseMSM::multi_scalar_mul(&params.b_g2_query[1..], &assignment);
+    let g_c = VariableBaseMSM::multi_scalar_mul(&params.l_query, &assignment[num_inputs..])
+        + &VariableBaseMSM::multi_scalar_mul(&params.h_query, &h_assignment);
+
+    Ok(Proof {
+        a: g_a.into_affine(),
+        b: g_b.into_affine(),
+        c: g_c.into_affine(),
+    })
+}
+
+/// Computes the coefficients of the quotient polynomial `h` of the QAP, exactly as groth16's
+/// (private) `R1CStoQAP::witness_map`, over the cached domain. The matrices and the
+/// assignment are freed (and the assignment zeroed) once the constraints are evaluated,
+/// before the FFTs.
+pub(crate) fn witness_map<F: PrimeField>(
+    matrices: ConstraintMatrices<F>,
+    mut instance: Vec<F>,
+    mut witness: Vec<F>,
+) -> Result<Vec<F>, SynthesisError> {
+    let num_inputs = matrices.num_instance_variables;
+    let num_constraints = matrices.num_constraints;
+    let domain = evaluation_domain::<F>(num_constraints + num_inputs)
+        .ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
+    let domain_size = domain.size();
+
+    let (mut a, mut b, mut c) = {
+        let evaluate = |row: &[(F, usize)]| evaluate_constraint(row, &instance, &witness);
+        let mut a = vec![F::zero(); domain_size];
+        let mut b = vec![F::zero(); domain_size];
+        let mut c = vec![F::zero(); domain_size];
+        a[..num_constraints]
+            .par_iter_mut()
+            .zip(&mut b[..num_constraints])
+            .zip(&mut c[..num_constraints])
+            .zip(matrices.a.par_iter().zip(&matrices.b).zip(&matrices.c))
+            .for_each(|(((a, b), c), ((a_row, b_row), c_row))| {
+                *a = evaluate(a_row.as_slice());
+                *b = evaluate(b_row.as_slice());
+                *c = evaluate(c_row.as_slice());
+            });
+        a[num_constraints..num_constraints + num_inputs].clone_from_slice(&instance);
+        (a, b, c)
+    };
+    drop(matrices);
+    zeroize(&mut instance);
+    zeroize(&mut witness);
+    drop(instance);
+    drop(witness);
+
+    domain.ifft_in_place(&mut a);
+    domain.ifft_in_place(&mut b);
+    domain.coset_fft_in_place(&mut a);
+    domain.coset_fft_in_place(&mut b);
+    // the product is computed in place, so that only three evaluations are ever in memory
+    a.par_iter_mut().zip(&b).for_each(|(a, b)| *a *= b);
+    drop(b);
+
+    domain.ifft_in_place(&mut c);
+    domain.coset_fft_in_place(&mut c);
+    a.par_iter_mut().zip(c).for_each(|(ab, c)| *ab -= &c);
+    domain.divide_by_vanishing_poly_on_coset_in_place(&mut a);
+    domain.coset_ifft_in_place(&mut a);
+    Ok(a)
+}
+
+/// Evaluates a row of a matrix against the assignment, whose instance variables come first
+fn evaluate_constraint<F: Field>(row: &[(F, usize)], instance: &[F], witness: &[F]) -> F {
+    row.iter()
+        .map(|(coeff, index)| match index.checked_sub(instance.len()) {
+            Some(index) => *coeff * &witness[index],
+            None => *coeff * &instance[*index],
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{test_helpers::Cubes, BWCurve, BWField};
+    use algebra::UniformRand;
+    use groth16::{create_proof_no_zk, generate_random_parameters};
+
+    #[test]
+    fn proofs_match_groth16() {
+        let rng = &mut rand::thread_rng();
+        let roots = (0..16).map(|_| BWField::rand(rng)).collect::<Vec<_>>();
+        let params =
+            generate_random_parameters::<BWCurve, _, _>(Cubes(roots.clone()), rng).unwrap();
+
+        let cached = create_proof_no_zk_cached(Cubes(roots.clone()), &params).unwrap();
+        let proof = create_proof_no_zk(Cubes(roots), &params).unwrap();
+        assert_eq!(cached, proof);
+    }
+
+    #[test]
+    fn reuses_cached_domains() {
+        // a number of roots which no other test proves, so that the hits are only ours
+        let rng = &mut rand::thread_rng();
+        let roots = (0..11).map(|_| BWField::rand(rng)).collect::<Vec<_>>();
+        let params =
+            generate_random_parameters::<BWCurve, _, _>(Cubes(roots.clone()), rng).unwrap();
+        let cs = ConstraintSystem::new_ref();
+        Cubes(roots.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        cs.inline_all_lcs();
+        let matrices = cs.to_matrices().unwrap();
+        let size = matrices.num_constraints + matrices.num_instance_variables;
+
+        create_proof_no_zk_cached(Cubes(roots.clone()), &params).unwrap();
+        let domain = evaluation_domain::<BWField>(size).unwrap();
+        let hits = cache_hits::<BWField>(size);
+        // the second proof of the same size takes the domain from the cache
+        create_proof_no_zk_cached(Cubes(roots), &params).unwrap();
+        assert_eq!(cache_hits::<BWField>(size), hits + 1);
+        assert!(Arc::ptr_eq(
+            &domain,
+            &evaluation_domain::<BWField>(size).unwrap()
+        ));
+    }
+}
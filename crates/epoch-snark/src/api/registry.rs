@@ -0,0 +1,212 @@
+/// Circuit Registry
+///
+/// Caches the synthesized constraint matrices of the Validator Set Update circuit per shape,
+/// so that identical constraint systems are only synthesized once per process. The matrices
+/// of a large circuit weigh gigabytes, so at most `capacity` circuits are cached (see
+/// `set_capacity`), evicting the least recently used one first.
+use super::{BLSCurve, BWField};
+use crate::gadgets::ValidatorSetUpdate;
+
+use groth16::VerifyingKey;
+use once_cell::sync::Lazy;
+use r1cs_core::{
+    ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem, SynthesisError, SynthesisMode,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tracing::{info, span, Level};
+
+/// Validator set sizes whose circuits are commonly used, which can be synthesized ahead
+/// of time via `precompute_common`
+pub const COMMON_VALIDATOR_COUNTS: [usize; 3] = [100, 110, 150];
+
+/// The parameters which fully determine the constraint system of the Validator Set Update circuit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CircuitShape {
+    pub num_validators: usize,
+    pub num_epochs: usize,
+    pub maximum_non_signers: usize,
+}
+
+impl CircuitShape {
+    /// Instantiates a shape, allowing up to a third of the validators to not sign
+    pub fn new(num_validators: usize, num_epochs: usize) -> Self {
+        Self {
+            num_validators,
+            num_epochs,
+            maximum_non_signers: num_validators.saturating_sub(1) / 3,
+        }
+    }
+}
+
+/// The number of circuits cached by default, i.e. the `COMMON_VALIDATOR_COUNTS` and one more
+pub const DEFAULT_CAPACITY: usize = COMMON_VALIDATOR_COUNTS.len() + 1;
+
+/// The cached circuits, along with their shapes from the least to the most recently used
+struct Registry {
+    circuits: HashMap<CircuitShape, Arc<ConstraintMatrices<BWField>>>,
+    recency: VecDeque<CircuitShape>,
+    capacity: usize,
+}
+
+impl Registry {
+    fn get(&mut self, shape: &CircuitShape) -> Option<Arc<ConstraintMatrices<BWField>>> {
+        let matrices = self.circuits.get(shape)?.clone();
+        self.touch(*shape);
+        Some(matrices)
+    }
+
+    /// Caches the matrices unless the shape is already cached, and returns the cached
+    /// matrices
+    fn insert(
+        &mut self,
+        shape: CircuitShape,
+        matrices: Arc<ConstraintMatrices<BWField>>,
+    ) -> Arc<ConstraintMatrices<BWField>> {
+        let matrices = self.circuits.entry(shape).or_insert(matrices).clone();
+        self.touch(shape);
+        self.evict();
+        matrices
+    }
+
+    /// Marks the shape as the most recently used
+    fn touch(&mut self, shape: CircuitShape) {
+        self.recency.retain(|cached| *cached != shape);
+        self.recency.push_back(shape);
+    }
+
+    /// Drops the least recently used circuits beyond the capacity. Callers which still hold
+    /// their matrices keep them alive.
+    fn evict(&mut self) {
+        while self.recency.len() > self.capacity {
+            if let Some(shape) = self.recency.pop_front() {
+                info!("Evicting cached circuit for {:?}", shape);
+                self.circuits.remove(&shape);
+            }
+        }
+    }
+}
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(|| {
+    Mutex::new(Registry {
+        circuits: HashMap::new(),
+        recency: VecDeque::new(),
+        capacity: DEFAULT_CAPACITY,
+    })
+});
+
+/// Returns the constraint matrices of the circuit with the provided shape, synthesizing
+/// them only if they are not cached.
+pub fn circuit_for(
+    shape: CircuitShape,
+) -> Result<Arc<ConstraintMatrices<BWField>>, SynthesisError> {
+    if let Some(matrices) = REGISTRY.lock().expect("mutex poisoned").get(&shape) {
+        return Ok(matrices);
+    }
+
+    // the lock is not held while synthesizing, since this may take a while. If another
+    // thread raced us, the first inserted entry is kept
+    let matrices = Arc::new(synthesize(shape, None)?);
+    Ok(REGISTRY
+        .lock()
+        .expect("mutex poisoned")
+        .insert(shape, matrices))
+}
+
+/// Sets the maximum number of cached circuits (`DEFAULT_CAPACITY` by default), evicting the
+/// least recently used ones beyond it. A capacity of 0 disables the cache.
+pub fn set_capacity(capacity: usize) {
+    let mut registry = REGISTRY.lock().expect("mutex poisoned");
+    registry.capacity = capacity;
+    registry.evict();
+}
+
+/// Synthesizes and caches the circuits for all the `COMMON_VALIDATOR_COUNTS` for the
+/// provided number of epochs
+pub fn precompute_common(num_epochs: usize) -> Result<(), SynthesisError> {
+    for num_validators in COMMON_VALIDATOR_COUNTS.iter() {
+        circuit_for(CircuitShape::new(*num_validators, num_epochs))?;
+    }
+    Ok(())
+}
+
+/// Removes all cached circuits
+pub fn clear() {
+    let mut registry = REGISTRY.lock().expect("mutex poisoned");
+    registry.circuits.clear();
+    registry.recency.clear();
+}
+
+/// Synthesizes the circuit in setup mode. If the CRH->XOF circuit's verifying key is
+/// provided, the circuit verifies its proof instead of constraining the hashes.
+pub(crate) fn synthesize(
+    shape: CircuitShape,
+    vk: Option<VerifyingKey<BLSCurve>>,
+) -> Result<ConstraintMatrices<BWField>, SynthesisError> {
+    info!("Synthesizing circuit for {:?}", shape);
+    let span = span!(Level::TRACE, "synthesize_circuit");
+    let _enter = span.enter();
+
+    let cs = ConstraintSystem::new_ref();
+    cs.set_mode(SynthesisMode::Setup);
+    let circuit = ValidatorSetUpdate::<BLSCurve>::empty(
+        shape.num_validators,
+        shape.num_epochs,
+        shape.maximum_non_signers,
+        vk,
+    );
+    circuit.generate_constraints(cs.clone())?;
+    cs.inline_all_lcs();
+    cs.to_matrices().ok_or(SynthesisError::AssignmentMissing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_circuits_per_shape() {
+        let shape = CircuitShape::new(3, 2);
+        assert_eq!(shape.maximum_non_signers, 0);
+
+        let first = circuit_for(shape).unwrap();
+        let second = circuit_for(shape).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.num_constraints > 0);
+
+        let other = circuit_for(CircuitShape::new(4, 2)).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert!(other.num_constraints > first.num_constraints);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        // a private registry, so that the test does not race with the global one
+        let mut registry = Registry {
+            circuits: HashMap::new(),
+            recency: VecDeque::new(),
+            capacity: 2,
+        };
+        let shapes = (1..=3)
+            .map(|num_validators| CircuitShape::new(num_validators, 1))
+            .collect::<Vec<_>>();
+        let matrices = Arc::new(synthesize(shapes[0], None).unwrap());
+
+        registry.insert(shapes[0], matrices.clone());
+        registry.insert(shapes[1], matrices.clone());
+        assert!(registry.get(&shapes[0]).is_some());
+        // the second shape is now the least recently used
+        registry.insert(shapes[2], matrices.clone());
+        assert!(registry.get(&shapes[1]).is_none());
+        assert!(registry.get(&shapes[0]).is_some());
+        assert!(registry.get(&shapes[2]).is_some());
+
+        registry.capacity = 0;
+        registry.evict();
+        assert!(registry.circuits.is_empty());
+        // the cache no longer holds the matrices
+        assert_eq!(Arc::strong_count(&matrices), 1);
+    }
+}
@@ -0,0 +1,586 @@
+//! Remote Proving
+//!
+//! Proving takes far more memory and time than validators on modest hardware can spare, so
+//! `prove_remote` ships the inputs of `prove` to a proving server which holds the parameters,
+//! and waits for the proof, which it checks against the verifying key before returning it. The
+//! server handles each request with `serve_remote_proof`, behind the HTTP framework of its
+//! choice, and rejects requests for another circuit than the one of its parameters.
+//!
+//! The client POSTs a JSON object with the fields
+//!
+//! - `num_validators` and `max_transitions`, as passed to `prove`
+//! - `initial_epoch`, the RLP encoding of the initial epoch (see `codec::rlp::encode_block`)
+//! - `transitions`, the RLP encodings of the transitions (see `codec::rlp::encode_transition`)
+//!
+//! where each encoding is a `0x` prefixed hex string. The codecs do not encode the validator
+//! set blindings, so they are never sent, and circuits built with the `blinded-validators`
+//! feature cannot be proven remotely.
+//!
+//! The server streams back one JSON object per line: any number of
+//! `{"progress": {"stage": .., "completed": .., "total": ..}}` updates, followed by either
+//! `{"proof": ..}` with the compressed `CanonicalSerialize` encoding of the proof as a `0x`
+//! prefixed hex string, or `{"error": ..}` with a message. The progress counts the decoding of
+//! the request, each `EpochStage` of each epoch of the circuit and the proof itself.
+use super::{
+    feature_public_inputs, prove, registry::CircuitShape, setup::Parameters,
+    verify_with_extra_inputs, BLSCurve, BWCurve, ProverError, VerificationError,
+};
+use crate::{
+    codec::{
+        rlp::{decode_block, decode_transition, encode_block, encode_transition},
+        CodecError,
+    },
+    epoch_block::{EpochBlock, EpochTransition},
+    progress::{observe_progress, EpochStage},
+};
+use algebra::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use groth16::{Proof as Groth16Proof, VerifyingKey};
+use r1cs_core::SynthesisError;
+use serde_json::{json, Value};
+use std::{
+    convert::TryFrom,
+    io::{self, BufRead, BufReader, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// The number of progress updates which `serve_remote_proof` reports for a proof of
+/// `max_transitions` epochs: the decoding, each stage of each epoch and the proof
+fn num_stages(max_transitions: usize) -> u64 {
+    2 + (max_transitions * EpochStage::ALL.len()) as u64
+}
+
+#[derive(Debug, Error)]
+/// Error raised while proving remotely, on either side
+pub enum RemoteProverError {
+    #[error("HTTP Error: {0}")]
+    Http(#[from] Box<ureq::Error>),
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("JSON Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Hex Error: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Codec Error: {0}")]
+    Codec(#[from] CodecError),
+    #[error("Zexe Error: {0}")]
+    ZexeSerialization(#[from] SerializationError),
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("Prover Error: {0}")]
+    Prover(#[from] ProverError),
+    #[error("the returned proof is invalid: {0}")]
+    InvalidProof(#[from] VerificationError),
+    #[error("expected {expected:?}, got {num_validators} validators and {max_transitions} epochs")]
+    ShapeMismatch {
+        expected: CircuitShape,
+        num_validators: u32,
+        max_transitions: usize,
+    },
+    #[error("blinded validator sets cannot be proven remotely")]
+    BlindedValidators,
+    #[error("invalid remote proving message: {0}")]
+    InvalidMessage(&'static str),
+    #[error("the proving server failed: {0}")]
+    Remote(String),
+}
+
+impl ToErrorCode for RemoteProverError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            RemoteProverError::Http(_) | RemoteProverError::IoError(_) => ErrorCode::Io,
+            RemoteProverError::Json(_)
+            | RemoteProverError::Hex(_)
+            | RemoteProverError::Codec(_)
+            | RemoteProverError::ZexeSerialization(_)
+            | RemoteProverError::InvalidMessage(_) => ErrorCode::Serialization,
+            RemoteProverError::ZexeSynthesisError(e) => e.error_code(),
+            RemoteProverError::Prover(e) => e.error_code(),
+            RemoteProverError::InvalidProof(e) => e.error_code(),
+            RemoteProverError::ShapeMismatch { .. } | RemoteProverError::BlindedValidators => {
+                ErrorCode::InvalidArgument
+            }
+            RemoteProverError::Remote(_) => ErrorCode::Synthesis,
+        }
+    }
+}
+
+/// A progress update streamed by the proving server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteProgress {
+    /// The stage which the server completed
+    pub stage: String,
+    /// The number of completed stages
+    pub completed: u64,
+    /// The total number of stages
+    pub total: u64,
+}
+
+/// Proves the transitions on the proving server at `endpoint`, and returns the proof which
+/// `prove` would have returned with the server's parameters, once it is checked against `vk`.
+/// `on_progress` is called with each progress update as the server streams it.
+pub fn prove_remote(
+    endpoint: &str,
+    vk: &VerifyingKey<BWCurve>,
+    num_validators: u32,
+    initial_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    mut on_progress: impl FnMut(&RemoteProgress),
+) -> Result<Groth16Proof<BWCurve>, RemoteProverError> {
+    if cfg!(feature = "blinded-validators") {
+        return Err(RemoteProverError::BlindedValidators);
+    }
+    let last_epoch = &transitions.last().ok_or(ProverError::NoTransitions)?.block;
+    let request = json!({
+        "num_validators": num_validators,
+        "max_transitions": max_transitions,
+        "initial_epoch": to_hex(&encode_block(initial_epoch)),
+        "transitions": transitions
+            .iter()
+            .map(|transition| to_hex(&encode_transition(transition)))
+            .collect::<Vec<_>>(),
+    });
+    info!(
+        "sending {} transitions to the proving server at {}",
+        transitions.len(),
+        endpoint
+    );
+    let response = ureq::post(endpoint)
+        .set("Accept", "application/x-ndjson")
+        .send_json(request)
+        .map_err(Box::new)?;
+
+    for line in BufReader::new(response.into_reader()).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = serde_json::from_str(&line)?;
+        if let Some(progress) = message.get("progress") {
+            on_progress(&parse_progress(progress)?);
+        } else if let Some(proof) = message.get("proof") {
+            let proof = proof.as_str().ok_or(RemoteProverError::InvalidMessage(
+                "the proof is not a string",
+            ))?;
+            let proof = Groth16Proof::deserialize(&from_hex(proof)?[..])?;
+            let feature_inputs =
+                feature_public_inputs(num_validators, transitions, max_transitions);
+            verify_with_extra_inputs(vk, initial_epoch, last_epoch, &feature_inputs, &[], &proof)?;
+            return Ok(proof);
+        } else if let Some(error) = message.get("error") {
+            return Err(RemoteProverError::Remote(
+                error.as_str().unwrap_or_default().to_owned(),
+            ));
+        } else {
+            return Err(RemoteProverError::InvalidMessage("unknown message"));
+        }
+    }
+    Err(RemoteProverError::InvalidMessage(
+        "the stream ended without a proof",
+    ))
+}
+
+/// Handles a request sent by `prove_remote` with the parameters generated for `shape`,
+/// writing the progress updates and the proof (or the error) to `response` as they are
+/// produced. The response is flushed after each message, so that it can be streamed to the
+/// client, which is why it is written from the prover's progress observer and must be owned.
+/// The error is also returned, for the server's logs.
+pub fn serve_remote_proof<W: Write + Send + 'static>(
+    parameters: &Parameters<BWCurve, BLSCurve>,
+    shape: CircuitShape,
+    request: &[u8],
+    response: W,
+) -> Result<(), RemoteProverError> {
+    let response = Arc::new(Mutex::new(response));
+    let result = prove_request(parameters, shape, request, &response).and_then(|proof| {
+        let mut bytes = vec![];
+        proof.serialize(&mut bytes)?;
+        Ok(bytes)
+    });
+    let message = match &result {
+        Ok(proof) => json!({ "proof": to_hex(proof) }),
+        Err(e) => {
+            warn!("remote proving failed: {}", e);
+            json!({ "error": e.to_string() })
+        }
+    };
+    write_message(&mut *response.lock().expect("mutex poisoned"), &message)?;
+    result.map(|_| ())
+}
+
+fn prove_request<W: Write + Send + 'static>(
+    parameters: &Parameters<BWCurve, BLSCurve>,
+    shape: CircuitShape,
+    request: &[u8],
+    response: &Arc<Mutex<W>>,
+) -> Result<Groth16Proof<BWCurve>, RemoteProverError> {
+    let request = decode_request(request)?;
+    if request.num_validators as usize != shape.num_validators
+        || request.max_transitions != shape.num_epochs
+    {
+        return Err(RemoteProverError::ShapeMismatch {
+            expected: shape,
+            num_validators: request.num_validators,
+            max_transitions: request.max_transitions,
+        });
+    }
+    let total = num_stages(request.max_transitions);
+    report(
+        &mut *response.lock().expect("mutex poisoned"),
+        "decoded",
+        1,
+        total,
+    )?;
+    info!(
+        "proving {} transitions for a remote client",
+        request.transitions.len()
+    );
+
+    // a client which went away only stops the updates, and the proof is still completed
+    let completed = AtomicU64::new(1);
+    let observer_response = response.clone();
+    let proof = observe_progress(
+        move |event| {
+            let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            let stage = format!("epoch {} {}", event.position, event.stage.name());
+            let mut response = observer_response.lock().expect("mutex poisoned");
+            if let Err(e) = report(&mut *response, &stage, completed, total) {
+                warn!("could not report the progress of a remote proof: {}", e);
+            }
+        },
+        || {
+            prove(
+                parameters,
+                request.num_validators,
+                &request.initial_epoch,
+                &request.transitions,
+                request.max_transitions,
+            )
+        },
+    )?;
+    report(
+        &mut *response.lock().expect("mutex poisoned"),
+        "proved",
+        total,
+        total,
+    )?;
+    Ok(proof)
+}
+
+/// The inputs of `prove`, as decoded by the server
+#[derive(Debug, PartialEq)]
+struct ProveRequest {
+    num_validators: u32,
+    max_transitions: usize,
+    initial_epoch: EpochBlock,
+    transitions: Vec<EpochTransition>,
+}
+
+fn decode_request(request: &[u8]) -> Result<ProveRequest, RemoteProverError> {
+    let request: Value = serde_json::from_slice(request)?;
+    let num_validators = request
+        .get("num_validators")
+        .and_then(Value::as_u64)
+        .ok_or(RemoteProverError::InvalidMessage("missing num_validators"))?;
+    let num_validators = u32::try_from(num_validators)
+        .map_err(|_| RemoteProverError::InvalidMessage("num_validators is too large"))?;
+    let max_transitions = request
+        .get("max_transitions")
+        .and_then(Value::as_u64)
+        .ok_or(RemoteProverError::InvalidMessage("missing max_transitions"))?;
+    let max_transitions = usize::try_from(max_transitions)
+        .map_err(|_| RemoteProverError::InvalidMessage("max_transitions is too large"))?;
+    let initial_epoch = request
+        .get("initial_epoch")
+        .and_then(Value::as_str)
+        .ok_or(RemoteProverError::InvalidMessage("missing initial_epoch"))?;
+    let transitions = request
+        .get("transitions")
+        .and_then(Value::as_array)
+        .ok_or(RemoteProverError::InvalidMessage("missing transitions"))?
+        .iter()
+        .map(|transition| {
+            let transition = transition
+                .as_str()
+                .ok_or(RemoteProverError::InvalidMessage(
+                    "a transition is not a string",
+                ))?;
+            Ok(decode_transition(&from_hex(transition)?)?)
+        })
+        .collect::<Result<Vec<_>, RemoteProverError>>()?;
+
+    Ok(ProveRequest {
+        num_validators,
+        max_transitions,
+        initial_epoch: decode_block(&from_hex(initial_epoch)?)?,
+        transitions,
+    })
+}
+
+fn parse_progress(progress: &Value) -> Result<RemoteProgress, RemoteProverError> {
+    let field = |name| progress.get(name).and_then(Value::as_u64);
+    Ok(RemoteProgress {
+        stage: progress
+            .get("stage")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned(),
+        completed: field("completed")
+            .ok_or(RemoteProverError::InvalidMessage("missing completed"))?,
+        total: field("total").ok_or(RemoteProverError::InvalidMessage("missing total"))?,
+    })
+}
+
+fn report<W: Write>(response: &mut W, stage: &str, completed: u64, total: u64) -> io::Result<()> {
+    write_message(
+        response,
+        &json!({
+            "progress": { "stage": stage, "completed": completed, "total": total },
+        }),
+    )
+}
+
+fn write_message<W: Write>(response: &mut W, message: &Value) -> io::Result<()> {
+    writeln!(response, "{}", message)?;
+    response.flush()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(encoded: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(encoded.trim_start_matches("0x"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::test_helpers::transitions;
+    use algebra::{
+        bw6_761::{G1Affine, G2Affine},
+        AffineCurve,
+    };
+    use groth16::Parameters as Groth16Parameters;
+    use std::{
+        io::Read,
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    /// A response which the test can read once the server is done with it
+    #[derive(Clone, Default)]
+    struct SharedResponse(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedResponse {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedResponse {
+        fn messages(&self) -> Vec<Value> {
+            self.0
+                .lock()
+                .unwrap()
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| serde_json::from_slice(line).unwrap())
+                .collect()
+        }
+    }
+
+    /// Answers one request with the messages, and returns the endpoint's URL along with the
+    /// body of the request
+    fn serve(messages: Vec<Value>) -> (String, JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim().to_lowercase();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut stream = vec![];
+            for message in &messages {
+                write_message(&mut stream, message).unwrap();
+            }
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                stream.len(),
+            )
+            .unwrap();
+            reader.get_mut().write_all(&stream).unwrap();
+            body
+        });
+        (url, server)
+    }
+
+    #[test]
+    #[cfg(not(feature = "blinded-validators"))]
+    fn streams_progress_and_checks_the_proof() {
+        let proof = Groth16Proof::<BWCurve> {
+            a: G1Affine::prime_subgroup_generator(),
+            b: G2Affine::prime_subgroup_generator(),
+            c: G1Affine::prime_subgroup_generator(),
+        };
+        let mut proof_bytes = vec![];
+        proof.serialize(&mut proof_bytes).unwrap();
+        let mut messages = vec![];
+        for completed in 1..=2 {
+            let mut progress = vec![];
+            report(&mut progress, "stage", completed, num_stages(5)).unwrap();
+            messages.push(serde_json::from_slice(&progress).unwrap());
+        }
+        messages.push(json!({ "proof": to_hex(&proof_bytes) }));
+
+        let transitions = transitions();
+        let (url, server) = serve(messages);
+        let mut updates = vec![];
+        let vk = VerifyingKey::<BWCurve>::default();
+        let result = prove_remote(&url, &vk, 3, &transitions[0].block, &transitions, 5, |p| {
+            updates.push(p.clone())
+        });
+        // the proof does not verify against the verifying key
+        assert!(matches!(result, Err(RemoteProverError::InvalidProof(_))));
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[1].completed, 2);
+        assert_eq!(updates[1].total, num_stages(5));
+
+        // the server decodes what the client sent, without the validator blinding
+        let request = decode_request(&server.join().unwrap()).unwrap();
+        assert!(transitions[2].block.validator_blinding.is_some());
+        assert_eq!(
+            request,
+            ProveRequest {
+                num_validators: 3,
+                max_transitions: 5,
+                initial_epoch: transitions[0].block.clone(),
+                transitions: transitions
+                    .iter()
+                    .map(|transition| {
+                        let mut transition = transition.clone();
+                        transition.block.validator_blinding = None;
+                        transition
+                    })
+                    .collect(),
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "blinded-validators"))]
+    fn reports_server_errors() {
+        let transitions = transitions();
+        let vk = VerifyingKey::<BWCurve>::default();
+        let (url, server) = serve(vec![json!({ "error": "out of memory" })]);
+        let result = prove_remote(&url, &vk, 3, &transitions[0].block, &transitions, 5, |_| {});
+        assert!(matches!(
+            result,
+            Err(RemoteProverError::Remote(message)) if message == "out of memory"
+        ));
+        server.join().unwrap();
+
+        let (url, server) = serve(vec![]);
+        let result = prove_remote(&url, &vk, 3, &transitions[0].block, &transitions, 5, |_| {});
+        assert!(matches!(result, Err(RemoteProverError::InvalidMessage(_))));
+        server.join().unwrap();
+
+        assert!(matches!(
+            decode_request(br#"{"num_validators": 3}"#),
+            Err(RemoteProverError::InvalidMessage("missing max_transitions"))
+        ));
+        assert!(matches!(
+            decode_request(br#"{"num_validators": 4294967296, "max_transitions": 5}"#),
+            Err(RemoteProverError::InvalidMessage(
+                "num_validators is too large"
+            ))
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "insecure_test_backend"))]
+    fn rejects_requests_for_other_circuits() {
+        // parameters without any public input, which cannot prove anything
+        let vk = VerifyingKey {
+            gamma_abc_g1: vec![Default::default()],
+            ..VerifyingKey::default()
+        };
+        let parameters = Parameters::<BWCurve, BLSCurve> {
+            epochs: Groth16Parameters {
+                vk,
+                beta_g1: Default::default(),
+                delta_g1: Default::default(),
+                a_query: vec![],
+                b_g1_query: vec![],
+                b_g2_query: vec![],
+                h_query: vec![],
+                l_query: vec![],
+            },
+            hash_to_bits: None,
+        };
+        let shape = CircuitShape::new(3, 5);
+        let transitions = transitions();
+        let request = |num_validators: u32| {
+            json!({
+                "num_validators": num_validators,
+                "max_transitions": 5,
+                "initial_epoch": to_hex(&encode_block(&transitions[0].block)),
+                "transitions": transitions
+                    .iter()
+                    .map(|transition| to_hex(&encode_transition(transition)))
+                    .collect::<Vec<_>>(),
+            })
+            .to_string()
+        };
+
+        let response = SharedResponse::default();
+        let result =
+            serve_remote_proof(&parameters, shape, request(4).as_bytes(), response.clone());
+        assert!(matches!(
+            result,
+            Err(RemoteProverError::ShapeMismatch {
+                num_validators: 4,
+                ..
+            })
+        ));
+        let messages = response.messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].get("error").is_some());
+
+        // the request is decoded before the parameters turn out to be unusable
+        let response = SharedResponse::default();
+        let result =
+            serve_remote_proof(&parameters, shape, request(3).as_bytes(), response.clone());
+        assert!(matches!(result, Err(RemoteProverError::Prover(_))));
+        let messages = response.messages();
+        assert_eq!(messages.len(), 2);
+        let progress = parse_progress(&messages[0]["progress"]).unwrap();
+        assert_eq!((progress.completed, progress.total), (1, num_stages(5)));
+        assert!(messages[1].get("error").is_some());
+    }
+}
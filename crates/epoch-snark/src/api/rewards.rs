@@ -0,0 +1,134 @@
+//! Reward Attestations
+//!
+//! A bridge chain which verified an epochs proof with the `bitmap-commitment` feature knows
+//! the commitment to the signed bitmaps of the proven range, but not the bitmaps. A reward
+//! attestation proves the number of epochs of the range which each validator signed (a
+//! `RewardVector`) from the bitmaps behind that commitment, so that the chain can distribute
+//! rewards according to the counts without trusting whoever relays them. The public inputs
+//! are the bitmap commitment followed by the commitment to the counts (see
+//! `reward_attestation_public_inputs`).
+use super::{
+    prover::{check_transitions, ProverError},
+    BWCurve, VerificationError,
+};
+use crate::{
+    bitmap_commitment::BitmapCommitment, epoch_block::EpochTransition, gadgets::RewardAttestation,
+    reward_vector::RewardVector, scalars::OuterScalar,
+};
+use bls_gadgets::utils::bytes_le_to_bits_le;
+
+use groth16::{
+    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    Parameters as Groth16Parameters, Proof, VerifyingKey,
+};
+use r1cs_core::SynthesisError;
+use rand::Rng;
+use tracing::info;
+
+/// Generates the parameters of the reward attestation circuit for ranges of `num_epochs`
+/// epochs (including the dummy epochs, i.e. the `max_transitions` of the epochs proof) with
+/// `num_validators` validators
+pub fn reward_attestation_setup<R: Rng>(
+    num_validators: usize,
+    num_epochs: usize,
+    rng: &mut R,
+) -> Result<Groth16Parameters<BWCurve>, SynthesisError> {
+    info!(
+        "Generating reward attestation parameters for {} validators and {} epochs",
+        num_validators, num_epochs
+    );
+    generate_random_parameters(RewardAttestation::empty(num_validators, num_epochs), rng)
+}
+
+/// Counts the epochs which each validator signed over the transitions of an epochs proof,
+/// and proves that the counts match the proof's bitmap commitment. The transitions and
+/// `max_transitions` must be those of the epochs proof, and every bitmap must have
+/// `num_validators` bits.
+pub fn prove_reward_attestation<R: Rng>(
+    parameters: &Groth16Parameters<BWCurve>,
+    num_validators: u32,
+    transitions: &[EpochTransition],
+    max_transitions: usize,
+    rng: &mut R,
+) -> Result<(RewardVector, Proof<BWCurve>), ProverError> {
+    info!(
+        "Generating reward attestation proof for {} epochs ({} validators per epoch)",
+        transitions.len(),
+        num_validators,
+    );
+    check_transitions(transitions, max_transitions)?;
+    let bitmaps = BitmapCommitment::new(transitions, max_transitions, num_validators);
+    let rewards = RewardVector::new(&bitmaps, num_validators as usize)?;
+
+    let circuit = RewardAttestation {
+        epochs: bitmaps
+            .bitmaps()
+            .iter()
+            .map(|(index, bitmap)| (Some(*index), Some(bitmap.clone())))
+            .collect(),
+        num_validators: num_validators as usize,
+    };
+    let proof = create_random_proof(circuit, parameters, rng)?;
+    Ok((rewards, proof))
+}
+
+/// Verifies a proof that the validators signed the epochs of `rewards` over the range whose
+/// bitmap commitment is `bitmap_commitment` (see `BitmapCommitment::commitment`)
+pub fn verify_reward_attestation(
+    vk: &VerifyingKey<BWCurve>,
+    bitmap_commitment: &[u8; 32],
+    rewards: &RewardVector,
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!(
+        "Verifying reward attestation proof for {} validators",
+        rewards.counts().len()
+    );
+    let public_inputs = OuterScalar::to_fields(&reward_attestation_public_inputs(
+        bitmap_commitment,
+        rewards,
+    ));
+    if verify_proof(&prepare_verifying_key(vk), proof, &public_inputs)? {
+        Ok(())
+    } else {
+        Err(VerificationError::VerificationFailed)
+    }
+}
+
+/// The public inputs of a reward attestation proof: the bitmap commitment followed by the
+/// commitment to the counts, each packed over BW6_761's Fr
+pub fn reward_attestation_public_inputs(
+    bitmap_commitment: &[u8; 32],
+    rewards: &RewardVector,
+) -> Vec<OuterScalar> {
+    let mut inputs = OuterScalar::pack(&bytes_le_to_bits_le(bitmap_commitment, 256));
+    inputs.extend(rewards.public_inputs());
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap_commitment::test_helpers::transition;
+
+    #[test]
+    fn proves_and_verifies_counts() {
+        let rng = &mut rand::thread_rng();
+        let params = reward_attestation_setup(3, 3, rng).unwrap();
+        let transitions = vec![
+            transition(1, vec![true, true, false]),
+            transition(2, vec![true, false, false]),
+        ];
+        let (rewards, proof) = prove_reward_attestation(&params, 3, &transitions, 3, rng).unwrap();
+        assert_eq!(rewards.counts(), &[2, 1, 0]);
+
+        let commitment = BitmapCommitment::new(&transitions, 3, 3).commitment();
+        verify_reward_attestation(&params.vk, &commitment, &rewards, &proof).unwrap();
+
+        // the counts of another range, or other counts, are rejected
+        let other = BitmapCommitment::new(&transitions[..1], 3, 3).commitment();
+        assert!(verify_reward_attestation(&params.vk, &other, &rewards, &proof).is_err());
+        let inflated = RewardVector::from_counts(vec![2, 2, 0]);
+        assert!(verify_reward_attestation(&params.vk, &commitment, &inflated, &proof).is_err());
+    }
+}
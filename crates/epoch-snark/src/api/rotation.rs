@@ -0,0 +1,116 @@
+//! Key Rotation Proofs
+//!
+//! Validators which rotate their keys within an epoch sign a handoff to the new key with the
+//! old one (see `KeyRotation`). The rotations of an epoch are proven with the `KeyRotations`
+//! circuit, whose public inputs are the epoch's index and its validator set before and after
+//! the rotations (see `key_rotation_public_inputs`), so whoever trusts the epoch's validators
+//! can adopt the rotated set without checking each handoff. The handoffs must be signed with
+//! `COMPOSITE_HASH_TO_G1_CIP22`, which the circuit computes.
+use super::{BLSCurve, BWCurve, BWField, VerificationError};
+use crate::{
+    encoding::{encode_public_key, EncodingError},
+    gadgets::KeyRotations,
+    scalars::OuterScalar,
+};
+use bls_crypto::{KeyRotation, PublicKey, Signature};
+
+use groth16::{
+    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    Parameters as Groth16Parameters, Proof, VerifyingKey,
+};
+use r1cs_core::SynthesisError;
+use rand::Rng;
+use tracing::info;
+
+/// Generates the parameters of the key rotation circuit for epochs with `num_validators`
+/// validators
+pub fn key_rotation_setup<R: Rng>(
+    num_validators: usize,
+    rng: &mut R,
+) -> Result<Groth16Parameters<BWCurve>, SynthesisError> {
+    info!(
+        "Generating key rotation parameters for {} validators",
+        num_validators
+    );
+    generate_random_parameters(KeyRotations::<BLSCurve>::empty(num_validators), rng)
+}
+
+/// Proves that the rotations were signed by validators of the epoch at `index`. Each
+/// validator can be rotated at most once, and the epoch must have the number of validators
+/// of the parameters.
+pub fn prove_key_rotations<R: Rng>(
+    parameters: &Groth16Parameters<BWCurve>,
+    index: u16,
+    validators: &[PublicKey],
+    rotations: &[KeyRotation],
+    rng: &mut R,
+) -> Result<Proof<BWCurve>, SynthesisError> {
+    info!("Generating key rotation proof for epoch {}", index);
+    let mut new_public_keys = validators.to_vec();
+    let mut rotated = vec![false; validators.len()];
+    for rotation in rotations {
+        // the constraints cannot be satisfied for rotations of keys outside the validator
+        // set, or for a validator which is rotated twice
+        let slot = validators
+            .iter()
+            .zip(&rotated)
+            .position(|(validator, is_rotated)| *validator == rotation.old_key && !is_rotated)
+            .ok_or(SynthesisError::Unsatisfiable)?;
+        new_public_keys[slot] = rotation.new_key.clone();
+        rotated[slot] = true;
+    }
+
+    let circuit = KeyRotations::<BLSCurve> {
+        index: Some(index),
+        public_keys: validators.iter().map(|pk| Some(*pk.as_ref())).collect(),
+        new_public_keys: new_public_keys
+            .iter()
+            .map(|pk| Some(*pk.as_ref()))
+            .collect(),
+        rotated: rotated.into_iter().map(Some).collect(),
+        aggregated_signature: Some(
+            *Signature::aggregate(rotations.iter().map(|rotation| &rotation.signature)).as_ref(),
+        ),
+    };
+    create_random_proof(circuit, parameters, rng)
+}
+
+/// Verifies a proof that the validators of the epoch at `index` were rotated to
+/// `rotated_validators`
+pub fn verify_key_rotations(
+    vk: &VerifyingKey<BWCurve>,
+    index: u16,
+    validators: &[PublicKey],
+    rotated_validators: &[PublicKey],
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying key rotation proof for epoch {}", index);
+    let public_inputs = OuterScalar::to_fields(&key_rotation_public_inputs(
+        index,
+        validators,
+        rotated_validators,
+    )?);
+    if verify_proof(&prepare_verifying_key(vk), proof, &public_inputs)? {
+        Ok(())
+    } else {
+        Err(VerificationError::VerificationFailed)
+    }
+}
+
+/// The public inputs of a key rotation proof: the index, followed by the bits of the
+/// compressed validators before and after the rotations, each packed over BW6_761's Fr
+pub fn key_rotation_public_inputs(
+    index: u16,
+    validators: &[PublicKey],
+    rotated_validators: &[PublicKey],
+) -> Result<Vec<OuterScalar>, EncodingError> {
+    let mut inputs = vec![OuterScalar(BWField::from(index))];
+    for keys in &[validators, rotated_validators] {
+        let mut bits = vec![];
+        for pk in keys.iter() {
+            bits.extend(encode_public_key(pk)?);
+        }
+        inputs.extend(OuterScalar::pack(&bits));
+    }
+    Ok(inputs)
+}
@@ -7,9 +7,13 @@ use algebra::PairingEngine;
 use r1cs_core::SynthesisError;
 use rand::Rng;
 
-use super::{BLSCurve, BWCurve, BWFrParams};
+use super::{
+    config::ProverConfig, registry::CircuitShape, snapshot::CircuitSnapshot, BLSCurve, BWCurve,
+    BWFrParams,
+};
 
 use groth16::{generate_random_parameters, Parameters as Groth16Parameters};
+use std::collections::{hash_map::Entry, HashMap};
 use tracing::{info, span, Level};
 
 type Result<T> = std::result::Result<T, SynthesisError>;
@@ -27,6 +31,9 @@ pub struct Parameters<CP: PairingEngine, BLS: PairingEngine> {
 /// which will perform 2 setups, one for the CRH->XOF hashes in BLS12-377 and the rest
 /// of the circuit in BW6_761. If set to `false, only 1 setup will be done (at the expense
 /// of having a longer proving time due to CRH->XOF hashes being done in BW6_761)
+///
+/// Store the parameters' `manifest` along with them, and check it with `verify_manifest`
+/// when loading them.
 pub fn trusted_setup<R: Rng>(
     num_validators: usize,
     num_epochs: usize,
@@ -45,14 +52,137 @@ pub fn trusted_setup<R: Rng>(
     )
 }
 
+/// Same as `trusted_setup` with `hashes_in_bls12_377 = false`, but replays the constraints of
+/// a snapshot of the circuit instead of synthesizing it, which makes repeated setups for the
+/// same shape cheaper. The snapshot's shape determines the number of validators, epochs and
+/// maximum non-signers.
+pub fn trusted_setup_from_snapshot<R: Rng>(
+    snapshot: &CircuitSnapshot,
+    rng: &mut R,
+) -> Result<Parameters<BWCurve, BLSCurve>> {
+    info!(
+        "Generating parameters from snapshot of {:?}",
+        snapshot.shape()
+    );
+    let span = span!(Level::TRACE, "setup_from_snapshot");
+    let _enter = span.enter();
+
+    Ok(Parameters {
+        epochs: generate_random_parameters(snapshot.circuit(), rng)?,
+        hash_to_bits: None,
+    })
+}
+
+/// Same as `trusted_setup`, but runs the setup with the parallelism specified in the `ProverConfig`.
+/// The RNG must be `Send`, since it is used from within the configured pool.
+pub fn trusted_setup_with_config<R: Rng + Send>(
+    num_validators: usize,
+    num_epochs: usize,
+    maximum_non_signers: usize,
+    rng: &mut R,
+    hashes_in_bls12_377: bool,
+    config: &ProverConfig,
+) -> Result<Parameters<BWCurve, BLSCurve>> {
+    config.install(|| {
+        trusted_setup(
+            num_validators,
+            num_epochs,
+            maximum_non_signers,
+            rng,
+            hashes_in_bls12_377,
+        )
+    })
+}
+
+/// Runs `trusted_setup` for each of the shapes (e.g. for 100, 125 and 150 validators) and
+/// passes each shape's parameters to `write` as soon as they are generated, so that only one
+/// set of parameters is held in memory at a time.
+///
+/// The epochs circuit of each shape is synthesized and set up on its own, so this takes as
+/// long as calling `trusted_setup` for each shape. Only the CRH->XOF circuit, which depends
+/// on the number of epochs alone, is set up once per distinct number of epochs when
+/// `hashes_in_bls12_377` is `true`, and shared by all the shapes with that number of epochs.
+/// Each set of parameters passed to `write` still includes the CRH->XOF parameters, so that
+/// it can be stored and loaded on its own.
+pub fn trusted_setup_for_shapes<R, W, E>(
+    shapes: &[CircuitShape],
+    rng: &mut R,
+    hashes_in_bls12_377: bool,
+    mut write: W,
+) -> std::result::Result<(), E>
+where
+    R: Rng,
+    W: FnMut(CircuitShape, Parameters<BWCurve, BLSCurve>) -> std::result::Result<(), E>,
+    E: From<SynthesisError>,
+{
+    let mut hash_to_bits = HashMap::new();
+    for shape in shapes {
+        let parameters = setup(
+            shape.num_validators,
+            shape.num_epochs,
+            shape.maximum_non_signers,
+            rng,
+            |c, rng| match hash_to_bits.entry(shape.num_epochs) {
+                Entry::Occupied(entry) => Ok(entry.get().clone()),
+                Entry::Vacant(entry) => {
+                    Ok(entry.insert(generate_random_parameters(c, rng)?).clone())
+                }
+            },
+            |c, rng| generate_random_parameters(c, rng),
+            hashes_in_bls12_377,
+        )?;
+        write(*shape, parameters)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
     #[test]
     fn runs_setup() {
         let rng = &mut rand::thread_rng();
         assert!(trusted_setup(3, 2, 1, rng, false).is_ok())
     }
+
+    #[test]
+    fn runs_setup_in_pool() {
+        let rng = &mut XorShiftRng::seed_from_u64(0);
+        let config = ProverConfig::with_threads(2).unwrap();
+        assert!(trusted_setup_with_config(3, 2, 1, rng, false, &config).is_ok())
+    }
+
+    #[test]
+    fn runs_setup_for_shapes() {
+        let rng = &mut rand::thread_rng();
+        let shapes = [
+            CircuitShape::new(3, 2),
+            CircuitShape::new(4, 2),
+            CircuitShape::new(3, 1),
+        ];
+        let mut generated = vec![];
+        trusted_setup_for_shapes(&shapes, rng, true, |shape, params| {
+            generated.push((shape, params));
+            Ok::<_, SynthesisError>(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            generated
+                .iter()
+                .map(|(shape, _)| *shape)
+                .collect::<Vec<_>>(),
+            shapes
+        );
+        let hash_vk = |i: usize| generated[i].1.hash_to_bits.as_ref().unwrap().vk.clone();
+        // the CRH->XOF setup is shared between the shapes with the same number of epochs
+        assert_eq!(hash_vk(0), hash_vk(1));
+        assert_ne!(hash_vk(0), hash_vk(2));
+        assert_ne!(generated[0].1.epochs.vk, generated[1].1.epochs.vk);
+    }
 }
 
 /// Performs a Groth16 setup over the 2 provided Pairing-friendly curves for the Hash to Bits and Validator set update circuits
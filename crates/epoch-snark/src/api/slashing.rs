@@ -0,0 +1,99 @@
+//! Slashing Proofs
+//!
+//! A validator which signs two different epoch blocks with the same index and round can be
+//! slashed with a succinct proof of it. The proof is generated for the `DoubleSigning` circuit, whose only
+//! public inputs are the index and the validator's public key (see
+//! `double_signing_public_inputs`), so it can be verified on-chain without the conflicting
+//! blocks. Unlike epoch proofs, slashing proofs are zero-knowledge, so they do not reveal the
+//! blocks either.
+use super::{to_epoch_data, BLSCurve, BWCurve, BWField, VerificationError};
+use crate::{
+    encoding::{encode_public_key, EncodingError},
+    epoch_block::EpochBlock,
+    gadgets::DoubleSigning,
+    scalars::OuterScalar,
+};
+use bls_crypto::{PublicKey, Signature};
+
+use groth16::{
+    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    Parameters as Groth16Parameters, Proof, VerifyingKey,
+};
+use r1cs_core::SynthesisError;
+use rand::Rng;
+use tracing::info;
+
+/// Generates the parameters of the double signing circuit for epochs with `num_validators`
+/// validators
+pub fn double_signing_setup<R: Rng>(
+    num_validators: usize,
+    rng: &mut R,
+) -> Result<Groth16Parameters<BWCurve>, SynthesisError> {
+    info!(
+        "Generating double signing parameters for {} validators",
+        num_validators
+    );
+    generate_random_parameters(DoubleSigning::<BLSCurve>::empty(num_validators), rng)
+}
+
+/// Proves that the public key signed both epochs, which have the same index and round but
+/// are different. Both epochs must have the number of validators of the parameters.
+pub fn prove_double_signing<R: Rng>(
+    parameters: &Groth16Parameters<BWCurve>,
+    first_epoch: &EpochBlock,
+    second_epoch: &EpochBlock,
+    public_key: &PublicKey,
+    first_signature: &Signature,
+    second_signature: &Signature,
+    rng: &mut R,
+) -> Result<Proof<BWCurve>, SynthesisError> {
+    info!(
+        "Generating double signing proof for epoch {}",
+        first_epoch.index
+    );
+    // the constraints cannot be satisfied for such epochs
+    if first_epoch.index != second_epoch.index
+        || first_epoch.round != second_epoch.round
+        || first_epoch.encode_inner_to_bytes_cip22().ok()
+            == second_epoch.encode_inner_to_bytes_cip22().ok()
+    {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    let circuit = DoubleSigning::<BLSCurve> {
+        first_epoch: to_epoch_data(first_epoch),
+        second_epoch: to_epoch_data(second_epoch),
+        public_key: Some(*public_key.as_ref()),
+        first_signature: Some(*first_signature.as_ref()),
+        second_signature: Some(*second_signature.as_ref()),
+    };
+    create_random_proof(circuit, parameters, rng)
+}
+
+/// Verifies a proof that the public key signed two different epochs at `index`, in the same
+/// round
+pub fn verify_double_signing(
+    vk: &VerifyingKey<BWCurve>,
+    index: u16,
+    public_key: &PublicKey,
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying double signing proof for epoch {}", index);
+    let public_inputs = OuterScalar::to_fields(&double_signing_public_inputs(index, public_key)?);
+    if verify_proof(&prepare_verifying_key(vk), proof, &public_inputs)? {
+        Ok(())
+    } else {
+        Err(VerificationError::VerificationFailed)
+    }
+}
+
+/// The public inputs of a double signing proof: the index, followed by the bits of the
+/// compressed public key packed over BW6_761's Fr
+pub fn double_signing_public_inputs(
+    index: u16,
+    public_key: &PublicKey,
+) -> Result<Vec<OuterScalar>, EncodingError> {
+    let mut inputs = vec![OuterScalar(BWField::from(index))];
+    inputs.extend(OuterScalar::pack(&encode_public_key(public_key)?));
+    Ok(inputs)
+}
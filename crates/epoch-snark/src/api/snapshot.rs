@@ -0,0 +1,231 @@
+/// Constraint System Snapshots
+///
+/// Synthesizing the Validator Set Update circuit dominates the cost of a setup for large
+/// validator sets. A `CircuitSnapshot` stores the constraint matrices of a `CircuitShape` so
+/// that they can be synthesized once, written to disk, and replayed by every subsequent
+/// setup for that shape with `trusted_setup_from_snapshot`.
+///
+/// Snapshots which are read back are not checked against the circuit beyond their variable
+/// indices, so they are never added to the circuit registry: a tampered snapshot only affects
+/// the setups it is explicitly passed to.
+use super::{
+    registry::{self, CircuitShape},
+    BWField,
+};
+
+use algebra::serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use r1cs_core::{
+    ConstraintMatrices, ConstraintSynthesizer, ConstraintSystemRef, LinearCombination,
+    SynthesisError, Variable,
+};
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
+
+/// The constraint matrices of the Validator Set Update circuit for a shape, synthesized
+/// without the CRH->XOF helper (i.e. with the hashes constrained in BW6_761)
+#[derive(Clone)]
+pub struct CircuitSnapshot {
+    shape: CircuitShape,
+    matrices: Arc<ConstraintMatrices<BWField>>,
+}
+
+impl CircuitShape {
+    /// Synthesizes the circuit of the shape, unless it is already cached in the registry,
+    /// and returns a snapshot which can be reused for repeated setups
+    pub fn synthesize_once(&self) -> Result<CircuitSnapshot, SynthesisError> {
+        Ok(CircuitSnapshot {
+            shape: *self,
+            matrices: registry::circuit_for(*self)?,
+        })
+    }
+}
+
+impl CircuitSnapshot {
+    /// The shape whose circuit was synthesized
+    pub fn shape(&self) -> CircuitShape {
+        self.shape
+    }
+
+    /// The constraint matrices of the circuit
+    pub fn matrices(&self) -> &ConstraintMatrices<BWField> {
+        &self.matrices
+    }
+
+    /// Returns a circuit which replays the snapshot's constraints. It can only be used for
+    /// setups, since it carries no witness.
+    pub fn circuit(&self) -> SnapshotCircuit {
+        SnapshotCircuit(self.matrices.clone())
+    }
+
+    /// Serializes the snapshot as its shape followed by the dimensions and the rows of the
+    /// A, B and C matrices
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        let shape = &self.shape;
+        for n in &[
+            shape.num_validators,
+            shape.num_epochs,
+            shape.maximum_non_signers,
+            self.matrices.num_instance_variables,
+            self.matrices.num_witness_variables,
+            self.matrices.num_constraints,
+        ] {
+            (*n as u64).serialize(&mut writer)?;
+        }
+        for matrix in &[&self.matrices.a, &self.matrices.b, &self.matrices.c] {
+            for row in matrix.iter() {
+                (row.len() as u64).serialize(&mut writer)?;
+                for (coeff, index) in row {
+                    coeff.serialize(&mut writer)?;
+                    (*index as u64).serialize(&mut writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserializes a snapshot written with `write`, checking that every entry refers to an
+    /// allocated variable
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let shape = CircuitShape {
+            num_validators: read_usize(&mut reader)?,
+            num_epochs: read_usize(&mut reader)?,
+            maximum_non_signers: read_usize(&mut reader)?,
+        };
+        let num_instance_variables = read_usize(&mut reader)?;
+        let num_witness_variables = read_usize(&mut reader)?;
+        let num_constraints = read_usize(&mut reader)?;
+        let num_variables = num_instance_variables
+            .checked_add(num_witness_variables)
+            .ok_or(SerializationError::InvalidData)?;
+
+        let mut matrices = vec![];
+        let mut num_non_zero = vec![];
+        for _ in 0..3 {
+            // the dimensions are untrusted, so nothing is allocated upfront
+            let mut matrix = vec![];
+            let mut non_zero = 0;
+            for _ in 0..num_constraints {
+                let len = read_usize(&mut reader)?;
+                let mut row = vec![];
+                for _ in 0..len {
+                    let coeff = BWField::deserialize(&mut reader)?;
+                    let index = read_usize(&mut reader)?;
+                    if index >= num_variables {
+                        return Err(SerializationError::InvalidData);
+                    }
+                    row.push((coeff, index));
+                }
+                non_zero += len;
+                matrix.push(row);
+            }
+            matrices.push(matrix);
+            num_non_zero.push(non_zero);
+        }
+        let c = matrices.pop().expect("3 matrices were read");
+        let b = matrices.pop().expect("3 matrices were read");
+        let a = matrices.pop().expect("3 matrices were read");
+
+        Ok(Self {
+            shape,
+            matrices: Arc::new(ConstraintMatrices {
+                num_instance_variables,
+                num_witness_variables,
+                num_constraints,
+                a_num_non_zero: num_non_zero[0],
+                b_num_non_zero: num_non_zero[1],
+                c_num_non_zero: num_non_zero[2],
+                a,
+                b,
+                c,
+            }),
+        })
+    }
+}
+
+fn read_usize<R: Read>(reader: &mut R) -> Result<usize, SerializationError> {
+    Ok(u64::deserialize(reader)? as usize)
+}
+
+/// Circuit which allocates the variables and enforces the constraints of a `CircuitSnapshot`
+pub struct SnapshotCircuit(Arc<ConstraintMatrices<BWField>>);
+
+impl ConstraintSynthesizer<BWField> for SnapshotCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<BWField>) -> Result<(), SynthesisError> {
+        let matrices = &self.0;
+        // the constant `One` is the first instance variable
+        for _ in 1..matrices.num_instance_variables {
+            cs.new_input_variable(|| Err(SynthesisError::AssignmentMissing))?;
+        }
+        for _ in 0..matrices.num_witness_variables {
+            cs.new_witness_variable(|| Err(SynthesisError::AssignmentMissing))?;
+        }
+
+        let num_instance_variables = matrices.num_instance_variables;
+        let to_lc = |row: &[(BWField, usize)]| {
+            LinearCombination(
+                row.iter()
+                    .map(|(coeff, index)| {
+                        let variable = if *index == 0 {
+                            Variable::One
+                        } else if *index < num_instance_variables {
+                            Variable::Instance(*index)
+                        } else {
+                            Variable::Witness(index - num_instance_variables)
+                        };
+                        (*coeff, variable)
+                    })
+                    .collect(),
+            )
+        };
+        for ((a, b), c) in matrices.a.iter().zip(&matrices.b).zip(&matrices.c) {
+            cs.enforce_constraint(to_lc(a), to_lc(b), to_lc(c))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::setup::{trusted_setup, trusted_setup_from_snapshot};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let snapshot = CircuitShape::new(3, 2).synthesize_once().unwrap();
+        let mut bytes = vec![];
+        snapshot.write(&mut bytes).unwrap();
+
+        let read = CircuitSnapshot::read(&bytes[..]).unwrap();
+        assert_eq!(read.shape(), snapshot.shape());
+        assert_eq!(read.matrices().a, snapshot.matrices().a);
+        assert_eq!(read.matrices().c, snapshot.matrices().c);
+        assert_eq!(
+            read.matrices().b_num_non_zero,
+            snapshot.matrices().b_num_non_zero
+        );
+
+        assert!(CircuitSnapshot::read(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn replayed_setup_matches_synthesized_setup() {
+        let shape = CircuitShape {
+            num_validators: 3,
+            num_epochs: 2,
+            maximum_non_signers: 1,
+        };
+        let snapshot = shape.synthesize_once().unwrap();
+
+        let rng = &mut XorShiftRng::seed_from_u64(1);
+        let expected = trusted_setup(3, 2, 1, rng, false).unwrap();
+        let rng = &mut XorShiftRng::seed_from_u64(1);
+        let replayed = trusted_setup_from_snapshot(&snapshot, rng).unwrap();
+
+        assert_eq!(replayed.epochs.vk, expected.epochs.vk);
+        assert!(replayed.hash_to_bits.is_none());
+    }
+}
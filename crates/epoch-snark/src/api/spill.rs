@@ -0,0 +1,234 @@
+//! Proving with the witness spilled to disk
+//!
+//! `create_proof_no_zk` keeps the constraint system, the full assignment and the coefficients
+//! of the quotient polynomial `h` in memory during all of its multi-scalar multiplications,
+//! along with a big integer copy of the assignment and of `h`. For the largest circuits (e.g.
+//! 150 validators and 180 epochs) this exceeds the memory of common machines.
+//!
+//! `create_proof_no_zk_spilled` moves the assignment out of the constraint system and frees the
+//! constraint system right after the synthesis, keeping only its matrices. The assignment is
+//! written to an anonymous temporary file before the QAP reduction (see `qap`), which
+//! evaluates the matrices against the (single, uncopied) assignment and then frees both of
+//! them before its FFTs, so that only the evaluations of the constraints are in memory while
+//! `h` is computed.
+//! `h` is then spilled to the same file, and each multi-scalar multiplication streams its
+//! scalars back from the file in chunks of `CHUNK_SIZE`, reading the file sequentially. This
+//! trades disk I/O (about 48 bytes per variable and constraint) for the memory of the
+//! constraint system and of the assignment, and produces the same proofs as
+//! `create_proof_no_zk`.
+use super::{erasure::zeroize, prover::ProverError, qap::witness_map};
+use algebra::{
+    msm::VariableBaseMSM, AffineCurve, FromBytes, PairingEngine, PrimeField, ProjectiveCurve,
+    ToBytes, Zero,
+};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use groth16::{Parameters, Proof};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::Path,
+};
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// The number of scalars read back from the spill file for each multi-scalar multiplication
+const CHUNK_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Error)]
+/// Error raised while proving with the witness spilled to disk
+pub enum WitnessSpillError {
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Prover Error: {0}")]
+    Prover(#[from] ProverError),
+}
+
+impl ToErrorCode for WitnessSpillError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            WitnessSpillError::ZexeSynthesisError(e) => e.error_code(),
+            WitnessSpillError::IoError(e) => e.error_code(),
+            WitnessSpillError::Prover(e) => e.error_code(),
+        }
+    }
+}
+
+/// Same as `create_proof_no_zk`, but with the assignment spilled to a temporary file in `dir`
+/// during the multi-scalar multiplications, see the module documentation
+pub(crate) fn create_proof_no_zk_spilled<E, C>(
+    circuit: C,
+    params: &Parameters<E>,
+    dir: &Path,
+) -> Result<Proof<E>, WitnessSpillError>
+where
+    E: PairingEngine,
+    C: ConstraintSynthesizer<E::Fr>,
+{
+    let cs = ConstraintSystem::new_ref();
+    circuit.generate_constraints(cs.clone())?;
+    cs.inline_all_lcs();
+    let matrices = cs.to_matrices().ok_or(SynthesisError::AssignmentMissing)?;
+    let (instance, witness) = {
+        let mut cs = cs.borrow_mut().ok_or(SynthesisError::MissingCS)?;
+        (
+            std::mem::take(&mut cs.instance_assignment),
+            std::mem::take(&mut cs.witness_assignment),
+        )
+    };
+    // the matrices are all that is left to prove
+    drop(cs);
+
+    // the constant 1 is not spilled, its bases are added directly
+    let mut spill = SpillFile::<E::Fr>::new(dir)?;
+    spill.write(instance[1..].iter().chain(&witness))?;
+    let (num_inputs, num_witnesses) = (instance.len() - 1, witness.len());
+    debug!("spilled the assignment of {} variables", spill.len);
+
+    let mut h = witness_map(matrices, instance, witness)?;
+    spill.write(h.iter())?;
+    zeroize(&mut h);
+    drop(h);
+    info!(
+        "spilled {} scalars ({} bytes) to disk",
+        spill.len,
+        spill.len * spill.scalar_size
+    );
+
+    let g_a = params.vk.alpha_g1.into_projective()
+        + &params.a_query[0].into_projective()
+        + &spill.msm(&params.a_query[1..], 0)?;
+    let g_b = params.vk.beta_g2.into_projective()
+        + &params.b_g2_query[0].into_projective()
+        + &spill.msm(&params.b_g2_query[1..], 0)?;
+    let g_c = spill.msm(&params.l_query, num_inputs)?
+        + &spill.msm(&params.h_query, num_inputs + num_witnesses)?;
+
+    Ok(Proof {
+        a: g_a.into_affine(),
+        b: g_b.into_affine(),
+        c: g_c.into_affine(),
+    })
+}
+
+/// Scalars written sequentially to an anonymous temporary file as big integers, which is
+/// overwritten with zeros when dropped
+struct SpillFile<F: PrimeField> {
+    file: File,
+    /// The number of scalars written
+    len: usize,
+    /// The size of a serialized scalar
+    scalar_size: usize,
+    _field: PhantomData<F>,
+}
+
+impl<F: PrimeField> SpillFile<F> {
+    fn new(dir: &Path) -> io::Result<Self> {
+        let mut zero = vec![];
+        F::zero().into_repr().write(&mut zero)?;
+        Ok(Self {
+            file: tempfile::tempfile_in(dir)?,
+            len: 0,
+            scalar_size: zero.len(),
+            _field: PhantomData,
+        })
+    }
+
+    /// Appends the scalars
+    fn write<'a>(&mut self, scalars: impl Iterator<Item = &'a F>) -> io::Result<()> {
+        self.file
+            .seek(SeekFrom::Start((self.len * self.scalar_size) as u64))?;
+        let mut writer = BufWriter::new(&mut self.file);
+        for scalar in scalars {
+            scalar.into_repr().write(&mut writer)?;
+            self.len += 1;
+        }
+        writer.flush()
+    }
+
+    /// Computes the multi-scalar multiplication of the bases with the scalars starting at
+    /// `offset`, one chunk of scalars at a time
+    fn msm<G: AffineCurve<ScalarField = F>>(
+        &mut self,
+        bases: &[G],
+        offset: usize,
+    ) -> io::Result<G::Projective> {
+        if offset + bases.len() > self.len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        debug!("msm of {} scalars from offset {}", bases.len(), offset);
+        self.file
+            .seek(SeekFrom::Start((offset * self.scalar_size) as u64))?;
+        let mut reader = BufReader::new(&mut self.file);
+        let mut result = G::Projective::zero();
+        let mut scalars = Vec::with_capacity(CHUNK_SIZE.min(bases.len()));
+        for bases in bases.chunks(CHUNK_SIZE) {
+            scalars.clear();
+            for _ in 0..bases.len() {
+                scalars.push(F::BigInt::read(&mut reader)?);
+            }
+            result += &VariableBaseMSM::multi_scalar_mul(bases, &scalars);
+        }
+        Ok(result)
+    }
+}
+
+impl<F: PrimeField> Drop for SpillFile<F> {
+    fn drop(&mut self) {
+        // best effort, the file is deleted once closed anyway
+        let zeros = vec![0u8; CHUNK_SIZE * self.scalar_size];
+        if self.file.seek(SeekFrom::Start(0)).is_ok() {
+            let mut remaining = self.len * self.scalar_size;
+            while remaining > 0 {
+                let len = remaining.min(zeros.len());
+                if self.file.write_all(&zeros[..len]).is_err() {
+                    return;
+                }
+                remaining -= len;
+            }
+            let _ = self.file.sync_data();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{test_helpers::Cubes, BWCurve, BWField};
+    use algebra::UniformRand;
+    use groth16::{create_proof_no_zk, generate_random_parameters};
+
+    #[test]
+    fn spilled_proofs_match() {
+        let rng = &mut rand::thread_rng();
+        let roots = (0..16).map(|_| BWField::rand(rng)).collect::<Vec<_>>();
+        let params =
+            generate_random_parameters::<BWCurve, _, _>(Cubes(roots.clone()), rng).unwrap();
+
+        let dir = std::env::temp_dir();
+        let spilled = create_proof_no_zk_spilled(Cubes(roots.clone()), &params, &dir).unwrap();
+        let proof = create_proof_no_zk(Cubes(roots), &params).unwrap();
+        assert_eq!(spilled, proof);
+    }
+
+    #[test]
+    fn reads_back_chunks() {
+        let rng = &mut rand::thread_rng();
+        let scalars = (0..CHUNK_SIZE + 3)
+            .map(|_| BWField::rand(rng))
+            .collect::<Vec<_>>();
+        let bases = vec![<BWCurve as PairingEngine>::G1Affine::prime_subgroup_generator(); 5];
+        let mut spill = SpillFile::new(&std::env::temp_dir()).unwrap();
+        spill.write(scalars.iter()).unwrap();
+
+        let offset = CHUNK_SIZE - 2;
+        let expected = bases[0]
+            .into_projective()
+            .mul(scalars[offset..].iter().sum::<BWField>());
+        assert_eq!(spill.msm(&bases, offset).unwrap(), expected);
+        assert!(spill.msm(&bases, offset + 1).is_err());
+    }
+}
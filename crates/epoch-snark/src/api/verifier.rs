@@ -1,11 +1,22 @@
-use super::{BWCurve, BWField, BWFrParams};
+use super::{BWCurve, BWField, CIRCUIT_VERSION};
+use crate::aux_data_commitment::AuxDataCommitment;
+use crate::bitmap_commitment::BitmapCommitment;
 use crate::encoding::EncodingError;
 use crate::epoch_block::{hash_first_last_epoch_block, EpochBlock};
-use crate::gadgets::pack;
+use crate::gadgets::{compress_public_inputs, extra_public_inputs, ExtraPublicInput};
+use crate::randomness_beacon::RandomnessBeacon;
+use crate::scalars::OuterScalar;
+use algebra::{
+    msm::VariableBaseMSM, AffineCurve, Field, PairingEngine, PrimeField, ProjectiveCurve,
+    UniformRand, Zero,
+};
+use bls_crypto::{ErrorCode, PublicKey, ToErrorCode};
+use bls_gadgets::bits::bits_le_to_bytes_le;
 use groth16::{prepare_verifying_key, verify_proof, Proof, VerifyingKey};
 use r1cs_core::SynthesisError;
+use std::{sync::Arc, time::Instant};
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Debug, Error)]
 /// Error raised while verifying the SNARK proof
@@ -16,6 +27,135 @@ pub enum VerificationError {
     ZexeSynthesisError(#[from] SynthesisError),
     #[error("Encoding Error: {0}")]
     EpochEncodingError(#[from] EncodingError),
+    #[error("The proof's points are not in the prime order subgroup")]
+    ProofNotInSubgroup,
+    #[error("Verification deadline exceeded before {0:?}")]
+    Timeout(VerificationStage),
+    #[error("The proof's CRH->XOF hashing mode does not match the verifying keys")]
+    HashingModeMismatch,
+    #[error("The proof was generated with a different verifying key")]
+    VerifyingKeyMismatch,
+    #[error("Invalid validator set commitment length: {0} bytes")]
+    ValidatorCommitmentLength(usize),
+}
+
+impl ToErrorCode for VerificationError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            VerificationError::VerificationFailed => ErrorCode::InvalidProof,
+            VerificationError::ZexeSynthesisError(e) => e.error_code(),
+            VerificationError::EpochEncodingError(e) => e.error_code(),
+            VerificationError::ProofNotInSubgroup => ErrorCode::NotInSubgroup,
+            VerificationError::Timeout(_) => ErrorCode::Timeout,
+            VerificationError::HashingModeMismatch
+            | VerificationError::VerifyingKeyMismatch
+            | VerificationError::ValidatorCommitmentLength(_) => ErrorCode::InvalidArgument,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The stages of `verify_with_deadline`, in the order in which they are run
+pub enum VerificationStage {
+    /// Hashing the first and last epochs to the public inputs
+    PublicInputs,
+    /// Checking that the proof's points are on the curve and in the prime order subgroup
+    SubgroupChecks,
+    /// Preparing the verifying key and evaluating the pairings
+    Pairings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The epoch field which differs between two consecutive links of a proof chain
+pub enum LinkMismatch {
+    Index,
+    AggregatePublicKey,
+    Entropy,
+}
+
+#[derive(Debug, Error)]
+/// Error raised while verifying a chain of SNARK proofs
+pub enum ProofChainError {
+    #[error("Empty proof chain")]
+    EmptyChain,
+    #[error("Got {proofs} proofs but {summaries} first/last epoch pairs")]
+    LengthMismatch { proofs: usize, summaries: usize },
+    #[error("Proof {link} does not start at the last epoch of proof {}: {reason:?} mismatch", .link - 1)]
+    Disconnected { link: usize, reason: LinkMismatch },
+    #[error("Proof {link} is invalid: {error}")]
+    InvalidProof {
+        link: usize,
+        error: VerificationError,
+    },
+}
+
+impl ToErrorCode for ProofChainError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ProofChainError::EmptyChain | ProofChainError::LengthMismatch { .. } => {
+                ErrorCode::InvalidArgument
+            }
+            ProofChainError::Disconnected { .. } => ErrorCode::InvalidProof,
+            ProofChainError::InvalidProof { error, .. } => error.error_code(),
+        }
+    }
+}
+
+/// Verifies a sequence of proofs over consecutive epoch ranges (e.g. `0..k, k..m, m..n`).
+/// `summaries` contains the first and last epoch of each proof. Besides verifying each proof,
+/// it checks that each proof starts at the epoch where the previous one ended, i.e. they have
+/// the same index, aggregate public key and entropy. On failure, the index of the first
+/// failing link is returned.
+pub fn verify_proof_chain(
+    vk: &VerifyingKey<BWCurve>,
+    proofs: &[Proof<BWCurve>],
+    summaries: &[(EpochBlock, EpochBlock)],
+) -> Result<(), ProofChainError> {
+    if proofs.is_empty() {
+        return Err(ProofChainError::EmptyChain);
+    }
+    if proofs.len() != summaries.len() {
+        return Err(ProofChainError::LengthMismatch {
+            proofs: proofs.len(),
+            summaries: summaries.len(),
+        });
+    }
+
+    // the linkage checks are cheap, so they are done before verifying any proof
+    for (i, window) in summaries.windows(2).enumerate() {
+        let (_, previous_last) = &window[0];
+        let (next_first, _) = &window[1];
+        if let Some(reason) = link_mismatch(previous_last, next_first) {
+            return Err(ProofChainError::Disconnected {
+                link: i + 1,
+                reason,
+            });
+        }
+    }
+
+    for (link, (proof, (first_epoch, last_epoch))) in proofs.iter().zip(summaries).enumerate() {
+        info!("Verifying proof {} of the chain", link);
+        verify(vk, first_epoch, last_epoch, proof)
+            .map_err(|error| ProofChainError::InvalidProof { link, error })?;
+    }
+
+    Ok(())
+}
+
+fn link_mismatch(previous: &EpochBlock, next: &EpochBlock) -> Option<LinkMismatch> {
+    if previous.index != next.index {
+        Some(LinkMismatch::Index)
+    } else if PublicKey::aggregate(&previous.new_public_keys)
+        != PublicKey::aggregate(&next.new_public_keys)
+    {
+        Some(LinkMismatch::AggregatePublicKey)
+    } else if previous.epoch_entropy != next.epoch_entropy
+        || previous.parent_entropy != next.parent_entropy
+    {
+        Some(LinkMismatch::Entropy)
+    } else {
+        None
+    }
 }
 
 /// Given the Verifying Key for the circuit and the SNARK proof and _only the first and last epoch_,
@@ -27,10 +167,248 @@ pub fn verify(
     proof: &Proof<BWCurve>,
 ) -> Result<(), VerificationError> {
     info!("Verifying proof");
-    // Hash the first-last block together
-    let hash = hash_first_last_epoch_block(first_epoch, last_epoch)?;
-    // packs them
-    let public_inputs = pack::<BWField, BWFrParams>(&hash)?;
+    let public_inputs = public_inputs(first_epoch, last_epoch)?;
+    verify_with_inputs(vk, &public_inputs, proof)
+}
+
+/// Same as `verify`, but also checks the proof against the commitment to the signed bitmaps
+/// of the proven epochs, after which they can be opened with `BitmapCommitment::open_bitmap`.
+/// The circuit only exposes this commitment when built with the `bitmap-commitment` feature.
+pub fn verify_with_bitmap_commitment(
+    vk: &VerifyingKey<BWCurve>,
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+    bitmap_commitment: &BitmapCommitment,
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying proof with bitmap commitment");
+    let mut public_inputs = public_inputs(first_epoch, last_epoch)?;
+    public_inputs.extend(bitmap_commitment.public_inputs());
+    verify_with_inputs(vk, &public_inputs, proof)
+}
+
+/// Same as `verify`, but also checks the proof against the commitment to the auxiliary data
+/// of the proven epochs, after which it can be opened with `AuxDataCommitment::open`. The
+/// circuit only exposes this commitment when built with the `epoch-aux-data` feature. If it
+/// was also built with the `bitmap-commitment` feature, the bitmap commitment must be provided.
+pub fn verify_with_aux_data_commitment(
+    vk: &VerifyingKey<BWCurve>,
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+    bitmap_commitment: Option<&BitmapCommitment>,
+    aux_data_commitment: &AuxDataCommitment,
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying proof with auxiliary data commitment");
+    let mut public_inputs = public_inputs(first_epoch, last_epoch)?;
+    if let Some(bitmap_commitment) = bitmap_commitment {
+        public_inputs.extend(bitmap_commitment.public_inputs());
+    }
+    public_inputs.extend(aux_data_commitment.public_inputs());
+    verify_with_inputs(vk, &public_inputs, proof)
+}
+
+/// Same as `verify`, but also checks the proof against the commitment to the randomness
+/// outputs of the proven epochs, after which they can be consumed with
+/// `RandomnessBeacon::output`. The circuit only exposes this commitment when built with the
+/// `randomness-beacon` feature. The commitments of the `bitmap-commitment` and `epoch-aux-data`
+/// features must be provided if the circuit was built with them.
+pub fn verify_with_randomness_beacon(
+    vk: &VerifyingKey<BWCurve>,
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+    bitmap_commitment: Option<&BitmapCommitment>,
+    aux_data_commitment: Option<&AuxDataCommitment>,
+    randomness_beacon: &RandomnessBeacon,
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying proof with randomness beacon");
+    let mut public_inputs = public_inputs(first_epoch, last_epoch)?;
+    if let Some(bitmap_commitment) = bitmap_commitment {
+        public_inputs.extend(bitmap_commitment.public_inputs());
+    }
+    if let Some(aux_data_commitment) = aux_data_commitment {
+        public_inputs.extend(aux_data_commitment.public_inputs());
+    }
+    public_inputs.extend(randomness_beacon.public_inputs());
+    verify_with_inputs(vk, &public_inputs, proof)
+}
+
+/// Same as `verify`, but for a circuit with extra public inputs registered with
+/// `ValidatorSetUpdate::with_extra_inputs`. `feature_inputs` are the public inputs of the
+/// commitments of the enabled features, in the order of `verify_with_randomness_beacon`
+/// (e.g. `bitmap_commitment.public_inputs()`), and `extra_inputs` are the registered inputs
+/// with their values.
+pub fn verify_with_extra_inputs(
+    vk: &VerifyingKey<BWCurve>,
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+    feature_inputs: &[OuterScalar],
+    extra_inputs: &[Arc<dyn ExtraPublicInput>],
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying proof with {} extra inputs", extra_inputs.len());
+    let mut public_inputs = public_inputs(first_epoch, last_epoch)?;
+    public_inputs.extend_from_slice(feature_inputs);
+    public_inputs.extend(extra_public_inputs(extra_inputs));
+    verify_with_inputs(vk, &public_inputs, proof)
+}
+
+/// Same as `verify` for circuits built with the `blinded-validators` feature, for verifiers
+/// which only know the commitments to the validator sets of the first and last epochs (see
+/// `EpochBlock::validator_commitment`) rather than their public keys. The public keys and
+/// blindings of `first_epoch` and `last_epoch` are ignored.
+#[cfg(feature = "blinded-validators")]
+pub fn verify_with_validator_commitment(
+    vk: &VerifyingKey<BWCurve>,
+    first_epoch: &EpochBlock,
+    first_commitment: &[u8],
+    last_epoch: &EpochBlock,
+    last_commitment: &[u8],
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    info!("Verifying proof with validator set commitments");
+    for commitment in &[first_commitment, last_commitment] {
+        if commitment.len() * 8 != EpochBlock::VALIDATOR_COMMITMENT_BITS {
+            return Err(VerificationError::ValidatorCommitmentLength(
+                commitment.len(),
+            ));
+        }
+    }
+    let hash = crate::epoch_block::hash_first_last_validator_commitments(
+        first_epoch,
+        first_commitment,
+        last_epoch,
+        last_commitment,
+    )?;
+    verify_with_inputs(vk, &pack_public_inputs(&hash, first_epoch), proof)
+}
+
+/// Same as `verify`, but aborts with `VerificationError::Timeout` if `deadline` has passed
+/// before any of the verification stages is started. A stage which has started always runs
+/// to completion, so the worst case latency is bounded by the deadline plus the duration of
+/// the most expensive stage (the pairings). This allows RPC servers to bound the time spent
+/// on proofs submitted by untrusted clients.
+pub fn verify_with_deadline(
+    vk: &VerifyingKey<BWCurve>,
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+    proof: &Proof<BWCurve>,
+    deadline: Instant,
+) -> Result<(), VerificationError> {
+    info!("Verifying proof with deadline");
+    check_deadline(deadline, VerificationStage::PublicInputs)?;
+    let public_inputs = public_inputs(first_epoch, last_epoch)?;
+
+    // cheap compared to the pairings, and rejects malformed proofs before reaching them
+    check_deadline(deadline, VerificationStage::SubgroupChecks)?;
+    if !(proof.a.is_on_curve()
+        && proof.b.is_on_curve()
+        && proof.c.is_on_curve()
+        && proof.a.is_in_correct_subgroup_assuming_on_curve()
+        && proof.b.is_in_correct_subgroup_assuming_on_curve()
+        && proof.c.is_in_correct_subgroup_assuming_on_curve())
+    {
+        return Err(VerificationError::ProofNotInSubgroup);
+    }
+
+    check_deadline(deadline, VerificationStage::Pairings)?;
+    verify_with_inputs(vk, &public_inputs, proof)
+}
+
+fn check_deadline(deadline: Instant, stage: VerificationStage) -> Result<(), VerificationError> {
+    if Instant::now() > deadline {
+        warn!("verification deadline exceeded before {:?}", stage);
+        return Err(VerificationError::Timeout(stage));
+    }
+    Ok(())
+}
+
+/// Verifies many proofs for the same verifying key at once. Each proof comes with its public
+/// inputs, as returned by `public_inputs` (extended with any commitments, as in
+/// `verify_with_bitmap_commitment`).
+///
+/// The Groth16 equations of the proofs are combined with random coefficients, so that a
+/// single check with one pairing per proof plus 2 more (sharing a final exponentiation) and
+/// 2 multi-scalar multiplications verifies all of them, instead of 4 pairings per proof. If
+/// any proof is invalid the batch fails with overwhelming probability, without indicating
+/// which one, so relayers should fall back to `verify` to find it.
+pub fn verify_batch(
+    vk: &VerifyingKey<BWCurve>,
+    batch: &[(Vec<OuterScalar>, Proof<BWCurve>)],
+) -> Result<(), VerificationError> {
+    info!("Verifying a batch of {} proofs", batch.len());
+    let rng = &mut rand::thread_rng();
+    let pvk = prepare_verifying_key(vk);
+    let num_inputs = vk.gamma_abc_g1.len().saturating_sub(1);
+
+    let mut pairs = Vec::with_capacity(batch.len() + 2);
+    let mut coefficients_sum = BWField::zero();
+    // the coefficient of each base of the inputs' linear combination, across all proofs
+    let mut input_scalars = vec![BWField::zero(); num_inputs + 1];
+    let mut c_bases = Vec::with_capacity(batch.len());
+    let mut c_scalars = Vec::with_capacity(batch.len());
+    for (public_inputs, proof) in batch {
+        if cfg!(feature = "insecure_test_backend")
+            && super::insecure::is_dummy_proof(proof, public_inputs)
+        {
+            warn!("accepting an insecure dummy proof");
+            continue;
+        }
+        let public_inputs = final_public_inputs(&OuterScalar::to_fields(public_inputs));
+        if public_inputs.len() != num_inputs {
+            return Err(SynthesisError::MalformedVerifyingKey.into());
+        }
+
+        let coefficient = BWField::rand(rng);
+        coefficients_sum += &coefficient;
+        input_scalars[0] += &coefficient;
+        for (scalar, input) in input_scalars[1..].iter_mut().zip(public_inputs.iter()) {
+            *scalar += &(coefficient * input);
+        }
+        c_bases.push(proof.c);
+        c_scalars.push(coefficient.into_repr());
+        pairs.push((
+            proof.a.mul(coefficient).into_affine().into(),
+            proof.b.into(),
+        ));
+    }
+    if pairs.is_empty() {
+        return Ok(());
+    }
+
+    let input_scalars = input_scalars
+        .into_iter()
+        .map(|scalar| scalar.into_repr())
+        .collect::<Vec<_>>();
+    let inputs_acc = VariableBaseMSM::multi_scalar_mul(&vk.gamma_abc_g1, &input_scalars);
+    let c_acc = VariableBaseMSM::multi_scalar_mul(&c_bases, &c_scalars);
+    pairs.push((inputs_acc.into_affine().into(), pvk.gamma_g2_neg_pc.clone()));
+    pairs.push((c_acc.into_affine().into(), pvk.delta_g2_neg_pc.clone()));
+
+    // prod e(r_i * A_i, B_i) * e(sum r_i * inputs_i, -gamma) * e(sum r_i * C_i, -delta)
+    //   = e(alpha, beta)^(sum r_i)
+    if BWCurve::product_of_pairings(&pairs)
+        == pvk.alpha_g1_beta_g2.pow(coefficients_sum.into_repr())
+    {
+        Ok(())
+    } else {
+        Err(VerificationError::VerificationFailed)
+    }
+}
+
+fn verify_with_inputs(
+    vk: &VerifyingKey<BWCurve>,
+    public_inputs: &[OuterScalar],
+    proof: &Proof<BWCurve>,
+) -> Result<(), VerificationError> {
+    if cfg!(feature = "insecure_test_backend")
+        && super::insecure::is_dummy_proof(proof, public_inputs)
+    {
+        warn!("accepting an insecure dummy proof");
+        return Ok(());
+    }
+    let public_inputs = final_public_inputs(&OuterScalar::to_fields(public_inputs));
     // verifies the BLS proof by using the First/Last epoch as public inputs over CP
     if verify_proof(&prepare_verifying_key(vk), proof, &public_inputs)? {
         Ok(())
@@ -38,3 +416,274 @@ pub fn verify(
         Err(VerificationError::VerificationFailed)
     }
 }
+
+/// The inputs of the circuit, i.e. their Poseidon hash with the `compressed-public-inputs`
+/// feature
+fn final_public_inputs(public_inputs: &[BWField]) -> Vec<BWField> {
+    if cfg!(feature = "compressed-public-inputs") {
+        vec![compress_public_inputs(public_inputs)]
+    } else {
+        public_inputs.to_vec()
+    }
+}
+
+/// Hashes the first and last epochs together and packs the result to the circuit's
+/// public inputs. With the `chain-binding` feature, the first epoch's chain identifier is
+/// packed after them, so that a proof only verifies for the chain it was generated for. With
+/// the `circuit-version` feature, `CIRCUIT_VERSION` follows as a single field element, so that
+/// a proof only verifies for the circuit revision it was generated for.
+pub fn public_inputs(
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+) -> Result<Vec<OuterScalar>, VerificationError> {
+    let hash = hash_first_last_epoch_block(first_epoch, last_epoch)?;
+    Ok(pack_public_inputs(&hash, first_epoch))
+}
+
+/// Packs the hash of the first and last epochs, followed by the bindings, see `public_inputs`
+fn pack_public_inputs(hash: &[bool], first_epoch: &EpochBlock) -> Vec<OuterScalar> {
+    let mut inputs = OuterScalar::pack(hash);
+    if cfg!(feature = "chain-binding") {
+        let chain_id = EpochBlock::encode_chain_id(first_epoch.chain_id.as_ref());
+        inputs.extend(OuterScalar::pack(&chain_id));
+    }
+    if cfg!(feature = "circuit-version") {
+        inputs.push(OuterScalar(BWField::from(CIRCUIT_VERSION)));
+    }
+    inputs
+}
+
+/// The hash of the first and last epochs as LE bytes, i.e. the public input expected by
+/// `epoch_verifier::verify_epoch_proof`. It does not include the chain identifier or the
+/// circuit version, which circuits built with the `chain-binding` and `circuit-version`
+/// features expect as `epoch_verifier::Bindings`.
+pub fn public_input_bytes(
+    first_epoch: &EpochBlock,
+    last_epoch: &EpochBlock,
+) -> Result<Vec<u8>, VerificationError> {
+    let hash = hash_first_last_epoch_block(first_epoch, last_epoch)?;
+    Ok(bits_le_to_bytes_le(&hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{bls12_377::G2Projective, bw6_761, ProjectiveCurve, UniformRand};
+    use bls_gadgets::utils::bytes_le_to_bits_le;
+    use std::time::Duration;
+
+    fn epoch(index: u16, entropy: u8, public_keys: &[PublicKey]) -> EpochBlock {
+        EpochBlock::new(
+            index,
+            0,
+            Some(vec![entropy; EpochBlock::ENTROPY_BYTES]),
+            Some(vec![0; EpochBlock::ENTROPY_BYTES]),
+            1,
+            public_keys.len(),
+            public_keys.to_vec(),
+        )
+    }
+
+    #[test]
+    fn detects_broken_links() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..3)
+            .map(|_| PublicKey::from(G2Projective::rand(rng)))
+            .collect::<Vec<_>>();
+        let other_keys = (0..3)
+            .map(|_| PublicKey::from(G2Projective::rand(rng)))
+            .collect::<Vec<_>>();
+        let vk = VerifyingKey::<BWCurve>::default();
+        let proofs = vec![Proof::<BWCurve>::default(); 3];
+
+        let check = |summaries: &[(EpochBlock, EpochBlock)]| match verify_proof_chain(
+            &vk, &proofs, summaries,
+        )
+        .unwrap_err()
+        {
+            ProofChainError::Disconnected { link, reason } => Some((link, reason)),
+            _ => None,
+        };
+
+        let mut summaries = vec![
+            (epoch(0, 0, &keys), epoch(10, 1, &keys)),
+            (epoch(10, 1, &keys), epoch(20, 2, &keys)),
+            (epoch(20, 2, &keys), epoch(30, 3, &keys)),
+        ];
+        // a fully linked chain proceeds to verifying the (invalid) proofs
+        assert!(matches!(
+            verify_proof_chain(&vk, &proofs, &summaries),
+            Err(ProofChainError::InvalidProof { link: 0, .. })
+        ));
+
+        summaries[2].0 = epoch(21, 2, &keys);
+        assert_eq!(check(&summaries), Some((2, LinkMismatch::Index)));
+
+        summaries[2].0 = epoch(20, 2, &other_keys);
+        assert_eq!(
+            check(&summaries),
+            Some((2, LinkMismatch::AggregatePublicKey))
+        );
+
+        summaries[1].0 = epoch(10, 5, &keys);
+        assert_eq!(check(&summaries), Some((1, LinkMismatch::Entropy)));
+
+        assert!(matches!(
+            verify_proof_chain(&vk, &proofs[..2], &summaries),
+            Err(ProofChainError::LengthMismatch {
+                proofs: 2,
+                summaries: 3
+            })
+        ));
+        assert!(matches!(
+            verify_proof_chain(&vk, &[], &[]),
+            Err(ProofChainError::EmptyChain)
+        ));
+    }
+
+    #[test]
+    fn deadline_aborts_between_stages() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..3)
+            .map(|_| PublicKey::from(G2Projective::rand(rng)))
+            .collect::<Vec<_>>();
+        let (first, last) = (epoch(0, 0, &keys), epoch(10, 1, &keys));
+        let vk = VerifyingKey::<BWCurve>::default();
+        let proof = Proof::<BWCurve> {
+            a: bw6_761::G1Projective::rand(rng).into_affine(),
+            b: bw6_761::G2Projective::rand(rng).into_affine(),
+            c: bw6_761::G1Projective::rand(rng).into_affine(),
+        };
+
+        let expired = Instant::now() - Duration::from_secs(1);
+        let err = verify_with_deadline(&vk, &first, &last, &proof, expired).unwrap_err();
+        assert!(matches!(
+            err,
+            VerificationError::Timeout(VerificationStage::PublicInputs)
+        ));
+        assert_eq!(err.error_code(), ErrorCode::Timeout);
+
+        // with enough time, the (invalid) proof reaches the pairings, where it is rejected
+        // against the dummy verifying key
+        let deadline = Instant::now() + Duration::from_secs(600);
+        assert!(matches!(
+            verify_with_deadline(&vk, &first, &last, &proof, deadline),
+            Err(VerificationError::VerificationFailed)
+                | Err(VerificationError::ZexeSynthesisError(_))
+        ));
+    }
+
+    #[test]
+    fn packs_like_the_lightweight_verifier() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..3)
+            .map(|_| PublicKey::from(G2Projective::rand(rng)))
+            .collect::<Vec<_>>();
+        let first = epoch(0, 0, &keys).with_chain_id([7; 32]);
+        let last = epoch(10, 1, &keys);
+
+        let bytes = public_input_bytes(&first, &last).unwrap();
+        assert_eq!(bytes.len(), epoch_verifier::PUBLIC_INPUT_BYTES);
+        let bindings = epoch_verifier::Bindings {
+            chain_id: if cfg!(feature = "chain-binding") {
+                first.chain_id
+            } else {
+                None
+            },
+            circuit_version: if cfg!(feature = "circuit-version") {
+                Some(CIRCUIT_VERSION)
+            } else {
+                None
+            },
+        };
+        assert_eq!(
+            epoch_verifier::pack_all_public_inputs(&bytes, &bindings, &[]).unwrap(),
+            OuterScalar::to_fields(&public_inputs(&first, &last).unwrap())
+        );
+
+        let beacon = RandomnessBeacon::new(&[], 0);
+        assert_eq!(
+            vec![epoch_verifier::pack_commitment(&beacon.commitment())],
+            OuterScalar::to_fields(&beacon.public_inputs())
+        );
+        let mut commitment = [0; epoch_verifier::COMMITMENT_BYTES];
+        commitment[0] = 0b101;
+        commitment[1] = 1;
+        commitment[31] = 0x80;
+        assert_eq!(
+            vec![epoch_verifier::pack_commitment(&commitment)],
+            OuterScalar::pack_fields(&bytes_le_to_bits_le(&commitment, 256))
+        );
+    }
+
+    /// Proves knowledge of a square root of the first input
+    struct SquareRoot {
+        root: BWField,
+        public_inputs: Vec<BWField>,
+    }
+
+    impl r1cs_core::ConstraintSynthesizer<BWField> for SquareRoot {
+        fn generate_constraints(
+            self,
+            cs: r1cs_core::ConstraintSystemRef<BWField>,
+        ) -> Result<(), SynthesisError> {
+            use r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+            let inputs = final_public_inputs(&self.public_inputs)
+                .into_iter()
+                .map(|input| FpVar::new_input(cs.clone(), || Ok(input)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let root = FpVar::new_witness(cs.clone(), || Ok(self.root))?;
+            let square = FpVar::new_witness(cs, || Ok(self.root.square()))?;
+            (&root * &root).enforce_equal(&square)?;
+            if !cfg!(feature = "compressed-public-inputs") {
+                square.enforce_equal(&inputs[0])?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn verifies_batches() {
+        let rng = &mut rand::thread_rng();
+        let circuit = |root: BWField| SquareRoot {
+            root,
+            public_inputs: vec![root.square(), BWField::from(7u64)],
+        };
+        let params =
+            groth16::generate_random_parameters::<BWCurve, _, _>(circuit(BWField::from(1u64)), rng)
+                .unwrap();
+        let batch = (1..4u64)
+            .map(|root| {
+                let root = BWField::from(root);
+                let proof = groth16::create_random_proof(circuit(root), &params, rng).unwrap();
+                let inputs = vec![root.square(), BWField::from(7u64)];
+                (
+                    inputs.into_iter().map(OuterScalar).collect::<Vec<_>>(),
+                    proof,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        verify_batch(&params.vk, &batch).unwrap();
+        verify_batch(&params.vk, &batch[..1]).unwrap();
+        verify_batch(&params.vk, &[]).unwrap();
+
+        // a single proof with the wrong inputs fails the whole batch
+        let mut invalid = batch.clone();
+        invalid[1].0 = batch[2].0.clone();
+        assert!(matches!(
+            verify_batch(&params.vk, &invalid),
+            Err(VerificationError::VerificationFailed)
+        ));
+        let mut invalid = batch.clone();
+        invalid[2].1 = batch[0].1.clone();
+        assert!(matches!(
+            verify_batch(&params.vk, &invalid),
+            Err(VerificationError::VerificationFailed)
+        ));
+
+        let mut malformed = batch;
+        malformed[0].0.pop();
+        assert!(verify_batch(&params.vk, &malformed).is_err());
+    }
+}
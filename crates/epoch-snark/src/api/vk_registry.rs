@@ -0,0 +1,350 @@
+//! Verifying Key Registry
+//!
+//! A verifier which accepts proofs for several validator counts, or across an upgrade of the
+//! epochs circuit, needs one set of verifying keys per circuit. A `VkRegistry` stores them
+//! keyed by a `VkId`, i.e. the validator count, the circuit version, the cargo features which
+//! change the circuit and whether the CRH->XOF hashes are proven in BLS12-377, and selects the
+//! keys of a `ProofBundle` from its epochs.
+//!
+//! Migrating to a new circuit version is done by inserting its keys and, once proofs of the
+//! previous version must no longer be accepted, retiring it. Retired versions are remembered,
+//! so that their keys cannot be inserted again by mistake, and persisted with the registry.
+use super::{bundle::ProofBundle, keys::CombinedVerifyingKey, VerificationError};
+use crate::encoding::EncodingError;
+
+use algebra::serialize::SerializationError;
+use bls_crypto::{ErrorCode, ToErrorCode};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+};
+use thiserror::Error;
+use tracing::info;
+
+/// The version of the epochs circuit generated by this crate. It is increased whenever the
+/// circuit changes in a way which invalidates the parameters generated for it.
+///
+/// Version 2 exposes the version itself as a native field element rather than as packed bits.
+pub const CIRCUIT_VERSION: u32 = 2;
+
+/// The cargo features of this build which change the epochs circuit (and therefore its keys),
+/// as a bitmask in the order `compat`, `bft-threshold`, `bitmap-commitment`, `epoch-aux-data`,
+/// `chain-binding`, `circuit-version`, `blinded-validators`, `randomness-beacon`,
+/// `entropy-256`, `compressed-public-inputs` and `canonical-validators`
+pub const CIRCUIT_FEATURES: u32 = (cfg!(feature = "compat") as u32)
+    | ((cfg!(feature = "bft-threshold") as u32) << 1)
+    | ((cfg!(feature = "bitmap-commitment") as u32) << 2)
+    | ((cfg!(feature = "epoch-aux-data") as u32) << 3)
+    | ((cfg!(feature = "chain-binding") as u32) << 4)
+    | ((cfg!(feature = "circuit-version") as u32) << 5)
+    | ((cfg!(feature = "blinded-validators") as u32) << 6)
+    | ((cfg!(feature = "randomness-beacon") as u32) << 7)
+    | ((cfg!(feature = "entropy-256") as u32) << 8)
+    | ((cfg!(feature = "compressed-public-inputs") as u32) << 9)
+    | ((cfg!(feature = "canonical-validators") as u32) << 10);
+
+/// The version of the format written by `VkRegistry::write`
+const REGISTRY_FORMAT_VERSION: u8 = 2;
+
+#[derive(Debug, Error)]
+/// Error raised while selecting keys from a `VkRegistry` or verifying with them
+pub enum VkRegistryError {
+    #[error("circuit version {0} is not supported")]
+    UnknownCircuitVersion(u32),
+    #[error("circuit version {0} has been retired")]
+    RetiredCircuitVersion(u32),
+    #[error("no verifying keys for {0:?}")]
+    UnknownKey(VkId),
+    #[error("Verification Error: {0}")]
+    VerificationError(#[from] VerificationError),
+}
+
+impl ToErrorCode for VkRegistryError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            VkRegistryError::VerificationError(e) => e.error_code(),
+            _ => ErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// The circuit which a set of verifying keys was generated for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VkId {
+    /// The maximum number of validators of the epochs
+    pub num_validators: usize,
+    /// The version of the epochs circuit, see `CIRCUIT_VERSION`
+    pub circuit_version: u32,
+    /// The circuit changing features the circuit was built with, see `CIRCUIT_FEATURES`
+    pub circuit_features: u32,
+    /// Whether the CRH->XOF hashes are proven in BLS12-377
+    pub hashes_in_bls12_377: bool,
+}
+
+impl VkId {
+    /// The keys which the bundle's proof must be verified with
+    pub fn of_bundle(bundle: &ProofBundle) -> Self {
+        Self {
+            num_validators: bundle.first_epoch.maximum_validators,
+            circuit_version: bundle.circuit_version,
+            circuit_features: bundle.circuit_features,
+            hashes_in_bls12_377: bundle.hashes_in_bls12_377,
+        }
+    }
+}
+
+/// Verifying keys of multiple circuits, see the module documentation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VkRegistry {
+    keys: BTreeMap<VkId, CombinedVerifyingKey>,
+    retired: BTreeSet<u32>,
+}
+
+impl VkRegistry {
+    /// Instantiates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the keys of the circuit version built with this build's `CIRCUIT_FEATURES`
+    /// for epochs with `num_validators` validators, returning the keys they replace, if any.
+    /// Fails if the version has been retired.
+    pub fn insert(
+        &mut self,
+        num_validators: usize,
+        circuit_version: u32,
+        keys: CombinedVerifyingKey,
+    ) -> Result<Option<CombinedVerifyingKey>, VkRegistryError> {
+        self.insert_with_features(num_validators, circuit_version, CIRCUIT_FEATURES, keys)
+    }
+
+    /// Same as `insert`, for keys of a circuit built with other features, e.g. by another
+    /// build of this crate
+    pub fn insert_with_features(
+        &mut self,
+        num_validators: usize,
+        circuit_version: u32,
+        circuit_features: u32,
+        keys: CombinedVerifyingKey,
+    ) -> Result<Option<CombinedVerifyingKey>, VkRegistryError> {
+        if self.retired.contains(&circuit_version) {
+            return Err(VkRegistryError::RetiredCircuitVersion(circuit_version));
+        }
+        let id = VkId {
+            num_validators,
+            circuit_version,
+            circuit_features,
+            hashes_in_bls12_377: keys.hash_vk().is_some(),
+        };
+        Ok(self.keys.insert(id, keys))
+    }
+
+    /// Returns the keys registered for the circuit, if any
+    pub fn get(&self, id: &VkId) -> Option<&CombinedVerifyingKey> {
+        self.keys.get(id)
+    }
+
+    /// The identifiers of the registered keys, in increasing order
+    pub fn ids(&self) -> impl Iterator<Item = &VkId> {
+        self.keys.keys()
+    }
+
+    /// The highest circuit version with registered keys, if any
+    pub fn latest_version(&self) -> Option<u32> {
+        self.keys.keys().map(|id| id.circuit_version).max()
+    }
+
+    /// Removes the keys of the circuit version and rejects its proofs from now on. Returns
+    /// the number of removed keys.
+    pub fn retire(&mut self, circuit_version: u32) -> usize {
+        info!("Retiring circuit version {}", circuit_version);
+        let ids = self
+            .keys
+            .keys()
+            .filter(|id| id.circuit_version == circuit_version)
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in &ids {
+            self.keys.remove(id);
+        }
+        self.retired.insert(circuit_version);
+        ids.len()
+    }
+
+    /// Whether the circuit version has been retired
+    pub fn is_retired(&self, circuit_version: u32) -> bool {
+        self.retired.contains(&circuit_version)
+    }
+
+    /// Returns the keys which the bundle's proof must be verified with
+    pub fn select(&self, bundle: &ProofBundle) -> Result<&CombinedVerifyingKey, VkRegistryError> {
+        let id = VkId::of_bundle(bundle);
+        if self.retired.contains(&id.circuit_version) {
+            return Err(VkRegistryError::RetiredCircuitVersion(id.circuit_version));
+        }
+        if !self
+            .keys
+            .keys()
+            .any(|registered| registered.circuit_version == id.circuit_version)
+        {
+            return Err(VkRegistryError::UnknownCircuitVersion(id.circuit_version));
+        }
+        self.keys.get(&id).ok_or(VkRegistryError::UnknownKey(id))
+    }
+
+    /// Verifies the bundle's proof with the keys selected for it
+    pub fn verify(&self, bundle: &ProofBundle) -> Result<(), VkRegistryError> {
+        Ok(self.select(bundle)?.verify(bundle)?)
+    }
+
+    /// Serializes the format version, the retired versions and the registered keys, each
+    /// preceded by its validator count, circuit version and circuit features
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), EncodingError> {
+        writer.write_u8(REGISTRY_FORMAT_VERSION)?;
+        writer.write_u32::<LittleEndian>(self.retired.len() as u32)?;
+        for version in &self.retired {
+            writer.write_u32::<LittleEndian>(*version)?;
+        }
+        writer.write_u32::<LittleEndian>(self.keys.len() as u32)?;
+        for (id, keys) in &self.keys {
+            writer.write_u64::<LittleEndian>(id.num_validators as u64)?;
+            writer.write_u32::<LittleEndian>(id.circuit_version)?;
+            writer.write_u32::<LittleEndian>(id.circuit_features)?;
+            keys.write(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Deserializes a registry written with `write`. Fails on unknown format versions and
+    /// on keys of retired circuit versions.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, EncodingError> {
+        if reader.read_u8()? != REGISTRY_FORMAT_VERSION {
+            return Err(SerializationError::InvalidData.into());
+        }
+        let mut registry = Self::new();
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            registry.retired.insert(reader.read_u32::<LittleEndian>()?);
+        }
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            let num_validators = reader.read_u64::<LittleEndian>()? as usize;
+            let circuit_version = reader.read_u32::<LittleEndian>()?;
+            let circuit_features = reader.read_u32::<LittleEndian>()?;
+            let keys = CombinedVerifyingKey::read(&mut reader)?;
+            registry
+                .insert_with_features(num_validators, circuit_version, circuit_features, keys)
+                .map_err(|_| SerializationError::InvalidData)?;
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch_block::EpochBlock;
+    use algebra::{bls12_377::G2Projective, ProjectiveCurve};
+    use bls_crypto::PublicKey;
+    use groth16::{Proof, VerifyingKey};
+
+    fn bundle(num_validators: usize, circuit_version: u32) -> ProofBundle {
+        ProofBundle {
+            proof: Proof::default(),
+            vk: VerifyingKey::default(),
+            hashes_in_bls12_377: false,
+            circuit_version,
+            circuit_features: CIRCUIT_FEATURES,
+            first_epoch: EpochBlock::new(
+                0,
+                0,
+                None,
+                None,
+                0,
+                num_validators,
+                vec![PublicKey::from(G2Projective::prime_subgroup_generator())],
+            ),
+            transitions: vec![],
+        }
+    }
+
+    fn keys(num_inputs: usize) -> CombinedVerifyingKey {
+        let mut epoch_vk = VerifyingKey::default();
+        epoch_vk.gamma_abc_g1 = vec![Default::default(); num_inputs];
+        CombinedVerifyingKey::new(epoch_vk, None)
+    }
+
+    #[test]
+    fn selects_keys_of_bundle() {
+        let mut registry = VkRegistry::new();
+        registry.insert(4, 1, keys(0)).unwrap();
+        registry.insert(8, 1, keys(1)).unwrap();
+        registry.insert(4, 2, keys(2)).unwrap();
+        assert_eq!(registry.latest_version(), Some(2));
+
+        assert_eq!(registry.select(&bundle(4, 1)).unwrap(), &keys(0));
+        assert_eq!(registry.select(&bundle(8, 1)).unwrap(), &keys(1));
+        assert_eq!(registry.select(&bundle(4, 2)).unwrap(), &keys(2));
+        // the keys of a circuit built with other features are distinct
+        let mut other_features = bundle(4, 2);
+        other_features.circuit_features ^= 1;
+        assert!(matches!(
+            registry.select(&other_features),
+            Err(VkRegistryError::UnknownKey(_))
+        ));
+        registry
+            .insert_with_features(4, 2, CIRCUIT_FEATURES ^ 1, keys(3))
+            .unwrap();
+        assert_eq!(registry.select(&other_features).unwrap(), &keys(3));
+        assert_eq!(registry.select(&bundle(4, 2)).unwrap(), &keys(2));
+        assert!(matches!(
+            registry.select(&bundle(8, 2)),
+            Err(VkRegistryError::UnknownKey(VkId {
+                num_validators: 8,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            registry.select(&bundle(4, 3)),
+            Err(VkRegistryError::UnknownCircuitVersion(3))
+        ));
+        // the selected keys must also be the bundle's
+        assert!(matches!(
+            registry.verify(&bundle(8, 1)),
+            Err(VkRegistryError::VerificationError(
+                VerificationError::VerifyingKeyMismatch
+            ))
+        ));
+
+        // migrating to version 2
+        assert_eq!(registry.retire(1), 2);
+        assert!(registry.is_retired(1));
+        assert!(matches!(
+            registry.select(&bundle(4, 1)),
+            Err(VkRegistryError::RetiredCircuitVersion(1))
+        ));
+        assert!(matches!(
+            registry.insert(4, 1, keys(0)),
+            Err(VkRegistryError::RetiredCircuitVersion(1))
+        ));
+        assert_eq!(registry.ids().count(), 2);
+    }
+
+    #[test]
+    fn registry_roundtrip() {
+        let mut registry = VkRegistry::new();
+        registry.insert(4, 1, keys(0)).unwrap();
+        registry.insert(4, 2, keys(1)).unwrap();
+        registry.retire(1);
+        registry.insert(6, 2, keys(2)).unwrap();
+        registry
+            .insert_with_features(6, 2, CIRCUIT_FEATURES ^ 1, keys(3))
+            .unwrap();
+
+        let mut bytes = vec![];
+        registry.write(&mut bytes).unwrap();
+        assert_eq!(VkRegistry::read(&bytes[..]).unwrap(), registry);
+
+        bytes[0] += 1;
+        assert!(VkRegistry::read(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Commitment to the auxiliary data of a proven epoch range
+//!
+//! When the `epoch-aux-data` feature is enabled, each epoch's `aux_data` (e.g. a state root
+//! or a bridge payload hash) is appended to the message signed by the validators. The circuit
+//! hashes the index and auxiliary data of every epoch it constrains (including the dummy
+//! epochs which pad the proof to its maximum number of transitions) and exposes the hash as
+//! an extra public input, after the bitmap commitment if that is enabled as well. An
+//! `AuxDataCommitment` reproduces that hash natively, so that a bridge which verified a proof
+//! against it can rely on the payload committed to by each epoch.
+use crate::{
+    epoch_block::{personalized_blake2s, EpochBlock, EpochTransition},
+    scalars::OuterScalar,
+};
+use bls_crypto::AUX_DATA_DOMAIN;
+use bls_gadgets::utils::bytes_le_to_bits_le;
+
+/// The index and auxiliary data of each epoch constrained by a proof, in circuit order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuxDataCommitment {
+    aux_data: Vec<(u16, [u8; 32])>,
+}
+
+impl AuxDataCommitment {
+    /// Lays out the transitions' auxiliary data the same way as the prover does, i.e. with
+    /// `max_transitions - transitions.len()` dummy epochs (index 0, zero auxiliary data)
+    /// inserted before the last transition. Missing auxiliary data is zero.
+    pub fn new(transitions: &[EpochTransition], max_transitions: usize) -> Self {
+        let mut aux_data = transitions
+            .iter()
+            .map(|transition| {
+                let block = &transition.block;
+                (block.index, block.aux_data.unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(last) = aux_data.pop() {
+            let num_dummies = max_transitions.saturating_sub(transitions.len());
+            aux_data.extend((0..num_dummies).map(|_| (0, [0u8; EpochBlock::AUX_DATA_BYTES])));
+            aux_data.push(last);
+        }
+
+        Self { aux_data }
+    }
+
+    /// Returns the auxiliary data which was signed on the epoch with the provided index, or
+    /// `None` if the epoch is not part of the committed range
+    pub fn open(&self, epoch_index: u16) -> Option<&[u8; 32]> {
+        // index 0 is reserved for dummy epochs
+        if epoch_index == 0 {
+            return None;
+        }
+        self.aux_data
+            .iter()
+            .find(|(index, _)| *index == epoch_index)
+            .map(|(_, aux_data)| aux_data)
+    }
+
+    /// Serializes each epoch as its index (2 bytes, LE) followed by its auxiliary data.
+    /// This is the pre-image of the commitment.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.aux_data.len() * (2 + EpochBlock::AUX_DATA_BYTES));
+        for (index, aux_data) in &self.aux_data {
+            bytes.extend_from_slice(&index.to_le_bytes());
+            bytes.extend_from_slice(aux_data);
+        }
+        bytes
+    }
+
+    /// Blake2 hash of the serialized auxiliary data, personalized to `AUX_DATA_DOMAIN`
+    pub fn commitment(&self) -> [u8; 32] {
+        personalized_blake2s(&self.to_bytes(), AUX_DATA_DOMAIN)
+    }
+
+    /// The public inputs which the circuit appends for the commitment
+    pub fn public_inputs(&self) -> Vec<OuterScalar> {
+        OuterScalar::pack(&bytes_le_to_bits_le(&self.commitment(), 256))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap_commitment::test_helpers::transition;
+
+    /// Transitions to the epochs with the given indices, whose blocks carry the given auxiliary
+    /// data
+    fn with_aux_data(epochs: &[(u16, Option<[u8; 32]>)]) -> Vec<EpochTransition> {
+        epochs
+            .iter()
+            .map(|&(index, aux_data)| {
+                let mut transition = transition(index, vec![true; 3]);
+                transition.block.aux_data = aux_data;
+                transition
+            })
+            .collect()
+    }
+
+    #[test]
+    fn opens_aux_data_and_pads_like_the_prover() {
+        let transitions = with_aux_data(&[(7, Some([1; 32])), (8, None)]);
+        let commitment = AuxDataCommitment::new(&transitions, 3);
+
+        assert_eq!(commitment.aux_data.len(), 3);
+        assert_eq!(commitment.aux_data[1], (0, [0; 32]));
+        assert_eq!(commitment.open(7), Some(&[1; 32]));
+        assert_eq!(commitment.open(8), Some(&[0; 32]));
+        assert_eq!(commitment.open(9), None);
+        assert_eq!(commitment.open(0), None);
+
+        let bytes = commitment.to_bytes();
+        assert_eq!(bytes.len(), 3 * 34);
+        assert_eq!(&bytes[..3], &[7, 0, 1]);
+    }
+
+    #[test]
+    fn commitment_binds_every_payload() {
+        let transitions = with_aux_data(&[(1, Some([2; 32])), (2, Some([3; 32]))]);
+        let commitment = AuxDataCommitment::new(&transitions, 2);
+        assert_eq!(commitment.public_inputs().len(), 1);
+
+        let other = with_aux_data(&[(1, Some([4; 32])), (2, Some([3; 32]))]);
+        let other = AuxDataCommitment::new(&other, 2);
+        assert_ne!(commitment.commitment(), other.commitment());
+        assert_ne!(commitment.public_inputs(), other.public_inputs());
+    }
+}
@@ -0,0 +1,156 @@
+//! Commitment to the signed bitmaps of a proven epoch range
+//!
+//! When the `bitmap-commitment` feature is enabled, the circuit hashes the index and the
+//! signed bitmap of every epoch it constrains (including the dummy epochs which pad the
+//! proof to its maximum number of transitions) and exposes the hash as an extra public
+//! input. A `BitmapCommitment` reproduces that hash natively, so that a consumer who
+//! verified a proof against it can audit exactly which validators signed each epoch.
+use crate::{
+    epoch_block::{personalized_blake2s, EpochTransition},
+    scalars::OuterScalar,
+};
+use bls_crypto::{Bitmap, BITMAP_DOMAIN};
+use bls_gadgets::utils::bytes_le_to_bits_le;
+
+/// The index and signed bitmap of each epoch constrained by a proof, in circuit order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitmapCommitment {
+    bitmaps: Vec<(u16, Bitmap)>,
+}
+
+impl BitmapCommitment {
+    /// Lays out the transitions' bitmaps the same way as the prover does, i.e. with
+    /// `max_transitions - transitions.len()` dummy epochs (index 0, every validator
+    /// signed) inserted before the last transition.
+    pub fn new(
+        transitions: &[EpochTransition],
+        max_transitions: usize,
+        num_validators: u32,
+    ) -> Self {
+        let mut bitmaps = transitions
+            .iter()
+            .map(|transition| (transition.block.index, transition.bitmap.clone()))
+            .collect::<Vec<_>>();
+
+        if let Some(last) = bitmaps.pop() {
+            let num_dummies = max_transitions.saturating_sub(transitions.len());
+            bitmaps.extend((0..num_dummies).map(|_| (0, Bitmap::full(num_validators as usize))));
+            bitmaps.push(last);
+        }
+
+        Self { bitmaps }
+    }
+
+    /// The index and signed bitmap of each epoch, in circuit order. Dummy epochs have
+    /// index 0.
+    pub fn bitmaps(&self) -> &[(u16, Bitmap)] {
+        &self.bitmaps
+    }
+
+    /// Returns the bitmap which was signed on the epoch with the provided index, or
+    /// `None` if the epoch is not part of the committed range
+    pub fn open_bitmap(&self, epoch_index: u16) -> Option<&Bitmap> {
+        // index 0 is reserved for dummy epochs
+        if epoch_index == 0 {
+            return None;
+        }
+        self.bitmaps
+            .iter()
+            .find(|(index, _)| *index == epoch_index)
+            .map(|(_, bitmap)| bitmap)
+    }
+
+    /// Serializes each epoch as its index (2 bytes, LE) followed by its bitmap's packed bits,
+    /// i.e. its canonical encoding without the length. This is the pre-image of the
+    /// commitment.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for (index, bitmap) in &self.bitmaps {
+            bytes.extend_from_slice(&index.to_le_bytes());
+            bytes.extend_from_slice(&bitmap.to_packed_bytes());
+        }
+        bytes
+    }
+
+    /// Blake2 hash of the serialized bitmaps, personalized to `BITMAP_DOMAIN`
+    pub fn commitment(&self) -> [u8; 32] {
+        personalized_blake2s(&self.to_bytes(), BITMAP_DOMAIN)
+    }
+
+    /// The public inputs which the circuit appends after the first and last epoch hashes
+    pub fn public_inputs(&self) -> Vec<OuterScalar> {
+        OuterScalar::pack(&bytes_le_to_bits_le(&self.commitment(), 256))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use crate::epoch_block::{EpochBlock, EpochTransition};
+    use algebra::{bls12_377::G1Projective, ProjectiveCurve};
+    use bls_crypto::Signature;
+
+    /// A transition to the epoch at `index` with the given bitmap, for tests which only
+    /// look at the indices and bitmaps
+    pub fn transition(index: u16, bitmap: Vec<bool>) -> EpochTransition {
+        transition_with_entropy(index, bitmap, None)
+    }
+
+    /// Same as `transition`, with the given epoch entropy
+    pub fn transition_with_entropy(
+        index: u16,
+        bitmap: Vec<bool>,
+        entropy: Option<Vec<u8>>,
+    ) -> EpochTransition {
+        EpochTransition {
+            block: EpochBlock::new(index, 0, entropy, None, 1, bitmap.len(), vec![]),
+            aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+            bitmap: bitmap.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_helpers::transition, *};
+
+    #[test]
+    fn opens_bitmaps_and_pads_like_the_prover() {
+        let transitions = vec![
+            transition(3, vec![true, false, true]),
+            transition(4, vec![false, true, true]),
+        ];
+        let commitment = BitmapCommitment::new(&transitions, 4, 3);
+
+        assert_eq!(commitment.bitmaps.len(), 4);
+        assert_eq!(commitment.bitmaps[1], (0, Bitmap::full(3)));
+        assert_eq!(commitment.bitmaps[3].0, 4);
+        assert_eq!(commitment.open_bitmap(3).unwrap()[..], [true, false, true]);
+        assert_eq!(commitment.open_bitmap(4).unwrap()[..], [false, true, true]);
+        assert_eq!(commitment.open_bitmap(5), None);
+        assert_eq!(commitment.open_bitmap(0), None);
+
+        // 2 index bytes and 1 bitmap byte per epoch
+        let bytes = commitment.to_bytes();
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(&bytes[..3], &[3, 0, 0b101]);
+    }
+
+    #[test]
+    fn commitment_binds_every_bitmap() {
+        let transitions = vec![
+            transition(1, vec![true, true, true]),
+            transition(2, vec![true, false, true]),
+        ];
+        let commitment = BitmapCommitment::new(&transitions, 2, 3);
+        assert_eq!(commitment.public_inputs().len(), 1);
+
+        let mut other = transitions.clone();
+        other[0].bitmap.set(2, false);
+        let other = BitmapCommitment::new(&other, 2, 3);
+        assert_ne!(commitment.commitment(), other.commitment());
+        assert_ne!(commitment.public_inputs(), other.public_inputs());
+
+        let padded = BitmapCommitment::new(&transitions, 3, 3);
+        assert_ne!(commitment.commitment(), padded.commitment());
+    }
+}
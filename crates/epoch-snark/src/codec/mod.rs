@@ -0,0 +1,168 @@
+//! External Encodings
+//!
+//! Encodings of epoch blocks and transitions in common serialization formats, so that the
+//! proving inputs can be exchanged between services (e.g. a relayer and a prover service)
+//! without a bespoke binary format. The schemas are specific to this crate: they are NOT the
+//! encodings of Celo's block headers, which must be converted to `EpochBlock`s first. Each
+//! codec is enabled by its feature:
+//!
+//! - `rlp`: RLP lists, see [`rlp`](rlp/index.html)
+//! - `protobuf`: the messages of `proto/epoch.proto`, see [`protobuf`](protobuf/index.html)
+//!
+//! In both formats, public keys and signatures are in their compressed `CanonicalSerialize`
+//! encoding, missing entropies and 32-byte values are empty byte strings, and the bitmap is
+//! its number of validators followed by its bits packed as in the canonical encoding of
+//! `bls_crypto::Bitmap`, i.e. in little-endian order with the padding bits set to zero.
+//!
+//! The validator set blinding of a block is secret, so it is never encoded and decoded blocks
+//! have none. Decoding rejects blocks and bitmaps with more than `MAX_VALIDATORS` validators,
+//! so that untrusted inputs cannot make it (or the encoding of the decoded blocks for the
+//! circuit) allocate unbounded memory.
+use crate::epoch_block::EpochBlock;
+use algebra::serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use bls_crypto::{Bitmap, ErrorCode, PublicKey, Signature, ToErrorCode};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+
+#[cfg(feature = "rlp")]
+pub mod rlp;
+
+/// The maximum number of validators of a decoded block or bitmap
+pub const MAX_VALIDATORS: usize = 1 << 16;
+
+#[derive(Debug, Error)]
+/// Error raised while decoding an epoch block or transition
+pub enum CodecError {
+    #[cfg(feature = "rlp")]
+    #[error("RLP Error: {0}")]
+    Rlp(#[from] ::rlp::DecoderError),
+    #[cfg(feature = "protobuf")]
+    #[error("Protobuf Error: {0}")]
+    Protobuf(#[from] prost::DecodeError),
+    #[error("Zexe Error: {0}")]
+    ZexeSerialization(#[from] SerializationError),
+    #[error("invalid length of {field}: {len} bytes")]
+    InvalidLength { field: &'static str, len: usize },
+    #[error("the bitmap's {bytes} bytes are not the canonical encoding of {len} bits")]
+    InvalidBitmap { len: u64, bytes: usize },
+    #[error("{field} exceeds the maximum of {} validators: {len}", MAX_VALIDATORS)]
+    TooManyValidators { field: &'static str, len: u64 },
+}
+
+impl ToErrorCode for CodecError {
+    fn error_code(&self) -> ErrorCode {
+        ErrorCode::Serialization
+    }
+}
+
+fn point_to_bytes<P: CanonicalSerialize>(point: &P) -> Vec<u8> {
+    let mut bytes = vec![];
+    point
+        .serialize(&mut bytes)
+        .expect("serializing to a vector cannot fail");
+    bytes
+}
+
+fn public_key_from_bytes(bytes: &[u8]) -> Result<PublicKey, CodecError> {
+    Ok(PublicKey::deserialize(bytes)?)
+}
+
+fn signature_from_bytes(bytes: &[u8]) -> Result<Signature, CodecError> {
+    Ok(Signature::deserialize(bytes)?)
+}
+
+fn entropy_to_bytes(entropy: Option<&Vec<u8>>) -> Vec<u8> {
+    entropy.cloned().unwrap_or_default()
+}
+
+fn entropy_from_bytes(bytes: Vec<u8>, field: &'static str) -> Result<Option<Vec<u8>>, CodecError> {
+    match bytes.len() {
+        0 => Ok(None),
+        EpochBlock::ENTROPY_BYTES => Ok(Some(bytes)),
+        len => Err(CodecError::InvalidLength { field, len }),
+    }
+}
+
+fn optional_to_bytes(value: Option<&[u8; 32]>) -> Vec<u8> {
+    value.map(|value| value.to_vec()).unwrap_or_default()
+}
+
+fn optional_from_bytes(bytes: &[u8], field: &'static str) -> Result<Option<[u8; 32]>, CodecError> {
+    match bytes.len() {
+        0 => Ok(None),
+        32 => {
+            let mut value = [0u8; 32];
+            value.copy_from_slice(bytes);
+            Ok(Some(value))
+        }
+        len => Err(CodecError::InvalidLength { field, len }),
+    }
+}
+
+/// Checks that a decoded number of validators is at most `MAX_VALIDATORS`
+fn validators_len(len: u64, field: &'static str) -> Result<usize, CodecError> {
+    match usize::try_from(len) {
+        Ok(len) if len <= MAX_VALIDATORS => Ok(len),
+        _ => Err(CodecError::TooManyValidators { field, len }),
+    }
+}
+
+fn bitmap_from_bytes(len: u64, bytes: &[u8]) -> Result<Bitmap, CodecError> {
+    let invalid = || CodecError::InvalidBitmap {
+        len,
+        bytes: bytes.len(),
+    };
+    // checked before the length is converted, so that it cannot overflow in `Bitmap`
+    if len > 8 * bytes.len() as u64 {
+        return Err(invalid());
+    }
+    Bitmap::from_packed_bytes(validators_len(len, "bitmap")?, bytes).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use crate::epoch_block::{EpochBlock, EpochTransition};
+    use algebra::{bls12_377::G1Projective, ProjectiveCurve};
+    use bls_crypto::{Bitmap, PrivateKey, Signature};
+    use rand::Rng;
+
+    /// Transitions with and without the optional values of the blocks
+    pub fn transitions() -> Vec<EpochTransition> {
+        let rng = &mut rand::thread_rng();
+        let public_keys = (0..3)
+            .map(|_| PrivateKey::generate(rng).to_public())
+            .collect::<Vec<_>>();
+        let block = EpochBlock::new(
+            7,
+            2,
+            Some(vec![1; EpochBlock::ENTROPY_BYTES]),
+            Some(vec![2; EpochBlock::ENTROPY_BYTES]),
+            1,
+            5,
+            public_keys,
+        );
+        vec![
+            EpochTransition {
+                block: block.clone(),
+                aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                bitmap: (0..11).map(|_| rng.gen()).collect(),
+            },
+            EpochTransition {
+                block: EpochBlock::new(8, 0, None, None, 0, 0, vec![]),
+                aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                bitmap: Bitmap::default(),
+            },
+            EpochTransition {
+                block: block
+                    .with_aux_data(rng.gen())
+                    .with_chain_id(rng.gen())
+                    .with_validator_blinding(rng.gen()),
+                aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                bitmap: Bitmap::full(16),
+            },
+        ]
+    }
+}
@@ -0,0 +1,211 @@
+//! Protobuf encoding of epoch blocks and transitions, as the `EpochBlock` and
+//! `EpochTransition` messages of `proto/epoch.proto`.
+use super::{
+    bitmap_from_bytes, entropy_from_bytes, entropy_to_bytes, optional_from_bytes,
+    optional_to_bytes, point_to_bytes, public_key_from_bytes, signature_from_bytes, validators_len,
+    CodecError,
+};
+use crate::epoch_block::{EpochBlock, EpochTransition};
+use prost::Message;
+use std::convert::TryFrom;
+
+#[derive(Clone, PartialEq, Message)]
+/// The `EpochBlock` message
+pub struct EpochBlockMessage {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub round: u32,
+    #[prost(bytes, tag = "3")]
+    pub epoch_entropy: Vec<u8>,
+    #[prost(bytes, tag = "4")]
+    pub parent_entropy: Vec<u8>,
+    #[prost(uint32, tag = "5")]
+    pub maximum_non_signers: u32,
+    #[prost(uint64, tag = "6")]
+    pub maximum_validators: u64,
+    #[prost(bytes, repeated, tag = "7")]
+    pub new_public_keys: Vec<Vec<u8>>,
+    #[prost(bytes, tag = "8")]
+    pub aux_data: Vec<u8>,
+    #[prost(bytes, tag = "9")]
+    pub chain_id: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+/// The `EpochTransition` message
+pub struct EpochTransitionMessage {
+    #[prost(message, optional, tag = "1")]
+    pub block: Option<EpochBlockMessage>,
+    #[prost(bytes, tag = "2")]
+    pub aggregate_signature: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub bitmap_len: u64,
+    #[prost(bytes, tag = "4")]
+    pub bitmap: Vec<u8>,
+}
+
+impl From<&EpochBlock> for EpochBlockMessage {
+    fn from(block: &EpochBlock) -> Self {
+        Self {
+            index: block.index.into(),
+            round: block.round.into(),
+            epoch_entropy: entropy_to_bytes(block.epoch_entropy.as_ref()),
+            parent_entropy: entropy_to_bytes(block.parent_entropy.as_ref()),
+            maximum_non_signers: block.maximum_non_signers,
+            maximum_validators: block.maximum_validators as u64,
+            new_public_keys: block.new_public_keys.iter().map(point_to_bytes).collect(),
+            aux_data: optional_to_bytes(block.aux_data.as_ref()),
+            chain_id: optional_to_bytes(block.chain_id.as_ref()),
+        }
+    }
+}
+
+impl TryFrom<EpochBlockMessage> for EpochBlock {
+    type Error = CodecError;
+
+    fn try_from(message: EpochBlockMessage) -> Result<Self, Self::Error> {
+        validators_len(message.new_public_keys.len() as u64, "public keys")?;
+        Ok(EpochBlock {
+            index: u16::try_from(message.index).map_err(|_| invalid_integer("index"))?,
+            round: u8::try_from(message.round).map_err(|_| invalid_integer("round"))?,
+            epoch_entropy: entropy_from_bytes(message.epoch_entropy, "epoch entropy")?,
+            parent_entropy: entropy_from_bytes(message.parent_entropy, "parent entropy")?,
+            maximum_non_signers: message.maximum_non_signers,
+            maximum_validators: validators_len(message.maximum_validators, "maximum validators")?,
+            new_public_keys: message
+                .new_public_keys
+                .iter()
+                .map(|bytes| public_key_from_bytes(bytes))
+                .collect::<Result<_, _>>()?,
+            aux_data: optional_from_bytes(&message.aux_data, "aux data")?,
+            chain_id: optional_from_bytes(&message.chain_id, "chain id")?,
+            validator_blinding: None,
+        })
+    }
+}
+
+impl From<&EpochTransition> for EpochTransitionMessage {
+    fn from(transition: &EpochTransition) -> Self {
+        Self {
+            block: Some(EpochBlockMessage::from(&transition.block)),
+            aggregate_signature: point_to_bytes(&transition.aggregate_signature),
+            bitmap_len: transition.bitmap.len() as u64,
+            bitmap: transition.bitmap.to_packed_bytes(),
+        }
+    }
+}
+
+impl TryFrom<EpochTransitionMessage> for EpochTransition {
+    type Error = CodecError;
+
+    fn try_from(message: EpochTransitionMessage) -> Result<Self, Self::Error> {
+        let block = message
+            .block
+            .ok_or_else(|| prost::DecodeError::new("missing block"))?;
+        Ok(EpochTransition {
+            block: EpochBlock::try_from(block)?,
+            aggregate_signature: signature_from_bytes(&message.aggregate_signature)?,
+            bitmap: bitmap_from_bytes(message.bitmap_len, &message.bitmap)?,
+        })
+    }
+}
+
+/// Encodes the block as an `EpochBlock` message
+pub fn encode_block(block: &EpochBlock) -> Vec<u8> {
+    encode_message(&EpochBlockMessage::from(block))
+}
+
+/// Decodes a block from an `EpochBlock` message
+pub fn decode_block(bytes: &[u8]) -> Result<EpochBlock, CodecError> {
+    EpochBlock::try_from(EpochBlockMessage::decode(bytes)?)
+}
+
+/// Encodes the transition as an `EpochTransition` message
+pub fn encode_transition(transition: &EpochTransition) -> Vec<u8> {
+    encode_message(&EpochTransitionMessage::from(transition))
+}
+
+/// Decodes a transition from an `EpochTransition` message
+pub fn decode_transition(bytes: &[u8]) -> Result<EpochTransition, CodecError> {
+    EpochTransition::try_from(EpochTransitionMessage::decode(bytes)?)
+}
+
+fn encode_message<M: Message>(message: &M) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(message.encoded_len());
+    message
+        .encode(&mut bytes)
+        .expect("the vector has the capacity for the message");
+    bytes
+}
+
+fn invalid_integer(field: &'static str) -> CodecError {
+    prost::DecodeError::new(format!("{} is out of range", field)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{test_helpers::transitions, MAX_VALIDATORS};
+
+    #[test]
+    fn roundtrips() {
+        for transition in transitions() {
+            // the blinding is secret, so it is not encoded
+            let mut expected = transition.clone();
+            expected.block.validator_blinding = None;
+
+            let bytes = encode_block(&transition.block);
+            assert_eq!(decode_block(&bytes).unwrap(), expected.block);
+
+            let bytes = encode_transition(&transition);
+            assert_eq!(decode_transition(&bytes).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let transition = &transitions()[0];
+        let mut message = EpochTransitionMessage::from(transition);
+        message.bitmap_len += 8;
+        assert!(matches!(
+            decode_transition(&encode_message(&message)),
+            Err(CodecError::InvalidBitmap { len: 19, bytes: 2 })
+        ));
+
+        // a length which would overflow when rounded up to whole bytes
+        let mut message = EpochTransitionMessage::from(transition);
+        message.bitmap_len = u64::MAX;
+        assert!(matches!(
+            decode_transition(&encode_message(&message)),
+            Err(CodecError::InvalidBitmap { .. })
+        ));
+
+        let mut message = EpochBlockMessage::from(&transition.block);
+        message.maximum_validators = MAX_VALIDATORS as u64 + 1;
+        assert!(matches!(
+            decode_block(&encode_message(&message)),
+            Err(CodecError::TooManyValidators { .. })
+        ));
+
+        let mut message = EpochBlockMessage::from(&transition.block);
+        message.index = u32::from(u16::MAX) + 1;
+        assert!(matches!(
+            decode_block(&encode_message(&message)),
+            Err(CodecError::Protobuf(_))
+        ));
+        message.index = 7;
+        message.chain_id = vec![1; 31];
+        assert!(matches!(
+            decode_block(&encode_message(&message)),
+            Err(CodecError::InvalidLength { len: 31, .. })
+        ));
+
+        // the block is required
+        let message = EpochTransitionMessage {
+            block: None,
+            ..EpochTransitionMessage::from(transition)
+        };
+        assert!(decode_transition(&encode_message(&message)).is_err());
+    }
+}
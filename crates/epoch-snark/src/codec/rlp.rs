@@ -0,0 +1,170 @@
+//! RLP encoding of epoch blocks and transitions.
+//!
+//! A block is the list
+//! `[index, round, epoch_entropy, parent_entropy, maximum_non_signers, maximum_validators,
+//! [public_key, ...], aux_data, chain_id]` and a transition is the list
+//! `[block, aggregate_signature, bitmap_len, bitmap]`, with the values encoded as described in
+//! the [module documentation](../index.html).
+use super::{
+    bitmap_from_bytes, entropy_from_bytes, entropy_to_bytes, optional_from_bytes,
+    optional_to_bytes, point_to_bytes, public_key_from_bytes, signature_from_bytes, validators_len,
+    CodecError,
+};
+use crate::epoch_block::{EpochBlock, EpochTransition};
+use ::rlp::{DecoderError, Rlp, RlpStream};
+
+const BLOCK_ITEMS: usize = 9;
+const TRANSITION_ITEMS: usize = 4;
+
+/// Encodes the block as an RLP list
+pub fn encode_block(block: &EpochBlock) -> Vec<u8> {
+    let mut stream = RlpStream::new();
+    append_block(&mut stream, block);
+    stream.out()
+}
+
+/// Decodes a block encoded with `encode_block`
+pub fn decode_block(bytes: &[u8]) -> Result<EpochBlock, CodecError> {
+    block_from_rlp(&Rlp::new(bytes))
+}
+
+/// Encodes the transition as an RLP list
+pub fn encode_transition(transition: &EpochTransition) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(TRANSITION_ITEMS);
+    append_block(&mut stream, &transition.block);
+    stream.append(&point_to_bytes(&transition.aggregate_signature));
+    stream.append(&(transition.bitmap.len() as u64));
+    stream.append(&transition.bitmap.to_packed_bytes());
+    stream.out()
+}
+
+/// Decodes a transition encoded with `encode_transition`
+pub fn decode_transition(bytes: &[u8]) -> Result<EpochTransition, CodecError> {
+    let rlp = Rlp::new(bytes);
+    check_item_count(&rlp, TRANSITION_ITEMS)?;
+    let bitmap_len: u64 = rlp.val_at(2)?;
+    Ok(EpochTransition {
+        block: block_from_rlp(&rlp.at(0)?)?,
+        aggregate_signature: signature_from_bytes(&rlp.val_at::<Vec<u8>>(1)?)?,
+        bitmap: bitmap_from_bytes(bitmap_len, &rlp.val_at::<Vec<u8>>(3)?)?,
+    })
+}
+
+fn append_block(stream: &mut RlpStream, block: &EpochBlock) {
+    stream.begin_list(BLOCK_ITEMS);
+    stream.append(&block.index);
+    stream.append(&block.round);
+    stream.append(&entropy_to_bytes(block.epoch_entropy.as_ref()));
+    stream.append(&entropy_to_bytes(block.parent_entropy.as_ref()));
+    stream.append(&block.maximum_non_signers);
+    stream.append(&(block.maximum_validators as u64));
+    stream.begin_list(block.new_public_keys.len());
+    for public_key in &block.new_public_keys {
+        stream.append(&point_to_bytes(public_key));
+    }
+    stream.append(&optional_to_bytes(block.aux_data.as_ref()));
+    stream.append(&optional_to_bytes(block.chain_id.as_ref()));
+}
+
+fn block_from_rlp(rlp: &Rlp) -> Result<EpochBlock, CodecError> {
+    check_item_count(rlp, BLOCK_ITEMS)?;
+    let public_keys = rlp.at(6)?;
+    validators_len(public_keys.item_count()? as u64, "public keys")?;
+    let new_public_keys = public_keys
+        .iter()
+        .map(|item| public_key_from_bytes(&item.as_val::<Vec<u8>>()?))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(EpochBlock {
+        index: rlp.val_at(0)?,
+        round: rlp.val_at(1)?,
+        epoch_entropy: entropy_from_bytes(rlp.val_at(2)?, "epoch entropy")?,
+        parent_entropy: entropy_from_bytes(rlp.val_at(3)?, "parent entropy")?,
+        maximum_non_signers: rlp.val_at(4)?,
+        maximum_validators: validators_len(rlp.val_at(5)?, "maximum validators")?,
+        new_public_keys,
+        aux_data: optional_from_bytes(&rlp.val_at::<Vec<u8>>(7)?, "aux data")?,
+        chain_id: optional_from_bytes(&rlp.val_at::<Vec<u8>>(8)?, "chain id")?,
+        validator_blinding: None,
+    })
+}
+
+fn check_item_count(rlp: &Rlp, expected: usize) -> Result<(), CodecError> {
+    if rlp.item_count()? != expected {
+        return Err(DecoderError::RlpIncorrectListLen.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{test_helpers::transitions, MAX_VALIDATORS};
+
+    #[test]
+    fn roundtrips() {
+        for transition in transitions() {
+            // the blinding is secret, so it is not encoded
+            let mut expected = transition.clone();
+            expected.block.validator_blinding = None;
+
+            let bytes = encode_block(&transition.block);
+            assert_eq!(decode_block(&bytes).unwrap(), expected.block);
+
+            let bytes = encode_transition(&transition);
+            assert_eq!(decode_transition(&bytes).unwrap(), expected);
+            // a block is not a transition
+            assert!(decode_transition(&encode_block(&transition.block)).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let transition = &transitions()[0];
+        let mut stream = RlpStream::new_list(TRANSITION_ITEMS);
+        append_block(&mut stream, &transition.block);
+        stream.append(&point_to_bytes(&transition.aggregate_signature));
+        // one bit more than the bytes hold
+        stream.append(&(8 * 2 + 1u64));
+        stream.append(&vec![0u8; 2]);
+        assert!(matches!(
+            decode_transition(&stream.out()),
+            Err(CodecError::InvalidBitmap { len: 17, bytes: 2 })
+        ));
+
+        // a padding bit is set
+        let mut stream = RlpStream::new_list(TRANSITION_ITEMS);
+        append_block(&mut stream, &transition.block);
+        stream.append(&point_to_bytes(&transition.aggregate_signature));
+        stream.append(&15u64);
+        stream.append(&vec![0u8, 0x80]);
+        assert!(matches!(
+            decode_transition(&stream.out()),
+            Err(CodecError::InvalidBitmap { len: 15, bytes: 2 })
+        ));
+
+        // a length which would overflow when rounded up to whole bytes
+        let mut stream = RlpStream::new_list(TRANSITION_ITEMS);
+        append_block(&mut stream, &transition.block);
+        stream.append(&point_to_bytes(&transition.aggregate_signature));
+        stream.append(&u64::MAX);
+        stream.append(&vec![0u8; 2]);
+        assert!(matches!(
+            decode_transition(&stream.out()),
+            Err(CodecError::InvalidBitmap { .. })
+        ));
+
+        let mut block = transition.block.clone();
+        block.epoch_entropy = Some(vec![1; 3]);
+        assert!(matches!(
+            decode_block(&encode_block(&block)),
+            Err(CodecError::InvalidLength { len: 3, .. })
+        ));
+
+        let mut block = transition.block.clone();
+        block.maximum_validators = MAX_VALIDATORS + 1;
+        assert!(matches!(
+            decode_block(&encode_block(&block)),
+            Err(CodecError::TooManyValidators { .. })
+        ));
+    }
+}
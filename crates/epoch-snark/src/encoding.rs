@@ -2,7 +2,7 @@ use algebra::{
     bls12_377::{Fq, FqParameters},
     FpParameters, PrimeField, ProjectiveCurve, ToBytes, Zero,
 };
-use bls_crypto::{BLSError, PublicKey};
+use bls_crypto::{BLSError, ErrorCode, PublicKey, ToErrorCode};
 use bls_gadgets::utils::bytes_le_to_bits_be;
 use byteorder::{LittleEndian, WriteBytesExt};
 use thiserror::Error;
@@ -19,6 +19,16 @@ pub enum EncodingError {
     BLSError(#[from] BLSError),
 }
 
+impl ToErrorCode for EncodingError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            EncodingError::ZexeSerialization(_) => ErrorCode::Serialization,
+            EncodingError::IoError(_) => ErrorCode::Io,
+            EncodingError::BLSError(e) => e.error_code(),
+        }
+    }
+}
+
 /// The function assumes that the public key is not the point in infinity, which is true for
 /// BLS public keys
 pub fn encode_public_key(public_key: &PublicKey) -> Result<Vec<bool>, EncodingError> {
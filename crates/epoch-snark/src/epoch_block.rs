@@ -1,5 +1,5 @@
 use super::encoding::{encode_public_key, encode_u16, encode_u32, EncodingError};
-use crate::encoding::encode_u8;
+use crate::{api::CIRCUIT_VERSION, encoding::encode_u8};
 use algebra::{
     bls12_377::{G1Projective, G2Projective},
     ProjectiveCurve,
@@ -7,9 +7,29 @@ use algebra::{
 use blake2s_simd::Params;
 use bls_crypto::{
     hash_to_curve::{try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, HashToCurve},
-    PublicKey, Signature, OUT_DOMAIN, SIG_DOMAIN,
+    hashers::composite::{CompositeHasher, COMPOSITE_HASHER, CRH, CRH_SEED},
+    BLSError, Bitmap, Hasher, PublicKey, Signature, OUT_DOMAIN, SIG_DOMAIN,
 };
-use bls_gadgets::utils::{bits_be_to_bytes_le, bytes_le_to_bits_le};
+use bls_gadgets::utils::{bits_be_to_bytes_le, bits_le_to_bytes_le, bytes_le_to_bits_le};
+use once_cell::sync::Lazy;
+use std::convert::TryFrom;
+
+/// The personalization used when deriving the generators of the validator set commitment,
+/// so that they are independent from the generators of the signatures' CRH
+pub const VALIDATOR_COMMITMENT_PERSONALIZATION: &[u8; 8] = b"UL_vcmts";
+
+/// Lazily evaluated Bowe-Hopwood-Pedersen CRH which commits to an epoch's validator set, see
+/// `EpochBlock::encode_validator_commitment`
+pub static VALIDATOR_COMMITMENT_HASHER: Lazy<CompositeHasher<CRH>> = Lazy::new(|| {
+    CompositeHasher::<CRH>::from_parameters(
+        CompositeHasher::<CRH>::setup_crh_from_seed(VALIDATOR_COMMITMENT_PERSONALIZATION, CRH_SEED)
+            .unwrap(),
+    )
+});
+
+/// The number of bits of the XOF output of the second hashing stage, from which the circuit
+/// decodes the G1 point
+pub const XOF_BITS: usize = 512;
 
 #[derive(Debug, Clone, Copy)]
 pub enum EpochType {
@@ -29,7 +49,35 @@ pub struct EpochTransition {
     /// by the validators of the previous epoch
     pub aggregate_signature: Signature,
     /// The bitmap which determined the state transition
-    pub bitmap: Vec<bool>,
+    pub bitmap: Bitmap,
+}
+
+/// A short description of an epoch, which allows external systems to reference
+/// an epoch by the same digests the circuit commits to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpochSummary {
+    /// The block number
+    pub index: u16,
+    /// The entropy of the epoch, if any
+    pub entropy: Option<Vec<u8>>,
+    /// The digest the circuit commits to when a proof ends at the epoch, i.e. the Blake2
+    /// hash of the epoch encoded with its aggregate public key
+    /// (`blake2_last_epoch_with_aggregated_pk_cip22`)
+    pub apk_commitment: [u8; 32],
+    /// The digest the circuit commits to when a proof starts from the epoch, i.e. the Blake2
+    /// hash of the epoch encoded with its public keys, padded to the maximum number of
+    /// validators (`blake2_first_epoch_cip22`)
+    pub pubkey_hash: [u8; 32],
+    /// The maximum allowed number of signers that may be absent
+    pub max_non_signers: u32,
+}
+
+impl EpochSummary {
+    /// The hash of a proof from the `first` to the `last` epoch as LE bytes, i.e. the
+    /// bytes of `hash_first_last_epoch_block` for the summarized epochs
+    pub fn range_hash(first: &EpochSummary, last: &EpochSummary) -> Vec<u8> {
+        [first.pubkey_hash, last.apk_commitment].concat()
+    }
 }
 
 /// Metadata about the next epoch
@@ -53,11 +101,49 @@ pub struct EpochBlock {
     pub maximum_validators: usize,
     /// The public keys of the new validators
     pub new_public_keys: Vec<PublicKey>,
+    /// Auxiliary data (e.g. a state root or a bridge payload hash) which is appended to
+    /// the signed epoch message when the `epoch-aux-data` feature is enabled. A missing
+    /// value is encoded as `AUX_DATA_BYTES` zeros.
+    pub aux_data: Option<[u8; 32]>,
+    /// Identifier of the chain the epoch belongs to (e.g. its genesis hash), which is
+    /// appended to the signed epoch message when the `chain-binding` feature is enabled. A
+    /// missing value is encoded as `CHAIN_ID_BYTES` zeros.
+    pub chain_id: Option<[u8; 32]>,
+    /// Randomness which hides the validator set in its commitment, which replaces the public
+    /// keys in the first and last epoch encodings when the `blinded-validators` feature is
+    /// enabled. It MUST be uniformly random and kept secret. A missing value is encoded as
+    /// `VALIDATOR_BLINDING_BYTES` zeros, which does not hide the validator set.
+    pub validator_blinding: Option<[u8; 32]>,
 }
 
 impl EpochBlock {
-    /// Each epoch entropy value is 128 bits.
+    /// Each epoch entropy value is 128 bits, or 256 bits with the `entropy-256` feature for
+    /// chains whose randomness values are 32 bytes long.
+    #[cfg(not(feature = "entropy-256"))]
     pub const ENTROPY_BYTES: usize = 16;
+    /// Each epoch entropy value is 256 bits.
+    #[cfg(feature = "entropy-256")]
+    pub const ENTROPY_BYTES: usize = 32;
+
+    /// The auxiliary data of each epoch is 256 bits.
+    pub const AUX_DATA_BYTES: usize = 32;
+
+    /// The chain identifier of each epoch is 256 bits.
+    pub const CHAIN_ID_BYTES: usize = 32;
+
+    /// The circuit version is 32 bits.
+    pub const CIRCUIT_VERSION_BYTES: usize = 4;
+
+    /// The blinding of each epoch's validator set commitment is 256 bits.
+    pub const VALIDATOR_BLINDING_BYTES: usize = 32;
+
+    /// The validator set commitment is the x coordinate of an Edwards BW6_761 point, i.e.
+    /// 377 bits padded to whole bytes.
+    pub const VALIDATOR_COMMITMENT_BITS: usize = 384;
+
+    /// The maximum number of validators which fit in the input of the validator set
+    /// commitment's CRH, along with the blinding
+    pub const MAX_BLINDED_VALIDATORS: usize = 203;
 
     /// Creates a new epoch block
     pub fn new(
@@ -77,9 +163,30 @@ impl EpochBlock {
             maximum_non_signers,
             maximum_validators,
             new_public_keys,
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
         }
     }
 
+    /// Sets the epoch's auxiliary data
+    pub fn with_aux_data(mut self, aux_data: [u8; 32]) -> Self {
+        self.aux_data = Some(aux_data);
+        self
+    }
+
+    /// Sets the identifier of the epoch's chain
+    pub fn with_chain_id(mut self, chain_id: [u8; 32]) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Sets the blinding of the epoch's validator set commitment
+    pub fn with_validator_blinding(mut self, validator_blinding: [u8; 32]) -> Self {
+        self.validator_blinding = Some(validator_blinding);
+        self
+    }
+
     /// Encodes the block to bytes and then proceeds to hash it to BLS12-377's G1
     /// group using `SIG_DOMAIN` as a domain separator
     pub fn hash_to_g1_cip22(&self) -> Result<G1Projective, EncodingError> {
@@ -89,6 +196,41 @@ impl EpochBlock {
         Ok(expected_hash)
     }
 
+    /// The LE bits of the epoch's signed message (see `encode_inner_to_bytes_cip22`), padded
+    /// to whole bytes. This is the pre-image which the first hashing stage compresses, while
+    /// the extra data only enters the second stage.
+    pub fn hash_preimage_bits(&self) -> Result<Vec<bool>, EncodingError> {
+        let (message, _) = self.encode_inner_to_bytes_cip22()?;
+        Ok(bytes_le_to_bits_le(&message, message.len() * 8))
+    }
+
+    /// The first stage of hashing the epoch to G1: the LE bits of the Bowe-Hopwood-Pedersen
+    /// CRH of the signed message. The circuit commits to these bits as each epoch's `crh_bits`.
+    pub fn hash_first_stage(&self) -> Result<Vec<bool>, EncodingError> {
+        let (message, _) = self.encode_inner_to_bytes_cip22()?;
+        let crh = COMPOSITE_HASHER.crh(SIG_DOMAIN, &message, XOF_BITS / 8)?;
+        Ok(bytes_le_to_bits_le(&crh, crh.len() * 8))
+    }
+
+    /// The second stage of hashing the epoch to G1: the Blake2Xs XOF, personalized to
+    /// `SIG_DOMAIN`, of `counter || extra data || first stage`, where the counter is the first
+    /// try-and-increment attempt whose output decodes to a point. Returns the counter and the
+    /// LE bits of the output, which the circuit commits to as each epoch's `xof_bits`. Fails
+    /// if the counter does not fit in the byte the circuit allocates for it.
+    pub fn hash_second_stage(&self) -> Result<(u8, Vec<bool>), EncodingError> {
+        let (message, extra_data) = self.encode_inner_to_bytes_cip22()?;
+        let (_, counter) = COMPOSITE_HASH_TO_G1_CIP22.hash_with_attempt_cip22(
+            SIG_DOMAIN,
+            &message,
+            &extra_data,
+        )?;
+        let counter = u8::try_from(counter).map_err(|_| BLSError::HashToCurveError)?;
+        let crh = COMPOSITE_HASHER.crh(SIG_DOMAIN, &message, XOF_BITS / 8)?;
+        let input = [&[counter][..], &extra_data, &crh].concat();
+        let xof = COMPOSITE_HASHER.xof(SIG_DOMAIN, &input, XOF_BITS / 8)?;
+        Ok((counter, bytes_le_to_bits_le(&xof, XOF_BITS)))
+    }
+
     /// Encodes the block to bytes and then hashes it with Blake2
     pub fn blake2_first_epoch_cip22(&self) -> Result<Vec<bool>, EncodingError> {
         Ok(hash_to_bits(&self.encode_first_epoch_to_bytes_cip22()?))
@@ -114,6 +256,33 @@ impl EpochBlock {
 
     /// Encodes the block to LE bits
     pub fn encode_to_bits_cip22(&self, epoch_type: EpochType) -> Result<Vec<bool>, EncodingError> {
+        let mut epoch_bits = self.encode_metadata_cip22(epoch_type)?;
+        if cfg!(feature = "blinded-validators") {
+            epoch_bits.extend_from_slice(&self.encode_validator_commitment()?);
+        } else {
+            epoch_bits.extend_from_slice(&self.encode_padded_public_keys()?);
+        }
+        Ok(epoch_bits)
+    }
+
+    /// Same as `encode_to_bits_cip22` with the `blinded-validators` feature, but encodes the
+    /// provided commitment to the validator set (see `validator_commitment`) instead of
+    /// computing it, so that verifiers which do not know the validators can encode the epoch
+    pub fn encode_to_bits_with_validator_commitment(
+        &self,
+        epoch_type: EpochType,
+        validator_commitment: &[u8],
+    ) -> Result<Vec<bool>, EncodingError> {
+        let mut epoch_bits = self.encode_metadata_cip22(epoch_type)?;
+        epoch_bits.extend_from_slice(&bytes_le_to_bits_le(
+            validator_commitment,
+            Self::VALIDATOR_COMMITMENT_BITS,
+        ));
+        Ok(epoch_bits)
+    }
+
+    /// Encodes the fields which precede the validator set in `encode_to_bits_cip22`
+    fn encode_metadata_cip22(&self, epoch_type: EpochType) -> Result<Vec<bool>, EncodingError> {
         let mut epoch_bits = vec![];
         epoch_bits.extend_from_slice(&encode_u16(self.index)?);
         // The first epoch doesn't need the current entropy and the last epoch doesn't need the
@@ -125,17 +294,51 @@ impl EpochBlock {
                 .extend_from_slice(&Self::encode_entropy_cip22(self.epoch_entropy.as_ref())),
         }
         epoch_bits.extend_from_slice(&encode_u32(self.maximum_non_signers)?);
+        Ok(epoch_bits)
+    }
+
+    /// Encodes the public keys, padded to the maximum number of validators with the G2
+    /// generator, to LE bits
+    fn encode_padded_public_keys(&self) -> Result<Vec<bool>, EncodingError> {
+        let mut pubkey_bits = vec![];
         for added_public_key in &self.new_public_keys {
-            epoch_bits.extend_from_slice(encode_public_key(&added_public_key)?.as_slice());
+            pubkey_bits.extend_from_slice(encode_public_key(&added_public_key)?.as_slice());
         }
         if self.maximum_validators > self.new_public_keys.len() {
             let difference = self.maximum_validators - self.new_public_keys.len();
             let generator = PublicKey::from(G2Projective::prime_subgroup_generator());
             for _ in 0..difference {
-                epoch_bits.extend_from_slice(encode_public_key(&generator)?.as_slice());
+                pubkey_bits.extend_from_slice(encode_public_key(&generator)?.as_slice());
             }
         }
-        Ok(epoch_bits)
+        Ok(pubkey_bits)
+    }
+
+    /// Encodes the validator set blinding to LE bits, in little-endian byte order
+    pub fn encode_validator_blinding(validator_blinding: Option<&[u8; 32]>) -> Vec<bool> {
+        let blinding = validator_blinding
+            .cloned()
+            .unwrap_or([0u8; Self::VALIDATOR_BLINDING_BYTES]);
+        bytes_le_to_bits_le(&blinding, Self::VALIDATOR_BLINDING_BYTES * 8)
+    }
+
+    /// Encodes the Pedersen commitment to the epoch's validator set to LE bits. The
+    /// commitment is the `VALIDATOR_COMMITMENT_HASHER` CRH of
+    /// `blinding || padded public keys`, and it fits at most `MAX_BLINDED_VALIDATORS`.
+    pub fn encode_validator_commitment(&self) -> Result<Vec<bool>, EncodingError> {
+        Ok(bytes_le_to_bits_le(
+            &self.validator_commitment()?,
+            Self::VALIDATOR_COMMITMENT_BITS,
+        ))
+    }
+
+    /// Returns the Pedersen commitment to the epoch's validator set, see
+    /// `encode_validator_commitment`. When the `blinded-validators` feature is enabled, this
+    /// is all a verifier needs to know about the validators of the first and last epoch.
+    pub fn validator_commitment(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut input = Self::encode_validator_blinding(self.validator_blinding.as_ref());
+        input.extend_from_slice(&self.encode_padded_public_keys()?);
+        Ok(VALIDATOR_COMMITMENT_HASHER.crh(&[], &bits_le_to_bytes_le(&input), 0)?)
     }
 
     pub fn encode_entropy_cip22(entropy: Option<&Vec<u8>>) -> Vec<bool> {
@@ -147,33 +350,70 @@ impl EpochBlock {
         bytes_le_to_bits_le(&entropy_bytes, Self::ENTROPY_BYTES * 8)
     }
 
+    /// Encodes the auxiliary data to LE bits, in little-endian byte order
+    pub fn encode_aux_data(aux_data: Option<&[u8; 32]>) -> Vec<bool> {
+        let aux_data = aux_data.cloned().unwrap_or([0u8; Self::AUX_DATA_BYTES]);
+        bytes_le_to_bits_le(&aux_data, Self::AUX_DATA_BYTES * 8)
+    }
+
+    /// Encodes the chain identifier to LE bits, in little-endian byte order
+    pub fn encode_chain_id(chain_id: Option<&[u8; 32]>) -> Vec<bool> {
+        let chain_id = chain_id.cloned().unwrap_or([0u8; Self::CHAIN_ID_BYTES]);
+        bytes_le_to_bits_le(&chain_id, Self::CHAIN_ID_BYTES * 8)
+    }
+
+    /// Encodes `CIRCUIT_VERSION` to LE bits, in little-endian byte order
+    pub fn encode_circuit_version() -> Vec<bool> {
+        bytes_le_to_bits_le(
+            &CIRCUIT_VERSION.to_le_bytes(),
+            Self::CIRCUIT_VERSION_BYTES * 8,
+        )
+    }
+
     /// Encodes the block to LE bits
     pub fn encode_inner_to_bits_cip22(&self) -> Result<(Vec<bool>, Vec<bool>), EncodingError> {
         let mut epoch_bits = vec![];
-        let mut extra_data_bits = vec![];
-        extra_data_bits.extend_from_slice(&encode_u16(self.index)?);
-        extra_data_bits.extend_from_slice(&encode_u8(self.round)?);
-        extra_data_bits.extend_from_slice(&encode_u32(self.maximum_non_signers)?);
+        let extra_data_bits = self.encode_signing_context_to_bits()?;
         epoch_bits.extend_from_slice(&Self::encode_entropy_cip22(self.epoch_entropy.as_ref()));
         epoch_bits.extend_from_slice(&Self::encode_entropy_cip22(self.parent_entropy.as_ref()));
-        for added_public_key in &self.new_public_keys {
-            epoch_bits.extend_from_slice(encode_public_key(&added_public_key)?.as_slice());
+        epoch_bits.extend_from_slice(&self.encode_padded_public_keys()?);
+        if cfg!(feature = "epoch-aux-data") {
+            epoch_bits.extend_from_slice(&Self::encode_aux_data(self.aux_data.as_ref()));
         }
-        if self.maximum_validators > self.new_public_keys.len() {
-            let difference = self.maximum_validators - self.new_public_keys.len();
-            let generator = PublicKey::from(G2Projective::prime_subgroup_generator());
-            for _ in 0..difference {
-                epoch_bits.extend_from_slice(encode_public_key(&generator)?.as_slice());
-            }
+        if cfg!(feature = "chain-binding") {
+            epoch_bits.extend_from_slice(&Self::encode_chain_id(self.chain_id.as_ref()));
+        }
+        if cfg!(feature = "circuit-version") {
+            epoch_bits.extend_from_slice(&Self::encode_circuit_version());
         }
         Ok((epoch_bits, extra_data_bits))
     }
 
-    /// Encodes the block with the aggregated public key from the vector of pubkeys to LE bits
+    /// Encodes the consensus metadata which the epoch's signature is bound to, i.e. the extra data
+    /// of the epoch message: `index || round || maximum_non_signers`. This is the epoch circuit's
+    /// equivalent of `bls_crypto::encode_signing_context`.
+    pub fn encode_signing_context(&self) -> Result<Vec<u8>, EncodingError> {
+        Ok(bits_be_to_bytes_le(&self.encode_signing_context_to_bits()?))
+    }
+
+    fn encode_signing_context_to_bits(&self) -> Result<Vec<bool>, EncodingError> {
+        let mut extra_data_bits = vec![];
+        extra_data_bits.extend_from_slice(&encode_u16(self.index)?);
+        extra_data_bits.extend_from_slice(&encode_u8(self.round)?);
+        extra_data_bits.extend_from_slice(&encode_u32(self.maximum_non_signers)?);
+        Ok(extra_data_bits)
+    }
+
+    /// Encodes the block with the aggregated public key from the vector of pubkeys to LE bits.
+    /// The aggregated public key is omitted when the `blinded-validators` feature is enabled,
+    /// since it would reveal the validator set.
     pub fn encode_last_epoch_to_bits_with_aggregated_pk_cip22(
         &self,
     ) -> Result<Vec<bool>, EncodingError> {
         let mut epoch_bits = self.encode_to_bits_cip22(EpochType::Last)?;
+        if cfg!(feature = "blinded-validators") {
+            return Ok(epoch_bits);
+        }
         let aggregated_pk = PublicKey::aggregate(&self.new_public_keys);
         epoch_bits.extend_from_slice(encode_public_key(&aggregated_pk)?.as_slice());
         Ok(epoch_bits)
@@ -200,6 +440,24 @@ impl EpochBlock {
         ))
     }
 
+    /// Returns a summary of the epoch, whose digests are the halves of
+    /// `hash_first_last_epoch_block` when the epoch is respectively the last or the first one
+    pub fn summarize(&self) -> Result<EpochSummary, EncodingError> {
+        Ok(EpochSummary {
+            index: self.index,
+            entropy: self.epoch_entropy.clone(),
+            apk_commitment: personalized_blake2s(
+                &self.encode_last_epoch_to_bytes_with_aggregated_pk_cip22()?,
+                OUT_DOMAIN,
+            ),
+            pubkey_hash: personalized_blake2s(
+                &self.encode_first_epoch_to_bytes_cip22()?,
+                OUT_DOMAIN,
+            ),
+            max_non_signers: self.maximum_non_signers,
+        })
+    }
+
     /// Encodes an inner block to LE bytes
     pub fn encode_inner_to_bytes_cip22(&self) -> Result<(Vec<u8>, Vec<u8>), EncodingError> {
         let (inner_bits, extra_data_bits) = self.encode_inner_to_bits_cip22()?;
@@ -221,23 +479,50 @@ pub fn hash_first_last_epoch_block(
     Ok([h1, h2].concat())
 }
 
+/// Same as `hash_first_last_epoch_block` with the `blinded-validators` feature, given the
+/// commitments to the validator sets of the first and last epochs (see
+/// `EpochBlock::validator_commitment`). The public keys and blindings of the epochs are
+/// ignored.
+pub fn hash_first_last_validator_commitments(
+    first: &EpochBlock,
+    first_commitment: &[u8],
+    last: &EpochBlock,
+    last_commitment: &[u8],
+) -> Result<Vec<bool>, EncodingError> {
+    let h1 = hash_to_bits(&bits_be_to_bytes_le(
+        &first.encode_to_bits_with_validator_commitment(EpochType::First, first_commitment)?,
+    ));
+    let h2 = hash_to_bits(&bits_be_to_bytes_le(
+        &last.encode_to_bits_with_validator_commitment(EpochType::Last, last_commitment)?,
+    ));
+    Ok([h1, h2].concat())
+}
+
 /// Blake2 hash of the input personalized to `OUT_DOMAIN`
 pub fn hash_to_bits(bytes: &[u8]) -> Vec<bool> {
-    let hash = Params::new()
-        .hash_length(32)
-        .personal(OUT_DOMAIN)
-        .to_state()
-        .update(&bytes)
-        .finalize()
-        .as_ref()
-        .to_vec();
-    bytes_le_to_bits_le(&hash, 256)
+    bytes_le_to_bits_le(&personalized_blake2s(bytes, OUT_DOMAIN), 256)
+}
+
+/// Blake2s hash of the input personalized to `domain`, e.g. the commitments which the circuit
+/// exposes for the enabled features
+pub(crate) fn personalized_blake2s(bytes: &[u8], domain: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(
+        Params::new()
+            .hash_length(32)
+            .personal(domain)
+            .to_state()
+            .update(bytes)
+            .finalize()
+            .as_ref(),
+    );
+    hash
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use algebra::{bls12_377, ProjectiveCurve};
+    use algebra::{bls12_377, ProjectiveCurve, UniformRand};
 
     static EXPECTED_ENCODING_WITH_ENTROPY: &str = "fdd542ddf4fdd764cddfee7f0933f1b9bc93330f9c7d44ce979da3ccdcef4ea6aa816263a3b4b8e1628000ce81c0d4594601f03d928fd309504ded4a7d22c66dae6d5fd50794fac540f980c4c197150774108e8ac25822fb171ec7f90212eeaf16eaa6efbf266bfe76ff4b9889cfe59d9c79e0ec2372beec1c65e67e7732550d141b1ba5c50d170304700e04a6ce320a80ef917c9c4e806a6a57ea13316e736dfbaa3ea0d42f06ca07240ebeac38a083705414c612d9bff038ce1790707fb550377dff3559f3b7fb5fc24c7c2eefe4cc03671f91f365e72833f7bb93a96aa0d8d8282d6eb8182080732030759651007c8fe4e374025453bb529f88719b6bdb57f501a57e31503e2071f065c5011d84a3a23096c8fe85c771be8084fbab85bae9fbafc99abfddff1266e2737927671e38fb889c2f3b4799b9df9d4c5503c5c6466971c3c500019c0381a9b38c02e07b241fa713a09ada95fa448cdb5cdbbeaa0f28f58b81f20189832f2b0ee8201c1585b144f62f3c8ef30524dc5f2dd44ddf7f4dd6fcedfe9730139fcb3b39f3c0d947e47cd939caccfdee64aa1a2836364a8b1b2e0608e01c084c9d651400df23f9389d00d5d4aed42762dce6daf6557d40a95f0c940f481c7c59714007e1a8288c25b27fe1719c2f20e1fe6aa16efafe6bb2e66ff7bf8499f85cdec99907ce3e22e7cbce5166ee772753d540b1b1515adc70314000e74060ea2ca300f81ec9c7e904a8a676a53e11e336d7b6afea034afd62a07c40e2e0cb8a033a084745612c91fd0b8fe37c0109f7570b75d3f75f93357fbbff25ccc4e7f24ece3c70f611395f768e3273bf3b99aa068a8d8dd2e2868b010238070253671905c0f7483e4e274035b52bf58918b7b9b67d551f50ea1703e50312075f561cd041382a0a6389ec5f781ce70b48b8bf5aa89bbeff9aacf9dbfd2f61263e977772e681b38fc8f9b2739499fbddc95435506c6c9416375c0c10c03910983acb2800be47f2713a01aaa95da94fc4b8cdb5edabfa8052bf18281f9038f8b2e2800ec25151184b64ffc2e3385f40c2fdd542ddf4fdd764cddfee7f0933f1b9bc93330f9c7d44ce979da3ccdcef4ea6aa816263a3b4b8e1628000ce81c0d4594601f03d928fd309504ded4a7d22c66dae6d5fd50794fac540f980c4c197150774108e8ac25822fb171ec7f90212eeaf16eaa6efbf266bfe76ff4b9889cfe59d9c79e0ec2372beec1c65e67e7732550d141b1ba5c50d170304700e04a6ce320a80ef917c9c4e806a6a57ea13316e736dfbaa3ea0d42f06ca07240ebeac38a083705414c612d9bff038ce179030000000f0dfdfdfdfdfdfdfdfdfdfdfdfdfdfdf1f8007";
     static EXPECTED_ENCODING_WITH_ENTROPY_PADDED: &str = "fdd542ddf4fdd764cddfee7f0933f1b9bc93330f9c7d44ce979da3ccdcef4ea6aa816263a3b4b8e1628000ce81c0d4594601f03d928fd309504ded4a7d22c66dae6d5fd50794fac540f980c4c197150774108e8ac25822fb171ec7f90212eeaf16eaa6efbf266bfe76ff4b9889cfe59d9c79e0ec2372beec1c65e67e7732550d141b1ba5c50d170304700e04a6ce320a80ef917c9c4e806a6a57ea13316e736dfbaa3ea0d42f06ca07240ebeac38a083705414c612d9bff038ce1790707fb550377dff3559f3b7fb5fc24c7c2eefe4cc03671f91f365e72833f7bb93a96aa0d8d8282d6eb8182080732030759651007c8fe4e374025453bb529f88719b6bdb57f501a57e31503e2071f065c5011d84a3a23096c8fe85c771be8084fbab85bae9fbafc99abfddff1266e2737927671e38fb889c2f3b4799b9df9d4c5503c5c6466971c3c500019c0381a9b38c02e07b241fa713a09ada95fa448cdb5cdbbeaa0f28f58b81f20189832f2b0ee8201c1585b144f62f3c8ef30524dc5f2dd44ddf7f4dd6fcedfe9730139fcb3b39f3c0d947e47cd939caccfdee64aa1a2836364a8b1b2e0608e01c084c9d651400df23f9389d00d5d4aed42762dce6daf6557d40a95f0c940f481c7c59714007e1a8288c25b27fe1719c2f20e1fe6aa16efafe6bb2e66ff7bf8499f85cdec99907ce3e22e7cbce5166ee772753d540b1b1515adc70314000e74060ea2ca300f81ec9c7e904a8a676a53e11e336d7b6afea034afd62a07c40e2e0cb8a033a084745612c91fd0b8fe37c0109f7570b75d3f75f93357fbbff25ccc4e7f24ece3c70f611395f768e3273bf3b99aa068a8d8dd2e2868b010238070253671905c0f7483e4e274035b52bf58918b7b9b67d551f50ea1703e50312075f561cd041382a0a6389ec5f781ce70b48b8bf5aa89bbeff9aacf9dbfd2f61263e977772e681b38fc8f9b2739499fbddc95435506c6c9416375c0c10c03910983acb2800be47f2713a01aaa95da94fc4b8cdb5edabfa8052bf18281f9038f8b2e2800ec25151184b64ffc2e3385f40c2fdd542ddf4fdd764cddfee7f0933f1b9bc93330f9c7d44ce979da3ccdcef4ea6aa816263a3b4b8e1628000ce81c0d4594601f03d928fd309504ded4a7d22c66dae6d5fd50794fac540f980c4c197150774108e8ac25822fb171ec7f90212eeaf16eaa6efbf266bfe76ff4b9889cfe59d9c79e0ec2372beec1c65e67e7732550d141b1ba5c50d170304700e04a6ce320a80ef917c9c4e806a6a57ea13316e736dfbaa3ea0d42f06ca07240ebeac38a083705414c612d9bff038ce1790707fb550377dff3559f3b7fb5fc24c7c2eefe4cc03671f91f365e72833f7bb93a96aa0d8d8282d6eb8182080732030759651007c8fe4e374025453bb529f88719b6bdb57f501a57e31503e2071f065c5011d84a3a23096c8fe85c771be808401000080fffefefefefefefefefefefefefefefe003c00";
@@ -245,6 +530,8 @@ mod tests {
     static EXPECTED_ENCODING_BEFORE_DONUT: &str = "fdd542ddf4fdd764cddfee7f0933f1b9bc93330f9c7d44ce979da3ccdcef4ea6aa816263a3b4b8e1628000ce81c0d4594601f03d928fd309504ded4a7d22c66dae6d5fd50794fac540f980c4c197150774108e8ac25822fb171ec7f90212eeaf16eaa6efbf266bfe76ff4b9889cfe59d9c79e0ec2372beec1c65e67e7732550d141b1ba5c50d170304700e04a6ce320a80ef917c9c4e806a6a57ea13316e736dfbaa3ea0d42f06ca07240ebeac38a083705414c612d9bff038ce1790707fb550377dff3559f3b7fb5fc24c7c2eefe4cc03671f91f365e72833f7bb93a96aa0d8d8282d6eb8182080732030759651007c8fe4e374025453bb529f88719b6bdb57f501a57e31503e2071f065c5011d84a3a23096c8fe85c771be8084fbab85bae9fbafc99abfddff1266e2737927671e38fb889c2f3b4799b9df9d4c5503c5c6466971c3c500019c0381a9b38c02e07b241fa713a09ada95fa448cdb5cdbbeaa0f28f58b81f20189832f2b0ee8201c1585b144f62f3c8ef30524dc5f2dd44ddf7f4dd6fcedfe9730139fcb3b39f3c0d947e47cd939caccfdee64aa1a2836364a8b1b2e0608e01c084c9d651400df23f9389d00d5d4aed42762dce6daf6557d40a95f0c940f481c7c59714007e1a8288c25b27fe1719c2f20e1fe6aa16efafe6bb2e66ff7bf8499f85cdec99907ce3e22e7cbce5166ee772753d540b1b1515adc70314000e74060ea2ca300f81ec9c7e904a8a676a53e11e336d7b6afea034afd62a07c40e2e0cb8a033a084745612c91fd0b8fe37c0109f7570b75d3f75f93357fbbff25ccc4e7f24ece3c70f611395f768e3273bf3b99aa068a8d8dd2e2868b010238070253671905c0f7483e4e274035b52bf58918b7b9b67d551f50ea1703e50312075f561cd041382a0a6389ec5f781ce70b48b8bf5aa89bbeff9aacf9dbfd2f61263e977772e681b38fc8f9b2739499fbddc95435506c6c9416375c0c10c03910983acb2800be47f2713a01aaa95da94fc4b8cdb5edabfa8052bf18281f9038f8b2e2800ec25151184b64ffc2e3385f40c2fdd542ddf4fdd764cddfee7f0933f1b9bc93330f9c7d44ce979da3ccdcef4ea6aa816263a3b4b8e1628000ce81c0d4594601f03d928fd309504ded4a7d22c66dae6d5fd50794fac540f980c4c197150774108e8ac25822fb171ec7f90212eeaf16eaa6efbf266bfe76ff4b9889cfe59d9c79e0ec2372beec1c65e67e7732550d141b1ba5c50d170304700e04a6ce320a80ef917c9c4e806a6a57ea13316e736dfbaa3ea0d42f06ca07240ebeac38a083705414c612d9bff038ce179030000000308007";
 
     #[test]
+    // the vectors encode the public keys rather than their commitment, and 128 bit entropy
+    #[cfg(not(any(feature = "blinded-validators", feature = "entropy-256")))]
     fn encode_to_bytes() -> Result<(), EncodingError> {
         let pubkeys = (0..10)
             .map(|_| bls12_377::G2Projective::prime_subgroup_generator().into())
@@ -266,6 +553,8 @@ mod tests {
     }
 
     #[test]
+    // the vectors encode the public keys rather than their commitment, and 128 bit entropy
+    #[cfg(not(any(feature = "blinded-validators", feature = "entropy-256")))]
     fn encode_to_bytes_without_entropy() -> Result<(), EncodingError> {
         let pubkeys = (0..10)
             .map(|_| bls12_377::G2Projective::prime_subgroup_generator().into())
@@ -294,6 +583,154 @@ mod tests {
     }
 
     #[test]
+    // the vector is the message without any of the optional fields, with 128 bit entropy
+    #[cfg(not(any(
+        feature = "epoch-aux-data",
+        feature = "chain-binding",
+        feature = "circuit-version",
+        feature = "entropy-256"
+    )))]
+    fn hash_preimage_vector() -> Result<(), EncodingError> {
+        let epoch = EpochBlock::new(
+            0x0102,
+            3,
+            Some((1..=16).collect()),
+            Some((0x80..0x90).collect()),
+            0x0405_0607,
+            0,
+            vec![],
+        );
+        let (message, extra_data) = epoch.encode_inner_to_bytes_cip22()?;
+        assert_eq!(
+            hex::encode(message),
+            "f171b131d1519111e161a121c141810108f070b030d0509010e060a020c04080"
+        );
+        assert_eq!(hex::encode(extra_data), "20a060e0c08040");
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_entropy_of_configured_size() -> Result<(), EncodingError> {
+        let entropy = vec![255u8; EpochBlock::ENTROPY_BYTES];
+        let bits = EpochBlock::encode_entropy_cip22(Some(&entropy));
+        assert_eq!(bits, vec![true; EpochBlock::ENTROPY_BYTES * 8]);
+        // a missing entropy is encoded as zeros of the same size
+        assert_eq!(
+            EpochBlock::encode_entropy_cip22(None),
+            vec![false; EpochBlock::ENTROPY_BYTES * 8]
+        );
+
+        let with_entropy =
+            EpochBlock::new(7, 0, Some(entropy.clone()), Some(entropy), 3, 0, vec![]);
+        let without_entropy = EpochBlock::new(7, 0, None, None, 3, 0, vec![]);
+        for epoch_type in &[EpochType::First, EpochType::Last] {
+            assert_eq!(
+                with_entropy.encode_to_bits_cip22(*epoch_type)?.len(),
+                without_entropy.encode_to_bits_cip22(*epoch_type)?.len()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn signs_circuit_version() -> Result<(), EncodingError> {
+        let version = EpochBlock::encode_circuit_version();
+        assert_eq!(version.len(), EpochBlock::CIRCUIT_VERSION_BYTES * 8);
+        assert_eq!(
+            bits_le_to_bytes_le(&version),
+            CIRCUIT_VERSION.to_le_bytes().to_vec()
+        );
+
+        let entropy = Some(vec![255u8; EpochBlock::ENTROPY_BYTES]);
+        let epoch = EpochBlock::new(7, 0, entropy.clone(), entropy, 3, 0, vec![]);
+        let (epoch_bits, _) = epoch.encode_inner_to_bits_cip22()?;
+        assert_eq!(
+            epoch_bits.ends_with(&version),
+            cfg!(feature = "circuit-version")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_signing_context() -> Result<(), EncodingError> {
+        let epoch = EpochBlock::new(
+            0x0102u16,
+            3u8,
+            None,
+            None,
+            0x0405_0607,
+            1,
+            vec![bls12_377::G2Projective::prime_subgroup_generator().into()],
+        );
+        let context = epoch.encode_signing_context()?;
+        assert_eq!(context, epoch.encode_inner_to_bytes_cip22()?.1);
+        assert_eq!(context.len(), 2 + 1 + 4);
+        Ok(())
+    }
+
+    #[test]
+    fn summarize() -> Result<(), EncodingError> {
+        let pubkeys = (0..10)
+            .map(|_| bls12_377::G2Projective::prime_subgroup_generator().into())
+            .collect::<Vec<_>>();
+        let epoch = EpochBlock::new(
+            120u16,
+            5u8,
+            Some(vec![255u8; EpochBlock::ENTROPY_BYTES]),
+            Some(vec![254u8; EpochBlock::ENTROPY_BYTES]),
+            3,
+            pubkeys.len(),
+            pubkeys.clone(),
+        );
+        let summary = epoch.summarize()?;
+        assert_eq!(summary.index, 120);
+        assert_eq!(summary.max_non_signers, 3);
+
+        // the digests are the ones the circuit commits to
+        let mut last = epoch.clone();
+        last.index += 1;
+        let last_summary = last.summarize()?;
+        assert_eq!(
+            EpochSummary::range_hash(&summary, &last_summary),
+            bits_le_to_bytes_le(&hash_first_last_epoch_block(&epoch, &last)?)
+        );
+        assert_ne!(last_summary.pubkey_hash, summary.pubkey_hash);
+        assert_ne!(last_summary.apk_commitment, summary.apk_commitment);
+
+        // the round is not part of the summary, and the parent entropy is only committed
+        // to when a proof starts from the epoch
+        let mut other = epoch.clone();
+        other.round = 6;
+        assert_eq!(other.summarize()?, summary);
+        other.parent_entropy = None;
+        assert_eq!(other.summarize()?.apk_commitment, summary.apk_commitment);
+        assert_ne!(other.summarize()?.pubkey_hash, summary.pubkey_hash);
+
+        if !cfg!(feature = "blinded-validators") {
+            // padding to the maximum validators is equivalent to adding generators, except
+            // for the aggregate public key
+            let mut padded = epoch.clone();
+            padded.maximum_validators += 1;
+            let mut extended = epoch;
+            extended
+                .new_public_keys
+                .push(bls12_377::G2Projective::prime_subgroup_generator().into());
+            extended.maximum_validators += 1;
+            assert_eq!(
+                padded.summarize()?.pubkey_hash,
+                extended.summarize()?.pubkey_hash
+            );
+            assert_ne!(
+                padded.summarize()?.apk_commitment,
+                extended.summarize()?.apk_commitment
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    // the vectors encode the public keys rather than their commitment, and 128 bit entropy
+    #[cfg(not(any(feature = "blinded-validators", feature = "entropy-256")))]
     fn encode_to_bytes_padded() -> Result<(), EncodingError> {
         let pubkeys = (0..10)
             .map(|_| bls12_377::G2Projective::prime_subgroup_generator().into())
@@ -317,4 +754,94 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn validator_commitment() -> Result<(), EncodingError> {
+        let rng = &mut rand::thread_rng();
+        let pubkeys = (0..EpochBlock::MAX_BLINDED_VALIDATORS + 1)
+            .map(|_| bls12_377::G2Projective::rand(rng).into())
+            .collect::<Vec<PublicKey>>();
+        let epoch = EpochBlock::new(1, 0, None, None, 1, 4, pubkeys[..4].to_vec())
+            .with_validator_blinding([1; 32]);
+
+        let commitment = epoch.encode_validator_commitment()?;
+        assert_eq!(commitment.len(), EpochBlock::VALIDATOR_COMMITMENT_BITS);
+        assert_eq!(epoch.encode_validator_commitment()?, commitment);
+        // the commitment depends on the blinding and on every public key
+        let other = epoch.clone().with_validator_blinding([2; 32]);
+        assert_ne!(other.encode_validator_commitment()?, commitment);
+        let mut other = epoch.clone();
+        other.new_public_keys[3] = pubkeys[4].clone();
+        assert_ne!(other.encode_validator_commitment()?, commitment);
+
+        // the CRH's input fits at most `MAX_BLINDED_VALIDATORS` public keys
+        let mut epoch = epoch;
+        epoch.new_public_keys = pubkeys[..EpochBlock::MAX_BLINDED_VALIDATORS].to_vec();
+        epoch.maximum_validators = epoch.new_public_keys.len();
+        epoch.validator_commitment()?;
+        epoch.new_public_keys = pubkeys;
+        epoch.maximum_validators = epoch.new_public_keys.len();
+        assert!(epoch.validator_commitment().is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "blinded-validators")]
+    fn hashes_validator_commitments() -> Result<(), EncodingError> {
+        let rng = &mut rand::thread_rng();
+        let pubkeys = (0..4)
+            .map(|_| bls12_377::G2Projective::rand(rng).into())
+            .collect::<Vec<PublicKey>>();
+        let first = EpochBlock::new(
+            1,
+            0,
+            None,
+            Some(vec![1; EpochBlock::ENTROPY_BYTES]),
+            1,
+            4,
+            pubkeys.clone(),
+        )
+        .with_validator_blinding([1; 32]);
+        let last = EpochBlock::new(
+            5,
+            0,
+            Some(vec![2; EpochBlock::ENTROPY_BYTES]),
+            None,
+            1,
+            4,
+            pubkeys,
+        )
+        .with_validator_blinding([2; 32]);
+        let (first_commitment, last_commitment) =
+            (first.validator_commitment()?, last.validator_commitment()?);
+
+        // a verifier which only knows the commitments gets the same hash
+        let unknown = |epoch: &EpochBlock| {
+            let mut epoch = epoch.clone();
+            epoch.new_public_keys = vec![];
+            epoch.validator_blinding = None;
+            epoch
+        };
+        let (first_unknown, last_unknown) = (unknown(&first), unknown(&last));
+        let hash = hash_first_last_epoch_block(&first, &last)?;
+        assert_eq!(
+            hash_first_last_validator_commitments(
+                &first_unknown,
+                &first_commitment,
+                &last_unknown,
+                &last_commitment
+            )?,
+            hash
+        );
+        assert_ne!(
+            hash_first_last_validator_commitments(
+                &first_unknown,
+                &last_commitment,
+                &last_unknown,
+                &first_commitment
+            )?,
+            hash
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,103 @@
+//! Structured fuzzing support
+//!
+//! `Arbitrary` implementations for the epoch types along with entrypoints for every
+//! path which deserializes or encodes untrusted data.
+use crate::{analysis::NonSignerReport, api::BWCurve, EpochBlock, EpochTransition};
+
+use algebra::CanonicalDeserialize;
+use arbitrary::{Arbitrary, Result, Unstructured};
+use bls_crypto::{PublicKey, Signature};
+use groth16::{Proof, VerifyingKey};
+
+/// Upper bound on the validators of an arbitrary epoch, so that encoding stays fast
+const MAX_FUZZ_VALIDATORS: usize = 150;
+
+fn arbitrary_entropy(u: &mut Unstructured<'_>) -> Result<Option<Vec<u8>>> {
+    Ok(if bool::arbitrary(u)? {
+        let entropy: [u8; EpochBlock::ENTROPY_BYTES] = Arbitrary::arbitrary(u)?;
+        Some(entropy.to_vec())
+    } else {
+        None
+    })
+}
+
+impl Arbitrary for EpochBlock {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Result<Self> {
+        let num_keys = u.int_in_range(0..=MAX_FUZZ_VALIDATORS)?;
+        let new_public_keys = (0..num_keys)
+            .map(|_| PublicKey::arbitrary(u))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(EpochBlock::new(
+            u16::arbitrary(u)?,
+            u8::arbitrary(u)?,
+            arbitrary_entropy(u)?,
+            arbitrary_entropy(u)?,
+            u32::arbitrary(u)?,
+            u.int_in_range(0..=MAX_FUZZ_VALIDATORS)?,
+            new_public_keys,
+        ))
+    }
+}
+
+impl Arbitrary for EpochTransition {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Result<Self> {
+        let block = EpochBlock::arbitrary(u)?;
+        let aggregate_signature = Signature::arbitrary(u)?;
+        let num_bits = u.int_in_range(0..=MAX_FUZZ_VALIDATORS)?;
+        let bitmap = (0..num_bits)
+            .map(|_| bool::arbitrary(u))
+            .collect::<Result<_>>()?;
+        Ok(EpochTransition {
+            block,
+            aggregate_signature,
+            bitmap,
+        })
+    }
+}
+
+/// Fuzz entrypoint for the non-signer report decoding. Decoded reports must re-encode
+/// to the same bytes.
+pub fn non_signer_report_from_bytes(data: &[u8]) {
+    if let Ok(report) = NonSignerReport::from_bytes(data) {
+        let encoded = report.to_bytes().unwrap();
+        assert_eq!(&data[..encoded.len()], &encoded[..]);
+    }
+}
+
+/// Fuzz entrypoint for proof deserialization
+pub fn proof_from_bytes(data: &[u8]) {
+    let _ = Proof::<BWCurve>::deserialize(data);
+}
+
+/// Fuzz entrypoint for verifying key deserialization
+pub fn verifying_key_from_bytes(data: &[u8]) {
+    let _ = VerifyingKey::<BWCurve>::deserialize(data);
+}
+
+/// Fuzz entrypoint for the epoch block encodings which are hashed in the circuit
+pub fn encode_epoch_block(block: &EpochBlock) {
+    let _ = block.encode_to_bytes();
+    let _ = block.encode_inner_to_bytes_cip22();
+    let _ = block.encode_first_epoch_to_bytes_cip22();
+    let _ = block.encode_last_epoch_to_bytes_with_aggregated_pk_cip22();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbitrary_epochs_encode() {
+        let data = (0..4096u32).map(|i| (i * 31) as u8).collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..4 {
+            let transition = EpochTransition::arbitrary(&mut u).unwrap();
+            assert!(transition.block.new_public_keys.len() <= MAX_FUZZ_VALIDATORS);
+            encode_epoch_block(&transition.block);
+        }
+
+        non_signer_report_from_bytes(&data);
+        proof_from_bytes(&data);
+        verifying_key_from_bytes(&data);
+    }
+}
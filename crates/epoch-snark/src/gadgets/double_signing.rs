@@ -0,0 +1,208 @@
+//! # Double Signing Circuit
+//!
+//! Proves that a validator signed two different epoch blocks with the same index and the
+//! same consensus round, which is grounds for slashing it. Blocks signed in different rounds
+//! are not conflicting, since validators legitimately sign a new proposal in each round. The
+//! public inputs are the epochs' index followed by the validator's public key, so the proof
+//! can be checked on-chain without revealing the conflicting blocks.
+
+use crate::gadgets::{g2_to_bits, EpochData, EpochIndexVar, MultipackGadget};
+use bls_gadgets::BlsVerifyGadget;
+
+use algebra::{
+    bls12_377::{Bls12_377, Parameters as Bls12_377_Parameters},
+    bw6_761::{Fr, FrParameters},
+    curves::bls12::Bls12Parameters,
+    FpParameters, PairingEngine,
+};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use r1cs_std::{
+    bls12_377::{G1Var, G2Var, PairingVar},
+    fields::fp::FpVar,
+    prelude::*,
+    Assignment,
+};
+use tracing::{info, span, Level};
+
+type BlsGadget = BlsVerifyGadget<Bls12_377, Fr, PairingVar>;
+type FrVar = FpVar<Fr>;
+type Bool = Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>;
+
+#[derive(Clone, Debug)]
+/// Two conflicting epoch blocks and the signatures of the same validator over each of them.
+/// Both epochs must have the same number of validators, which determines the circuit.
+pub struct DoubleSigning<E: PairingEngine> {
+    /// The first of the conflicting epochs
+    pub first_epoch: EpochData<E>,
+    /// The second of the conflicting epochs
+    pub second_epoch: EpochData<E>,
+    /// The public key of the validator which signed both epochs
+    pub public_key: Option<E::G2Projective>,
+    /// The validator's signature over the first epoch
+    pub first_signature: Option<E::G1Projective>,
+    /// The validator's signature over the second epoch
+    pub second_signature: Option<E::G1Projective>,
+}
+
+impl<E: PairingEngine> DoubleSigning<E> {
+    /// Initializes an empty double signing statement. This is used when running the trusted
+    /// setup.
+    pub fn empty(num_validators: usize) -> Self {
+        DoubleSigning {
+            first_epoch: EpochData::empty(num_validators, 0),
+            second_epoch: EpochData::empty(num_validators, 0),
+            public_key: None,
+            first_signature: None,
+            second_signature: None,
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for DoubleSigning<Bls12_377> {
+    /// Enforces that both epochs have the public index and the same round, that they are
+    /// different and that the public key signed both of them
+    #[tracing::instrument(target = "r1cs")]
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let span = span!(Level::TRACE, "DoubleSigning");
+        let _enter = span.enter();
+        info!("generating constraints");
+
+        let index = FrVar::new_input(cs.clone(), || Ok(Fr::from(self.first_epoch.index.get()?)))?;
+        let public_key = G2Var::new_witness(cs.clone(), || self.public_key.get())?;
+        MultipackGadget::pack::<_, FrParameters>(
+            &g2_to_bits(&public_key)?,
+            FrParameters::CAPACITY as usize,
+            true,
+        )?;
+
+        let (first_index, first_round, first_bits, first_hash) =
+            self.first_epoch.constrain_message(&cs)?;
+        let (second_index, second_round, second_bits, second_hash) =
+            self.second_epoch.constrain_message(&cs)?;
+        index.enforce_equal(first_index.as_fp())?;
+        index.enforce_equal(second_index.as_fp())?;
+        first_round[..].enforce_equal(&second_round[..])?;
+
+        // the epochs conflict if any of their signed bits differ
+        let equal_bits = first_bits
+            .iter()
+            .zip(&second_bits)
+            .map(|(first, second)| first.is_eq(second))
+            .collect::<Result<Vec<_>, _>>()?;
+        Bool::kary_and(&equal_bits)?.enforce_equal(&Bool::constant(false))?;
+
+        let first_signature = G1Var::new_witness(cs.clone(), || self.first_signature.get())?;
+        let second_signature = G1Var::new_witness(cs, || self.second_signature.get())?;
+        BlsGadget::batch_verify(&[public_key.clone()], &[first_hash], &first_signature)?;
+        BlsGadget::batch_verify(&[public_key], &[second_hash], &second_signature)?;
+        info!("constraints generated");
+
+        Ok(())
+    }
+}
+
+impl EpochData<Bls12_377> {
+    /// Returns the epoch's index, the bits of its round, the bits of the message which
+    /// validators sign for it and its hash to G1, with the CRH->XOF hash constrained in
+    /// BW6_761
+    fn constrain_message(
+        &self,
+        cs: &ConstraintSystemRef<Fr>,
+    ) -> Result<(EpochIndexVar, Vec<Bool>, Vec<Bool>, G1Var), SynthesisError> {
+        let (bits, extra_data_bits, _, _, index, _, _, _, _, _, _) = self.to_bits(cs.clone())?;
+        let (message_hash, _, _) =
+            Self::hash_bits_to_g1(&bits, &extra_data_bits, self.hash_counter, true)?;
+        // the signing context is the index, the round and the maximum number of non-signers
+        let round = extra_data_bits[EpochIndexVar::BITS..EpochIndexVar::BITS + 8].to_vec();
+        Ok((index, round, [bits, extra_data_bits].concat(), message_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::to_epoch_data, encoding::encode_public_key, epoch_block::EpochBlock, gadgets::pack,
+    };
+    use algebra::{bls12_377::G2Projective, ProjectiveCurve};
+    use bls_crypto::{
+        hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, PrivateKey, PublicKey,
+    };
+    use bls_gadgets::utils::test_helpers::print_unsatisfied_constraints;
+    use r1cs_core::ConstraintSystem;
+
+    fn epoch(index: u16, round: u8, public_keys: Vec<PublicKey>) -> EpochBlock {
+        EpochBlock::new(
+            index,
+            round,
+            Some(vec![1; EpochBlock::ENTROPY_BYTES]),
+            Some(vec![2; EpochBlock::ENTROPY_BYTES]),
+            0,
+            public_keys.len(),
+            public_keys,
+        )
+    }
+
+    fn sign(key: &PrivateKey, block: &EpochBlock) -> <Bls12_377 as PairingEngine>::G1Projective {
+        let (message, extra_data) = block.encode_inner_to_bytes_cip22().unwrap();
+        *key.sign(&message, &extra_data, &*COMPOSITE_HASH_TO_G1_CIP22)
+            .unwrap()
+            .as_ref()
+    }
+
+    fn is_satisfied(first: &EpochBlock, second: &EpochBlock, key: &PrivateKey) -> bool {
+        let circuit = DoubleSigning {
+            first_epoch: to_epoch_data(first),
+            second_epoch: to_epoch_data(second),
+            public_key: Some(*key.to_public().as_ref()),
+            first_signature: Some(sign(key, first)),
+            second_signature: Some(sign(key, second)),
+        };
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+
+        // the public inputs are the index and the packed public key
+        let mut public_inputs = vec![Fr::from(first.index)];
+        public_inputs.extend(
+            pack::<Fr, FrParameters>(&encode_public_key(&key.to_public()).unwrap()).unwrap(),
+        );
+        assert_eq!(
+            cs.borrow().unwrap().instance_assignment[1..].to_vec(),
+            public_inputs
+        );
+
+        print_unsatisfied_constraints(cs.clone());
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn proves_double_signing() {
+        let rng = &mut rand::thread_rng();
+        let key = PrivateKey::generate(rng);
+        let validators = vec![
+            key.to_public(),
+            PublicKey::from(G2Projective::prime_subgroup_generator()),
+        ];
+        let first = epoch(3, 0, validators.clone());
+        let mut other_validators = validators.clone();
+        other_validators[1] = PrivateKey::generate(rng).to_public();
+
+        // a different validator set at the same index and round is a conflict
+        assert!(is_satisfied(
+            &first,
+            &epoch(3, 0, other_validators.clone()),
+            &key
+        ));
+        // signing the same epoch twice is not, and neither is signing a new proposal in
+        // another round
+        assert!(!is_satisfied(&first, &first, &key));
+        assert!(!is_satisfied(
+            &first,
+            &epoch(3, 1, validators.clone()),
+            &key
+        ));
+        assert!(!is_satisfied(&first, &epoch(3, 1, other_validators), &key));
+        // and neither is signing epochs with different indices
+        assert!(!is_satisfied(&first, &epoch(4, 0, validators), &key));
+    }
+}
@@ -1,31 +1,46 @@
 use algebra::{
-    bls12_377::{
-        Bls12_377, Fr as BlsFr, FrParameters as BlsFrParameters, Parameters as Bls12_377_Parameters,
-    },
+    bls12_377::{Bls12_377, FrParameters as BlsFrParameters, Parameters as Bls12_377_Parameters},
     bw6_761::{Fr, FrParameters},
     curves::bls12::Bls12Parameters,
     FpParameters,
 };
 
-// Groth16 Specific imports
-use crypto_primitives::{
-    nizk::{
-        constraints::NIZKVerifierGadget,
-        groth16::{
-            constraints::{Groth16VerifierGadget, ProofVar, VerifyingKeyVar},
-            Groth16,
-        },
-    },
-    prf::blake2s::{constraints::evaluate_blake2s_with_parameters, Blake2sWithParameterBlock},
+use crypto_primitives::prf::blake2s::{
+    constraints::evaluate_blake2s_with_parameters, Blake2sWithParameterBlock,
 };
 use r1cs_core::{ConstraintSystemRef, SynthesisError};
-use r1cs_std::{bls12_377::PairingVar, fields::fp::FpVar, prelude::*};
+use r1cs_std::{alloc::AllocationMode, fields::fp::FpVar, prelude::*};
 
 type FrVar = FpVar<Fr>;
 type Bool = Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>;
 
-use crate::gadgets::{HashToBits, HashToBitsHelper, MultipackGadget};
-use bls_crypto::OUT_DOMAIN;
+use crate::{
+    api::CIRCUIT_VERSION,
+    gadgets::{
+        extra_inputs::enforce_extra_inputs, verify_bls12_377_groth16, ExtraPublicInput, HashToBits,
+        HashToBitsHelper, MultipackGadget,
+    },
+};
+use bls_crypto::{
+    PoseidonParameters, AUX_DATA_DOMAIN, BITMAP_DOMAIN, OUT_DOMAIN, RANDOMNESS_COMMITMENT_DOMAIN,
+};
+use bls_gadgets::PoseidonGadget;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+/// Poseidon parameters (8 full and 60 partial rounds, with the `x^5` S-box) with which the
+/// packed public inputs are compressed to a single field element when the
+/// `compressed-public-inputs` feature is enabled
+pub static PUBLIC_INPUTS_POSEIDON: Lazy<PoseidonParameters<Fr>> = Lazy::new(|| {
+    PoseidonParameters::new(8, 60, 5, b"public inputs")
+        .expect("the Poseidon domain is not larger than 8 bytes")
+});
+
+/// Compresses the packed public inputs of the epochs circuit to their Poseidon hash, which is
+/// the only public input of the circuit with the `compressed-public-inputs` feature
+pub fn compress_public_inputs(inputs: &[Fr]) -> Fr {
+    PUBLIC_INPUTS_POSEIDON.hash(inputs)
+}
 
 /// Contains the first and last epoch's bits, along with auxiliary CRH and XOF bits
 /// which are used for verifying the CRH -> XOF hash calculation
@@ -38,26 +53,43 @@ pub struct EpochBits {
     pub crh_bits: Vec<Bool>,
     /// The XOF bits for all intermediate state transitions
     pub xof_bits: Vec<Bool>,
+    /// The index and signed bitmap of each epoch, padded to whole bytes, which are
+    /// committed to when the `bitmap-commitment` feature is enabled
+    pub bitmap_bits: Vec<Bool>,
+    /// The index and auxiliary data of each epoch, which are committed to when the
+    /// `epoch-aux-data` feature is enabled
+    pub aux_data_bits: Vec<Bool>,
+    /// The index and randomness output of each epoch, which are committed to when the
+    /// `randomness-beacon` feature is enabled
+    pub randomness_bits: Vec<Bool>,
+    /// The first epoch's chain identifier, which every epoch is signed for when the
+    /// `chain-binding` feature is enabled
+    pub chain_id_bits: Vec<Bool>,
 }
 
 impl EpochBits {
-    /// Verify that the intermediate proofs are computed correctly and that the edges are correctly calculated
+    /// Verify that the intermediate proofs are computed correctly and that the edges are correctly calculated.
+    /// The hashes of the extra inputs are exposed after the edges and the features' commitments.
     pub fn verify(
         &self,
         helper: Option<HashToBitsHelper<Bls12_377>>,
+        extra_inputs: &[Arc<dyn ExtraPublicInput>],
         cs: ConstraintSystemRef<<Bls12_377_Parameters as Bls12Parameters>::Fp>,
     ) -> Result<(), SynthesisError> {
         // Only verify the proof if it was provided
         if let Some(helper) = helper {
             self.verify_proof(&helper, cs)?;
         }
-        self.verify_edges()?;
+        self.verify_edges(extra_inputs)?;
         Ok(())
     }
 
     /// Generates constrained hash outputs on the first and last
     /// epoch bits
-    fn verify_edges(&self) -> Result<Vec<FrVar>, SynthesisError> {
+    fn verify_edges(
+        &self,
+        extra_inputs: &[Arc<dyn ExtraPublicInput>],
+    ) -> Result<Vec<FrVar>, SynthesisError> {
         // Verify the edges
         let mut xof_bits = vec![];
         let first_and_last_bits = [self.first_epoch_bits.clone(), self.last_epoch_bits.clone()];
@@ -66,41 +98,81 @@ impl EpochBits {
             message.reverse();
             let message_rounded_len = 8 * ((message.len() + 7) / 8);
             message.resize(message_rounded_len, Bool::constant(false));
-
-            let mut personalization = [0; 8];
-            personalization.copy_from_slice(OUT_DOMAIN);
-
-            let blake2s_parameters = Blake2sWithParameterBlock {
-                digest_length: 32,
-                key_length: 0,
-                fan_out: 1,
-                depth: 1,
-                leaf_length: 0,
-                node_offset: 0,
-                xof_digest_length: 0,
-                node_depth: 0,
-                inner_length: 0,
-                salt: [0; 8],
-                personalization,
-            };
-            let xof_result =
-                evaluate_blake2s_with_parameters(&message, &blake2s_parameters.parameters())?;
-            let xof_bits_i = xof_result
-                .into_iter()
-                .map(|n| n.to_bits_le())
-                .flatten()
-                .collect::<Vec<Bool>>();
-            xof_bits.extend_from_slice(&xof_bits_i);
+            xof_bits.extend_from_slice(&blake2s(&message, OUT_DOMAIN)?);
         }
 
         // Make the edges public inputs
-        // packed over BW6_761 Fr.
-        let packed = MultipackGadget::pack::<_, FrParameters>(
+        // packed over BW6_761 Fr. With the `compressed-public-inputs` feature, the packed
+        // elements are witnesses and only their Poseidon hash is a public input.
+        let alloc_inputs = !cfg!(feature = "compressed-public-inputs");
+        let mut packed = MultipackGadget::pack::<_, FrParameters>(
             &xof_bits,
             FrParameters::CAPACITY as usize,
-            true,
+            alloc_inputs,
         )?;
 
+        if cfg!(feature = "chain-binding") {
+            packed.extend(MultipackGadget::pack::<_, FrParameters>(
+                &self.chain_id_bits,
+                FrParameters::CAPACITY as usize,
+                alloc_inputs,
+            )?);
+        }
+
+        // the version which every epoch's message is signed for, see `EpochData::to_bits`
+        if cfg!(feature = "circuit-version") {
+            let version = Fr::from(CIRCUIT_VERSION);
+            let mode = if alloc_inputs {
+                AllocationMode::Input
+            } else {
+                AllocationMode::Witness
+            };
+            let input = FrVar::new_variable(self.first_epoch_bits.cs(), || Ok(version), mode)?;
+            input.enforce_equal(&FrVar::constant(version))?;
+            packed.push(input);
+        }
+
+        if cfg!(feature = "bitmap-commitment") {
+            let commitment_bits = blake2s(&self.bitmap_bits, BITMAP_DOMAIN)?;
+            packed.extend(MultipackGadget::pack::<_, FrParameters>(
+                &commitment_bits,
+                FrParameters::CAPACITY as usize,
+                alloc_inputs,
+            )?);
+        }
+
+        if cfg!(feature = "epoch-aux-data") {
+            let commitment_bits = blake2s(&self.aux_data_bits, AUX_DATA_DOMAIN)?;
+            packed.extend(MultipackGadget::pack::<_, FrParameters>(
+                &commitment_bits,
+                FrParameters::CAPACITY as usize,
+                alloc_inputs,
+            )?);
+        }
+
+        if cfg!(feature = "randomness-beacon") {
+            let commitment_bits = blake2s(&self.randomness_bits, RANDOMNESS_COMMITMENT_DOMAIN)?;
+            packed.extend(MultipackGadget::pack::<_, FrParameters>(
+                &commitment_bits,
+                FrParameters::CAPACITY as usize,
+                alloc_inputs,
+            )?);
+        }
+
+        packed.extend(enforce_extra_inputs(
+            extra_inputs,
+            self.first_epoch_bits.cs(),
+            self,
+            alloc_inputs,
+        )?);
+
+        if cfg!(feature = "compressed-public-inputs") {
+            let compressed = PoseidonGadget::hash(&PUBLIC_INPUTS_POSEIDON, &packed)?;
+            let input = FrVar::new_input(compressed.cs(), || compressed.value())?;
+            input.enforce_equal(&compressed)?;
+            packed = vec![input];
+        }
+
         Ok(packed)
     }
 
@@ -110,15 +182,6 @@ impl EpochBits {
         helper: &HashToBitsHelper<Bls12_377>,
         cs: ConstraintSystemRef<<Bls12_377_Parameters as Bls12Parameters>::Fp>,
     ) -> Result<(), SynthesisError> {
-        // Verify the proof
-        let proof = ProofVar::<_, PairingVar>::new_witness(cs, || Ok(helper.proof.clone()))?;
-
-        // Allocate the VK
-        let verifying_key = VerifyingKeyVar::<_, PairingVar>::new_constant(
-            proof.a.cs(),
-            helper.verifying_key.clone(),
-        )?;
-
         // The public inputs are the CRH and XOF bits split in `Fr::CAPACITY` chunks
         // encoded in LE
         let packed_crh_bits = le_chunks(&self.crh_bits, BlsFrParameters::CAPACITY);
@@ -126,15 +189,42 @@ impl EpochBits {
 
         let public_inputs: Vec<Vec<Bool>> = [packed_crh_bits, packed_xof_bits].concat();
 
-        let _ = <Groth16VerifierGadget<_, PairingVar> as NIZKVerifierGadget<
-            Groth16<Bls12_377, HashToBits, BlsFr>,
-            Fr,
-        >>::verify(&verifying_key, public_inputs.iter(), &proof)?;
-
-        Ok(())
+        verify_bls12_377_groth16::<HashToBits>(
+            cs,
+            &helper.verifying_key,
+            &helper.proof,
+            &public_inputs,
+        )
     }
 }
 
+/// Returns the LE bits of the Blake2s hash of the LE bits of `message`, personalized
+/// to `domain`
+pub(super) fn blake2s(message: &[Bool], domain: &[u8]) -> Result<Vec<Bool>, SynthesisError> {
+    let mut personalization = [0; 8];
+    personalization.copy_from_slice(domain);
+
+    let blake2s_parameters = Blake2sWithParameterBlock {
+        digest_length: 32,
+        key_length: 0,
+        fan_out: 1,
+        depth: 1,
+        leaf_length: 0,
+        node_offset: 0,
+        xof_digest_length: 0,
+        node_depth: 0,
+        inner_length: 0,
+        salt: [0; 8],
+        personalization,
+    };
+    let result = evaluate_blake2s_with_parameters(message, &blake2s_parameters.parameters())?;
+    Ok(result
+        .into_iter()
+        .map(|n| n.to_bits_le())
+        .flatten()
+        .collect())
+}
+
 /// Returns a vector of vectors of constrained booleans
 /// all of the same size given a vector of constrained
 /// booleans
@@ -151,14 +241,48 @@ fn le_chunks(iter: &[Bool], chunk_size: u32) -> Vec<Vec<Bool>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{epoch_block::hash_to_bits, gadgets::pack};
+    use crate::{
+        aux_data_commitment::AuxDataCommitment,
+        bitmap_commitment::BitmapCommitment,
+        epoch_block::{hash_to_bits, EpochBlock, EpochTransition},
+        gadgets::{extra_public_inputs, pack},
+        randomness_beacon::RandomnessBeacon,
+        scalars::OuterScalar,
+    };
+    use algebra::{bls12_377::G1Projective, ProjectiveCurve};
+    use bls_crypto::Signature;
     use bls_gadgets::utils::{
-        bytes_le_to_bits_be,
+        bytes_le_to_bits_be, bytes_le_to_bits_le,
         test_helpers::{print_unsatisfied_constraints, run_profile_constraints},
     };
 
     use r1cs_core::ConstraintSystem;
-    use rand::RngCore;
+    use rand::{Rng, RngCore};
+
+    // an extra input which exposes the hash of fixed bytes
+    #[derive(Debug)]
+    struct FixedBytes(Vec<u8>);
+
+    impl ExtraPublicInput for FixedBytes {
+        fn domain(&self) -> [u8; 8] {
+            *b"ULtestex"
+        }
+
+        fn generate_bits(
+            &self,
+            cs: ConstraintSystemRef<Fr>,
+            _: &EpochBits,
+        ) -> Result<Vec<Bool>, SynthesisError> {
+            bytes_le_to_bits_le(&self.0, 8 * self.0.len())
+                .iter()
+                .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)))
+                .collect()
+        }
+
+        fn to_bytes(&self) -> Vec<u8> {
+            self.0.clone()
+        }
+    }
 
     #[test]
     fn correct_blake2_hash() {
@@ -178,6 +302,22 @@ mod tests {
             .flatten()
             .collect::<Vec<bool>>();
 
+        let transitions = (1..4)
+            .map(|index| EpochTransition {
+                block: EpochBlock::new(index, 0, None, None, 3, 10, vec![])
+                    .with_aux_data(rng.gen()),
+                aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                bitmap: (0..10).map(|_| rng.gen()).collect(),
+            })
+            .collect::<Vec<_>>();
+        let bitmap_commitment = BitmapCommitment::new(&transitions, 5, 10);
+        let bitmap_bytes = bitmap_commitment.to_bytes();
+        let aux_data_commitment = AuxDataCommitment::new(&transitions, 5);
+        let aux_data_bytes = aux_data_commitment.to_bytes();
+        let randomness_beacon = RandomnessBeacon::new(&transitions, 5);
+        let randomness_bytes = randomness_beacon.to_bytes();
+        let chain_id = rng.gen();
+
         let cs = ConstraintSystem::<Fr>::new_ref();
         // encode each epoch's bytes to LE and pass them to the constraint system
         let first_epoch_bits = bytes_le_to_bits_be(&first_bytes, 256);
@@ -195,9 +335,33 @@ mod tests {
                 .map(|b| Boolean::new_input(cs.clone(), || Ok(*b)))
                 .collect::<Result<Vec<_>, _>>()
                 .unwrap(),
+            bitmap_bits: bytes_le_to_bits_le(&bitmap_bytes, 8 * bitmap_bytes.len())
+                .iter()
+                .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            aux_data_bits: bytes_le_to_bits_le(&aux_data_bytes, 8 * aux_data_bytes.len())
+                .iter()
+                .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            randomness_bits: bytes_le_to_bits_le(&randomness_bytes, 8 * randomness_bytes.len())
+                .iter()
+                .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            chain_id_bits: EpochBlock::encode_chain_id(Some(&chain_id))
+                .iter()
+                .map(|b| Boolean::new_witness(cs.clone(), || Ok(*b)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
         };
 
-        let packed = bits.verify_edges().unwrap();
+        let extra_inputs: Vec<Arc<dyn ExtraPublicInput>> = vec![
+            Arc::new(FixedBytes(vec![7; 5])),
+            Arc::new(FixedBytes(vec![9; 40])),
+        ];
+        let packed = bits.verify_edges(&extra_inputs).unwrap();
 
         print_unsatisfied_constraints(cs.clone());
         assert!(cs.is_satisfied().unwrap());
@@ -208,7 +372,28 @@ mod tests {
             .map(|i| i.value().unwrap())
             .collect::<Vec<_>>();
         // pack our bits to Fr as well, and see if they match
-        let public_inputs = pack::<Fr, FrParameters>(&both_blake_bits).unwrap();
+        let mut public_inputs = pack::<Fr, FrParameters>(&both_blake_bits).unwrap();
+        if cfg!(feature = "chain-binding") {
+            public_inputs.extend(
+                pack::<Fr, FrParameters>(&EpochBlock::encode_chain_id(Some(&chain_id))).unwrap(),
+            );
+        }
+        if cfg!(feature = "circuit-version") {
+            public_inputs.push(Fr::from(CIRCUIT_VERSION));
+        }
+        if cfg!(feature = "bitmap-commitment") {
+            public_inputs.extend(OuterScalar::to_fields(&bitmap_commitment.public_inputs()));
+        }
+        if cfg!(feature = "epoch-aux-data") {
+            public_inputs.extend(OuterScalar::to_fields(&aux_data_commitment.public_inputs()));
+        }
+        if cfg!(feature = "randomness-beacon") {
+            public_inputs.extend(OuterScalar::to_fields(&randomness_beacon.public_inputs()));
+        }
+        public_inputs.extend(OuterScalar::to_fields(&extra_public_inputs(&extra_inputs)));
+        if cfg!(feature = "compressed-public-inputs") {
+            public_inputs = vec![compress_public_inputs(&public_inputs)];
+        }
         assert_eq!(inner, public_inputs);
     }
 }
@@ -2,10 +2,16 @@ use algebra::{
     bls12_377::{Bls12_377, Fq as Bls12_377_Fq, Parameters as Bls12_377_Parameters},
     bw6_761::Fr,
     curves::bls12::Bls12Parameters,
-    One, PairingEngine,
+    AffineCurve, PairingEngine, ProjectiveCurve,
+};
+use bls_crypto::{
+    hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, ErrorCode, ToErrorCode,
+    SIG_DOMAIN,
+};
+use bls_gadgets::{
+    bits::{bytes_le_to_fp, fp_to_bits_le, is_less_than_be},
+    FpUtils, HashToGroupGadget,
 };
-use bls_crypto::{hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, SIG_DOMAIN};
-use bls_gadgets::{FpUtils, HashToGroupGadget};
 use r1cs_core::{ConstraintSystemRef, SynthesisError};
 use r1cs_std::{
     alloc::AllocationMode,
@@ -15,7 +21,9 @@ use r1cs_std::{
     Assignment,
 };
 
-use super::{bytes_to_fr, fr_to_bits, g2_to_bits};
+use super::{g2_to_bits, EpochIndexVar, G2_BITS};
+use crate::epoch_block::{EpochBlock, VALIDATOR_COMMITMENT_HASHER};
+use thiserror::Error;
 use tracing::{span, trace, Level};
 
 type FrVar = FpVar<Fr>;
@@ -40,6 +48,53 @@ pub struct EpochData<E: PairingEngine> {
     pub parent_entropy: Option<Vec<u8>>,
     /// The public keys at the epoch
     pub public_keys: Vec<Option<E::G2Projective>>,
+    /// Auxiliary data which is part of the signed message when the `epoch-aux-data`
+    /// feature is enabled
+    pub aux_data: Option<[u8; 32]>,
+    /// Identifier of the epoch's chain which is part of the signed message when the
+    /// `chain-binding` feature is enabled
+    pub chain_id: Option<[u8; 32]>,
+    /// Blinding of the commitment to the epoch's validator set, which replaces the public
+    /// keys in the first and last epoch bits when the `blinded-validators` feature is enabled
+    pub validator_blinding: Option<[u8; 32]>,
+    /// The try-and-increment counter of the epoch's hash to G1, which is searched for while
+    /// generating the constraints if it is not set. The prover searches for the counters of
+    /// all the epochs in parallel beforehand.
+    pub hash_counter: Option<u8>,
+}
+
+/// Bit vectors which are recycled across the epochs of a circuit, so that the encoding of each
+/// epoch reuses the allocations of the previous one. The epoch bits hold one bit gadget per bit
+/// of every public key, so for large validator sets they are by far the largest buffers which
+/// the synthesis of an epoch allocates outside of the constraint system.
+#[derive(Clone, Debug, Default)]
+pub struct BitBuffers {
+    free: Vec<Vec<Bool>>,
+}
+
+impl BitBuffers {
+    /// Takes an empty buffer from the pool, with room for at least `capacity` bits
+    pub fn take(&mut self, capacity: usize) -> Vec<Bool> {
+        let mut buffer = self.free.pop().unwrap_or_default();
+        buffer.reserve_exact(capacity);
+        buffer
+    }
+
+    /// Returns a buffer to the pool, to be reused by the next epoch
+    pub fn recycle(&mut self, mut buffer: Vec<Bool>) {
+        buffer.clear();
+        self.free.push(buffer);
+    }
+
+    /// The number of buffers available for reuse
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Whether there are no buffers available for reuse
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
 }
 
 /// Output type of EpochData.to_bits including bit representation and gadgets.
@@ -48,11 +103,13 @@ type EpochDataToBits = (
     Vec<Bool>,
     Vec<Bool>,
     Vec<Bool>,
-    FrVar,
+    EpochIndexVar,
     FrVar,
     FrVar,
     FrVar,
     Vec<G2Var>,
+    Vec<Bool>,
+    Vec<Bool>,
 );
 
 /// [`EpochData`] is constrained to a `ConstrainedEpochData` via [`EpochData.constrain`]
@@ -61,7 +118,7 @@ type EpochDataToBits = (
 /// [`EpochData.constrain`]: struct.EpochData.html#method.constrain
 pub struct ConstrainedEpochData {
     /// The epoch's index
-    pub index: FrVar,
+    pub index: EpochIndexVar,
     /// Unpredicatble value to add entropy to the epoch data,
     pub epoch_entropy: FrVar,
     /// Entropy value for the previous epoch.
@@ -80,11 +137,16 @@ pub struct ConstrainedEpochData {
     pub crh_bits: Vec<Bool>,
     /// Aux data for proving the CRH->XOF hash outside of BW6_761
     pub xof_bits: Vec<Bool>,
+    /// The epoch's auxiliary data bits, empty unless the `epoch-aux-data` feature is enabled
+    pub aux_data_bits: Vec<Bool>,
+    /// The epoch's chain identifier bits, empty unless the `chain-binding` feature is enabled
+    pub chain_id_bits: Vec<Bool>,
 }
 
 impl<E: PairingEngine> EpochData<E> {
-    /// Each epoch entropy value is 128 bits.
-    pub const ENTROPY_BYTES: usize = 16;
+    /// The length of each epoch entropy value, see `EpochBlock::ENTROPY_BYTES`. Entropy is
+    /// allocated as a single field element, so it must be shorter than the field's capacity.
+    pub const ENTROPY_BYTES: usize = EpochBlock::ENTROPY_BYTES;
 
     /// Initializes an empty epoch, to be used for the setup
     pub fn empty(num_validators: usize, maximum_non_signers: usize) -> Self {
@@ -95,7 +157,262 @@ impl<E: PairingEngine> EpochData<E> {
             parent_entropy: None,
             maximum_non_signers: maximum_non_signers as u32,
             public_keys: vec![None; num_validators],
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
+            hash_counter: None,
+        }
+    }
+
+    /// Validates the epoch's witness data and returns it in canonical form, with every public
+    /// key normalized to affine coordinates (`z = 1`). Rejects epochs with a missing index,
+    /// round or public key, entropy of the wrong length, a number of public keys other than
+    /// `num_validators`, public keys outside the prime order subgroup (which no compressed
+    /// encoding represents) and too many non signers.
+    pub fn canonicalize(&self, num_validators: usize) -> Result<Self, EpochDataError> {
+        let index = self.index.ok_or(EpochDataError::MissingIndex)?;
+        let round = self.round.ok_or(EpochDataError::MissingRound)?;
+        check_entropy::<E>(&self.epoch_entropy)?;
+        check_entropy::<E>(&self.parent_entropy)?;
+        if self.public_keys.len() != num_validators {
+            return Err(EpochDataError::InvalidNumPublicKeys {
+                expected: num_validators,
+                got: self.public_keys.len(),
+            });
         }
+        let public_keys = self
+            .public_keys
+            .iter()
+            .enumerate()
+            .map(|(i, public_key)| {
+                let public_key = public_key
+                    .ok_or(EpochDataError::MissingPublicKey(i))?
+                    .into_affine();
+                if !public_key.is_in_correct_subgroup_assuming_on_curve() {
+                    return Err(EpochDataError::InvalidPublicKey(i));
+                }
+                Ok(Some(public_key.into_projective()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        EpochDataBuilder::<E>::check_non_signers(self.maximum_non_signers, num_validators)?;
+
+        Ok(EpochData {
+            index: Some(index),
+            round: Some(round),
+            public_keys,
+            ..self.clone()
+        })
+    }
+}
+
+/// Checks that the entropy, if any, has `ENTROPY_BYTES` bytes
+fn check_entropy<E: PairingEngine>(entropy: &Option<Vec<u8>>) -> Result<(), EpochDataError> {
+    match entropy {
+        Some(entropy) if entropy.len() != EpochData::<E>::ENTROPY_BYTES => {
+            Err(EpochDataError::InvalidEntropyLength {
+                expected: EpochData::<E>::ENTROPY_BYTES,
+                got: entropy.len(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+/// Error raised when an [`EpochDataBuilder`] is given malformed epoch data, or when
+/// canonicalizing malformed epoch data
+///
+/// [`EpochDataBuilder`]: struct.EpochDataBuilder.html
+pub enum EpochDataError {
+    #[error("the epoch index was not set")]
+    MissingIndex,
+    #[error("the epoch round was not set")]
+    MissingRound,
+    #[error("entropy must be {expected} bytes, got {got}")]
+    InvalidEntropyLength { expected: usize, got: usize },
+    #[error("maximum non signers ({maximum_non_signers}) exceeds the number of validators ({num_validators})")]
+    TooManyNonSigners {
+        maximum_non_signers: u32,
+        num_validators: usize,
+    },
+    #[error("expected {expected} public keys, got {got}")]
+    InvalidNumPublicKeys { expected: usize, got: usize },
+    #[error("public key {0} was not set")]
+    MissingPublicKey(usize),
+    #[error("public key {0} is not in the prime order subgroup")]
+    InvalidPublicKey(usize),
+    #[error("the signed bitmap was not set")]
+    MissingBitmap,
+    #[error("the signed bitmap has bits set past its {num_validators} validators")]
+    TrailingBitmapBits { num_validators: usize },
+}
+
+impl ToErrorCode for EpochDataError {
+    fn error_code(&self) -> ErrorCode {
+        ErrorCode::InvalidEpochData
+    }
+}
+
+/// Builder which validates the epoch's data before producing an [`EpochData`], so that
+/// malformed updates are caught before synthesis instead of at proving time.
+///
+/// [`EpochData`]: struct.EpochData.html
+#[derive(Clone, Debug)]
+pub struct EpochDataBuilder<E: PairingEngine> {
+    index: Option<u16>,
+    round: Option<u8>,
+    epoch_entropy: Option<Vec<u8>>,
+    parent_entropy: Option<Vec<u8>>,
+    maximum_non_signers: u32,
+    num_validators: Option<usize>,
+    public_keys: Vec<E::G2Projective>,
+    aux_data: Option<[u8; 32]>,
+    chain_id: Option<[u8; 32]>,
+    validator_blinding: Option<[u8; 32]>,
+}
+
+impl<E: PairingEngine> Default for EpochDataBuilder<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: PairingEngine> EpochDataBuilder<E> {
+    /// Initializes a builder with no data set
+    pub fn new() -> Self {
+        Self {
+            index: None,
+            round: None,
+            epoch_entropy: None,
+            parent_entropy: None,
+            maximum_non_signers: 0,
+            num_validators: None,
+            public_keys: vec![],
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
+        }
+    }
+
+    /// Sets the epoch's index
+    pub fn index(mut self, index: u16) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Sets the consensus round of the epoch
+    pub fn round(mut self, round: u8) -> Self {
+        self.round = Some(round);
+        self
+    }
+
+    /// Sets the epoch's entropy. Must be `ENTROPY_BYTES` long.
+    pub fn epoch_entropy(mut self, entropy: Vec<u8>) -> Self {
+        self.epoch_entropy = Some(entropy);
+        self
+    }
+
+    /// Sets the parent epoch's entropy. Must be `ENTROPY_BYTES` long.
+    pub fn parent_entropy(mut self, entropy: Vec<u8>) -> Self {
+        self.parent_entropy = Some(entropy);
+        self
+    }
+
+    /// Sets the maximum number of validators which may not sign on the next epoch
+    pub fn maximum_non_signers(mut self, maximum_non_signers: u32) -> Self {
+        self.maximum_non_signers = maximum_non_signers;
+        self
+    }
+
+    /// Sets the expected size of the validator set. If not set, it is inferred from
+    /// the provided public keys. Required when building for the setup.
+    pub fn num_validators(mut self, num_validators: usize) -> Self {
+        self.num_validators = Some(num_validators);
+        self
+    }
+
+    /// Sets the epoch's validator public keys
+    pub fn public_keys(mut self, public_keys: Vec<E::G2Projective>) -> Self {
+        self.public_keys = public_keys;
+        self
+    }
+
+    /// Sets the epoch's auxiliary data, which is only signed and committed to when the
+    /// `epoch-aux-data` feature is enabled
+    pub fn aux_data(mut self, aux_data: [u8; 32]) -> Self {
+        self.aux_data = Some(aux_data);
+        self
+    }
+
+    /// Sets the identifier of the epoch's chain, which is only signed when the
+    /// `chain-binding` feature is enabled
+    pub fn chain_id(mut self, chain_id: [u8; 32]) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Sets the blinding of the epoch's validator set commitment, which is only used when
+    /// the `blinded-validators` feature is enabled
+    pub fn validator_blinding(mut self, validator_blinding: [u8; 32]) -> Self {
+        self.validator_blinding = Some(validator_blinding);
+        self
+    }
+
+    /// Validates the data and returns the epoch
+    pub fn build(self) -> Result<EpochData<E>, EpochDataError> {
+        let index = self.index.ok_or(EpochDataError::MissingIndex)?;
+        let round = self.round.ok_or(EpochDataError::MissingRound)?;
+        check_entropy::<E>(&self.epoch_entropy)?;
+        check_entropy::<E>(&self.parent_entropy)?;
+        let num_validators = self
+            .num_validators
+            .unwrap_or_else(|| self.public_keys.len());
+        if num_validators != self.public_keys.len() {
+            return Err(EpochDataError::InvalidNumPublicKeys {
+                expected: num_validators,
+                got: self.public_keys.len(),
+            });
+        }
+        Self::check_non_signers(self.maximum_non_signers, num_validators)?;
+
+        Ok(EpochData {
+            maximum_non_signers: self.maximum_non_signers,
+            index: Some(index),
+            round: Some(round),
+            epoch_entropy: self.epoch_entropy,
+            parent_entropy: self.parent_entropy,
+            public_keys: self.public_keys.into_iter().map(Some).collect(),
+            aux_data: self.aux_data,
+            chain_id: self.chain_id,
+            validator_blinding: self.validator_blinding,
+            hash_counter: None,
+        })
+    }
+
+    /// Returns an epoch with all witness values unset, to be used for the trusted setup.
+    /// Only the number of validators and the maximum number of non signers are used.
+    pub fn build_for_setup(self) -> Result<EpochData<E>, EpochDataError> {
+        let num_validators = self
+            .num_validators
+            .unwrap_or_else(|| self.public_keys.len());
+        Self::check_non_signers(self.maximum_non_signers, num_validators)?;
+        Ok(EpochData::empty(
+            num_validators,
+            self.maximum_non_signers as usize,
+        ))
+    }
+
+    fn check_non_signers(
+        maximum_non_signers: u32,
+        num_validators: usize,
+    ) -> Result<(), EpochDataError> {
+        if maximum_non_signers as usize > num_validators {
+            return Err(EpochDataError::TooManyNonSigners {
+                maximum_non_signers,
+                num_validators,
+            });
+        }
+        Ok(())
     }
 }
 
@@ -106,8 +423,25 @@ impl EpochData<Bls12_377> {
     #[tracing::instrument(target = "r1cs")]
     pub fn constrain(
         &self,
-        previous_index: &FrVar,
+        previous_index: &EpochIndexVar,
+        generate_constraints_for_hash: bool,
+    ) -> Result<ConstrainedEpochData, SynthesisError> {
+        self.constrain_with_buffers(
+            previous_index,
+            generate_constraints_for_hash,
+            &mut BitBuffers::default(),
+        )
+    }
+
+    /// Same as `constrain`, with the bit vectors taken from `buffers`. The epoch bits are
+    /// returned to the pool once hashed, the first and last epoch bits are for the caller to
+    /// recycle.
+    #[tracing::instrument(target = "r1cs", skip(buffers))]
+    pub fn constrain_with_buffers(
+        &self,
+        previous_index: &EpochIndexVar,
         generate_constraints_for_hash: bool,
+        buffers: &mut BitBuffers,
     ) -> Result<ConstrainedEpochData, SynthesisError> {
         let span = span!(Level::TRACE, "EpochData");
         let _enter = span.enter();
@@ -122,12 +456,19 @@ impl EpochData<Bls12_377> {
             parent_entropy,
             maximum_non_signers,
             pubkeys,
-        ) = self.to_bits(previous_index.cs())?;
+            aux_data_bits,
+            chain_id_bits,
+        ) = self.to_bits_with_buffers(previous_index.cs(), buffers)?;
         Self::enforce_next_epoch(previous_index, &index)?;
 
         // Hash to G1
-        let (message_hash, crh_bits, xof_bits) =
-            Self::hash_bits_to_g1(&bits, &extra_data_bits, generate_constraints_for_hash)?;
+        let (message_hash, crh_bits, xof_bits) = Self::hash_bits_to_g1(
+            &bits,
+            &extra_data_bits,
+            self.hash_counter,
+            generate_constraints_for_hash,
+        )?;
+        buffers.recycle(bits);
 
         Ok(ConstrainedEpochData {
             combined_first_epoch_bits,
@@ -140,6 +481,8 @@ impl EpochData<Bls12_377> {
             message_hash,
             crh_bits,
             xof_bits,
+            aux_data_bits,
+            chain_id_bits,
         })
     }
 
@@ -149,33 +492,50 @@ impl EpochData<Bls12_377> {
         &self,
         cs: ConstraintSystemRef<Bls12_377_Fq>,
     ) -> Result<EpochDataToBits, SynthesisError> {
-        let index = FpVar::new_witness(cs.clone(), || Ok(Fr::from(self.index.get()?)))?;
-        let index_bits = fr_to_bits(&index, 16)?;
+        self.to_bits_with_buffers(cs, &mut BitBuffers::default())
+    }
+
+    /// Same as `to_bits`, with the epoch bits and the first and last epoch bits taken from
+    /// `buffers`
+    #[tracing::instrument(target = "r1cs", skip(buffers))]
+    pub fn to_bits_with_buffers(
+        &self,
+        cs: ConstraintSystemRef<Bls12_377_Fq>,
+        buffers: &mut BitBuffers,
+    ) -> Result<EpochDataToBits, SynthesisError> {
+        let index = EpochIndexVar::new_witness(cs.clone(), || self.index.get())?;
+        let index_bits = index.to_bits_le();
         let round = FpVar::new_witness(cs.clone(), || Ok(Fr::from(self.round.get()?)))?;
-        let round_bits = fr_to_bits(&round, 8)?;
+        let round_bits = fp_to_bits_le(&round, 8)?;
 
         let maximum_non_signers =
-            FpVar::new_witness(index.cs(), || Ok(Fr::from(self.maximum_non_signers)))?;
+            FpVar::new_witness(cs.clone(), || Ok(Fr::from(self.maximum_non_signers)))?;
 
-        let maximum_non_signers_bits = fr_to_bits(&maximum_non_signers, 32)?;
+        let maximum_non_signers_bits = fp_to_bits_le(&maximum_non_signers, 32)?;
 
         let empty_entropy = vec![0u8; Self::ENTROPY_BYTES];
         let epoch_entropy = match &self.epoch_entropy {
             Some(v) => v,
             None => &empty_entropy,
         };
-        let epoch_entropy_var = bytes_to_fr(cs.clone(), Some(&epoch_entropy))?;
-        let epoch_entropy_bits = fr_to_bits(&epoch_entropy_var, 8 * Self::ENTROPY_BYTES)?;
+        let epoch_entropy_var = bytes_le_to_fp(cs.clone(), Some(&epoch_entropy))?;
+        let epoch_entropy_bits = fp_to_bits_le(&epoch_entropy_var, 8 * Self::ENTROPY_BYTES)?;
 
         let parent_entropy = match &self.parent_entropy {
             Some(v) => v,
             None => &empty_entropy,
         };
-        let parent_entropy_var = bytes_to_fr(cs, Some(&parent_entropy))?;
-        let parent_entropy_bits = fr_to_bits(&parent_entropy_var, 8 * Self::ENTROPY_BYTES)?;
+        let parent_entropy_var = bytes_le_to_fp(cs.clone(), Some(&parent_entropy))?;
+        let parent_entropy_bits = fp_to_bits_le(&parent_entropy_var, 8 * Self::ENTROPY_BYTES)?;
 
-        let mut epoch_bits: Vec<Bool> =
-            [epoch_entropy_bits.clone(), parent_entropy_bits.clone()].concat();
+        // the buffers have room for the pubkey bits upfront, to avoid reallocating them for
+        // every pubkey in large validator sets
+        let pubkey_bits_len = self.public_keys.len() * G2_BITS;
+        let entropy_bits_len = 8 * Self::ENTROPY_BYTES;
+
+        let mut epoch_bits = buffers.take(2 * entropy_bits_len + pubkey_bits_len);
+        epoch_bits.extend_from_slice(&epoch_entropy_bits);
+        epoch_bits.extend_from_slice(&parent_entropy_bits);
 
         let extra_data_bits: Vec<Bool> = [
             index_bits.clone(),
@@ -184,20 +544,48 @@ impl EpochData<Bls12_377> {
         ]
         .concat();
 
-        let mut first_epoch_bits: Vec<Bool> = [
-            index_bits.clone(),
-            parent_entropy_bits,
-            maximum_non_signers_bits.clone(),
-        ]
-        .concat();
+        let header_bits_len = index_bits.len() + entropy_bits_len + maximum_non_signers_bits.len();
+        let mut first_epoch_bits = buffers.take(header_bits_len + pubkey_bits_len);
+        first_epoch_bits.extend_from_slice(&index_bits);
+        first_epoch_bits.extend_from_slice(&parent_entropy_bits);
+        first_epoch_bits.extend_from_slice(&maximum_non_signers_bits);
 
-        let mut last_epoch_bits: Vec<Bool> =
-            [index_bits, epoch_entropy_bits, maximum_non_signers_bits].concat();
+        // the aggregate pubkey of the last epoch is appended to its bits, see `ValidatorSetUpdate`
+        let mut last_epoch_bits = buffers.take(header_bits_len + pubkey_bits_len + G2_BITS);
+        last_epoch_bits.extend_from_slice(&index_bits);
+        last_epoch_bits.extend_from_slice(&epoch_entropy_bits);
+        last_epoch_bits.extend_from_slice(&maximum_non_signers_bits);
 
+        let aux_data_bits = if cfg!(feature = "epoch-aux-data") {
+            let bits = EpochBlock::encode_aux_data(self.aux_data.as_ref());
+            bits.iter()
+                .map(|bit| Bool::new_witness(cs.clone(), || Ok(*bit)))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            vec![]
+        };
+
+        let chain_id_bits = if cfg!(feature = "chain-binding") {
+            let bits = EpochBlock::encode_chain_id(self.chain_id.as_ref());
+            bits.iter()
+                .map(|bit| Bool::new_witness(cs.clone(), || Ok(*bit)))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            vec![]
+        };
+
+        // with the `canonical-validators` feature, the public keys of epochs which are not
+        // dummies must be in strictly increasing order of their bits, see `ValidatorSet`
+        let enforce_canonical_order = if cfg!(feature = "canonical-validators") {
+            Some(index.is_zero()?.not())
+        } else {
+            None
+        };
+        let mut previous_pk_bits: Option<Vec<Bool>> = None;
         let mut pubkey_vars = Vec::with_capacity(self.public_keys.len());
         for maybe_pk in self.public_keys.iter() {
             let pk_var = G2Var::new_variable_omit_prime_order_check(
-                index.cs(),
+                cs.clone(),
                 || maybe_pk.get(),
                 AllocationMode::Witness,
             )?;
@@ -207,10 +595,29 @@ impl EpochData<Bls12_377> {
             epoch_bits.extend_from_slice(&pk_bits);
             first_epoch_bits.extend_from_slice(&pk_bits);
             last_epoch_bits.extend_from_slice(&pk_bits);
+            if let Some(enforce) = &enforce_canonical_order {
+                if let Some(previous_pk_bits) = &previous_pk_bits {
+                    is_less_than_be(previous_pk_bits, &pk_bits)?
+                        .conditional_enforce_equal(&Bool::constant(true), enforce)?;
+                }
+                previous_pk_bits = Some(pk_bits);
+            }
 
             // save the allocated pubkeys
             pubkey_vars.push(pk_var);
         }
+        // the auxiliary data, the chain identifier and the circuit version are signed after
+        // the pubkeys, see `EpochBlock::encode_inner_to_bits_cip22`. The version is a constant,
+        // so only messages signed for this circuit's version are accepted.
+        epoch_bits.extend_from_slice(&aux_data_bits);
+        epoch_bits.extend_from_slice(&chain_id_bits);
+        if cfg!(feature = "circuit-version") {
+            epoch_bits.extend(
+                EpochBlock::encode_circuit_version()
+                    .into_iter()
+                    .map(Bool::constant),
+            );
+        }
 
         Ok((
             epoch_bits,
@@ -222,27 +629,55 @@ impl EpochData<Bls12_377> {
             parent_entropy_var,
             maximum_non_signers,
             pubkey_vars,
+            aux_data_bits,
+            chain_id_bits,
         ))
     }
 
-    /// Enforces that `index = previous_index + 1`
+    /// Enforces that `index = previous_index + 1`, unless the epoch is a dummy
     #[tracing::instrument(target = "r1cs")]
-    fn enforce_next_epoch(previous_index: &FrVar, index: &FrVar) -> Result<(), SynthesisError> {
+    fn enforce_next_epoch(
+        previous_index: &EpochIndexVar,
+        index: &EpochIndexVar,
+    ) -> Result<(), SynthesisError> {
         trace!("enforcing next epoch");
-        let previous_plus_one = previous_index + Fr::one();
+        let index_bit = index.is_zero()?.not();
+        index.conditional_enforce_next(previous_index, &index_bit)
+    }
 
-        let index_bit = index.is_eq_zero()?.not();
+    /// Replaces the public key bits at the end of the epoch's first or last epoch bits with
+    /// the commitment to them under the epoch's validator blinding, see
+    /// `EpochBlock::encode_validator_commitment`
+    #[tracing::instrument(target = "r1cs")]
+    pub(crate) fn blind_validators(
+        &self,
+        mut epoch_bits: Vec<Bool>,
+    ) -> Result<Vec<Bool>, SynthesisError> {
+        let pubkey_bits = epoch_bits.split_off(epoch_bits.len() - self.public_keys.len() * G2_BITS);
+        let blinding_bits = EpochBlock::encode_validator_blinding(self.validator_blinding.as_ref());
+        let mut input = blinding_bits
+            .iter()
+            .map(|bit| Bool::new_witness(pubkey_bits.cs(), || Ok(*bit)))
+            .collect::<Result<Vec<_>, _>>()?;
+        input.extend(pubkey_bits);
+        let input_bytes = input.chunks(8).map(U8::from_bits_le).collect::<Vec<_>>();
 
-        index.conditional_enforce_equal(&previous_plus_one, &index_bit)?;
-        Ok(())
+        let commitment_bits = HashToGroupGadget::pedersen_hash(
+            &input_bytes,
+            VALIDATOR_COMMITMENT_HASHER.parameters(),
+        )?;
+        epoch_bits.extend(commitment_bits);
+        Ok(epoch_bits)
     }
 
-    /// Packs the provided bits in U8s, and calculates the hash and the counter
+    /// Packs the provided bits in U8s, and calculates the hash and the counter, unless the
+    /// counter is provided.
     /// Also returns the auxiliary CRH and XOF bits for potential compression from consumers
     #[tracing::instrument(target = "r1cs")]
-    fn hash_bits_to_g1(
+    pub(crate) fn hash_bits_to_g1(
         epoch_bits: &[Bool],
         epoch_extra_data_bits: &[Bool],
+        counter: Option<u8>,
         generate_constraints_for_hash: bool,
     ) -> Result<(G1Var, Vec<Bool>, Vec<Bool>), SynthesisError> {
         trace!("hashing epoch to g1");
@@ -279,6 +714,8 @@ impl EpochData<Bls12_377> {
         // Get the inner values
         let counter = if is_setup {
             0
+        } else if let Some(counter) = counter {
+            counter as usize
         } else {
             // find the counter value for the hash
             let input_bytes = input_bytes_var
@@ -309,7 +746,7 @@ impl EpochData<Bls12_377> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::epoch_block::{EpochBlock, EpochType};
+    use crate::epoch_block::EpochType;
     use bls_crypto::PublicKey;
     use bls_gadgets::utils::test_helpers::{
         print_unsatisfied_constraints, run_profile_constraints,
@@ -337,6 +774,10 @@ mod tests {
             ]),
             maximum_non_signers: 12,
             public_keys: pubkeys,
+            aux_data: Some([index as u8; 32]),
+            chain_id: Some([7; 32]),
+            validator_blinding: Some([index as u8 + 1; 32]),
+            hash_counter: None,
         }
     }
 
@@ -345,13 +786,83 @@ mod tests {
         run_profile_constraints(|| {
             let epoch = test_epoch(10);
             let cs = ConstraintSystem::<Fr>::new_ref();
-            let index = FrVar::new_witness(cs.clone(), || Ok(Fr::from(9u32))).unwrap();
+            let index = EpochIndexVar::new_witness(cs.clone(), || Ok(9)).unwrap();
             epoch.constrain(&index, false).unwrap();
             print_unsatisfied_constraints(cs.clone());
             assert!(cs.is_satisfied().unwrap());
         });
     }
 
+    #[test]
+    fn reuses_buffers_across_epochs() {
+        let values = |bits: &[Bool]| bits.iter().map(|b| b.value().unwrap()).collect::<Vec<_>>();
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut buffers = BitBuffers::default();
+        let index = EpochIndexVar::new_witness(cs.clone(), || Ok(9)).unwrap();
+        let first = test_epoch(10)
+            .constrain_with_buffers(&index, false, &mut buffers)
+            .unwrap();
+        // the epoch bits are recycled once hashed
+        assert_eq!(buffers.len(), 1);
+        buffers.recycle(first.combined_first_epoch_bits);
+        buffers.recycle(first.combined_last_epoch_bits);
+        let recycled = buffers.free.iter().map(|b| b.as_ptr()).collect::<Vec<_>>();
+
+        let epoch = test_epoch(11);
+        let second = epoch
+            .constrain_with_buffers(&first.index, false, &mut buffers)
+            .unwrap();
+        assert_eq!(buffers.len(), 1);
+        assert!(recycled.contains(&second.combined_first_epoch_bits.as_ptr()));
+        assert!(recycled.contains(&second.combined_last_epoch_bits.as_ptr()));
+        assert!(cs.is_satisfied().unwrap());
+
+        // the bits are the same as with buffers of their own
+        let fresh = epoch.constrain(&first.index, false).unwrap();
+        assert_eq!(
+            values(&second.combined_first_epoch_bits),
+            values(&fresh.combined_first_epoch_bits)
+        );
+        assert_eq!(
+            values(&second.combined_last_epoch_bits),
+            values(&fresh.combined_last_epoch_bits)
+        );
+        assert_eq!(values(&second.crh_bits), values(&fresh.crh_bits));
+    }
+
+    #[cfg(feature = "canonical-validators")]
+    #[test]
+    fn enforces_canonical_validators() {
+        let mut epoch = test_epoch(10);
+        let public_keys = epoch
+            .public_keys
+            .iter()
+            .map(|pk| PublicKey::from(pk.unwrap()))
+            .collect();
+        epoch.public_keys = crate::ValidatorSet::canonicalize(public_keys)
+            .unwrap()
+            .public_keys()
+            .iter()
+            .map(|pk| Some(*pk.as_ref()))
+            .collect();
+        let is_satisfied = |epoch: &EpochData<Bls12_377>| {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            epoch.to_bits(cs.clone()).unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(&epoch));
+
+        let mut unordered = epoch.clone();
+        unordered.public_keys.swap(3, 4);
+        assert!(!is_satisfied(&unordered));
+        let mut duplicate = epoch.clone();
+        duplicate.public_keys[4] = duplicate.public_keys[3];
+        assert!(!is_satisfied(&duplicate));
+        // dummy epochs are exempt
+        duplicate.index = Some(0);
+        assert!(is_satisfied(&duplicate));
+    }
+
     #[test]
     fn test_hash_epoch_to_g1() {
         run_profile_constraints(test_hash_epoch_to_g1_inner);
@@ -374,6 +885,8 @@ mod tests {
             pubkeys.len(),
             pubkeys,
         )
+        .with_aux_data(epoch.aux_data.unwrap())
+        .with_chain_id(epoch.chain_id.unwrap())
         .encode_inner_to_bytes_cip22()
         .unwrap();
         let (hash, _) = COMPOSITE_HASH_TO_G1_CIP22
@@ -382,13 +895,153 @@ mod tests {
 
         // compare it with the one calculated in the circuit from its bytes
         let cs = ConstraintSystem::<Fr>::new_ref();
-        let (bits, extra_data_bits, _, _, _, _, _, _, _) = epoch.to_bits(cs.clone()).unwrap();
-        let ret = EpochData::hash_bits_to_g1(&bits, &extra_data_bits, true).unwrap();
+        let (bits, extra_data_bits, _, _, _, _, _, _, _, _, _) = epoch.to_bits(cs.clone()).unwrap();
+        let ret = EpochData::hash_bits_to_g1(&bits, &extra_data_bits, None, true).unwrap();
         print_unsatisfied_constraints(cs.clone());
         assert!(cs.is_satisfied().unwrap());
         assert_eq!(ret.0.value().unwrap(), hash);
     }
 
+    #[test]
+    fn uses_precomputed_hash_counter() {
+        let epoch = test_epoch(10);
+        let pubkeys = epoch
+            .public_keys
+            .iter()
+            .map(|pk| PublicKey::from(pk.unwrap()))
+            .collect::<Vec<_>>();
+        let (counter, _) = EpochBlock::new(
+            epoch.index.unwrap(),
+            epoch.round.unwrap(),
+            epoch.epoch_entropy.as_ref().map(|v| v.to_vec()),
+            epoch.parent_entropy.as_ref().map(|v| v.to_vec()),
+            epoch.maximum_non_signers,
+            pubkeys.len(),
+            pubkeys,
+        )
+        .with_aux_data(epoch.aux_data.unwrap())
+        .with_chain_id(epoch.chain_id.unwrap())
+        .hash_second_stage()
+        .unwrap();
+
+        let hash = |counter| {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let (bits, extra_data_bits, _, _, _, _, _, _, _, _, _) =
+                epoch.to_bits(cs.clone()).unwrap();
+            let (hash, _, _) =
+                EpochData::hash_bits_to_g1(&bits, &extra_data_bits, counter, true).unwrap();
+            (hash.value().unwrap(), cs.is_satisfied().unwrap())
+        };
+        let (searched, satisfied) = hash(None);
+        assert!(satisfied);
+        assert_eq!(hash(Some(counter)), (searched, true));
+    }
+
+    #[test]
+    fn exposes_hashing_stages() {
+        let epoch = test_epoch(10);
+        let pubkeys = epoch
+            .public_keys
+            .iter()
+            .map(|pk| PublicKey::from(pk.unwrap()))
+            .collect::<Vec<_>>();
+        let block = EpochBlock::new(
+            epoch.index.unwrap(),
+            epoch.round.unwrap(),
+            epoch.epoch_entropy.as_ref().map(|v| v.to_vec()),
+            epoch.parent_entropy.as_ref().map(|v| v.to_vec()),
+            epoch.maximum_non_signers,
+            pubkeys.len(),
+            pubkeys,
+        )
+        .with_aux_data(epoch.aux_data.unwrap())
+        .with_chain_id(epoch.chain_id.unwrap());
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let (bits, extra_data_bits, _, _, _, _, _, _, _, _, _) = epoch.to_bits(cs.clone()).unwrap();
+        let (_, crh_bits, xof_bits) =
+            EpochData::hash_bits_to_g1(&bits, &extra_data_bits, None, true).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        let values = |bits: &[Bool]| bits.iter().map(|b| b.value().unwrap()).collect::<Vec<_>>();
+
+        // the circuit hashes the LE bits of the reversed epoch bits, padded to whole bytes
+        let mut preimage = values(&bits);
+        preimage.reverse();
+        preimage.resize((preimage.len() + 7) / 8 * 8, false);
+        assert_eq!(block.hash_preimage_bits().unwrap(), preimage);
+        assert_eq!(block.hash_first_stage().unwrap(), values(&crh_bits));
+        let (counter, xof) = block.hash_second_stage().unwrap();
+        assert_eq!(xof, values(&xof_bits));
+        assert_eq!(xof.len(), crate::epoch_block::XOF_BITS);
+
+        let (message, extra_data) = block.encode_inner_to_bytes_cip22().unwrap();
+        let (_, attempt) = COMPOSITE_HASH_TO_G1_CIP22
+            .hash_with_attempt_cip22(SIG_DOMAIN, &message, &extra_data)
+            .unwrap();
+        assert_eq!(counter as usize, attempt);
+    }
+
+    #[test]
+    fn builder_validates_data() {
+        let rng = &mut rand::thread_rng();
+        let pubkeys = (0..4)
+            .map(|_| Bls12_377G2Projective::rand(rng))
+            .collect::<Vec<_>>();
+        let entropy = vec![1u8; EpochData::<Bls12_377>::ENTROPY_BYTES];
+        let builder = EpochDataBuilder::<Bls12_377>::new()
+            .index(3)
+            .round(1)
+            .epoch_entropy(entropy.clone())
+            .parent_entropy(entropy)
+            .maximum_non_signers(1)
+            .public_keys(pubkeys.clone());
+
+        let epoch = builder.clone().build().unwrap();
+        assert_eq!(epoch.index, Some(3));
+        assert_eq!(epoch.public_keys.len(), 4);
+
+        assert_eq!(
+            EpochDataBuilder::<Bls12_377>::new()
+                .round(1)
+                .build()
+                .unwrap_err(),
+            EpochDataError::MissingIndex
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .epoch_entropy(vec![0; 3])
+                .build()
+                .unwrap_err(),
+            EpochDataError::InvalidEntropyLength {
+                expected: EpochData::<Bls12_377>::ENTROPY_BYTES,
+                got: 3
+            }
+        );
+        assert_eq!(
+            builder.clone().maximum_non_signers(5).build().unwrap_err(),
+            EpochDataError::TooManyNonSigners {
+                maximum_non_signers: 5,
+                num_validators: 4
+            }
+        );
+        assert_eq!(
+            builder.num_validators(5).build().unwrap_err(),
+            EpochDataError::InvalidNumPublicKeys {
+                expected: 5,
+                got: 4
+            }
+        );
+
+        let empty = EpochDataBuilder::<Bls12_377>::new()
+            .num_validators(10)
+            .maximum_non_signers(3)
+            .build_for_setup()
+            .unwrap();
+        assert_eq!(empty.index, None);
+        assert_eq!(empty.public_keys, vec![None; 10]);
+    }
+
     #[test]
     fn enforce_next_epoch() {
         run_profile_constraints(enforce_next_epoch_inner);
@@ -404,8 +1057,8 @@ mod tests {
             (5, 0, true),
         ] {
             let cs = ConstraintSystem::<Fr>::new_ref();
-            let epoch1 = FrVar::new_witness(cs.clone(), || Ok(Fr::from(*index1))).unwrap();
-            let epoch2 = FrVar::new_witness(cs.clone(), || Ok(Fr::from(*index2))).unwrap();
+            let epoch1 = EpochIndexVar::new_witness(cs.clone(), || Ok(*index1)).unwrap();
+            let epoch2 = EpochIndexVar::new_witness(cs.clone(), || Ok(*index2)).unwrap();
             EpochData::enforce_next_epoch(&epoch1, &epoch2).unwrap();
             print_unsatisfied_constraints(cs.clone());
             assert_eq!(cs.is_satisfied().unwrap(), *expected);
@@ -434,6 +1087,7 @@ mod tests {
             pubkeys.len(),
             pubkeys.clone(),
         )
+        .with_validator_blinding(epoch.validator_blinding.unwrap())
         .encode_to_bits_cip22(EpochType::First)
         .unwrap();
 
@@ -446,12 +1100,17 @@ mod tests {
             pubkeys.len(),
             pubkeys,
         )
+        .with_validator_blinding(epoch.validator_blinding.unwrap())
         .encode_last_epoch_to_bits_with_aggregated_pk_cip22()
         .unwrap();
 
         // calculate the bits from the epoch
         let cs = ConstraintSystem::<Fr>::new_ref();
-        let ret = epoch.to_bits(cs).unwrap();
+        let mut ret = epoch.to_bits(cs).unwrap();
+        if cfg!(feature = "blinded-validators") {
+            ret.2 = epoch.blind_validators(ret.2).unwrap();
+            ret.3 = epoch.blind_validators(ret.3).unwrap();
+        }
 
         // compare with the result
         let bits_inner = ret.2.iter().map(|x| x.value().unwrap()).collect::<Vec<_>>();
@@ -460,4 +1119,46 @@ mod tests {
         let bits_inner = ret.3.iter().map(|x| x.value().unwrap()).collect::<Vec<_>>();
         assert_eq!(bits_inner, last_bits[..bits_inner.len()].to_vec());
     }
+
+    #[test]
+    fn blinds_validators() {
+        let epoch = test_epoch(18);
+        let block = EpochBlock::new(
+            epoch.index.unwrap(),
+            epoch.round.unwrap(),
+            epoch.epoch_entropy.clone(),
+            epoch.parent_entropy.clone(),
+            epoch.maximum_non_signers,
+            epoch.public_keys.len(),
+            epoch
+                .public_keys
+                .iter()
+                .map(|pk| PublicKey::from(pk.unwrap()))
+                .collect(),
+        )
+        .with_validator_blinding(epoch.validator_blinding.unwrap());
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let first_epoch_bits = epoch.to_bits(cs.clone()).unwrap().2;
+        let prefix_len = first_epoch_bits.len() - epoch.public_keys.len() * G2_BITS;
+        let blinded = epoch.blind_validators(first_epoch_bits.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // the public keys are replaced by their commitment
+        let blinded = blinded
+            .iter()
+            .map(|bit| bit.value().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blinded[..prefix_len],
+            first_epoch_bits[..prefix_len]
+                .iter()
+                .map(|bit| bit.value().unwrap())
+                .collect::<Vec<_>>()[..]
+        );
+        assert_eq!(
+            blinded[prefix_len..],
+            block.encode_validator_commitment().unwrap()[..]
+        );
+    }
 }
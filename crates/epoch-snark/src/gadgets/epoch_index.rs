@@ -0,0 +1,187 @@
+use algebra::{bw6_761::Fr, Field, One};
+use bls_gadgets::bits::is_less_than_be;
+use r1cs_core::{ConstraintSystemRef, SynthesisError};
+use r1cs_std::{fields::fp::FpVar, prelude::*};
+
+use super::Bool;
+
+type FrVar = FpVar<Fr>;
+
+/// An epoch index, i.e. a `u16`, along with its bits. Every constructor enforces that the
+/// index fits in 16 bits, so that its bits (which end up in the signed and hashed epoch
+/// encodings) and its value (which is compared with the previous epoch's) always agree.
+#[derive(Clone, Debug)]
+pub struct EpochIndexVar {
+    value: FrVar,
+    bits: Vec<Bool>,
+}
+
+impl EpochIndexVar {
+    /// The number of bits of an epoch index
+    pub const BITS: usize = 16;
+
+    /// Allocates the index from its bits, which costs one constraint per bit
+    pub fn new_witness(
+        cs: ConstraintSystemRef<Fr>,
+        f: impl FnOnce() -> Result<u16, SynthesisError>,
+    ) -> Result<Self, SynthesisError> {
+        // the index is only assigned when generating the proof
+        let index = f().ok();
+        let bits = (0..Self::BITS)
+            .map(|i| {
+                Bool::new_witness(cs.clone(), || {
+                    index
+                        .map(|index| (index >> i) & 1 == 1)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_bits_le(bits))
+    }
+
+    /// Range checks an index which was allocated as a field element, e.g. a public input, by
+    /// decomposing it and enforcing that its bits above the 16th are zero
+    pub fn from_fp(value: &FrVar) -> Result<Self, SynthesisError> {
+        let bits = value.to_bits_le()?;
+        for bit in &bits[Self::BITS..] {
+            bit.enforce_equal(&Bool::constant(false))?;
+        }
+        Ok(Self {
+            value: value.clone(),
+            bits: bits[..Self::BITS].to_vec(),
+        })
+    }
+
+    /// Packs the bits, which must be constrained to be booleans, without adding constraints
+    fn from_bits_le(bits: Vec<Bool>) -> Self {
+        let mut value = FrVar::zero();
+        let mut power = Fr::one();
+        for bit in &bits {
+            value += FrVar::from(bit.clone()) * power;
+            power.double_in_place();
+        }
+        Self { value, bits }
+    }
+
+    /// The constraint system of the index
+    pub fn cs(&self) -> ConstraintSystemRef<Fr> {
+        self.value.cs()
+    }
+
+    /// The index as a field element
+    pub fn as_fp(&self) -> &FrVar {
+        &self.value
+    }
+
+    /// The index's bits, in little endian order
+    pub fn to_bits_le(&self) -> Vec<Bool> {
+        self.bits.clone()
+    }
+
+    /// The index's value, if it was assigned
+    pub fn value(&self) -> Result<u16, SynthesisError> {
+        self.bits
+            .iter()
+            .rev()
+            .try_fold(0u16, |index, bit| Ok((index << 1) | bit.value()? as u16))
+    }
+
+    /// Whether the index is zero, which marks dummy epochs
+    pub fn is_zero(&self) -> Result<Bool, SynthesisError> {
+        self.value.is_eq_zero()
+    }
+
+    /// Whether the index is equal to the other index
+    pub fn is_eq(&self, other: &Self) -> Result<Bool, SynthesisError> {
+        self.value.is_eq(&other.value)
+    }
+
+    /// Whether the index is smaller than the other index
+    pub fn is_less_than(&self, other: &Self) -> Result<Bool, SynthesisError> {
+        let be = |bits: &[Bool]| bits.iter().rev().cloned().collect::<Vec<_>>();
+        is_less_than_be(&be(&self.bits), &be(&other.bits))
+    }
+
+    /// Returns the next index, wrapping around to zero after `u16::MAX`, along with whether
+    /// it wrapped around
+    pub fn add_one(&self) -> Result<(Self, Bool), SynthesisError> {
+        let mut carry = Bool::constant(true);
+        let mut bits = Vec::with_capacity(Self::BITS);
+        for bit in &self.bits {
+            bits.push(bit.xor(&carry)?);
+            carry = bit.and(&carry)?;
+        }
+        Ok((Self::from_bits_le(bits), carry))
+    }
+
+    /// Enforces that the index is `previous + 1` if `condition` is set. Since both indices
+    /// are range checked, comparing their values cannot wrap around the field, and an index
+    /// cannot follow `u16::MAX`.
+    pub fn conditional_enforce_next(
+        &self,
+        previous: &Self,
+        condition: &Bool,
+    ) -> Result<(), SynthesisError> {
+        self.value
+            .conditional_enforce_equal(&(&previous.value + Fr::one()), condition)
+    }
+}
+
+impl CondSelectGadget<Fr> for EpochIndexVar {
+    fn conditionally_select(
+        cond: &Bool,
+        true_value: &Self,
+        false_value: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let bits = true_value
+            .bits
+            .iter()
+            .zip(&false_value.bits)
+            .map(|(t, f)| Bool::conditionally_select(cond, t, f))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_bits_le(bits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use r1cs_core::ConstraintSystem;
+
+    #[test]
+    fn enforces_range() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let index = EpochIndexVar::new_witness(cs.clone(), || Ok(513)).unwrap();
+        assert_eq!(index.value().unwrap(), 513);
+        assert_eq!(index.as_fp().value().unwrap(), Fr::from(513u64));
+        assert!(cs.is_satisfied().unwrap());
+
+        for (value, in_range) in &[(u16::MAX as u64, true), (u16::MAX as u64 + 1, false)] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let value = FrVar::new_input(cs.clone(), || Ok(Fr::from(*value))).unwrap();
+            EpochIndexVar::from_fp(&value).unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), *in_range);
+        }
+    }
+
+    #[test]
+    fn adds_one_and_compares() {
+        for (a, b) in &[(0u16, 1u16), (255, 256), (7, 7), (9, 3), (u16::MAX, 0)] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let a_var = EpochIndexVar::new_witness(cs.clone(), || Ok(*a)).unwrap();
+            let b_var = EpochIndexVar::new_witness(cs.clone(), || Ok(*b)).unwrap();
+
+            let (next, wrapped) = a_var.add_one().unwrap();
+            assert_eq!(next.value().unwrap(), a.wrapping_add(1));
+            assert_eq!(next.as_fp().value().unwrap(), Fr::from(a.wrapping_add(1)));
+            assert_eq!(wrapped.value().unwrap(), *a == u16::MAX);
+            assert_eq!(a_var.is_eq(&b_var).unwrap().value().unwrap(), a == b);
+            assert_eq!(a_var.is_less_than(&b_var).unwrap().value().unwrap(), a < b);
+
+            b_var
+                .conditional_enforce_next(&a_var, &Bool::constant(true))
+                .unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), a.checked_add(1) == Some(*b));
+        }
+    }
+}
@@ -2,7 +2,11 @@
 //!
 //! Prove the validator state transition function for the BLS 12-377 curve.
 
-use crate::gadgets::{g2_to_bits, single_update::SingleUpdate, EpochBits, EpochData};
+use crate::gadgets::{
+    g2_to_bits, randomness_output, single_update::SingleUpdate, BitBuffers, EpochBits, EpochData,
+    EpochIndexVar, ExtraPublicInput,
+};
+use crate::progress::{EpochStage, PROGRESS_TARGET};
 use bls_gadgets::{BlsVerifyGadget, FpUtils};
 
 use algebra::{
@@ -22,6 +26,7 @@ use r1cs_std::{
     prelude::*,
     Assignment,
 };
+use std::sync::Arc;
 use tracing::{debug, info, span, Level};
 
 // Initialize BLS verification gadget
@@ -29,6 +34,12 @@ type BlsGadget = BlsVerifyGadget<Bls12_377, Fr, PairingVar>;
 type FrVar = FpVar<Fr>;
 type Bool = Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>;
 
+/// The maximum number of validators which may not sign under the BFT assumption that
+/// less than a third of the validators are faulty, i.e. `floor((num_validators - 1) / 3)`
+pub fn bft_maximum_non_signers(num_validators: u32) -> u32 {
+    num_validators.saturating_sub(1) / 3
+}
+
 #[derive(Clone, Debug)]
 /// Contains the initial epoch block, followed by a list of epoch block transitions. The
 /// aggregated signature is calculated over all epoch blokc changes. Providing the hash helper
@@ -45,6 +56,8 @@ pub struct ValidatorSetUpdate<E: PairingEngine> {
     /// constrain the inner CRH->XOF hashes in BW6_761 and instead it will be verified
     /// via the helper's proof which is in BLS12-377.
     pub hash_helper: Option<HashToBitsHelper<E>>,
+    /// The public inputs which integrators added to the circuit, see `ExtraPublicInput`
+    pub extra_inputs: Vec<Arc<dyn ExtraPublicInput>>,
 }
 
 #[derive(Clone, Debug)]
@@ -77,8 +90,16 @@ impl<E: PairingEngine> ValidatorSetUpdate<E> {
             epochs: vec![empty_update; num_epochs],
             aggregated_signature: None,
             hash_helper,
+            extra_inputs: vec![],
         }
     }
+
+    /// Registers extra public inputs, which the circuit exposes after those of the enabled
+    /// features. The same inputs, without values, must be registered for the trusted setup.
+    pub fn with_extra_inputs(mut self, extra_inputs: Vec<Arc<dyn ExtraPublicInput>>) -> Self {
+        self.extra_inputs.extend(extra_inputs);
+        self
+    }
 }
 
 impl ConstraintSynthesizer<Fr> for ValidatorSetUpdate<Bls12_377> {
@@ -93,7 +114,7 @@ impl ConstraintSynthesizer<Fr> for ValidatorSetUpdate<Bls12_377> {
         let epoch_bits = self.enforce(cs)?;
         let cs = epoch_bits.first_epoch_bits.cs();
         // Compress public inputs
-        epoch_bits.verify(self.hash_helper, cs)?;
+        epoch_bits.verify(self.hash_helper, &self.extra_inputs, cs)?;
         info!("constraints generated");
 
         Ok(())
@@ -113,18 +134,32 @@ impl ValidatorSetUpdate<Bls12_377> {
         let _enter = span.enter();
 
         debug!("converting initial EpochData to_bits");
+        // the bit vectors of the epochs are recycled from one epoch to the next
+        let mut buffers = BitBuffers::default();
         // Constrain the initial epoch and get its bits
         let (
-            _,
+            initial_epoch_bits,
             _,
             first_epoch_bits,
-            _,
+            initial_last_epoch_bits,
             first_epoch_index,
             first_epoch_entropy,
             _,
             initial_maximum_non_signers,
             initial_pubkey_vars,
-        ) = self.initial_epoch.to_bits(cs)?;
+            _,
+            chain_id_bits,
+        ) = self.initial_epoch.to_bits_with_buffers(cs, &mut buffers)?;
+        buffers.recycle(initial_epoch_bits);
+        buffers.recycle(initial_last_epoch_bits);
+        // the verifier only knows a commitment to the initial validator set
+        let first_epoch_bits = if cfg!(feature = "blinded-validators") {
+            self.initial_epoch.blind_validators(first_epoch_bits)?
+        } else {
+            first_epoch_bits
+        };
+        #[cfg(feature = "bft-threshold")]
+        self.enforce_bft_threshold(&initial_maximum_non_signers, &Boolean::Constant(true))?;
 
         // Constrain all intermediate epochs, and get the aggregate pubkey and epoch hash
         // from each one, to be used for the batch verification
@@ -133,6 +168,9 @@ impl ValidatorSetUpdate<Bls12_377> {
             last_epoch_bits,
             crh_bits,
             xof_bits,
+            bitmap_bits,
+            aux_data_bits,
+            randomness_bits,
             prepared_aggregated_public_keys,
             prepared_message_hashes,
         ) = self.verify_intermediate_epochs(
@@ -140,6 +178,8 @@ impl ValidatorSetUpdate<Bls12_377> {
             first_epoch_entropy,
             initial_pubkey_vars,
             initial_maximum_non_signers,
+            &chain_id_bits,
+            &mut buffers,
         )?;
 
         // Verify the aggregate BLS signature
@@ -155,6 +195,10 @@ impl ValidatorSetUpdate<Bls12_377> {
             last_epoch_bits,
             crh_bits,
             xof_bits,
+            bitmap_bits,
+            aux_data_bits,
+            randomness_bits,
+            chain_id_bits,
         })
     }
 
@@ -162,15 +206,20 @@ impl ValidatorSetUpdate<Bls12_377> {
     /// and generates the witness data necessary for the final BLS Sig
     /// verification and witness compression
     #[allow(clippy::type_complexity)]
-    #[tracing::instrument(target = "r1cs")]
+    #[tracing::instrument(target = "r1cs", skip(buffers))]
     fn verify_intermediate_epochs(
         &self,
-        first_epoch_index: FrVar,
+        first_epoch_index: EpochIndexVar,
         first_epoch_entropy: FrVar,
         initial_pubkey_vars: Vec<G2Var>,
         initial_max_non_signers: FrVar,
+        chain_id_bits: &[Bool],
+        buffers: &mut BitBuffers,
     ) -> Result<
         (
+            Vec<Bool>,
+            Vec<Bool>,
+            Vec<Bool>,
             Vec<Bool>,
             Vec<Bool>,
             Vec<Bool>,
@@ -198,8 +247,8 @@ impl ValidatorSetUpdate<Bls12_377> {
         // Assumes all epochs past a single version will contain entropy
         let entropy_bit = first_epoch_entropy.is_eq_zero()?.not();
 
-        let mut prepared_aggregated_public_keys = vec![];
-        let mut prepared_message_hashes = vec![];
+        let mut prepared_aggregated_public_keys = Vec::with_capacity(self.epochs.len());
+        let mut prepared_message_hashes = Vec::with_capacity(self.epochs.len());
         let mut last_epoch_bits = vec![];
         let mut previous_epoch_index = first_epoch_index;
         let mut previous_pubkey_vars = initial_pubkey_vars;
@@ -207,10 +256,13 @@ impl ValidatorSetUpdate<Bls12_377> {
         let mut previous_epoch_entropy = first_epoch_entropy;
         let mut all_crh_bits = vec![];
         let mut all_xof_bits = vec![];
+        let mut all_bitmap_bits = vec![];
+        let mut all_aux_data_bits = vec![];
+        let mut all_randomness_bits = vec![];
         for (i, epoch) in self.epochs.iter().enumerate() {
             let span = span!(Level::TRACE, "index", i);
             let _enter = span.enter();
-            let constrained_epoch = epoch.constrain(
+            let constrained_epoch = epoch.constrain_with_buffers(
                 &previous_pubkey_vars,
                 &previous_epoch_index,
                 &previous_epoch_entropy,
@@ -218,11 +270,24 @@ impl ValidatorSetUpdate<Bls12_377> {
                 &entropy_bit,
                 self.num_validators,
                 self.hash_helper.is_none(), // generate all constraints in BW6_761 if no helper was provided
+                buffers,
             )?;
+            // the first epoch bits which are public inputs are the initial epoch's
+            buffers.recycle(constrained_epoch.combined_first_epoch_bits);
 
             // If zero, indicates the current epoch is a "dummy" value, and so
             // some values shouldn't be updated in this loop
-            let index_bit = constrained_epoch.index.is_eq_zero()?.not();
+            let index_bit = constrained_epoch.index.is_zero()?.not();
+            #[cfg(feature = "bft-threshold")]
+            self.enforce_bft_threshold(&constrained_epoch.new_max_non_signers, &index_bit)?;
+
+            // every signed epoch must be for the chain of the first epoch, whose identifier
+            // is a public input
+            if cfg!(feature = "chain-binding") {
+                for (bit, expected) in constrained_epoch.chain_id_bits.iter().zip(chain_id_bits) {
+                    bit.conditional_enforce_equal(expected, &index_bit)?;
+                }
+            }
 
             // Update the randomness for the next iteration
             previous_epoch_entropy = FrVar::conditionally_select(
@@ -231,7 +296,7 @@ impl ValidatorSetUpdate<Bls12_377> {
                 &previous_epoch_entropy,
             )?;
             // Update the pubkeys for the next iteration
-            previous_epoch_index = FrVar::conditionally_select(
+            previous_epoch_index = EpochIndexVar::conditionally_select(
                 &index_bit,
                 &constrained_epoch.index,
                 &previous_epoch_index,
@@ -271,21 +336,58 @@ impl ValidatorSetUpdate<Bls12_377> {
             // Save the xof/crh and the last epoch's bits for compressing the public inputs
             all_crh_bits.extend_from_slice(&constrained_epoch.crh_bits);
             all_xof_bits.extend_from_slice(&constrained_epoch.xof_bits);
+            if cfg!(feature = "bitmap-commitment") {
+                // each epoch is encoded as its 2 byte index followed by its bitmap, padded
+                // to whole bytes, matching `BitmapCommitment::to_bytes`
+                all_bitmap_bits.extend(constrained_epoch.index.to_bits_le());
+                all_bitmap_bits.extend_from_slice(&constrained_epoch.signed_bitmap);
+                let padded_len = 8 * ((all_bitmap_bits.len() + 7) / 8);
+                all_bitmap_bits.resize(padded_len, Boolean::Constant(false));
+            }
+            if cfg!(feature = "epoch-aux-data") {
+                // each epoch is encoded as its 2 byte index followed by its auxiliary data,
+                // matching `AuxDataCommitment::to_bytes`
+                all_aux_data_bits.extend(constrained_epoch.index.to_bits_le());
+                all_aux_data_bits.extend_from_slice(&constrained_epoch.aux_data_bits);
+            }
+            if cfg!(feature = "randomness-beacon") {
+                // each epoch is encoded as its 2 byte index followed by its randomness
+                // output, matching `RandomnessBeacon::to_bytes`
+                all_randomness_bits.extend(constrained_epoch.index.to_bits_le());
+                all_randomness_bits.extend(randomness_output(
+                    &constrained_epoch.index,
+                    &constrained_epoch.epoch_entropy,
+                )?);
+            }
             if i == self.epochs.len() - 1 {
-                let last_apk = BlsGadget::enforce_aggregated_all_pubkeys(
-                    &previous_pubkey_vars, // These are now the last epoch new pubkeys
-                )?;
-                let affine_x = last_apk.x.mul_by_inverse(&last_apk.z)?;
-                let affine_y = last_apk.y.mul_by_inverse(&last_apk.z)?;
-                let last_apk_affine = G2Var::new(affine_x, affine_y, Fq2Var::one());
-                let last_apk_bits = g2_to_bits(&last_apk_affine)?;
-                last_epoch_bits = constrained_epoch.combined_last_epoch_bits;
-                last_epoch_bits.extend_from_slice(&last_apk_bits);
+                if cfg!(feature = "blinded-validators") {
+                    // the last validator set is committed to instead of being aggregated
+                    last_epoch_bits = epoch
+                        .epoch_data
+                        .blind_validators(constrained_epoch.combined_last_epoch_bits)?;
+                } else {
+                    let last_apk = BlsGadget::enforce_aggregated_all_pubkeys(
+                        &previous_pubkey_vars, // These are now the last epoch new pubkeys
+                    )?;
+                    let affine_x = last_apk.x.mul_by_inverse(&last_apk.z)?;
+                    let affine_y = last_apk.y.mul_by_inverse(&last_apk.z)?;
+                    let last_apk_affine = G2Var::new(affine_x, affine_y, Fq2Var::one());
+                    let last_apk_bits = g2_to_bits(&last_apk_affine)?;
+                    last_epoch_bits = constrained_epoch.combined_last_epoch_bits;
+                    last_epoch_bits.extend_from_slice(&last_apk_bits);
+                }
 
                 // make sure the last epoch index is not zero
                 index_bit.enforce_equal(&Boolean::Constant(true))?;
+            } else {
+                buffers.recycle(constrained_epoch.combined_last_epoch_bits);
             }
-            debug!("epoch {} constrained", i);
+            debug!(
+                target: PROGRESS_TARGET,
+                stage = EpochStage::Constrained.name(),
+                "epoch {} constrained",
+                i
+            );
         }
 
         debug!("intermediate epochs verified");
@@ -294,11 +396,29 @@ impl ValidatorSetUpdate<Bls12_377> {
             last_epoch_bits,
             all_crh_bits,
             all_xof_bits,
+            all_bitmap_bits,
+            all_aux_data_bits,
+            all_randomness_bits,
             prepared_aggregated_public_keys,
             prepared_message_hashes,
         ))
     }
 
+    /// Enforces that the maximum number of non-signers is `floor((num_validators - 1) / 3)`,
+    /// so that the prover cannot choose a weaker threshold than BFT consensus allows
+    #[cfg(feature = "bft-threshold")]
+    fn enforce_bft_threshold(
+        &self,
+        maximum_non_signers: &FrVar,
+        should_enforce: &Bool,
+    ) -> Result<(), SynthesisError> {
+        let threshold = FrVar::new_constant(
+            maximum_non_signers.cs(),
+            Fr::from(bft_maximum_non_signers(self.num_validators)),
+        )?;
+        maximum_non_signers.conditional_enforce_equal(&threshold, should_enforce)
+    }
+
     // Verify the aggregate signature
     #[tracing::instrument(target = "r1cs")]
     fn verify_signature(
@@ -333,6 +453,35 @@ mod tests {
     type Curve = Bls12_377;
     type Entropy = Option<Vec<u8>>;
 
+    #[test]
+    fn computes_bft_threshold() {
+        assert_eq!(bft_maximum_non_signers(0), 0);
+        assert_eq!(bft_maximum_non_signers(1), 0);
+        assert_eq!(bft_maximum_non_signers(7), 2);
+        assert_eq!(bft_maximum_non_signers(9), 2);
+        assert_eq!(bft_maximum_non_signers(100), 33);
+    }
+
+    #[cfg(feature = "bft-threshold")]
+    #[test]
+    fn enforces_bft_threshold() {
+        let valset = ValidatorSetUpdate::<Curve>::empty(7, 1, 2, None);
+        for (max_non_signers, should_enforce, satisfied) in &[
+            (2u32, true, true),
+            (3, true, false),
+            (1, true, false),
+            (0, false, true),
+        ] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let max_non_signers =
+                FrVar::new_witness(cs.clone(), || Ok(Fr::from(*max_non_signers))).unwrap();
+            valset
+                .enforce_bft_threshold(&max_non_signers, &Boolean::constant(*should_enforce))
+                .unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), *satisfied);
+        }
+    }
+
     // let's run our tests with 7 validators and 2 faulty ones
     mod epoch_batch_verification {
         use super::*;
@@ -452,17 +601,21 @@ mod tests {
                 num_validators,
                 aggregated_signature: Some(aggregated_signature),
                 hash_helper: None,
+                extra_inputs: vec![],
             };
 
             let cs = ConstraintSystem::<Fr>::new_ref();
             let epoch_bits = valset.enforce(cs.clone()).unwrap();
-            epoch_bits.verify(None, cs.clone()).unwrap();
+            epoch_bits.verify(None, &[], cs.clone()).unwrap();
             let hash = hash_first_last_epoch_block(
                 &epoch_data_to_block(&initial_epoch),
                 &epoch_data_to_block(&epochs[epochs.len() - 1].epoch_data),
             )
             .unwrap();
-            let public_inputs = crate::gadgets::pack::<BWField, BWFrParams>(&hash).unwrap();
+            let mut public_inputs = crate::gadgets::pack::<BWField, BWFrParams>(&hash).unwrap();
+            if cfg!(feature = "compressed-public-inputs") {
+                public_inputs = vec![crate::gadgets::compress_public_inputs(&public_inputs)];
+            }
             assert_eq!(
                 cs.borrow().unwrap().instance_assignment[1..].to_vec(),
                 public_inputs
@@ -0,0 +1,76 @@
+//! # Extra Public Inputs
+//!
+//! An extension point for integrators which need the epochs circuit to expose more public
+//! inputs, e.g. a bridge-specific commitment, without forking the circuit. Each
+//! `ExtraPublicInput` registered with `ValidatorSetUpdate::with_extra_inputs` allocates some
+//! bits, which it may tie to the proven epochs through their `EpochBits`, and the circuit
+//! exposes the Blake2s hash of the bits, personalized to the input's domain. The hashes are
+//! packed in registration order after the public inputs of the enabled features, and are
+//! compressed along with them with the `compressed-public-inputs` feature.
+
+use super::{epoch_bits::blake2s, Bool, EpochBits, MultipackGadget};
+use crate::{epoch_block::personalized_blake2s, scalars::OuterScalar};
+use bls_gadgets::utils::bytes_le_to_bits_le;
+
+use algebra::{
+    bw6_761::{Fr, FrParameters},
+    FpParameters,
+};
+use r1cs_core::{ConstraintSystemRef, SynthesisError};
+use r1cs_std::fields::fp::FpVar;
+use std::{fmt::Debug, sync::Arc};
+
+/// A public input which integrators add to the epochs circuit
+pub trait ExtraPublicInput: Debug + Send + Sync {
+    /// The personalization of the Blake2s hash of the input's bits. It must differ from the
+    /// domains of `bls_crypto` and of the other registered inputs.
+    fn domain(&self) -> [u8; 8];
+
+    /// Allocates the bits to hash, in little-endian order and a whole number of bytes, along
+    /// with any constraints which tie them to the proven epochs. It is also called for the
+    /// trusted setup, in which case the input holds no values and must allocate the same
+    /// number of bits with missing assignments.
+    fn generate_bits(
+        &self,
+        cs: ConstraintSystemRef<Fr>,
+        epoch_bits: &EpochBits,
+    ) -> Result<Vec<Bool>, SynthesisError>;
+
+    /// The bytes whose little-endian bits `generate_bits` allocates, as the verifier computes
+    /// them natively
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+/// Enforces the hash of each input's bits and packs it over BW6_761's Fr, allocating the
+/// packed elements as public inputs if `alloc_inputs` is set
+pub(super) fn enforce_extra_inputs(
+    inputs: &[Arc<dyn ExtraPublicInput>],
+    cs: ConstraintSystemRef<Fr>,
+    epoch_bits: &EpochBits,
+    alloc_inputs: bool,
+) -> Result<Vec<FpVar<Fr>>, SynthesisError> {
+    let mut packed = vec![];
+    for input in inputs {
+        let bits = input.generate_bits(cs.clone(), epoch_bits)?;
+        let hash_bits = blake2s(&bits, &input.domain())?;
+        packed.extend(MultipackGadget::pack::<_, FrParameters>(
+            &hash_bits,
+            FrParameters::CAPACITY as usize,
+            alloc_inputs,
+        )?);
+    }
+    Ok(packed)
+}
+
+/// The public inputs which the epochs circuit exposes for the extra inputs, i.e. the packed
+/// hash of each input's bytes in registration order. They follow the public inputs of the
+/// enabled features, see `verify_with_extra_inputs`.
+pub fn extra_public_inputs(inputs: &[Arc<dyn ExtraPublicInput>]) -> Vec<OuterScalar> {
+    inputs
+        .iter()
+        .flat_map(|input| {
+            let hash = personalized_blake2s(&input.to_bytes(), &input.domain());
+            OuterScalar::pack(&bytes_le_to_bits_le(&hash, 256))
+        })
+        .collect()
+}
@@ -5,11 +5,11 @@ use algebra::{
 use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
 use tracing::{debug, info, span, trace, Level};
 
-use crate::gadgets::constrain_bool;
 use bls_crypto::SIG_DOMAIN;
-use bls_gadgets::hash_to_bits;
+use bls_gadgets::{bits::constrain_bool, hash_to_bits};
 
 use super::MultipackGadget;
+use crate::scalars::InnerScalar;
 
 #[derive(Clone)]
 /// Gadget which converts its inputs to Boolean constraints, applies blake2x to them
@@ -26,11 +26,29 @@ pub struct HashToBits {
 impl HashToBits {
     /// Initializes an empty vector of bits. This is called when running the trusted setup
     pub fn empty<P: FpParameters>(num_epochs: usize) -> Self {
-        let modulus_bit_rounded = (((P::MODULUS_BITS + 7) / 8) * 8) as usize;
         HashToBits {
-            message_bits: vec![vec![None; modulus_bit_rounded]; num_epochs],
+            message_bits: vec![vec![None; Self::message_len::<P>()]; num_epochs],
         }
     }
+
+    /// The number of public inputs of the circuit returned by `empty`, i.e. of the field
+    /// elements which the CRH bits and the XOF bits of all the epochs are packed in
+    pub fn num_public_inputs<P: FpParameters>(num_epochs: usize) -> usize {
+        let capacity = FrParameters::CAPACITY as usize;
+        let num_elements = |bits: usize| (bits + capacity - 1) / capacity;
+        num_elements(num_epochs * Self::message_len::<P>()) + num_elements(num_epochs * 512)
+    }
+
+    /// The public inputs of a proof for the CRH bits of all the epochs, concatenated, and
+    /// their XOF bits, i.e. both packed in chunks of `InnerScalar::CAPACITY` bits
+    pub fn public_inputs(crh_bits: &[bool], xof_bits: &[bool]) -> Vec<InnerScalar> {
+        [InnerScalar::pack(crh_bits), InnerScalar::pack(xof_bits)].concat()
+    }
+
+    /// The number of CRH bits of each epoch, i.e. the modulus of `P` rounded up to whole bytes
+    fn message_len<P: FpParameters>() -> usize {
+        (((P::MODULUS_BITS + 7) / 8) * 8) as usize
+    }
 }
 
 impl ConstraintSynthesizer<Fr> for HashToBits {
@@ -74,7 +92,6 @@ impl ConstraintSynthesizer<Fr> for HashToBits {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gadgets::pack;
     use bls_crypto::hashers::{DirectHasher, Hasher};
     use bls_gadgets::utils::{bits_le_to_bytes_le, bytes_le_to_bits_le};
 
@@ -103,6 +120,10 @@ mod tests {
             let empty = HashToBits::empty::<BW6_761FrParameters>(num_epochs);
             generate_random_parameters::<Bls12_377, _, _>(empty, rng).unwrap()
         };
+        assert_eq!(
+            params.vk.gamma_abc_g1.len(),
+            HashToBits::num_public_inputs::<BW6_761FrParameters>(num_epochs) + 1
+        );
 
         // Prover generates the input and the proof
         // Each message must be 384 bits.
@@ -140,9 +161,7 @@ mod tests {
             }
             // The public inputs are the CRH and XOF bits split in `Fr::CAPACITY` chunks
             // encoded in LE
-            let packed_crh_bits = pack::<Fr, FrParameters>(&message_bits).unwrap();
-            let packed_xof_bits = pack::<Fr, FrParameters>(&xof_bits).unwrap();
-            [packed_crh_bits, packed_xof_bits].concat()
+            InnerScalar::to_fields(&HashToBits::public_inputs(&message_bits, &xof_bits))
         };
 
         let pvk = prepare_verifying_key(&params.vk);
@@ -0,0 +1,292 @@
+//! # Key Rotation Circuit
+//!
+//! Proves that validators of an epoch rotated their keys within the epoch, each with a
+//! handoff signed by its old key (see `bls_crypto::KeyRotation`). The handoff messages are
+//! hashed in the circuit from the bits which encode the old and new keys in the epoch blocks,
+//! so a rotation cannot be proven for a key pair other than the one which was signed. The
+//! public inputs are the epoch's index, followed by the validator set before and after the
+//! rotations, so the rotated set can be checked without the handoff signatures.
+
+use crate::gadgets::{g2_to_bits, MultipackGadget};
+use bls_crypto::{
+    hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, ROTATION_DOMAIN,
+};
+use bls_gadgets::{HashToGroupGadget, KeyRotationGadget};
+
+use algebra::{
+    bls12_377::{Bls12_377, Parameters as Bls12_377_Parameters},
+    bw6_761::{Fr, FrParameters},
+    curves::bls12::Bls12Parameters,
+    FpParameters, PairingEngine,
+};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use r1cs_std::{
+    alloc::AllocationMode,
+    bls12_377::{G1Var, G2Var, PairingVar},
+    fields::fp::FpVar,
+    prelude::*,
+    Assignment,
+};
+use std::convert::TryFrom;
+use tracing::{info, span, Level};
+
+type RotationGadget = KeyRotationGadget<Bls12_377, Fr, PairingVar>;
+type FrVar = FpVar<Fr>;
+type Bool = Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>;
+
+#[derive(Clone, Debug)]
+/// The key rotations of an epoch's validators and the aggregate of their handoff signatures.
+/// The number of validators determines the circuit.
+pub struct KeyRotations<E: PairingEngine> {
+    /// The index of the epoch whose validators are rotated
+    pub index: Option<u16>,
+    /// The epoch's validators before the rotations
+    pub public_keys: Vec<Option<E::G2Projective>>,
+    /// The key which replaces each validator's key, ignored for validators which are not
+    /// rotated
+    pub new_public_keys: Vec<Option<E::G2Projective>>,
+    /// Whether each validator's key is rotated
+    pub rotated: Vec<Option<bool>>,
+    /// The aggregate of the rotated validators' handoff signatures
+    pub aggregated_signature: Option<E::G1Projective>,
+}
+
+impl<E: PairingEngine> KeyRotations<E> {
+    /// Initializes empty key rotations. This is used when running the trusted setup.
+    pub fn empty(num_validators: usize) -> Self {
+        KeyRotations {
+            index: None,
+            public_keys: vec![None; num_validators],
+            new_public_keys: vec![None; num_validators],
+            rotated: vec![None; num_validators],
+            aggregated_signature: None,
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for KeyRotations<Bls12_377> {
+    /// Enforces that the old key of every rotated validator signed the handoff to its new key
+    /// and exposes the validator set before and after the rotations
+    ///
+    /// # Panics
+    ///
+    /// If the number of old keys, new keys and rotation bits differ
+    #[tracing::instrument(target = "r1cs")]
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let span = span!(Level::TRACE, "KeyRotations");
+        let _enter = span.enter();
+        info!("generating constraints");
+        assert_eq!(self.public_keys.len(), self.new_public_keys.len());
+        assert_eq!(self.public_keys.len(), self.rotated.len());
+
+        FrVar::new_input(cs.clone(), || Ok(Fr::from(self.index.get()?)))?;
+
+        let mut public_keys = Vec::with_capacity(self.public_keys.len());
+        let mut new_public_keys = Vec::with_capacity(self.public_keys.len());
+        let mut rotated = Vec::with_capacity(self.public_keys.len());
+        let mut handoff_hashes = Vec::with_capacity(self.public_keys.len());
+        let mut public_key_bits = vec![];
+        for ((pk, new_pk), is_rotated) in self
+            .public_keys
+            .iter()
+            .zip(&self.new_public_keys)
+            .zip(&self.rotated)
+        {
+            // the old keys are the epoch's, which are not checked to be in the prime order
+            // subgroup either
+            let pk = G2Var::new_variable_omit_prime_order_check(
+                cs.clone(),
+                || pk.get(),
+                AllocationMode::Witness,
+            )?;
+            let new_pk = G2Var::new_witness(cs.clone(), || new_pk.get())?;
+            let pk_bits = g2_to_bits(&pk)?;
+            let handoff = [pk_bits.clone(), g2_to_bits(&new_pk)?].concat();
+            handoff_hashes.push(hash_handoff(&handoff)?);
+            public_key_bits.extend(pk_bits);
+            public_keys.push(pk);
+            new_public_keys.push(new_pk);
+            rotated.push(Bool::new_witness(cs.clone(), || is_rotated.get())?);
+        }
+
+        let aggregated_signature = G1Var::new_variable_omit_prime_order_check(
+            cs,
+            || self.aggregated_signature.get(),
+            AllocationMode::Witness,
+        )?;
+        let rotated_keys = RotationGadget::enforce_rotations(
+            &public_keys,
+            &new_public_keys,
+            &rotated,
+            &handoff_hashes,
+            &aggregated_signature,
+        )?;
+
+        let mut rotated_key_bits = vec![];
+        for pk in &rotated_keys {
+            rotated_key_bits.extend(g2_to_bits(pk)?);
+        }
+        MultipackGadget::pack::<_, FrParameters>(
+            &public_key_bits,
+            FrParameters::CAPACITY as usize,
+            true,
+        )?;
+        MultipackGadget::pack::<_, FrParameters>(
+            &rotated_key_bits,
+            FrParameters::CAPACITY as usize,
+            true,
+        )?;
+        info!("constraints generated");
+
+        Ok(())
+    }
+}
+
+/// Hashes the big-endian bits of a handoff message to G1 as `bls_crypto::KeyRotation`
+/// does with `COMPOSITE_HASH_TO_G1_CIP22`, with the CRH->XOF hash constrained in BW6_761
+fn hash_handoff(bits: &[Bool]) -> Result<G1Var, SynthesisError> {
+    // pack the bits to little-endian bytes, as in `KeyRotation::encode_handoff`
+    let mut bits = bits.to_vec();
+    bits.reverse();
+    let message = bits
+        .chunks(8)
+        .map(|chunk| {
+            let mut chunk = chunk.to_vec();
+            chunk.resize(8, Bool::constant(false));
+            UInt8::from_bits_le(&chunk)
+        })
+        .collect::<Vec<_>>();
+
+    let counter = if bits.cs().is_in_setup_mode() {
+        0
+    } else {
+        let message = message
+            .iter()
+            .map(|byte| byte.value())
+            .collect::<Result<Vec<_>, _>>()?;
+        let (_, counter) = COMPOSITE_HASH_TO_G1_CIP22
+            .hash_with_attempt_cip22(ROTATION_DOMAIN, &message, &[])
+            .map_err(|_| SynthesisError::Unsatisfiable)?;
+        u8::try_from(counter).map_err(|_| SynthesisError::Unsatisfiable)?
+    };
+    let counter = UInt8::new_witness(bits.cs(), || Ok(counter))?;
+    HashToGroupGadget::enforce_hash_to_group_in_domain(ROTATION_DOMAIN, counter, &message, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encoding::encode_public_key, gadgets::pack};
+    use algebra::{bls12_377::G1Projective, Zero};
+    use bls_crypto::{KeyRotation, PrivateKey, PublicKey};
+    use bls_gadgets::utils::test_helpers::print_unsatisfied_constraints;
+    use r1cs_core::ConstraintSystem;
+
+    fn is_satisfied(
+        validators: &[PublicKey],
+        new_keys: &[PublicKey],
+        rotated: &[bool],
+        signature: G1Projective,
+        rotated_validators: &[PublicKey],
+    ) -> bool {
+        let circuit = KeyRotations::<Bls12_377> {
+            index: Some(3),
+            public_keys: validators.iter().map(|pk| Some(*pk.as_ref())).collect(),
+            new_public_keys: new_keys.iter().map(|pk| Some(*pk.as_ref())).collect(),
+            rotated: rotated.iter().map(|bit| Some(*bit)).collect(),
+            aggregated_signature: Some(signature),
+        };
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+
+        // the public inputs are the index and the packed validator sets
+        let encode = |keys: &[PublicKey]| {
+            let bits = keys
+                .iter()
+                .map(|pk| encode_public_key(pk).unwrap())
+                .collect::<Vec<_>>()
+                .concat();
+            pack::<Fr, FrParameters>(&bits).unwrap()
+        };
+        let mut public_inputs = vec![Fr::from(3u16)];
+        public_inputs.extend(encode(validators));
+        public_inputs.extend(encode(rotated_validators));
+        assert_eq!(
+            cs.borrow().unwrap().instance_assignment[1..].to_vec(),
+            public_inputs
+        );
+
+        print_unsatisfied_constraints(cs.clone());
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn proves_signed_rotations() {
+        let rng = &mut rand::thread_rng();
+        let hasher = &*COMPOSITE_HASH_TO_G1_CIP22;
+        let keys = (0..2)
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let validators = keys.iter().map(|key| key.to_public()).collect::<Vec<_>>();
+        let new_keys = (0..2)
+            .map(|_| PrivateKey::generate(rng).to_public())
+            .collect::<Vec<_>>();
+
+        // the first validator rotates its key
+        let rotation = KeyRotation::sign(&keys[0], new_keys[0].clone(), hasher).unwrap();
+        let signature = *rotation.signature.as_ref();
+        let rotated_validators = vec![new_keys[0].clone(), validators[1].clone()];
+        assert!(is_satisfied(
+            &validators,
+            &new_keys,
+            &[true, false],
+            signature,
+            &rotated_validators,
+        ));
+
+        // the handoff was signed for another key
+        let mut redirected_keys = new_keys.clone();
+        redirected_keys[0] = PrivateKey::generate(rng).to_public();
+        assert!(!is_satisfied(
+            &validators,
+            &redirected_keys,
+            &[true, false],
+            signature,
+            &[redirected_keys[0].clone(), validators[1].clone()],
+        ));
+
+        // the second validator did not sign a handoff
+        assert!(!is_satisfied(
+            &validators,
+            &new_keys,
+            &[true, true],
+            signature,
+            &new_keys,
+        ));
+
+        // no rotations keep the validator set
+        assert!(is_satisfied(
+            &validators,
+            &new_keys,
+            &[false, false],
+            G1Projective::zero(),
+            &validators,
+        ));
+    }
+
+    #[test]
+    fn encodes_handoffs_as_the_epoch_keys() {
+        let rng = &mut rand::thread_rng();
+        let old_key = PrivateKey::generate(rng).to_public();
+        let new_key = PrivateKey::generate(rng).to_public();
+        let bits = [
+            encode_public_key(&old_key).unwrap(),
+            encode_public_key(&new_key).unwrap(),
+        ]
+        .concat();
+        assert_eq!(
+            KeyRotation::encode_handoff(&old_key, &new_key).unwrap(),
+            bls_gadgets::utils::bits_be_to_bytes_le(&bits)
+        );
+    }
+}
@@ -1,5 +1,8 @@
 mod epoch_data;
-pub use epoch_data::EpochData;
+pub use epoch_data::{BitBuffers, EpochData, EpochDataBuilder, EpochDataError};
+
+mod epoch_index;
+pub use epoch_index::EpochIndexVar;
 
 mod hash_to_bits;
 pub use hash_to_bits::HashToBits;
@@ -11,22 +14,39 @@ mod pack;
 pub use pack::MultipackGadget;
 
 mod epoch_bits;
-pub use epoch_bits::EpochBits;
+pub use epoch_bits::{compress_public_inputs, EpochBits, PUBLIC_INPUTS_POSEIDON};
+
+mod randomness;
+pub use randomness::randomness_output;
+
+mod double_signing;
+pub use double_signing::DoubleSigning;
+
+mod key_rotation;
+pub use key_rotation::KeyRotations;
+
+mod reward_attestation;
+pub use reward_attestation::RewardAttestation;
+
+mod extra_inputs;
+pub use extra_inputs::{extra_public_inputs, ExtraPublicInput};
+
+mod recursion;
+pub use recursion::verify_bls12_377_groth16;
 
 mod epochs;
-pub use epochs::{HashToBitsHelper, ValidatorSetUpdate};
+pub use epochs::{bft_maximum_non_signers, HashToBitsHelper, ValidatorSetUpdate};
 
 // some helpers
 use algebra::{
-    bls12_377::Parameters as Bls12_377_Parameters, bw6_761::Fr, curves::bls12::Bls12Parameters,
-    BigInteger, FpParameters, PrimeField,
+    bls12_377::Parameters as Bls12_377_Parameters, curves::bls12::Bls12Parameters, BigInteger,
+    FpParameters, PrimeField,
 };
-use r1cs_core::{ConstraintSystemRef, SynthesisError};
-use r1cs_std::{bls12_377::G2Var, fields::fp::FpVar, prelude::*, Assignment};
+use r1cs_core::SynthesisError;
+use r1cs_std::{bls12_377::G2Var, prelude::*, Assignment};
 
-type FrVar = FpVar<Fr>;
 pub type Bool = Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>;
-use bls_gadgets::{utils::bytes_le_to_bits_be, YToBitGadget};
+use bls_gadgets::G2CompressGadget;
 
 #[cfg(test)]
 pub mod test_helpers {
@@ -82,45 +102,11 @@ pub fn pack<F: PrimeField, P: FpParameters>(values: &[bool]) -> Result<Vec<F>, S
         .collect::<Result<Vec<_>, _>>()
 }
 
-/// Returns a constrained field element given a list of bytes in little endian order
-fn bytes_to_fr(cs: ConstraintSystemRef<Fr>, bytes: Option<&[u8]>) -> Result<FrVar, SynthesisError> {
-    FrVar::new_witness(cs, || {
-        let bits = bytes_le_to_bits_be(bytes.get()?, 64 * <Fr as PrimeField>::BigInt::NUM_LIMBS);
-        Ok(Fr::from(<Fr as PrimeField>::BigInt::from_bits(&bits)))
-    })
-}
-
-/// Returns the bit representation of the Fr element in *little-endian* ordering.
-#[tracing::instrument(target = "r1cs")]
-fn fr_to_bits(input: &FrVar, length: usize) -> Result<Vec<Bool>, SynthesisError> {
-    let input = input.to_bits_le()?;
-    let result = input[0..length].to_vec();
-    Ok(result)
-}
+/// The number of bits produced by `g2_to_bits` (x.c0, x.c1 and the y bit)
+const G2_BITS: usize = G2CompressGadget::COMPRESSED_BITS;
 
 /// Returns elements in big-endian order
 #[tracing::instrument(target = "r1cs")]
 fn g2_to_bits(input: &G2Var) -> Result<Vec<Bool>, SynthesisError> {
-    let mut x_0 = input.x.c0.to_bits_le()?;
-    let mut x_1 = input.x.c1.to_bits_le()?;
-    x_0.reverse();
-    x_1.reverse();
-    let y_bit = input.y_to_bit()?;
-    let mut output = Vec::new();
-    output.extend_from_slice(&x_0);
-    output.extend_from_slice(&x_1);
-    output.push(y_bit);
-    Ok(output)
-}
-
-/// Constrains booleans to be witness variables
-#[tracing::instrument(target = "r1cs")]
-fn constrain_bool<F: PrimeField>(
-    input: &[Option<bool>],
-    cs: ConstraintSystemRef<F>,
-) -> Result<Vec<Boolean<F>>, SynthesisError> {
-    input
-        .iter()
-        .map(|b| Boolean::new_witness(cs.clone(), || b.get()))
-        .collect::<Result<Vec<_>, _>>()
+    G2CompressGadget::compress(input)
 }
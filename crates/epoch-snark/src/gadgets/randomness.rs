@@ -0,0 +1,48 @@
+use super::{epoch_bits::blake2s, Bool, EpochIndexVar};
+use crate::epoch_block::EpochBlock;
+use algebra::bw6_761::Fr;
+use bls_crypto::RANDOMNESS_DOMAIN;
+use bls_gadgets::bits::fp_to_bits_le;
+use r1cs_core::SynthesisError;
+use r1cs_std::fields::fp::FpVar;
+
+/// Derives the randomness output of an epoch from its index and entropy, as in
+/// `randomness_beacon::randomness_output`. Returns the LE bits of the output.
+pub fn randomness_output(
+    index: &EpochIndexVar,
+    epoch_entropy: &FpVar<Fr>,
+) -> Result<Vec<Bool>, SynthesisError> {
+    let mut message = index.to_bits_le();
+    message.extend(fp_to_bits_le(epoch_entropy, 8 * EpochBlock::ENTROPY_BYTES)?);
+    blake2s(&message, RANDOMNESS_DOMAIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::randomness_beacon;
+    use bls_gadgets::{bits::bytes_le_to_fp, utils::bytes_le_to_bits_le};
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::R1CSVar;
+    use rand::RngCore;
+
+    #[test]
+    fn matches_native_output() {
+        let rng = &mut rand::thread_rng();
+        let mut entropy = vec![0; EpochBlock::ENTROPY_BYTES];
+        rng.fill_bytes(&mut entropy);
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let index = EpochIndexVar::new_witness(cs.clone(), || Ok(513)).unwrap();
+        let entropy_var = bytes_le_to_fp(cs.clone(), Some(&entropy)).unwrap();
+        let output = randomness_output(&index, &entropy_var).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        let expected = randomness_beacon::randomness_output(513, Some(&entropy));
+        let output = output
+            .iter()
+            .map(|bit| bit.value().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(output, bytes_le_to_bits_le(&expected, 256));
+    }
+}
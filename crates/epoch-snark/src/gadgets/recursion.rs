@@ -0,0 +1,111 @@
+//! # One-Layer Recursion
+//!
+//! Verifies Groth16 proofs over BLS12-377 inside BW6-761 circuits, so that applications can
+//! compose a BLS12-377 proof (e.g. of the CRH->XOF hashes, see `HashToBits`) with their own
+//! BW6-761 circuit. This is the direction which the two curves support: BW6-761's scalar
+//! field is BLS12-377's base field, so BLS12-377's pairing can be computed natively in a
+//! BW6-761 circuit.
+//!
+//! The opposite direction, verifying BW6-761 proofs (such as epoch proofs) inside BLS12-377
+//! circuits, is not supported. BW6-761's pairing is computed over its 761-bit base field,
+//! which BLS12-377's 253-bit scalar field cannot represent natively, and BLS12-377 and
+//! BW6-761 form a two-chain rather than a cycle, so there is no curve to close the loop.
+//! Proving it would require emulating BW6-761's base field, which the constraint libraries
+//! which this crate builds on do not provide.
+
+use super::Bool;
+use algebra::{
+    bls12_377::{Bls12_377, Fr as BlsFr},
+    bw6_761::Fr,
+};
+use crypto_primitives::nizk::{
+    constraints::NIZKVerifierGadget,
+    groth16::{
+        constraints::{Groth16VerifierGadget, ProofVar, VerifyingKeyVar},
+        Groth16,
+    },
+};
+use groth16::{Proof, VerifyingKey};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use r1cs_std::{bls12_377::PairingVar, prelude::*};
+
+/// Enforces that `proof` is a valid Groth16 proof over BLS12-377 of the circuit `C` for the
+/// public inputs, each given as its little-endian bits. The proof is allocated as a witness,
+/// and the verifying key as a constant, so the resulting circuit is tied to that key.
+pub fn verify_bls12_377_groth16<C: ConstraintSynthesizer<BlsFr>>(
+    cs: ConstraintSystemRef<Fr>,
+    verifying_key: &VerifyingKey<Bls12_377>,
+    proof: &Proof<Bls12_377>,
+    public_inputs: &[Vec<Bool>],
+) -> Result<(), SynthesisError> {
+    let proof = ProofVar::<_, PairingVar>::new_witness(cs, || Ok(proof.clone()))?;
+    let verifying_key =
+        VerifyingKeyVar::<_, PairingVar>::new_constant(proof.a.cs(), verifying_key.clone())?;
+
+    let _ = <Groth16VerifierGadget<_, PairingVar> as NIZKVerifierGadget<
+        Groth16<Bls12_377, C, BlsFr>,
+        Fr,
+    >>::verify(&verifying_key, public_inputs.iter(), &proof)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::Field;
+    use groth16::{create_random_proof, generate_random_parameters};
+    use r1cs_core::ConstraintSystem;
+    use r1cs_std::fields::fp::FpVar;
+
+    // proves knowledge of a square root of the public input
+    #[derive(Clone)]
+    struct Square {
+        root: Option<BlsFr>,
+    }
+
+    impl ConstraintSynthesizer<BlsFr> for Square {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<BlsFr>,
+        ) -> Result<(), SynthesisError> {
+            let root = FpVar::new_witness(cs.clone(), || {
+                self.root.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let square = FpVar::new_input(cs, || {
+                self.root
+                    .map(|root| root.square())
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            (&root * &root).enforce_equal(&square)
+        }
+    }
+
+    fn verify_square(square: u8) -> bool {
+        let rng = &mut rand::thread_rng();
+        let params =
+            generate_random_parameters::<Bls12_377, _, _>(Square { root: None }, rng).unwrap();
+        let proof = create_random_proof(
+            Square {
+                root: Some(BlsFr::from(3u8)),
+            },
+            &params,
+            rng,
+        )
+        .unwrap();
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let input = (0..8)
+            .map(|i| Bool::new_witness(cs.clone(), || Ok((square >> i) & 1 == 1)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        verify_bls12_377_groth16::<Square>(cs.clone(), &params.vk, &proof, &[input]).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn verifies_bls12_377_proofs() {
+        assert!(verify_square(9));
+        assert!(!verify_square(10));
+    }
+}
@@ -0,0 +1,155 @@
+//! # Reward Attestation Circuit
+//!
+//! Proves the number of epochs which each validator signed over a proven epoch range. The
+//! epochs' indices and signed bitmaps are witnesses, which are tied to the range by
+//! recomputing its bitmap commitment (see `bitmap_commitment`). The public inputs are that
+//! commitment followed by the commitment to the counts (see `reward_vector`).
+
+use super::{epoch_bits::blake2s, Bool, EpochIndexVar, MultipackGadget};
+use bls_crypto::{Bitmap, BITMAP_DOMAIN, REWARD_DOMAIN};
+use bls_gadgets::BitmapVar;
+
+use algebra::{
+    bw6_761::{Fr, FrParameters},
+    FpParameters, PrimeField,
+};
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use r1cs_std::{fields::fp::FpVar, prelude::*};
+use tracing::{info, span, Level};
+
+type FrVar = FpVar<Fr>;
+
+/// The number of bits of each validator's count
+const COUNT_BITS: usize = 16;
+
+#[derive(Clone, Debug)]
+/// The index and signed bitmap of each epoch of a proven range, laid out as in its
+/// `BitmapCommitment` (i.e. including the dummy epochs). The number of epochs and of
+/// validators determine the circuit.
+pub struct RewardAttestation {
+    /// The index and signed bitmap of each epoch
+    pub epochs: Vec<(Option<u16>, Option<Bitmap>)>,
+    /// The number of validators, i.e. the length of each bitmap
+    pub num_validators: usize,
+}
+
+impl RewardAttestation {
+    /// Initializes an empty attestation. This is used when running the trusted setup.
+    pub fn empty(num_validators: usize, num_epochs: usize) -> Self {
+        RewardAttestation {
+            epochs: vec![(None, None); num_epochs],
+            num_validators,
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for RewardAttestation {
+    /// Enforces that the counts are those of the bitmaps behind the public bitmap commitment,
+    /// and that the public reward commitment is their hash
+    #[tracing::instrument(target = "r1cs")]
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let span = span!(Level::TRACE, "RewardAttestation");
+        let _enter = span.enter();
+        info!("generating constraints");
+
+        let mut bitmap_bits = vec![];
+        let mut counts = vec![FrVar::zero(); self.num_validators];
+        for (index, bitmap) in self.epochs {
+            let index = EpochIndexVar::new_witness(cs.clone(), || {
+                index.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let bitmap = BitmapVar::new_witness(cs.clone(), self.num_validators, || {
+                bitmap.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+
+            // each epoch is encoded as its 2 byte index followed by its bitmap, padded to
+            // whole bytes, matching `BitmapCommitment::to_bytes`
+            bitmap_bits.extend(index.to_bits_le());
+            bitmap_bits.extend(bitmap.to_packed_bits_le());
+
+            // dummy epochs are fully signed, but are not rewarded
+            let is_rewarded = index.is_zero()?.not();
+            for (count, signed) in counts.iter_mut().zip(bitmap.iter()) {
+                *count += FrVar::from(signed.and(&is_rewarded)?);
+            }
+        }
+        MultipackGadget::pack::<_, FrParameters>(
+            &blake2s(&bitmap_bits, BITMAP_DOMAIN)?,
+            FrParameters::CAPACITY as usize,
+            true,
+        )?;
+
+        // each count is encoded as 2 bytes, matching `RewardVector::to_bytes`, which also
+        // enforces that it fits in a u16
+        let mut count_bits = vec![];
+        for count in &counts {
+            let value = count
+                .value()
+                .ok()
+                .map(|value| value.into_repr().as_ref()[0]);
+            let bits = (0..COUNT_BITS)
+                .map(|i| {
+                    Bool::new_witness(cs.clone(), || {
+                        value
+                            .map(|value| (value >> i) & 1 == 1)
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut packed = FrVar::zero();
+            for (i, bit) in bits.iter().enumerate() {
+                packed += FrVar::from(bit.clone()) * Fr::from(1u64 << i);
+            }
+            packed.enforce_equal(count)?;
+            count_bits.extend(bits);
+        }
+        MultipackGadget::pack::<_, FrParameters>(
+            &blake2s(&count_bits, REWARD_DOMAIN)?,
+            FrParameters::CAPACITY as usize,
+            true,
+        )?;
+        info!("constraints generated");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bitmap_commitment::{test_helpers::transition, BitmapCommitment},
+        reward_vector::RewardVector,
+        scalars::OuterScalar,
+    };
+    use r1cs_core::ConstraintSystem;
+
+    #[test]
+    fn public_inputs_match_native_commitments() {
+        let transitions = vec![
+            transition(7, vec![true, false, true, true]),
+            transition(8, vec![false, false, true, true]),
+        ];
+        let bitmaps = BitmapCommitment::new(&transitions, 3, 4);
+        let rewards = RewardVector::new(&bitmaps, 4).unwrap();
+        assert_eq!(rewards.counts(), &[1, 0, 2, 2]);
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let circuit = RewardAttestation {
+            epochs: bitmaps
+                .bitmaps()
+                .iter()
+                .map(|(index, bitmap)| (Some(*index), Some(bitmap.clone())))
+                .collect(),
+            num_validators: 4,
+        };
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        let expected =
+            OuterScalar::to_fields(&[bitmaps.public_inputs(), rewards.public_inputs()].concat());
+        let cs = cs.borrow().unwrap();
+        // the first instance variable is the constant 1
+        assert_eq!(cs.instance_assignment[1..], expected[..]);
+    }
+}
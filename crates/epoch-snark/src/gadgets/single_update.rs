@@ -13,9 +13,11 @@ use r1cs_std::{
     R1CSVar,
 };
 
-use super::{constrain_bool, EpochData};
-use bls_gadgets::{BlsVerifyGadget, FpUtils};
-use tracing::{span, Level};
+use super::{BitBuffers, EpochData, EpochDataError, EpochIndexVar};
+use crate::progress::{EpochStage, PROGRESS_TARGET};
+use bls_crypto::Bitmap;
+use bls_gadgets::{BitmapVar, BlsVerifyGadget, FpUtils};
+use tracing::{debug, span, Level};
 
 // Instantiate the BLS Verification gadget
 type BlsGadget = BlsVerifyGadget<Bls12_377, Fr, PairingVar>;
@@ -28,8 +30,9 @@ type Bool = Boolean<<Bls12_377_Parameters as Bls12Parameters>::Fp>;
 pub struct SingleUpdate<E: PairingEngine> {
     /// The new epoch block's metadata
     pub epoch_data: EpochData<E>,
-    /// Bitmap of the validators who signed on the next epoch block
-    pub signed_bitmap: Vec<Option<bool>>,
+    /// Bitmap of the validators who signed on the next epoch block, which is only assigned
+    /// when generating the proof
+    pub signed_bitmap: Option<Bitmap>,
 }
 
 impl<E: PairingEngine> SingleUpdate<E> {
@@ -37,9 +40,36 @@ impl<E: PairingEngine> SingleUpdate<E> {
     pub fn empty(num_validators: usize, maximum_non_signers: usize) -> Self {
         Self {
             epoch_data: EpochData::<E>::empty(num_validators, maximum_non_signers),
-            signed_bitmap: vec![None; num_validators],
+            signed_bitmap: None,
         }
     }
+
+    /// Validates the update's witness data and returns it in the canonical form which the
+    /// Go encoder produces, so that the prover never proves different data than was signed.
+    /// The epoch data is canonicalized as by `EpochData::canonicalize`, and the signed bitmap
+    /// is padded with unset bits to `num_validators` bits. Bitmaps with bits set past
+    /// `num_validators` are rejected, while unset trailing bits are dropped.
+    pub fn canonicalize(&self, num_validators: usize) -> Result<Self, EpochDataError> {
+        let epoch_data = self.epoch_data.canonicalize(num_validators)?;
+        let bitmap = self
+            .signed_bitmap
+            .as_ref()
+            .ok_or(EpochDataError::MissingBitmap)?;
+        if bitmap.iter().skip(num_validators).any(|signed| *signed) {
+            return Err(EpochDataError::TrailingBitmapBits { num_validators });
+        }
+        let signed_bitmap = bitmap
+            .iter()
+            .copied()
+            .chain(std::iter::repeat(false))
+            .take(num_validators)
+            .collect();
+
+        Ok(Self {
+            epoch_data,
+            signed_bitmap: Some(signed_bitmap),
+        })
+    }
 }
 
 /// A [`SingleUpdate`] is constrained to a `ConstrainedEpoch` via [`SingleUpdate.constrain`]
@@ -57,7 +87,9 @@ pub struct ConstrainedEpoch {
     /// of the previous epoch
     pub aggregate_pk: G2Var,
     /// The epoch's index
-    pub index: FrVar,
+    pub index: EpochIndexVar,
+    /// Bitmap of the validators of the previous epoch who signed this epoch
+    pub signed_bitmap: BitmapVar<Fr>,
     /// Unpredictable value to add entropy to the epoch data,
     pub epoch_entropy: FrVar,
     /// Entropy value for the previous epoch.
@@ -70,6 +102,10 @@ pub struct ConstrainedEpoch {
     pub xof_bits: Vec<Bool>,
     /// Aux data for proving the CRH->XOF hash outside of BW6_761
     pub crh_bits: Vec<Bool>,
+    /// The epoch's auxiliary data bits, empty unless the `epoch-aux-data` feature is enabled
+    pub aux_data_bits: Vec<Bool>,
+    /// The epoch's chain identifier bits, empty unless the `chain-binding` feature is enabled
+    pub chain_id_bits: Vec<Bool>,
 }
 
 impl SingleUpdate<Bls12_377> {
@@ -84,23 +120,53 @@ impl SingleUpdate<Bls12_377> {
     pub fn constrain(
         &self,
         previous_pubkeys: &[G2Var],
-        previous_epoch_index: &FrVar,
+        previous_epoch_index: &EpochIndexVar,
         previous_epoch_randomness: &FrVar,
         previous_max_non_signers: &FrVar,
         constrain_entropy_bit: &Bool, // True if entropy present in first epoch block
         num_validators: u32,
         generate_constraints_for_hash: bool,
+    ) -> Result<ConstrainedEpoch, SynthesisError> {
+        self.constrain_with_buffers(
+            previous_pubkeys,
+            previous_epoch_index,
+            previous_epoch_randomness,
+            previous_max_non_signers,
+            constrain_entropy_bit,
+            num_validators,
+            generate_constraints_for_hash,
+            &mut BitBuffers::default(),
+        )
+    }
+
+    /// Same as `constrain`, with the epoch's bit vectors taken from `buffers`, see
+    /// `EpochData::constrain_with_buffers`
+    #[tracing::instrument(target = "r1cs", skip(buffers))]
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    pub fn constrain_with_buffers(
+        &self,
+        previous_pubkeys: &[G2Var],
+        previous_epoch_index: &EpochIndexVar,
+        previous_epoch_randomness: &FrVar,
+        previous_max_non_signers: &FrVar,
+        constrain_entropy_bit: &Bool, // True if entropy present in first epoch block
+        num_validators: u32,
+        generate_constraints_for_hash: bool,
+        buffers: &mut BitBuffers,
     ) -> Result<ConstrainedEpoch, SynthesisError> {
         let span = span!(Level::TRACE, "SingleUpdate");
         let _enter = span.enter();
         // the number of validators across all epochs must be consistent
         assert_eq!(num_validators as usize, self.epoch_data.public_keys.len());
         // Get the constrained epoch data
-        let epoch_data = self
-            .epoch_data
-            .constrain(previous_epoch_index, generate_constraints_for_hash)?;
+        let epoch_data = self.epoch_data.constrain_with_buffers(
+            previous_epoch_index,
+            generate_constraints_for_hash,
+            buffers,
+        )?;
+        debug!(target: PROGRESS_TARGET, stage = EpochStage::EpochData.name());
         // False (0) if a dummy epoch for padding
-        let index_bit = epoch_data.index.is_eq_zero()?.not();
+        let index_bit = epoch_data.index.is_zero()?.not();
 
         // Enforce equality with previous epoch's entropy if current
         // epoch is not a dummy block and entropy was present in the
@@ -109,18 +175,25 @@ impl SingleUpdate<Bls12_377> {
             &epoch_data.parent_entropy,
             &index_bit.and(&constrain_entropy_bit)?,
         )?;
+        debug!(target: PROGRESS_TARGET, stage = EpochStage::Entropy.name());
 
         // convert the bitmap to constraints
-        let signed_bitmap = constrain_bool(&self.signed_bitmap, previous_epoch_index.cs())?;
+        let signed_bitmap =
+            BitmapVar::new_witness(previous_epoch_index.cs(), num_validators as usize, || {
+                self.signed_bitmap
+                    .clone()
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
 
         // Verify that the bitmap is consistent with the pubkeys read from the
         // previous epoch and prepare the message hash and the aggregate pk
-        let (message_hash, aggregated_public_key) = BlsGadget::enforce_bitmap(
+        let (message_hash, aggregated_public_key, _) = BlsGadget::enforce_bitmap(
             previous_pubkeys,
             &signed_bitmap,
             &epoch_data.message_hash,
             &previous_max_non_signers,
         )?;
+        debug!(target: PROGRESS_TARGET, stage = EpochStage::Bitmap.name());
 
         Ok(ConstrainedEpoch {
             new_pubkeys: epoch_data.pubkeys,
@@ -128,12 +201,15 @@ impl SingleUpdate<Bls12_377> {
             message_hash,
             aggregate_pk: aggregated_public_key,
             index: epoch_data.index,
+            signed_bitmap,
             epoch_entropy: epoch_data.epoch_entropy,
             parent_entropy: epoch_data.parent_entropy,
             combined_first_epoch_bits: epoch_data.combined_first_epoch_bits,
             combined_last_epoch_bits: epoch_data.combined_last_epoch_bits,
             xof_bits: epoch_data.xof_bits,
             crh_bits: epoch_data.crh_bits,
+            aux_data_bits: epoch_data.aux_data_bits,
+            chain_id_bits: epoch_data.chain_id_bits,
         })
     }
 }
@@ -162,17 +238,20 @@ pub mod test_helpers {
             parent_entropy,
             maximum_non_signers,
             public_keys: to_option_iter(public_keys),
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
+            hash_counter: None,
         };
 
         SingleUpdate::<E> {
             epoch_data,
-            signed_bitmap: to_option_iter(bitmap),
+            signed_bitmap: Some(Bitmap::from(bitmap.to_vec())),
         }
     }
 
     #[tracing::instrument(target = "r1cs")]
     pub fn generate_dummy_update<E: PairingEngine>(num_validators: u32) -> SingleUpdate<E> {
-        let bitmap = (0..num_validators).map(|_| true).collect::<Vec<_>>();
         let public_keys = (0..num_validators)
             .map(|_| E::G2Projective::prime_subgroup_generator())
             .collect::<Vec<_>>();
@@ -183,11 +262,15 @@ pub mod test_helpers {
             parent_entropy: Some(vec![0u8; 8 * EpochData::<E>::ENTROPY_BYTES]),
             maximum_non_signers: 0u32,
             public_keys: to_option_iter(public_keys.as_slice()),
+            aux_data: None,
+            chain_id: None,
+            validator_blinding: None,
+            hash_counter: None,
         };
 
         SingleUpdate::<E> {
             epoch_data,
-            signed_bitmap: to_option_iter(&bitmap),
+            signed_bitmap: Some(Bitmap::full(num_validators as usize)),
         }
     }
 }
@@ -195,13 +278,12 @@ pub mod test_helpers {
 #[cfg(test)]
 mod tests {
     use super::{test_helpers::generate_single_update, *};
-    use crate::gadgets::bytes_to_fr;
     use bls_gadgets::utils::test_helpers::{
         print_unsatisfied_constraints, run_profile_constraints,
     };
 
-    use algebra::{BigInteger, PrimeField, UniformRand};
-    use bls_gadgets::utils::bytes_le_to_bits_le;
+    use algebra::{BigInteger, PrimeField, ProjectiveCurve, UniformRand};
+    use bls_gadgets::{bits::bytes_le_to_fp, utils::bytes_le_to_bits_le};
     use r1cs_core::{ConstraintSystem, ConstraintSystemRef};
     use r1cs_std::{
         alloc::{AllocVar, AllocationMode},
@@ -262,6 +344,68 @@ mod tests {
         });
     }
 
+    #[test]
+    fn canonicalizes_update() {
+        let public_keys = pubkeys::<Bls12_377>(4);
+        let entropy = Some(vec![1u8; EpochData::<Bls12_377>::ENTROPY_BYTES]);
+        let update = generate_single_update::<Bls12_377>(
+            3,
+            1,
+            entropy.clone(),
+            entropy,
+            1,
+            &public_keys,
+            &[true, false, true],
+        );
+
+        let canonical = update.canonicalize(4).unwrap();
+        assert_eq!(
+            canonical.signed_bitmap,
+            Some(Bitmap::from(vec![true, false, true, false]))
+        );
+        for (key, expected) in canonical.epoch_data.public_keys.iter().zip(&public_keys) {
+            let key = key.unwrap();
+            assert_eq!(key, *expected);
+            assert!(key.is_normalized());
+        }
+        // canonical updates are left unchanged
+        let again = canonical.canonicalize(4).unwrap();
+        assert_eq!(again.signed_bitmap, canonical.signed_bitmap);
+        assert_eq!(
+            again.epoch_data.public_keys,
+            canonical.epoch_data.public_keys
+        );
+
+        let mut trailing = update.clone();
+        trailing.signed_bitmap = Some(Bitmap::from(vec![true, true, true, false, true]));
+        assert_eq!(
+            trailing.canonicalize(4).unwrap_err(),
+            EpochDataError::TrailingBitmapBits { num_validators: 4 }
+        );
+        let mut short_entropy = update.clone();
+        short_entropy.epoch_data.parent_entropy = Some(vec![1u8; 3]);
+        assert_eq!(
+            short_entropy.canonicalize(4).unwrap_err(),
+            EpochDataError::InvalidEntropyLength {
+                expected: EpochData::<Bls12_377>::ENTROPY_BYTES,
+                got: 3
+            }
+        );
+        let mut missing_key = update.clone();
+        missing_key.epoch_data.public_keys[2] = None;
+        assert_eq!(
+            missing_key.canonicalize(4).unwrap_err(),
+            EpochDataError::MissingPublicKey(2)
+        );
+        assert_eq!(
+            update.canonicalize(5).unwrap_err(),
+            EpochDataError::InvalidNumPublicKeys {
+                expected: 5,
+                got: 4
+            }
+        );
+    }
+
     #[test]
     #[should_panic]
     fn validator_number_cannot_change() {
@@ -295,7 +439,7 @@ mod tests {
                 )
             })
             .collect::<Result<Vec<_>, _>>()?;
-        let prev_index = FrVar::new_witness(cs.clone(), || Ok(Fr::from(prev_index)))?;
+        let prev_index = EpochIndexVar::new_witness(cs.clone(), || Ok(prev_index))?;
         let prev_max_non_signers =
             FrVar::new_witness(cs.clone(), || Ok(Fr::from(maximum_non_signers)))?;
 
@@ -306,7 +450,7 @@ mod tests {
                 let bigint = <Fr as PrimeField>::BigInt::from_bits(&bits);
                 FrVar::new_witness(cs, || Ok(Fr::from(bigint)))?
             }
-            None => bytes_to_fr(
+            None => bytes_le_to_fp(
                 cs,
                 Some(&vec![0u8; EpochData::<Bls12_377>::ENTROPY_BYTES][..]),
             )?,
@@ -24,11 +24,61 @@
 mod api;
 pub use api::*;
 
+/// Helpers for inspecting the signed bitmaps of a sequence of epoch transitions
+pub mod analysis;
+
+/// Succinct commitment to the auxiliary data of a proven epoch range
+pub mod aux_data_commitment;
+
+/// Succinct commitment to the signed bitmaps of a proven epoch range
+pub mod bitmap_commitment;
+
+/// RLP and protobuf encodings of epoch blocks and transitions
+#[cfg(any(feature = "rlp", feature = "protobuf"))]
+pub mod codec;
+
 mod encoding;
 pub use encoding::EncodingError;
 
 mod epoch_block;
-pub use epoch_block::{EpochBlock, EpochTransition};
+pub use epoch_block::{EpochBlock, EpochSummary, EpochTransition};
+
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+
+/// Validation of epoch transitions without SNARKs
+mod native;
+pub use native::{native_verify_transitions, NativeVerificationError};
+
+/// Observing the progress of constraint generation
+pub mod progress;
+
+/// Sources of epoch transitions for the prover
+pub mod provider;
+
+/// Verified randomness derived from the entropy of a proven epoch range
+pub mod randomness_beacon;
+
+/// Reports on the size and on-chain verification cost of proofs
+pub mod report;
+
+/// Per-validator signed epoch counts of a proven epoch range
+pub mod reward_vector;
+
+/// Seeded generation of realistic epoch transition workloads
+pub mod simulation;
+
+/// Typed scalars of the inner and outer constraint fields
+mod scalars;
+pub use scalars::{InnerScalar, OuterScalar, ScalarError};
+
+/// Validator sets without duplicates in canonical order
+mod validator_set;
+pub use validator_set::{ValidatorSet, ValidatorSetError};
 
 mod gadgets;
-pub use gadgets::ValidatorSetUpdate;
+pub use gadgets::{
+    bft_maximum_non_signers, compress_public_inputs, extra_public_inputs, verify_bls12_377_groth16,
+    BitBuffers, DoubleSigning, EpochBits, EpochData, EpochDataBuilder, EpochDataError,
+    EpochIndexVar, ExtraPublicInput, KeyRotations, RewardAttestation, ValidatorSetUpdate,
+};
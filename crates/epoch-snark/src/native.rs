@@ -0,0 +1,301 @@
+//! Native Transition Validation
+//!
+//! Checks a sequence of epoch transitions against the rules which the epochs circuit
+//! enforces, without generating a proof. This serves light clients which fall back to
+//! verifying the transitions themselves, and as a differential oracle for the circuit:
+//! transitions which are rejected here must not be provable, and vice versa.
+use crate::{
+    encoding::EncodingError,
+    epoch_block::{EpochBlock, EpochTransition},
+    gadgets::bft_maximum_non_signers,
+    validator_set::{ValidatorSet, ValidatorSetError},
+};
+use bls_crypto::{
+    hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, BLSError, Bitmap,
+    ErrorCode, PublicKey, ToErrorCode,
+};
+use thiserror::Error;
+use tracing::debug;
+
+#[derive(Debug, Error)]
+/// Error raised when an epoch transition violates the rules enforced by the circuit. The
+/// transition is identified by its position in the provided transitions.
+pub enum NativeVerificationError {
+    #[error("Encoding Error: {0}")]
+    EncodingError(#[from] EncodingError),
+    #[error("transition {transition} has index {actual}, expected {expected}")]
+    IndexMismatch {
+        transition: usize,
+        expected: u16,
+        actual: u16,
+    },
+    #[error("the parent entropy of transition {0} is not the entropy of the previous epoch")]
+    EntropyMismatch(usize),
+    #[error("transition {transition} has bitmap bits set past its {num_validators} validators")]
+    TrailingBitmapBits {
+        transition: usize,
+        num_validators: usize,
+    },
+    #[error("transition {transition} has {non_signers} non-signers, the maximum is {maximum}")]
+    TooManyNonSigners {
+        transition: usize,
+        non_signers: usize,
+        maximum: u32,
+    },
+    #[error("the epoch at transition {transition:?} allows {actual} non-signers, expected the BFT threshold {expected}")]
+    NotBftThreshold {
+        transition: Option<usize>,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("the chain identifier of transition {0} is not the one of the first epoch")]
+    ChainIdMismatch(usize),
+    #[error("the validators of the epoch at transition {transition:?} are not canonical: {error}")]
+    NonCanonicalValidators {
+        transition: Option<usize>,
+        error: ValidatorSetError,
+    },
+    #[error("the aggregate signature of transition {transition} is invalid: {error}")]
+    InvalidSignature { transition: usize, error: BLSError },
+}
+
+impl ToErrorCode for NativeVerificationError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            NativeVerificationError::EncodingError(e) => e.error_code(),
+            NativeVerificationError::InvalidSignature { error, .. } => error.error_code(),
+            NativeVerificationError::NonCanonicalValidators { error, .. } => error.error_code(),
+            _ => ErrorCode::InvalidEpochData,
+        }
+    }
+}
+
+/// Verifies the transitions from the first epoch natively. Each transition must:
+///
+/// - have the index following the one of the previous epoch
+/// - have the previous epoch's entropy as its parent entropy, if the first epoch has entropy
+/// - have a bitmap over the validators of the previous epoch, with at most the previous
+///   epoch's maximum number of non-signers. As in `SingleUpdate::canonicalize`, short bitmaps
+///   are padded with non-signers and unset bits past the validators are dropped.
+/// - be signed by the validators of the previous epoch which are set in the bitmap
+///
+/// Features which extend the circuit extend the checks accordingly, e.g. with the
+/// `bft-threshold` feature every epoch must allow the BFT maximum number of non-signers, and
+/// with the `canonical-validators` feature every epoch with a non-zero index must have a
+/// canonical `ValidatorSet`.
+pub fn native_verify_transitions(
+    first_epoch: &EpochBlock,
+    transitions: &[EpochTransition],
+) -> Result<(), NativeVerificationError> {
+    debug!(
+        "Natively verifying {} transitions from epoch {}",
+        transitions.len(),
+        first_epoch.index
+    );
+    check_bft_threshold(None, first_epoch)?;
+    check_canonical_validators(None, first_epoch)?;
+    let entropy_chained = first_epoch.epoch_entropy.is_some();
+
+    let mut previous = first_epoch;
+    for (i, transition) in transitions.iter().enumerate() {
+        let block = &transition.block;
+        let expected_index = previous.index.wrapping_add(1);
+        if block.index != expected_index {
+            return Err(NativeVerificationError::IndexMismatch {
+                transition: i,
+                expected: expected_index,
+                actual: block.index,
+            });
+        }
+        if entropy_chained && block.parent_entropy != previous.epoch_entropy {
+            return Err(NativeVerificationError::EntropyMismatch(i));
+        }
+        if cfg!(feature = "chain-binding") && block.chain_id != first_epoch.chain_id {
+            return Err(NativeVerificationError::ChainIdMismatch(i));
+        }
+        check_bft_threshold(Some(i), block)?;
+        check_canonical_validators(Some(i), block)?;
+
+        let bitmap = canonical_bitmap(i, &transition.bitmap, previous.new_public_keys.len())?;
+        let non_signers = bitmap.complement().popcount();
+        if non_signers > previous.maximum_non_signers as usize {
+            return Err(NativeVerificationError::TooManyNonSigners {
+                transition: i,
+                non_signers,
+                maximum: previous.maximum_non_signers,
+            });
+        }
+
+        let signers = previous
+            .new_public_keys
+            .iter()
+            .zip(&bitmap)
+            .filter(|(_, signed)| **signed)
+            .map(|(public_key, _)| public_key);
+        let (message, extra_data) = block.encode_inner_to_bytes_cip22()?;
+        PublicKey::aggregate(signers)
+            .verify(
+                &message,
+                &extra_data,
+                &transition.aggregate_signature,
+                &*COMPOSITE_HASH_TO_G1_CIP22,
+            )
+            .map_err(|error| NativeVerificationError::InvalidSignature {
+                transition: i,
+                error,
+            })?;
+
+        previous = block;
+    }
+    Ok(())
+}
+
+/// Canonicalizes the bitmap as the prover does, see `SingleUpdate::canonicalize`
+fn canonical_bitmap(
+    transition: usize,
+    bitmap: &Bitmap,
+    num_validators: usize,
+) -> Result<Bitmap, NativeVerificationError> {
+    if bitmap.iter().skip(num_validators).any(|signed| *signed) {
+        return Err(NativeVerificationError::TrailingBitmapBits {
+            transition,
+            num_validators,
+        });
+    }
+    Ok(bitmap
+        .iter()
+        .copied()
+        .chain(std::iter::repeat(false))
+        .take(num_validators)
+        .collect())
+}
+
+fn check_bft_threshold(
+    transition: Option<usize>,
+    block: &EpochBlock,
+) -> Result<(), NativeVerificationError> {
+    let expected = bft_maximum_non_signers(block.maximum_validators as u32);
+    if cfg!(feature = "bft-threshold") && block.maximum_non_signers != expected {
+        return Err(NativeVerificationError::NotBftThreshold {
+            transition,
+            expected,
+            actual: block.maximum_non_signers,
+        });
+    }
+    Ok(())
+}
+
+fn check_canonical_validators(
+    transition: Option<usize>,
+    block: &EpochBlock,
+) -> Result<(), NativeVerificationError> {
+    if cfg!(feature = "canonical-validators") && block.index != 0 {
+        ValidatorSet::new(block.new_public_keys.clone()).map_err(|error| {
+            NativeVerificationError::NonCanonicalValidators { transition, error }
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{simulate, SimulationConfig};
+    use bls_crypto::Signature;
+
+    #[test]
+    fn accepts_valid_transitions() {
+        let simulation =
+            simulate(&SimulationConfig::new(7, 4).with_churn(0.5).unwrap(), 3).unwrap();
+        native_verify_transitions(&simulation.first_epoch, &simulation.transitions).unwrap();
+        native_verify_transitions(&simulation.first_epoch, &[]).unwrap();
+    }
+
+    #[test]
+    fn canonicalizes_bitmaps() {
+        let simulation = simulate(&SimulationConfig::new(7, 3), 2).unwrap();
+        let mut transitions = simulation.transitions.clone();
+        for transition in transitions.iter_mut() {
+            let mut bitmap = transition.bitmap.to_vec();
+            if bitmap[6] {
+                // unset trailing bits are dropped
+                bitmap.extend(&[false, false]);
+            } else {
+                // and short bitmaps are padded with non-signers
+                bitmap.pop();
+            }
+            transition.bitmap = bitmap.into();
+        }
+        native_verify_transitions(&simulation.first_epoch, &transitions).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_transitions() {
+        let simulation = simulate(&SimulationConfig::new(7, 3), 5).unwrap();
+        let first_epoch = &simulation.first_epoch;
+        let verify = |transitions: &[EpochTransition]| {
+            native_verify_transitions(first_epoch, transitions).unwrap_err()
+        };
+
+        let mut transitions = simulation.transitions.clone();
+        transitions[1].block.index += 1;
+        assert!(matches!(
+            verify(&transitions),
+            NativeVerificationError::IndexMismatch {
+                transition: 1,
+                expected: 2,
+                actual: 3
+            }
+        ));
+
+        let mut transitions = simulation.transitions.clone();
+        transitions[2].block.parent_entropy = transitions[0].block.epoch_entropy.clone();
+        assert!(matches!(
+            verify(&transitions),
+            NativeVerificationError::EntropyMismatch(2)
+        ));
+
+        let mut transitions = simulation.transitions.clone();
+        transitions[0].bitmap = transitions[0]
+            .bitmap
+            .iter()
+            .copied()
+            .chain(Some(true))
+            .collect();
+        assert!(matches!(
+            verify(&transitions),
+            NativeVerificationError::TrailingBitmapBits {
+                transition: 0,
+                num_validators: 7
+            }
+        ));
+
+        let mut transitions = simulation.transitions.clone();
+        transitions[1].bitmap = Bitmap::new(7);
+        assert!(matches!(
+            verify(&transitions),
+            NativeVerificationError::TooManyNonSigners {
+                transition: 1,
+                non_signers: 7,
+                maximum: 2
+            }
+        ));
+
+        // the signature of another epoch, or over another bitmap
+        let mut transitions = simulation.transitions.clone();
+        transitions[2].aggregate_signature = transitions[1].aggregate_signature.clone();
+        assert!(matches!(
+            verify(&transitions),
+            NativeVerificationError::InvalidSignature { transition: 2, .. }
+        ));
+        let mut transitions = simulation.transitions.clone();
+        transitions[0].aggregate_signature = Signature::aggregate(vec![
+            transitions[0].aggregate_signature.clone(),
+            transitions[1].aggregate_signature.clone(),
+        ]);
+        assert!(matches!(
+            verify(&transitions),
+            NativeVerificationError::InvalidSignature { transition: 0, .. }
+        ));
+    }
+}
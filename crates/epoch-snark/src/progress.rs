@@ -0,0 +1,237 @@
+//! # Constraint Generation Progress
+//!
+//! Generating the constraints of the epochs circuit takes a while for large validator sets.
+//! As each epoch goes through the stages of `EpochStage`, the gadgets emit a `tracing` event
+//! with the `PROGRESS_TARGET` target and the name of the stage in its `stage` field.
+//! Embedders can observe the events of a call as `ProgressEvent`s with `observe_progress`,
+//! e.g. to report the progress of a proof:
+//!
+//! ```ignore
+//! let proof = observe_progress(
+//!     |event| println!("epoch {} reached {:?}", event.position, event.stage),
+//!     || prove(&parameters, num_validators, &first_epoch, &transitions, max_transitions),
+//! )?;
+//! ```
+//!
+//! `observe_progress` replaces the thread's subscriber during the call. Embedders which keep
+//! their own subscriber can instead add a `ProgressLayer` to it:
+//!
+//! ```ignore
+//! let subscriber = Registry::default()
+//!     .with(fmt_layer)
+//!     .with(ProgressLayer::new(|event| println!("{:?}", event)));
+//! ```
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id},
+    Event, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, Layer, SubscriberExt},
+    registry::{LookupSpan, SpanRef},
+    Registry,
+};
+
+/// The target of the progress events
+pub const PROGRESS_TARGET: &str = "epoch_snark::progress";
+
+/// The name of the span which the epochs circuit enters for each epoch, whose `i` field is
+/// the position of the epoch
+const EPOCH_SPAN: &str = "index";
+
+/// A stage of the constraint generation of an epoch, in the order in which they are reached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EpochStage {
+    /// The epoch's data was allocated and hashed
+    EpochData,
+    /// The epoch's entropy was checked against its parent's
+    Entropy,
+    /// The signed bitmap was checked and the signers' public keys were aggregated
+    Bitmap,
+    /// The epoch is fully constrained
+    Constrained,
+}
+
+impl EpochStage {
+    pub(crate) const ALL: [EpochStage; 4] = [
+        EpochStage::EpochData,
+        EpochStage::Entropy,
+        EpochStage::Bitmap,
+        EpochStage::Constrained,
+    ];
+
+    /// The name of the stage in the `stage` field of the progress events
+    pub fn name(self) -> &'static str {
+        match self {
+            EpochStage::EpochData => "epoch_data",
+            EpochStage::Entropy => "entropy",
+            EpochStage::Bitmap => "bitmap",
+            EpochStage::Constrained => "constrained",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|stage| stage.name() == name)
+    }
+}
+
+/// An epoch reaching a stage of its constraint generation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgressEvent {
+    /// The position of the epoch among the transitions of the proof, including the dummy
+    /// epochs which pad them to `max_transitions`
+    pub position: usize,
+    /// The stage which the epoch reached
+    pub stage: EpochStage,
+}
+
+/// Runs `f`, passing the progress events which it emits on this thread to `observer`. This
+/// replaces the thread's `tracing` subscriber for the duration of the call, so the events of
+/// the embedder's own layers are dropped meanwhile; see `ProgressLayer` to keep them.
+pub fn observe_progress<T>(
+    observer: impl Fn(ProgressEvent) + Send + Sync + 'static,
+    f: impl FnOnce() -> T,
+) -> T {
+    let subscriber = Registry::default().with(ProgressLayer::new(observer));
+    tracing::subscriber::with_default(subscriber, f)
+}
+
+/// The position of an epoch, stored in the extensions of its span
+struct Position(usize);
+
+/// Tracing layer which records the position of each epoch span, and passes the progress
+/// events to the observer along with the position of their epoch. It can be composed with
+/// other layers over any subscriber which stores span data, e.g. a `Registry`.
+pub struct ProgressLayer {
+    observer: Box<dyn Fn(ProgressEvent) + Send + Sync>,
+}
+
+impl ProgressLayer {
+    /// Creates a layer which passes the progress events to `observer`
+    pub fn new(observer: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        ProgressLayer {
+            observer: Box::new(observer),
+        }
+    }
+}
+
+impl<S> Layer<S> for ProgressLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != EPOCH_SPAN {
+            return;
+        }
+        let mut fields = ProgressFields::default();
+        attrs.record(&mut fields);
+        if let (Some(position), Some(span)) = (fields.position, ctx.span(id)) {
+            span.extensions_mut().insert(Position(position));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != PROGRESS_TARGET {
+            return;
+        }
+        let mut fields = ProgressFields::default();
+        event.record(&mut fields);
+        let stage = match fields.stage {
+            Some(stage) => stage,
+            None => return,
+        };
+        let position = ctx.lookup_current().and_then(|current| {
+            let position = |span: &SpanRef<'_, S>| span.extensions().get::<Position>().map(|p| p.0);
+            position(&current).or_else(|| current.parents().find_map(|span| position(&span)))
+        });
+        if let Some(position) = position {
+            (self.observer)(ProgressEvent { position, stage });
+        }
+    }
+}
+
+/// Collects the position of an epoch span, or the stage of a progress event
+#[derive(Default)]
+struct ProgressFields {
+    position: Option<usize>,
+    stage: Option<EpochStage>,
+}
+
+impl Visit for ProgressFields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "i" {
+            self.position = Some(value as usize);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "stage" {
+            self.stage = EpochStage::from_name(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::BLSCurve, gadgets::ValidatorSetUpdate};
+    use algebra::bw6_761::Fr;
+    use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisMode};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn observes_each_epoch_stage() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let observed = events.clone();
+        observe_progress(
+            move |event| observed.lock().unwrap().push(event),
+            || {
+                let cs = ConstraintSystem::<Fr>::new_ref();
+                cs.set_mode(SynthesisMode::Setup);
+                ValidatorSetUpdate::<BLSCurve>::empty(3, 2, 0, None)
+                    .generate_constraints(cs)
+                    .unwrap();
+            },
+        );
+
+        let expected = (0..2)
+            .flat_map(|position| {
+                EpochStage::ALL
+                    .iter()
+                    .map(move |&stage| ProgressEvent { position, stage })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(*events.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn composes_with_other_layers() {
+        // stands for the embedder's own layer, which sees every event
+        struct CountingLayer(Arc<Mutex<usize>>);
+        impl<S: Subscriber> Layer<S> for CountingLayer {
+            fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let observed = events.clone();
+        let count = Arc::new(Mutex::new(0));
+        let progress = ProgressLayer::new(move |event| observed.lock().unwrap().push(event));
+        let subscriber = Registry::default()
+            .with(CountingLayer(count.clone()))
+            .with(progress);
+        tracing::subscriber::with_default(subscriber, || {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            cs.set_mode(SynthesisMode::Setup);
+            ValidatorSetUpdate::<BLSCurve>::empty(3, 1, 0, None)
+                .generate_constraints(cs)
+                .unwrap();
+        });
+
+        assert_eq!(events.lock().unwrap().len(), EpochStage::ALL.len());
+        assert!(*count.lock().unwrap() > EpochStage::ALL.len());
+    }
+}
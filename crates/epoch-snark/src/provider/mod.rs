@@ -0,0 +1,98 @@
+//! Epoch Transition Providers
+//!
+//! An `EpochTransitionProvider` hands over epoch transitions one at a time, so that the
+//! prover can take them from wherever they are stored (a Celo node, a database, files)
+//! instead of requiring the caller to gather them beforehand. `prove_from_provider` takes
+//! them one at a time as it builds the circuit's input.
+//!
+//! The providers of this crate are:
+//!
+//! - `std::vec::IntoIter<EpochTransition>`, for transitions which are already in memory
+//! - `rpc::RpcProvider` (with the `rpc` feature), which fetches them from a JSON-RPC endpoint
+use crate::{api::ProverError, epoch_block::EpochTransition};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use r1cs_core::SynthesisError;
+use std::{convert::Infallible, error::Error as StdError};
+use thiserror::Error;
+
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+#[derive(Debug, Error)]
+/// Error raised while proving the transitions of a provider
+pub enum ProviderError<E: StdError + 'static> {
+    #[error("Provider Error: {0}")]
+    Source(#[source] E),
+    #[error("the provider has no transitions")]
+    NoTransitions,
+    #[error("Synthesis Error: {0}")]
+    ZexeSynthesisError(#[from] SynthesisError),
+    #[error("Prover Error: {0}")]
+    Prover(#[from] ProverError),
+}
+
+impl<E: StdError + ToErrorCode + 'static> ToErrorCode for ProviderError<E> {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ProviderError::Source(e) => e.error_code(),
+            ProviderError::NoTransitions => ErrorCode::InvalidArgument,
+            ProviderError::ZexeSynthesisError(e) => e.error_code(),
+            ProviderError::Prover(e) => e.error_code(),
+        }
+    }
+}
+
+/// A source of consecutive epoch transitions
+pub trait EpochTransitionProvider {
+    /// The error raised when a transition cannot be obtained
+    type Error: StdError + 'static;
+
+    /// Returns the next transition, or `None` once there are no more transitions
+    fn next_transition(&mut self) -> Result<Option<EpochTransition>, Self::Error>;
+
+    /// Returns the next transitions, until there are no more transitions or `max` of them
+    /// were returned
+    fn take_transitions(&mut self, max: usize) -> Result<Vec<EpochTransition>, Self::Error> {
+        let mut transitions = vec![];
+        while transitions.len() < max {
+            match self.next_transition()? {
+                Some(transition) => transitions.push(transition),
+                None => break,
+            }
+        }
+        Ok(transitions)
+    }
+}
+
+impl EpochTransitionProvider for std::vec::IntoIter<EpochTransition> {
+    type Error = Infallible;
+
+    fn next_transition(&mut self) -> Result<Option<EpochTransition>, Self::Error> {
+        Ok(self.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch_block::EpochBlock;
+    use algebra::{bls12_377::G1Projective, ProjectiveCurve};
+    use bls_crypto::{Bitmap, Signature};
+
+    #[test]
+    fn takes_up_to_max_transitions() {
+        let transitions = (1..=3)
+            .map(|index| EpochTransition {
+                block: EpochBlock::new(index, 0, None, None, 0, 0, vec![]),
+                aggregate_signature: Signature::from(G1Projective::prime_subgroup_generator()),
+                bitmap: Bitmap::default(),
+            })
+            .collect::<Vec<_>>();
+        let mut provider = transitions.clone().into_iter();
+
+        assert_eq!(provider.take_transitions(2).unwrap(), &transitions[..2]);
+        assert_eq!(provider.take_transitions(2).unwrap(), &transitions[2..]);
+        assert_eq!(provider.next_transition().unwrap(), None);
+        assert!(provider.take_transitions(2).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+//! JSON-RPC provider of epoch transitions.
+//!
+//! `RpcProvider` fetches consecutive transitions from a JSON-RPC endpoint over HTTP. The
+//! endpoint must serve a method (`DEFAULT_METHOD` unless configured otherwise) which takes an
+//! epoch index and returns the RLP encoding (see `codec::rlp::encode_transition`) of the
+//! transition to that epoch as a `0x` prefixed hex string, or `null` if the epoch has not been
+//! reached yet. Celo nodes do not serve it themselves, it is served by the relayer (or any
+//! adapter in front of a node) which assembles the transitions from the node's epoch blocks.
+use super::EpochTransitionProvider;
+use crate::{
+    codec::{rlp::decode_transition, CodecError},
+    epoch_block::EpochTransition,
+};
+use bls_crypto::{ErrorCode, ToErrorCode};
+use serde_json::{json, Value};
+use std::io;
+use thiserror::Error;
+
+/// The method called by default to fetch a transition
+pub const DEFAULT_METHOD: &str = "celo_getEpochTransition";
+
+#[derive(Debug, Error)]
+/// Error raised while fetching a transition from a JSON-RPC endpoint
+pub enum RpcError {
+    #[error("HTTP Error: {0}")]
+    Http(#[from] Box<ureq::Error>),
+    #[error("I/O Error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("the endpoint returned error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    #[error("invalid JSON-RPC response: {0}")]
+    InvalidResponse(&'static str),
+    #[error("Hex Error: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Codec Error: {0}")]
+    Codec(#[from] CodecError),
+    #[error("expected the transition to epoch {expected}, got epoch {actual}")]
+    UnexpectedIndex { expected: u16, actual: u16 },
+}
+
+impl ToErrorCode for RpcError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            RpcError::Http(_) | RpcError::IoError(_) | RpcError::Rpc { .. } => ErrorCode::Io,
+            RpcError::InvalidResponse(_) | RpcError::Hex(_) | RpcError::Codec(_) => {
+                ErrorCode::Serialization
+            }
+            RpcError::UnexpectedIndex { .. } => ErrorCode::InvalidEpochData,
+        }
+    }
+}
+
+/// Fetches the transitions to consecutive epochs from a JSON-RPC endpoint, see the module
+/// documentation
+#[derive(Clone, Debug)]
+pub struct RpcProvider {
+    url: String,
+    method: String,
+    next_index: u16,
+    last_index: u16,
+    request_id: u64,
+}
+
+impl RpcProvider {
+    /// Fetches the transitions to the epochs `first_index..=last_index` from the endpoint at
+    /// `url`, stopping early at the first epoch which has not been reached yet
+    pub fn new(url: impl Into<String>, first_index: u16, last_index: u16) -> Self {
+        Self {
+            url: url.into(),
+            method: DEFAULT_METHOD.to_owned(),
+            next_index: first_index,
+            last_index,
+            request_id: 0,
+        }
+    }
+
+    /// Calls `method` instead of `DEFAULT_METHOD`
+    pub fn with_method(mut self, method: impl Into<String>) -> Self {
+        self.method = method.into();
+        self
+    }
+
+    /// The index of the epoch whose transition is fetched next
+    pub fn next_index(&self) -> u16 {
+        self.next_index
+    }
+
+    /// Calls the method for the epoch and returns its result
+    fn call(&mut self, index: u16) -> Result<Value, RpcError> {
+        self.request_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.request_id,
+            "method": self.method,
+            "params": [index],
+        });
+        let mut response: Value = ureq::post(&self.url)
+            .send_json(request)
+            .map_err(Box::new)?
+            .into_json()?;
+
+        if let Some(error) = response.get("error") {
+            return Err(RpcError::Rpc {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            });
+        }
+        response
+            .get_mut("result")
+            .map(Value::take)
+            .ok_or(RpcError::InvalidResponse("missing result"))
+    }
+}
+
+impl EpochTransitionProvider for RpcProvider {
+    type Error = RpcError;
+
+    fn next_transition(&mut self) -> Result<Option<EpochTransition>, RpcError> {
+        let index = self.next_index;
+        if index > self.last_index {
+            return Ok(None);
+        }
+        let encoded = match self.call(index)? {
+            Value::Null => return Ok(None),
+            Value::String(encoded) => encoded,
+            _ => return Err(RpcError::InvalidResponse("the result is not a string")),
+        };
+        let bytes = hex::decode(encoded.trim_start_matches("0x"))?;
+        let transition = decode_transition(&bytes)?;
+        if transition.block.index != index {
+            return Err(RpcError::UnexpectedIndex {
+                expected: index,
+                actual: transition.block.index,
+            });
+        }
+
+        // the last index may be u16::MAX, in which case the provider stops after it
+        match index.checked_add(1) {
+            Some(next_index) => self.next_index = next_index,
+            None => self.last_index = 0,
+        }
+        Ok(Some(transition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{rlp::encode_transition, test_helpers::transitions};
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    /// Serves the results to one request each, and returns the endpoint's URL along with the
+    /// bodies of the requests
+    fn serve(results: Vec<Value>) -> (String, JoinHandle<Vec<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = vec![];
+            for result in results {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+
+                let body =
+                    json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    fn encoded(transition: &EpochTransition) -> Value {
+        Value::String(format!("0x{}", hex::encode(encode_transition(transition))))
+    }
+
+    #[test]
+    fn fetches_consecutive_transitions() {
+        let mut transitions = transitions();
+        transitions.truncate(2);
+        // the helper's second transition already follows its first one
+        assert_eq!(transitions[1].block.index, transitions[0].block.index + 1);
+        let first_index = transitions[0].block.index;
+
+        let results = transitions.iter().map(encoded).chain(vec![Value::Null]);
+        let (url, server) = serve(results.collect());
+        let mut provider =
+            RpcProvider::new(url, first_index, first_index + 5).with_method("test_transition");
+        assert_eq!(provider.take_transitions(10).unwrap(), transitions);
+        assert_eq!(provider.next_index(), first_index + 2);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        for (i, request) in requests.iter().enumerate() {
+            assert_eq!(request["method"], "test_transition");
+            assert_eq!(request["params"], json!([first_index as usize + i]));
+        }
+    }
+
+    #[test]
+    fn rejects_unexpected_transitions() {
+        let transition = &transitions()[0];
+        let index = transition.block.index;
+
+        let (url, server) = serve(vec![encoded(transition), json!(17)]);
+        let mut provider = RpcProvider::new(url.clone(), index + 1, index + 1);
+        assert!(matches!(
+            provider.next_transition(),
+            Err(RpcError::UnexpectedIndex { expected, actual }) if expected == index + 1 && actual == index
+        ));
+        let mut provider = RpcProvider::new(url, index, index);
+        assert!(matches!(
+            provider.next_transition(),
+            Err(RpcError::InvalidResponse(_))
+        ));
+        server.join().unwrap();
+
+        // nothing is fetched past the last index
+        let mut provider = RpcProvider::new("http://127.0.0.1:1", 2, 1);
+        assert_eq!(provider.next_transition().unwrap(), None);
+    }
+}
@@ -0,0 +1,140 @@
+//! Randomness beacon outputs of a proven epoch range
+//!
+//! When the `randomness-beacon` feature is enabled, the circuit derives a randomness output
+//! from every epoch it constrains (including the dummy epochs which pad the proof to its
+//! maximum number of transitions) as `randomness_output(index, epoch_entropy)`, and exposes a
+//! commitment to the outputs as an extra public input, after the auxiliary data commitment if
+//! that is enabled as well. A `RandomnessBeacon` reproduces the outputs and their commitment
+//! natively, so that an application which verified a proof against it can consume each
+//! epoch's output as verified randomness.
+use crate::{
+    epoch_block::{personalized_blake2s, EpochBlock, EpochTransition},
+    scalars::OuterScalar,
+};
+use bls_crypto::{RANDOMNESS_COMMITMENT_DOMAIN, RANDOMNESS_DOMAIN};
+use bls_gadgets::utils::bytes_le_to_bits_le;
+
+/// Blake2s hash, personalized to `RANDOMNESS_DOMAIN`, of the epoch's index (2 bytes, LE)
+/// followed by its entropy. A missing entropy is `ENTROPY_BYTES` zeros.
+pub fn randomness_output(index: u16, epoch_entropy: Option<&[u8]>) -> [u8; 32] {
+    let mut input = index.to_le_bytes().to_vec();
+    match epoch_entropy {
+        Some(entropy) => input.extend_from_slice(entropy),
+        None => input.extend_from_slice(&[0u8; EpochBlock::ENTROPY_BYTES]),
+    }
+    personalized_blake2s(&input, RANDOMNESS_DOMAIN)
+}
+
+/// The index and randomness output of each epoch constrained by a proof, in circuit order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RandomnessBeacon {
+    outputs: Vec<(u16, [u8; 32])>,
+}
+
+impl RandomnessBeacon {
+    /// Lays out the transitions' outputs the same way as the prover does, i.e. with
+    /// `max_transitions - transitions.len()` dummy epochs (index 0, zero entropy) inserted
+    /// before the last transition
+    pub fn new(transitions: &[EpochTransition], max_transitions: usize) -> Self {
+        let mut outputs = transitions
+            .iter()
+            .map(|transition| {
+                let block = &transition.block;
+                let output = randomness_output(block.index, block.epoch_entropy.as_deref());
+                (block.index, output)
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(last) = outputs.pop() {
+            let num_dummies = max_transitions.saturating_sub(transitions.len());
+            let dummy = randomness_output(0, None);
+            outputs.extend((0..num_dummies).map(|_| (0, dummy)));
+            outputs.push(last);
+        }
+
+        Self { outputs }
+    }
+
+    /// Returns the randomness output of the epoch with the provided index, or `None` if the
+    /// epoch is not part of the committed range
+    pub fn output(&self, epoch_index: u16) -> Option<&[u8; 32]> {
+        // index 0 is reserved for dummy epochs
+        if epoch_index == 0 {
+            return None;
+        }
+        self.outputs
+            .iter()
+            .find(|(index, _)| *index == epoch_index)
+            .map(|(_, output)| output)
+    }
+
+    /// Serializes each epoch as its index (2 bytes, LE) followed by its randomness output.
+    /// This is the pre-image of the commitment.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.outputs.len() * (2 + 32));
+        for (index, output) in &self.outputs {
+            bytes.extend_from_slice(&index.to_le_bytes());
+            bytes.extend_from_slice(output);
+        }
+        bytes
+    }
+
+    /// Blake2 hash of the serialized outputs, personalized to `RANDOMNESS_COMMITMENT_DOMAIN`
+    pub fn commitment(&self) -> [u8; 32] {
+        personalized_blake2s(&self.to_bytes(), RANDOMNESS_COMMITMENT_DOMAIN)
+    }
+
+    /// The public inputs which the circuit appends for the commitment
+    pub fn public_inputs(&self) -> Vec<OuterScalar> {
+        OuterScalar::pack(&bytes_le_to_bits_le(&self.commitment(), 256))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap_commitment::test_helpers::transition_with_entropy;
+
+    #[test]
+    fn outputs_depend_on_index_and_entropy() {
+        let entropy = vec![1; EpochBlock::ENTROPY_BYTES];
+        let output = randomness_output(7, Some(&entropy));
+        assert_ne!(output, randomness_output(8, Some(&entropy)));
+        assert_ne!(output, randomness_output(7, None));
+        assert_eq!(
+            randomness_output(7, None),
+            randomness_output(7, Some(&[0; EpochBlock::ENTROPY_BYTES]))
+        );
+    }
+
+    #[test]
+    fn opens_outputs_and_pads_like_the_prover() {
+        let entropy = vec![2; EpochBlock::ENTROPY_BYTES];
+        let transitions = vec![
+            transition_with_entropy(7, vec![true; 3], Some(entropy.clone())),
+            transition_with_entropy(8, vec![true; 3], None),
+        ];
+        let beacon = RandomnessBeacon::new(&transitions, 3);
+
+        assert_eq!(beacon.outputs.len(), 3);
+        assert_eq!(beacon.outputs[1], (0, randomness_output(0, None)));
+        assert_eq!(
+            beacon.output(7),
+            Some(&randomness_output(7, Some(&entropy)))
+        );
+        assert_eq!(beacon.output(8), Some(&randomness_output(8, None)));
+        assert_eq!(beacon.output(9), None);
+        assert_eq!(beacon.output(0), None);
+        assert_eq!(beacon.to_bytes().len(), 3 * 34);
+        assert_eq!(beacon.public_inputs().len(), 1);
+
+        let other = vec![
+            transition_with_entropy(7, vec![true; 3], Some(entropy)),
+            transition_with_entropy(8, vec![true; 3], Some(vec![3; EpochBlock::ENTROPY_BYTES])),
+        ];
+        assert_ne!(
+            beacon.commitment(),
+            RandomnessBeacon::new(&other, 3).commitment()
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::api::{BWCurve, BWField};
+use algebra::{CanonicalSerialize, SerializationError, Zero};
+use groth16::{Proof, VerifyingKey};
+use std::fmt;
+
+/// Gas prices of the operations performed by an on-chain Groth16 verifier over BW6_761.
+///
+/// There is no canonical pricing for BW6_761 precompiles, so the prices of the target
+/// chain's precompiles must be provided by the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GasSchedule {
+    /// Base cost of a pairing check
+    pub pairing_base: u64,
+    /// Cost of each pair in a pairing check
+    pub pairing_per_pair: u64,
+    /// Cost of a G1 scalar multiplication
+    pub g1_mul: u64,
+    /// Cost of a G1 addition
+    pub g1_add: u64,
+    /// Cost of a zero calldata byte (4 after EIP-2028)
+    pub calldata_zero_byte: u64,
+    /// Cost of a non-zero calldata byte (16 after EIP-2028)
+    pub calldata_nonzero_byte: u64,
+}
+
+/// Size and verification cost of a proof, for evaluating changes to the public inputs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofReport {
+    /// Size of the compressed proof in bytes
+    pub proof_size: usize,
+    /// Size of the uncompressed proof in bytes, as passed to an on-chain verifier
+    pub proof_size_uncompressed: usize,
+    /// The number of public inputs expected by the verifying key
+    pub num_public_inputs: usize,
+    /// The number of pairs in the verifier's pairing check
+    pub num_pairings: usize,
+    /// Estimated gas of verifying the proof on-chain
+    pub estimated_gas: u64,
+}
+
+impl ProofReport {
+    /// The Groth16 verification equation `e(A, B) = e(alpha, beta) * e(L, gamma) * e(C, delta)`
+    /// is checked with a single product of pairings
+    pub const NUM_PAIRINGS: usize = 4;
+
+    /// Reports the sizes and the estimated verification gas. The gas accounts for the
+    /// calldata of the uncompressed proof and public inputs, the linear combination of the
+    /// public inputs with the verifying key (one multiplication and addition per input), and
+    /// the pairing check. Public inputs are assumed to be encoded as non-zero calldata bytes.
+    pub fn new(
+        vk: &VerifyingKey<BWCurve>,
+        proof: &Proof<BWCurve>,
+        schedule: &GasSchedule,
+    ) -> Result<Self, SerializationError> {
+        let mut compressed = vec![];
+        proof.serialize(&mut compressed)?;
+        let mut uncompressed = vec![];
+        proof.serialize_uncompressed(&mut uncompressed)?;
+
+        let num_public_inputs = vk.gamma_abc_g1.len().saturating_sub(1);
+        let public_input_bytes = num_public_inputs * BWField::zero().serialized_size();
+
+        let zero_bytes = uncompressed.iter().filter(|b| **b == 0).count() as u64;
+        let nonzero_bytes = (uncompressed.len() + public_input_bytes) as u64 - zero_bytes;
+        let calldata_gas = zero_bytes * schedule.calldata_zero_byte
+            + nonzero_bytes * schedule.calldata_nonzero_byte;
+        let inputs_gas = num_public_inputs as u64 * (schedule.g1_mul + schedule.g1_add);
+        let pairing_gas =
+            schedule.pairing_base + Self::NUM_PAIRINGS as u64 * schedule.pairing_per_pair;
+
+        Ok(Self {
+            proof_size: compressed.len(),
+            proof_size_uncompressed: uncompressed.len(),
+            num_public_inputs,
+            num_pairings: Self::NUM_PAIRINGS,
+            estimated_gas: calldata_gas + inputs_gas + pairing_gas,
+        })
+    }
+}
+
+impl fmt::Display for ProofReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "proof size: {} bytes ({} uncompressed)",
+            self.proof_size, self.proof_size_uncompressed
+        )?;
+        writeln!(f, "public inputs: {}", self.num_public_inputs)?;
+        writeln!(f, "pairings: {}", self.num_pairings)?;
+        write!(f, "estimated gas: {}", self.estimated_gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{bw6_761::G1Affine, AffineCurve};
+
+    const SCHEDULE: GasSchedule = GasSchedule {
+        pairing_base: 100,
+        pairing_per_pair: 10,
+        g1_mul: 5,
+        g1_add: 1,
+        calldata_zero_byte: 0,
+        calldata_nonzero_byte: 0,
+    };
+
+    #[test]
+    fn reports_verification_cost() {
+        let mut vk = VerifyingKey::<BWCurve>::default();
+        vk.gamma_abc_g1 = vec![G1Affine::prime_subgroup_generator(); 3];
+        let proof = Proof::<BWCurve>::default();
+
+        let report = ProofReport::new(&vk, &proof, &SCHEDULE).unwrap();
+        assert_eq!(report.num_public_inputs, 2);
+        assert_eq!(report.num_pairings, 4);
+        assert_eq!(report.estimated_gas, 100 + 4 * 10 + 2 * (5 + 1));
+        assert!(report.proof_size < report.proof_size_uncompressed);
+
+        // every calldata byte is accounted for
+        let schedule = GasSchedule {
+            calldata_zero_byte: 1,
+            calldata_nonzero_byte: 1,
+            ..SCHEDULE
+        };
+        let with_calldata = ProofReport::new(&vk, &proof, &schedule).unwrap();
+        assert_eq!(
+            with_calldata.estimated_gas - report.estimated_gas,
+            (report.proof_size_uncompressed + 2 * BWField::zero().serialized_size()) as u64
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Per-validator signed epoch counts of a proven epoch range
+//!
+//! A `RewardVector` counts, for each validator, the epochs of a `BitmapCommitment` whose
+//! bitmap it signed, skipping the dummy epochs which pad the proof. The `RewardAttestation`
+//! circuit proves that the counts were computed from the bitmaps behind a bitmap commitment,
+//! and exposes a hash of the counts as a public input, so that a bridge chain which verified
+//! an epochs proof with the `bitmap-commitment` feature can distribute rewards according to
+//! the counts without trusting whoever relays them. See `prove_reward_attestation`.
+use crate::{
+    bitmap_commitment::BitmapCommitment, epoch_block::personalized_blake2s, scalars::OuterScalar,
+};
+use bls_crypto::{ErrorCode, ToErrorCode, REWARD_DOMAIN};
+use bls_gadgets::utils::bytes_le_to_bits_le;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+/// Error raised when counting the signed epochs of a range
+pub enum RewardVectorError {
+    #[error("the signed epochs of validator {0} do not fit in a u16")]
+    CountOverflow(usize),
+}
+
+impl ToErrorCode for RewardVectorError {
+    fn error_code(&self) -> ErrorCode {
+        ErrorCode::InvalidArgument
+    }
+}
+
+/// The number of epochs of each validator's bitmap which it signed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RewardVector {
+    counts: Vec<u16>,
+}
+
+impl RewardVector {
+    /// Counts the signed epochs of the first `num_validators` validators over the
+    /// committed epochs, excluding the dummy epochs (index 0). Fails if a validator signed
+    /// more epochs than fit in a u16, which the counts are serialized as.
+    pub fn new(
+        bitmaps: &BitmapCommitment,
+        num_validators: usize,
+    ) -> Result<Self, RewardVectorError> {
+        let mut counts = vec![0u16; num_validators];
+        for (_, bitmap) in bitmaps.bitmaps().iter().filter(|(index, _)| *index != 0) {
+            for (validator, (count, signed)) in counts.iter_mut().zip(bitmap.iter()).enumerate() {
+                *count = count
+                    .checked_add(*signed as u16)
+                    .ok_or(RewardVectorError::CountOverflow(validator))?;
+            }
+        }
+        Ok(Self { counts })
+    }
+
+    /// Wraps counts which were obtained elsewhere, e.g. relayed to a bridge chain
+    pub fn from_counts(counts: Vec<u16>) -> Self {
+        Self { counts }
+    }
+
+    /// The number of signed epochs of each validator, in validator order
+    pub fn counts(&self) -> &[u16] {
+        &self.counts
+    }
+
+    /// Serializes each count as 2 bytes (LE). This is the pre-image of the commitment.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.counts
+            .iter()
+            .flat_map(|count| count.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    /// Blake2 hash of the serialized counts, personalized to `REWARD_DOMAIN`
+    pub fn commitment(&self) -> [u8; 32] {
+        personalized_blake2s(&self.to_bytes(), REWARD_DOMAIN)
+    }
+
+    /// The public inputs which the reward attestation circuit appends after the bitmap
+    /// commitment
+    pub fn public_inputs(&self) -> Vec<OuterScalar> {
+        OuterScalar::pack(&bytes_le_to_bits_le(&self.commitment(), 256))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap_commitment::test_helpers::transition;
+
+    #[test]
+    fn counts_signed_epochs_without_dummies() {
+        let transitions = vec![
+            transition(3, vec![true, false, true]),
+            transition(4, vec![false, false, true]),
+            transition(5, vec![true, false, true]),
+        ];
+        // the dummy epochs are fully signed, but must not be counted
+        let bitmaps = BitmapCommitment::new(&transitions, 5, 3);
+        let rewards = RewardVector::new(&bitmaps, 3).unwrap();
+        assert_eq!(rewards.counts(), &[2, 0, 3]);
+        assert_eq!(rewards.to_bytes(), vec![2, 0, 0, 0, 3, 0]);
+        assert_eq!(rewards, RewardVector::from_counts(vec![2, 0, 3]));
+        assert_eq!(rewards.public_inputs().len(), 1);
+
+        let other = RewardVector::from_counts(vec![2, 1, 3]);
+        assert_ne!(rewards.commitment(), other.commitment());
+    }
+
+    #[test]
+    fn rejects_counts_which_overflow() {
+        // the second validator signs every epoch of a range of 2^16 epochs
+        let transitions = (0..=u16::MAX as usize)
+            .map(|i| transition((i % u16::MAX as usize) as u16 + 1, vec![false, true]))
+            .collect::<Vec<_>>();
+        let bitmaps = BitmapCommitment::new(&transitions, transitions.len(), 2);
+        assert_eq!(
+            RewardVector::new(&bitmaps, 2).unwrap_err(),
+            RewardVectorError::CountOverflow(1)
+        );
+        let bitmaps = BitmapCommitment::new(&transitions[1..], transitions.len() - 1, 2);
+        assert_eq!(
+            RewardVector::new(&bitmaps, 2).unwrap().counts(),
+            &[0, u16::MAX]
+        );
+    }
+}
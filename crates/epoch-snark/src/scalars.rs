@@ -0,0 +1,158 @@
+//! # Constraint Field Scalars
+//!
+//! Epoch proofs involve two scalar fields which are easy to mix up, as both are called `Fr`:
+//!
+//! - BLS12-377's scalar field, the constraint field of the CRH->XOF helper circuit, whose
+//!   proof is verified inside the epochs circuit (the inner circuit)
+//! - BW6-761's scalar field, the constraint field of the epochs circuit and of the other
+//!   circuits proven over BW6-761 (the outer circuits). It is also BLS12-377's base field.
+//!
+//! `InnerScalar` and `OuterScalar` wrap the elements of each field, so that packing bits
+//! over the wrong field, or passing the public inputs of one circuit to the other, is a type
+//! error. The functions which compute or take public inputs (e.g. `public_inputs`,
+//! `BitmapCommitment::public_inputs` or `HashToBits::public_inputs`) use them accordingly.
+//! The conversions between them embed an inner scalar in the outer field, which always
+//! fits, and check that an outer scalar fits in the inner field.
+use crate::gadgets::pack;
+
+use algebra::{bls12_377, bw6_761, FpParameters, PrimeField};
+use algebra_core::biginteger::{BigInteger256, BigInteger384};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+// BW6-761's scalar field must be BLS12-377's base field, so that BLS12-377's points can be
+// constrained natively in the outer circuits
+const _: fn(bls12_377::Fq) -> bw6_761::Fr = |base| base;
+
+// every inner scalar must fit in the outer field, see `From<InnerScalar> for OuterScalar`
+const _: () = [()][(<bls12_377::FrParameters as FpParameters>::MODULUS_BITS
+    > <bw6_761::FrParameters as FpParameters>::CAPACITY) as usize];
+
+#[derive(Debug, Error, PartialEq)]
+/// Error raised when converting between the scalars
+pub enum ScalarError {
+    #[error("the scalar does not fit in BLS12-377's scalar field")]
+    OutOfRange,
+}
+
+macro_rules! scalar {
+    ($(#[$doc:meta])* $name:ident, $field:ty, $params:ty) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+        pub struct $name(pub $field);
+
+        impl $name {
+            /// The number of bits packed in each scalar
+            pub const CAPACITY: usize = <$params as FpParameters>::CAPACITY as usize;
+
+            /// Packs the bits in chunks of `CAPACITY` bits, matching the public inputs
+            /// which `MultipackGadget` allocates for them
+            pub fn pack(bits: &[bool]) -> Vec<Self> {
+                Self::pack_fields(bits).into_iter().map(Self).collect()
+            }
+
+            /// Same as `pack`, but returns the field elements, e.g. to verify a proof
+            pub fn pack_fields(bits: &[bool]) -> Vec<$field> {
+                pack::<$field, $params>(bits)
+                    .expect("bits are packed in chunks which fit in the field")
+            }
+
+            /// The field elements of the scalars, e.g. to verify a proof with zexe
+            pub fn to_fields(scalars: &[Self]) -> Vec<$field> {
+                scalars.iter().map(|scalar| scalar.0).collect()
+            }
+        }
+
+        impl From<$field> for $name {
+            fn from(scalar: $field) -> Self {
+                Self(scalar)
+            }
+        }
+
+        impl From<$name> for $field {
+            fn from(scalar: $name) -> Self {
+                scalar.0
+            }
+        }
+    };
+}
+
+scalar!(
+    /// An element of BLS12-377's scalar field, the constraint field of the inner circuit
+    InnerScalar,
+    bls12_377::Fr,
+    bls12_377::FrParameters
+);
+
+scalar!(
+    /// An element of BW6-761's scalar field, the constraint field of the outer circuits
+    OuterScalar,
+    bw6_761::Fr,
+    bw6_761::FrParameters
+);
+
+impl From<InnerScalar> for OuterScalar {
+    fn from(scalar: InnerScalar) -> Self {
+        let mut limbs = [0; 6];
+        limbs[..4].copy_from_slice(&scalar.0.into_repr().0);
+        OuterScalar(
+            bw6_761::Fr::from_repr(BigInteger384(limbs))
+                .expect("inner scalars fit in the outer field"),
+        )
+    }
+}
+
+impl TryFrom<OuterScalar> for InnerScalar {
+    type Error = ScalarError;
+
+    fn try_from(scalar: OuterScalar) -> Result<Self, ScalarError> {
+        let limbs = scalar.0.into_repr().0;
+        if limbs[4..].iter().any(|limb| *limb != 0) {
+            return Err(ScalarError::OutOfRange);
+        }
+        let mut inner = [0; 4];
+        inner.copy_from_slice(&limbs[..4]);
+        bls12_377::Fr::from_repr(BigInteger256(inner))
+            .map(InnerScalar)
+            .ok_or(ScalarError::OutOfRange)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{Field, UniformRand};
+
+    #[test]
+    fn packs_over_each_field() {
+        let bits = vec![true; InnerScalar::CAPACITY + 1];
+        assert_eq!(InnerScalar::pack(&bits).len(), 2);
+        assert_eq!(OuterScalar::pack(&bits).len(), 1);
+        assert_eq!(
+            OuterScalar::pack(&[true, false, true]),
+            vec![OuterScalar(bw6_761::Fr::from(5u8))]
+        );
+    }
+
+    #[test]
+    fn embeds_inner_scalars() {
+        let rng = &mut rand::thread_rng();
+        for _ in 0..10 {
+            let inner = InnerScalar(bls12_377::Fr::rand(rng));
+            let outer = OuterScalar::from(inner);
+            assert_eq!(
+                outer.0.into_repr().as_ref()[..4],
+                inner.0.into_repr().as_ref()[..]
+            );
+            assert_eq!(InnerScalar::try_from(outer), Ok(inner));
+        }
+
+        // the inner modulus, and anything above, is out of range
+        let large = OuterScalar(-bw6_761::Fr::one());
+        assert_eq!(InnerScalar::try_from(large), Err(ScalarError::OutOfRange));
+        let mut limbs = [0; 6];
+        limbs[..4].copy_from_slice(&<bls12_377::FrParameters as FpParameters>::MODULUS.0);
+        let modulus = OuterScalar(bw6_761::Fr::from_repr(BigInteger384(limbs)).unwrap());
+        assert_eq!(InnerScalar::try_from(modulus), Err(ScalarError::OutOfRange));
+    }
+}
@@ -0,0 +1,253 @@
+//! Epoch Transition Simulator
+//!
+//! Generates plausible sequences of signed epoch transitions from a seed, along with the
+//! public inputs which a proof over them would be verified against. The same seed and
+//! configuration always produce the same scenario, so they can be shared with other
+//! implementations for differential testing.
+use crate::{
+    api::{public_inputs, VerificationError},
+    epoch_block::{EpochBlock, EpochTransition},
+    gadgets::bft_maximum_non_signers,
+    scalars::OuterScalar,
+};
+use bls_crypto::{Bitmap, ErrorCode, PrivateKey, PublicKey, Signature, ToErrorCode};
+use rand::{rngs::StdRng, seq::index::sample, Rng, SeedableRng};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+/// Error raised while configuring or running a simulation
+pub enum SimulationError {
+    #[error("the churn must be a probability between 0 and 1, got {0}")]
+    InvalidChurn(f64),
+    #[error(transparent)]
+    Verification(#[from] VerificationError),
+}
+
+impl ToErrorCode for SimulationError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            SimulationError::InvalidChurn(_) => ErrorCode::InvalidArgument,
+            SimulationError::Verification(e) => e.error_code(),
+        }
+    }
+}
+
+/// Parameters of a simulated sequence of epochs
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationConfig {
+    /// The number of validators in each epoch
+    pub num_validators: usize,
+    /// The number of transitions after the initial epoch
+    pub num_epochs: usize,
+    /// The maximum number of validators which may not sign a transition
+    pub maximum_non_signers: u32,
+    /// The probability that each validator is replaced by a new one on each epoch
+    pub churn: f64,
+}
+
+impl SimulationConfig {
+    /// Instantiates a configuration with the BFT maximum number of non-signers and
+    /// 10% validator churn per epoch
+    pub fn new(num_validators: usize, num_epochs: usize) -> Self {
+        Self {
+            num_validators,
+            num_epochs,
+            maximum_non_signers: bft_maximum_non_signers(num_validators as u32),
+            churn: 0.1,
+        }
+    }
+
+    /// Sets the maximum number of non-signers of each transition
+    pub fn with_maximum_non_signers(mut self, maximum_non_signers: u32) -> Self {
+        self.maximum_non_signers = maximum_non_signers;
+        self
+    }
+
+    /// Sets the probability that each validator is replaced on each epoch, which must be
+    /// between 0 and 1
+    pub fn with_churn(mut self, churn: f64) -> Result<Self, SimulationError> {
+        if !(0.0..=1.0).contains(&churn) {
+            return Err(SimulationError::InvalidChurn(churn));
+        }
+        self.churn = churn;
+        Ok(self)
+    }
+}
+
+/// A simulated sequence of epochs
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// The initial epoch
+    pub first_epoch: EpochBlock,
+    /// The signed transitions following the initial epoch
+    pub transitions: Vec<EpochTransition>,
+    /// The last epoch, i.e. the block of the last transition
+    pub last_epoch: EpochBlock,
+    /// The public inputs of a proof from the first to the last epoch, as returned by
+    /// `public_inputs`
+    pub public_inputs: Vec<OuterScalar>,
+}
+
+/// Generates a sequence of epochs according to the configuration.
+///
+/// On each epoch, every validator is replaced with probability `churn`, and between 0 and
+/// `maximum_non_signers` randomly chosen validators of the previous epoch do not sign. Each
+/// epoch's parent entropy is the previous epoch's entropy. Fails if the churn is not
+/// between 0 and 1.
+pub fn simulate(config: &SimulationConfig, seed: u64) -> Result<Simulation, SimulationError> {
+    // the churn is a public field, so it is checked again
+    if !(0.0..=1.0).contains(&config.churn) {
+        return Err(SimulationError::InvalidChurn(config.churn));
+    }
+    let rng = &mut StdRng::seed_from_u64(seed);
+    let maximum_non_signers = (config.maximum_non_signers as usize).min(config.num_validators);
+
+    let mut validators = (0..config.num_validators)
+        .map(|_| PrivateKey::generate(rng))
+        .collect::<Vec<_>>();
+    let mut entropy = random_entropy(rng);
+    let first_epoch = EpochBlock::new(
+        0,
+        0,
+        Some(entropy.clone()),
+        Some(random_entropy(rng)),
+        config.maximum_non_signers,
+        config.num_validators,
+        public_keys(&validators),
+    );
+
+    let mut transitions = Vec::with_capacity(config.num_epochs);
+    for index in 1..=config.num_epochs {
+        let signers = validators.clone();
+        for validator in validators.iter_mut() {
+            if rng.gen_bool(config.churn) {
+                *validator = PrivateKey::generate(rng);
+            }
+        }
+
+        let parent_entropy = entropy;
+        entropy = random_entropy(rng);
+        let block = EpochBlock::new(
+            index as u16,
+            rng.gen_range(0, 4),
+            Some(entropy.clone()),
+            Some(parent_entropy),
+            config.maximum_non_signers,
+            config.num_validators,
+            public_keys(&validators),
+        );
+
+        let mut bitmap = Bitmap::full(config.num_validators);
+        let num_non_signers = rng.gen_range(0, maximum_non_signers + 1);
+        for i in sample(rng, config.num_validators, num_non_signers).iter() {
+            bitmap.set(i, false);
+        }
+
+        let hash = block.hash_to_g1_cip22()?;
+        let aggregate_signature = Signature::aggregate(
+            signers
+                .iter()
+                .zip(&bitmap)
+                .filter(|(_, signed)| **signed)
+                .map(|(signer, _)| signer.sign_exact(&hash)),
+        );
+
+        transitions.push(EpochTransition {
+            block,
+            aggregate_signature,
+            bitmap,
+        });
+    }
+
+    let last_epoch = transitions
+        .last()
+        .map(|transition| transition.block.clone())
+        .unwrap_or_else(|| first_epoch.clone());
+    let public_inputs = public_inputs(&first_epoch, &last_epoch)?;
+
+    Ok(Simulation {
+        first_epoch,
+        transitions,
+        last_epoch,
+        public_inputs,
+    })
+}
+
+fn public_keys(validators: &[PrivateKey]) -> Vec<PublicKey> {
+    validators.iter().map(|key| key.to_public()).collect()
+}
+
+fn random_entropy<R: Rng>(rng: &mut R) -> Vec<u8> {
+    (0..EpochBlock::ENTROPY_BYTES).map(|_| rng.gen()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls_crypto::hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22;
+
+    #[test]
+    fn deterministic_per_seed() {
+        let config = SimulationConfig::new(4, 3);
+        let first = simulate(&config, 7).unwrap();
+        let second = simulate(&config, 7).unwrap();
+        let other = simulate(&config, 8).unwrap();
+
+        assert_eq!(first.transitions, second.transitions);
+        assert_eq!(first.public_inputs, second.public_inputs);
+        assert_ne!(first.public_inputs, other.public_inputs);
+    }
+
+    #[test]
+    fn rejects_invalid_churn() {
+        for churn in &[-0.1, 1.5, f64::NAN] {
+            assert!(matches!(
+                SimulationConfig::new(4, 3).with_churn(*churn),
+                Err(SimulationError::InvalidChurn(_))
+            ));
+            let mut config = SimulationConfig::new(4, 3);
+            config.churn = *churn;
+            assert!(matches!(
+                simulate(&config, 7),
+                Err(SimulationError::InvalidChurn(_))
+            ));
+        }
+        SimulationConfig::new(4, 3).with_churn(1.0).unwrap();
+    }
+
+    #[test]
+    fn transitions_are_valid() {
+        let config = SimulationConfig::new(7, 3).with_churn(0.5).unwrap();
+        let simulation = simulate(&config, 1).unwrap();
+        assert_eq!(simulation.transitions.len(), 3);
+        assert_eq!(simulation.last_epoch, simulation.transitions[2].block);
+
+        let mut previous = &simulation.first_epoch;
+        for transition in &simulation.transitions {
+            let block = &transition.block;
+            assert_eq!(block.index, previous.index + 1);
+            assert_eq!(block.parent_entropy, previous.epoch_entropy);
+
+            let non_signers = transition.bitmap.complement().popcount();
+            assert!(non_signers <= config.maximum_non_signers as usize);
+
+            let signers = previous
+                .new_public_keys
+                .iter()
+                .zip(&transition.bitmap)
+                .filter(|(_, signed)| **signed)
+                .map(|(pk, _)| pk);
+            let (message, extra_data) = block.encode_inner_to_bytes_cip22().unwrap();
+            PublicKey::aggregate(signers)
+                .verify(
+                    &message,
+                    &extra_data,
+                    &transition.aggregate_signature,
+                    &*COMPOSITE_HASH_TO_G1_CIP22,
+                )
+                .unwrap();
+
+            previous = block;
+        }
+    }
+}
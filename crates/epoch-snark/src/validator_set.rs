@@ -0,0 +1,180 @@
+//! Canonical Validator Sets
+//!
+//! A `ValidatorSet` is a list of validator public keys without duplicates, in canonical
+//! order: ascending by their compressed encoding (see `encode_public_key`), compared as big
+//! endian numbers. Repeating a key would give its holder several votes in the aggregate
+//! public key, so epochs built from a `ValidatorSet` cannot carry such rogue aggregation
+//! tricks. Since the order is canonical, a key's index in the set (i.e. its position in the
+//! signed bitmaps) is determined by the set alone and can be found with a binary search.
+//!
+//! With the `canonical-validators` feature, the epochs circuit enforces the same invariant
+//! on the public keys of every epoch with a non-zero index.
+use crate::encoding::{encode_public_key, EncodingError};
+use bls_crypto::{ErrorCode, PublicKey, ToErrorCode};
+use std::{collections::HashSet, convert::TryFrom};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+/// Error raised when public keys do not form a canonical validator set
+pub enum ValidatorSetError {
+    #[error("Encoding Error: {0}")]
+    EncodingError(#[from] EncodingError),
+    #[error("the public key at index {0} is a duplicate")]
+    DuplicateKey(usize),
+    #[error("the public key at index {0} is not in canonical order")]
+    Unordered(usize),
+}
+
+impl ToErrorCode for ValidatorSetError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ValidatorSetError::EncodingError(e) => e.error_code(),
+            _ => ErrorCode::InvalidEpochData,
+        }
+    }
+}
+
+/// Validator public keys without duplicates in canonical order, see the module documentation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorSet {
+    public_keys: Vec<PublicKey>,
+    /// The compressed encoding of each public key, which defines their order
+    encodings: Vec<Vec<bool>>,
+}
+
+impl ValidatorSet {
+    /// Wraps the public keys, which must already be in canonical order without duplicates
+    pub fn new(public_keys: Vec<PublicKey>) -> Result<Self, ValidatorSetError> {
+        let encodings = public_keys
+            .iter()
+            .map(encode_public_key)
+            .collect::<Result<Vec<_>, _>>()?;
+        // report duplicates as such, even when they are not adjacent
+        let mut seen = HashSet::with_capacity(encodings.len());
+        if let Some(i) = encodings.iter().position(|encoding| !seen.insert(encoding)) {
+            return Err(ValidatorSetError::DuplicateKey(i));
+        }
+        if let Some(i) = (1..encodings.len()).find(|i| encodings[i - 1] > encodings[*i]) {
+            return Err(ValidatorSetError::Unordered(i));
+        }
+        Ok(Self {
+            public_keys,
+            encodings,
+        })
+    }
+
+    /// Sorts the public keys in canonical order. Fails if they contain duplicates.
+    pub fn canonicalize(public_keys: Vec<PublicKey>) -> Result<Self, ValidatorSetError> {
+        let mut keys = public_keys
+            .into_iter()
+            .map(|public_key| Ok((encode_public_key(&public_key)?, public_key)))
+            .collect::<Result<Vec<_>, EncodingError>>()?;
+        keys.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if let Some(i) = (1..keys.len()).find(|i| keys[i - 1].0 == keys[*i].0) {
+            return Err(ValidatorSetError::DuplicateKey(i));
+        }
+        let (encodings, public_keys) = keys.into_iter().unzip();
+        Ok(Self {
+            public_keys,
+            encodings,
+        })
+    }
+
+    /// The public keys, in canonical order
+    pub fn public_keys(&self) -> &[PublicKey] {
+        &self.public_keys
+    }
+
+    /// The number of validators
+    pub fn len(&self) -> usize {
+        self.public_keys.len()
+    }
+
+    /// Whether the set has no validators
+    pub fn is_empty(&self) -> bool {
+        self.public_keys.is_empty()
+    }
+
+    /// Returns the index of the public key in the set, if it is a member
+    pub fn index_of(&self, public_key: &PublicKey) -> Option<usize> {
+        let encoding = encode_public_key(public_key).ok()?;
+        self.encodings.binary_search(&encoding).ok()
+    }
+
+    /// Returns the public keys
+    pub fn into_public_keys(self) -> Vec<PublicKey> {
+        self.public_keys
+    }
+}
+
+impl TryFrom<Vec<PublicKey>> for ValidatorSet {
+    type Error = ValidatorSetError;
+
+    fn try_from(public_keys: Vec<PublicKey>) -> Result<Self, Self::Error> {
+        Self::new(public_keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use algebra::{bls12_377::G2Projective, ProjectiveCurve, UniformRand};
+
+    fn public_keys(num: usize) -> Vec<PublicKey> {
+        let rng = &mut rand::thread_rng();
+        (0..num)
+            .map(|_| PublicKey::from(G2Projective::rand(rng)))
+            .collect()
+    }
+
+    #[test]
+    fn canonicalizes_keys() {
+        let keys = public_keys(6);
+        let set = ValidatorSet::canonicalize(keys.clone()).unwrap();
+        assert_eq!(set.len(), 6);
+        for key in &keys {
+            let index = set.index_of(key).unwrap();
+            assert_eq!(&set.public_keys()[index], key);
+        }
+        assert_eq!(set.index_of(&public_keys(1)[0]), None);
+
+        // the canonical order is accepted as is, and is the same for any permutation
+        let canonical = set.clone().into_public_keys();
+        assert_eq!(ValidatorSet::new(canonical.clone()).unwrap(), set);
+        let mut reversed = keys;
+        reversed.reverse();
+        assert_eq!(ValidatorSet::canonicalize(reversed).unwrap(), set);
+
+        let mut swapped = canonical;
+        swapped.swap(2, 3);
+        assert!(matches!(
+            ValidatorSet::new(swapped),
+            Err(ValidatorSetError::Unordered(3))
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let mut keys = ValidatorSet::canonicalize(public_keys(4))
+            .unwrap()
+            .into_public_keys();
+        keys.push(keys[1].clone());
+        assert!(matches!(
+            ValidatorSet::new(keys.clone()),
+            Err(ValidatorSetError::DuplicateKey(4))
+        ));
+        assert!(matches!(
+            ValidatorSet::canonicalize(keys),
+            Err(ValidatorSetError::DuplicateKey(2))
+        ));
+
+        // the same point in different projective coordinates is the same key
+        let key = public_keys(1).remove(0);
+        let doubled = PublicKey::from(key.as_ref().double());
+        let aggregated = PublicKey::aggregate(&[key.clone(), key]);
+        assert!(matches!(
+            ValidatorSet::new(vec![doubled, aggregated]),
+            Err(ValidatorSetError::DuplicateKey(1))
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+use bls_crypto::Bitmap;
+use epoch_snark::prove_check_only;
+
+mod fixtures;
+use fixtures::generate_test_data;
+
+#[test]
+fn checks_witness_without_proving() {
+    let num_transitions = 2;
+    let faults = 1;
+    let num_validators = 3 * faults + 1;
+
+    let (first_epoch, mut transitions, _) =
+        generate_test_data(num_validators, faults, num_transitions);
+
+    let unsatisfied = prove_check_only(
+        num_validators as u32,
+        &first_epoch,
+        &transitions,
+        num_transitions + 1,
+    )
+    .unwrap();
+    assert!(unsatisfied.is_none());
+
+    // more validators than allowed did not sign the first transition
+    transitions[0].bitmap = Bitmap::new(num_validators);
+    let unsatisfied = prove_check_only(
+        num_validators as u32,
+        &first_epoch,
+        &transitions,
+        num_transitions + 1,
+    )
+    .unwrap()
+    .unwrap();
+    assert!(unsatisfied.path.is_some());
+}
@@ -0,0 +1,29 @@
+// The snapshot is of the circuit with the default features, since the optional ones
+// alter the circuit
+#![cfg(not(any(
+    feature = "bft-threshold",
+    feature = "bitmap-commitment",
+    feature = "epoch-aux-data",
+    feature = "randomness-beacon",
+    feature = "chain-binding",
+    feature = "circuit-version",
+    feature = "blinded-validators",
+    feature = "compressed-public-inputs",
+    feature = "entropy-256",
+    feature = "canonical-validators"
+)))]
+use epoch_snark::{circuit_fingerprint, registry::CircuitShape};
+
+#[test]
+// Fails if the circuit changed, which invalidates the deployed verifying keys, or if the
+// snapshot is missing. If the change is intended, run the test again with
+// `UPDATE_CIRCUIT_SNAPSHOTS=1` and commit the snapshot.
+fn circuit_matches_snapshot() {
+    let fingerprint = circuit_fingerprint(CircuitShape::new(3, 2)).unwrap();
+    fingerprint
+        .check_snapshot(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/snapshots/circuit_3_validators_2_epochs.txt"
+        ))
+        .unwrap();
+}
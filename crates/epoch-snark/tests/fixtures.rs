@@ -74,7 +74,7 @@ pub fn generate_test_data(
         let transition = EpochTransition {
             block,
             aggregate_signature: asig,
-            bitmap: bitmap_epoch.to_vec(),
+            bitmap: bitmap_epoch.to_vec().into(),
         };
         transitions.push(transition);
     }
@@ -100,6 +100,9 @@ fn generate_block(
         maximum_non_signers: non_signers as u32,
         maximum_validators: max_validators,
         new_public_keys: pubkeys.to_vec(),
+        aux_data: None,
+        chain_id: None,
+        validator_blinding: None,
     }
 }
 
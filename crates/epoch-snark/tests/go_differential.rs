@@ -0,0 +1,250 @@
+// Differential tests against the Go implementation of celo-blockchain, which validators run
+// in production. They compare signatures, hashes to G1, epoch encodings and aggregates on
+// randomized inputs, so that the two implementations cannot drift apart unnoticed.
+//
+// The tests talk to a helper program, given by the `CELO_GO_HELPER` environment variable,
+// which wraps celo-blockchain's `crypto/bls` package. The helper reads one JSON request per
+// line on stdin and writes one JSON response per line on stdout, either `{"result": {..}}`
+// or `{"error": ".."}`. All byte strings are `0x` prefixed hex strings, and keys, signatures
+// and points use the compressed `CanonicalSerialize` encoding. The requests are
+//
+// - `{"op": "sign", "private_key", "message", "extra_data"}`, which signs with the CIP22
+//   composite hasher and returns `{"public_key", "signature"}`
+// - `{"op": "hash_to_g1", "message", "extra_data"}`, which hashes to G1 with the CIP22
+//   composite hasher in the signature domain and returns `{"point"}`
+// - `{"op": "aggregate", "public_keys", "signatures"}`, which returns the aggregates
+//   `{"public_key", "signature"}`
+// - `{"op": "encode_epoch", "index", "round", "epoch_entropy", "parent_entropy",
+//   "maximum_non_signers", "maximum_validators", "public_keys"}`, which returns the CIP22
+//   encoding that validators sign for the epoch, `{"message", "extra_data"}`
+//
+// Run them with
+//
+//   CELO_GO_HELPER=/path/to/helper cargo test --features go-differential --test go_differential
+//
+// Set `GO_DIFFERENTIAL_SEED` to replay the inputs of a failed run (the seed of every run is
+// printed) and `GO_DIFFERENTIAL_ITERATIONS` to change the number of inputs per test.
+#![cfg(feature = "go-differential")]
+use algebra::{bls12_377::G1Projective, CanonicalSerialize, ProjectiveCurve};
+use bls_crypto::{
+    hash_to_curve::try_and_increment_cip22::COMPOSITE_HASH_TO_G1_CIP22, HashToCurve, PrivateKey,
+    PublicKey, Signature, SIG_DOMAIN,
+};
+use epoch_snark::EpochBlock;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde_json::{json, Value};
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+const HELPER_VAR: &str = "CELO_GO_HELPER";
+const SEED_VAR: &str = "GO_DIFFERENTIAL_SEED";
+const ITERATIONS_VAR: &str = "GO_DIFFERENTIAL_ITERATIONS";
+const DEFAULT_ITERATIONS: usize = 32;
+
+/// A running helper process
+struct GoHelper {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl GoHelper {
+    fn spawn() -> Self {
+        let path = env::var(HELPER_VAR).unwrap_or_else(|_| {
+            panic!(
+                "{} must be set to the Go helper when the go-differential feature is enabled",
+                HELPER_VAR
+            )
+        });
+        let mut child = Command::new(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| panic!("could not run the Go helper {}: {}", path, e));
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Self {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+
+    /// Sends the request and returns the result of its response
+    fn call(&mut self, request: Value) -> Value {
+        writeln!(self.stdin, "{}", request).expect("could not write to the Go helper");
+        self.stdin
+            .flush()
+            .expect("could not write to the Go helper");
+        let mut line = String::new();
+        self.stdout
+            .read_line(&mut line)
+            .expect("could not read from the Go helper");
+        let mut response: Value = serde_json::from_str(&line)
+            .unwrap_or_else(|e| panic!("invalid response {:?}: {}", line, e));
+        if let Some(error) = response.get("error") {
+            panic!("the Go helper failed on {}: {}", request, error);
+        }
+        response["result"].take()
+    }
+}
+
+impl Drop for GoHelper {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The inputs' RNG, seeded from `GO_DIFFERENTIAL_SEED` or at random
+fn rng() -> XorShiftRng {
+    let seed = env::var(SEED_VAR)
+        .map(|seed| seed.parse().expect("the seed must be a u64"))
+        .unwrap_or_else(|_| rand::thread_rng().gen());
+    println!("{}={}", SEED_VAR, seed);
+    XorShiftRng::seed_from_u64(seed)
+}
+
+fn iterations() -> usize {
+    env::var(ITERATIONS_VAR)
+        .map(|n| {
+            n.parse()
+                .expect("the number of iterations must be a number")
+        })
+        .unwrap_or(DEFAULT_ITERATIONS)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn serialized_hex<T: CanonicalSerialize>(value: &T) -> String {
+    let mut bytes = vec![];
+    value.serialize(&mut bytes).unwrap();
+    to_hex(&bytes)
+}
+
+fn random_bytes<R: Rng>(rng: &mut R, max_len: usize) -> Vec<u8> {
+    let len = rng.gen_range(0, max_len + 1);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn signatures_and_hashes_match() {
+    let rng = &mut rng();
+    let mut helper = GoHelper::spawn();
+    for _ in 0..iterations() {
+        let key = PrivateKey::generate(rng);
+        let message = random_bytes(rng, 256);
+        let extra_data = random_bytes(rng, 64);
+
+        let hash: G1Projective = COMPOSITE_HASH_TO_G1_CIP22
+            .hash(SIG_DOMAIN, &message, &extra_data)
+            .unwrap();
+        let result = helper.call(json!({
+            "op": "hash_to_g1",
+            "message": to_hex(&message),
+            "extra_data": to_hex(&extra_data),
+        }));
+        assert_eq!(result["point"], serialized_hex(&hash.into_affine()));
+
+        let signature = key
+            .sign(&message, &extra_data, &*COMPOSITE_HASH_TO_G1_CIP22)
+            .unwrap();
+        let result = helper.call(json!({
+            "op": "sign",
+            "private_key": serialized_hex(&key),
+            "message": to_hex(&message),
+            "extra_data": to_hex(&extra_data),
+        }));
+        assert_eq!(result["public_key"], serialized_hex(&key.to_public()));
+        assert_eq!(result["signature"], serialized_hex(&signature));
+    }
+}
+
+#[test]
+fn aggregates_match() {
+    let rng = &mut rng();
+    let mut helper = GoHelper::spawn();
+    for _ in 0..iterations() {
+        let message = random_bytes(rng, 64);
+        let keys = (0..rng.gen_range(1, 9))
+            .map(|_| PrivateKey::generate(rng))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(PrivateKey::to_public).collect::<Vec<_>>();
+        let signatures = keys
+            .iter()
+            .map(|key| {
+                key.sign(&message, &[], &*COMPOSITE_HASH_TO_G1_CIP22)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let result = helper.call(json!({
+            "op": "aggregate",
+            "public_keys": public_keys.iter().map(serialized_hex).collect::<Vec<_>>(),
+            "signatures": signatures.iter().map(serialized_hex).collect::<Vec<_>>(),
+        }));
+        assert_eq!(
+            result["public_key"],
+            serialized_hex(&PublicKey::aggregate(&public_keys))
+        );
+        assert_eq!(
+            result["signature"],
+            serialized_hex(&Signature::aggregate(&signatures))
+        );
+    }
+}
+
+#[test]
+// these features change the encoding which validators sign, which celo-blockchain does not
+#[cfg(not(any(
+    feature = "epoch-aux-data",
+    feature = "chain-binding",
+    feature = "circuit-version",
+    feature = "entropy-256"
+)))]
+fn epoch_encodings_match() {
+    let rng = &mut rng();
+    let mut helper = GoHelper::spawn();
+    for _ in 0..iterations() {
+        let num_validators = rng.gen_range(1, 11);
+        let maximum_validators = num_validators + rng.gen_range(0, 4);
+        let public_keys = (0..num_validators)
+            .map(|_| PrivateKey::generate(rng).to_public())
+            .collect::<Vec<_>>();
+        let mut entropy = || {
+            (0..EpochBlock::ENTROPY_BYTES)
+                .map(|_| rng.gen())
+                .collect::<Vec<u8>>()
+        };
+        let (epoch_entropy, parent_entropy) = (entropy(), entropy());
+        let block = EpochBlock::new(
+            rng.gen(),
+            rng.gen(),
+            Some(epoch_entropy.clone()),
+            Some(parent_entropy.clone()),
+            rng.gen_range(0, num_validators as u32),
+            maximum_validators,
+            public_keys.clone(),
+        );
+        let (message, extra_data) = block.encode_inner_to_bytes_cip22().unwrap();
+
+        let result = helper.call(json!({
+            "op": "encode_epoch",
+            "index": block.index,
+            "round": block.round,
+            "epoch_entropy": to_hex(&epoch_entropy),
+            "parent_entropy": to_hex(&parent_entropy),
+            "maximum_non_signers": block.maximum_non_signers,
+            "maximum_validators": maximum_validators,
+            "public_keys": public_keys.iter().map(serialized_hex).collect::<Vec<_>>(),
+        }));
+        assert_eq!(result["message"], to_hex(&message), "{:?}", block);
+        assert_eq!(result["extra_data"], to_hex(&extra_data), "{:?}", block);
+    }
+}
@@ -0,0 +1,283 @@
+//! # Epoch Verifier
+//!
+//! `no_std` verifier for the epoch SNARK proofs produced by `epoch-snark`. It has no
+//! filesystem or threading dependencies, so that it can be used inside a Substrate runtime.
+#![no_std]
+#![deny(missing_docs)]
+
+extern crate alloc;
+
+use algebra::{
+    bw6_761::{Fr, FrParameters, BW6_761},
+    BigInteger, CanonicalDeserialize, FpParameters, PrimeField,
+};
+use alloc::vec::Vec;
+use core::fmt;
+use groth16::{prepare_verifying_key, verify_proof, PreparedVerifyingKey, Proof, VerifyingKey};
+
+/// Size of the public input: the Blake2s hashes of the first and the last epoch
+pub const PUBLIC_INPUT_BYTES: usize = 64;
+
+/// Size of each commitment which the circuit's optional features expose as an extra public
+/// input, see `EpochVerifier::verify_with_commitments`
+pub const COMMITMENT_BYTES: usize = 32;
+
+/// Size of the chain identifier which circuits built with `chain-binding` expose, see
+/// `Bindings`
+pub const CHAIN_ID_BYTES: usize = 32;
+
+/// The public inputs which circuits built with the `chain-binding` and `circuit-version`
+/// features expose between the epoch hashes and the commitments. Each value must be set if
+/// and only if the circuit was built with the corresponding feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bindings {
+    /// The chain identifier of the first epoch, all zeros if it has none
+    pub chain_id: Option<[u8; CHAIN_ID_BYTES]>,
+    /// The `CIRCUIT_VERSION` of the circuit
+    pub circuit_version: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Error raised while verifying an epoch proof
+pub enum VerifierError {
+    /// The verifying key could not be deserialized
+    InvalidVerifyingKey,
+    /// The proof could not be deserialized
+    InvalidProof,
+    /// The public input has the wrong length
+    InvalidPublicInputs,
+    /// The proof is not valid for the provided verifying key and public input
+    VerificationFailed,
+    /// The verifying key is of a circuit built with `compressed-public-inputs`, whose public
+    /// input is a Poseidon hash which only `epoch_snark` computes
+    CompressedPublicInputs,
+}
+
+impl fmt::Display for VerifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifierError::InvalidVerifyingKey => write!(f, "Invalid verifying key"),
+            VerifierError::InvalidProof => write!(f, "Invalid proof"),
+            VerifierError::InvalidPublicInputs => {
+                write!(f, "Public inputs must be {} bytes long", PUBLIC_INPUT_BYTES)
+            }
+            VerifierError::VerificationFailed => write!(f, "Verification failed"),
+            VerifierError::CompressedPublicInputs => {
+                write!(
+                    f,
+                    "Compressed public inputs must be verified with `verify_packed`"
+                )
+            }
+        }
+    }
+}
+
+/// Verifies an epoch proof. `public_inputs` is the Blake2s hash of the first epoch followed
+/// by the Blake2s hash of the last epoch, as computed by `epoch_snark`'s
+/// `hash_first_last_epoch_block`. The verifying key and the proof are expected in their
+/// `CanonicalSerialize` encoding.
+pub fn verify_epoch_proof(
+    vk_bytes: &[u8],
+    public_inputs: &[u8],
+    proof_bytes: &[u8],
+) -> Result<(), VerifierError> {
+    if public_inputs.len() != PUBLIC_INPUT_BYTES {
+        return Err(VerifierError::InvalidPublicInputs);
+    }
+    EpochVerifier::new(vk_bytes)?.verify(public_inputs, proof_bytes)
+}
+
+/// Verifier of the proofs of a single verifying key, which is only deserialized and
+/// prepared once. Services which check every proof of a chain should keep one around rather
+/// than calling `verify_epoch_proof` for each proof.
+pub struct EpochVerifier {
+    pvk: PreparedVerifyingKey<BW6_761>,
+    num_public_inputs: usize,
+}
+
+impl EpochVerifier {
+    /// Deserializes and prepares the verifying key produced by `epoch_snark::trusted_setup`
+    pub fn new(vk_bytes: &[u8]) -> Result<Self, VerifierError> {
+        let vk = VerifyingKey::<BW6_761>::deserialize(&mut &vk_bytes[..])
+            .map_err(|_| VerifierError::InvalidVerifyingKey)?;
+        let num_public_inputs = vk
+            .gamma_abc_g1
+            .len()
+            .checked_sub(1)
+            .ok_or(VerifierError::InvalidVerifyingKey)?;
+        Ok(Self {
+            pvk: prepare_verifying_key(&vk),
+            num_public_inputs,
+        })
+    }
+
+    /// The number of field elements which the verifying key expects as public inputs
+    pub fn num_public_inputs(&self) -> usize {
+        self.num_public_inputs
+    }
+
+    /// Verifies a proof against the public input of `verify_epoch_proof`
+    pub fn verify(&self, public_inputs: &[u8], proof_bytes: &[u8]) -> Result<(), VerifierError> {
+        self.verify_with_commitments(public_inputs, &[], proof_bytes)
+    }
+
+    /// Verifies a proof of a circuit built with features which expose commitments as extra
+    /// public inputs (`bitmap-commitment`, `epoch-aux-data` and `randomness-beacon`). The
+    /// commitments must be provided in that order, skipping those of disabled features.
+    pub fn verify_with_commitments(
+        &self,
+        public_inputs: &[u8],
+        commitments: &[[u8; COMMITMENT_BYTES]],
+        proof_bytes: &[u8],
+    ) -> Result<(), VerifierError> {
+        self.verify_with_bindings(
+            public_inputs,
+            &Bindings::default(),
+            commitments,
+            proof_bytes,
+        )
+    }
+
+    /// Verifies a proof of a circuit built with any of the features which expose extra public
+    /// inputs, except for `compressed-public-inputs`, which is rejected, and the extra
+    /// inputs of `ValidatorSetUpdate::with_extra_inputs`, whose encoding is defined by their
+    /// producer.
+    pub fn verify_with_bindings(
+        &self,
+        public_inputs: &[u8],
+        bindings: &Bindings,
+        commitments: &[[u8; COMMITMENT_BYTES]],
+        proof_bytes: &[u8],
+    ) -> Result<(), VerifierError> {
+        // the uncompressed layouts expose at least the two elements of the epoch hashes
+        if self.num_public_inputs == 1 {
+            return Err(VerifierError::CompressedPublicInputs);
+        }
+        let public_inputs = pack_all_public_inputs(public_inputs, bindings, commitments)?;
+        self.verify_packed(&public_inputs, &deserialize_proof(proof_bytes)?)
+    }
+
+    /// Verifies a proof against public inputs which were already packed to field elements
+    pub fn verify_packed(
+        &self,
+        public_inputs: &[Fr],
+        proof: &Proof<BW6_761>,
+    ) -> Result<(), VerifierError> {
+        if public_inputs.len() != self.num_public_inputs {
+            return Err(VerifierError::InvalidPublicInputs);
+        }
+        match verify_proof(&self.pvk, proof, public_inputs) {
+            Ok(true) => Ok(()),
+            _ => Err(VerifierError::VerificationFailed),
+        }
+    }
+}
+
+/// Packs the public input of `verify_epoch_proof` into field elements, in the same way as the
+/// circuit
+pub fn pack_public_inputs(bytes: &[u8]) -> Result<Vec<Fr>, VerifierError> {
+    if bytes.len() != PUBLIC_INPUT_BYTES {
+        return Err(VerifierError::InvalidPublicInputs);
+    }
+    Ok(pack(bytes))
+}
+
+/// Packs the public input of `verify_epoch_proof`, followed by the bindings and the
+/// commitments, in the order in which the circuit allocates them
+pub fn pack_all_public_inputs(
+    bytes: &[u8],
+    bindings: &Bindings,
+    commitments: &[[u8; COMMITMENT_BYTES]],
+) -> Result<Vec<Fr>, VerifierError> {
+    let mut public_inputs = pack_public_inputs(bytes)?;
+    if let Some(chain_id) = &bindings.chain_id {
+        public_inputs.extend(pack(chain_id));
+    }
+    if let Some(version) = bindings.circuit_version {
+        public_inputs.push(Fr::from(version));
+    }
+    public_inputs.extend(commitments.iter().map(pack_commitment));
+    Ok(public_inputs)
+}
+
+/// Packs a commitment exposed by one of the circuit's optional features into the single field
+/// element which the circuit allocates for it
+pub fn pack_commitment(commitment: &[u8; COMMITMENT_BYTES]) -> Fr {
+    pack(commitment)
+        .pop()
+        .expect("a commitment fits in a single field element")
+}
+
+fn deserialize_proof(proof_bytes: &[u8]) -> Result<Proof<BW6_761>, VerifierError> {
+    Proof::<BW6_761>::deserialize(&mut &proof_bytes[..]).map_err(|_| VerifierError::InvalidProof)
+}
+
+/// Packs the LE bits of the bytes into field elements of `CAPACITY` bits each
+fn pack(bytes: &[u8]) -> Vec<Fr> {
+    let bits = bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .collect::<Vec<_>>();
+    bits.chunks(FrParameters::CAPACITY as usize)
+        .map(|chunk| {
+            Fr::from_repr(<Fr as PrimeField>::BigInt::from_bits(chunk))
+                .expect("chunks of CAPACITY bits are smaller than the modulus")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_public_inputs() {
+        let packed = pack_public_inputs(&[0xff; PUBLIC_INPUT_BYTES]).unwrap();
+        let capacity = FrParameters::CAPACITY as usize;
+        assert_eq!(
+            packed.len(),
+            (PUBLIC_INPUT_BYTES * 8 + capacity - 1) / capacity
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_inputs() {
+        assert_eq!(
+            verify_epoch_proof(&[], &[0; 63], &[]),
+            Err(VerifierError::InvalidPublicInputs)
+        );
+        assert_eq!(
+            verify_epoch_proof(&[1, 2, 3], &[0; PUBLIC_INPUT_BYTES], &[]),
+            Err(VerifierError::InvalidVerifyingKey)
+        );
+    }
+
+    // the values are checked against `epoch_snark`'s packing in its `verifier` tests
+    #[test]
+    fn packs_commitments_in_one_element() {
+        assert_eq!(pack(&[0xff; COMMITMENT_BYTES]).len(), 1);
+        assert_eq!(pack(&[0xff; CHAIN_ID_BYTES]).len(), 1);
+    }
+
+    #[test]
+    fn packs_bindings_in_order() {
+        let bytes = [0; PUBLIC_INPUT_BYTES];
+        let hash = pack_public_inputs(&bytes).unwrap();
+        let bindings = Bindings {
+            chain_id: Some([0xff; CHAIN_ID_BYTES]),
+            circuit_version: Some(2),
+        };
+        let commitment = [1; COMMITMENT_BYTES];
+        let packed = pack_all_public_inputs(&bytes, &bindings, &[commitment]).unwrap();
+
+        assert_eq!(packed.len(), hash.len() + 3);
+        assert_eq!(packed[..hash.len()], hash[..]);
+        assert_eq!(packed[hash.len()], pack(&[0xff; CHAIN_ID_BYTES])[0]);
+        assert_eq!(packed[hash.len() + 1], Fr::from(2u32));
+        assert_eq!(packed[hash.len() + 2], pack_commitment(&commitment));
+        assert_eq!(
+            pack_all_public_inputs(&bytes, &Bindings::default(), &[]).unwrap(),
+            hash
+        );
+    }
+}